@@ -19,7 +19,7 @@ pub type ChainSpec = sc_service::ChainSpec<GenesisConfig>;
 pub enum Alternative {
 	/// Whatever the current runtime is, with just Alice as an auth.
 	Development,
-	/// Whatever the current runtime is, with simple Alice/Bob auths.
+	/// Whatever the current runtime is, with simple Alice/Bob/Charlie auths.
 	LocalTestnet,
 }
 
@@ -77,6 +77,7 @@ impl Alternative {
 				|| testnet_genesis(vec![
 					get_authority_keys_from_seed("Alice"),
 					get_authority_keys_from_seed("Bob"),
+					get_authority_keys_from_seed("Charlie"),
 				],
 				get_account_id_from_seed::<sr25519::Public>("Alice"),
 				vec![
@@ -11,7 +11,10 @@ use std::cell::RefCell;
 use tokio::runtime::Runtime;
 pub use sc_cli::{VersionInfo, IntoExit, error};
 use sc_cli::{informant, parse_and_prepare, ParseAndPrepare, NoCustom};
+use crate::inspect;
+use crate::recipe_metrics::RecipeMetricsParams;
 use sc_service::{AbstractService, Roles as ServiceRoles, Configuration};
+use sc_client::ExecutionStrategy;
 use crate::chain_spec;
 use log::info;
 
@@ -22,9 +25,12 @@ pub fn run<I, T, E>(args: I, exit: E, version: VersionInfo) -> error::Result<()>
 	E: IntoExit,
 {
 	type Config<T> = Configuration<(), T>;
-	match parse_and_prepare::<NoCustom, NoCustom, _>(&version, "substrate-node", args) {
+	match parse_and_prepare::<inspect::Subcommand, RecipeMetricsParams, _>(&version, "substrate-node", args) {
 		ParseAndPrepare::Run(cmd) => cmd.run(load_spec, exit,
-		|exit, _cli_args, _custom_args, config: Config<_>| {
+		|exit, _cli_args, recipe_metrics: RecipeMetricsParams, mut config: Config<_>| {
+			if config.tracing_targets.is_some() {
+				prefer_native_execution(&mut config);
+			}
 			info!("{}", version.name);
 			info!("  version {}", config.full_version());
 			info!("  by {}, 2017, 2018", version.author);
@@ -40,7 +46,7 @@ pub fn run<I, T, E>(args: I, exit: E, version: VersionInfo) -> error::Result<()>
 				),
 				_ => run_until_exit(
 					runtime,
-					service::new_full(config).map_err(|e| format!("{:?}", e))?,
+					service::new_full(config, recipe_metrics).map_err(|e| format!("{:?}", e))?,
 					exit
 				),
 			}.map_err(|e| format!("{:?}", e))
@@ -55,12 +61,21 @@ pub fn run<I, T, E>(args: I, exit: E, version: VersionInfo) -> error::Result<()>
 		ParseAndPrepare::PurgeChain(cmd) => cmd.run(load_spec),
 		ParseAndPrepare::RevertChain(cmd) => cmd.run_with_builder(|config: Config<_>|
 			Ok(new_full_start!(config).0), load_spec),
-		ParseAndPrepare::CustomCommand(_) => Ok(())
+		ParseAndPrepare::CustomCommand(cmd) => inspect::run(cmd, &version, load_spec),
 	}?;
 
 	Ok(())
 }
 
+/// Runtime spans are only recorded when the runtime is executed natively, so a node that was asked
+/// for `--tracing-targets` imports and builds blocks with the native runtime where it can.
+fn prefer_native_execution<G>(config: &mut Configuration<(), G>) {
+	info!("Tracing targets given, preferring native execution to record runtime spans");
+	config.execution_strategies.importing = ExecutionStrategy::NativeElseWasm;
+	config.execution_strategies.block_construction = ExecutionStrategy::NativeElseWasm;
+	config.execution_strategies.syncing = ExecutionStrategy::NativeElseWasm;
+}
+
 fn load_spec(id: &str) -> Result<Option<chain_spec::ChainSpec>, String> {
 	Ok(match chain_spec::Alternative::from(id) {
 		Some(spec) => Some(spec.load()?),
@@ -0,0 +1,222 @@
+//! Gossip of recipe event digests between nodes.
+//!
+//! Every full node summarizes the events of each finalized block into a digest, the number of
+//! events per pallet, and gossips it to its peers on a notifications protocol of its own. The
+//! protocol is identified by `RECIPE_DIGEST_ENGINE_ID` and runs next to block sync, GRANDPA and the
+//! other protocols of the node, over the same connections.
+//!
+//! Incoming digests are checked by `DigestValidator` before they are kept or passed on, and logged
+//! under the `digest-gossip` target:
+//!
+//! ```text
+//! Digest from Bob of block #12: adding_machine: 1, simple_map: 2
+//! ```
+
+use std::collections::BTreeMap;
+use std::sync::{atomic::{AtomicU32, Ordering}, Arc};
+use futures::{future, Future, FutureExt, StreamExt};
+use log::{debug, info};
+use codec::{Decode, Encode};
+use sc_client_api::BlockchainEvents;
+use sc_network::PeerId;
+use sc_network_gossip::{GossipEngine, Network, ValidationResult, Validator, ValidatorContext};
+use sp_runtime::{
+	traits::{BlakeTwo256, Hash as HashT, Header as HeaderT},
+	ConsensusEngineId,
+};
+use runtime::{opaque::Block, BlockNumber, Event, Hash};
+use frame_system::EventRecord;
+use crate::recipe_metrics::{pallet_name, ReadEvents};
+
+const TARGET: &str = "digest-gossip";
+
+/// Identifies the protocol among the node's notifications protocols
+pub const RECIPE_DIGEST_ENGINE_ID: ConsensusEngineId = *b"rcpd";
+
+/// How many blocks behind the latest finalized block a digest is still gossiped
+const DIGEST_LIFETIME: BlockNumber = 64;
+
+/// What one node saw in one finalized block
+#[derive(Encode, Decode, Debug, Clone, PartialEq)]
+pub struct EventDigest {
+	/// The name of the node that sent the digest. It also keeps the identical digests of different
+	/// nodes apart, which the gossip layer would otherwise treat as one message.
+	pub sender: Vec<u8>,
+	/// The number of the block
+	pub number: BlockNumber,
+	/// The hash of the block
+	pub hash: Hash,
+	/// The number of events each pallet deposited in the block
+	pub pallet_events: Vec<(Vec<u8>, u32)>,
+}
+
+impl EventDigest {
+	/// Summarize the events of a block
+	pub fn new(
+		sender: &str,
+		number: BlockNumber,
+		hash: Hash,
+		events: &[EventRecord<Event, Hash>],
+	) -> Self {
+		let mut counts = BTreeMap::<String, u32>::new();
+		for record in events {
+			*counts.entry(pallet_name(&record.event)).or_default() += 1;
+		}
+		Self {
+			sender: sender.as_bytes().to_vec(),
+			number,
+			hash,
+			pallet_events: counts.into_iter()
+				.map(|(pallet, count)| (pallet.into_bytes(), count))
+				.collect(),
+		}
+	}
+}
+
+/// All digests are gossiped under one topic
+fn topic() -> Hash {
+	BlakeTwo256::hash(b"recipe-event-digests")
+}
+
+/// Decides which digests are kept and passed on to other peers
+#[derive(Default)]
+pub struct DigestValidator {
+	/// The number of the latest block this node has finalized
+	finalized: AtomicU32,
+}
+
+impl DigestValidator {
+	/// Finality notifications arrive in order, so the last one is the latest
+	fn note_finalized(&self, number: BlockNumber) {
+		self.finalized.store(number, Ordering::Relaxed);
+	}
+
+	/// Whether a digest is too old to be worth gossiping
+	fn is_expired(&self, digest: &EventDigest) -> bool {
+		digest.number.saturating_add(DIGEST_LIFETIME) < self.finalized.load(Ordering::Relaxed)
+	}
+}
+
+impl Validator<Block> for DigestValidator {
+	fn validate(
+		&self,
+		_context: &mut dyn ValidatorContext<Block>,
+		sender: &PeerId,
+		data: &[u8],
+	) -> ValidationResult<Hash> {
+		match EventDigest::decode(&mut &data[..]) {
+			Ok(ref digest) if !self.is_expired(digest) => ValidationResult::ProcessAndKeep(topic()),
+			Ok(_) => ValidationResult::ProcessAndDiscard(topic()),
+			Err(_) => {
+				debug!(target: TARGET, "Undecodable digest from {}", sender);
+				ValidationResult::Discard
+			}
+		}
+	}
+
+	fn message_expired<'a>(&'a self) -> Box<dyn FnMut(Hash, &[u8]) -> bool + 'a> {
+		Box::new(move |_topic, data| match EventDigest::decode(&mut &data[..]) {
+			Ok(digest) => self.is_expired(&digest),
+			Err(_) => true,
+		})
+	}
+}
+
+/// A future that gossips a digest of every finalized block, and logs the digests of peers, until
+/// the client shuts down
+pub fn run<C, N>(
+	client: Arc<C>,
+	network: N,
+	executor: &impl futures::task::Spawn,
+	node_name: String,
+) -> impl Future<Output = ()>
+where
+	C: BlockchainEvents<Block> + ReadEvents + Send + Sync + 'static,
+	N: Network<Block> + Clone + Send + 'static,
+{
+	let validator = Arc::new(DigestValidator::default());
+	// Creating the engine registers the notifications protocol with the network
+	let gossip = GossipEngine::new(network, executor, RECIPE_DIGEST_ENGINE_ID, validator.clone());
+
+	let outgoing_gossip = gossip.clone();
+	let outgoing = client.finality_notification_stream().for_each(move |notification| {
+		let number = *notification.header.number();
+		validator.note_finalized(number);
+		if let Some(events) = client.read_events(notification.hash) {
+			let digest = EventDigest::new(&node_name, number, notification.hash, &events);
+			outgoing_gossip.gossip_message(topic(), digest.encode(), false);
+		}
+		future::ready(())
+	});
+
+	let incoming = gossip.messages_for(topic()).for_each(|notification| {
+		// Our own digests come back without a sender
+		if notification.sender.is_some() {
+			match EventDigest::decode(&mut &notification.message[..]) {
+				Ok(digest) => info!(target: TARGET, "{}", describe(&digest)),
+				Err(e) => debug!(target: TARGET, "Undecodable digest: {:?}", e),
+			}
+		}
+		future::ready(())
+	});
+
+	future::join(outgoing, incoming).map(|_| ())
+}
+
+/// A line for the log
+fn describe(digest: &EventDigest) -> String {
+	let counts = digest.pallet_events.iter()
+		.map(|(pallet, count)| format!("{}: {}", String::from_utf8_lossy(pallet), count))
+		.collect::<Vec<_>>()
+		.join(", ");
+	format!(
+		"Digest from {} of block #{}: {}",
+		String::from_utf8_lossy(&digest.sender),
+		digest.number,
+		if counts.is_empty() { "no events".into() } else { counts },
+	)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use frame_system::Phase;
+
+	fn record(event: Event) -> EventRecord<Event, Hash> {
+		EventRecord { phase: Phase::ApplyExtrinsic(0), event, topics: vec![] }
+	}
+
+	#[test]
+	fn digest_counts_events_per_pallet() {
+		let digest = EventDigest::new("Alice", 12, Hash::default(), &[
+			record(Event::simple_map(simple_map::RawEvent::EntrySet(Default::default(), 1))),
+			record(Event::adding_machine(adding_machine::Event::Added(1, 2, 3))),
+			record(Event::simple_map(simple_map::RawEvent::EntryTook(Default::default(), 1))),
+		]);
+
+		assert_eq!(digest.pallet_events, vec![
+			(b"adding_machine".to_vec(), 1),
+			(b"simple_map".to_vec(), 2),
+		]);
+		assert_eq!(
+			describe(&digest),
+			"Digest from Alice of block #12: adding_machine: 1, simple_map: 2",
+		);
+	}
+
+	#[test]
+	fn old_digests_expire() {
+		let validator = DigestValidator::default();
+		let digest = EventDigest::new("Alice", 10, Hash::default(), &[]);
+		assert_eq!(describe(&digest), "Digest from Alice of block #10: no events");
+
+		validator.note_finalized(10 + DIGEST_LIFETIME);
+		assert!(!validator.is_expired(&digest));
+		validator.note_finalized(11 + DIGEST_LIFETIME);
+		assert!(validator.is_expired(&digest));
+
+		let mut expired = validator.message_expired();
+		assert!(expired(topic(), &digest.encode()));
+		assert!(expired(topic(), b"not a digest"));
+	}
+}
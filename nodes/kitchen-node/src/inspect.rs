@@ -0,0 +1,104 @@
+//! The `recipes` subcommand, a home for tools that do something other than run a single node,
+//! like working on the node's database without starting the node.
+//!
+//! ```text
+//! kitchen-node recipes inspect-storage --dev --account 5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY
+//! ```
+
+use structopt::StructOpt;
+use sc_cli::{error, GetSharedParams, SharedParams, VersionInfo};
+use sc_service::Configuration;
+use sp_core::{crypto::Ss58Codec, storage::StorageKey, H256};
+use sp_runtime::generic::BlockId;
+use codec::Decode;
+use runtime::{opaque::Block, AccountId, Runtime, RuntimeApi};
+use support::StorageMap;
+use crate::chain_spec;
+use crate::service::Executor;
+use crate::local_testnet::{self, LocalTestnetCmd};
+
+/// Custom subcommands of the kitchen node
+#[derive(Debug, StructOpt, Clone)]
+pub enum Subcommand {
+	/// Recipe tools that don't start the node
+	#[structopt(name = "recipes")]
+	Recipes(RecipesCmd),
+}
+
+/// The individual recipe tools
+#[derive(Debug, StructOpt, Clone)]
+pub enum RecipesCmd {
+	/// Print the simple-map entries of some accounts
+	#[structopt(name = "inspect-storage")]
+	InspectStorage(InspectStorageCmd),
+
+	/// Start Alice, Bob and Charlie as validators of a local testnet
+	#[structopt(name = "local-testnet")]
+	LocalTestnet(LocalTestnetCmd),
+}
+
+#[derive(Debug, StructOpt, Clone)]
+pub struct InspectStorageCmd {
+	/// SS58 address of an account whose simple-map entry is printed. May be repeated.
+	#[structopt(long = "account", value_name = "SS58")]
+	pub accounts: Vec<String>,
+
+	/// Hash of the block whose state is inspected. Defaults to the best block.
+	#[structopt(long = "at", value_name = "HASH")]
+	pub at: Option<H256>,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub shared_params: SharedParams,
+}
+
+impl GetSharedParams for Subcommand {
+	fn shared_params(&self) -> Option<&SharedParams> {
+		match self {
+			Subcommand::Recipes(RecipesCmd::InspectStorage(cmd)) => Some(&cmd.shared_params),
+			// Each node of the testnet gets its own flags
+			Subcommand::Recipes(RecipesCmd::LocalTestnet(_)) => None,
+		}
+	}
+}
+
+/// Run a custom subcommand
+pub fn run<F>(subcommand: Subcommand, version: &VersionInfo, spec_factory: F) -> error::Result<()> where
+	F: FnOnce(&str) -> Result<Option<chain_spec::ChainSpec>, String>,
+{
+	match subcommand {
+		Subcommand::Recipes(RecipesCmd::InspectStorage(cmd)) => inspect_storage(cmd, version, spec_factory),
+		Subcommand::Recipes(RecipesCmd::LocalTestnet(cmd)) => local_testnet::run(cmd),
+	}
+}
+
+fn inspect_storage<F>(cmd: InspectStorageCmd, version: &VersionInfo, spec_factory: F) -> error::Result<()> where
+	F: FnOnce(&str) -> Result<Option<chain_spec::ChainSpec>, String>,
+{
+	let config: Configuration<(), _> =
+		sc_cli::create_config_with_db_path(spec_factory, &cmd.shared_params, version)?;
+	// Opening the client only opens the database. No networking or block production is started.
+	let client = sc_service::new_full_client::<Block, RuntimeApi, Executor, _, _>(&config)?;
+
+	let at = cmd.at.unwrap_or_else(|| client.info().chain.best_hash);
+	println!("Inspecting state at block {}", at);
+
+	for address in &cmd.accounts {
+		let account = AccountId::from_ss58check(address)
+			.map_err(|e| format!("Invalid account {}: {:?}", address, e))?;
+		let key = StorageKey(simple_map::SimpleMap::<Runtime>::hashed_key_for(&account));
+		let raw = client.storage(&BlockId::Hash(at), &key)
+			.map_err(|e| format!("Failed to read storage: {:?}", e))?;
+
+		match raw {
+			Some(data) => {
+				let entry = u32::decode(&mut &data.0[..])
+					.map_err(|e| format!("Failed to decode entry of {}: {:?}", address, e))?;
+				println!("SimpleMap[{}] = {}", address, entry);
+			},
+			None => println!("SimpleMap[{}] is empty", address),
+		}
+	}
+
+	Ok(())
+}
@@ -0,0 +1,172 @@
+//! The `recipes local-testnet` subcommand, which starts a network of three validators on this
+//! machine.
+//!
+//! ```text
+//! kitchen-node recipes local-testnet --purge -- -lafg=debug
+//! ```
+//!
+//! Alice, Bob and Charlie each run in their own process of this executable, on the `local` chain
+//! spec. Alice has a fixed network key, so the others can be given her address as their bootnode.
+//! Ctrl-C stops all three, and so does any one of them exiting.
+
+use std::{
+	path::{Path, PathBuf},
+	process::{Child, Command},
+	sync::{atomic::{AtomicBool, Ordering}, Arc},
+	thread,
+	time::Duration,
+};
+use structopt::StructOpt;
+use sc_cli::error;
+
+/// The validators of the `local` chain spec, in the order they are started. The first one is the
+/// bootnode.
+const VALIDATORS: [&str; 3] = ["alice", "bob", "charlie"];
+
+/// The bootnode's network key
+const BOOTNODE_KEY: &str = "0000000000000000000000000000000000000000000000000000000000000001";
+
+/// The peer id that belongs to `BOOTNODE_KEY`
+const BOOTNODE_PEER_ID: &str = "QmRpheLN4JWdAnY7HGJfWUNbKGukJ8jRadcG2WPn3mgBdu";
+
+/// How often the launcher checks whether a node has exited
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How long the nodes get to shut down by themselves after Ctrl-C, before they are killed
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(5);
+
+#[derive(Debug, StructOpt, Clone)]
+pub struct LocalTestnetCmd {
+	/// Directory in which each node gets a subdirectory for its database and keystore. Defaults to
+	/// `kitchen-local-testnet` in the system's temporary directory.
+	#[structopt(long = "base-path", value_name = "PATH", parse(from_os_str))]
+	pub base_path: Option<PathBuf>,
+
+	/// Delete the nodes' databases first, to start again from genesis
+	#[structopt(long = "purge")]
+	pub purge: bool,
+
+	/// The p2p port of the first node. The others use the ports after it.
+	#[structopt(long = "port", value_name = "PORT", default_value = "30333")]
+	pub port: u16,
+
+	/// The WebSockets RPC port of the first node. The others use the ports after it.
+	#[structopt(long = "ws-port", value_name = "PORT", default_value = "9944")]
+	pub ws_port: u16,
+
+	/// The HTTP RPC port of the first node. The others use the ports after it.
+	#[structopt(long = "rpc-port", value_name = "PORT", default_value = "9933")]
+	pub rpc_port: u16,
+
+	/// The recipe metrics port of the first node. The others use the ports after it.
+	#[structopt(long = "recipe-metrics-port", value_name = "PORT", default_value = "9616")]
+	pub recipe_metrics_port: u16,
+
+	/// Flags passed on to every node, after `--`
+	#[structopt(last = true)]
+	pub node_flags: Vec<String>,
+}
+
+impl LocalTestnetCmd {
+	/// The command line of the `index`th validator
+	fn node_args(&self, index: usize, base_path: &Path) -> Vec<String> {
+		let name = VALIDATORS[index];
+		let offset = index as u16;
+		let mut args = vec![
+			"--chain".into(), "local".into(),
+			format!("--{}", name),
+			"--base-path".into(), base_path.join(name).display().to_string(),
+			"--port".into(), (self.port + offset).to_string(),
+			"--ws-port".into(), (self.ws_port + offset).to_string(),
+			"--rpc-port".into(), (self.rpc_port + offset).to_string(),
+			"--recipe-metrics-port".into(), (self.recipe_metrics_port + offset).to_string(),
+		];
+		if index == 0 {
+			args.extend(vec!["--node-key".into(), BOOTNODE_KEY.into()]);
+		} else {
+			let bootnode = format!("/ip4/127.0.0.1/tcp/{}/p2p/{}", self.port, BOOTNODE_PEER_ID);
+			args.extend(vec!["--bootnodes".into(), bootnode]);
+		}
+		args.extend(self.node_flags.iter().cloned());
+		args
+	}
+}
+
+/// Start the validators, and wait until Ctrl-C or until one of them exits
+pub fn run(cmd: LocalTestnetCmd) -> error::Result<()> {
+	let executable = std::env::current_exe()
+		.map_err(|e| format!("Failed to find the node executable: {}", e))?;
+	let base_path = cmd.base_path.clone()
+		.unwrap_or_else(|| std::env::temp_dir().join("kitchen-local-testnet"));
+
+	if cmd.purge && base_path.exists() {
+		std::fs::remove_dir_all(&base_path)
+			.map_err(|e| format!("Failed to purge {}: {}", base_path.display(), e))?;
+		println!("Purged {}", base_path.display());
+	}
+
+	let interrupted = Arc::new(AtomicBool::new(false));
+	let flag = interrupted.clone();
+	ctrlc::set_handler(move || flag.store(true, Ordering::SeqCst))
+		.map_err(|e| format!("Failed to set the Ctrl-C handler: {}", e))?;
+
+	let mut nodes: Vec<(&str, Child)> = Vec::new();
+	for (index, name) in VALIDATORS.iter().enumerate() {
+		let args = cmd.node_args(index, &base_path);
+		println!("Starting {}: {} {}", name, executable.display(), args.join(" "));
+		match Command::new(&executable).args(&args).spawn() {
+			Ok(child) => nodes.push((*name, child)),
+			Err(e) => {
+				stop(&mut nodes, Duration::from_secs(0));
+				return Err(format!("Failed to start {}: {}", name, e).into());
+			}
+		}
+	}
+	println!(
+		"Local testnet running. Connect to Alice on ws://127.0.0.1:{}. Press Ctrl-C to stop.",
+		cmd.ws_port,
+	);
+
+	while !interrupted.load(Ordering::SeqCst) {
+		let exited = nodes.iter_mut()
+			.find_map(|(name, child)| match child.try_wait() {
+				Ok(Some(status)) => Some((*name, status)),
+				_ => None,
+			});
+		if let Some((name, status)) = exited {
+			println!("{} exited with {}, stopping the others", name, status);
+			break;
+		}
+		thread::sleep(POLL_INTERVAL);
+	}
+
+	// Ctrl-C in a terminal reaches the nodes as well, and they are shutting down already
+	let grace = if interrupted.load(Ordering::SeqCst) { SHUTDOWN_GRACE } else { Duration::from_secs(0) };
+	stop(&mut nodes, grace);
+	Ok(())
+}
+
+/// Give the nodes up to `grace` to exit, then kill the ones that are still running
+fn stop(nodes: &mut Vec<(&str, Child)>, grace: Duration) {
+	let mut waited = Duration::from_secs(0);
+	while waited < grace && nodes.iter_mut().any(|(_, child)| is_running(child)) {
+		thread::sleep(POLL_INTERVAL);
+		waited += POLL_INTERVAL;
+	}
+
+	for (name, child) in nodes.iter_mut() {
+		if is_running(child) {
+			if let Err(e) = child.kill() {
+				eprintln!("Failed to stop {}: {}", name, e);
+			}
+		}
+		let _ = child.wait();
+	}
+}
+
+fn is_running(child: &mut Child) -> bool {
+	match child.try_wait() {
+		Ok(None) => true,
+		_ => false,
+	}
+}
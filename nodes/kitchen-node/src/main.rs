@@ -7,6 +7,11 @@ mod chain_spec;
 #[macro_use]
 mod service;
 mod cli;
+mod digest_gossip;
+mod inspect;
+mod local_testnet;
+mod pool_logger;
+mod recipe_metrics;
 
 pub use sc_cli::{VersionInfo, IntoExit, error};
 
@@ -0,0 +1,178 @@
+//! A service that logs per-pallet statistics about transactions.
+//!
+//! Two event sources are followed:
+//! * the transaction pool's import notifications, which fire when a transaction enters the pool,
+//!   whether it was submitted locally or gossiped by a peer
+//! * the client's finality notifications, whose block bodies contain the transactions that made it
+//!   on chain
+//!
+//! Each transaction is decoded into the runtime's `Call` to find out which pallet it targets. An
+//! import notification only carries the transaction's hash, and the pool can only look up ready
+//! transactions, so a transaction that waits for an earlier nonce in the future queue is counted as
+//! pending until it shows up in a finalized block and is attributed to its pallet there.
+//!
+//! The statistics are logged under the `pool-logger` target. Run the node with
+//! `-lpool-logger=debug` to see the summary of every finalized block, or with `-lpool-logger=trace`
+//! to see every transaction too.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+use futures::{future, Future, StreamExt};
+use log::{debug, trace};
+use parking_lot::Mutex;
+use codec::{Decode, Encode};
+use sc_client_api::BlockchainEvents;
+use sp_runtime::{generic::BlockId, traits::{BlakeTwo256, Hash as HashT}};
+use sp_transaction_pool::{InPoolTransaction, TransactionPool};
+use runtime::{opaque::Block, Call, Hash, UncheckedExtrinsic};
+
+const TARGET: &str = "pool-logger";
+
+/// Counts of transactions seen per pallet
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct PalletStats {
+	/// Transactions that entered the pool
+	pub imported: u64,
+	/// Transactions included in a finalized block
+	pub finalized: u64,
+}
+
+/// What the logger has seen so far
+#[derive(Default, Debug)]
+pub struct PoolStats {
+	/// The counts of each pallet, keyed by pallet name
+	pub pallets: BTreeMap<String, PalletStats>,
+	/// Imported transactions that were not ready yet, so their pallet is not known
+	pub pending: BTreeSet<Hash>,
+}
+
+/// The shared statistics of the logger
+pub type Stats = Arc<Mutex<PoolStats>>;
+
+/// The name of the pallet a call is dispatched to.
+///
+/// The outer `Call` enum generated by `construct_runtime!` has one variant per pallet, named after
+/// the pallet (e.g. `SimpleMap(set_single_entry(19))`), so the name is the Debug output up to the
+/// first parenthesis.
+fn pallet_name(call: &Call) -> String {
+	let debug = format!("{:?}", call);
+	debug.split('(').next().unwrap_or_default().to_string()
+}
+
+/// Decode an opaque extrinsic into the runtime call it carries
+fn decode_call(opaque: &<Block as sp_runtime::traits::Block>::Extrinsic) -> Option<Call> {
+	// The opaque extrinsic wraps the encoded runtime extrinsic
+	let encoded = opaque.encode();
+	let bytes: Vec<u8> = Decode::decode(&mut &encoded[..]).ok()?;
+	UncheckedExtrinsic::decode(&mut &bytes[..]).ok().map(|xt| xt.function)
+}
+
+/// Record a transaction under its pallet, applying `update` to the pallet's counts
+fn record(stats: &Stats, call: &Call, update: impl FnOnce(&mut PalletStats)) {
+	let pallet = pallet_name(call);
+	let mut stats = stats.lock();
+	update(stats.pallets.entry(pallet).or_default());
+}
+
+/// Count the transactions of a finalized block, and the imports of those that were pending
+fn record_finalized(stats: &Stats, body: &[<Block as sp_runtime::traits::Block>::Extrinsic]) {
+	for xt in body {
+		if let Some(call) = decode_call(xt) {
+			let was_pending = stats.lock().pending.remove(&BlakeTwo256::hash_of(xt));
+			record(stats, &call, |s| {
+				s.finalized += 1;
+				if was_pending {
+					s.imported += 1;
+				}
+			});
+		}
+	}
+}
+
+/// A future that counts every transaction imported into the pool
+pub fn log_imports<P>(pool: Arc<P>, stats: Stats) -> impl Future<Output = ()> where
+	P: TransactionPool<Block = Block, Hash = Hash> + 'static,
+{
+	pool.import_notification_stream().for_each(move |hash| {
+		let imported = pool.ready().find(|tx| *tx.hash() == hash);
+		match imported.and_then(|tx| decode_call(tx.data())) {
+			Some(call) => {
+				trace!(target: TARGET, "Imported {:?} targeting {}", hash, pallet_name(&call));
+				record(&stats, &call, |s| s.imported += 1);
+			}
+			None => {
+				trace!(target: TARGET, "Imported {:?}, which is not ready yet", hash);
+				stats.lock().pending.insert(hash);
+			}
+		}
+		future::ready(())
+	})
+}
+
+/// A future that counts every transaction in a finalized block and logs a summary
+pub fn log_finalized<C>(client: Arc<C>, stats: Stats) -> impl Future<Output = ()> where
+	C: BlockchainEvents<Block> + ReadBody + Send + Sync + 'static,
+{
+	client.finality_notification_stream().for_each(move |notification| {
+		let body = client.read_body(notification.hash).unwrap_or_default();
+		record_finalized(&stats, &body);
+		let stats = stats.lock();
+		debug!(
+			target: TARGET,
+			"Block {} finalized. Transactions per pallet: {:?}, pending: {}",
+			notification.hash,
+			stats.pallets,
+			stats.pending.len(),
+		);
+		future::ready(())
+	})
+}
+
+/// Read the extrinsics of a block
+pub trait ReadBody {
+	fn read_body(&self, hash: Hash) -> Option<Vec<<Block as sp_runtime::traits::Block>::Extrinsic>>;
+}
+
+impl<B, E, RA> ReadBody for sc_client::Client<B, E, Block, RA> where
+	B: sc_client_api::backend::Backend<Block, sp_core::Blake2Hasher>,
+	E: sc_client::CallExecutor<Block, sp_core::Blake2Hasher>,
+{
+	fn read_body(&self, hash: Hash) -> Option<Vec<<Block as sp_runtime::traits::Block>::Extrinsic>> {
+		self.body(&BlockId::Hash(hash)).ok()?
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn calls_are_attributed_to_pallets() {
+		let stats = Stats::default();
+		let call = Call::SimpleMap(simple_map::Call::set_single_entry(19));
+		record(&stats, &call, |s| s.imported += 1);
+		record(&stats, &call, |s| s.finalized += 1);
+
+		assert_eq!(
+			stats.lock().pallets.get("SimpleMap"),
+			Some(&PalletStats { imported: 1, finalized: 1 }),
+		);
+	}
+
+	#[test]
+	fn pending_imports_are_attributed_on_finality() {
+		let stats = Stats::default();
+		let call = Call::SimpleMap(simple_map::Call::set_single_entry(19));
+		let xt = sp_runtime::OpaqueExtrinsic(UncheckedExtrinsic::new_unsigned(call).encode());
+		stats.lock().pending.insert(BlakeTwo256::hash_of(&xt));
+
+		record_finalized(&stats, &[xt]);
+
+		let stats = stats.lock();
+		assert!(stats.pending.is_empty());
+		assert_eq!(
+			stats.pallets.get("SimpleMap"),
+			Some(&PalletStats { imported: 1, finalized: 1 }),
+		);
+	}
+}
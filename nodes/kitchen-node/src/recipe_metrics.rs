@@ -0,0 +1,199 @@
+//! Prometheus metrics about recipe pallet events.
+//!
+//! A background task follows finality notifications, reads the `System::Events` storage item of
+//! every finalized block, and counts the events per recipe pallet. Events of the FRAME pallets the
+//! runtime is built on, like System and Balances, are not counted. The counts are kept in a
+//! Prometheus registry that is served in the text exposition format on `--recipe-metrics-port`.
+//!
+//! The service has no Prometheus registry to register with at this Substrate revision. Its only
+//! metrics endpoint is the Grafana data source behind `--grafana-port`, which takes unlabelled
+//! series, so the recipe keeps a registry of its own and serves it itself.
+//!
+//! ```text
+//! $ curl 127.0.0.1:9616/metrics
+//! # HELP recipe_pallet_events_total Number of events deposited in finalized blocks, by pallet
+//! # TYPE recipe_pallet_events_total counter
+//! recipe_pallet_events_total{pallet="simple_map"} 3
+//! ```
+
+use std::io::{Read, Write};
+use std::net::{Ipv4Addr, TcpListener};
+use std::sync::Arc;
+use futures::{future, Future, StreamExt};
+use log::{debug, warn};
+use prometheus::{Encoder, IntCounterVec, Opts, Registry, TextEncoder};
+use structopt::StructOpt;
+use sc_client_api::BlockchainEvents;
+use sp_core::storage::StorageKey;
+use sp_runtime::generic::BlockId;
+use codec::Decode;
+use runtime::{opaque::Block, Event, Hash, Runtime};
+use frame_system::EventRecord;
+use support::StorageValue;
+
+/// The pallets of `construct_runtime!` that are not recipes, by the names of their event variants
+const FRAME_PALLETS: [&str; 5] = ["system", "indices", "balances", "grandpa", "sudo"];
+
+/// The kitchen node's flags for the recipe metrics, next to Substrate's flags
+#[derive(Debug, StructOpt, Clone)]
+pub struct RecipeMetricsParams {
+	/// Port on which the recipe metrics are served, on 127.0.0.1
+	#[structopt(long = "recipe-metrics-port", value_name = "PORT", default_value = "9616")]
+	pub port: u16,
+
+	/// Don't serve the recipe metrics
+	#[structopt(long = "no-recipe-metrics")]
+	pub disabled: bool,
+}
+
+sc_cli::impl_augment_clap!(RecipeMetricsParams);
+
+/// The metrics collected by the recipe subsystem
+#[derive(Clone)]
+pub struct RecipeMetrics {
+	registry: Registry,
+	pallet_events: IntCounterVec,
+}
+
+impl RecipeMetrics {
+	/// Create the metrics and register them with a fresh registry
+	pub fn new() -> Result<Self, prometheus::Error> {
+		let registry = Registry::new();
+		let pallet_events = IntCounterVec::new(
+			Opts::new(
+				"recipe_pallet_events_total",
+				"Number of events deposited in finalized blocks, by pallet",
+			),
+			&["pallet"],
+		)?;
+		registry.register(Box::new(pallet_events.clone()))?;
+		Ok(Self { registry, pallet_events })
+	}
+
+	/// Count each event of a recipe pallet under the name of the pallet that deposited it
+	pub fn observe(&self, events: &[EventRecord<Event, Hash>]) {
+		for record in events {
+			let pallet = pallet_name(&record.event);
+			if is_recipe_pallet(&pallet) {
+				self.pallet_events.with_label_values(&[&pallet]).inc();
+			}
+		}
+	}
+
+	/// Render all registered metrics in the Prometheus text format
+	pub fn render(&self) -> Vec<u8> {
+		let mut buffer = Vec::new();
+		let encoder = TextEncoder::new();
+		if let Err(e) = encoder.encode(&self.registry.gather(), &mut buffer) {
+			warn!("Failed to encode recipe metrics: {:?}", e);
+		}
+		buffer
+	}
+}
+
+/// The name of the pallet that deposited an event.
+///
+/// The outer `Event` enum generated by `construct_runtime!` has one variant per pallet, named after
+/// the pallet's module (e.g. `simple_map(EntrySet(..))`), so the name is the Debug output up to the
+/// first parenthesis.
+pub(crate) fn pallet_name(event: &Event) -> String {
+	let debug = format!("{:?}", event);
+	debug.split('(').next().unwrap_or_default().to_string()
+}
+
+/// Whether a pallet, named as by `pallet_name`, is a recipe rather than part of FRAME
+pub(crate) fn is_recipe_pallet(pallet: &str) -> bool {
+	!FRAME_PALLETS.contains(&pallet)
+}
+
+/// A future that counts the events of every finalized block until the client shuts down
+pub fn count_finalized_events<C>(client: Arc<C>, metrics: RecipeMetrics) -> impl Future<Output = ()>
+where
+	C: BlockchainEvents<Block> + ReadEvents + Send + Sync + 'static,
+{
+	client.finality_notification_stream().for_each(move |notification| {
+		match client.read_events(notification.hash) {
+			Some(events) => metrics.observe(&events),
+			None => debug!("No events found in finalized block {}", notification.hash),
+		}
+		future::ready(())
+	})
+}
+
+/// Read the decoded `System::Events` of a block
+pub trait ReadEvents {
+	fn read_events(&self, hash: Hash) -> Option<Vec<EventRecord<Event, Hash>>>;
+}
+
+impl<B, E, RA> ReadEvents for sc_client::Client<B, E, Block, RA> where
+	B: sc_client_api::backend::Backend<Block, sp_core::Blake2Hasher>,
+	E: sc_client::CallExecutor<Block, sp_core::Blake2Hasher>,
+{
+	fn read_events(&self, hash: Hash) -> Option<Vec<EventRecord<Event, Hash>>> {
+		let key = StorageKey(frame_system::Events::<Runtime>::hashed_key().to_vec());
+		let data = self.storage(&BlockId::Hash(hash), &key).ok()??;
+		Decode::decode(&mut &data.0[..]).ok()
+	}
+}
+
+/// Serve the metrics over plain HTTP on a dedicated thread
+///
+/// Every request, whatever its path, receives the current metrics. This is enough for Prometheus
+/// to scrape, and keeps the recipe free of an HTTP framework.
+pub fn serve(metrics: RecipeMetrics, port: u16) -> std::io::Result<()> {
+	let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+	std::thread::Builder::new()
+		.name("recipe-metrics".into())
+		.spawn(move || {
+			for stream in listener.incoming() {
+				let mut stream = match stream {
+					Ok(stream) => stream,
+					Err(e) => {
+						warn!("Recipe metrics connection failed: {:?}", e);
+						continue;
+					}
+				};
+				// The request itself is irrelevant, but it must be read before responding
+				let mut request = [0u8; 1024];
+				let _ = stream.read(&mut request);
+
+				let body = metrics.render();
+				let header = format!(
+					"HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\n\r\n",
+					body.len(),
+				);
+				if let Err(e) = stream.write_all(header.as_bytes()).and_then(|_| stream.write_all(&body)) {
+					warn!("Failed to send recipe metrics: {:?}", e);
+				}
+			}
+		})?;
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use frame_system::Phase;
+
+	#[test]
+	fn events_are_counted_per_pallet() {
+		let metrics = RecipeMetrics::new().unwrap();
+		let record = |event| EventRecord { phase: Phase::ApplyExtrinsic(0), event, topics: vec![] };
+		metrics.observe(&[
+			record(Event::simple_map(simple_map::RawEvent::EntrySet(Default::default(), 1))),
+			record(Event::simple_map(simple_map::RawEvent::EntryTook(Default::default(), 1))),
+			record(Event::adding_machine(adding_machine::Event::Added(1, 2, 3))),
+		]);
+
+		let rendered = String::from_utf8(metrics.render()).unwrap();
+		assert!(rendered.contains("recipe_pallet_events_total{pallet=\"simple_map\"} 2"));
+		assert!(rendered.contains("recipe_pallet_events_total{pallet=\"adding_machine\"} 1"));
+	}
+
+	#[test]
+	fn frame_pallets_are_not_recipes() {
+		assert!(is_recipe_pallet("simple_map"));
+		assert!(!is_recipe_pallet("system"));
+		assert!(!is_recipe_pallet("balances"));
+	}
+}
@@ -12,11 +12,13 @@ pub use sc_executor::NativeExecutor;
 use sc_consensus_babe;
 use sc_finality_grandpa::{self, FinalityProofProvider as GrandpaFinalityProofProvider};
 
-// Our native executor instance.
+// Our native executor instance. The runtime uses the host function of the hash-chain pallet, so the
+// executor must provide it in addition to the standard ones.
 native_executor_instance!(
 	pub Executor,
 	runtime::api::dispatch,
 	runtime::native_version,
+	hash_chain::host_hashing::HostFunctions,
 );
 
 construct_simple_protocol! {
@@ -82,9 +84,10 @@ macro_rules! new_full_start {
 }
 
 /// Builds a new service for a full client.
-pub fn new_full<C: Send + Default + 'static>(config: Configuration<C, GenesisConfig>)
-	-> Result<impl AbstractService, ServiceError>
-{
+pub fn new_full<C: Send + Default + 'static>(
+	config: Configuration<C, GenesisConfig>,
+	recipe_metrics_params: crate::recipe_metrics::RecipeMetricsParams,
+) -> Result<impl AbstractService, ServiceError> {
 
 	let is_authority = config.roles.is_authority();
 	let name = config.name.clone();
@@ -99,6 +102,37 @@ pub fn new_full<C: Send + Default + 'static>(config: Configuration<C, GenesisCon
 		)?
 		.build()?;
 
+	// Count recipe pallet events in finalized blocks and expose them to Prometheus
+	let recipe_metrics = crate::recipe_metrics::RecipeMetrics::new()
+		.map_err(|e| format!("Failed to register recipe metrics: {:?}", e))?;
+	// The node is useful without its metrics, so a port that is taken only costs the metrics
+	if !recipe_metrics_params.disabled {
+		let port = recipe_metrics_params.port;
+		if let Err(e) = crate::recipe_metrics::serve(recipe_metrics.clone(), port) {
+			log::warn!("Not serving recipe metrics on port {}: {}", port, e);
+		}
+	}
+	service.spawn_task(Box::pin(
+		crate::recipe_metrics::count_finalized_events(service.client(), recipe_metrics)
+	));
+
+	// Log per-pallet statistics about transactions entering the pool and reaching finality
+	let pool_stats = crate::pool_logger::Stats::default();
+	service.spawn_task(Box::pin(
+		crate::pool_logger::log_imports(service.transaction_pool(), pool_stats.clone())
+	));
+	service.spawn_task(Box::pin(
+		crate::pool_logger::log_finalized(service.client(), pool_stats)
+	));
+
+	// Gossip a digest of the events of every finalized block to peers, and log theirs
+	service.spawn_task(Box::pin(crate::digest_gossip::run(
+		service.client(),
+		service.network(),
+		&service.spawn_task_handle(),
+		name.clone(),
+	)));
+
 	let (block_import, grandpa_link, babe_link) =
 		import_setup.take()
 			.expect("Link Half and Block Import are present for Full Services or setup failed before. qed");
@@ -0,0 +1,49 @@
+//! Watches imported blocks for miners who mine two blocks at the same height.
+//!
+//! Every imported header is remembered by its height and its author, which the `pow_` pre-runtime
+//! digest names. When a second header by the same author arrives at a height, the two headers are
+//! queued with the equivocation pallet's inherent data provider, and the next block this node mines
+//! reports them to the runtime.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use futures::{future, Future, StreamExt};
+use log::info;
+use sc_client_api::BlockchainEvents;
+use sp_runtime::traits::Header as HeaderT;
+use equivocation::{EquivocationProof, InherentDataProvider, PowAuthor};
+use runtime::{opaque::{Block, Header}, AccountId, BlockHashCount, BlockNumber, FindAuthor, Get};
+
+/// Queue proofs of equivocations among the blocks `client` imports
+pub fn run<C>(
+	client: Arc<C>,
+	equivocations: InherentDataProvider<Header>,
+) -> impl Future<Output = ()> where
+	C: BlockchainEvents<Block>,
+{
+	let mut seen: BTreeMap<BlockNumber, Vec<(AccountId, Header)>> = BTreeMap::new();
+
+	client.import_notification_stream().for_each(move |notification| {
+		let header = notification.header;
+		let number = *header.number();
+		let digests = header.digest().logs().iter().filter_map(|d| d.as_pre_runtime());
+
+		if let Some(author) = PowAuthor::<AccountId>::find_author(digests) {
+			let at_height = seen.entry(number).or_default();
+			match at_height.iter().find(|(a, _)| *a == author) {
+				Some((_, first)) => {
+					info!("{} mined two blocks at height {}, reporting it", author, number);
+					equivocations.report(EquivocationProof { first: first.clone(), second: header });
+				},
+				None => at_height.push((author, header)),
+			}
+
+			// The runtime only knows the hashes of the last `BlockHashCount` blocks, so it can't
+			// check older proofs
+			let newest = *seen.keys().next_back().expect("an entry was just added; qed");
+			seen = seen.split_off(&newest.saturating_sub(BlockHashCount::get()));
+		}
+
+		future::ready(())
+	})
+}
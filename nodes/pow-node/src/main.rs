@@ -0,0 +1,27 @@
+//! A proof of work node that mines blocks of the pow-runtime.
+
+#![warn(missing_docs)]
+#![warn(unused_extern_crates)]
+
+mod chain_spec;
+#[macro_use]
+mod service;
+mod cli;
+mod equivocation_reporter;
+mod pow;
+
+pub use sc_cli::{VersionInfo, IntoExit, error};
+
+fn main() -> Result<(), cli::error::Error> {
+	let version = VersionInfo {
+		name: "Proof of Work Node",
+		commit: env!("VERGEN_SHA_SHORT"),
+		version: env!("CARGO_PKG_VERSION"),
+		executable_name: "pow-node",
+		author: "Anonymous",
+		description: "Proof of Work Node",
+		support_url: "support.anonymous.an",
+	};
+
+	cli::run(std::env::args(), cli::Exit, version)
+}
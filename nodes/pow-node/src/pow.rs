@@ -0,0 +1,79 @@
+//! The node's proof of work algorithm.
+//!
+//! A seal is any 32 bytes. It is valid for a block when the blake2 hash of the block's pre-seal
+//! hash and the seal, read as a number, times the difficulty fits in 256 bits. This is the check
+//! the uncles pallet's `Blake2Seal` repeats in the runtime, so the two must not drift apart.
+
+use codec::Encode;
+use rand::Rng;
+use structopt::StructOpt;
+use sp_core::{blake2_256, crypto::Ss58Codec, H256, U256};
+use sp_runtime::{generic::BlockId, traits::Block as BlockT};
+use sp_consensus_pow::{Difficulty, Seal};
+use sc_consensus_pow::PowAlgorithm;
+use runtime::{AccountId, Get, MiningDifficulty};
+
+/// The pow node's flags for mining, next to Substrate's flags
+#[derive(Debug, StructOpt, Clone)]
+pub struct MiningParams {
+	/// SS58 address of the account that earns the rewards of blocks this node mines. Defaults to
+	/// Alice's development account.
+	#[structopt(long = "author", value_name = "SS58", parse(try_from_str = parse_account),
+		default_value = "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY")]
+	pub author: AccountId,
+
+	/// How many seals the miner tries before it checks for a better block to build on
+	#[structopt(long = "mining-rounds", value_name = "COUNT", default_value = "10000")]
+	pub rounds: u32,
+}
+
+sc_cli::impl_augment_clap!(MiningParams);
+
+fn parse_account(address: &str) -> Result<AccountId, String> {
+	AccountId::from_ss58check(address).map_err(|e| format!("Invalid account {}: {:?}", address, e))
+}
+
+/// Blake2 proof of work with the runtime's fixed `MiningDifficulty`
+#[derive(Clone)]
+pub struct Blake2Algorithm;
+
+impl<B: BlockT<Hash = H256>> PowAlgorithm<B> for Blake2Algorithm {
+	fn difficulty(&self, _parent: &BlockId<B>) -> Result<Difficulty, String> {
+		// The difficulty never changes, so the native runtime's constant is the chain's
+		Ok(U256::from(MiningDifficulty::get()))
+	}
+
+	fn verify(
+		&self,
+		_parent: &BlockId<B>,
+		pre_hash: &H256,
+		seal: &Seal,
+		difficulty: Difficulty,
+	) -> Result<bool, String> {
+		Ok(is_valid(pre_hash, seal, difficulty))
+	}
+
+	fn mine(
+		&self,
+		_parent: &BlockId<B>,
+		pre_hash: &H256,
+		difficulty: Difficulty,
+		round: u32,
+	) -> Result<Option<Seal>, String> {
+		let mut rng = rand::thread_rng();
+		for _ in 0..round {
+			let seal = rng.gen::<[u8; 32]>().to_vec();
+			if is_valid(pre_hash, &seal, difficulty) {
+				return Ok(Some(seal))
+			}
+		}
+		Ok(None)
+	}
+}
+
+/// Whether `seal` is a solution for the block whose pre-seal hash is `pre_hash`
+fn is_valid(pre_hash: &H256, seal: &Seal, difficulty: Difficulty) -> bool {
+	// Encoded like the runtime's `(T::Hash, Vec<u8>)`
+	let work = blake2_256(&(pre_hash, seal).encode());
+	U256::from(&work[..]).checked_mul(difficulty).is_some()
+}
@@ -0,0 +1,168 @@
+//! Service and ServiceFactory implementation. Specialized wrapper over substrate service.
+
+use std::sync::Arc;
+use std::time::Duration;
+use codec::Encode;
+use sc_client::LongestChain;
+use runtime::{self, GenesisConfig, opaque::Block, RuntimeApi};
+use sc_service::{error::{Error as ServiceError}, AbstractService, Configuration, ServiceBuilder};
+use sp_inherents::InherentDataProviders;
+use sc_network::{config::DummyFinalityProofRequestBuilder, construct_simple_protocol};
+use sc_executor::native_executor_instance;
+pub use sc_executor::NativeExecutor;
+use sc_basic_authority;
+use crate::pow::{Blake2Algorithm, MiningParams};
+
+// Our native executor instance.
+native_executor_instance!(
+	pub Executor,
+	runtime::api::dispatch,
+	runtime::native_version,
+);
+
+construct_simple_protocol! {
+	/// Demo protocol attachment for substrate.
+	pub struct NodeProtocol where Block = Block { }
+}
+
+/// How long the miner spends building a block's body before it starts mining it
+const BUILD_TIME: Duration = Duration::from_secs(2);
+
+/// Starts a `ServiceBuilder` for a full service.
+///
+/// Use this macro if you don't actually need the full service, but just the builder in order to
+/// be able to perform chain operations.
+macro_rules! new_full_start {
+	($config:expr) => {{
+		let inherent_data_providers = sp_inherents::InherentDataProviders::new();
+
+		let builder = sc_service::ServiceBuilder::new_full::<
+			runtime::opaque::Block, runtime::RuntimeApi, crate::service::Executor
+		>($config)?
+			.with_select_chain(|_config, backend| {
+				Ok(sc_client::LongestChain::new(backend.clone()))
+			})?
+			.with_transaction_pool(|config, client, _fetcher| {
+				let pool_api = sc_transaction_pool::FullChainApi::new(client.clone());
+				let pool = sc_transaction_pool::BasicPool::new(config, pool_api);
+				let maintainer = sc_transaction_pool::FullBasicPoolMaintainer::new(pool.pool().clone(), client);
+				let maintainable_pool = sp_transaction_pool::MaintainableTransactionPool::new(pool, maintainer);
+				Ok(maintainable_pool)
+			})?
+			.with_import_queue(|_config, client, select_chain, _transaction_pool| {
+				// The verifier checks each header's seal, and registers the timestamp inherent
+				// data provider
+				let import_queue = sc_consensus_pow::import_queue(
+					Box::new(client.clone()),
+					client,
+					crate::pow::Blake2Algorithm,
+					0,
+					select_chain,
+					inherent_data_providers.clone(),
+				)?;
+
+				Ok(import_queue)
+			})?;
+
+		(builder, inherent_data_providers)
+	}}
+}
+
+/// Builds a new service for a full client.
+pub fn new_full<C: Send + Default + 'static>(
+	config: Configuration<C, GenesisConfig>,
+	mining: MiningParams,
+) -> Result<impl AbstractService, ServiceError> {
+	let is_authority = config.roles.is_authority();
+
+	let (builder, inherent_data_providers) = new_full_start!(config);
+
+	let service = builder.with_network_protocol(|_| Ok(NodeProtocol::new()))?
+		.with_finality_proof_provider(|_client, _backend| Ok(Arc::new(()) as _))?
+		.build()?;
+
+	// Queue the equivocations of other miners, to be reported in the blocks this node mines
+	let equivocations = equivocation::InherentDataProvider::new();
+	service.spawn_task(Box::pin(
+		crate::equivocation_reporter::run(service.client(), equivocations.clone())
+	));
+
+	if is_authority {
+		let proposer = sc_basic_authority::ProposerFactory {
+			client: service.client(),
+			transaction_pool: service.transaction_pool(),
+		};
+
+		let select_chain = service.select_chain()
+			.ok_or(ServiceError::SelectChainRequired)?;
+
+		// Offer the uncles of the chain head to the uncles pallet's inherent
+		sc_consensus_uncles::register_uncles_inherent_data_provider(
+			service.client(),
+			select_chain.clone(),
+			&inherent_data_providers,
+		)?;
+		inherent_data_providers.register_provider(equivocations)
+			.map_err(|e| format!("Failed to register the equivocation inherent: {:?}", e))?;
+
+		log::info!("Mining, with rewards paid to {}", mining.author);
+
+		// Mining runs on threads of its own
+		sc_consensus_pow::start_mine(
+			Box::new(service.client().clone()),
+			service.client(),
+			Blake2Algorithm,
+			proposer,
+			// The runtime's `PowAuthor` reads the author from this pre-runtime digest
+			Some(mining.author.encode()),
+			mining.rounds,
+			service.network(),
+			BUILD_TIME,
+			Some(select_chain),
+			inherent_data_providers,
+			sp_consensus::AlwaysCanAuthor,
+		);
+	}
+
+	Ok(service)
+}
+
+/// Builds a new service for a light client.
+pub fn new_light<C: Send + Default + 'static>(config: Configuration<C, GenesisConfig>)
+	-> Result<impl AbstractService, ServiceError>
+{
+	let inherent_data_providers = InherentDataProviders::new();
+
+	ServiceBuilder::new_light::<Block, RuntimeApi, Executor>(config)?
+		.with_select_chain(|_config, backend| {
+			Ok(LongestChain::new(backend.clone()))
+		})?
+		.with_transaction_pool(|config, client, fetcher| {
+			let fetcher = fetcher
+				.ok_or_else(|| "Trying to start light transaction pool without active fetcher")?;
+			let pool_api = sc_transaction_pool::LightChainApi::new(client.clone(), fetcher.clone());
+			let pool = sc_transaction_pool::BasicPool::new(config, pool_api);
+			let maintainer = sc_transaction_pool::LightBasicPoolMaintainer::with_defaults(pool.pool().clone(), client, fetcher);
+			let maintainable_pool = sp_transaction_pool::MaintainableTransactionPool::new(pool, maintainer);
+			Ok(maintainable_pool)
+		})?
+		.with_import_queue_and_fprb(|_config, client, _backend, _fetcher, select_chain, _tx_pool| {
+			// Proof of work has no finality, so there are no finality proofs to request
+			let finality_proof_request_builder =
+				Box::new(DummyFinalityProofRequestBuilder::default()) as _;
+
+			let import_queue = sc_consensus_pow::import_queue(
+				Box::new(client.clone()),
+				client,
+				Blake2Algorithm,
+				0,
+				select_chain,
+				inherent_data_providers.clone(),
+			)?;
+
+			Ok((import_queue, finality_proof_request_builder))
+		})?
+		.with_network_protocol(|_| Ok(NodeProtocol::new()))?
+		.with_finality_proof_provider(|_client, _backend| Ok(Arc::new(()) as _))?
+		.build()
+}
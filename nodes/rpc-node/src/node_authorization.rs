@@ -0,0 +1,160 @@
+//! Restricting the connections of the node to the peers the chain authorizes.
+//!
+//! After every finalized block, a background task asks the runtime which peers the local node may
+//! connect to, through the `NodeAuthorizationApi`. It reserves connections to newly authorized
+//! peers and drops the reserved peers that lost their authorization. Started with
+//! `--reserved-only`, the node refuses all other peers, so only authorized peers can connect.
+//!
+//! Peers are reserved by peer id alone. The node learns their addresses through discovery, so at
+//! least one authorized peer must be reachable at a known address, e.g. a bootnode.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+use futures::{future, Future, StreamExt};
+use log::{info, warn};
+use sc_client_api::BlockchainEvents;
+use sc_network::{specialization::NetworkSpecialization, ExHashT, NetworkService, PeerId};
+use sp_runtime::{
+	generic::BlockId,
+	traits::{Block as BlockT, ProvideRuntimeApi},
+};
+use node_authorization_runtime_api::NodeAuthorizationApi;
+use runtime::opaque::Block;
+
+const TARGET: &str = "node-authorization";
+
+/// The network operations that restricting connections needs
+pub trait ReservedPeers {
+	fn local_peer_id(&self) -> PeerId;
+	fn add_reserved_peer(&self, peer: &PeerId) -> Result<(), String>;
+	fn remove_reserved_peer(&self, peer: &PeerId);
+}
+
+impl<B, S, H> ReservedPeers for NetworkService<B, S, H> where
+	B: BlockT + 'static,
+	S: NetworkSpecialization<B>,
+	H: ExHashT,
+{
+	fn local_peer_id(&self) -> PeerId {
+		NetworkService::local_peer_id(self).clone()
+	}
+
+	fn add_reserved_peer(&self, peer: &PeerId) -> Result<(), String> {
+		// A multiaddr without an address only names the peer
+		NetworkService::add_reserved_peer(self, format!("/p2p/{}", peer))
+	}
+
+	fn remove_reserved_peer(&self, peer: &PeerId) {
+		NetworkService::remove_reserved_peer(self, peer.clone())
+	}
+}
+
+/// The peers the node reserved connections to
+#[derive(Default, Debug)]
+pub struct Reserved {
+	peers: HashSet<PeerId>,
+}
+
+impl Reserved {
+	/// Make `authorized` the reserved peers. Returns the peers to add and the peers to remove.
+	pub fn update(&mut self, authorized: HashSet<PeerId>) -> (Vec<PeerId>, Vec<PeerId>) {
+		let added = authorized.difference(&self.peers).cloned().collect();
+		let removed = self.peers.difference(&authorized).cloned().collect();
+		self.peers = authorized;
+		(added, removed)
+	}
+
+	/// Forget a peer that could not be reserved, so the next update tries again
+	pub fn forget(&mut self, peer: &PeerId) {
+		self.peers.remove(peer);
+	}
+}
+
+/// The peer ids among the bytes the runtime returned. Invalid ones are skipped.
+pub fn decode_peers(peers: Vec<Vec<u8>>) -> HashSet<PeerId> {
+	peers.into_iter().filter_map(|bytes| PeerId::from_bytes(bytes).ok()).collect()
+}
+
+/// A future that keeps the reserved peers in step with the authorized peers of every finalized
+/// block, until the client shuts down
+pub fn run<C, N>(client: Arc<C>, network: Arc<N>) -> impl Future<Output = ()>
+where
+	C: BlockchainEvents<Block> + ProvideRuntimeApi + Send + Sync + 'static,
+	C::Api: NodeAuthorizationApi<Block>,
+	N: ReservedPeers + Send + Sync + 'static,
+{
+	let local = network.local_peer_id().into_bytes();
+	let mut reserved = Reserved::default();
+	client.finality_notification_stream().for_each(move |notification| {
+		let at = BlockId::hash(notification.hash);
+		match client.runtime_api().authorized_peers(&at, local.clone()) {
+			Ok(peers) => {
+				let (added, removed) = reserved.update(decode_peers(peers));
+				for peer in added {
+					match network.add_reserved_peer(&peer) {
+						Ok(()) => info!(target: TARGET, "Authorized peer {}", peer),
+						Err(e) => {
+							warn!(target: TARGET, "Failed to reserve peer {}: {}", peer, e);
+							reserved.forget(&peer);
+						}
+					}
+				}
+				for peer in removed {
+					network.remove_reserved_peer(&peer);
+					info!(target: TARGET, "Peer {} is no longer authorized", peer);
+				}
+			}
+			Err(e) => warn!(
+				target: TARGET,
+				"Failed to read the authorized peers at {}: {:?}",
+				notification.hash,
+				e,
+			),
+		}
+		future::ready(())
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn set(peers: &[PeerId]) -> HashSet<PeerId> {
+		peers.iter().cloned().collect()
+	}
+
+	#[test]
+	fn update_reports_changes_only() {
+		let (alice, bob, charlie) = (PeerId::random(), PeerId::random(), PeerId::random());
+		let mut reserved = Reserved::default();
+
+		let (added, removed) = reserved.update(set(&[alice.clone(), bob.clone()]));
+		assert_eq!(set(&added), set(&[alice.clone(), bob.clone()]));
+		assert!(removed.is_empty());
+
+		let (added, removed) = reserved.update(set(&[bob.clone(), charlie.clone()]));
+		assert_eq!(added, vec![charlie.clone()]);
+		assert_eq!(removed, vec![alice]);
+
+		let (added, removed) = reserved.update(set(&[bob, charlie]));
+		assert!(added.is_empty() && removed.is_empty());
+	}
+
+	#[test]
+	fn forgotten_peers_are_added_again() {
+		let bob = PeerId::random();
+		let mut reserved = Reserved::default();
+		reserved.update(set(&[bob.clone()]));
+		reserved.forget(&bob);
+
+		let (added, _) = reserved.update(set(&[bob.clone()]));
+		assert_eq!(added, vec![bob]);
+	}
+
+	#[test]
+	fn invalid_peer_ids_are_skipped() {
+		let bob = PeerId::random();
+		let decoded = decode_peers(vec![bob.clone().into_bytes(), b"not a peer id".to_vec()]);
+		assert_eq!(decoded, set(&[bob]));
+	}
+}
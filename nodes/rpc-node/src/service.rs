@@ -75,7 +75,7 @@ macro_rules! new_full_start {
 
 				Ok(import_queue)
 			})?
-			.with_rpc_extensions(|client, _pool, _backend, _fetcher, _remote_blockchain| -> Result<RpcExtension, _> {
+			.with_rpc_extensions(|client, _pool, backend, _fetcher, _remote_blockchain| -> Result<RpcExtension, _> {
 				// Make an io handler to be extended with individual RPCs
 				let mut io = jsonrpc_core::IoHandler::default();
 
@@ -83,12 +83,31 @@ macro_rules! new_full_start {
 				// Use the fully qualified name starting from `crate` because we're in macro_rules!
 				io.extend_with(crate::silly_rpc::SillyRpc::to_delegate(crate::silly_rpc::Silly{}));
 
+				// Add the dry run RPC extension
+				// It calls a Runtime API too, so it gets a clone of the client before the next
+				// extension takes ownership of it. It decodes calls and encodes events with the
+				// runtime's own types, so they are named here.
+				io.extend_with(dry_run_rpc::DryRunApi::to_delegate(
+					dry_run_rpc::DryRun::<_, (Block, runtime::AccountId, runtime::Call, runtime::Event)>::new(
+						client.clone(),
+					),
+				));
+
 				// Add the second RPC extension
 				// Because this one calls a Runtime API it needs a reference to the client.
 				// In this case we give ownership to the existing client, but in general, you
 				// may need to use client.clone()
 				io.extend_with(sum_storage_rpc::SumStorageApi::to_delegate(sum_storage_rpc::SumStorage::new(client)));
 
+				// Add the third RPC extension
+				// This one reads blobs that the transaction-storage pallet's offchain worker wrote,
+				// so it needs the backend's offchain storage rather than the client.
+				if let Some(storage) = sc_client_api::backend::Backend::offchain_storage(&*backend) {
+					io.extend_with(transaction_storage_rpc::TransactionStorageApi::to_delegate(
+						transaction_storage_rpc::TransactionStorage::new(storage),
+					));
+				}
+
 				Ok(io)
 			})?;
 
@@ -122,6 +141,11 @@ pub fn new_full<C: Send + Default + 'static>(config: Configuration<C, GenesisCon
 		)?
 		.build()?;
 
+	// Keep the reserved peers in step with the peers the chain authorizes for this node
+	service.spawn_task(Box::pin(
+		crate::node_authorization::run(service.client(), service.network())
+	));
+
 	if participates_in_consensus {
 		let proposer = sc_basic_authority::ProposerFactory {
 			client: service.client(),
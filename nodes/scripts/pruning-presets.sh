@@ -0,0 +1,34 @@
+#!/usr/bin/env bash
+#
+# Start the rpc-node as an archive node, which keeps the state of every block, or as a pruned
+# node, which only keeps the state of the latest 16 blocks.
+#
+#   ./nodes/scripts/pruning-presets.sh archive|pruned [extra node flags]
+#
+# A database is created in one pruning mode and cannot be switched to the other, so each preset
+# uses its own base path.
+
+set -e
+
+NODE=${NODE:-./target/release/rpc-node}
+BASE_PATH=${BASE_PATH:-/tmp/recipes-rpc-node}
+
+PRESET=$1
+shift || true
+
+case "$PRESET" in
+   archive)
+      PRUNING=archive
+      ;;
+   pruned)
+      PRUNING=16
+      ;;
+   *)
+      echo "Usage: $0 archive|pruned [extra node flags]"
+      exit 1
+      ;;
+esac
+
+echo "*** Starting $NODE with --pruning $PRUNING in $BASE_PATH/$PRESET"
+
+exec "$NODE" --dev --pruning "$PRUNING" --base-path "$BASE_PATH/$PRESET" "$@"
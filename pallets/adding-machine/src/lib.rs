@@ -2,18 +2,52 @@
 
 /// Adding Machine
 /// A simple adding machine which checks for overflow and emits an event with
-/// the result, without using storage.
-use support::{decl_event, decl_module, dispatch::{DispatchResult, DispatchError}};
-use system::ensure_signed;
+/// the result. It also keeps a running sum in storage, which root may reset.
+///
+/// The sum is a `u128`. A first version might well have stored it as a `u32`, which overflows
+/// after about four billion. To show how such a value changes type, a `u32` sum found in storage
+/// is converted in the first block after a runtime upgrade, see `migrate_sum_to_u128`.
+use support::{
+    decl_event, decl_module, decl_storage,
+    dispatch::{DispatchResult, DispatchError},
+    storage::unhashed,
+    traits::Get,
+    StorageValue,
+};
+use system::{ensure_root, ensure_signed};
 
 pub trait Trait: system::Trait {
     type Event: From<Event> + Into<<Self as system::Trait>::Event>;
 }
 
+decl_storage! {
+    trait Store for Module<T: Trait> as AddingMachine {
+        // The running sum of all accumulated values
+        Sum get(fn sum): u128;
+
+        // Whether `Sum` has been converted from the old `u32` encoding
+        SumIsU128 get(fn sum_is_u128): bool;
+
+        // The `spec_version` of the runtime that last ran the migrations
+        MigratedSpecVersion get(fn migrated_spec_version): Option<u32>;
+    }
+}
+
 decl_module! {
     pub struct Module<T: Trait> for enum Call where origin: T::Origin {
         fn deposit_event() = default;
 
+        // Migrations must run before any call can read the old encoding as the new type. This
+        // Substrate version has no hook for runtime upgrades, so the pallet notices them itself
+        // by comparing the runtime's spec version with the one it last migrated for.
+        fn on_initialize(_n: T::BlockNumber) {
+            let spec_version = T::Version::get().spec_version;
+            if Self::migrated_spec_version() != Some(spec_version) {
+                Self::on_runtime_upgrade();
+                MigratedSpecVersion::put(spec_version);
+            }
+        }
+
         fn add(origin, val1: u32, val2: u32) -> DispatchResult {
             let _ = ensure_signed(origin)?;
             // checks for overflow
@@ -24,26 +58,72 @@ decl_module! {
             Self::deposit_event(Event::Added(val1, val2, result));
             Ok(())
         }
+
+        fn accumulate(origin, val: u128) -> DispatchResult {
+            let _ = ensure_signed(origin)?;
+            // `checked_add` returns `None` instead of wrapping around in release builds or
+            // panicking in debug builds. The sum is left unchanged when the call fails.
+            let sum = Self::sum()
+                .checked_add(val)
+                .ok_or(DispatchError::Other("Sum overflowed"))?;
+            Sum::put(sum);
+            Self::deposit_event(Event::Accumulated(val, sum));
+            Ok(())
+        }
+
+        fn reset(origin) -> DispatchResult {
+            ensure_root(origin)?;
+            let old_sum = Sum::take();
+            Self::deposit_event(Event::Reset(old_sum));
+            Ok(())
+        }
     }
 }
 
 decl_event!(
     pub enum Event {
         Added(u32, u32, u32),
+        // The value that was accumulated, and the new sum
+        Accumulated(u128, u128),
+        // Root reset the sum, which had this value
+        Reset(u128),
     }
 );
 
+impl<T: Trait> Module<T> {
+    // Runs once in the first block of every new runtime version
+    fn on_runtime_upgrade() {
+        Self::migrate_sum_to_u128();
+    }
+
+    // Convert a sum stored as a `u32` by the old version of the pallet into a `u128`. The flag
+    // makes sure this happens only once: afterwards the first four bytes of the new `u128` would
+    // decode as a `u32` just as well, and the conversion would truncate the sum.
+    fn migrate_sum_to_u128() {
+        if Self::sum_is_u128() {
+            return;
+        }
+        if let Some(old_sum) = unhashed::get::<u32>(&Sum::hashed_key()) {
+            Sum::put(u128::from(old_sum));
+        }
+        SumIsU128::put(true);
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::{Module, Trait};
+    use crate::{MigratedSpecVersion, Module, Sum, SumIsU128, Trait};
     use sp_runtime::{
         testing::Header,
-        traits::{BlakeTwo256, IdentityLookup},
+        traits::{BlakeTwo256, IdentityLookup, OnInitialize},
         Perbill,
     };
     use sp_core::H256;
-    use support::{assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types};
-    use system::{EventRecord, Phase};
+    use support::{
+        assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types,
+        storage::unhashed, StorageValue,
+    };
+    use system::{EventRecord, Phase, RawOrigin};
 
     impl_outer_origin! {
         pub enum Origin for TestRuntime {}
@@ -156,4 +236,89 @@ mod tests {
             );
         })
     }
+
+    #[test]
+    fn accumulate_keeps_running_sum() {
+        ExtBuilder::build().execute_with(|| {
+            assert_ok!(AddingMachine::accumulate(Origin::signed(1), 5));
+            assert_ok!(AddingMachine::accumulate(Origin::signed(2), u128::from(u32::max_value())));
+            assert_eq!(AddingMachine::sum(), 5 + u128::from(u32::max_value()));
+
+            let expected_event = TestEvent::added(crate::Event::Accumulated(5, 5));
+            assert!(System::events().iter().any(|a| a.event == expected_event));
+        })
+    }
+
+    #[test]
+    fn accumulate_overflow_fails() {
+        ExtBuilder::build().execute_with(|| {
+            assert_ok!(AddingMachine::accumulate(Origin::signed(1), u128::max_value() - 1));
+            assert_err!(
+                AddingMachine::accumulate(Origin::signed(1), 2),
+                "Sum overflowed"
+            );
+            assert_eq!(AddingMachine::sum(), u128::max_value() - 1);
+        })
+    }
+
+    #[test]
+    fn only_root_resets() {
+        ExtBuilder::build().execute_with(|| {
+            assert_ok!(AddingMachine::accumulate(Origin::signed(1), 42));
+            assert!(AddingMachine::reset(Origin::signed(1)).is_err());
+            assert_eq!(AddingMachine::sum(), 42);
+
+            assert_ok!(AddingMachine::reset(RawOrigin::Root.into()));
+            assert_eq!(AddingMachine::sum(), 0);
+            let expected_event = TestEvent::added(crate::Event::Reset(42));
+            assert!(System::events().iter().any(|a| a.event == expected_event));
+        })
+    }
+
+    #[test]
+    fn migrates_u32_sum() {
+        ExtBuilder::build().execute_with(|| {
+            // What the old version of the pallet left in storage
+            unhashed::put(&Sum::hashed_key(), &u32::max_value());
+
+            AddingMachine::on_initialize(1);
+            assert!(AddingMachine::sum_is_u128());
+            assert_eq!(AddingMachine::sum(), u128::from(u32::max_value()));
+
+            // A later block must not convert the sum again
+            assert_ok!(AddingMachine::accumulate(Origin::signed(1), 1));
+            AddingMachine::on_initialize(2);
+            assert_eq!(AddingMachine::sum(), u128::from(u32::max_value()) + 1);
+        })
+    }
+
+    #[test]
+    fn migrations_run_once_per_runtime_version() {
+        ExtBuilder::build().execute_with(|| {
+            AddingMachine::on_initialize(1);
+            // The test runtime's version is the default, with a `spec_version` of zero
+            assert_eq!(MigratedSpecVersion::get(), Some(0));
+
+            // Later blocks of the same version don't look at the sum at all
+            SumIsU128::kill();
+            unhashed::put(&Sum::hashed_key(), &7u32);
+            AddingMachine::on_initialize(2);
+            assert!(!AddingMachine::sum_is_u128());
+
+            // An upgrade runs them again
+            MigratedSpecVersion::put(1);
+            AddingMachine::on_initialize(3);
+            assert!(AddingMachine::sum_is_u128());
+            assert_eq!(AddingMachine::sum(), 7);
+        })
+    }
+
+    #[test]
+    fn migration_without_old_sum() {
+        ExtBuilder::build().execute_with(|| {
+            AddingMachine::on_initialize(1);
+            assert!(SumIsU128::get());
+            assert!(!Sum::exists());
+        })
+    }
 }
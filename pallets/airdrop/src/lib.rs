@@ -0,0 +1,649 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! Airdrops with a claim window, whose unclaimed funds go to the treasury
+//!
+//! Anyone can `create` an airdrop, paying its funds into the pallet's pot, and becomes the
+//! airdrop's admin. Accounts claim their share until the airdrop `ends`. There are two ways to say
+//! who gets what:
+//!
+//! * An airdrop to a few accounts stores them on chain. The admin `allocate`s amounts to accounts,
+//!   at most `MaxAllocations` in one call, and each account `claim`s its own.
+//! * An airdrop to many accounts only stores the root of a Merkle tree, whose leaves are the
+//!   hashes of `(account, amount)` pairs. Each account claims with `claim_with_proof`, giving its
+//!   amount and the hashes on the path from its leaf to the root. The two nodes of a pair are
+//!   sorted before they are hashed, so the proof doesn't need to say which side each hash is on.
+//!
+//! Nothing happens when an airdrop ends. Its unclaimed funds stay in the pot, and the entries of
+//! accounts that didn't claim stay in storage, until anyone `sweep`s it. Sweeping hands the funds
+//! to the `Treasury` and removes the entries with `remove_prefix`. It is weighed by the number of
+//! entries, which the airdrop keeps count of.
+
+use codec::{Decode, Encode};
+use sp_std::{marker::PhantomData, prelude::*};
+use sp_runtime::{
+	traits::{AccountIdConversion, CheckedAdd, CheckedSub, Hash, Saturating, Zero},
+	ModuleId, RuntimeDebug,
+};
+use frame_support::{
+	decl_event, decl_module, decl_storage,
+	dispatch::DispatchResult,
+	ensure,
+	traits::{
+		Currency, ExistenceRequirement::AllowDeath, Get, OnUnbalanced, WithdrawReason,
+	},
+	weights::{ClassifyDispatch, DispatchClass, PaysFee, SimpleDispatchInfo, WeighData, Weight},
+};
+use frame_system::{self as system, ensure_signed};
+
+pub mod weights;
+pub use weights::WeightInfo;
+
+pub type AirdropIndex = u32;
+
+type BalanceOf<T> = <<T as Trait>::Currency as Currency<<T as system::Trait>::AccountId>>::Balance;
+type NegativeImbalanceOf<T> =
+	<<T as Trait>::Currency as Currency<<T as system::Trait>::AccountId>>::NegativeImbalance;
+type AirdropOf<T> = Airdrop<
+	<T as system::Trait>::AccountId,
+	BalanceOf<T>,
+	<T as system::Trait>::BlockNumber,
+	<T as system::Trait>::Hash,
+>;
+
+/// Hardcoded pallet ID; used to create the account that holds the airdropped funds
+/// Must be exactly 8 characters long
+const PALLET_ID: ModuleId = ModuleId(*b"airdrops");
+
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct Airdrop<AccountId, Balance, BlockNumber, Hash> {
+	/// The account that created the airdrop, and allocates its funds
+	pub admin: AccountId,
+	/// The funds nobody has claimed yet
+	pub remaining: Balance,
+	/// The funds the admin has not allocated yet. Always zero for airdrops with a Merkle root.
+	pub unallocated: Balance,
+	/// The first block in which nothing can be claimed
+	pub ends: BlockNumber,
+	/// The root of the tree of `(account, amount)` pairs, for airdrops that don't store them
+	pub root: Option<Hash>,
+	/// The number of allocations and claimed flags the airdrop has in storage
+	pub entries: u32,
+}
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The currency that is airdropped
+	type Currency: Currency<Self::AccountId>;
+
+	/// Receives the funds nobody claimed
+	type Treasury: OnUnbalanced<NegativeImbalanceOf<Self>>;
+
+	/// The most allocations loaded in one call
+	type MaxAllocations: Get<u32>;
+
+	/// The most hashes in a Merkle proof, which is the height of the largest tree
+	type MaxProofLength: Get<u32>;
+
+	/// Weight information for the dispatchables in this pallet
+	type WeightInfo: WeightInfo;
+}
+
+/// Weighs `sweep` by the number of entries the airdrop it is called with has in storage
+pub struct SweepWeight<T>(PhantomData<T>);
+
+impl<T: Trait> WeighData<(&AirdropIndex,)> for SweepWeight<T> {
+	fn weigh_data(&self, (index,): (&AirdropIndex,)) -> Weight {
+		let entries = <Module<T>>::airdrop(index).map_or(0, |airdrop| airdrop.entries);
+		T::WeightInfo::sweep(entries)
+	}
+}
+
+impl<T, A> ClassifyDispatch<A> for SweepWeight<T> {
+	fn classify_dispatch(&self, _: A) -> DispatchClass {
+		Default::default()
+	}
+}
+
+impl<T> PaysFee for SweepWeight<T> {
+	fn pays_fee(&self) -> bool {
+		true
+	}
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as Airdrop {
+		/// Airdrops that have not been swept
+		Airdrops get(fn airdrop): map AirdropIndex => Option<AirdropOf<T>>;
+
+		/// Number of airdrops ever created; the next airdrop's index
+		AirdropCount get(fn airdrop_count): AirdropIndex;
+
+		/// The amount allocated to each account that has not claimed it, by airdrop
+		Allocations get(fn allocation):
+			double_map AirdropIndex, blake2_256(T::AccountId) => Option<BalanceOf<T>>;
+
+		/// The accounts that have claimed from airdrops with a Merkle root
+		Claimed get(fn claimed): double_map AirdropIndex, blake2_256(T::AccountId) => bool;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as system::Trait>::AccountId,
+		Balance = BalanceOf<T>,
+		BlockNumber = <T as system::Trait>::BlockNumber,
+	{
+		/// An account created an airdrop of this amount, which can be claimed until this block
+		Created(AirdropIndex, AccountId, Balance, BlockNumber),
+		/// The admin allocated funds to this many accounts, in total this amount
+		Allocated(AirdropIndex, u32, Balance),
+		/// An account claimed its share of an airdrop
+		Claimed(AirdropIndex, AccountId, Balance),
+		/// An ended airdrop was removed, and its unclaimed funds went to the treasury
+		Swept(AirdropIndex, Balance),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		const MaxAllocations: u32 = T::MaxAllocations::get();
+
+		const MaxProofLength: u32 = T::MaxProofLength::get();
+
+		/// Create an airdrop of `amount`, which can be claimed for `duration` blocks. Without a
+		/// `root`, the caller allocates the funds to accounts afterwards.
+		#[weight = SimpleDispatchInfo::FixedNormal(T::WeightInfo::create())]
+		fn create(
+			origin,
+			amount: BalanceOf<T>,
+			duration: T::BlockNumber,
+			root: Option<T::Hash>,
+		) -> DispatchResult {
+			let admin = ensure_signed(origin)?;
+			ensure!(!amount.is_zero(), "nothing to airdrop");
+			ensure!(!duration.is_zero(), "claim window is empty");
+			let index = Self::airdrop_count();
+			let next_index = index.checked_add(1).ok_or("too many airdrops")?;
+
+			T::Currency::transfer(&admin, &Self::account_id(), amount, AllowDeath)?;
+
+			let ends = <system::Module<T>>::block_number().saturating_add(duration);
+			let unallocated = if root.is_some() { Zero::zero() } else { amount };
+			let airdrop = Airdrop {
+				admin: admin.clone(),
+				remaining: amount,
+				unallocated,
+				ends,
+				root,
+				entries: 0,
+			};
+			<Airdrops<T>>::insert(index, airdrop);
+			AirdropCount::put(next_index);
+
+			Self::deposit_event(RawEvent::Created(index, admin, amount, ends));
+			Ok(())
+		}
+
+		/// Allocate some of the airdrop's funds to accounts that have no allocation yet
+		#[weight = SimpleDispatchInfo::FixedNormal(
+			T::WeightInfo::allocate(T::MaxAllocations::get())
+		)]
+		fn allocate(
+			origin,
+			index: AirdropIndex,
+			allocations: Vec<(T::AccountId, BalanceOf<T>)>,
+		) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let mut airdrop = Self::airdrop(index).ok_or("no such airdrop")?;
+			ensure!(who == airdrop.admin, "only the admin can allocate");
+			ensure!(airdrop.root.is_none(), "airdrop uses a Merkle root");
+			Self::ensure_open(&airdrop)?;
+			let count = allocations.len() as u32;
+			ensure!(count <= T::MaxAllocations::get(), "too many allocations");
+
+			let mut accounts: Vec<_> = allocations.iter().map(|(account, _)| account).collect();
+			accounts.sort();
+			accounts.dedup();
+			ensure!(accounts.len() == allocations.len(), "duplicate account");
+			ensure!(
+				accounts.iter().all(|account| !<Allocations<T>>::exists(index, *account)),
+				"account already allocated"
+			);
+
+			let total = allocations
+				.iter()
+				.try_fold(BalanceOf::<T>::zero(), |total, (_, amount)| total.checked_add(amount))
+				.ok_or("allocations overflow")?;
+			airdrop.unallocated = airdrop
+				.unallocated
+				.checked_sub(&total)
+				.ok_or("allocations exceed the airdrop")?;
+			airdrop.entries = airdrop.entries.saturating_add(count);
+
+			for (account, amount) in allocations {
+				<Allocations<T>>::insert(index, &account, amount);
+			}
+			<Airdrops<T>>::insert(index, airdrop);
+
+			Self::deposit_event(RawEvent::Allocated(index, count, total));
+			Ok(())
+		}
+
+		/// Claim the funds allocated to the caller
+		#[weight = SimpleDispatchInfo::FixedNormal(T::WeightInfo::claim())]
+		fn claim(origin, index: AirdropIndex) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let mut airdrop = Self::airdrop(index).ok_or("no such airdrop")?;
+			Self::ensure_open(&airdrop)?;
+			let amount = Self::allocation(index, &who).ok_or("nothing allocated")?;
+
+			T::Currency::transfer(&Self::account_id(), &who, amount, AllowDeath)?;
+
+			// Allocations never exceed the airdrop, so the remaining funds cover every one
+			airdrop.remaining = airdrop.remaining.saturating_sub(amount);
+			airdrop.entries = airdrop.entries.saturating_sub(1);
+			<Allocations<T>>::remove(index, &who);
+			<Airdrops<T>>::insert(index, airdrop);
+
+			Self::deposit_event(RawEvent::Claimed(index, who, amount));
+			Ok(())
+		}
+
+		/// Claim `amount` from an airdrop with a Merkle root, proving that the root commits to it
+		#[weight = SimpleDispatchInfo::FixedNormal(
+			T::WeightInfo::claim_with_proof(T::MaxProofLength::get())
+		)]
+		fn claim_with_proof(
+			origin,
+			index: AirdropIndex,
+			amount: BalanceOf<T>,
+			proof: Vec<T::Hash>,
+		) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let mut airdrop = Self::airdrop(index).ok_or("no such airdrop")?;
+			let root = airdrop.root.ok_or("airdrop stores its allocations")?;
+			Self::ensure_open(&airdrop)?;
+			ensure!(proof.len() as u32 <= T::MaxProofLength::get(), "proof too long");
+			ensure!(!Self::claimed(index, &who), "already claimed");
+			ensure!(Self::root_of(&who, amount, &proof) == root, "invalid proof");
+			// The tree is built off chain, and may promise more than the airdrop holds
+			airdrop.remaining = airdrop.remaining.checked_sub(&amount).ok_or("airdrop exhausted")?;
+
+			T::Currency::transfer(&Self::account_id(), &who, amount, AllowDeath)?;
+
+			airdrop.entries = airdrop.entries.saturating_add(1);
+			<Claimed<T>>::insert(index, &who, true);
+			<Airdrops<T>>::insert(index, airdrop);
+
+			Self::deposit_event(RawEvent::Claimed(index, who, amount));
+			Ok(())
+		}
+
+		/// Remove an ended airdrop and everything it left in storage, and hand its unclaimed
+		/// funds to the treasury
+		#[weight = SweepWeight::<T>(PhantomData)]
+		fn sweep(origin, index: AirdropIndex) -> DispatchResult {
+			ensure_signed(origin)?;
+			let airdrop = Self::airdrop(index).ok_or("no such airdrop")?;
+			let now = <system::Module<T>>::block_number();
+			ensure!(now >= airdrop.ends, "claim window still open");
+
+			if !airdrop.remaining.is_zero() {
+				let unclaimed = T::Currency::withdraw(
+					&Self::account_id(),
+					airdrop.remaining,
+					WithdrawReason::Transfer.into(),
+					AllowDeath,
+				)?;
+				T::Treasury::on_unbalanced(unclaimed);
+			}
+
+			<Allocations<T>>::remove_prefix(&index);
+			<Claimed<T>>::remove_prefix(&index);
+			<Airdrops<T>>::remove(index);
+
+			Self::deposit_event(RawEvent::Swept(index, airdrop.remaining));
+			Ok(())
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// The account that holds the funds of every airdrop
+	pub fn account_id() -> T::AccountId {
+		PALLET_ID.into_account()
+	}
+
+	/// The leaf of an airdrop's Merkle tree that gives `amount` to `who`
+	pub fn leaf_of(who: &T::AccountId, amount: BalanceOf<T>) -> T::Hash {
+		T::Hashing::hash_of(&(who, amount))
+	}
+
+	/// The node above two nodes of a Merkle tree, which doesn't depend on their order
+	pub fn parent_of(a: T::Hash, b: T::Hash) -> T::Hash {
+		if a <= b {
+			T::Hashing::hash_of(&(a, b))
+		} else {
+			T::Hashing::hash_of(&(b, a))
+		}
+	}
+
+	/// The root reached by combining the leaf for `who` and `amount` with each hash of `proof`
+	pub fn root_of(who: &T::AccountId, amount: BalanceOf<T>, proof: &[T::Hash]) -> T::Hash {
+		proof
+			.iter()
+			.fold(Self::leaf_of(who, amount), |node, sibling| Self::parent_of(node, *sibling))
+	}
+
+	fn ensure_open(airdrop: &AirdropOf<T>) -> DispatchResult {
+		let now = <system::Module<T>>::block_number();
+		ensure!(now < airdrop.ends, "claim window closed");
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use event_assert::assert_last_event;
+	use sp_core::H256;
+	use frame_support::{
+		assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types,
+		weights::GetDispatchInfo,
+	};
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup},
+		Perbill,
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	const TREASURY: u64 = 99;
+
+	/// Pays unclaimed funds into the `TREASURY` account
+	pub struct TestTreasury;
+	impl OnUnbalanced<NegativeImbalanceOf<TestRuntime>> for TestTreasury {
+		fn on_unbalanced(unclaimed: NegativeImbalanceOf<TestRuntime>) {
+			Balances::resolve_creating(&TREASURY, unclaimed);
+		}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const ExistentialDeposit: u64 = 0;
+		pub const TransferFee: u64 = 0;
+		pub const CreationFee: u64 = 0;
+
+		pub const MaxAllocations: u32 = 3;
+		pub const MaxProofLength: u32 = 2;
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	impl balances::Trait for TestRuntime {
+		type Balance = u64;
+		type OnFreeBalanceZero = ();
+		type OnNewAccount = ();
+		type Event = TestEvent;
+		type TransferPayment = ();
+		type DustRemoval = ();
+		type ExistentialDeposit = ExistentialDeposit;
+		type TransferFee = TransferFee;
+		type CreationFee = CreationFee;
+	}
+
+	mod airdrop {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			balances<T>,
+			airdrop<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type Currency = balances::Module<Self>;
+		type Treasury = TestTreasury;
+		type MaxAllocations = MaxAllocations;
+		type MaxProofLength = MaxProofLength;
+		type WeightInfo = ();
+	}
+
+	pub type System = system::Module<TestRuntime>;
+	pub type Balances = balances::Module<TestRuntime>;
+	pub type Airdrop = Module<TestRuntime>;
+
+	/// Account 1 has 1000 and creates every airdrop. The tests start in block 1.
+	fn new_test_ext() -> sp_io::TestExternalities {
+		let mut t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		balances::GenesisConfig::<TestRuntime> {
+			balances: vec![(1, 1_000)],
+			vesting: vec![],
+		}
+		.assimilate_storage(&mut t)
+		.unwrap();
+		let mut ext: sp_io::TestExternalities = t.into();
+		ext.execute_with(|| System::set_block_number(1));
+		ext
+	}
+
+	/// The leaves of the tree in `merkle_root`, in order
+	const TREE: [(u64, u64); 4] = [(2, 10), (3, 20), (4, 30), (5, 40)];
+
+	fn leaf(index: usize) -> H256 {
+		let (who, amount) = TREE[index];
+		Airdrop::leaf_of(&who, amount)
+	}
+
+	fn merkle_root() -> H256 {
+		Airdrop::parent_of(
+			Airdrop::parent_of(leaf(0), leaf(1)),
+			Airdrop::parent_of(leaf(2), leaf(3)),
+		)
+	}
+
+	/// An airdrop of 100 that ends in block 11, with allocations of 30 to 2 and 50 to 3
+	fn allocated_airdrop() -> AirdropIndex {
+		let index = Airdrop::airdrop_count();
+		assert_ok!(Airdrop::create(Origin::signed(1), 100, 10, None));
+		assert_ok!(Airdrop::allocate(Origin::signed(1), index, vec![(2, 30), (3, 50)]));
+		index
+	}
+
+	#[test]
+	fn allocations_can_be_claimed() {
+		new_test_ext().execute_with(|| {
+			let index = allocated_airdrop();
+			assert_eq!(Balances::free_balance(&Airdrop::account_id()), 100);
+			assert_eq!(Airdrop::airdrop(index).unwrap().unallocated, 20);
+
+			assert_ok!(Airdrop::claim(Origin::signed(2), index));
+			assert_last_event::<TestRuntime>(RawEvent::Claimed(index, 2, 30));
+			assert_eq!(Balances::free_balance(&2), 30);
+			assert_eq!(Airdrop::allocation(index, &2), None);
+			assert_err!(Airdrop::claim(Origin::signed(2), index), "nothing allocated");
+			assert_err!(Airdrop::claim(Origin::signed(4), index), "nothing allocated");
+
+			let airdrop = Airdrop::airdrop(index).unwrap();
+			assert_eq!(airdrop.remaining, 70);
+			assert_eq!(airdrop.entries, 1);
+		})
+	}
+
+	#[test]
+	fn only_the_admin_allocates_what_the_airdrop_holds() {
+		new_test_ext().execute_with(|| {
+			let index = allocated_airdrop();
+			assert_err!(
+				Airdrop::allocate(Origin::signed(2), index, vec![(4, 10)]),
+				"only the admin can allocate"
+			);
+			assert_err!(
+				Airdrop::allocate(Origin::signed(1), index, vec![(4, 10), (4, 10)]),
+				"duplicate account"
+			);
+			assert_err!(
+				Airdrop::allocate(Origin::signed(1), index, vec![(3, 10)]),
+				"account already allocated"
+			);
+			assert_err!(
+				Airdrop::allocate(Origin::signed(1), index, vec![(4, 10), (5, 11)]),
+				"allocations exceed the airdrop"
+			);
+			assert_err!(
+				Airdrop::allocate(Origin::signed(1), index, vec![(4, 1), (5, 1), (6, 1), (7, 1)]),
+				"too many allocations"
+			);
+
+			assert_ok!(Airdrop::allocate(Origin::signed(1), index, vec![(4, 10), (5, 10)]));
+			assert_last_event::<TestRuntime>(RawEvent::Allocated(index, 2, 20));
+			assert_eq!(Airdrop::airdrop(index).unwrap().unallocated, 0);
+		})
+	}
+
+	#[test]
+	fn merkle_proofs_can_be_claimed_once() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(Airdrop::create(Origin::signed(1), 100, 10, Some(merkle_root())));
+			assert_err!(
+				Airdrop::allocate(Origin::signed(1), 0, vec![(2, 10)]),
+				"airdrop uses a Merkle root"
+			);
+			assert_err!(Airdrop::claim(Origin::signed(3), 0), "nothing allocated");
+
+			let proof = vec![leaf(0), Airdrop::parent_of(leaf(2), leaf(3))];
+			assert_err!(
+				Airdrop::claim_with_proof(Origin::signed(3), 0, 25, proof.clone()),
+				"invalid proof"
+			);
+			assert_err!(
+				Airdrop::claim_with_proof(Origin::signed(4), 0, 20, proof.clone()),
+				"invalid proof"
+			);
+			assert_ok!(Airdrop::claim_with_proof(Origin::signed(3), 0, 20, proof.clone()));
+			assert_last_event::<TestRuntime>(RawEvent::Claimed(0, 3, 20));
+			assert_eq!(Balances::free_balance(&3), 20);
+			assert!(Airdrop::claimed(0, &3));
+			assert_err!(
+				Airdrop::claim_with_proof(Origin::signed(3), 0, 20, proof),
+				"already claimed"
+			);
+
+			let proof = vec![leaf(2), Airdrop::parent_of(leaf(0), leaf(1))];
+			assert_ok!(Airdrop::claim_with_proof(Origin::signed(5), 0, 40, proof));
+			let airdrop = Airdrop::airdrop(0).unwrap();
+			assert_eq!(airdrop.remaining, 40);
+			assert_eq!(airdrop.entries, 2);
+		})
+	}
+
+	#[test]
+	fn merkle_claims_are_bounded() {
+		new_test_ext().execute_with(|| {
+			// The tree promises 100 in total, more than the airdrop holds
+			assert_ok!(Airdrop::create(Origin::signed(1), 50, 10, Some(merkle_root())));
+			let proof = vec![leaf(2), Airdrop::parent_of(leaf(0), leaf(1))];
+			assert_ok!(Airdrop::claim_with_proof(Origin::signed(5), 0, 40, proof));
+			let proof = vec![leaf(3), Airdrop::parent_of(leaf(0), leaf(1))];
+			assert_err!(
+				Airdrop::claim_with_proof(Origin::signed(4), 0, 30, proof),
+				"airdrop exhausted"
+			);
+
+			let proof = vec![leaf(0), leaf(1), leaf(2)];
+			assert_err!(
+				Airdrop::claim_with_proof(Origin::signed(2), 0, 10, proof),
+				"proof too long"
+			);
+		})
+	}
+
+	#[test]
+	fn unclaimed_funds_are_swept_to_the_treasury() {
+		new_test_ext().execute_with(|| {
+			let index = allocated_airdrop();
+			assert_ok!(Airdrop::claim(Origin::signed(2), index));
+			assert_err!(Airdrop::sweep(Origin::signed(4), index), "claim window still open");
+
+			System::set_block_number(11);
+			assert_err!(Airdrop::claim(Origin::signed(3), index), "claim window closed");
+			assert_err!(
+				Airdrop::allocate(Origin::signed(1), index, vec![(4, 10)]),
+				"claim window closed"
+			);
+
+			assert_ok!(Airdrop::sweep(Origin::signed(4), index));
+			assert_last_event::<TestRuntime>(RawEvent::Swept(index, 70));
+			assert_eq!(Balances::free_balance(&TREASURY), 70);
+			assert_eq!(Balances::free_balance(&Airdrop::account_id()), 0);
+			assert_eq!(Airdrop::airdrop(index), None);
+			assert_eq!(Airdrop::allocation(index, &3), None);
+			assert_err!(Airdrop::sweep(Origin::signed(4), index), "no such airdrop");
+		})
+	}
+
+	#[test]
+	fn sweeping_leaves_other_airdrops_alone() {
+		new_test_ext().execute_with(|| {
+			let first = allocated_airdrop();
+			System::set_block_number(5);
+			let second = allocated_airdrop();
+
+			System::set_block_number(11);
+			assert_ok!(Airdrop::sweep(Origin::signed(4), first));
+			assert_eq!(Balances::free_balance(&Airdrop::account_id()), 100);
+			assert_eq!(Airdrop::allocation(second, &3), Some(50));
+			assert_ok!(Airdrop::claim(Origin::signed(3), second));
+		})
+	}
+
+	#[test]
+	fn sweep_weight_depends_on_entries() {
+		new_test_ext().execute_with(|| {
+			let index = allocated_airdrop();
+			let weight = Call::<TestRuntime>::sweep(index).get_dispatch_info().weight;
+			assert_eq!(weight, <() as WeightInfo>::sweep(2));
+
+			assert_ok!(Airdrop::claim(Origin::signed(2), index));
+			let weight = Call::<TestRuntime>::sweep(index).get_dispatch_info().weight;
+			assert_eq!(weight, <() as WeightInfo>::sweep(1));
+		})
+	}
+}
@@ -0,0 +1,65 @@
+//! Weights for the airdrop pallet
+//!
+//! Loading allocations and checking a Merkle proof grow with the number of allocations and the
+//! length of the proof, and both are bounded by constants. Sweeping removes every entry the
+//! airdrop left in storage, so its weight is a function of the entry count, which is stored with
+//! the airdrop and read when the call is weighed.
+
+use sp_std::marker::PhantomData;
+use frame_support::weights::Weight;
+
+/// Weight functions needed by the airdrop pallet
+pub trait WeightInfo {
+    fn create() -> Weight;
+    fn allocate(allocations: u32) -> Weight;
+    fn claim() -> Weight;
+    fn claim_with_proof(proof_length: u32) -> Weight;
+    fn sweep(entries: u32) -> Weight;
+}
+
+/// Hand-picked estimates of the airdrop pallet's weights
+pub struct EstimatedWeight<T>(PhantomData<T>);
+impl<T: frame_system::Trait> WeightInfo for EstimatedWeight<T> {
+    // a transfer into the pot, and writes of the airdrop and the airdrop count
+    fn create() -> Weight {
+        45_000
+    }
+    // Fixed: a read and a write of the airdrop, and sorting the accounts to find duplicates.
+    // Per allocation: a read and a write of the allocation.
+    fn allocate(allocations: u32) -> Weight {
+        20_000u32.saturating_add(10_000u32.saturating_mul(allocations))
+    }
+    // reads and writes of the airdrop and the allocation, and a transfer from the pot
+    fn claim() -> Weight {
+        50_000
+    }
+    // Fixed: reads and writes of the airdrop and the claimed flag, and a transfer from the pot.
+    // Per proof item: one hash.
+    fn claim_with_proof(proof_length: u32) -> Weight {
+        50_000u32.saturating_add(2_000u32.saturating_mul(proof_length))
+    }
+    // Fixed: a read and a removal of the airdrop, and a withdrawal from the pot.
+    // Per entry: the removal of an allocation or a claimed flag.
+    fn sweep(entries: u32) -> Weight {
+        40_000u32.saturating_add(10_000u32.saturating_mul(entries))
+    }
+}
+
+// For backwards compatibility and tests
+impl WeightInfo for () {
+    fn create() -> Weight {
+        10_000
+    }
+    fn allocate(allocations: u32) -> Weight {
+        10_000u32.saturating_add(100u32.saturating_mul(allocations))
+    }
+    fn claim() -> Weight {
+        10_000
+    }
+    fn claim_with_proof(proof_length: u32) -> Weight {
+        10_000u32.saturating_add(100u32.saturating_mul(proof_length))
+    }
+    fn sweep(entries: u32) -> Weight {
+        10_000u32.saturating_add(1_000u32.saturating_mul(entries))
+    }
+}
@@ -0,0 +1,516 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! A constant-product automated market maker, in the style of Uniswap v1
+//!
+//! A single pool holds reserves of a base and a quote currency in the pallet's account. A swap
+//! of `x` into the pool pays out `y` such that the product of the reserves, `k`, does not
+//! decrease. A fraction `Fee` of every input stays in the pool, so `k` grows with each swap and
+//! liquidity providers earn the fees.
+//!
+//! Liquidity providers own shares of the pool. Depositing adds to both reserves in their current
+//! ratio and mints shares; withdrawing burns shares for the same fraction of both reserves. All
+//! rounding favours the pool.
+
+use sp_std::convert::TryInto;
+use sp_runtime::{
+	traits::{AccountIdConversion, CheckedAdd, CheckedSub, One, Zero},
+	ModuleId, Permill,
+};
+use frame_support::{
+	decl_event, decl_module, decl_storage,
+	dispatch::{DispatchError, DispatchResult},
+	ensure,
+	traits::{Currency, ExistenceRequirement::AllowDeath, Get},
+};
+use frame_system::{self as system, ensure_signed};
+
+type BalanceOf<T> = <<T as Trait>::Base as Currency<<T as system::Trait>::AccountId>>::Balance;
+
+/// Hardcoded pallet ID; used to create the account that holds the pool's reserves
+/// Must be exactly 8 characters long
+const PALLET_ID: ModuleId = ModuleId(*b"cpmm/pol");
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// One of the two currencies in the pool
+	type Base: Currency<Self::AccountId>;
+
+	/// The other currency in the pool
+	type Quote: Currency<Self::AccountId, Balance = BalanceOf<Self>>;
+
+	/// The part of every swap's input that is kept by the pool
+	type Fee: Get<Permill>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as Amm {
+		/// Pool shares held by each liquidity provider
+		Shares get(fn shares): map T::AccountId => BalanceOf<T>;
+
+		/// The sum of all shares. Zero means that there is no pool.
+		TotalShares get(fn total_shares): BalanceOf<T>;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as system::Trait>::AccountId,
+		Balance = BalanceOf<T>,
+	{
+		/// A provider deposited base and quote currency and received shares
+		LiquidityAdded(AccountId, Balance, Balance, Balance),
+		/// A provider burned shares and withdrew base and quote currency
+		LiquidityRemoved(AccountId, Balance, Balance, Balance),
+		/// Base currency was swapped for quote currency: amount in, amount out
+		SoldBase(AccountId, Balance, Balance),
+		/// Quote currency was swapped for base currency: amount in, amount out
+		SoldQuote(AccountId, Balance, Balance),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		const Fee: Permill = T::Fee::get();
+
+		/// Create the pool with its first liquidity, which sets the initial price. The creator
+		/// receives as many shares as base currency deposited.
+		fn create_pool(origin, base: BalanceOf<T>, quote: BalanceOf<T>) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			ensure!(Self::total_shares().is_zero(), "pool already exists");
+			ensure!(!base.is_zero() && !quote.is_zero(), "pool needs both currencies");
+
+			let shares = Self::minted(&who, base)?;
+			Self::deposit(&who, base, quote)?;
+			Self::mint(&who, shares);
+
+			Self::deposit_event(RawEvent::LiquidityAdded(who, base, quote, base));
+			Ok(())
+		}
+
+		/// Deposit `base` and the matching amount of quote currency, but no more than
+		/// `max_quote`, in exchange for shares
+		fn add_liquidity(origin, base: BalanceOf<T>, max_quote: BalanceOf<T>) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let total = Self::total_shares();
+			ensure!(!total.is_zero(), "no pool");
+			ensure!(!base.is_zero(), "must deposit something");
+
+			let (base_reserve, quote_reserve) = Self::reserves();
+			// Round the deposit up and the shares down
+			let quote = Self::mul_div(base, quote_reserve, base_reserve)?
+				.checked_add(&One::one())
+				.ok_or("arithmetic overflow")?;
+			ensure!(quote <= max_quote, "quote deposit above limit");
+			let minted = Self::mul_div(base, total, base_reserve)?;
+			ensure!(!minted.is_zero(), "deposit too small");
+			let shares = Self::minted(&who, minted)?;
+
+			Self::deposit(&who, base, quote)?;
+			Self::mint(&who, shares);
+
+			Self::deposit_event(RawEvent::LiquidityAdded(who, base, quote, minted));
+			Ok(())
+		}
+
+		/// Burn `shares` for the same fraction of both reserves
+		fn remove_liquidity(
+			origin,
+			shares: BalanceOf<T>,
+			min_base: BalanceOf<T>,
+			min_quote: BalanceOf<T>
+		) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let remaining = Self::shares(&who).checked_sub(&shares).ok_or("not enough shares")?;
+			ensure!(!shares.is_zero(), "must withdraw something");
+
+			let total = Self::total_shares();
+			let (base_reserve, quote_reserve) = Self::reserves();
+			let base = Self::mul_div(shares, base_reserve, total)?;
+			let quote = Self::mul_div(shares, quote_reserve, total)?;
+			ensure!(base >= min_base && quote >= min_quote, "withdrawal below limit");
+
+			let pool = Self::account_id();
+			T::Base::transfer(&pool, &who, base, AllowDeath)?;
+			T::Quote::transfer(&pool, &who, quote, AllowDeath)?;
+			<Shares<T>>::insert(&who, remaining);
+			<TotalShares<T>>::put(total - shares);
+
+			Self::deposit_event(RawEvent::LiquidityRemoved(who, base, quote, shares));
+			Ok(())
+		}
+
+		/// Swap exactly `amount` of base currency for at least `min_out` of quote currency
+		fn sell_base(origin, amount: BalanceOf<T>, min_out: BalanceOf<T>) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let (base_reserve, quote_reserve) = Self::reserves();
+			let out = Self::output(amount, base_reserve, quote_reserve)?;
+			ensure!(out >= min_out, "output below limit");
+
+			let pool = Self::account_id();
+			T::Base::transfer(&who, &pool, amount, AllowDeath)?;
+			T::Quote::transfer(&pool, &who, out, AllowDeath)?;
+
+			Self::deposit_event(RawEvent::SoldBase(who, amount, out));
+			Ok(())
+		}
+
+		/// Swap exactly `amount` of quote currency for at least `min_out` of base currency
+		fn sell_quote(origin, amount: BalanceOf<T>, min_out: BalanceOf<T>) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let (base_reserve, quote_reserve) = Self::reserves();
+			let out = Self::output(amount, quote_reserve, base_reserve)?;
+			ensure!(out >= min_out, "output below limit");
+
+			let pool = Self::account_id();
+			T::Quote::transfer(&who, &pool, amount, AllowDeath)?;
+			T::Base::transfer(&pool, &who, out, AllowDeath)?;
+
+			Self::deposit_event(RawEvent::SoldQuote(who, amount, out));
+			Ok(())
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// The account that holds the pool's reserves
+	pub fn account_id() -> T::AccountId {
+		PALLET_ID.into_account()
+	}
+
+	/// `a * b / c` rounded down, computed in `u128`. Fails if a value or the result does not fit.
+	fn mul_div(
+		a: BalanceOf<T>,
+		b: BalanceOf<T>,
+		c: BalanceOf<T>,
+	) -> Result<BalanceOf<T>, DispatchError> {
+		let a: u128 = a.try_into().map_err(|_| "arithmetic overflow")?;
+		let b: u128 = b.try_into().map_err(|_| "arithmetic overflow")?;
+		let c: u128 = c.try_into().map_err(|_| "arithmetic overflow")?;
+		let product = a.checked_mul(b).ok_or("arithmetic overflow")?;
+		let quotient = product.checked_div(c).ok_or("empty pool")?;
+		Ok(quotient.try_into().map_err(|_| "arithmetic overflow")?)
+	}
+
+	/// The pool's base and quote reserves
+	pub fn reserves() -> (BalanceOf<T>, BalanceOf<T>) {
+		let pool = Self::account_id();
+		(T::Base::free_balance(&pool), T::Quote::free_balance(&pool))
+	}
+
+	/// The amount paid out for `amount` paid in, so that the product of the reserves after the
+	/// fee is deducted stays the same. Rounding down keeps the remainder in the pool.
+	pub fn output(
+		amount: BalanceOf<T>,
+		reserve_in: BalanceOf<T>,
+		reserve_out: BalanceOf<T>,
+	) -> Result<BalanceOf<T>, DispatchError> {
+		ensure!(!Self::total_shares().is_zero(), "no pool");
+		ensure!(!amount.is_zero(), "must swap something");
+		let after_fee = amount - T::Fee::get() * amount;
+		let reserve_after = reserve_in.checked_add(&after_fee).ok_or("arithmetic overflow")?;
+		let out = Self::mul_div(after_fee, reserve_out, reserve_after)?;
+		ensure!(!out.is_zero(), "swap too small");
+		Ok(out)
+	}
+
+	/// Move both parts of a deposit into the pool, or neither
+	fn deposit(who: &T::AccountId, base: BalanceOf<T>, quote: BalanceOf<T>) -> DispatchResult {
+		ensure!(T::Quote::free_balance(who) >= quote, "not enough quote currency");
+		let pool = Self::account_id();
+		T::Base::transfer(who, &pool, base, AllowDeath)?;
+		T::Quote::transfer(who, &pool, quote, AllowDeath)
+	}
+
+	/// The shares of `who` and the total after minting `shares`, checked before anything moves
+	fn minted(
+		who: &T::AccountId,
+		shares: BalanceOf<T>,
+	) -> Result<(BalanceOf<T>, BalanceOf<T>), DispatchError> {
+		let held = Self::shares(who).checked_add(&shares).ok_or("arithmetic overflow")?;
+		let total = Self::total_shares().checked_add(&shares).ok_or("arithmetic overflow")?;
+		Ok((held, total))
+	}
+
+	fn mint(who: &T::AccountId, (held, total): (BalanceOf<T>, BalanceOf<T>)) {
+		<Shares<T>>::insert(who, held);
+		<TotalShares<T>>::put(total);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use rand::{rngs::StdRng, Rng, SeedableRng};
+	use sp_core::H256;
+	use frame_support::{
+		assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types
+	};
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup},
+		Perbill,
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const ExistentialDeposit: u64 = 0;
+		pub const TransferFee: u64 = 0;
+		pub const CreationFee: u64 = 0;
+
+		pub const Fee: Permill = Permill::from_percent(3);
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	impl balances::Trait for TestRuntime {
+		type Balance = u64;
+		type OnFreeBalanceZero = ();
+		type OnNewAccount = ();
+		type Event = TestEvent;
+		type TransferPayment = ();
+		type DustRemoval = ();
+		type ExistentialDeposit = ExistentialDeposit;
+		type TransferFee = TransferFee;
+		type CreationFee = CreationFee;
+	}
+
+	// A second instance of balances serves as the quote currency
+	impl balances::Trait<balances::Instance1> for TestRuntime {
+		type Balance = u64;
+		type OnFreeBalanceZero = ();
+		type OnNewAccount = ();
+		type Event = ();
+		type TransferPayment = ();
+		type DustRemoval = ();
+		type ExistentialDeposit = ExistentialDeposit;
+		type TransferFee = TransferFee;
+		type CreationFee = CreationFee;
+	}
+
+	mod amm {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			balances<T>,
+			amm<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type Base = Base;
+		type Quote = Quote;
+		type Fee = Fee;
+	}
+
+	pub type System = system::Module<TestRuntime>;
+	pub type Base = balances::Module<TestRuntime>;
+	pub type Quote = balances::Module<TestRuntime, balances::Instance1>;
+	pub type Amm = Module<TestRuntime>;
+
+	/// Accounts 1 to 4 have 1_000_000 of each currency
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		let mut t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		let endowed: Vec<(u64, u64)> = (1..=4).map(|who| (who, 1_000_000)).collect();
+		balances::GenesisConfig::<TestRuntime> {
+			balances: endowed.clone(),
+			vesting: vec![],
+		}
+		.assimilate_storage(&mut t)
+		.unwrap();
+		balances::GenesisConfig::<TestRuntime, balances::Instance1> {
+			balances: endowed,
+			vesting: vec![],
+		}
+		.assimilate_storage(&mut t)
+		.unwrap();
+		t.into()
+	}
+
+	fn event_emitted(event: RawEvent<u64, u64>) -> bool {
+		let expected = TestEvent::amm(event);
+		System::events().iter().any(|a| a.event == expected)
+	}
+
+	fn k() -> u128 {
+		let (base, quote) = Amm::reserves();
+		base as u128 * quote as u128
+	}
+
+	#[test]
+	fn create_pool_mints_shares() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(Amm::create_pool(Origin::signed(1), 1000, 4000));
+			assert_eq!(Amm::reserves(), (1000, 4000));
+			assert_eq!(Amm::shares(&1), 1000);
+			assert_eq!(Amm::total_shares(), 1000);
+			assert!(event_emitted(RawEvent::LiquidityAdded(1, 1000, 4000, 1000)));
+
+			assert_err!(Amm::create_pool(Origin::signed(2), 1000, 4000), "pool already exists");
+		})
+	}
+
+	#[test]
+	fn add_liquidity_keeps_ratio() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(Amm::create_pool(Origin::signed(1), 1000, 4000));
+			assert_err!(Amm::add_liquidity(Origin::signed(2), 500, 2000), "quote deposit above limit");
+
+			assert_ok!(Amm::add_liquidity(Origin::signed(2), 500, 2001));
+			assert_eq!(Amm::reserves(), (1500, 6001));
+			assert_eq!(Amm::shares(&2), 500);
+			assert_eq!(Amm::total_shares(), 1500);
+		})
+	}
+
+	#[test]
+	fn remove_liquidity_returns_share_of_reserves() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(Amm::create_pool(Origin::signed(1), 1000, 4000));
+			assert_ok!(Amm::add_liquidity(Origin::signed(2), 500, 2001));
+
+			assert_err!(
+				Amm::remove_liquidity(Origin::signed(2), 501, 0, 0),
+				"not enough shares"
+			);
+			assert_err!(
+				Amm::remove_liquidity(Origin::signed(2), 500, 0, 2001),
+				"withdrawal below limit"
+			);
+			assert_ok!(Amm::remove_liquidity(Origin::signed(2), 500, 500, 2000));
+			assert_eq!(Base::free_balance(&2), 1_000_000);
+			assert_eq!(Quote::free_balance(&2), 999_999);
+			assert_eq!(Amm::total_shares(), 1000);
+		})
+	}
+
+	#[test]
+	fn swap_charges_fee() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(Amm::create_pool(Origin::signed(1), 1000, 4000));
+			// 100 in, 97 after the fee: 97 * 4000 / 1097 = 353.69...
+			assert_err!(Amm::sell_base(Origin::signed(2), 100, 354), "output below limit");
+			assert_ok!(Amm::sell_base(Origin::signed(2), 100, 353));
+			assert_eq!(Amm::reserves(), (1100, 3647));
+			assert!(event_emitted(RawEvent::SoldBase(2, 100, 353)));
+
+			// A round trip loses the fee twice
+			assert_ok!(Amm::sell_quote(Origin::signed(2), 353, 0));
+			assert!(Base::free_balance(&2) < 1_000_000);
+		})
+	}
+
+	#[test]
+	fn swaps_need_a_pool() {
+		new_test_ext().execute_with(|| {
+			assert_err!(Amm::sell_base(Origin::signed(2), 100, 0), "no pool");
+			assert_err!(Amm::add_liquidity(Origin::signed(2), 100, 100), "no pool");
+			assert_ok!(Amm::create_pool(Origin::signed(1), 1000, 4000));
+			assert_err!(Amm::sell_quote(Origin::signed(2), 0, 0), "must swap something");
+			assert_err!(Amm::sell_quote(Origin::signed(2), 1, 0), "swap too small");
+		})
+	}
+
+	#[test]
+	fn overflow_is_an_error() {
+		new_test_ext().execute_with(|| {
+			assert_eq!(Amm::mul_div(u64::max_value(), 2, 2), Ok(u64::max_value()));
+			assert_eq!(Amm::mul_div(u64::max_value(), 3, 2), Err("arithmetic overflow".into()));
+
+			<TotalShares<TestRuntime>>::put(1);
+			assert_eq!(Amm::output(100, u64::max_value(), 1), Err("arithmetic overflow".into()));
+
+			<TotalShares<TestRuntime>>::put(u64::max_value());
+			assert_eq!(Amm::minted(&1, 1), Err("arithmetic overflow".into()));
+		})
+	}
+
+	#[test]
+	fn k_never_decreases() {
+		new_test_ext().execute_with(|| {
+			let mut rng = StdRng::seed_from_u64(42);
+			assert_ok!(Amm::create_pool(Origin::signed(1), 100_000, 300_000));
+
+			for _ in 0..500 {
+				let before = k();
+				let who = rng.gen_range(2, 5);
+				let amount = rng.gen_range(1, 20_000);
+				let result = if rng.gen() {
+					Amm::sell_base(Origin::signed(who), amount, 0)
+				} else {
+					Amm::sell_quote(Origin::signed(who), amount, 0)
+				};
+				if result.is_ok() {
+					assert!(k() >= before, "swap of {} decreased k", amount);
+				}
+			}
+		})
+	}
+
+	#[test]
+	fn k_per_share_never_decreases() {
+		new_test_ext().execute_with(|| {
+			let mut rng = StdRng::seed_from_u64(7);
+			assert_ok!(Amm::create_pool(Origin::signed(1), 100_000, 300_000));
+
+			// k / shares² is each share's claim on the pool; compare by cross-multiplying
+			let per_share = || (k(), Amm::total_shares() as u128);
+			for _ in 0..500 {
+				let (k_before, shares_before) = per_share();
+				let who = rng.gen_range(1, 5);
+				let amount = rng.gen_range(1, 20_000);
+				let _ = match rng.gen_range(0, 3) {
+					0 => Amm::sell_base(Origin::signed(who), amount, 0),
+					1 => Amm::add_liquidity(Origin::signed(who), amount, u64::max_value()),
+					_ => {
+						let shares = Amm::shares(&who).min(amount);
+						Amm::remove_liquidity(Origin::signed(who), shares, 0, 0)
+					}
+				};
+				let (k_after, shares_after) = per_share();
+				if shares_after > 0 {
+					assert!(
+						k_after * shares_before * shares_before >= k_before * shares_after * shares_after
+					);
+				}
+			}
+		})
+	}
+}
@@ -0,0 +1,338 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! Block author whitelisting for permissioned proof of authority chains
+//!
+//! On a permissioned chain, only known parties author blocks. This pallet keeps the list of them:
+//! each approved author is an account, which governance answers to, together with the key the
+//! author signs blocks with. `ManageOrigin` approves and revokes authors.
+//!
+//! The pallet does not author anything itself. Whenever the list changed during a block, it hands
+//! the new list to `OnAuthorsChanged` when the block is finalized. `AuraAuthorities` makes the list
+//! Aura's authority set, so a change takes effect from the next block on. The pallet refuses to
+//! revoke the last author, which would stall the chain.
+
+use sp_std::{marker::PhantomData, prelude::*};
+use sp_runtime::traits::{MaybeSerializeDeserialize, Member};
+use frame_support::{
+	decl_event, decl_module, decl_storage,
+	dispatch::DispatchResult,
+	ensure,
+	traits::{EnsureOrigin, Get},
+	Parameter, StorageValue,
+};
+use frame_system::{self as system};
+use pallet_session::OneSessionHandler;
+
+/// Something that follows the approved authors
+pub trait OnAuthorsChanged<AccountId, AuthorityId> {
+	/// The approved authors changed to `authors`, in the order of approval
+	fn on_authors_changed(authors: &[(AccountId, AuthorityId)]);
+}
+
+impl<AccountId, AuthorityId> OnAuthorsChanged<AccountId, AuthorityId> for () {
+	fn on_authors_changed(_authors: &[(AccountId, AuthorityId)]) {}
+}
+
+/// Makes the approved authors Aura's authorities
+///
+/// Aura only changes its authorities when `pallet_session` tells it about a new session. This
+/// adapter tells it the same way, without a session pallet in the runtime.
+pub struct AuraAuthorities<T>(PhantomData<T>);
+
+impl<T: pallet_aura::Trait> OnAuthorsChanged<T::AccountId, <T as pallet_aura::Trait>::AuthorityId>
+	for AuraAuthorities<T>
+{
+	fn on_authors_changed(authors: &[(T::AccountId, <T as pallet_aura::Trait>::AuthorityId)]) {
+		let keys = || authors.iter().map(|(who, key)| (who, key.clone()));
+		<pallet_aura::Module<T> as OneSessionHandler<T::AccountId>>::on_new_session(
+			true,
+			keys(),
+			keys(),
+		);
+	}
+}
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The key authors sign blocks with
+	type AuthorityId: Member + Parameter + MaybeSerializeDeserialize;
+
+	/// The origin that approves and revokes authors
+	type ManageOrigin: EnsureOrigin<Self::Origin>;
+
+	/// The maximum number of approved authors
+	type MaxAuthors: Get<u32>;
+
+	/// What to tell about changes of the approved authors
+	type OnAuthorsChanged: OnAuthorsChanged<Self::AccountId, Self::AuthorityId>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as ApprovedAuthors {
+		/// The approved authors and their keys, in the order of approval. The genesis config must
+		/// list the same keys as the consensus engine's genesis authorities.
+		Authors get(fn authors) config(): Vec<(T::AccountId, T::AuthorityId)>;
+
+		/// Whether the authors changed during the current block
+		Changed: bool;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as system::Trait>::AccountId,
+		AuthorityId = <T as Trait>::AuthorityId,
+	{
+		/// An account was approved to author blocks with a key
+		AuthorApproved(AccountId, AuthorityId),
+		/// An account may no longer author blocks
+		AuthorRevoked(AccountId),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		/// The maximum number of approved authors
+		const MaxAuthors: u32 = T::MaxAuthors::get();
+
+		/// Approve `who` to author blocks, signed with `key`
+		fn approve(origin, who: T::AccountId, key: T::AuthorityId) -> DispatchResult {
+			T::ManageOrigin::ensure_origin(origin)?;
+			let mut authors = Self::authors();
+			ensure!(!authors.iter().any(|(author, _)| *author == who), "already approved");
+			ensure!(!authors.iter().any(|(_, used)| *used == key), "key already in use");
+			ensure!((authors.len() as u32) < T::MaxAuthors::get(), "too many authors");
+
+			authors.push((who.clone(), key.clone()));
+			<Authors<T>>::put(authors);
+			Changed::put(true);
+			Self::deposit_event(RawEvent::AuthorApproved(who, key));
+			Ok(())
+		}
+
+		/// Revoke the approval of `who`
+		fn revoke(origin, who: T::AccountId) -> DispatchResult {
+			T::ManageOrigin::ensure_origin(origin)?;
+			let mut authors = Self::authors();
+			let index = authors.iter().position(|(author, _)| *author == who)
+				.ok_or("not an approved author")?;
+			ensure!(authors.len() > 1, "cannot revoke the last author");
+
+			authors.remove(index);
+			<Authors<T>>::put(authors);
+			Changed::put(true);
+			Self::deposit_event(RawEvent::AuthorRevoked(who));
+			Ok(())
+		}
+
+		fn on_finalize() {
+			if Changed::take() {
+				T::OnAuthorsChanged::on_authors_changed(&Self::authors());
+			}
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// Whether `who` is an approved author
+	pub fn is_approved(who: &T::AccountId) -> bool {
+		Self::authors().iter().any(|(author, _)| author == who)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use event_assert::assert_last_event;
+
+	use sp_core::H256;
+	use frame_support::{
+		assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types,
+		traits::OnFinalize,
+	};
+	use frame_system::{EnsureRoot, RawOrigin};
+	use sp_runtime::{
+		testing::{Header, UintAuthorityId},
+		traits::{BlakeTwo256, IdentityLookup},
+		Perbill,
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	parameter_types! {
+		pub const MinimumPeriod: u64 = 1;
+	}
+	impl pallet_timestamp::Trait for TestRuntime {
+		type Moment = u64;
+		type OnTimestampSet = ();
+		type MinimumPeriod = MinimumPeriod;
+	}
+
+	impl pallet_aura::Trait for TestRuntime {
+		type AuthorityId = UintAuthorityId;
+	}
+
+	mod approved_authors {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			approved_authors<T>,
+		}
+	}
+
+	parameter_types! {
+		pub const MaxAuthors: u32 = 3;
+	}
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type AuthorityId = UintAuthorityId;
+		type ManageOrigin = EnsureRoot<u64>;
+		type MaxAuthors = MaxAuthors;
+		type OnAuthorsChanged = AuraAuthorities<TestRuntime>;
+	}
+
+	pub type System = system::Module<TestRuntime>;
+	pub type Aura = pallet_aura::Module<TestRuntime>;
+	pub type ApprovedAuthors = Module<TestRuntime>;
+
+	/// Account 1 authors with key 1, in both this pallet and Aura
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		let mut t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		GenesisConfig::<TestRuntime> {
+			authors: vec![(1, UintAuthorityId(1))],
+		}
+		.assimilate_storage(&mut t)
+		.unwrap();
+		pallet_aura::GenesisConfig::<TestRuntime> {
+			authorities: vec![UintAuthorityId(1)],
+		}
+		.assimilate_storage(&mut t)
+		.unwrap();
+		t.into()
+	}
+
+	fn approve(who: u64, key: u64) -> DispatchResult {
+		ApprovedAuthors::approve(RawOrigin::Root.into(), who, UintAuthorityId(key))
+	}
+
+	fn finalize() {
+		ApprovedAuthors::on_finalize(System::block_number());
+	}
+
+	fn aura_keys() -> Vec<u64> {
+		Aura::authorities().into_iter().map(|key| key.0).collect()
+	}
+
+	#[test]
+	fn approved_authors_become_aura_authorities() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(approve(2, 20));
+			assert_ok!(approve(3, 30));
+			assert!(ApprovedAuthors::is_approved(&2));
+			// Aura only learns about the new authors at the end of the block
+			assert_eq!(aura_keys(), vec![1]);
+
+			finalize();
+			assert_eq!(aura_keys(), vec![1, 20, 30]);
+		})
+	}
+
+	#[test]
+	fn revoked_authors_leave_aura() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(approve(2, 20));
+			finalize();
+			assert_ok!(ApprovedAuthors::revoke(RawOrigin::Root.into(), 1));
+			finalize();
+
+			assert!(!ApprovedAuthors::is_approved(&1));
+			assert_eq!(aura_keys(), vec![20]);
+			assert_last_event::<TestRuntime>(RawEvent::AuthorRevoked(1));
+		})
+	}
+
+	#[test]
+	fn aura_is_only_told_about_changes() {
+		new_test_ext().execute_with(|| {
+			finalize();
+			assert!(System::digest().logs.is_empty());
+
+			assert_ok!(approve(2, 20));
+			finalize();
+			assert_eq!(System::digest().logs.len(), 1);
+		})
+	}
+
+	#[test]
+	fn only_manage_origin_changes_authors() {
+		new_test_ext().execute_with(|| {
+			assert!(ApprovedAuthors::approve(Origin::signed(1), 2, UintAuthorityId(20)).is_err());
+			assert!(ApprovedAuthors::revoke(Origin::signed(1), 1).is_err());
+			assert_eq!(ApprovedAuthors::authors(), vec![(1, UintAuthorityId(1))]);
+		})
+	}
+
+	#[test]
+	fn authors_are_unique_and_bounded() {
+		new_test_ext().execute_with(|| {
+			assert_err!(approve(1, 10), "already approved");
+			assert_err!(approve(2, 1), "key already in use");
+			assert_ok!(approve(2, 20));
+			assert_ok!(approve(3, 30));
+			assert_err!(approve(4, 40), "too many authors");
+		})
+	}
+
+	#[test]
+	fn last_author_stays() {
+		new_test_ext().execute_with(|| {
+			assert_err!(
+				ApprovedAuthors::revoke(RawOrigin::Root.into(), 2),
+				"not an approved author"
+			);
+			assert_err!(
+				ApprovedAuthors::revoke(RawOrigin::Root.into(), 1),
+				"cannot revoke the last author"
+			);
+		})
+	}
+}
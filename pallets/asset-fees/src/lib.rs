@@ -0,0 +1,364 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! Paying fees in an asset
+//!
+//! Fees are normally paid in the chain's native currency. This pallet lets accounts pay them in the
+//! token of the basic-token pallet instead, at a conversion rate kept in storage and set by root.
+//!
+//! Fees are charged by a signed extension. `ChargeAsset` takes the place of
+//! `transaction_payment::ChargeTransactionPayment` in the runtime's `SignedExtra` and computes the
+//! fee the same way. If a rate is set and the signer has enough tokens, the fee is converted and
+//! paid in tokens to the `AssetFeeCollector`. Otherwise it falls back to the native currency.
+
+use codec::{Decode, Encode};
+use sp_runtime::{
+	traits::{SaturatedConversion, SignedExtension, Zero},
+	transaction_validity::{
+		InvalidTransaction, TransactionPriority, TransactionValidity, TransactionValidityError,
+		ValidTransaction,
+	},
+};
+use frame_support::{
+	decl_event, decl_module, decl_storage,
+	dispatch::DispatchResult,
+	ensure,
+	storage::StorageMap,
+	traits::{Currency, ExistenceRequirement, Get, OnUnbalanced, WithdrawReason},
+	weights::DispatchInfo,
+};
+use frame_system::{self as system, ensure_root};
+use transaction_payment::ChargeTransactionPayment;
+
+type BalanceOf<T> = <<T as transaction_payment::Trait>::Currency as Currency<
+	<T as system::Trait>::AccountId,
+>>::Balance;
+
+pub trait Trait: system::Trait + basic_token::Trait + transaction_payment::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The account that receives fees paid in tokens
+	type AssetFeeCollector: Get<Self::AccountId>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as AssetFees {
+		/// How many tokens (first) pay for how much native currency (second). Fees cannot be paid
+		/// in tokens while this is `None`.
+		Rate get(fn rate): Option<(u64, BalanceOf<T>)>;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		Balance = BalanceOf<T>,
+	{
+		/// This many tokens now pay for this much native currency
+		RateChanged(u64, Balance),
+		/// Fees can no longer be paid in tokens
+		RateRemoved,
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		/// Set how many tokens pay for how much native currency
+		fn set_rate(origin, tokens: u64, native: BalanceOf<T>) -> DispatchResult {
+			ensure_root(origin)?;
+			ensure!(tokens > 0 && !native.is_zero(), "rate must not be zero");
+
+			<Rate<T>>::put((tokens, native));
+			Self::deposit_event(RawEvent::RateChanged(tokens, native));
+			Ok(())
+		}
+
+		/// Stop accepting fees in tokens
+		fn remove_rate(origin) -> DispatchResult {
+			ensure_root(origin)?;
+
+			<Rate<T>>::kill();
+			Self::deposit_event(RawEvent::RateRemoved);
+			Ok(())
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// The tokens that pay for `fee` in native currency, rounded up
+	pub fn token_cost(fee: BalanceOf<T>) -> Option<u64> {
+		let (tokens, native) = Self::rate()?;
+		let fee = fee.saturated_into::<u128>();
+		let native = native.saturated_into::<u128>();
+		let cost = fee.checked_mul(u128::from(tokens))?;
+		// Rounding up favours the collector, so that nobody pays less than the fee is worth
+		let cost = cost / native + if cost % native == 0 { 0 } else { 1 };
+		Some(cost.saturated_into())
+	}
+
+	/// Pay `fee` in tokens if possible, and return the tokens paid
+	fn pay_in_tokens(who: &T::AccountId, fee: BalanceOf<T>) -> Option<u64> {
+		let cost = Self::token_cost(fee)?;
+		let left = basic_token::Module::<T>::get_balance(who).checked_sub(cost)?;
+
+		basic_token::GetBalance::<T>::insert(who, left);
+		basic_token::GetBalance::<T>::mutate(T::AssetFeeCollector::get(), |balance| {
+			*balance = balance.saturating_add(cost)
+		});
+		Some(cost)
+	}
+}
+
+/// Charges transaction fees in tokens where possible, and in native currency otherwise
+#[derive(Encode, Decode, Clone, Eq, PartialEq)]
+pub struct ChargeAsset<T: Trait + Send + Sync>(#[codec(compact)] BalanceOf<T>);
+
+impl<T: Trait + Send + Sync> ChargeAsset<T> {
+	/// Pay `tip` on top of the fee
+	pub fn from(tip: BalanceOf<T>) -> Self {
+		Self(tip)
+	}
+}
+
+impl<T: Trait + Send + Sync> sp_std::fmt::Debug for ChargeAsset<T> {
+	#[cfg(feature = "std")]
+	fn fmt(&self, f: &mut sp_std::fmt::Formatter) -> sp_std::fmt::Result {
+		write!(f, "ChargeAsset<{:?}>", self.0)
+	}
+	#[cfg(not(feature = "std"))]
+	fn fmt(&self, _: &mut sp_std::fmt::Formatter) -> sp_std::fmt::Result {
+		Ok(())
+	}
+}
+
+impl<T: Trait + Send + Sync> SignedExtension for ChargeAsset<T> {
+	type AccountId = T::AccountId;
+	type Call = <T as system::Trait>::Call;
+	type AdditionalSigned = ();
+	type DispatchInfo = DispatchInfo;
+	type Pre = ();
+
+	fn additional_signed(&self) -> Result<(), TransactionValidityError> {
+		Ok(())
+	}
+
+	fn validate(
+		&self,
+		who: &Self::AccountId,
+		_call: &Self::Call,
+		info: Self::DispatchInfo,
+		len: usize,
+	) -> TransactionValidity {
+		// The fee in native currency, computed exactly as `ChargeTransactionPayment` does
+		let fee = ChargeTransactionPayment::<T>::compute_fee(len as u32, info, self.0);
+
+		if !fee.is_zero() && Module::<T>::pay_in_tokens(who, fee).is_none() {
+			let imbalance = T::Currency::withdraw(
+				who,
+				fee,
+				WithdrawReason::TransactionPayment | WithdrawReason::Tip,
+				ExistenceRequirement::KeepAlive,
+			)
+			.map_err(|_| InvalidTransaction::Payment)?;
+			T::OnTransactionPayment::on_unbalanced(imbalance);
+		}
+
+		let mut valid = ValidTransaction::default();
+		valid.priority = fee.saturated_into::<TransactionPriority>();
+		Ok(valid)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use event_assert::assert_last_event;
+	use sp_core::H256;
+	use frame_support::{
+		assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types,
+		weights::DispatchClass,
+	};
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, ConvertInto, IdentityLookup},
+		Perbill,
+	};
+	use system::RawOrigin;
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const ExistentialDeposit: u64 = 1;
+		pub const TransferFee: u64 = 0;
+		pub const CreationFee: u64 = 0;
+
+		pub const TransactionBaseFee: u64 = 5;
+		pub const TransactionByteFee: u64 = 1;
+
+		pub const AssetFeeCollector: u64 = 99;
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	impl balances::Trait for TestRuntime {
+		type Balance = u64;
+		type OnFreeBalanceZero = ();
+		type OnNewAccount = ();
+		type Event = TestEvent;
+		type TransferPayment = ();
+		type DustRemoval = ();
+		type ExistentialDeposit = ExistentialDeposit;
+		type TransferFee = TransferFee;
+		type CreationFee = CreationFee;
+	}
+
+	impl transaction_payment::Trait for TestRuntime {
+		type Currency = Balances;
+		type OnTransactionPayment = ();
+		type TransactionBaseFee = TransactionBaseFee;
+		type TransactionByteFee = TransactionByteFee;
+		type WeightToFee = ConvertInto;
+		type FeeMultiplierUpdate = ();
+	}
+
+	impl basic_token::Trait for TestRuntime {
+		type Event = TestEvent;
+		type TransferFilter = ();
+	}
+
+	mod asset_fees {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			balances<T>,
+			basic_token<T>,
+			asset_fees<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type AssetFeeCollector = AssetFeeCollector;
+	}
+
+	pub type Balances = balances::Module<TestRuntime>;
+	pub type BasicToken = basic_token::Module<TestRuntime>;
+	pub type AssetFees = Module<TestRuntime>;
+
+	/// Accounts 1 and 2 have 100 native currency. Account 1 has all the tokens, and gives 50 to
+	/// account 2.
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		let mut t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		balances::GenesisConfig::<TestRuntime> {
+			balances: vec![(1, 100), (2, 100)],
+			vesting: vec![],
+		}
+		.assimilate_storage(&mut t)
+		.unwrap();
+		let mut ext: sp_io::TestExternalities = t.into();
+		ext.execute_with(|| {
+			assert_ok!(BasicToken::init(Origin::signed(1)));
+			assert_ok!(BasicToken::transfer(Origin::signed(1), 2, 50));
+		});
+		ext
+	}
+
+	/// Charge a transaction signed by `who` with a fee of 5 (base) + 10 (length) + 5 (weight) = 20
+	fn charge(who: u64) -> TransactionValidity {
+		let info = DispatchInfo { weight: 5, class: DispatchClass::Normal, pays_fee: true };
+		ChargeAsset::<TestRuntime>::from(0).validate(&who, &(), info, 10)
+	}
+
+	#[test]
+	fn native_currency_without_rate() {
+		new_test_ext().execute_with(|| {
+			assert!(charge(2).is_ok());
+			assert_eq!(Balances::free_balance(&2), 80);
+			assert_eq!(BasicToken::get_balance(2), 50);
+		})
+	}
+
+	#[test]
+	fn tokens_at_stored_rate() {
+		new_test_ext().execute_with(|| {
+			// 3 tokens pay for 2 native, so a fee of 20 costs 30 tokens
+			assert_ok!(AssetFees::set_rate(RawOrigin::Root.into(), 3, 2));
+			assert_last_event::<TestRuntime>(RawEvent::RateChanged(3, 2));
+
+			assert!(charge(2).is_ok());
+			assert_eq!(Balances::free_balance(&2), 100);
+			assert_eq!(BasicToken::get_balance(2), 20);
+			assert_eq!(BasicToken::get_balance(99), 30);
+		})
+	}
+
+	#[test]
+	fn falls_back_to_native_currency() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(AssetFees::set_rate(RawOrigin::Root.into(), 3, 2));
+			assert!(charge(2).is_ok());
+
+			// 20 tokens are not enough for another fee
+			for native_left in &[80, 60, 40, 20] {
+				assert!(charge(2).is_ok());
+				assert_eq!(Balances::free_balance(&2), *native_left);
+			}
+			assert_eq!(BasicToken::get_balance(2), 20);
+
+			// Paying 20 more would not leave the existential deposit
+			let payment: TransactionValidity = InvalidTransaction::Payment.into();
+			assert_eq!(charge(2), payment);
+
+			// Without a rate, tokens are never used
+			assert_ok!(AssetFees::remove_rate(RawOrigin::Root.into()));
+			assert!(charge(1).is_ok());
+			assert_eq!(Balances::free_balance(&1), 80);
+		})
+	}
+
+	#[test]
+	fn costs_round_up() {
+		new_test_ext().execute_with(|| {
+			assert_eq!(AssetFees::token_cost(20), None);
+			assert_ok!(AssetFees::set_rate(RawOrigin::Root.into(), 1, 3));
+			assert_eq!(AssetFees::token_cost(20), Some(7));
+			assert_eq!(AssetFees::token_cost(21), Some(7));
+
+			assert_err!(AssetFees::set_rate(RawOrigin::Root.into(), 0, 3), "rate must not be zero");
+			assert!(AssetFees::set_rate(Origin::signed(1), 1, 1).is_err());
+		})
+	}
+}
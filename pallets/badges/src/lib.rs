@@ -0,0 +1,242 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! Badges that accounts earn and can't transfer
+//!
+//! A badge records that an account reached some milestone, such as winning a game. Badges are
+//! soulbound: there is no call to transfer one, so a badge says something about the account that
+//! holds it, not about whoever could afford to buy it. A holder can only `renounce` a badge.
+//!
+//! This pallet doesn't know what the milestones are. Other pallets report them through the
+//! `BadgeIssuer` trait, which they ask for in their `Trait`. A runtime hands the same `Module` to
+//! every pallet that should award badges, or `()` to those that should not. Each pallet names its
+//! badges with its own `BadgeId`s, so many pallets can feed this one without knowing about each
+//! other.
+
+use frame_support::{decl_event, decl_module, decl_storage, dispatch::DispatchResult, ensure};
+use frame_system::{self as system, ensure_signed};
+
+/// Names a badge. Like a `ModuleId`, it is exactly 8 bytes long, and each pallet picks its own.
+pub type BadgeId = [u8; 8];
+
+/// Something that awards badges for milestones that other pallets report
+pub trait BadgeIssuer<AccountId> {
+	/// Award `badge` to `who`. Returns false if `who` already had it.
+	fn issue(who: &AccountId, badge: BadgeId) -> bool;
+
+	/// Whether `who` holds `badge`
+	fn has_badge(who: &AccountId, badge: &BadgeId) -> bool;
+}
+
+impl<AccountId> BadgeIssuer<AccountId> for () {
+	fn issue(_who: &AccountId, _badge: BadgeId) -> bool {
+		false
+	}
+
+	fn has_badge(_who: &AccountId, _badge: &BadgeId) -> bool {
+		false
+	}
+}
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as Badges {
+		/// The block in which each account earned each of its badges
+		EarnedAt get(fn earned_at):
+			double_map T::AccountId, blake2_256(BadgeId) => Option<T::BlockNumber>;
+
+		/// The number of badges each account holds
+		BadgeCount get(fn badge_count): map T::AccountId => u32;
+
+		/// The number of accounts that hold each badge
+		Holders get(fn holders): map BadgeId => u32;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as system::Trait>::AccountId,
+	{
+		/// An account earned a badge
+		Awarded(AccountId, BadgeId),
+		/// An account gave up a badge
+		Renounced(AccountId, BadgeId),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		/// Give up a badge. It can be earned again.
+		fn renounce(origin, badge: BadgeId) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			ensure!(<EarnedAt<T>>::exists(&who, &badge), "badge not held");
+
+			<EarnedAt<T>>::remove(&who, &badge);
+			<BadgeCount<T>>::mutate(&who, |count| *count -= 1);
+			Holders::mutate(&badge, |holders| *holders -= 1);
+
+			Self::deposit_event(RawEvent::Renounced(who, badge));
+			Ok(())
+		}
+	}
+}
+
+impl<T: Trait> BadgeIssuer<T::AccountId> for Module<T> {
+	fn issue(who: &T::AccountId, badge: BadgeId) -> bool {
+		if <EarnedAt<T>>::exists(who, &badge) {
+			return false;
+		}
+		<EarnedAt<T>>::insert(who, &badge, <system::Module<T>>::block_number());
+		<BadgeCount<T>>::mutate(who, |count| *count = count.saturating_add(1));
+		Holders::mutate(&badge, |holders| *holders = holders.saturating_add(1));
+
+		Self::deposit_event(RawEvent::Awarded(who.clone(), badge));
+		true
+	}
+
+	fn has_badge(who: &T::AccountId, badge: &BadgeId) -> bool {
+		<EarnedAt<T>>::exists(who, badge)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use event_assert::assert_last_event;
+	use frame_support::{
+		assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types,
+	};
+	use sp_core::H256;
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup},
+		Perbill,
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	mod badges {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			badges<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+	}
+
+	pub type System = system::Module<TestRuntime>;
+	pub type Badges = Module<TestRuntime>;
+
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		let mut ext: sp_io::TestExternalities = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap()
+			.into();
+		ext.execute_with(|| System::set_block_number(1));
+		ext
+	}
+
+	const FIRST_WIN: BadgeId = *b"firstwin";
+	const VETERAN: BadgeId = *b"veteran ";
+
+	#[test]
+	fn badges_are_awarded_once() {
+		new_test_ext().execute_with(|| {
+			System::set_block_number(5);
+			assert!(Badges::issue(&1, FIRST_WIN));
+			assert_last_event::<TestRuntime>(RawEvent::Awarded(1, FIRST_WIN));
+			assert!(Badges::has_badge(&1, &FIRST_WIN));
+			assert_eq!(Badges::earned_at(&1, &FIRST_WIN), Some(5));
+
+			// A second award changes nothing, not even the block it was earned in
+			System::set_block_number(6);
+			assert!(!Badges::issue(&1, FIRST_WIN));
+			assert_eq!(Badges::earned_at(&1, &FIRST_WIN), Some(5));
+			assert_eq!(Badges::badge_count(&1), 1);
+			assert_eq!(Badges::holders(&FIRST_WIN), 1);
+		})
+	}
+
+	#[test]
+	fn badges_are_counted_per_account_and_badge() {
+		new_test_ext().execute_with(|| {
+			Badges::issue(&1, FIRST_WIN);
+			Badges::issue(&1, VETERAN);
+			Badges::issue(&2, FIRST_WIN);
+
+			assert_eq!(Badges::badge_count(&1), 2);
+			assert_eq!(Badges::badge_count(&2), 1);
+			assert_eq!(Badges::holders(&FIRST_WIN), 2);
+			assert_eq!(Badges::holders(&VETERAN), 1);
+			assert!(!Badges::has_badge(&2, &VETERAN));
+		})
+	}
+
+	#[test]
+	fn holders_can_renounce_badges() {
+		new_test_ext().execute_with(|| {
+			assert_err!(Badges::renounce(Origin::signed(1), FIRST_WIN), "badge not held");
+			Badges::issue(&1, FIRST_WIN);
+
+			assert_ok!(Badges::renounce(Origin::signed(1), FIRST_WIN));
+			assert_last_event::<TestRuntime>(RawEvent::Renounced(1, FIRST_WIN));
+			assert!(!Badges::has_badge(&1, &FIRST_WIN));
+			assert_eq!(Badges::badge_count(&1), 0);
+			assert_eq!(Badges::holders(&FIRST_WIN), 0);
+
+			// A renounced badge can be earned again
+			assert!(Badges::issue(&1, FIRST_WIN));
+		})
+	}
+
+	#[test]
+	fn unit_awards_nothing() {
+		new_test_ext().execute_with(|| {
+			assert!(!<() as BadgeIssuer<u64>>::issue(&1, FIRST_WIN));
+			assert!(!<() as BadgeIssuer<u64>>::has_badge(&1, &FIRST_WIN));
+			assert_eq!(Badges::badge_count(&1), 0);
+		})
+	}
+}
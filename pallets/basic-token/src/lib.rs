@@ -9,9 +9,12 @@ use frame_support::{
     decl_event, decl_module, decl_storage, dispatch::DispatchResult, ensure, StorageMap, StorageValue,
 };
 use frame_system::{self as system, ensure_signed};
+use compliance::TransferFilter;
 
 pub trait Trait: system::Trait {
     type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+    // decides which transfers are allowed; `()` allows all of them
+    type TransferFilter: TransferFilter<Self::AccountId, u64>;
 }
 
 decl_storage! {
@@ -54,6 +57,7 @@ decl_module! {
         // transfer tokens from one account to another
         fn transfer(_origin, to: T::AccountId, value: u64) -> DispatchResult {
             let sender = ensure_signed(_origin)?;
+            T::TransferFilter::check_transfer(&sender, &to, &value)?;
             let sender_balance = Self::get_balance(sender.clone());
             ensure!(sender_balance >= value, "Not enough balance.");
 
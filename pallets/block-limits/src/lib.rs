@@ -0,0 +1,172 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! Block limits per dispatch class
+//!
+//! Every block has a maximum weight and a maximum length, set in `system::Trait`. Normal calls may
+//! only fill `AvailableBlockRatio` of each. The rest is headroom that only operational calls can
+//! use, so that a chain flooded with normal transactions can still be maintained.
+//!
+//! This pallet has one call of each class with the same weight. Its tests fill a block with them
+//! to show which class is turned away first.
+
+use sp_std::prelude::*;
+use frame_support::{
+	decl_module, decl_storage,
+	dispatch::DispatchResult,
+	weights::{SimpleDispatchInfo, Weight},
+};
+use frame_system::{self as system, ensure_root, ensure_signed};
+
+/// The weight of both calls
+pub const WORK_WEIGHT: Weight = 100_000;
+
+pub trait Trait: system::Trait {}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as BlockLimits {
+		/// The number of normal calls dispatched
+		Work get(fn work): u32;
+
+		/// The number of operational calls dispatched
+		Maintenance get(fn maintenance): u32;
+	}
+}
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		/// Some normal work. The payload only makes the transaction longer.
+		#[weight = SimpleDispatchInfo::FixedNormal(WORK_WEIGHT)]
+		fn work(origin, _payload: Vec<u8>) -> DispatchResult {
+			ensure_signed(origin)?;
+			Work::mutate(|n| *n = n.saturating_add(1));
+			Ok(())
+		}
+
+		/// The same work, but operational. Only root may call it.
+		#[weight = SimpleDispatchInfo::FixedOperational(WORK_WEIGHT)]
+		fn maintain(origin, _payload: Vec<u8>) -> DispatchResult {
+			ensure_root(origin)?;
+			Maintenance::mutate(|n| *n = n.saturating_add(1));
+			Ok(())
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use frame_support::{assert_ok, impl_outer_origin, parameter_types, weights::GetDispatchInfo};
+	use sp_core::H256;
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup, SignedExtension},
+		transaction_validity::{InvalidTransaction, TransactionValidityError},
+		Perbill,
+	};
+	use system::{CheckWeight, RawOrigin};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		// Room for ten calls, and 1000 bytes
+		pub const MaximumBlockWeight: u32 = 10 * WORK_WEIGHT;
+		pub const MaximumBlockLength: u32 = 1000;
+		// Normal calls may use 75% of both
+		pub const AvailableBlockRatio: Perbill = Perbill::from_percent(75);
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = ();
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	impl Trait for TestRuntime {}
+
+	pub type System = system::Module<TestRuntime>;
+	pub type BlockLimits = Module<TestRuntime>;
+
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		let t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		t.into()
+	}
+
+	/// Admit `call` into the current block as a transaction of `len` bytes, the way block
+	/// production does
+	fn admit(call: Call<TestRuntime>, len: usize) -> Result<(), TransactionValidityError> {
+		CheckWeight::<TestRuntime>::new().pre_dispatch(&1, &(), call.get_dispatch_info(), len)
+	}
+
+	fn exhausted() -> Result<(), TransactionValidityError> {
+		Err(InvalidTransaction::ExhaustsResources.into())
+	}
+
+	#[test]
+	fn normal_calls_are_rejected_first_by_weight() {
+		new_test_ext().execute_with(|| {
+			// Seven normal calls fit in 75% of the weight
+			for _ in 0..7 {
+				assert_ok!(admit(Call::work(vec![]), 0));
+			}
+			assert_eq!(admit(Call::work(vec![]), 0), exhausted());
+			assert_eq!(System::all_extrinsics_weight(), 7 * WORK_WEIGHT);
+
+			// Operational calls can use the headroom, up to the full weight
+			for _ in 0..3 {
+				assert_ok!(admit(Call::maintain(vec![]), 0));
+			}
+			assert_eq!(admit(Call::maintain(vec![]), 0), exhausted());
+			assert_eq!(System::all_extrinsics_weight(), 10 * WORK_WEIGHT);
+		})
+	}
+
+	#[test]
+	fn normal_calls_are_rejected_first_by_length() {
+		new_test_ext().execute_with(|| {
+			// Three normal calls of 200 bytes fit in 75% of the length, even though there is weight
+			// left for more
+			for _ in 0..3 {
+				assert_ok!(admit(Call::work(vec![]), 200));
+			}
+			assert_eq!(admit(Call::work(vec![]), 200), exhausted());
+			// A shorter one still fits
+			assert_ok!(admit(Call::work(vec![]), 150));
+
+			assert_ok!(admit(Call::maintain(vec![]), 200));
+			assert_eq!(admit(Call::maintain(vec![]), 200), exhausted());
+			assert_ok!(admit(Call::maintain(vec![]), 50));
+			assert_eq!(System::all_extrinsics_len(), 1000);
+		})
+	}
+
+	#[test]
+	fn calls_check_their_origin() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(BlockLimits::work(Origin::signed(1), vec![]));
+			assert!(BlockLimits::maintain(Origin::signed(1), vec![]).is_err());
+			assert_ok!(BlockLimits::maintain(RawOrigin::Root.into(), vec![]));
+			assert_eq!((BlockLimits::work(), BlockLimits::maintenance()), (1, 1));
+		})
+	}
+}
@@ -0,0 +1,299 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! Rewarding block authors
+//!
+//! Every block, this pallet asks the runtime's `FindAuthor` who built the block. It mints a fixed
+//! `RewardPerBlock` to that author in `on_finalize`. It also implements `OnUnbalanced` so it can be
+//! used as `pallet_transaction_payment`'s `OnTransactionPayment`: a `FeeShare` of every fee goes
+//! to the author, and the rest is handed to the `FeeRemainder` handler.
+//!
+//! Nothing here is specific to a consensus engine. Aura, Babe and proof of work differ only in
+//! how the author is found, which is the runtime's `FindAuthor` choice.
+
+use sp_runtime::Perbill;
+use frame_support::{
+	decl_event, decl_module, decl_storage,
+	traits::{Currency, FindAuthor, Get, Imbalance, OnUnbalanced},
+};
+use frame_system as system;
+
+type BalanceOf<T> = <<T as Trait>::Currency as Currency<<T as system::Trait>::AccountId>>::Balance;
+type NegativeImbalanceOf<T> =
+	<<T as Trait>::Currency as Currency<<T as system::Trait>::AccountId>>::NegativeImbalance;
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The currency in which rewards are paid
+	type Currency: Currency<Self::AccountId>;
+
+	/// Identifies the author of the current block from its pre-runtime digests
+	type FindAuthor: FindAuthor<Self::AccountId>;
+
+	/// Amount minted for the author of each block
+	type RewardPerBlock: Get<BalanceOf<Self>>;
+
+	/// Fraction of each transaction fee paid to the author
+	type FeeShare: Get<Perbill>;
+
+	/// Handler for the fees that don't go to the author
+	type FeeRemainder: OnUnbalanced<NegativeImbalanceOf<Self>>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as BlockReward {
+		/// The author of the current block. Only set while a block is being executed.
+		Author get(fn author): Option<T::AccountId>;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as system::Trait>::AccountId,
+		Balance = BalanceOf<T>,
+	{
+		/// An author was paid the block reward
+		BlockRewarded(AccountId, Balance),
+		/// An author was paid a share of a transaction fee
+		FeeShared(AccountId, Balance),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		const RewardPerBlock: BalanceOf<T> = T::RewardPerBlock::get();
+
+		const FeeShare: Perbill = T::FeeShare::get();
+
+		fn on_initialize(_n: T::BlockNumber) {
+			let digest = <system::Module<T>>::digest();
+			let pre_runtime_digests = digest.logs().iter().filter_map(|d| d.as_pre_runtime());
+			if let Some(author) = T::FindAuthor::find_author(pre_runtime_digests) {
+				<Author<T>>::put(author);
+			}
+		}
+
+		fn on_finalize(_n: T::BlockNumber) {
+			if let Some(author) = <Author<T>>::take() {
+				let reward = T::RewardPerBlock::get();
+				// Dropping the positive imbalance increases total issuance
+				let _ = T::Currency::deposit_creating(&author, reward);
+				Self::deposit_event(RawEvent::BlockRewarded(author, reward));
+			}
+		}
+	}
+}
+
+/// Splits transaction fees between the block author and `FeeRemainder`
+impl<T: Trait> OnUnbalanced<NegativeImbalanceOf<T>> for Module<T> {
+	fn on_unbalanced(fees: NegativeImbalanceOf<T>) {
+		match Self::author() {
+			Some(author) => {
+				let share = T::FeeShare::get() * fees.peek();
+				let (to_author, rest) = fees.split(share);
+				let paid = to_author.peek();
+				T::Currency::resolve_creating(&author, to_author);
+				T::FeeRemainder::on_unbalanced(rest);
+				Self::deposit_event(RawEvent::FeeShared(author, paid));
+			}
+			// Without a known author (e.g. fees paid in genesis), nobody gets a share
+			None => T::FeeRemainder::on_unbalanced(fees),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use std::cell::RefCell;
+	use sp_core::H256;
+	use frame_support::{
+		impl_outer_event, impl_outer_origin, parameter_types,
+		traits::{ExistenceRequirement, WithdrawReason},
+	};
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup, OnFinalize, OnInitialize},
+		ConsensusEngineId,
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	thread_local! {
+		static AUTHOR: RefCell<Option<u64>> = RefCell::new(None);
+	}
+
+	/// Pretends the author of every block is whoever `set_author` named last
+	pub struct TestAuthor;
+	impl FindAuthor<u64> for TestAuthor {
+		fn find_author<'a, I>(_digests: I) -> Option<u64>
+		where
+			I: 'a + IntoIterator<Item = (ConsensusEngineId, &'a [u8])>,
+		{
+			AUTHOR.with(|a| *a.borrow())
+		}
+	}
+
+	fn set_author(author: Option<u64>) {
+		AUTHOR.with(|a| *a.borrow_mut() = author);
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const ExistentialDeposit: u64 = 0;
+		pub const TransferFee: u64 = 0;
+		pub const CreationFee: u64 = 0;
+
+		pub const RewardPerBlock: u64 = 10;
+		pub const FeeShare: Perbill = Perbill::from_percent(20);
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	impl balances::Trait for TestRuntime {
+		type Balance = u64;
+		type OnFreeBalanceZero = ();
+		type OnNewAccount = ();
+		type Event = TestEvent;
+		type TransferPayment = ();
+		type DustRemoval = ();
+		type ExistentialDeposit = ExistentialDeposit;
+		type TransferFee = TransferFee;
+		type CreationFee = CreationFee;
+	}
+
+	mod block_reward {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			balances<T>,
+			block_reward<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type Currency = balances::Module<Self>;
+		type FindAuthor = TestAuthor;
+		type RewardPerBlock = RewardPerBlock;
+		type FeeShare = FeeShare;
+		// Dropping the imbalance burns it
+		type FeeRemainder = ();
+	}
+
+	pub type System = system::Module<TestRuntime>;
+	pub type Balances = balances::Module<TestRuntime>;
+	pub type BlockReward = Module<TestRuntime>;
+
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		set_author(None);
+		let mut t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		balances::GenesisConfig::<TestRuntime> {
+			balances: vec![(1, 100), (2, 100)],
+			vesting: vec![],
+		}
+		.assimilate_storage(&mut t)
+		.unwrap();
+		t.into()
+	}
+
+	/// Charge `who` a fee of `amount` and hand it to the pallet, as transaction payment would
+	fn pay_fee(who: u64, amount: u64) {
+		let fee = Balances::withdraw(
+			&who,
+			amount,
+			WithdrawReason::TransactionPayment.into(),
+			ExistenceRequirement::KeepAlive,
+		)
+		.expect("account can pay the fee");
+		BlockReward::on_unbalanced(fee);
+	}
+
+	#[test]
+	fn author_is_rewarded() {
+		new_test_ext().execute_with(|| {
+			set_author(Some(1));
+			BlockReward::on_initialize(1);
+			assert_eq!(BlockReward::author(), Some(1));
+
+			BlockReward::on_finalize(1);
+			assert_eq!(Balances::free_balance(&1), 110);
+			assert_eq!(BlockReward::author(), None);
+
+			let expected_event = TestEvent::block_reward(RawEvent::BlockRewarded(1, 10));
+			assert!(System::events().iter().any(|a| a.event == expected_event));
+		})
+	}
+
+	#[test]
+	fn no_author_no_reward() {
+		new_test_ext().execute_with(|| {
+			let issuance = Balances::total_issuance();
+			BlockReward::on_initialize(1);
+			BlockReward::on_finalize(1);
+			assert_eq!(Balances::total_issuance(), issuance);
+		})
+	}
+
+	#[test]
+	fn author_gets_fee_share() {
+		new_test_ext().execute_with(|| {
+			let issuance = Balances::total_issuance();
+			set_author(Some(1));
+			BlockReward::on_initialize(1);
+
+			pay_fee(2, 50);
+			// 20% of the fee goes to the author and the rest is burned
+			assert_eq!(Balances::free_balance(&1), 110);
+			assert_eq!(Balances::free_balance(&2), 50);
+			assert_eq!(Balances::total_issuance(), issuance - 40);
+
+			let expected_event = TestEvent::block_reward(RawEvent::FeeShared(1, 10));
+			assert!(System::events().iter().any(|a| a.event == expected_event));
+		})
+	}
+
+	#[test]
+	fn fees_without_author_go_to_remainder() {
+		new_test_ext().execute_with(|| {
+			let issuance = Balances::total_issuance();
+			pay_fee(2, 50);
+			assert_eq!(Balances::free_balance(&1), 100);
+			assert_eq!(Balances::total_issuance(), issuance - 50);
+		})
+	}
+}
@@ -0,0 +1,466 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! A token sold along a bonding curve, against the native currency
+//!
+//! The pallet mints its own token, and is the only place to get it. Anyone can `buy` tokens, and
+//! the price of the next token is set by the curve from how many tokens exist. Anyone can `sell`
+//! tokens back, which burns them and pays out along the same curve. What buyers pay is kept as the
+//! reserve, in the pallet's account, so every token in existence can always be sold.
+//!
+//! The price is a function `p(s)` of the supply `s`, and the cost of tokens is the area under
+//! it, `F(s + n) - F(s)` where `F` is the integral of `p`. Two curves are offered:
+//!
+//! ```text
+//! Linear:     p(s) = base + slope · s         F(s) = base · s + slope · s² / 2
+//! Polynomial: p(s) = factor · s^exponent      F(s) = factor · s^(exponent + 1) / (exponent + 1)
+//! ```
+//!
+//! Coefficients are fixed-point numbers with `PRECISION` parts per unit, so a slope can be a
+//! fraction of a unit. `F` is rounded down, but as every trade pays the difference `F` makes, the
+//! reserve is always exactly `F` of the supply, and buying in steps costs the same as buying at
+//! once. `CurveOrigin` can change the curve, as long as the reserve still covers the new `F` of
+//! the supply.
+
+use codec::{Decode, Encode};
+use sp_runtime::{
+	traits::{AccountIdConversion, CheckedAdd, SaturatedConversion, Zero},
+	ModuleId, RuntimeDebug,
+};
+use frame_support::{
+	decl_event, decl_module, decl_storage,
+	dispatch::DispatchResult,
+	ensure,
+	traits::{Currency, EnsureOrigin, ExistenceRequirement::AllowDeath},
+};
+use frame_system::{self as system, ensure_signed};
+
+type BalanceOf<T> = <<T as Trait>::Currency as Currency<<T as system::Trait>::AccountId>>::Balance;
+
+/// Hardcoded pallet ID; used to create the account that holds the reserve
+/// Must be exactly 8 characters long
+const PALLET_ID: ModuleId = ModuleId(*b"bondcurv");
+
+/// Curve coefficients have this many parts per unit of the currency
+pub const PRECISION: u128 = 1_000_000_000;
+
+/// The steepest polynomial curve. Higher powers overflow for modest supplies.
+pub const MAX_EXPONENT: u32 = 4;
+
+/// The price of the next token, as a function of the supply. Coefficients are in fixed point with
+/// `PRECISION` parts per unit of the currency.
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, RuntimeDebug)]
+pub enum Curve {
+	/// `base + slope · supply`
+	Linear { base: u128, slope: u128 },
+	/// `factor · supply^exponent`
+	Polynomial { factor: u128, exponent: u32 },
+}
+
+impl Curve {
+	/// The price of the next token at a supply, in fixed point
+	pub fn price(&self, supply: u128) -> Option<u128> {
+		match *self {
+			Curve::Linear { base, slope } => slope.checked_mul(supply)?.checked_add(base),
+			Curve::Polynomial { factor, exponent } =>
+				factor.checked_mul(supply.checked_pow(exponent)?),
+		}
+	}
+
+	/// The area under the curve up to a supply, `F(supply)`, rounded down to a whole unit. `None`
+	/// if it overflows.
+	pub fn integral(&self, supply: u128) -> Option<u128> {
+		let area = match *self {
+			Curve::Linear { base, slope } => {
+				let triangle = slope.checked_mul(supply)?.checked_mul(supply)? / 2;
+				base.checked_mul(supply)?.checked_add(triangle)?
+			}
+			Curve::Polynomial { factor, exponent } => {
+				let power = supply.checked_pow(exponent + 1)?;
+				factor.checked_mul(power)? / u128::from(exponent + 1)
+			}
+		};
+		Some(area / PRECISION)
+	}
+}
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The native currency that tokens are bought with
+	type Currency: Currency<Self::AccountId>;
+
+	/// Origin that sets the curve
+	type CurveOrigin: EnsureOrigin<Self::Origin>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as BondingCurve {
+		/// The curve tokens are sold along. Nothing can be bought until it is set.
+		ActiveCurve get(fn curve): Option<Curve>;
+
+		/// Tokens held by each account
+		Tokens get(fn tokens): map T::AccountId => BalanceOf<T>;
+
+		/// The sum of all tokens
+		TotalSupply get(fn total_supply): BalanceOf<T>;
+
+		/// The currency paid for the tokens in existence, held in the pallet's account
+		Reserve get(fn reserve): BalanceOf<T>;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as system::Trait>::AccountId,
+		Balance = BalanceOf<T>,
+	{
+		/// The curve was set
+		CurveSet(Curve),
+		/// Tokens were bought: tokens, cost
+		Bought(AccountId, Balance, Balance),
+		/// Tokens were sold: tokens, amount paid out
+		Sold(AccountId, Balance, Balance),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		const MaxExponent: u32 = MAX_EXPONENT;
+
+		/// Change the curve. The reserve must cover the new curve's area up to the current supply,
+		/// so that every token can still be sold along it.
+		fn set_curve(origin, curve: Curve) -> DispatchResult {
+			T::CurveOrigin::ensure_origin(origin)?;
+			if let Curve::Polynomial { exponent, .. } = curve {
+				ensure!(exponent <= MAX_EXPONENT, "exponent too large");
+			}
+			let supply = Self::total_supply().saturated_into();
+			let area = curve.integral(supply).ok_or("curve overflow")?;
+			let reserve: u128 = Self::reserve().saturated_into();
+			ensure!(area <= reserve, "reserve does not cover the curve");
+
+			ActiveCurve::put(curve);
+			Self::deposit_event(RawEvent::CurveSet(curve));
+			Ok(())
+		}
+
+		/// Mint `amount` tokens to the caller, paying at most `max_cost` for them
+		fn buy(origin, amount: BalanceOf<T>, max_cost: BalanceOf<T>) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let curve = Self::curve().ok_or("no curve")?;
+			ensure!(!amount.is_zero(), "must buy something");
+
+			let supply = Self::total_supply();
+			let new_supply = supply.checked_add(&amount).ok_or("supply overflow")?;
+			let before = curve.integral(supply.saturated_into()).ok_or("curve overflow")?;
+			let after = curve.integral(new_supply.saturated_into()).ok_or("curve overflow")?;
+			let cost = (after - before).saturated_into();
+			ensure!(cost <= max_cost, "cost exceeds limit");
+			T::Currency::transfer(&who, &Self::account_id(), cost, AllowDeath)?;
+
+			<Tokens<T>>::mutate(&who, |held| *held += amount);
+			<TotalSupply<T>>::put(new_supply);
+			<Reserve<T>>::mutate(|reserve| *reserve += cost);
+			Self::deposit_event(RawEvent::Bought(who, amount, cost));
+			Ok(())
+		}
+
+		/// Burn `amount` of the caller's tokens, receiving at least `min_return` for them
+		fn sell(origin, amount: BalanceOf<T>, min_return: BalanceOf<T>) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let curve = Self::curve().ok_or("no curve")?;
+			ensure!(!amount.is_zero(), "must sell something");
+			let held = Self::tokens(&who);
+			ensure!(held >= amount, "not enough tokens");
+
+			let supply = Self::total_supply();
+			let before = curve.integral(supply.saturated_into()).ok_or("curve overflow")?;
+			let after = curve.integral((supply - amount).saturated_into()).ok_or("curve overflow")?;
+			// After a curve change the reserve may hold more than the area, but never less
+			let reserve = Self::reserve();
+			let paid = reserve.min((before - after).saturated_into());
+			ensure!(paid >= min_return, "return below limit");
+			T::Currency::transfer(&Self::account_id(), &who, paid, AllowDeath)?;
+
+			<Tokens<T>>::insert(&who, held - amount);
+			<TotalSupply<T>>::put(supply - amount);
+			<Reserve<T>>::put(reserve - paid);
+			Self::deposit_event(RawEvent::Sold(who, amount, paid));
+			Ok(())
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// The account ID that holds the reserve
+	pub fn account_id() -> T::AccountId {
+		PALLET_ID.into_account()
+	}
+
+	/// The price of the next token, in fixed point with `PRECISION` parts per unit, if there is a
+	/// curve
+	pub fn spot_price() -> Option<u128> {
+		Self::curve()?.price(Self::total_supply().saturated_into())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use event_assert::assert_last_event;
+
+	use sp_core::H256;
+	use frame_support::{
+		assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types,
+	};
+	use frame_system::{EnsureRoot, RawOrigin};
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup},
+		Perbill,
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const ExistentialDeposit: u64 = 0;
+		pub const TransferFee: u64 = 0;
+		pub const CreationFee: u64 = 0;
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	impl balances::Trait for TestRuntime {
+		type Balance = u64;
+		type OnFreeBalanceZero = ();
+		type OnNewAccount = ();
+		type Event = TestEvent;
+		type TransferPayment = ();
+		type DustRemoval = ();
+		type ExistentialDeposit = ExistentialDeposit;
+		type TransferFee = TransferFee;
+		type CreationFee = CreationFee;
+	}
+
+	mod bonding_curve {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			balances<T>,
+			bonding_curve<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type Currency = balances::Module<Self>;
+		type CurveOrigin = EnsureRoot<u64>;
+	}
+
+	pub type Balances = balances::Module<TestRuntime>;
+	pub type BondingCurve = Module<TestRuntime>;
+
+	/// A price that starts at 1 and rises by 0.01 per token
+	const LINEAR: Curve = Curve::Linear { base: PRECISION, slope: PRECISION / 100 };
+
+	/// A price of 0.001 times the square of the supply
+	const QUADRATIC: Curve = Curve::Polynomial { factor: PRECISION / 1_000, exponent: 2 };
+
+	/// Accounts 1 to 3 have 100_000 each, and tokens are sold along `curve`
+	fn new_test_ext(curve: Curve) -> sp_io::TestExternalities {
+		let mut t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		balances::GenesisConfig::<TestRuntime> {
+			balances: (1..=3).map(|who| (who, 100_000)).collect(),
+			vesting: vec![],
+		}
+		.assimilate_storage(&mut t)
+		.unwrap();
+		let mut ext: sp_io::TestExternalities = t.into();
+		ext.execute_with(|| {
+			assert_ok!(BondingCurve::set_curve(RawOrigin::Root.into(), curve));
+		});
+		ext
+	}
+
+	fn buy(who: u64, amount: u64) -> u64 {
+		let before = Balances::free_balance(&who);
+		assert_ok!(BondingCurve::buy(Origin::signed(who), amount, u64::max_value()));
+		before - Balances::free_balance(&who)
+	}
+
+	fn sell(who: u64, amount: u64) -> u64 {
+		let before = Balances::free_balance(&who);
+		assert_ok!(BondingCurve::sell(Origin::signed(who), amount, 0));
+		Balances::free_balance(&who) - before
+	}
+
+	/// The reserve is what the pallet's account holds, and `F` of the supply
+	fn assert_backed() {
+		let reserve = BondingCurve::reserve();
+		assert_eq!(Balances::free_balance(&BondingCurve::account_id()), reserve);
+		let area = BondingCurve::curve().unwrap().integral(BondingCurve::total_supply().into());
+		assert!(area.unwrap() <= u128::from(reserve));
+	}
+
+	#[test]
+	fn integrals_agree_with_the_reference() {
+		for supply in &[0u128, 1, 7, 100, 12_345, 1_000_000] {
+			let s = *supply as f64;
+			let linear = s + 0.01 * s * s / 2.0;
+			let quadratic = 0.001 * s * s * s / 3.0;
+			for (curve, expected) in &[(LINEAR, linear), (QUADRATIC, quadratic)] {
+				let actual = curve.integral(*supply).unwrap() as f64;
+				// Rounded down, so at most a unit below
+				assert!(actual <= expected + expected / 1e12 && actual > expected - 1.0);
+			}
+		}
+		assert_eq!(LINEAR.price(100), Some(2 * PRECISION));
+		assert_eq!(QUADRATIC.price(100), Some(10 * PRECISION));
+		assert_eq!(QUADRATIC.price(0), Some(0));
+		let steep = Curve::Polynomial { factor: PRECISION, exponent: MAX_EXPONENT };
+		assert_eq!(steep.integral(u128::from(u64::max_value())), None);
+	}
+
+	#[test]
+	fn linear_price_path() {
+		new_test_ext(LINEAR).execute_with(|| {
+			assert_eq!(BondingCurve::spot_price(), Some(PRECISION));
+			// 100 + 0.01 · 100² / 2
+			assert_eq!(buy(1, 100), 150);
+			assert_last_event::<TestRuntime>(RawEvent::Bought(1, 100, 150));
+			assert_eq!(BondingCurve::spot_price(), Some(2 * PRECISION));
+			// The next 100 tokens cost more: 400 - 150
+			assert_eq!(buy(2, 100), 250);
+			assert_eq!(BondingCurve::spot_price(), Some(3 * PRECISION));
+			assert_eq!(BondingCurve::tokens(&1), 100);
+			assert_eq!(BondingCurve::total_supply(), 200);
+			assert_eq!(BondingCurve::reserve(), 400);
+			assert_backed();
+
+			// Selling walks back down the curve, so the first buyer profits from the second
+			assert_eq!(sell(1, 100), 250);
+			assert_last_event::<TestRuntime>(RawEvent::Sold(1, 100, 250));
+			assert_eq!(sell(2, 100), 150);
+			assert_eq!(Balances::free_balance(&1), 100_000 + 100);
+			assert_eq!(Balances::free_balance(&2), 100_000 - 100);
+			assert_eq!(BondingCurve::reserve(), 0);
+			assert_backed();
+		})
+	}
+
+	#[test]
+	fn polynomial_price_path() {
+		new_test_ext(QUADRATIC).execute_with(|| {
+			// 0.001 · 30³ / 3
+			assert_eq!(buy(1, 30), 9);
+			// 0.001 · 60³ / 3 - 9
+			assert_eq!(buy(1, 30), 63);
+			// 0.001 · 90³ / 3 - 72
+			assert_eq!(buy(1, 30), 171);
+			assert_eq!(BondingCurve::spot_price(), Some(8_100_000_000));
+			assert_backed();
+			assert_eq!(sell(1, 90), 243);
+			assert_eq!(Balances::free_balance(&1), 100_000);
+		})
+	}
+
+	#[test]
+	fn buying_in_steps_costs_the_same_as_at_once() {
+		let mut stepped = 0;
+		new_test_ext(QUADRATIC).execute_with(|| {
+			// Each step rounds down, but the rounding is in `F`, not in the steps
+			stepped = (0..77).map(|_| buy(1, 13)).sum::<u64>();
+			assert_backed();
+		});
+		new_test_ext(QUADRATIC).execute_with(|| {
+			assert_eq!(buy(1, 77 * 13), stepped);
+			assert_eq!(u128::from(stepped), QUADRATIC.integral(77 * 13).unwrap());
+		})
+	}
+
+	#[test]
+	fn trades_respect_their_limits() {
+		new_test_ext(LINEAR).execute_with(|| {
+			assert_err!(BondingCurve::buy(Origin::signed(1), 100, 149), "cost exceeds limit");
+			assert_ok!(BondingCurve::buy(Origin::signed(1), 100, 150));
+			assert_err!(BondingCurve::sell(Origin::signed(1), 100, 151), "return below limit");
+			assert_err!(BondingCurve::sell(Origin::signed(1), 101, 0), "not enough tokens");
+			assert_err!(BondingCurve::sell(Origin::signed(2), 1, 0), "not enough tokens");
+			assert_err!(BondingCurve::buy(Origin::signed(1), 0, 0), "must buy something");
+			assert_err!(BondingCurve::sell(Origin::signed(1), 0, 0), "must sell something");
+			assert_eq!(BondingCurve::tokens(&1), 100);
+			assert_backed();
+		})
+	}
+
+	#[test]
+	fn curve_changes_stay_backed() {
+		new_test_ext(LINEAR).execute_with(|| {
+			assert_eq!(buy(1, 100), 150);
+			assert!(BondingCurve::set_curve(Origin::signed(1), QUADRATIC).is_err());
+
+			// 0.001 · 100³ / 3 = 333 is more than the reserve of 150
+			assert_err!(
+				BondingCurve::set_curve(RawOrigin::Root.into(), QUADRATIC),
+				"reserve does not cover the curve"
+			);
+			let too_steep = Curve::Polynomial { factor: 1, exponent: MAX_EXPONENT + 1 };
+			assert_err!(
+				BondingCurve::set_curve(RawOrigin::Root.into(), too_steep),
+				"exponent too large"
+			);
+
+			// 0.0004 · 100³ / 3 = 133 is covered. The reserve keeps the other 17.
+			let gentle = Curve::Polynomial { factor: PRECISION / 2_500, exponent: 2 };
+			assert_ok!(BondingCurve::set_curve(RawOrigin::Root.into(), gentle));
+			assert_eq!(BondingCurve::curve(), Some(gentle));
+			assert_last_event::<TestRuntime>(RawEvent::CurveSet(gentle));
+			assert_eq!(sell(1, 100), 133);
+			assert_eq!(BondingCurve::reserve(), 17);
+			assert_backed();
+		})
+	}
+
+	#[test]
+	fn nothing_is_sold_without_a_curve() {
+		new_test_ext(LINEAR).execute_with(|| {
+			ActiveCurve::kill();
+			assert_eq!(BondingCurve::spot_price(), None);
+			assert_err!(BondingCurve::buy(Origin::signed(1), 1, 1_000), "no curve");
+		})
+	}
+}
@@ -20,9 +20,13 @@ use frame_support::{
 	decl_module,
 	decl_storage,
 	dispatch::{DispatchResult, DispatchError},
+	weights::SimpleDispatchInfo,
 };
 use frame_system::{self as system, ensure_signed, ensure_root};
 
+pub mod weights;
+pub use weights::WeightInfo;
+
 type BalanceOf<T> = <<T as Trait>::Currency as Currency<<T as system::Trait>::AccountId>>::Balance;
 type NegativeImbalanceOf<T> = <<T as Trait>::Currency as Currency<<T as system::Trait>::AccountId>>::NegativeImbalance;
 
@@ -35,6 +39,8 @@ pub trait Trait: system::Trait {
     type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
     /// The currency type that the charity deals in
     type Currency: Currency<Self::AccountId>;
+    /// Weight information for the dispatchables in this pallet
+    type WeightInfo: WeightInfo;
 }
 
 decl_storage! {
@@ -74,6 +80,7 @@ decl_module! {
         fn deposit_event() = default;
 
         /// Donate some funds to the charity
+        #[weight = SimpleDispatchInfo::FixedNormal(T::WeightInfo::donate())]
         fn donate(
             origin,
             amount: BalanceOf<T>
@@ -91,6 +98,7 @@ decl_module! {
 		///
         /// Take funds from the Charity's pot and send them somewhere. This cal lrequires root origin,
 		/// which means it must come from a governance mechanism such as Substrate's Democracy pallet.
+        #[weight = SimpleDispatchInfo::FixedNormal(T::WeightInfo::allocate())]
         fn allocate(
             origin,
             dest: T::AccountId,
@@ -221,6 +229,7 @@ mod tests {
     impl Trait for TestRuntime {
         type Event = TestEvent;
         type Currency = balances::Module<Self>;
+        type WeightInfo = ();
     }
 
     pub type System = system::Module<TestRuntime>;
@@ -0,0 +1,36 @@
+//! Weights for the charity pallet
+//!
+//! See the simple-map pallet for a description of the `WeightInfo` pattern.
+
+use sp_std::marker::PhantomData;
+use frame_support::weights::Weight;
+use frame_system as system;
+
+/// Weight functions needed by the charity pallet
+pub trait WeightInfo {
+    fn donate() -> Weight;
+    fn allocate() -> Weight;
+}
+
+/// Hand-picked estimates of the charity pallet's weights
+pub struct EstimatedWeight<T>(PhantomData<T>);
+impl<T: system::Trait> WeightInfo for EstimatedWeight<T> {
+    // a currency transfer plus reading the pot for the event
+    fn donate() -> Weight {
+        50_000
+    }
+    // a currency transfer plus reading the pot for the event
+    fn allocate() -> Weight {
+        50_000
+    }
+}
+
+// For backwards compatibility and tests
+impl WeightInfo for () {
+    fn donate() -> Weight {
+        10_000
+    }
+    fn allocate() -> Weight {
+        10_000
+    }
+}
@@ -0,0 +1,332 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! Transfer restrictions for regulated assets
+//!
+//! Some assets may only move between accounts that meet rules set off chain, such as having
+//! passed identity checks or not being sanctioned. `ManageOrigin`, typically governance, keeps
+//! the lists those rules produce:
+//! * The deny list. Denied accounts can neither send nor receive.
+//! * The allow list. While `AllowListRequired` is set, both sides of a transfer must be on it.
+//! * Jurisdiction tags. Each account may be tagged with the country it is in, and transfers from
+//!   or to an account tagged with a blocked jurisdiction fail.
+//!
+//! Asset pallets don't depend on this one directly. They ask for a `TransferFilter` in their
+//! `Trait` and consult it before every transfer, and the runtime decides whether that is this
+//! pallet or `()`, which allows every transfer.
+
+use frame_support::{
+	decl_event, decl_module, decl_storage, dispatch::DispatchResult, ensure, traits::EnsureOrigin,
+};
+use frame_system::{self as system};
+
+/// An ISO 3166-1 alpha-2 country code, such as `*b"CH"`
+pub type Jurisdiction = [u8; 2];
+
+/// Something that decides which transfers of an asset are allowed
+pub trait TransferFilter<AccountId, Balance> {
+	/// Check that `from` may transfer `amount` to `to`, or fail with the reason why not
+	fn check_transfer(from: &AccountId, to: &AccountId, amount: &Balance) -> DispatchResult;
+}
+
+impl<AccountId, Balance> TransferFilter<AccountId, Balance> for () {
+	fn check_transfer(_from: &AccountId, _to: &AccountId, _amount: &Balance) -> DispatchResult {
+		Ok(())
+	}
+}
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The origin that manages the lists and tags
+	type ManageOrigin: EnsureOrigin<Self::Origin>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as Compliance {
+		/// Accounts that may not send or receive
+		Denied get(fn is_denied): map T::AccountId => bool;
+
+		/// Accounts that may send and receive while the allow list is required
+		Allowed get(fn is_allowed): map T::AccountId => bool;
+
+		/// Whether only accounts on the allow list may send and receive
+		AllowListRequired get(fn allow_list_required): bool;
+
+		/// The jurisdiction each account is in, if known
+		Jurisdictions get(fn jurisdiction): map T::AccountId => Option<Jurisdiction>;
+
+		/// Jurisdictions whose accounts may not send or receive
+		BlockedJurisdictions get(fn is_blocked): map Jurisdiction => bool;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as system::Trait>::AccountId,
+	{
+		/// An account was added to or removed from the deny list
+		DenyListChanged(AccountId, bool),
+		/// An account was added to or removed from the allow list
+		AllowListChanged(AccountId, bool),
+		/// The allow list became required, or stopped being required
+		AllowListRequiredChanged(bool),
+		/// An account's jurisdiction tag changed
+		JurisdictionSet(AccountId, Option<Jurisdiction>),
+		/// A jurisdiction was blocked or unblocked
+		JurisdictionBlockChanged(Jurisdiction, bool),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		/// Add an account to the deny list, or remove it
+		fn set_denied(origin, who: T::AccountId, denied: bool) -> DispatchResult {
+			T::ManageOrigin::ensure_origin(origin)?;
+			if denied {
+				<Denied<T>>::insert(&who, true);
+			} else {
+				<Denied<T>>::remove(&who);
+			}
+			Self::deposit_event(RawEvent::DenyListChanged(who, denied));
+			Ok(())
+		}
+
+		/// Add an account to the allow list, or remove it
+		fn set_allowed(origin, who: T::AccountId, allowed: bool) -> DispatchResult {
+			T::ManageOrigin::ensure_origin(origin)?;
+			if allowed {
+				<Allowed<T>>::insert(&who, true);
+			} else {
+				<Allowed<T>>::remove(&who);
+			}
+			Self::deposit_event(RawEvent::AllowListChanged(who, allowed));
+			Ok(())
+		}
+
+		/// Require the allow list, or stop requiring it
+		fn set_allow_list_required(origin, required: bool) -> DispatchResult {
+			T::ManageOrigin::ensure_origin(origin)?;
+			AllowListRequired::put(required);
+			Self::deposit_event(RawEvent::AllowListRequiredChanged(required));
+			Ok(())
+		}
+
+		/// Tag an account with the jurisdiction it is in, or remove its tag
+		fn set_jurisdiction(
+			origin,
+			who: T::AccountId,
+			jurisdiction: Option<Jurisdiction>,
+		) -> DispatchResult {
+			T::ManageOrigin::ensure_origin(origin)?;
+			match jurisdiction {
+				Some(code) => <Jurisdictions<T>>::insert(&who, code),
+				None => <Jurisdictions<T>>::remove(&who),
+			}
+			Self::deposit_event(RawEvent::JurisdictionSet(who, jurisdiction));
+			Ok(())
+		}
+
+		/// Block the accounts of a jurisdiction from sending and receiving, or unblock them
+		fn set_jurisdiction_blocked(
+			origin,
+			jurisdiction: Jurisdiction,
+			blocked: bool,
+		) -> DispatchResult {
+			T::ManageOrigin::ensure_origin(origin)?;
+			if blocked {
+				BlockedJurisdictions::insert(jurisdiction, true);
+			} else {
+				BlockedJurisdictions::remove(jurisdiction);
+			}
+			Self::deposit_event(RawEvent::JurisdictionBlockChanged(jurisdiction, blocked));
+			Ok(())
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// Check that `who` may take part in transfers, as sender or receiver
+	pub fn check_account(who: &T::AccountId) -> DispatchResult {
+		ensure!(!Self::is_denied(who), "account is denied");
+		ensure!(
+			!Self::allow_list_required() || Self::is_allowed(who),
+			"account is not on the allow list"
+		);
+		if let Some(jurisdiction) = Self::jurisdiction(who) {
+			ensure!(!Self::is_blocked(jurisdiction), "jurisdiction is blocked");
+		}
+		Ok(())
+	}
+}
+
+/// The lists and tags apply to every amount. A filter with limits per transfer would use
+/// `amount` too.
+impl<T: Trait, Balance> TransferFilter<T::AccountId, Balance> for Module<T> {
+	fn check_transfer(from: &T::AccountId, to: &T::AccountId, _amount: &Balance) -> DispatchResult {
+		Self::check_account(from)?;
+		Self::check_account(to)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use event_assert::assert_last_event;
+	use frame_support::{
+		assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types,
+	};
+	use frame_system::{EnsureRoot, RawOrigin};
+	use sp_core::H256;
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup},
+		Perbill,
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	mod compliance {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			compliance<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type ManageOrigin = EnsureRoot<u64>;
+	}
+
+	pub type System = system::Module<TestRuntime>;
+	pub type Compliance = Module<TestRuntime>;
+
+	/// The tests start in block 1, with empty lists
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		let mut ext: sp_io::TestExternalities = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap()
+			.into();
+		ext.execute_with(|| System::set_block_number(1));
+		ext
+	}
+
+	fn root() -> Origin {
+		RawOrigin::Root.into()
+	}
+
+	fn check(from: u64, to: u64) -> DispatchResult {
+		<Compliance as TransferFilter<u64, u64>>::check_transfer(&from, &to, &100)
+	}
+
+	#[test]
+	fn everything_is_allowed_by_default() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(check(1, 2));
+			assert_ok!(<() as TransferFilter<u64, u64>>::check_transfer(&1, &2, &100));
+		})
+	}
+
+	#[test]
+	fn denied_accounts_neither_send_nor_receive() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(Compliance::set_denied(root(), 2, true));
+			assert_last_event::<TestRuntime>(RawEvent::DenyListChanged(2, true));
+			assert_err!(check(1, 2), "account is denied");
+			assert_err!(check(2, 1), "account is denied");
+			assert_ok!(check(1, 3));
+
+			assert_ok!(Compliance::set_denied(root(), 2, false));
+			assert_ok!(check(2, 1));
+		})
+	}
+
+	#[test]
+	fn allow_list_applies_only_while_required() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(Compliance::set_allowed(root(), 1, true));
+			assert_ok!(check(2, 3));
+
+			assert_ok!(Compliance::set_allow_list_required(root(), true));
+			assert_last_event::<TestRuntime>(RawEvent::AllowListRequiredChanged(true));
+			assert_err!(check(1, 2), "account is not on the allow list");
+			assert_err!(check(2, 1), "account is not on the allow list");
+
+			assert_ok!(Compliance::set_allowed(root(), 2, true));
+			assert_ok!(check(1, 2));
+
+			// The deny list wins over the allow list
+			assert_ok!(Compliance::set_denied(root(), 2, true));
+			assert_err!(check(1, 2), "account is denied");
+		})
+	}
+
+	#[test]
+	fn blocked_jurisdictions_cannot_transfer() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(Compliance::set_jurisdiction(root(), 1, Some(*b"CH")));
+			assert_ok!(Compliance::set_jurisdiction(root(), 2, Some(*b"XX")));
+			assert_last_event::<TestRuntime>(RawEvent::JurisdictionSet(2, Some(*b"XX")));
+			assert_ok!(check(1, 2));
+
+			assert_ok!(Compliance::set_jurisdiction_blocked(root(), *b"XX", true));
+			assert_last_event::<TestRuntime>(RawEvent::JurisdictionBlockChanged(*b"XX", true));
+			assert_err!(check(1, 2), "jurisdiction is blocked");
+			assert_err!(check(2, 3), "jurisdiction is blocked");
+			// Untagged accounts are not affected
+			assert_ok!(check(1, 3));
+
+			assert_ok!(Compliance::set_jurisdiction(root(), 2, None));
+			assert_ok!(check(1, 2));
+		})
+	}
+
+	#[test]
+	fn only_the_manager_changes_the_rules() {
+		new_test_ext().execute_with(|| {
+			assert!(Compliance::set_denied(Origin::signed(1), 2, true).is_err());
+			assert!(Compliance::set_allowed(Origin::signed(1), 1, true).is_err());
+			assert!(Compliance::set_allow_list_required(Origin::signed(1), true).is_err());
+			assert!(Compliance::set_jurisdiction(Origin::signed(1), 1, None).is_err());
+			assert!(Compliance::set_jurisdiction_blocked(Origin::signed(1), *b"CH", true).is_err());
+			assert_ok!(check(1, 2));
+		})
+	}
+}
@@ -0,0 +1,581 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! Conviction voting: more votes for tokens that stay locked longer
+//!
+//! `ManageOrigin` starts referenda on proposals, which run for `VotingPeriod` blocks. Accounts vote
+//! with some of their balance and a `Conviction`. Without conviction, the balance is worth a tenth
+//! of a vote per unit, and is only locked until the referendum ends. With conviction, it is worth
+//! one to six votes per unit, and stays locked for one to 32 `EnactmentPeriod`s after the end.
+//! Whoever wants more say must stay invested in the outcome for longer, until well after the
+//! proposal is enacted.
+//!
+//! Every vote is locked with the same `LockableCurrency` lock, as in the lockable currency recipe.
+//! An account's votes share the lock, which holds the largest amount any of them locks, until the
+//! latest of their ends. Locks of other pallets overlap with it rather than add up. The pallet
+//! remembers each vote's lock, and `unlock` shrinks the lock to the votes whose locks have not
+//! expired yet. Each account has at most `MaxLocks` of them, so `unlock` does bounded work.
+//!
+//! Unlike Substrate's democracy pallet, this recipe locks every vote with conviction, whichever
+//! side won.
+
+use codec::{Decode, Encode};
+use sp_std::prelude::*;
+use sp_runtime::{
+	traits::{Saturating, Zero},
+	RuntimeDebug,
+};
+use frame_support::{
+	decl_event, decl_module, decl_storage,
+	dispatch::DispatchResult,
+	ensure,
+	traits::{
+		Currency, EnsureOrigin, Get, LockIdentifier, LockableCurrency, WithdrawReason,
+		WithdrawReasons,
+	},
+};
+use frame_system::{self as system, ensure_signed};
+
+const VOTING_ID: LockIdentifier = *b"convictn";
+
+pub type ReferendumIndex = u32;
+
+type BalanceOf<T> = <<T as Trait>::Currency as Currency<<T as system::Trait>::AccountId>>::Balance;
+type ReferendumOf<T> =
+	Referendum<<T as system::Trait>::Hash, BalanceOf<T>, <T as system::Trait>::BlockNumber>;
+type VoteLockOf<T> = VoteLock<BalanceOf<T>, <T as system::Trait>::BlockNumber>;
+
+/// How long a vote's balance stays locked, and how many votes it is worth in return
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, RuntimeDebug)]
+pub enum Conviction {
+	/// A tenth of a vote per unit, locked until the referendum ends
+	None,
+	/// One vote per unit, locked for one enactment period after the end
+	Locked1x,
+	/// Two votes per unit, locked for two enactment periods
+	Locked2x,
+	/// Three votes per unit, locked for four enactment periods
+	Locked3x,
+	/// Four votes per unit, locked for eight enactment periods
+	Locked4x,
+	/// Five votes per unit, locked for 16 enactment periods
+	Locked5x,
+	/// Six votes per unit, locked for 32 enactment periods
+	Locked6x,
+}
+
+impl Conviction {
+	/// The number of enactment periods the balance stays locked after the referendum ends
+	pub fn lock_periods(self) -> u32 {
+		match self {
+			Conviction::None => 0,
+			Conviction::Locked1x => 1,
+			Conviction::Locked2x => 2,
+			Conviction::Locked3x => 4,
+			Conviction::Locked4x => 8,
+			Conviction::Locked5x => 16,
+			Conviction::Locked6x => 32,
+		}
+	}
+
+	/// The votes `balance` is worth with this conviction
+	pub fn votes<B>(self, balance: B) -> B where
+		B: From<u8> + Saturating + sp_std::ops::Div<Output = B>,
+	{
+		let multiplier: u8 = match self {
+			Conviction::None => return balance / B::from(10),
+			Conviction::Locked1x => 1,
+			Conviction::Locked2x => 2,
+			Conviction::Locked3x => 3,
+			Conviction::Locked4x => 4,
+			Conviction::Locked5x => 5,
+			Conviction::Locked6x => 6,
+		};
+		balance.saturating_mul(B::from(multiplier))
+	}
+}
+
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct Vote<Balance> {
+	pub aye: bool,
+	pub balance: Balance,
+	pub conviction: Conviction,
+}
+
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct Referendum<Hash, Balance, BlockNumber> {
+	pub proposal: Hash,
+	/// Votes are accepted before this block
+	pub end: BlockNumber,
+	/// Votes in favour, weighted by conviction
+	pub ayes: Balance,
+	/// Votes against, weighted by conviction
+	pub nays: Balance,
+}
+
+/// What a single vote locks
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct VoteLock<Balance, BlockNumber> {
+	pub referendum: ReferendumIndex,
+	pub amount: Balance,
+	/// The lock expires at the start of this block
+	pub until: BlockNumber,
+}
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The currency in which votes are locked
+	type Currency: LockableCurrency<Self::AccountId, Moment = Self::BlockNumber>;
+
+	/// The origin that starts referenda
+	type ManageOrigin: EnsureOrigin<Self::Origin>;
+
+	/// The number of blocks a referendum accepts votes
+	type VotingPeriod: Get<Self::BlockNumber>;
+
+	/// The number of blocks between a referendum passing and its proposal being enacted, and the
+	/// unit of conviction locks
+	type EnactmentPeriod: Get<Self::BlockNumber>;
+
+	/// The most votes an account may have locks for
+	type MaxLocks: Get<u32>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as ConvictionVoting {
+		/// Referenda that have not been closed
+		Referenda get(fn referendum): map ReferendumIndex => Option<ReferendumOf<T>>;
+
+		/// Number of referenda ever started; the next referendum's index
+		ReferendumCount get(fn referendum_count): ReferendumIndex;
+
+		/// Each account's vote in each referendum that has not been closed
+		Votes get(fn vote_of):
+			double_map ReferendumIndex, blake2_256(T::AccountId) => Option<Vote<BalanceOf<T>>>;
+
+		/// The locks of each account's votes that have not been unlocked
+		VoteLocks get(fn vote_locks): map T::AccountId => Vec<VoteLockOf<T>>;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as system::Trait>::AccountId,
+		Balance = BalanceOf<T>,
+		BlockNumber = <T as system::Trait>::BlockNumber,
+		Hash = <T as system::Trait>::Hash,
+	{
+		/// A referendum on a proposal started, accepting votes until a block
+		Started(ReferendumIndex, Hash, BlockNumber),
+		/// An account voted aye (true) or nay (false) with this many votes
+		Voted(ReferendumIndex, AccountId, bool, Balance),
+		/// A referendum passed, and its proposal is enacted in a block
+		Passed(ReferendumIndex, Hash, BlockNumber),
+		Rejected(ReferendumIndex),
+		/// Expired locks were removed, and this much of the account stays locked
+		Unlocked(AccountId, Balance),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		const VotingPeriod: T::BlockNumber = T::VotingPeriod::get();
+
+		const EnactmentPeriod: T::BlockNumber = T::EnactmentPeriod::get();
+
+		const MaxLocks: u32 = T::MaxLocks::get();
+
+		fn start_referendum(origin, proposal: T::Hash) -> DispatchResult {
+			T::ManageOrigin::ensure_origin(origin)?;
+			let now = <system::Module<T>>::block_number();
+			let end = now.saturating_add(T::VotingPeriod::get());
+
+			let index = Self::referendum_count();
+			let referendum = Referendum {
+				proposal,
+				end,
+				ayes: Zero::zero(),
+				nays: Zero::zero(),
+			};
+			<Referenda<T>>::insert(index, referendum);
+			ReferendumCount::put(index + 1);
+			Self::deposit_event(RawEvent::Started(index, proposal, end));
+			Ok(())
+		}
+
+		/// Vote with `balance` of the caller's funds. A second vote in the same referendum
+		/// replaces the first, along with its lock.
+		fn vote(
+			origin,
+			index: ReferendumIndex,
+			aye: bool,
+			balance: BalanceOf<T>,
+			conviction: Conviction,
+		) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let mut referendum = Self::referendum(index).ok_or("no such referendum")?;
+			ensure!(<system::Module<T>>::block_number() < referendum.end, "referendum ended");
+			ensure!(!balance.is_zero(), "empty vote");
+			ensure!(balance <= T::Currency::free_balance(&who), "not enough free balance");
+
+			let mut locks = Self::vote_locks(&who);
+			let lock = VoteLock {
+				referendum: index,
+				amount: balance,
+				until: referendum.end.saturating_add(
+					T::EnactmentPeriod::get().saturating_mul(conviction.lock_periods().into())
+				),
+			};
+			match locks.iter().position(|lock| lock.referendum == index) {
+				Some(position) => locks[position] = lock,
+				None => {
+					ensure!((locks.len() as u32) < T::MaxLocks::get(), "too many locks");
+					locks.push(lock);
+				}
+			}
+
+			if let Some(previous) = Self::vote_of(index, &who) {
+				Self::tally(&mut referendum, &previous, false);
+			}
+			let vote = Vote { aye, balance, conviction };
+			Self::tally(&mut referendum, &vote, true);
+
+			<Referenda<T>>::insert(index, referendum);
+			<Votes<T>>::insert(index, &who, vote);
+			Self::set_lock(&who, locks);
+			Self::deposit_event(RawEvent::Voted(index, who, aye, conviction.votes(balance)));
+			Ok(())
+		}
+
+		/// Decide a referendum whose voting period is over. More ayes than nays pass it.
+		fn close(origin, index: ReferendumIndex) -> DispatchResult {
+			let _ = ensure_signed(origin)?;
+			let referendum = Self::referendum(index).ok_or("no such referendum")?;
+			ensure!(<system::Module<T>>::block_number() >= referendum.end, "referendum ongoing");
+
+			<Referenda<T>>::remove(index);
+			// The votes were counted, and their locks are kept in `VoteLocks`
+			<Votes<T>>::remove_prefix(&index);
+			if referendum.ayes > referendum.nays {
+				let enactment = referendum.end.saturating_add(T::EnactmentPeriod::get());
+				Self::deposit_event(RawEvent::Passed(index, referendum.proposal, enactment));
+			} else {
+				Self::deposit_event(RawEvent::Rejected(index));
+			}
+			Ok(())
+		}
+
+		/// Remove the caller's expired vote locks, and shrink the lock to the others
+		fn unlock(origin) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let now = <system::Module<T>>::block_number();
+			let mut locks = Self::vote_locks(&who);
+			let before = locks.len();
+			locks.retain(|lock| lock.until > now);
+			ensure!(locks.len() < before, "nothing to unlock");
+
+			let locked = Self::set_lock(&who, locks);
+			Self::deposit_event(RawEvent::Unlocked(who, locked));
+			Ok(())
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// Add a vote to, or remove it from, a referendum's tally
+	fn tally(referendum: &mut ReferendumOf<T>, vote: &Vote<BalanceOf<T>>, add: bool) {
+		let votes = vote.conviction.votes(vote.balance);
+		let side = if vote.aye { &mut referendum.ayes } else { &mut referendum.nays };
+		*side = if add { side.saturating_add(votes) } else { side.saturating_sub(votes) };
+	}
+
+	/// Store an account's vote locks, and lock the most any of them locks until the last one
+	/// expires. Returns the amount locked.
+	fn set_lock(who: &T::AccountId, locks: Vec<VoteLockOf<T>>) -> BalanceOf<T> {
+		if locks.is_empty() {
+			T::Currency::remove_lock(VOTING_ID, who);
+			<VoteLocks<T>>::remove(who);
+			return Zero::zero();
+		}
+
+		let amount = locks.iter().map(|lock| lock.amount).max().unwrap_or_else(Zero::zero);
+		let until = locks.iter().map(|lock| lock.until).max().unwrap_or_else(Zero::zero);
+		T::Currency::set_lock(
+			VOTING_ID,
+			who,
+			amount,
+			until,
+			WithdrawReasons::except(WithdrawReason::TransactionPayment),
+		);
+		<VoteLocks<T>>::insert(who, locks);
+		amount
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use event_assert::assert_has_event;
+	use sp_core::H256;
+	use frame_support::{
+		assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types,
+	};
+	use frame_system::{EnsureRoot, RawOrigin};
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup},
+		Perbill,
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const ExistentialDeposit: u64 = 0;
+		pub const TransferFee: u64 = 0;
+		pub const CreationFee: u64 = 0;
+
+		pub const VotingPeriod: u64 = 10;
+		pub const EnactmentPeriod: u64 = 5;
+		pub const MaxLocks: u32 = 2;
+		// The lockable currency recipe locks until this block
+		pub const LockPeriod: u64 = 1_000;
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	impl balances::Trait for TestRuntime {
+		type Balance = u64;
+		type OnFreeBalanceZero = ();
+		type OnNewAccount = ();
+		type Event = TestEvent;
+		type TransferPayment = ();
+		type DustRemoval = ();
+		type ExistentialDeposit = ExistentialDeposit;
+		type TransferFee = TransferFee;
+		type CreationFee = CreationFee;
+	}
+
+	// Another pallet that locks the same currency
+	impl lockable_currency::Trait for TestRuntime {
+		type Currency = balances::Module<Self>;
+		type Event = TestEvent;
+		type LockPeriod = LockPeriod;
+	}
+
+	mod conviction_voting {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			balances<T>,
+			lockable_currency<T>,
+			conviction_voting<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type Currency = balances::Module<Self>;
+		type ManageOrigin = EnsureRoot<u64>;
+		type VotingPeriod = VotingPeriod;
+		type EnactmentPeriod = EnactmentPeriod;
+		type MaxLocks = MaxLocks;
+	}
+
+	pub type System = system::Module<TestRuntime>;
+	pub type Balances = balances::Module<TestRuntime>;
+	pub type LockableExample = lockable_currency::Module<TestRuntime>;
+	pub type ConvictionVoting = Module<TestRuntime>;
+
+	/// Accounts 1 to 4 have 100 each. In block 1, referenda 0 and 1 start, ending in block 11.
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		let mut t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		balances::GenesisConfig::<TestRuntime> {
+			balances: (1..=4).map(|who| (who, 100)).collect(),
+			vesting: vec![],
+		}
+		.assimilate_storage(&mut t)
+		.unwrap();
+		let mut ext: sp_io::TestExternalities = t.into();
+		ext.execute_with(|| {
+			System::set_block_number(1);
+			for proposal in 0..2 {
+				assert_ok!(start(proposal));
+			}
+		});
+		ext
+	}
+
+	fn start(proposal: u64) -> DispatchResult {
+		ConvictionVoting::start_referendum(RawOrigin::Root.into(), H256::from_low_u64_be(proposal))
+	}
+
+	fn vote(
+		who: u64,
+		index: ReferendumIndex,
+		aye: bool,
+		balance: u64,
+		conviction: Conviction,
+	) -> DispatchResult {
+		ConvictionVoting::vote(Origin::signed(who), index, aye, balance, conviction)
+	}
+
+	fn tally(index: ReferendumIndex) -> (u64, u64) {
+		let referendum = ConvictionVoting::referendum(index).unwrap();
+		(referendum.ayes, referendum.nays)
+	}
+
+	/// Whether `who` can transfer `amount`, which is then sent to account 4
+	fn can_transfer(who: u64, amount: u64) -> bool {
+		Balances::transfer(Origin::signed(who), 4, amount).is_ok()
+	}
+
+	#[test]
+	fn conviction_trades_votes_for_lock_time() {
+		assert_eq!(Conviction::None.votes(100u64), 10);
+		assert_eq!(Conviction::None.lock_periods(), 0);
+		assert_eq!(Conviction::Locked1x.votes(100u64), 100);
+		assert_eq!(Conviction::Locked1x.lock_periods(), 1);
+		assert_eq!(Conviction::Locked3x.votes(100u64), 300);
+		assert_eq!(Conviction::Locked3x.lock_periods(), 4);
+		assert_eq!(Conviction::Locked6x.votes(100u64), 600);
+		assert_eq!(Conviction::Locked6x.lock_periods(), 32);
+		assert_eq!(Conviction::Locked6x.votes(u64::max_value()), u64::max_value());
+	}
+
+	#[test]
+	fn votes_are_weighted_by_conviction() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(vote(1, 0, true, 10, Conviction::Locked6x));
+			assert_ok!(vote(2, 0, false, 50, Conviction::None));
+			assert_ok!(vote(3, 0, false, 40, Conviction::Locked1x));
+			assert_eq!(tally(0), (60, 45));
+
+			// Voting again replaces the vote
+			assert_ok!(vote(2, 0, true, 50, Conviction::Locked2x));
+			assert_eq!(tally(0), (160, 40));
+			assert_eq!(ConvictionVoting::vote_locks(2).len(), 1);
+
+			assert_err!(ConvictionVoting::close(Origin::signed(4), 0), "referendum ongoing");
+			System::set_block_number(11);
+			assert_err!(vote(4, 0, true, 10, Conviction::None), "referendum ended");
+			assert_ok!(ConvictionVoting::close(Origin::signed(4), 0));
+			assert_has_event::<TestRuntime>(RawEvent::Passed(0, H256::from_low_u64_be(0), 16));
+			assert_eq!(ConvictionVoting::referendum(0), None);
+			assert_eq!(ConvictionVoting::vote_of(0, 1), None);
+
+			// Without votes, a referendum is rejected
+			assert_ok!(ConvictionVoting::close(Origin::signed(4), 1));
+			assert_has_event::<TestRuntime>(RawEvent::Rejected(1));
+		})
+	}
+
+	#[test]
+	fn locks_expire_in_multiples_of_the_enactment_period() {
+		new_test_ext().execute_with(|| {
+			// Locked until block 11 + 5 = 16, and 11 + 4 * 5 = 31
+			assert_ok!(vote(1, 0, true, 80, Conviction::Locked1x));
+			assert_ok!(vote(1, 1, true, 20, Conviction::Locked3x));
+			// The votes share one lock of the larger amount until the later block
+			assert!(!can_transfer(1, 21));
+
+			System::set_block_number(15);
+			assert!(!can_transfer(1, 21));
+			assert_err!(ConvictionVoting::unlock(Origin::signed(1)), "nothing to unlock");
+
+			System::set_block_number(16);
+			assert_ok!(ConvictionVoting::unlock(Origin::signed(1)));
+			assert_has_event::<TestRuntime>(RawEvent::Unlocked(1, 20));
+			assert!(!can_transfer(1, 81));
+			assert!(can_transfer(1, 70));
+
+			// The lock expires by itself, but is only removed from storage by `unlock`
+			System::set_block_number(31);
+			assert!(can_transfer(1, 30));
+			assert_ok!(ConvictionVoting::unlock(Origin::signed(1)));
+			assert!(ConvictionVoting::vote_locks(1).is_empty());
+			assert!(Balances::locks(1).is_empty());
+		})
+	}
+
+	#[test]
+	fn votes_without_conviction_are_locked_while_voting() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(vote(1, 0, true, 60, Conviction::None));
+			// Otherwise the same funds could vote again from another account
+			assert!(!can_transfer(1, 41));
+
+			System::set_block_number(11);
+			assert!(can_transfer(1, 100));
+		})
+	}
+
+	#[test]
+	fn locks_overlap_with_other_pallets() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(LockableExample::lock_capital(Origin::signed(1), 30));
+			assert_ok!(vote(1, 0, true, 60, Conviction::None));
+			// 60 is locked, not 90
+			assert!(!can_transfer(1, 41));
+			assert!(can_transfer(1, 40));
+
+			// Removing the voting lock leaves the other pallet's lock in place
+			System::set_block_number(11);
+			assert_ok!(ConvictionVoting::unlock(Origin::signed(1)));
+			assert!(!can_transfer(1, 31));
+			assert!(can_transfer(1, 30));
+		})
+	}
+
+	#[test]
+	fn votes_are_bounded() {
+		new_test_ext().execute_with(|| {
+			assert_err!(vote(1, 0, true, 101, Conviction::None), "not enough free balance");
+			assert_err!(vote(1, 0, true, 0, Conviction::None), "empty vote");
+			assert_err!(vote(1, 2, true, 10, Conviction::None), "no such referendum");
+
+			// The same funds may vote in several referenda, up to `MaxLocks`
+			assert_ok!(start(2));
+			assert_ok!(vote(1, 0, true, 100, Conviction::None));
+			assert_ok!(vote(1, 1, true, 100, Conviction::None));
+			assert_err!(vote(1, 2, true, 100, Conviction::None), "too many locks");
+			assert_ok!(vote(1, 0, false, 100, Conviction::Locked1x));
+
+			assert!(ConvictionVoting::start_referendum(Origin::signed(1), H256::zero()).is_err());
+		})
+	}
+}
@@ -0,0 +1,30 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use codec::{Codec, Decode, Encode};
+use sp_runtime::RuntimeDebug;
+
+/// Whether a credential can be relied on
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, RuntimeDebug)]
+pub enum CredentialStatus {
+	/// No credential has this id
+	Unknown,
+	/// The issuer offered the credential, and the subject has not accepted it yet
+	Offered,
+	/// The subject accepted the credential, and it has neither expired nor been revoked
+	Valid,
+	/// The credential expired
+	Expired,
+	/// The issuer revoked the credential
+	Revoked,
+}
+
+// Verifiers ask for the status rather than reading the credential from storage, so that they need
+// not know how expiry and revocation are recorded, nor what the current block is.
+sp_api::decl_runtime_apis! {
+	pub trait CredentialsApi<Hash> where
+		Hash: Codec,
+	{
+		/// The status of the credential `id` in the best block
+		fn credential_status(id: Hash) -> CredentialStatus;
+	}
+}
@@ -0,0 +1,431 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! A registry of verifiable credentials
+//!
+//! An issuer, such as a university or a KYC provider, first registers a schema that describes
+//! what its credentials claim. It then issues credentials under that schema about subjects. A
+//! credential holds only the hash of its claim, so the claim itself stays off chain, shared by the
+//! subject with whoever needs to verify it.
+//!
+//! Nobody is credentialed against their will. Issuing only offers a credential, and it becomes
+//! valid once the subject accepts it. The subject can discard a credential at any time, offered or
+//! accepted. Issuers can give credentials an expiry, and revoke them. Revoked credentials are kept,
+//! so verifiers learn that they were revoked rather than that they never existed.
+//!
+//! Verifiers check a credential through the `CredentialsApi`, which reports its status in the
+//! best block.
+
+use codec::{Decode, Encode};
+use sp_std::prelude::*;
+use sp_runtime::{
+	traits::{Hash, Zero},
+	RuntimeDebug,
+};
+use frame_support::{
+	decl_event, decl_module, decl_storage,
+	dispatch::DispatchResult,
+	ensure,
+	traits::Get,
+};
+use frame_system::{self as system, ensure_signed};
+
+pub use credentials_runtime_api::CredentialStatus;
+
+/// What the credentials of an issuer claim
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct Schema<AccountId> {
+	pub issuer: AccountId,
+	/// The format is up to the issuer, for example a JSON schema
+	pub definition: Vec<u8>,
+}
+
+/// A claim that an issuer makes about a subject
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct Credential<AccountId, Hash, BlockNumber> {
+	pub schema: Hash,
+	pub issuer: AccountId,
+	pub subject: AccountId,
+	/// The hash of the claim, which the subject shares off chain
+	pub claim: Hash,
+	/// The block from which the credential is expired, if any
+	pub expires_at: Option<BlockNumber>,
+	/// Whether the subject consented to the credential
+	pub accepted: bool,
+	pub revoked: bool,
+}
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The longest schema definition that may be registered
+	type MaxSchemaLength: Get<u32>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as Credentials {
+		/// Registered schemas, keyed by `schema_id`
+		Schemas get(fn schema): map T::Hash => Option<Schema<T::AccountId>>;
+
+		/// Offered and accepted credentials, keyed by `credential_id`
+		Credentials get(fn credential):
+			map T::Hash => Option<Credential<T::AccountId, T::Hash, T::BlockNumber>>;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as system::Trait>::AccountId,
+		Hash = <T as system::Trait>::Hash,
+	{
+		/// An issuer registered a schema
+		SchemaRegistered(AccountId, Hash),
+		/// An issuer (first) offered a credential to a subject (second)
+		CredentialOffered(AccountId, AccountId, Hash),
+		/// A subject accepted a credential
+		CredentialAccepted(AccountId, Hash),
+		/// A subject discarded a credential
+		CredentialDiscarded(AccountId, Hash),
+		/// An issuer revoked a credential
+		CredentialRevoked(AccountId, Hash),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		const MaxSchemaLength: u32 = T::MaxSchemaLength::get();
+
+		/// Register a schema under which the caller can issue credentials
+		fn register_schema(origin, definition: Vec<u8>) -> DispatchResult {
+			let issuer = ensure_signed(origin)?;
+			ensure!(!definition.is_empty(), "schema is empty");
+			ensure!(
+				definition.len() as u32 <= T::MaxSchemaLength::get(),
+				"schema is too long"
+			);
+			let id = Self::schema_id(&issuer, &definition);
+			ensure!(!<Schemas<T>>::exists(&id), "schema already registered");
+
+			<Schemas<T>>::insert(&id, Schema { issuer: issuer.clone(), definition });
+			Self::deposit_event(RawEvent::SchemaRegistered(issuer, id));
+			Ok(())
+		}
+
+		/// Offer `subject` a credential for `claim`, which expires after `valid_for` blocks if
+		/// given. The credential is valid once the subject accepts it.
+		fn issue(
+			origin,
+			schema: T::Hash,
+			subject: T::AccountId,
+			claim: T::Hash,
+			valid_for: Option<T::BlockNumber>
+		) -> DispatchResult {
+			let issuer = ensure_signed(origin)?;
+			let registered = Self::schema(&schema).ok_or("no such schema")?;
+			ensure!(registered.issuer == issuer, "not the issuer of the schema");
+			ensure!(
+				valid_for.map_or(true, |blocks| !blocks.is_zero()),
+				"credential would expire at once"
+			);
+			let id = Self::credential_id(&schema, &subject, &claim);
+			ensure!(!<Credentials<T>>::exists(&id), "credential already exists");
+
+			let now = <system::Module<T>>::block_number();
+			let credential = Credential {
+				schema,
+				issuer: issuer.clone(),
+				subject: subject.clone(),
+				claim,
+				expires_at: valid_for.map(|blocks| now + blocks),
+				accepted: false,
+				revoked: false,
+			};
+			<Credentials<T>>::insert(&id, credential);
+			Self::deposit_event(RawEvent::CredentialOffered(issuer, subject, id));
+			Ok(())
+		}
+
+		/// Consent to a credential offered to the caller
+		fn accept(origin, id: T::Hash) -> DispatchResult {
+			let subject = ensure_signed(origin)?;
+			let mut credential = Self::credential(&id).ok_or("no such credential")?;
+			ensure!(credential.subject == subject, "not the subject");
+			ensure!(
+				Self::status_of(&credential) == CredentialStatus::Offered,
+				"credential is not on offer"
+			);
+
+			credential.accepted = true;
+			<Credentials<T>>::insert(&id, credential);
+			Self::deposit_event(RawEvent::CredentialAccepted(subject, id));
+			Ok(())
+		}
+
+		/// Decline a credential offered to the caller, or withdraw consent to an accepted one
+		fn discard(origin, id: T::Hash) -> DispatchResult {
+			let subject = ensure_signed(origin)?;
+			let credential = Self::credential(&id).ok_or("no such credential")?;
+			ensure!(credential.subject == subject, "not the subject");
+
+			<Credentials<T>>::remove(&id);
+			Self::deposit_event(RawEvent::CredentialDiscarded(subject, id));
+			Ok(())
+		}
+
+		/// Revoke a credential the caller issued
+		fn revoke(origin, id: T::Hash) -> DispatchResult {
+			let issuer = ensure_signed(origin)?;
+			let mut credential = Self::credential(&id).ok_or("no such credential")?;
+			ensure!(credential.issuer == issuer, "not the issuer");
+			ensure!(!credential.revoked, "credential already revoked");
+
+			credential.revoked = true;
+			<Credentials<T>>::insert(&id, credential);
+			Self::deposit_event(RawEvent::CredentialRevoked(issuer, id));
+			Ok(())
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// The id of a schema. Including the issuer lets issuers register the same definition.
+	pub fn schema_id(issuer: &T::AccountId, definition: &[u8]) -> T::Hash {
+		T::Hashing::hash_of(&(issuer, definition))
+	}
+
+	/// The id of the credential for `claim` about `subject` under `schema`
+	pub fn credential_id(schema: &T::Hash, subject: &T::AccountId, claim: &T::Hash) -> T::Hash {
+		T::Hashing::hash_of(&(schema, subject, claim))
+	}
+
+	/// The status of the credential `id` in the current block
+	pub fn credential_status(id: &T::Hash) -> CredentialStatus {
+		Self::credential(id)
+			.map(|credential| Self::status_of(&credential))
+			.unwrap_or(CredentialStatus::Unknown)
+	}
+
+	fn status_of(
+		credential: &Credential<T::AccountId, T::Hash, T::BlockNumber>,
+	) -> CredentialStatus {
+		let now = <system::Module<T>>::block_number();
+		if credential.revoked {
+			CredentialStatus::Revoked
+		} else if credential.expires_at.map_or(false, |expires_at| now >= expires_at) {
+			CredentialStatus::Expired
+		} else if credential.accepted {
+			CredentialStatus::Valid
+		} else {
+			CredentialStatus::Offered
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use event_assert::{assert_has_event, assert_last_event};
+
+	use sp_core::H256;
+	use frame_support::{
+		assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types,
+	};
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup},
+		Perbill,
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const MaxSchemaLength: u32 = 16;
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	mod credentials {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			credentials<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type MaxSchemaLength = MaxSchemaLength;
+	}
+
+	pub type System = system::Module<TestRuntime>;
+	pub type Credentials = Module<TestRuntime>;
+
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap()
+			.into()
+	}
+
+	const ISSUER: u64 = 1;
+	const SUBJECT: u64 = 2;
+	const DEGREE: &[u8] = b"degree";
+
+	/// Registers the issuer's schema and offers the subject a credential under it
+	fn offer(claim: H256, valid_for: Option<u64>) -> H256 {
+		let schema = Credentials::schema_id(&ISSUER, DEGREE);
+		if Credentials::schema(&schema).is_none() {
+			assert_ok!(Credentials::register_schema(Origin::signed(ISSUER), DEGREE.to_vec()));
+		}
+		assert_ok!(Credentials::issue(Origin::signed(ISSUER), schema, SUBJECT, claim, valid_for));
+		Credentials::credential_id(&schema, &SUBJECT, &claim)
+	}
+
+	#[test]
+	fn schemas_are_registered_per_issuer() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(Credentials::register_schema(Origin::signed(ISSUER), DEGREE.to_vec()));
+			let schema = Credentials::schema_id(&ISSUER, DEGREE);
+			assert_last_event::<TestRuntime>(RawEvent::SchemaRegistered(ISSUER, schema));
+			assert_err!(
+				Credentials::register_schema(Origin::signed(ISSUER), DEGREE.to_vec()),
+				"schema already registered"
+			);
+			assert_ok!(Credentials::register_schema(Origin::signed(3), DEGREE.to_vec()));
+
+			assert_err!(
+				Credentials::register_schema(Origin::signed(ISSUER), vec![0; 17]),
+				"schema is too long"
+			);
+			assert_err!(
+				Credentials::register_schema(Origin::signed(ISSUER), vec![]),
+				"schema is empty"
+			);
+		})
+	}
+
+	#[test]
+	fn only_the_schema_issuer_issues() {
+		new_test_ext().execute_with(|| {
+			let schema = Credentials::schema_id(&ISSUER, DEGREE);
+			assert_err!(
+				Credentials::issue(Origin::signed(ISSUER), schema, SUBJECT, H256::zero(), None),
+				"no such schema"
+			);
+			assert_ok!(Credentials::register_schema(Origin::signed(ISSUER), DEGREE.to_vec()));
+			assert_err!(
+				Credentials::issue(Origin::signed(3), schema, SUBJECT, H256::zero(), None),
+				"not the issuer of the schema"
+			);
+		})
+	}
+
+	#[test]
+	fn credentials_need_consent() {
+		new_test_ext().execute_with(|| {
+			let id = offer(H256::zero(), None);
+			assert_eq!(Credentials::credential_status(&id), CredentialStatus::Offered);
+			assert_last_event::<TestRuntime>(RawEvent::CredentialOffered(ISSUER, SUBJECT, id));
+
+			assert_err!(Credentials::accept(Origin::signed(3), id), "not the subject");
+			assert_ok!(Credentials::accept(Origin::signed(SUBJECT), id));
+			assert_eq!(Credentials::credential_status(&id), CredentialStatus::Valid);
+			assert_last_event::<TestRuntime>(RawEvent::CredentialAccepted(SUBJECT, id));
+			assert_err!(
+				Credentials::accept(Origin::signed(SUBJECT), id),
+				"credential is not on offer"
+			);
+		})
+	}
+
+	#[test]
+	fn subjects_discard_credentials() {
+		new_test_ext().execute_with(|| {
+			let declined = offer(H256::zero(), None);
+			assert_ok!(Credentials::discard(Origin::signed(SUBJECT), declined));
+			assert_eq!(Credentials::credential_status(&declined), CredentialStatus::Unknown);
+			assert_has_event::<TestRuntime>(RawEvent::CredentialDiscarded(SUBJECT, declined));
+
+			let withdrawn = offer(H256::repeat_byte(1), None);
+			assert_ok!(Credentials::accept(Origin::signed(SUBJECT), withdrawn));
+			assert_err!(Credentials::discard(Origin::signed(ISSUER), withdrawn), "not the subject");
+			assert_ok!(Credentials::discard(Origin::signed(SUBJECT), withdrawn));
+			assert_eq!(Credentials::credential_status(&withdrawn), CredentialStatus::Unknown);
+			assert_last_event::<TestRuntime>(RawEvent::CredentialDiscarded(SUBJECT, withdrawn));
+		})
+	}
+
+	#[test]
+	fn credentials_expire() {
+		new_test_ext().execute_with(|| {
+			System::set_block_number(1);
+			let accepted = offer(H256::zero(), Some(10));
+			let pending = offer(H256::repeat_byte(1), Some(10));
+			assert_ok!(Credentials::accept(Origin::signed(SUBJECT), accepted));
+
+			System::set_block_number(10);
+			assert_eq!(Credentials::credential_status(&accepted), CredentialStatus::Valid);
+			System::set_block_number(11);
+			assert_eq!(Credentials::credential_status(&accepted), CredentialStatus::Expired);
+			assert_err!(
+				Credentials::accept(Origin::signed(SUBJECT), pending),
+				"credential is not on offer"
+			);
+		})
+	}
+
+	#[test]
+	fn issuers_revoke_credentials() {
+		new_test_ext().execute_with(|| {
+			let id = offer(H256::zero(), None);
+			assert_ok!(Credentials::accept(Origin::signed(SUBJECT), id));
+
+			assert_err!(Credentials::revoke(Origin::signed(SUBJECT), id), "not the issuer");
+			assert_ok!(Credentials::revoke(Origin::signed(ISSUER), id));
+			assert_eq!(Credentials::credential_status(&id), CredentialStatus::Revoked);
+			assert_last_event::<TestRuntime>(RawEvent::CredentialRevoked(ISSUER, id));
+			assert_err!(
+				Credentials::revoke(Origin::signed(ISSUER), id),
+				"credential already revoked"
+			);
+
+			// Revoked credentials can't be issued again
+			let schema = Credentials::schema_id(&ISSUER, DEGREE);
+			assert_err!(
+				Credentials::issue(Origin::signed(ISSUER), schema, SUBJECT, H256::zero(), None),
+				"credential already exists"
+			);
+		})
+	}
+}
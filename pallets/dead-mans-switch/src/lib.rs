@@ -0,0 +1,444 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! Dead man's switch
+//!
+//! An account names an heir and a timeout. As long as the account keeps acting, nothing happens.
+//! Once it has not sent a transaction for `timeout` blocks, the heir may claim its free balance.
+//! Locked funds stay with the account, and the heir may claim them again once the locks expire.
+//!
+//! Activity is tracked by a signed extension. `TrackActivity` belongs in the runtime's
+//! `SignedExtra`, and notes the block of every transaction that an account with an heir signs,
+//! whatever the call. Pallets don't need to know about the switch at all. Accounts that have
+//! nothing else to do can call `heartbeat`.
+
+use codec::{Decode, Encode};
+use sp_std::marker::PhantomData;
+use sp_runtime::{
+	traits::{One, SignedExtension, Saturating, Zero},
+	transaction_validity::TransactionValidityError,
+	RuntimeDebug,
+};
+use frame_support::{
+	decl_event, decl_module, decl_storage,
+	dispatch::DispatchResult,
+	ensure,
+	traits::{Currency, ExistenceRequirement, Get, WithdrawReason},
+	weights::DispatchInfo,
+};
+use frame_system::{self as system, ensure_signed};
+
+type BalanceOf<T> = <<T as Trait>::Currency as Currency<<T as system::Trait>::AccountId>>::Balance;
+
+/// Who inherits an account, and after how long
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct Switch<AccountId, BlockNumber> {
+	pub heir: AccountId,
+	/// How many blocks without activity the heir has to wait
+	pub timeout: BlockNumber,
+	/// The last block in which the account was active
+	pub last_active: BlockNumber,
+}
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The currency that is inherited
+	type Currency: Currency<Self::AccountId>;
+
+	/// The shortest timeout an account may choose, so a switch is not triggered by accident
+	type MinTimeout: Get<Self::BlockNumber>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as DeadMansSwitch {
+		/// The switch of each account that has named an heir
+		Switches get(fn switch): map T::AccountId => Option<Switch<T::AccountId, T::BlockNumber>>;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as system::Trait>::AccountId,
+		Balance = BalanceOf<T>,
+		BlockNumber = <T as system::Trait>::BlockNumber,
+	{
+		/// An account (first) named an heir (second), with a timeout
+		HeirSet(AccountId, AccountId, BlockNumber),
+		/// An account removed its switch
+		HeirRemoved(AccountId),
+		/// An heir (second) claimed the balance of an inactive account (first)
+		Claimed(AccountId, AccountId, Balance),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		const MinTimeout: T::BlockNumber = T::MinTimeout::get();
+
+		/// Name an heir, replacing any earlier one
+		fn set_heir(origin, heir: T::AccountId, timeout: T::BlockNumber) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			ensure!(heir != who, "cannot be your own heir");
+			ensure!(timeout >= T::MinTimeout::get(), "timeout is too short");
+
+			let last_active = <system::Module<T>>::block_number();
+			<Switches<T>>::insert(&who, Switch { heir: heir.clone(), timeout, last_active });
+			Self::deposit_event(RawEvent::HeirSet(who, heir, timeout));
+			Ok(())
+		}
+
+		fn remove_heir(origin) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			ensure!(<Switches<T>>::exists(&who), "no heir to remove");
+
+			<Switches<T>>::remove(&who);
+			Self::deposit_event(RawEvent::HeirRemoved(who));
+			Ok(())
+		}
+
+		/// Do nothing. Like any other transaction, this resets the caller's timer.
+		fn heartbeat(origin) -> DispatchResult {
+			ensure_signed(origin)?;
+			Ok(())
+		}
+
+		/// Claim the free balance of an account that has been inactive for its timeout, as far as
+		/// its locks allow. The switch is removed once nothing is left to claim.
+		fn claim(origin, owner: T::AccountId) -> DispatchResult {
+			let heir = ensure_signed(origin)?;
+			let switch = Self::switch(&owner).ok_or("account has no heir")?;
+			ensure!(switch.heir == heir, "not the heir of this account");
+			ensure!(Self::expired(&switch), "account is still active");
+
+			let amount = Self::usable_balance(&owner);
+			ensure!(!amount.is_zero(), "nothing to claim");
+			let imbalance = T::Currency::withdraw(
+				&owner,
+				amount,
+				WithdrawReason::Transfer.into(),
+				ExistenceRequirement::AllowDeath,
+			)?;
+			T::Currency::resolve_creating(&heir, imbalance);
+			if T::Currency::free_balance(&owner).is_zero() {
+				<Switches<T>>::remove(&owner);
+			}
+
+			Self::deposit_event(RawEvent::Claimed(owner, heir, amount));
+			Ok(())
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// Whether the timer of `switch` has run out
+	pub fn expired(switch: &Switch<T::AccountId, T::BlockNumber>) -> bool {
+		let now = <system::Module<T>>::block_number();
+		now.saturating_sub(switch.last_active) >= switch.timeout
+	}
+
+	/// How much of the free balance of `who` may be transferred away. `Currency` does not list an
+	/// account's locks, but `ensure_can_withdraw` checks them, and a balance that satisfies the
+	/// locks still does when it grows. So this searches for the least balance `who` must keep.
+	pub fn usable_balance(who: &T::AccountId) -> BalanceOf<T> {
+		let free = T::Currency::free_balance(who);
+		let reasons = WithdrawReason::Transfer.into();
+		let can_keep = |kept: BalanceOf<T>| {
+			T::Currency::ensure_can_withdraw(who, free - kept, reasons, kept).is_ok()
+		};
+
+		// Keeping `high` always satisfies the locks, keeping `low` never does
+		let (mut low, mut high) = (Zero::zero(), free);
+		if can_keep(low) {
+			return free
+		}
+		if !can_keep(high) {
+			return Zero::zero()
+		}
+		while high - low > One::one() {
+			let middle = low + (high - low) / 2u32.into();
+			if can_keep(middle) {
+				high = middle;
+			} else {
+				low = middle;
+			}
+		}
+		free - high
+	}
+
+	/// Reset the timer of `who`, if they have a switch
+	pub fn note_activity(who: &T::AccountId) {
+		// Accounts without a switch are left alone, so that tracking costs them no storage
+		<Switches<T>>::mutate(who, |switch| {
+			if let Some(switch) = switch {
+				switch.last_active = <system::Module<T>>::block_number();
+			}
+		});
+	}
+}
+
+/// Resets the timer of the signer of every transaction
+#[derive(Encode, Decode, Clone, Eq, PartialEq)]
+pub struct TrackActivity<T: Trait + Send + Sync>(PhantomData<T>);
+
+impl<T: Trait + Send + Sync> TrackActivity<T> {
+	pub fn new() -> Self {
+		Self(PhantomData)
+	}
+}
+
+impl<T: Trait + Send + Sync> sp_std::fmt::Debug for TrackActivity<T> {
+	#[cfg(feature = "std")]
+	fn fmt(&self, f: &mut sp_std::fmt::Formatter) -> sp_std::fmt::Result {
+		write!(f, "TrackActivity")
+	}
+	#[cfg(not(feature = "std"))]
+	fn fmt(&self, _: &mut sp_std::fmt::Formatter) -> sp_std::fmt::Result {
+		Ok(())
+	}
+}
+
+impl<T: Trait + Send + Sync> SignedExtension for TrackActivity<T> {
+	type AccountId = T::AccountId;
+	type Call = <T as system::Trait>::Call;
+	type AdditionalSigned = ();
+	type DispatchInfo = DispatchInfo;
+	type Pre = ();
+
+	fn additional_signed(&self) -> Result<(), TransactionValidityError> {
+		Ok(())
+	}
+
+	// `validate` runs in the pool as well, where writes are thrown away. `pre_dispatch` only runs
+	// when the transaction is included in a block, so that is where the timer is reset.
+	fn pre_dispatch(
+		self,
+		who: &Self::AccountId,
+		_call: &Self::Call,
+		_info: Self::DispatchInfo,
+		_len: usize,
+	) -> Result<(), TransactionValidityError> {
+		Module::<T>::note_activity(who);
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use event_assert::assert_has_event;
+	use sp_core::H256;
+	use frame_support::{
+		assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types,
+		traits::{LockableCurrency, WithdrawReasons},
+	};
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup},
+		Perbill,
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const ExistentialDeposit: u64 = 0;
+		pub const TransferFee: u64 = 0;
+		pub const CreationFee: u64 = 0;
+
+		pub const MinTimeout: u64 = 10;
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	impl balances::Trait for TestRuntime {
+		type Balance = u64;
+		type OnFreeBalanceZero = ();
+		type OnNewAccount = ();
+		type Event = TestEvent;
+		type TransferPayment = ();
+		type DustRemoval = ();
+		type ExistentialDeposit = ExistentialDeposit;
+		type TransferFee = TransferFee;
+		type CreationFee = CreationFee;
+	}
+
+	mod dead_mans_switch {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			balances<T>,
+			dead_mans_switch<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type Currency = balances::Module<Self>;
+		type MinTimeout = MinTimeout;
+	}
+
+	pub type System = system::Module<TestRuntime>;
+	pub type Balances = balances::Module<TestRuntime>;
+	pub type DeadMansSwitch = Module<TestRuntime>;
+
+	/// Account 1 names account 2 as heir with a timeout of 10 blocks, in block 1
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		let mut t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		balances::GenesisConfig::<TestRuntime> {
+			balances: vec![(1, 100), (2, 10)],
+			vesting: vec![],
+		}
+		.assimilate_storage(&mut t)
+		.unwrap();
+		let mut ext: sp_io::TestExternalities = t.into();
+		ext.execute_with(|| {
+			System::set_block_number(1);
+			assert_ok!(DeadMansSwitch::set_heir(Origin::signed(1), 2, 10));
+		});
+		ext
+	}
+
+	/// Include a transaction signed by `who` in block `n`, the way block execution does
+	fn transact_at(n: u64, who: u64) {
+		System::set_block_number(n);
+		let info = Default::default();
+		assert_ok!(TrackActivity::<TestRuntime>::new().pre_dispatch(&who, &(), info, 0));
+	}
+
+	#[test]
+	fn heir_claims_after_the_timeout() {
+		new_test_ext().execute_with(|| {
+			assert_has_event::<TestRuntime>(RawEvent::HeirSet(1, 2, 10));
+
+			System::set_block_number(10);
+			assert_err!(DeadMansSwitch::claim(Origin::signed(2), 1), "account is still active");
+
+			System::set_block_number(11);
+			assert_err!(
+				DeadMansSwitch::claim(Origin::signed(3), 1),
+				"not the heir of this account"
+			);
+			assert_ok!(DeadMansSwitch::claim(Origin::signed(2), 1));
+			assert_eq!(Balances::free_balance(&1), 0);
+			assert_eq!(Balances::free_balance(&2), 110);
+			assert_eq!(DeadMansSwitch::switch(&1), None);
+			assert_has_event::<TestRuntime>(RawEvent::Claimed(1, 2, 100));
+		})
+	}
+
+	#[test]
+	fn locked_funds_are_claimed_once_unlocked() {
+		new_test_ext().execute_with(|| {
+			Balances::set_lock(*b"testlock", &1, 40, 20, WithdrawReasons::all());
+
+			System::set_block_number(11);
+			assert_eq!(DeadMansSwitch::usable_balance(&1), 60);
+			assert_ok!(DeadMansSwitch::claim(Origin::signed(2), 1));
+			assert_eq!(Balances::free_balance(&1), 40);
+			assert_eq!(Balances::free_balance(&2), 70);
+			assert_has_event::<TestRuntime>(RawEvent::Claimed(1, 2, 60));
+
+			// The switch stays until the locked funds are claimed as well
+			assert_err!(DeadMansSwitch::claim(Origin::signed(2), 1), "nothing to claim");
+			System::set_block_number(20);
+			assert_ok!(DeadMansSwitch::claim(Origin::signed(2), 1));
+			assert_eq!(Balances::free_balance(&2), 110);
+			assert_eq!(DeadMansSwitch::switch(&1), None);
+		})
+	}
+
+	#[test]
+	fn any_transaction_resets_the_timer() {
+		new_test_ext().execute_with(|| {
+			transact_at(8, 1);
+			assert_eq!(DeadMansSwitch::switch(&1).unwrap().last_active, 8);
+
+			System::set_block_number(17);
+			assert_err!(DeadMansSwitch::claim(Origin::signed(2), 1), "account is still active");
+			System::set_block_number(18);
+			assert_ok!(DeadMansSwitch::claim(Origin::signed(2), 1));
+		})
+	}
+
+	#[test]
+	fn heartbeat_is_a_transaction_like_any_other() {
+		new_test_ext().execute_with(|| {
+			// Block execution runs the extension before the call
+			transact_at(9, 1);
+			assert_ok!(DeadMansSwitch::heartbeat(Origin::signed(1)));
+
+			System::set_block_number(11);
+			assert_err!(DeadMansSwitch::claim(Origin::signed(2), 1), "account is still active");
+		})
+	}
+
+	#[test]
+	fn accounts_without_a_switch_are_not_tracked() {
+		new_test_ext().execute_with(|| {
+			transact_at(5, 3);
+			assert_eq!(DeadMansSwitch::switch(&3), None);
+
+			// The validation in the pool does not touch storage either
+			let info = Default::default();
+			assert_ok!(TrackActivity::<TestRuntime>::new().validate(&1, &(), info, 0));
+			assert_eq!(DeadMansSwitch::switch(&1).unwrap().last_active, 1);
+		})
+	}
+
+	#[test]
+	fn switch_can_be_changed_or_removed() {
+		new_test_ext().execute_with(|| {
+			assert_err!(
+				DeadMansSwitch::set_heir(Origin::signed(1), 1, 10),
+				"cannot be your own heir"
+			);
+			assert_err!(DeadMansSwitch::set_heir(Origin::signed(1), 3, 9), "timeout is too short");
+
+			assert_ok!(DeadMansSwitch::set_heir(Origin::signed(1), 3, 20));
+			System::set_block_number(30);
+			assert_err!(
+				DeadMansSwitch::claim(Origin::signed(2), 1),
+				"not the heir of this account"
+			);
+
+			assert_ok!(DeadMansSwitch::remove_heir(Origin::signed(1)));
+			assert_has_event::<TestRuntime>(RawEvent::HeirRemoved(1));
+			assert_err!(DeadMansSwitch::claim(Origin::signed(3), 1), "account has no heir");
+			assert_err!(DeadMansSwitch::remove_heir(Origin::signed(1)), "no heir to remove");
+		})
+	}
+}
@@ -0,0 +1,81 @@
+//! Handling for things that should never happen
+//!
+//! A runtime must not panic, so even a broken invariant needs a fallback. But a silent fallback
+//! hides the bug that broke the invariant. The helpers here do both: they log an error and
+//! continue with the fallback, and in debug builds they also panic, so the bug fails a test
+//! before it reaches a chain. The Wasm runtime is built without debug assertions, so on chain
+//! only the log message remains.
+//!
+//! Newer versions of FRAME ship helpers of the same names in `frame_support::traits`.
+
+/// The log target of defensive failures
+pub const LOG_TARGET: &str = "runtime::defensive";
+
+/// Report that something that should never happen has happened. Logs `$proof`, the reason it
+/// should not have happened, and panics in debug builds.
+#[macro_export]
+macro_rules! defensive {
+	($proof:expr) => {{
+		// The Wasm runtime drops `log` messages until the runtime logger forwards them to the node
+		$crate::frame_support::debug::RuntimeLogger::init();
+		$crate::frame_support::debug::error!(
+			target: $crate::defensive::LOG_TARGET,
+			"defensive failure: {}",
+			$proof
+		);
+		debug_assert!(false, "defensive failure: {}", $proof);
+	}};
+}
+
+/// Unwrapping that falls back to a value instead of panicking, and reports the fallback with
+/// `defensive!`. Use it only where the fallback is truly unexpected; expected failures are
+/// ordinary errors.
+pub trait Defensive<T> {
+	/// The contained value, or `default` after reporting a defensive failure
+	fn defensive_unwrap_or(self, default: T) -> T;
+
+	/// The contained value, or `T::default()` after reporting a defensive failure
+	fn defensive_unwrap_or_default(self) -> T
+	where
+		T: Default;
+}
+
+impl<T> Defensive<T> for Option<T> {
+	fn defensive_unwrap_or(self, default: T) -> T {
+		match self {
+			Some(value) => value,
+			None => {
+				defensive!("expected Some, found None");
+				default
+			}
+		}
+	}
+
+	fn defensive_unwrap_or_default(self) -> T
+	where
+		T: Default,
+	{
+		self.defensive_unwrap_or(T::default())
+	}
+}
+
+impl<T, E: sp_std::fmt::Debug> Defensive<T> for Result<T, E> {
+	fn defensive_unwrap_or(self, default: T) -> T {
+		match self {
+			Ok(value) => value,
+			Err(error) => {
+				frame_support::debug::RuntimeLogger::init();
+				frame_support::debug::error!(target: LOG_TARGET, "unexpected error: {:?}", error);
+				defensive!("expected Ok, found Err");
+				default
+			}
+		}
+	}
+
+	fn defensive_unwrap_or_default(self) -> T
+	where
+		T: Default,
+	{
+		self.defensive_unwrap_or(T::default())
+	}
+}
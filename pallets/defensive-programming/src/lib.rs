@@ -0,0 +1,299 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! Defensive programming for invariants that should never break
+//!
+//! Members collect points. Three storage items describe the same members, and must agree:
+//! * `Members`, a sorted list of all members
+//! * `Points`, which holds an entry for every member and nobody else
+//! * `TotalPoints`, the sum of all entries in `Points`
+//!
+//! The calls check everything that depends on the caller, such as whether they are a member, and
+//! return ordinary errors. What they cannot check is whether the three items still agree: a
+//! member without an entry in `Members`, or a total below one member's points, would be a bug in
+//! this pallet or in a migration. When a call finds such a state, it reports it with the helpers
+//! in the `defensive` module and carries on with a safe fallback instead of panicking.
+
+use sp_std::prelude::*;
+use frame_support::{decl_event, decl_module, decl_storage, dispatch::DispatchResult};
+use frame_system::{self as system, ensure_signed};
+
+#[macro_use]
+pub mod defensive;
+pub use defensive::Defensive;
+
+// Used by the `defensive!` macro
+#[doc(hidden)]
+pub use frame_support;
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as DefensiveProgramming {
+		/// All members, sorted
+		Members get(fn members): Vec<T::AccountId>;
+
+		/// The points of each member
+		Points get(fn points): map T::AccountId => Option<u32>;
+
+		/// The sum of all members' points
+		TotalPoints get(fn total_points): u64;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as system::Trait>::AccountId,
+	{
+		/// An account became a member
+		Joined(AccountId),
+		/// A member earned points, and now has this many
+		Earned(AccountId, u32),
+		/// A member left, taking this many points with them
+		Left(AccountId, u32),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		fn join(origin) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let mut members = Self::members();
+			match members.binary_search(&who) {
+				Ok(_) => return Err("already a member".into()),
+				Err(index) => members.insert(index, who.clone()),
+			}
+			<Members<T>>::put(members);
+			<Points<T>>::insert(&who, 0);
+
+			Self::deposit_event(RawEvent::Joined(who));
+			Ok(())
+		}
+
+		fn earn(origin, amount: u32) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			// Callers who are not members are expected, so this is an ordinary error
+			let points = Self::points(&who).ok_or("not a member")?;
+			let new_points = points.checked_add(amount).ok_or("points overflowed")?;
+			<Points<T>>::insert(&who, new_points);
+			// The sum of `u32`s needs billions of members to overflow a `u64`
+			TotalPoints::mutate(|total| *total = total.saturating_add(u64::from(amount)));
+
+			Self::deposit_event(RawEvent::Earned(who, new_points));
+			Ok(())
+		}
+
+		fn leave(origin) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let points = Self::points(&who).ok_or("not a member")?;
+
+			// Every account with points is a member, so the search cannot fail. If it does, the
+			// account is still removed from `Points` below, which repairs the disagreement.
+			let mut members = Self::members();
+			match members.binary_search(&who) {
+				Ok(index) => {
+					members.remove(index);
+					<Members<T>>::put(members);
+				}
+				Err(_) => defensive!("an account with points must be in the member list"),
+			}
+			<Points<T>>::remove(&who);
+			// The total includes the member's points, so this cannot underflow. If it does, zero
+			// is the closest the total can get to correct.
+			TotalPoints::mutate(|total| {
+				*total = total.checked_sub(u64::from(points)).defensive_unwrap_or(0)
+			});
+
+			Self::deposit_event(RawEvent::Left(who, points));
+			Ok(())
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use event_assert::assert_last_event;
+	use sp_core::H256;
+	use frame_support::{
+		assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types,
+		StorageValue,
+	};
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup},
+		Perbill,
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	mod defensive_programming {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			defensive_programming<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+	}
+
+	pub type DefensiveProgramming = Module<TestRuntime>;
+
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		let t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		t.into()
+	}
+
+	/// Accounts 1 and 2 join, and earn 10 and 20 points
+	fn two_members() {
+		for who in 1..=2 {
+			assert_ok!(DefensiveProgramming::join(Origin::signed(who)));
+			assert_ok!(DefensiveProgramming::earn(Origin::signed(who), who as u32 * 10));
+		}
+	}
+
+	#[test]
+	fn members_earn_and_leave() {
+		new_test_ext().execute_with(|| {
+			two_members();
+			assert_eq!(DefensiveProgramming::members(), vec![1, 2]);
+			assert_eq!(DefensiveProgramming::total_points(), 30);
+
+			assert_ok!(DefensiveProgramming::leave(Origin::signed(1)));
+			assert_last_event::<TestRuntime>(RawEvent::Left(1, 10));
+			assert_eq!(DefensiveProgramming::members(), vec![2]);
+			assert_eq!(DefensiveProgramming::points(1), None);
+			assert_eq!(DefensiveProgramming::total_points(), 20);
+		})
+	}
+
+	#[test]
+	fn expected_failures_are_errors() {
+		new_test_ext().execute_with(|| {
+			assert_err!(DefensiveProgramming::earn(Origin::signed(1), 5), "not a member");
+			assert_err!(DefensiveProgramming::leave(Origin::signed(1)), "not a member");
+			assert_ok!(DefensiveProgramming::join(Origin::signed(1)));
+			assert_err!(DefensiveProgramming::join(Origin::signed(1)), "already a member");
+			assert_ok!(DefensiveProgramming::earn(Origin::signed(1), u32::max_value()));
+			assert_err!(DefensiveProgramming::earn(Origin::signed(1), 1), "points overflowed");
+		})
+	}
+
+	#[test]
+	fn helpers_pass_values_through() {
+		assert_eq!(Some(3).defensive_unwrap_or(0), 3);
+		assert_eq!(Ok::<u32, ()>(3).defensive_unwrap_or_default(), 3);
+	}
+
+	// Debug builds, such as a plain `cargo test`, panic on a defensive failure, so that the bug
+	// fails the test that triggered it
+	#[cfg(debug_assertions)]
+	mod debug_build {
+		use super::*;
+
+		#[test]
+		#[should_panic(expected = "defensive failure: expected Some, found None")]
+		fn unwrap_panics() {
+			None::<u32>.defensive_unwrap_or(0);
+		}
+
+		#[test]
+		#[should_panic(expected = "defensive failure: an account with points must be in the member list")]
+		fn missing_member_panics() {
+			new_test_ext().execute_with(|| {
+				two_members();
+				<Members<TestRuntime>>::put(vec![2]);
+				let _ = DefensiveProgramming::leave(Origin::signed(1));
+			})
+		}
+
+		#[test]
+		#[should_panic(expected = "defensive failure: expected Some, found None")]
+		fn low_total_panics() {
+			new_test_ext().execute_with(|| {
+				two_members();
+				TotalPoints::put(5);
+				let _ = DefensiveProgramming::leave(Origin::signed(1));
+			})
+		}
+	}
+
+	// Release builds, such as `cargo test --release` and the Wasm runtime, log the failure and
+	// continue with the fallback
+	#[cfg(not(debug_assertions))]
+	mod release_build {
+		use super::*;
+
+		#[test]
+		fn unwrap_falls_back() {
+			assert_eq!(None.defensive_unwrap_or(7), 7);
+			assert_eq!(Err::<u32, &str>("broken").defensive_unwrap_or_default(), 0);
+		}
+
+		#[test]
+		fn missing_member_still_leaves() {
+			new_test_ext().execute_with(|| {
+				two_members();
+				<Members<TestRuntime>>::put(vec![2]);
+
+				assert_ok!(DefensiveProgramming::leave(Origin::signed(1)));
+				assert_eq!(DefensiveProgramming::points(1), None);
+				assert_eq!(DefensiveProgramming::members(), vec![2]);
+				assert_eq!(DefensiveProgramming::total_points(), 20);
+			})
+		}
+
+		#[test]
+		fn low_total_falls_back_to_zero() {
+			new_test_ext().execute_with(|| {
+				two_members();
+				TotalPoints::put(5);
+
+				assert_ok!(DefensiveProgramming::leave(Origin::signed(1)));
+				assert_eq!(DefensiveProgramming::total_points(), 0);
+			})
+		}
+	}
+}
@@ -0,0 +1,528 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! Telemetry from registered devices, such as sensors
+//!
+//! An account registers the ed25519 key of each device it owns. Devices have no funds, so they
+//! submit their readings as unsigned transactions, signed with the device key. The pallet's
+//! `ValidateUnsigned` implementation decides which readings may enter the pool: only readings of
+//! registered devices, correctly signed, newer than the device's last reading, and at least
+//! `MinInterval` blocks after it. The interval rate limits each device, since no fee does.
+//!
+//! The pallet keeps the latest `Window` readings of each device, and the count, mean, minimum and
+//! maximum over them, so that other pallets and clients can read a rolling aggregate without
+//! iterating readings themselves.
+
+use codec::{Decode, Encode};
+use sp_std::prelude::*;
+use sp_runtime::{
+	traits::{Member, Saturating},
+	transaction_validity::{
+		InvalidTransaction, TransactionPriority, TransactionValidity, ValidTransaction,
+	},
+	RuntimeAppPublic, RuntimeDebug,
+};
+use frame_support::{
+	decl_event, decl_module, decl_storage,
+	dispatch::DispatchResult,
+	ensure,
+	traits::Get,
+	Parameter,
+};
+use frame_system::{self as system, ensure_none, ensure_signed};
+
+/// The key type of device keys
+pub const KEY_TYPE: sp_application_crypto::KeyTypeId = sp_application_crypto::KeyTypeId(*b"iotd");
+
+/// The application-specific crypto devices sign readings with
+pub mod crypto {
+	use super::KEY_TYPE;
+	use sp_application_crypto::{app_crypto, ed25519};
+	app_crypto!(ed25519, KEY_TYPE);
+}
+
+/// A device identifier that a runtime can use for `Trait::DeviceId`
+pub type DeviceId = crypto::Public;
+
+/// A measurement that a device signs
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct Reading<DeviceId, BlockNumber> {
+	pub device: DeviceId,
+	/// The block in which the device took the reading
+	pub taken_at: BlockNumber,
+	/// What was measured, in a unit the device and its readers agree on
+	pub value: i32,
+}
+
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct Device<AccountId, BlockNumber> {
+	pub owner: AccountId,
+	/// Readings taken before are stale, so that they can't be replayed after registering again
+	pub registered_at: BlockNumber,
+	/// The block in which the latest accepted reading was taken
+	pub last_reading: Option<BlockNumber>,
+}
+
+/// Aggregates over the latest readings of a device
+#[derive(Encode, Decode, Clone, PartialEq, Eq, Default, RuntimeDebug)]
+pub struct Aggregate {
+	pub count: u32,
+	/// Rounded towards zero
+	pub mean: i32,
+	pub min: i32,
+	pub max: i32,
+}
+
+impl Aggregate {
+	/// The aggregate of `values`, or the default for no values
+	pub fn of(values: impl Iterator<Item = i32>) -> Self {
+		let (mut count, mut sum) = (0u32, 0i64);
+		let (mut min, mut max) = (i32::max_value(), i32::min_value());
+		for value in values {
+			count += 1;
+			sum += i64::from(value);
+			min = min.min(value);
+			max = max.max(value);
+		}
+		if count == 0 {
+			return Aggregate::default();
+		}
+		// The mean of `i32`s is between their minimum and maximum, so it fits
+		let mean = (sum / i64::from(count)) as i32;
+		Aggregate { count, mean, min, max }
+	}
+}
+
+/// Why a reading is rejected
+#[derive(Clone, Copy, PartialEq, Eq, RuntimeDebug)]
+pub enum ReadingError {
+	/// The device is not registered
+	UnknownDevice,
+	/// The device did not sign the reading
+	BadSignature,
+	/// The reading was taken in a block that has not started yet
+	Future,
+	/// The reading is older than the device's registration or no newer than its last reading,
+	/// for example a replay
+	Stale,
+	/// The reading came less than `MinInterval` blocks after the device's last one
+	TooFrequent,
+}
+
+impl From<ReadingError> for InvalidTransaction {
+	fn from(error: ReadingError) -> Self {
+		match error {
+			ReadingError::UnknownDevice | ReadingError::BadSignature => {
+				InvalidTransaction::BadProof
+			}
+			ReadingError::Future => InvalidTransaction::Future,
+			ReadingError::Stale => InvalidTransaction::Stale,
+			ReadingError::TooFrequent => InvalidTransaction::ExhaustsResources,
+		}
+	}
+}
+
+impl From<ReadingError> for &'static str {
+	fn from(error: ReadingError) -> Self {
+		match error {
+			ReadingError::UnknownDevice => "unknown device",
+			ReadingError::BadSignature => "invalid device signature",
+			ReadingError::Future => "reading from the future",
+			ReadingError::Stale => "reading not newer than the last",
+			ReadingError::TooFrequent => "reading too soon after the last",
+		}
+	}
+}
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The key that identifies a device and signs its readings
+	type DeviceId: Member + Parameter + RuntimeAppPublic + Default;
+
+	/// The fewest blocks between the readings of a device
+	type MinInterval: Get<Self::BlockNumber>;
+
+	/// The number of latest readings that are kept and aggregated per device
+	type Window: Get<u32>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as DeviceTelemetry {
+		Devices get(fn device): map T::DeviceId => Option<Device<T::AccountId, T::BlockNumber>>;
+
+		/// The latest readings of each device, oldest first, as (block taken, value)
+		Readings get(fn readings): map T::DeviceId => Vec<(T::BlockNumber, i32)>;
+
+		/// The aggregate over each device's `Readings`
+		Aggregates get(fn aggregate): map T::DeviceId => Aggregate;
+
+		/// The number of the latest block that started. The system pallet forgets the block number
+		/// when a block is finalized, and the pool validates transactions on a finalized state.
+		LastBlock get(fn last_block): T::BlockNumber;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as system::Trait>::AccountId,
+		DeviceId = <T as Trait>::DeviceId,
+	{
+		/// An account registered a device
+		DeviceRegistered(AccountId, DeviceId),
+		DeviceDeregistered(DeviceId),
+		/// A device submitted a reading with this value
+		ReadingReceived(DeviceId, i32),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		const MinInterval: T::BlockNumber = T::MinInterval::get();
+
+		const Window: u32 = T::Window::get();
+
+		/// Register a device the caller owns
+		fn register(origin, device: T::DeviceId) -> DispatchResult {
+			let owner = ensure_signed(origin)?;
+			ensure!(!<Devices<T>>::exists(&device), "device already registered");
+
+			let registered_at = <system::Module<T>>::block_number();
+			<Devices<T>>::insert(&device, Device {
+				owner: owner.clone(),
+				registered_at,
+				last_reading: None,
+			});
+			Self::deposit_event(RawEvent::DeviceRegistered(owner, device));
+			Ok(())
+		}
+
+		/// Deregister a device of the caller, and forget its readings
+		fn deregister(origin, device: T::DeviceId) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let registered = Self::device(&device).ok_or("unknown device")?;
+			ensure!(registered.owner == who, "not the owner");
+
+			<Devices<T>>::remove(&device);
+			<Readings<T>>::remove(&device);
+			<Aggregates<T>>::remove(&device);
+			Self::deposit_event(RawEvent::DeviceDeregistered(device));
+			Ok(())
+		}
+
+		/// Record a reading. `validate_unsigned` has checked it already, but the checks are
+		/// repeated so that the call is safe on its own.
+		fn submit_reading(
+			origin,
+			reading: Reading<T::DeviceId, T::BlockNumber>,
+			signature: <T::DeviceId as RuntimeAppPublic>::Signature
+		) -> DispatchResult {
+			ensure_none(origin)?;
+			let mut device = Self::check_reading(&reading, &signature)
+				.map_err(<&'static str>::from)?;
+
+			device.last_reading = Some(reading.taken_at);
+			<Devices<T>>::insert(&reading.device, device);
+			let readings = <Readings<T>>::mutate(&reading.device, |readings| {
+				readings.push((reading.taken_at, reading.value));
+				// The window was full at most before the push, so one removal is enough
+				if readings.len() as u32 > T::Window::get() {
+					readings.remove(0);
+				}
+				readings.clone()
+			});
+			let aggregate = Aggregate::of(readings.into_iter().map(|(_, value)| value));
+			<Aggregates<T>>::insert(&reading.device, aggregate);
+
+			Self::deposit_event(RawEvent::ReadingReceived(reading.device, reading.value));
+			Ok(())
+		}
+
+		fn on_initialize(now: T::BlockNumber) {
+			<LastBlock<T>>::put(now);
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// Check a reading against its device, and return the device
+	fn check_reading(
+		reading: &Reading<T::DeviceId, T::BlockNumber>,
+		signature: &<T::DeviceId as RuntimeAppPublic>::Signature,
+	) -> Result<Device<T::AccountId, T::BlockNumber>, ReadingError> {
+		let device = Self::device(&reading.device).ok_or(ReadingError::UnknownDevice)?;
+		if !reading.device.verify(&reading.encode(), signature) {
+			return Err(ReadingError::BadSignature);
+		}
+		if reading.taken_at > Self::last_block() {
+			return Err(ReadingError::Future);
+		}
+		if reading.taken_at < device.registered_at {
+			return Err(ReadingError::Stale);
+		}
+		if let Some(last) = device.last_reading {
+			if reading.taken_at <= last {
+				return Err(ReadingError::Stale);
+			}
+			if reading.taken_at < last.saturating_add(T::MinInterval::get()) {
+				return Err(ReadingError::TooFrequent);
+			}
+		}
+		Ok(device)
+	}
+}
+
+impl<T: Trait> frame_support::unsigned::ValidateUnsigned for Module<T> {
+	type Call = Call<T>;
+
+	fn validate_unsigned(call: &Self::Call) -> TransactionValidity {
+		let (reading, signature) = match call {
+			Call::submit_reading(reading, signature) => (reading, signature),
+			_ => return InvalidTransaction::Call.into(),
+		};
+		if let Err(error) = Self::check_reading(reading, signature) {
+			return InvalidTransaction::from(error).into();
+		}
+
+		Ok(ValidTransaction {
+			priority: TransactionPriority::max_value() / 2,
+			requires: vec![],
+			// One reading per device at a time. Once it is included, the next reading of the
+			// device has to wait for `MinInterval` anyway.
+			provides: vec![reading.device.encode()],
+			longevity: 64,
+			propagate: true,
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use event_assert::{assert_has_event, assert_last_event};
+
+	use sp_core::H256;
+	use frame_support::{
+		assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types,
+		unsigned::ValidateUnsigned,
+	};
+	use frame_system::RawOrigin;
+	use sp_runtime::{
+		testing::{Header, UintAuthorityId},
+		traits::{BlakeTwo256, IdentityLookup, OnInitialize},
+		Perbill,
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const MinInterval: u64 = 5;
+		pub const Window: u32 = 3;
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	mod device_telemetry {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			device_telemetry<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type DeviceId = UintAuthorityId;
+		type MinInterval = MinInterval;
+		type Window = Window;
+	}
+
+	pub type System = system::Module<TestRuntime>;
+	pub type DeviceTelemetry = Module<TestRuntime>;
+
+	const OWNER: u64 = 1;
+	const SENSOR: UintAuthorityId = UintAuthorityId(7);
+
+	/// Account 1 owns device 7, and the chain is at block 1
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		let mut ext: sp_io::TestExternalities = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap()
+			.into();
+		ext.execute_with(|| {
+			run_to_block(1);
+			assert_ok!(DeviceTelemetry::register(Origin::signed(OWNER), SENSOR));
+		});
+		ext
+	}
+
+	fn run_to_block(n: u64) {
+		while System::block_number() < n {
+			let next = System::block_number() + 1;
+			System::set_block_number(next);
+			DeviceTelemetry::on_initialize(next);
+		}
+	}
+
+	/// A reading of `value` taken in block `taken_at` by the sensor, signed with `signer`'s key
+	fn signed_reading(taken_at: u64, value: i32, signer: u64) -> Call<TestRuntime> {
+		let reading = Reading { device: SENSOR, taken_at, value };
+		let signature = UintAuthorityId(signer).sign(&reading.encode()).unwrap();
+		Call::submit_reading(reading, signature)
+	}
+
+	/// Validates `call` like the pool would, and dispatches it if it is valid
+	fn submit(call: Call<TestRuntime>) -> TransactionValidity {
+		let validity = DeviceTelemetry::validate_unsigned(&call);
+		if validity.is_ok() {
+			if let Call::submit_reading(reading, signature) = call {
+				assert_ok!(DeviceTelemetry::submit_reading(
+					RawOrigin::None.into(),
+					reading,
+					signature
+				));
+			}
+		}
+		validity
+	}
+
+	fn invalid(error: InvalidTransaction) -> TransactionValidity {
+		error.into()
+	}
+
+	#[test]
+	fn aggregates_are_computed() {
+		assert_eq!(Aggregate::of(vec![].into_iter()), Aggregate::default());
+		assert_eq!(
+			Aggregate::of(vec![3, -4, 10].into_iter()),
+			Aggregate { count: 3, mean: 3, min: -4, max: 10 },
+		);
+		// The sum overflows an `i32`, but the mean does not
+		let max = i32::max_value();
+		assert_eq!(
+			Aggregate::of(vec![max; 2].into_iter()),
+			Aggregate { count: 2, mean: max, min: max, max },
+		);
+	}
+
+	#[test]
+	fn devices_are_registered_by_their_owner() {
+		new_test_ext().execute_with(|| {
+			assert_err!(
+				DeviceTelemetry::register(Origin::signed(2), SENSOR),
+				"device already registered"
+			);
+			assert_err!(DeviceTelemetry::deregister(Origin::signed(2), SENSOR), "not the owner");
+			assert_has_event::<TestRuntime>(RawEvent::DeviceRegistered(OWNER, SENSOR));
+			assert_ok!(DeviceTelemetry::deregister(Origin::signed(OWNER), SENSOR));
+			assert_last_event::<TestRuntime>(RawEvent::DeviceDeregistered(SENSOR));
+			assert_eq!(submit(signed_reading(1, 20, 7)), invalid(InvalidTransaction::BadProof));
+
+			// Readings from before the device was registered again can't be replayed
+			run_to_block(3);
+			assert_ok!(DeviceTelemetry::register(Origin::signed(2), SENSOR));
+			assert_eq!(submit(signed_reading(1, 20, 7)), invalid(InvalidTransaction::Stale));
+			assert!(submit(signed_reading(3, 20, 7)).is_ok());
+		})
+	}
+
+	#[test]
+	fn readings_roll_through_the_window() {
+		new_test_ext().execute_with(|| {
+			for (i, value) in [10, 20, 30, 40].iter().enumerate() {
+				let taken_at = 1 + 5 * i as u64;
+				run_to_block(taken_at);
+				assert!(submit(signed_reading(taken_at, *value, 7)).is_ok());
+			}
+
+			// The first reading dropped out of the window
+			assert_eq!(DeviceTelemetry::readings(SENSOR), vec![(6, 20), (11, 30), (16, 40)]);
+			assert_eq!(
+				DeviceTelemetry::aggregate(SENSOR),
+				Aggregate { count: 3, mean: 30, min: 20, max: 40 },
+			);
+			assert_eq!(DeviceTelemetry::device(SENSOR).unwrap().last_reading, Some(16));
+			assert_last_event::<TestRuntime>(RawEvent::ReadingReceived(SENSOR, 40));
+		})
+	}
+
+	#[test]
+	fn readings_must_be_signed_by_the_device() {
+		new_test_ext().execute_with(|| {
+			assert_eq!(submit(signed_reading(1, 20, 8)), invalid(InvalidTransaction::BadProof));
+			assert!(DeviceTelemetry::readings(SENSOR).is_empty());
+		})
+	}
+
+	#[test]
+	fn devices_are_rate_limited() {
+		new_test_ext().execute_with(|| {
+			assert!(submit(signed_reading(1, 20, 7)).is_ok());
+			// Replaying the same reading, or an older one, is stale
+			assert_eq!(submit(signed_reading(1, 20, 7)), invalid(InvalidTransaction::Stale));
+
+			run_to_block(5);
+			assert_eq!(
+				submit(signed_reading(5, 21, 7)),
+				invalid(InvalidTransaction::ExhaustsResources)
+			);
+			// The call itself repeats the check, for readings that bypass the pool
+			if let Call::submit_reading(reading, signature) = signed_reading(5, 21, 7) {
+				assert_err!(
+					DeviceTelemetry::submit_reading(RawOrigin::None.into(), reading, signature),
+					"reading too soon after the last"
+				);
+			}
+
+			run_to_block(6);
+			assert!(submit(signed_reading(6, 21, 7)).is_ok());
+		})
+	}
+
+	#[test]
+	fn readings_from_the_future_are_rejected() {
+		new_test_ext().execute_with(|| {
+			assert_eq!(submit(signed_reading(2, 20, 7)), invalid(InvalidTransaction::Future));
+		})
+	}
+
+	#[test]
+	fn readings_share_a_tag_per_device() {
+		new_test_ext().execute_with(|| {
+			run_to_block(2);
+			let tag = |call| DeviceTelemetry::validate_unsigned(&call).unwrap().provides;
+			assert_eq!(tag(signed_reading(1, 20, 7)), tag(signed_reading(2, 21, 7)));
+		})
+	}
+}
@@ -0,0 +1,51 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use codec::{Codec, Decode, Encode};
+use sp_runtime::RuntimeDebug;
+use sp_std::vec::Vec;
+
+/// What a verification key may be used for
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, RuntimeDebug)]
+pub enum KeyPurpose {
+	/// Proving that one is the subject of the DID, such as when logging in
+	Authentication,
+	/// Signing claims, such as credentials
+	Assertion,
+}
+
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct VerificationKey<Public> {
+	pub key: Public,
+	pub purpose: KeyPurpose,
+}
+
+/// A way to reach the subject of a DID, such as a messaging inbox
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct Service {
+	/// Unique within the document
+	pub id: Vec<u8>,
+	pub endpoint: Vec<u8>,
+}
+
+/// What a DID resolves to
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct DidDocument<Public> {
+	/// The key that signs changes to the document
+	pub controller: Public,
+	pub keys: Vec<VerificationKey<Public>>,
+	pub services: Vec<Service>,
+	/// The number of changes so far, which the next change signs to prevent replays
+	pub nonce: u64,
+}
+
+// Resolution goes through a runtime API so that wallets and verifiers get the document as a whole,
+// without reading the pallet's storage.
+sp_api::decl_runtime_apis! {
+	pub trait DidApi<Hash, Public> where
+		Hash: Codec,
+		Public: Codec,
+	{
+		/// The document of `did`, if it exists
+		fn resolve(did: Hash) -> Option<DidDocument<Public>>;
+	}
+}
@@ -0,0 +1,414 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! Decentralized identifiers whose documents are changed by a controller key
+//!
+//! A DID names a subject, such as a person or an organization, independently of any account.
+//! It resolves to a document listing the subject's verification keys and service endpoints. Each
+//! document has a controller key, and only changes signed by the controller are applied. Anyone
+//! may submit them and pay the fees, so the subject needs no funded account.
+//!
+//! A DID is the hash of its first controller key, and creating it takes a signature of that key,
+//! so nobody can claim the DID of someone else's key. The DID stays the same when the controller
+//! is rotated. Each change signs the document's nonce, so a change can't be replayed.
+//!
+//! Wallets and verifiers resolve documents through the `DidApi`.
+
+use codec::{Decode, Encode};
+use sp_std::prelude::*;
+use sp_runtime::{
+	traits::{Hash, IdentifyAccount, Member, Verify},
+	RuntimeDebug,
+};
+use frame_support::{
+	decl_event, decl_module, decl_storage,
+	dispatch::DispatchResult,
+	ensure,
+	traits::Get,
+	Parameter,
+};
+use frame_system::{self as system, ensure_signed};
+
+pub use did_runtime_api::{DidDocument, KeyPurpose, Service, VerificationKey};
+
+/// A change to a document
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub enum Operation<Public> {
+	AddKey(VerificationKey<Public>),
+	RevokeKey(Public),
+	/// Hand control of the document to another key
+	RotateController(Public),
+	AddService(Service),
+	/// Remove the service with this id
+	RemoveService(Vec<u8>),
+}
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The keys that documents list and that control them
+	type Public: IdentifyAccount + Parameter + Member;
+
+	/// The signature controllers put on changes
+	type Signature: Verify<Signer = Self::Public> + Parameter;
+
+	/// The most verification keys a document may list
+	type MaxKeys: Get<u32>;
+
+	/// The most services a document may list
+	type MaxServices: Get<u32>;
+
+	/// The longest id or endpoint a service may have
+	type MaxServiceLength: Get<u32>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as Did {
+		/// The document of each DID
+		Documents get(fn document): map T::Hash => Option<DidDocument<T::Public>>;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		Hash = <T as system::Trait>::Hash,
+	{
+		/// A DID was created
+		DidCreated(Hash),
+		/// The document of a DID was changed; its new nonce
+		DidUpdated(Hash, u64),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		const MaxKeys: u32 = T::MaxKeys::get();
+
+		const MaxServices: u32 = T::MaxServices::get();
+
+		const MaxServiceLength: u32 = T::MaxServiceLength::get();
+
+		/// Create the DID of `controller`, which signs the DID to prove it holds the key
+		fn create(origin, controller: T::Public, signature: T::Signature) -> DispatchResult {
+			ensure_signed(origin)?;
+			let did = Self::did_of(&controller);
+			ensure!(!<Documents<T>>::exists(&did), "DID already exists");
+			ensure!(
+				signature.verify(&did.encode()[..], &controller),
+				"invalid controller signature"
+			);
+
+			<Documents<T>>::insert(&did, DidDocument {
+				controller,
+				keys: Vec::new(),
+				services: Vec::new(),
+				nonce: 0,
+			});
+			Self::deposit_event(RawEvent::DidCreated(did));
+			Ok(())
+		}
+
+		/// Apply `operation` to the document of `did`. The controller signs `update_payload`.
+		fn update(
+			origin,
+			did: T::Hash,
+			operation: Operation<T::Public>,
+			signature: T::Signature
+		) -> DispatchResult {
+			ensure_signed(origin)?;
+			let mut document = Self::document(&did).ok_or("no such DID")?;
+			let payload = Self::update_payload(&did, document.nonce, &operation);
+			ensure!(
+				signature.verify(&payload[..], &document.controller),
+				"invalid controller signature"
+			);
+
+			// Changes are made to a copy, so nothing is written if they fail
+			Self::apply(&mut document, operation)?;
+			document.nonce += 1;
+			let nonce = document.nonce;
+			<Documents<T>>::insert(&did, document);
+			Self::deposit_event(RawEvent::DidUpdated(did, nonce));
+			Ok(())
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// The DID whose first controller is `controller`
+	pub fn did_of(controller: &T::Public) -> T::Hash {
+		T::Hashing::hash_of(controller)
+	}
+
+	/// What the controller signs to apply `operation` to a document with `nonce`
+	pub fn update_payload(did: &T::Hash, nonce: u64, operation: &Operation<T::Public>) -> Vec<u8> {
+		(did, nonce, operation).encode()
+	}
+
+	fn apply(
+		document: &mut DidDocument<T::Public>,
+		operation: Operation<T::Public>,
+	) -> Result<(), &'static str> {
+		match operation {
+			Operation::AddKey(key) => {
+				ensure!(document.keys.len() < T::MaxKeys::get() as usize, "too many keys");
+				ensure!(document.keys.iter().all(|k| k.key != key.key), "key already added");
+				document.keys.push(key);
+			}
+			Operation::RevokeKey(key) => {
+				let index = document.keys.iter().position(|k| k.key == key).ok_or("no such key")?;
+				document.keys.remove(index);
+			}
+			Operation::RotateController(controller) => {
+				document.controller = controller;
+			}
+			Operation::AddService(service) => {
+				let max_length = T::MaxServiceLength::get() as usize;
+				ensure!(
+					service.id.len() <= max_length && service.endpoint.len() <= max_length,
+					"service is too long"
+				);
+				ensure!(
+					document.services.len() < T::MaxServices::get() as usize,
+					"too many services"
+				);
+				ensure!(
+					document.services.iter().all(|s| s.id != service.id),
+					"service id already used"
+				);
+				document.services.push(service);
+			}
+			Operation::RemoveService(id) => {
+				let index = document.services.iter().position(|s| s.id == id)
+					.ok_or("no such service")?;
+				document.services.remove(index);
+			}
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use event_assert::assert_last_event;
+
+	use sp_core::{sr25519, Pair, H256};
+	use frame_support::{
+		assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types,
+	};
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup},
+		Perbill,
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const MaxKeys: u32 = 2;
+		pub const MaxServices: u32 = 2;
+		pub const MaxServiceLength: u32 = 16;
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	mod did {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			did<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type Public = sr25519::Public;
+		type Signature = sr25519::Signature;
+		type MaxKeys = MaxKeys;
+		type MaxServices = MaxServices;
+		type MaxServiceLength = MaxServiceLength;
+	}
+
+	pub type Did = Module<TestRuntime>;
+
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap()
+			.into()
+	}
+
+	/// Account 1 submits all changes; it holds none of the keys
+	const SUBMITTER: u64 = 1;
+
+	fn key(seed: u8) -> sr25519::Pair {
+		sr25519::Pair::from_seed(&[seed; 32])
+	}
+
+	/// Creates the DID of key 1
+	fn create() -> H256 {
+		let did = Did::did_of(&key(1).public());
+		let signature = key(1).sign(&did.encode());
+		assert_ok!(Did::create(Origin::signed(SUBMITTER), key(1).public(), signature));
+		did
+	}
+
+	/// Applies `operation` to `did`, signed by `signer`
+	fn update(did: H256, operation: Operation<sr25519::Public>, signer: u8) -> DispatchResult {
+		let nonce = Did::document(&did).unwrap().nonce;
+		let signature = key(signer).sign(&Did::update_payload(&did, nonce, &operation));
+		Did::update(Origin::signed(SUBMITTER), did, operation, signature)
+	}
+
+	fn authentication(seed: u8) -> Operation<sr25519::Public> {
+		Operation::AddKey(VerificationKey {
+			key: key(seed).public(),
+			purpose: KeyPurpose::Authentication,
+		})
+	}
+
+	fn service(id: &[u8], endpoint: &[u8]) -> Operation<sr25519::Public> {
+		Operation::AddService(Service { id: id.to_vec(), endpoint: endpoint.to_vec() })
+	}
+
+	#[test]
+	fn creating_needs_the_controller() {
+		new_test_ext().execute_with(|| {
+			let did = Did::did_of(&key(1).public());
+			assert_err!(
+				Did::create(Origin::signed(SUBMITTER), key(1).public(), key(2).sign(&did.encode())),
+				"invalid controller signature"
+			);
+
+			create();
+			assert_last_event::<TestRuntime>(RawEvent::DidCreated(did));
+			let document = Did::document(&did).unwrap();
+			assert_eq!(document.controller, key(1).public());
+			assert_eq!(document.nonce, 0);
+			assert_err!(
+				Did::create(Origin::signed(SUBMITTER), key(1).public(), key(1).sign(&did.encode())),
+				"DID already exists"
+			);
+		})
+	}
+
+	#[test]
+	fn keys_are_added_and_revoked() {
+		new_test_ext().execute_with(|| {
+			let did = create();
+			assert_ok!(update(did, authentication(2), 1));
+			assert_ok!(update(did, Operation::AddKey(VerificationKey {
+				key: key(3).public(),
+				purpose: KeyPurpose::Assertion,
+			}), 1));
+			assert_eq!(Did::document(&did).unwrap().keys.len(), 2);
+			assert_err!(update(did, authentication(4), 1), "too many keys");
+
+			assert_ok!(update(did, Operation::RevokeKey(key(2).public()), 1));
+			assert_err!(update(did, Operation::RevokeKey(key(2).public()), 1), "no such key");
+			assert_err!(update(did, authentication(3), 1), "key already added");
+
+			let document = Did::document(&did).unwrap();
+			let keys = document.keys.iter().map(|k| k.key).collect::<Vec<_>>();
+			assert_eq!(keys, vec![key(3).public()]);
+			assert_eq!(document.nonce, 3);
+			// Failed changes deposit nothing, so the last event is still the revocation
+			assert_last_event::<TestRuntime>(RawEvent::DidUpdated(did, 3));
+		})
+	}
+
+	#[test]
+	fn only_the_controller_changes_documents() {
+		new_test_ext().execute_with(|| {
+			let did = create();
+			assert_ok!(update(did, authentication(2), 1));
+			// Verification keys don't control the document
+			assert_err!(update(did, authentication(3), 2), "invalid controller signature");
+		})
+	}
+
+	#[test]
+	fn changes_cannot_be_replayed() {
+		new_test_ext().execute_with(|| {
+			let did = create();
+			let operation = Operation::RevokeKey(key(2).public());
+			assert_ok!(update(did, authentication(2), 1));
+			let signature = key(1).sign(&Did::update_payload(&did, 1, &operation));
+			let submitter = Origin::signed(SUBMITTER);
+			assert_ok!(Did::update(submitter, did, operation.clone(), signature.clone()));
+			assert_ok!(update(did, authentication(2), 1));
+			// The nonce moved on, so the signature no longer verifies
+			assert_err!(
+				Did::update(Origin::signed(SUBMITTER), did, operation, signature),
+				"invalid controller signature"
+			);
+		})
+	}
+
+	#[test]
+	fn controllers_are_rotated() {
+		new_test_ext().execute_with(|| {
+			let did = create();
+			assert_ok!(update(did, Operation::RotateController(key(9).public()), 1));
+
+			assert_err!(update(did, authentication(2), 1), "invalid controller signature");
+			assert_ok!(update(did, authentication(2), 9));
+			// The DID does not change with its controller
+			assert_eq!(Did::document(&did).unwrap().controller, key(9).public());
+		})
+	}
+
+	#[test]
+	fn services_are_added_and_removed() {
+		new_test_ext().execute_with(|| {
+			let did = create();
+			assert_err!(update(did, service(b"inbox", &[0; 17]), 1), "service is too long");
+			assert_ok!(update(did, service(b"inbox", b"https://a.io"), 1));
+			assert_err!(
+				update(did, service(b"inbox", b"https://b.io"), 1),
+				"service id already used"
+			);
+			assert_ok!(update(did, service(b"web", b"https://b.io"), 1));
+			assert_err!(update(did, service(b"chat", b"https://c.io"), 1), "too many services");
+
+			let unknown = Operation::RemoveService(b"mail".to_vec());
+			assert_err!(update(did, unknown, 1), "no such service");
+			assert_ok!(update(did, Operation::RemoveService(b"inbox".to_vec()), 1));
+			let services = Did::document(&did).unwrap().services;
+			let web = Service { id: b"web".to_vec(), endpoint: b"https://b.io".to_vec() };
+			assert_eq!(services, vec![web]);
+		})
+	}
+}
@@ -7,27 +7,56 @@
 // the second key might be a unique identifier
 // `remove_prefix` enables clean removal of all values with the group identifier
 
-use sp_std::prelude::*;
+use sp_std::{marker::PhantomData, prelude::*};
 use frame_support::{
     decl_event, decl_module, decl_storage,
     dispatch::DispatchResult,
     ensure,
     storage::{StorageDoubleMap, StorageMap, StorageValue},
+    weights::{ClassifyDispatch, DispatchClass, PaysFee, SimpleDispatchInfo, WeighData, Weight},
 };
 use frame_system::{self as system, ensure_signed};
 
+pub mod weights;
+pub use weights::WeightInfo;
+
 pub trait Trait: system::Trait {
     type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+    /// Weight information for the dispatchables in this pallet
+    type WeightInfo: WeightInfo;
 }
 
 pub type GroupIndex = u32; // this is Encode (which is necessary for double_map)
 
+/// Weighs `remove_group` by the size of the group it is called with
+pub struct RemoveGroupWeight<T>(PhantomData<T>);
+
+impl<T: Trait> WeighData<(&GroupIndex,)> for RemoveGroupWeight<T> {
+    fn weigh_data(&self, (group,): (&GroupIndex,)) -> Weight {
+        T::WeightInfo::remove_group(<Module<T>>::group_size(group))
+    }
+}
+
+impl<T, A> ClassifyDispatch<A> for RemoveGroupWeight<T> {
+    fn classify_dispatch(&self, _: A) -> DispatchClass {
+        Default::default()
+    }
+}
+
+impl<T> PaysFee for RemoveGroupWeight<T> {
+    fn pays_fee(&self) -> bool {
+        true
+    }
+}
+
 decl_storage! {
     trait Store for Module<T: Trait> as Dmap {
         /// Member score (double map)
         MemberScore get(fn member_score): double_map GroupIndex, twox_128(T::AccountId) => u32;
         /// Get group ID for member
         GroupMembership get(fn group_membership): map T::AccountId => GroupIndex;
+        /// Number of scores in each group, which `remove_group` is weighed by
+        GroupSize get(fn group_size): map GroupIndex => u32;
         /// For fast membership checks, see check-membership recipe for more details
         AllMembers get(fn all_members): Vec<T::AccountId>;
     }
@@ -54,6 +83,9 @@ decl_module! {
         fn deposit_event() = default;
 
         /// Join the `AllMembers` vec before joining a group
+        #[weight = SimpleDispatchInfo::FixedNormal(
+            T::WeightInfo::join_all_members(<Module<T>>::member_count())
+        )]
         fn join_all_members(origin) -> DispatchResult {
             let new_member = ensure_signed(origin)?;
             ensure!(!Self::is_member(&new_member), "already a member, can't join");
@@ -63,24 +95,37 @@ decl_module! {
         }
 
         /// Put MemberScore (for testing purposes)
+        #[weight = SimpleDispatchInfo::FixedNormal(
+            T::WeightInfo::join_a_group(<Module<T>>::member_count())
+        )]
         fn join_a_group(origin, index: GroupIndex, score: u32) -> DispatchResult {
             let member = ensure_signed(origin)?;
             ensure!(Self::is_member(&member), "not a member, can't remove");
+            if !<MemberScore<T>>::exists(&index, &member) {
+                GroupSize::mutate(&index, |size| *size += 1);
+            }
             <MemberScore<T>>::insert(&index, &member, score);
             <GroupMembership<T>>::insert(&member, &index);
             Self::deposit_event(RawEvent::MemberJoinsGroup(member, index, score));
             Ok(())
         }
 
+        #[weight = SimpleDispatchInfo::FixedNormal(
+            T::WeightInfo::remove_member(<Module<T>>::member_count())
+        )]
         fn remove_member(origin) -> DispatchResult {
             let member_to_remove = ensure_signed(origin)?;
             ensure!(Self::is_member(&member_to_remove), "not a member, can't remove");
             let group_id = <GroupMembership<T>>::take(member_to_remove.clone());
+            if <MemberScore<T>>::exists(&group_id, &member_to_remove) {
+                GroupSize::mutate(&group_id, |size| *size -= 1);
+            }
             <MemberScore<T>>::remove(&group_id, &member_to_remove);
             Self::deposit_event(RawEvent::RemoveMember(member_to_remove));
             Ok(())
         }
 
+        #[weight = RemoveGroupWeight::<T>(PhantomData)]
         fn remove_group(origin, group: GroupIndex) -> DispatchResult {
             let member = ensure_signed(origin)?;
 
@@ -90,6 +135,7 @@ decl_module! {
 
             // remove all group members from MemberScore at once
             <MemberScore<T>>::remove_prefix(&group_id);
+            GroupSize::remove(&group_id);
 
             Self::deposit_event(RawEvent::RemoveGroup(group_id));
             Ok(())
@@ -102,12 +148,17 @@ impl<T: Trait> Module<T> {
     fn is_member(who: &T::AccountId) -> bool {
         <AllMembers<T>>::get().contains(who)
     }
+
+    /// The number of members, without decoding them
+    pub fn member_count() -> u32 {
+        <AllMembers<T>>::decode_len().unwrap_or(0) as u32
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::RawEvent;
-    use crate::{Module, Trait};
+    use crate::{Call, Module, Trait, WeightInfo};
     use sp_core::H256;
     use sp_io::TestExternalities;
     use sp_runtime::{
@@ -115,7 +166,10 @@ mod tests {
         traits::{BlakeTwo256, IdentityLookup},
         Perbill,
     };
-    use frame_support::{assert_ok, assert_err, impl_outer_event, impl_outer_origin, parameter_types};
+    use frame_support::{
+        assert_ok, assert_err, impl_outer_event, impl_outer_origin, parameter_types,
+        weights::GetDispatchInfo,
+    };
     use frame_system as system;
 
     impl_outer_origin! {
@@ -162,6 +216,7 @@ mod tests {
 
     impl Trait for TestRuntime {
         type Event = TestEvent;
+        type WeightInfo = ();
     }
 
     pub type System = system::Module<TestRuntime>;
@@ -264,4 +319,27 @@ mod tests {
             // TODO: test correct changes to storage
         })
     }
+
+    #[test]
+    fn removal_weight_grows_with_group_size() {
+        ExtBuilder::build().execute_with(|| {
+            let weight = |group| Call::<TestRuntime>::remove_group(group).get_dispatch_info().weight;
+            assert_eq!(weight(3), <() as WeightInfo>::remove_group(0));
+
+            for who in 1..=3 {
+                assert_ok!(DoubleMap::join_all_members(Origin::signed(who)));
+                assert_ok!(DoubleMap::join_a_group(Origin::signed(who), 3, 5));
+            }
+            // a new score for the same member does not grow the group
+            assert_ok!(DoubleMap::join_a_group(Origin::signed(1), 3, 6));
+            assert_eq!(DoubleMap::group_size(3), 3);
+            assert_eq!(weight(3), <() as WeightInfo>::remove_group(3));
+
+            assert_ok!(DoubleMap::remove_member(Origin::signed(2)));
+            assert_eq!(weight(3), <() as WeightInfo>::remove_group(2));
+
+            assert_ok!(DoubleMap::remove_group(Origin::signed(1), 3));
+            assert_eq!(weight(3), <() as WeightInfo>::remove_group(0));
+        })
+    }
 }
@@ -0,0 +1,54 @@
+//! Weights for the double-map pallet
+//!
+//! `remove_group` removes every score under the group's prefix, so its weight is a function of the
+//! group size. The size is stored per group, and read when the call is weighed. The other calls
+//! check membership by decoding the `AllMembers` vec, so they grow with the number of members.
+
+use sp_std::marker::PhantomData;
+use frame_support::weights::Weight;
+
+/// Weight functions needed by the double-map pallet
+pub trait WeightInfo {
+    fn join_all_members(members: u32) -> Weight;
+    fn join_a_group(members: u32) -> Weight;
+    fn remove_member(members: u32) -> Weight;
+    fn remove_group(group_size: u32) -> Weight;
+}
+
+/// Hand-picked estimates of the double-map pallet's weights. They were not measured, because this
+/// Substrate version has no benchmarking framework.
+pub struct EstimatedWeight<T>(PhantomData<T>);
+impl<T: frame_system::Trait> WeightInfo for EstimatedWeight<T> {
+    // decodes the member vec to check membership, then re-encodes it with the new member
+    fn join_all_members(members: u32) -> Weight {
+        20_000u32.saturating_add(1_000u32.saturating_mul(members))
+    }
+    // decodes the member vec, then writes a score, a membership and a group size
+    fn join_a_group(members: u32) -> Weight {
+        40_000u32.saturating_add(500u32.saturating_mul(members))
+    }
+    // decodes the member vec, then removes a score and a membership
+    fn remove_member(members: u32) -> Weight {
+        40_000u32.saturating_add(500u32.saturating_mul(members))
+    }
+    // reads a membership, then removes every score under the prefix
+    fn remove_group(group_size: u32) -> Weight {
+        20_000u32.saturating_add(10_000u32.saturating_mul(group_size))
+    }
+}
+
+// For backwards compatibility and tests
+impl WeightInfo for () {
+    fn join_all_members(members: u32) -> Weight {
+        10_000u32.saturating_add(100u32.saturating_mul(members))
+    }
+    fn join_a_group(members: u32) -> Weight {
+        10_000u32.saturating_add(100u32.saturating_mul(members))
+    }
+    fn remove_member(members: u32) -> Weight {
+        10_000u32.saturating_add(100u32.saturating_mul(members))
+    }
+    fn remove_group(group_size: u32) -> Weight {
+        10_000u32.saturating_add(1_000u32.saturating_mul(group_size))
+    }
+}
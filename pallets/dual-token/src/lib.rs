@@ -0,0 +1,364 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! A dual-token economy
+//!
+//! Many chains separate a governance token, which gives a say over the chain's future, from a
+//! utility token, which pays for using it. This pallet coordinates two such tokens. Both are plain
+//! currencies, usually two instances of `pallet-balances`, and the pallet only sees them through
+//! the `Currency` traits:
+//! * Accounts *stake* governance tokens. Staked tokens are locked, but never leave the account.
+//! * Actions cost a fee in utility tokens. Every `StakePerPercent` governance tokens staked take
+//!   one percent off the fee, up to `MaxDiscount`.
+//!
+//! Staking is immediate in both directions to keep the recipe short. A real economy would delay
+//! unstaking, so that tokens cannot be staked just for a single action.
+
+use sp_runtime::{
+	traits::{CheckedDiv, SaturatedConversion, Saturating, Zero},
+	Percent,
+};
+use frame_support::{
+	decl_event, decl_module, decl_storage,
+	dispatch::DispatchResult,
+	ensure,
+	traits::{
+		Currency, ExistenceRequirement, Get, LockIdentifier, LockableCurrency, OnUnbalanced,
+		WithdrawReason, WithdrawReasons,
+	},
+};
+use frame_system::{self as system, ensure_signed};
+
+const STAKE_ID: LockIdentifier = *b"dualstke";
+
+type GovernanceBalanceOf<T> =
+	<<T as Trait>::GovernanceToken as Currency<<T as system::Trait>::AccountId>>::Balance;
+type UtilityBalanceOf<T> =
+	<<T as Trait>::UtilityToken as Currency<<T as system::Trait>::AccountId>>::Balance;
+type UtilityImbalanceOf<T> =
+	<<T as Trait>::UtilityToken as Currency<<T as system::Trait>::AccountId>>::NegativeImbalance;
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The token that is staked for discounts
+	type GovernanceToken: LockableCurrency<Self::AccountId, Moment = Self::BlockNumber>;
+
+	/// The token in which actions are paid
+	type UtilityToken: Currency<Self::AccountId>;
+
+	/// The fee of an action without any discount
+	type ActionFee: Get<UtilityBalanceOf<Self>>;
+
+	/// The governance tokens to stake for each percent of discount
+	type StakePerPercent: Get<GovernanceBalanceOf<Self>>;
+
+	/// The largest discount, however much is staked
+	type MaxDiscount: Get<Percent>;
+
+	/// Receives the fees of actions
+	type FeeDestination: OnUnbalanced<UtilityImbalanceOf<Self>>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as DualToken {
+		/// The governance tokens each account has staked
+		Staked get(fn staked): map T::AccountId => GovernanceBalanceOf<T>;
+
+		/// The number of actions each account has performed
+		Actions get(fn actions): map T::AccountId => u32;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as system::Trait>::AccountId,
+		GovernanceBalance = GovernanceBalanceOf<T>,
+		UtilityBalance = UtilityBalanceOf<T>,
+	{
+		/// An account now has this many governance tokens staked
+		StakeChanged(AccountId, GovernanceBalance),
+		/// An account performed an action and paid this fee in utility tokens
+		ActionPerformed(AccountId, UtilityBalance),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		const ActionFee: UtilityBalanceOf<T> = T::ActionFee::get();
+		const StakePerPercent: GovernanceBalanceOf<T> = T::StakePerPercent::get();
+		const MaxDiscount: Percent = T::MaxDiscount::get();
+
+		/// Stake `amount` more governance tokens
+		fn stake(origin, amount: GovernanceBalanceOf<T>) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let staked = Self::staked(&who).saturating_add(amount);
+			ensure!(
+				staked <= T::GovernanceToken::free_balance(&who),
+				"not enough governance tokens"
+			);
+
+			Self::set_stake(&who, staked);
+			Ok(())
+		}
+
+		/// Stop staking `amount` governance tokens
+		fn unstake(origin, amount: GovernanceBalanceOf<T>) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let staked = Self::staked(&who);
+			ensure!(amount <= staked, "not staked that much");
+
+			Self::set_stake(&who, staked - amount);
+			Ok(())
+		}
+
+		/// An action that costs a fee in utility tokens, discounted for stakers
+		fn perform_action(origin) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let fee = Self::fee_for(&who);
+			let imbalance = T::UtilityToken::withdraw(
+				&who,
+				fee,
+				WithdrawReason::Fee.into(),
+				ExistenceRequirement::KeepAlive,
+			)?;
+			T::FeeDestination::on_unbalanced(imbalance);
+
+			<Actions<T>>::mutate(&who, |actions| *actions = actions.saturating_add(1));
+			Self::deposit_event(RawEvent::ActionPerformed(who, fee));
+			Ok(())
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// The discount that `who` earns with their stake
+	pub fn discount(who: &T::AccountId) -> Percent {
+		let percent: u32 = Self::staked(who)
+			.checked_div(&T::StakePerPercent::get())
+			.unwrap_or_else(Zero::zero)
+			.saturated_into();
+		let max = T::MaxDiscount::get().deconstruct();
+		Percent::from_percent(percent.min(u32::from(max)) as u8)
+	}
+
+	/// The fee that `who` pays for an action
+	pub fn fee_for(who: &T::AccountId) -> UtilityBalanceOf<T> {
+		let fee = T::ActionFee::get();
+		fee.saturating_sub(Self::discount(who) * fee)
+	}
+
+	/// Lock exactly `staked` governance tokens of `who`
+	fn set_stake(who: &T::AccountId, staked: GovernanceBalanceOf<T>) {
+		if staked.is_zero() {
+			T::GovernanceToken::remove_lock(STAKE_ID, who);
+			<Staked<T>>::remove(who);
+		} else {
+			T::GovernanceToken::set_lock(
+				STAKE_ID,
+				who,
+				staked,
+				T::BlockNumber::max_value(),
+				WithdrawReasons::all(),
+			);
+			<Staked<T>>::insert(who, staked);
+		}
+		Self::deposit_event(RawEvent::StakeChanged(who.clone(), staked));
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use event_assert::assert_last_event;
+	use sp_core::H256;
+	use frame_support::{assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types};
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup},
+		Perbill,
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const ExistentialDeposit: u64 = 1;
+		pub const TransferFee: u64 = 0;
+		pub const CreationFee: u64 = 0;
+
+		pub const ActionFee: u64 = 20;
+		pub const StakePerPercent: u64 = 10;
+		pub const MaxDiscount: Percent = Percent::from_percent(50);
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	// The default instance of balances is the governance token
+	impl balances::Trait for TestRuntime {
+		type Balance = u64;
+		type OnFreeBalanceZero = ();
+		type OnNewAccount = ();
+		type Event = TestEvent;
+		type TransferPayment = ();
+		type DustRemoval = ();
+		type ExistentialDeposit = ExistentialDeposit;
+		type TransferFee = TransferFee;
+		type CreationFee = CreationFee;
+	}
+
+	// A second instance is the utility token. It could have its own balance type and parameters.
+	impl balances::Trait<balances::Instance1> for TestRuntime {
+		type Balance = u64;
+		type OnFreeBalanceZero = ();
+		type OnNewAccount = ();
+		type Event = ();
+		type TransferPayment = ();
+		type DustRemoval = ();
+		type ExistentialDeposit = ExistentialDeposit;
+		type TransferFee = TransferFee;
+		type CreationFee = CreationFee;
+	}
+
+	mod dual_token {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			balances<T>,
+			dual_token<T>,
+		}
+	}
+
+	pub type GovernanceToken = balances::Module<TestRuntime>;
+	pub type UtilityToken = balances::Module<TestRuntime, balances::Instance1>;
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type GovernanceToken = GovernanceToken;
+		type UtilityToken = UtilityToken;
+		type ActionFee = ActionFee;
+		type StakePerPercent = StakePerPercent;
+		type MaxDiscount = MaxDiscount;
+		// Dropping the imbalance burns the fees
+		type FeeDestination = ();
+	}
+
+	pub type DualToken = Module<TestRuntime>;
+
+	/// Account 1 has 1000 governance tokens, account 2 has none, and both have 100 utility tokens
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		let mut t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		balances::GenesisConfig::<TestRuntime> {
+			balances: vec![(1, 1000)],
+			vesting: vec![],
+		}
+		.assimilate_storage(&mut t)
+		.unwrap();
+		balances::GenesisConfig::<TestRuntime, balances::Instance1> {
+			balances: vec![(1, 100), (2, 100)],
+			vesting: vec![],
+		}
+		.assimilate_storage(&mut t)
+		.unwrap();
+		t.into()
+	}
+
+	#[test]
+	fn stake_earns_discount() {
+		new_test_ext().execute_with(|| {
+			assert_eq!(DualToken::fee_for(&1), 20);
+
+			assert_ok!(DualToken::stake(Origin::signed(1), 250));
+			assert_last_event::<TestRuntime>(RawEvent::StakeChanged(1, 250));
+			assert_eq!(DualToken::discount(&1), Percent::from_percent(25));
+			assert_eq!(DualToken::fee_for(&1), 15);
+
+			assert_ok!(DualToken::perform_action(Origin::signed(1)));
+			assert_last_event::<TestRuntime>(RawEvent::ActionPerformed(1, 15));
+			assert_eq!(UtilityToken::free_balance(&1), 85);
+			assert_eq!(DualToken::actions(&1), 1);
+		})
+	}
+
+	#[test]
+	fn discount_is_capped() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(DualToken::stake(Origin::signed(1), 1000));
+			assert_eq!(DualToken::discount(&1), Percent::from_percent(50));
+			assert_eq!(DualToken::fee_for(&1), 10);
+		})
+	}
+
+	#[test]
+	fn fees_are_paid_in_utility_tokens_only() {
+		new_test_ext().execute_with(|| {
+			let utility_issuance = UtilityToken::total_issuance();
+			let governance_issuance = GovernanceToken::total_issuance();
+
+			assert_ok!(DualToken::perform_action(Origin::signed(2)));
+			assert_eq!(UtilityToken::free_balance(&2), 80);
+			// The fee is burned
+			assert_eq!(UtilityToken::total_issuance(), utility_issuance - 20);
+			assert_eq!(GovernanceToken::total_issuance(), governance_issuance);
+
+			// Without enough utility tokens, governance tokens do not help
+			for _ in 0..3 {
+				assert_ok!(DualToken::perform_action(Origin::signed(2)));
+			}
+			assert!(DualToken::perform_action(Origin::signed(2)).is_err());
+			assert_eq!(DualToken::actions(&2), 4);
+		})
+	}
+
+	#[test]
+	fn staked_tokens_are_locked() {
+		new_test_ext().execute_with(|| {
+			assert_err!(DualToken::stake(Origin::signed(1), 1001), "not enough governance tokens");
+			assert_ok!(DualToken::stake(Origin::signed(1), 600));
+			assert_err!(DualToken::stake(Origin::signed(1), 401), "not enough governance tokens");
+
+			// The tokens stay in the account, but only the unstaked ones can move
+			assert_eq!(GovernanceToken::free_balance(&1), 1000);
+			assert!(GovernanceToken::transfer(Origin::signed(1), 2, 500).is_err());
+			assert_ok!(GovernanceToken::transfer(Origin::signed(1), 2, 400));
+
+			assert_err!(DualToken::unstake(Origin::signed(1), 601), "not staked that much");
+			assert_ok!(DualToken::unstake(Origin::signed(1), 600));
+			assert_eq!(DualToken::staked(&1), 0);
+			assert_ok!(GovernanceToken::transfer(Origin::signed(1), 2, 500));
+		})
+	}
+}
@@ -0,0 +1,571 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! An open, ascending (English) auction for unique items
+//!
+//! The pallet does not know what the items are. It relies on the `UniqueItems` trait, which any
+//! NFT pallet can implement, and takes custody of an item in the pallet's own account for as long
+//! as it is on auction.
+//!
+//! * `create` puts an item up for auction until `AuctionPeriod` blocks from now.
+//! * `bid` reserves the bid and releases the funds of the bidder who was outbid.
+//! * A bid in the last `ExtensionPeriod` blocks pushes the end back, so nobody can win by bidding
+//!   at the last moment (sniping).
+//! * In `on_finalize` of the final block the winner receives the item and pays the seller. An
+//!   auction without bids returns the item to the seller. So does an auction whose winner can't
+//!   receive the item, and the winner's bid is released.
+
+use codec::{Decode, Encode};
+use sp_std::prelude::*;
+use sp_runtime::{
+	traits::{AccountIdConversion, Saturating},
+	ModuleId, RuntimeDebug,
+};
+use frame_support::{
+	decl_event, decl_module, decl_storage,
+	dispatch::DispatchResult,
+	ensure,
+	traits::{Currency, Get, ReservableCurrency},
+	Parameter,
+};
+use frame_system::{self as system, ensure_signed};
+
+pub type AuctionIndex = u32;
+
+type BalanceOf<T> = <<T as Trait>::Currency as Currency<<T as system::Trait>::AccountId>>::Balance;
+type ItemIdOf<T> = <<T as Trait>::Items as UniqueItems<<T as system::Trait>::AccountId>>::ItemId;
+type AuctionOf<T> = Auction<
+	<T as system::Trait>::AccountId,
+	BalanceOf<T>,
+	<T as system::Trait>::BlockNumber,
+	ItemIdOf<T>,
+>;
+
+/// Hardcoded pallet ID; used to create the account that holds items on auction
+/// Must be exactly 8 characters long
+const PALLET_ID: ModuleId = ModuleId(*b"EngAuctn");
+
+/// Ownership of unique items, as provided by an NFT pallet
+pub trait UniqueItems<AccountId> {
+	/// The identifier of an item
+	type ItemId: Parameter;
+
+	/// The current owner of an item, if it exists
+	fn owner_of(item: &Self::ItemId) -> Option<AccountId>;
+
+	/// Give an item to a new owner
+	fn transfer(item: &Self::ItemId, to: &AccountId) -> DispatchResult;
+}
+
+/// The highest bid so far
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct Bid<AccountId, Balance> {
+	pub who: AccountId,
+	pub value: Balance,
+}
+
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct Auction<AccountId, Balance, BlockNumber, ItemId> {
+	/// The seller, who receives the winning bid
+	pub owner: AccountId,
+	/// The item on auction, held by the pallet until the auction ends
+	pub item: ItemId,
+	/// The lowest acceptable first bid
+	pub min_bid: Balance,
+	/// The auction ends at the end of this block
+	pub ends: BlockNumber,
+	pub best: Option<Bid<AccountId, Balance>>,
+}
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The currency in which bids are made
+	type Currency: ReservableCurrency<Self::AccountId>;
+
+	/// The items that are auctioned
+	type Items: UniqueItems<Self::AccountId>;
+
+	/// Number of blocks an auction runs if nobody bids near its end
+	type AuctionPeriod: Get<Self::BlockNumber>;
+
+	/// A bid this close to the end moves the end to this many blocks after the bid
+	type ExtensionPeriod: Get<Self::BlockNumber>;
+
+	/// The amount by which each bid must beat the previous one
+	type MinIncrement: Get<BalanceOf<Self>>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as EnglishAuction {
+		/// Auctions that have not ended yet
+		Auctions get(fn auction): map AuctionIndex => Option<AuctionOf<T>>;
+
+		/// Number of auctions ever created; the next auction's index
+		AuctionCount get(fn auction_count): AuctionIndex;
+
+		/// Auctions that may end at the end of each block. An extended auction stays listed under
+		/// its old end as well, and is skipped there.
+		Deadlines get(fn deadlines): map T::BlockNumber => Vec<AuctionIndex>;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as system::Trait>::AccountId,
+		Balance = BalanceOf<T>,
+		BlockNumber = <T as system::Trait>::BlockNumber,
+	{
+		/// An auction was created; it ends at the given block
+		AuctionCreated(AuctionIndex, AccountId, BlockNumber),
+		/// A new highest bid was placed
+		BidPlaced(AuctionIndex, AccountId, Balance),
+		/// A late bid moved the end of an auction to the given block
+		AuctionExtended(AuctionIndex, BlockNumber),
+		/// An auction was won by an account at the given price
+		AuctionSettled(AuctionIndex, AccountId, Balance),
+		/// An auction ended without a sale and the item went back to the seller
+		AuctionUnsold(AuctionIndex),
+		/// The winner of an auction could not receive the item, and their bid was released
+		DeliveryFailed(AuctionIndex, AccountId),
+		/// The item of an unsold auction could not be returned, and stays in the pallet's account
+		ItemHeld(AuctionIndex),
+		/// The seller cancelled an auction before anybody bid
+		AuctionCancelled(AuctionIndex),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		const AuctionPeriod: T::BlockNumber = T::AuctionPeriod::get();
+
+		const ExtensionPeriod: T::BlockNumber = T::ExtensionPeriod::get();
+
+		const MinIncrement: BalanceOf<T> = T::MinIncrement::get();
+
+		/// Put an item up for auction. The pallet holds the item until the auction ends.
+		fn create(origin, item: ItemIdOf<T>, min_bid: BalanceOf<T>) -> DispatchResult {
+			let owner = ensure_signed(origin)?;
+			ensure!(T::Items::owner_of(&item) == Some(owner.clone()), "not the owner of the item");
+
+			let index = Self::auction_count();
+			let next = index.checked_add(1).ok_or("auction index overflow")?;
+			let ends = <system::Module<T>>::block_number() + T::AuctionPeriod::get();
+
+			T::Items::transfer(&item, &Self::account_id())?;
+
+			<Auctions<T>>::insert(index, Auction {
+				owner: owner.clone(),
+				item,
+				min_bid,
+				ends,
+				best: None,
+			});
+			<Deadlines<T>>::mutate(ends, |due| due.push(index));
+			AuctionCount::put(next);
+
+			Self::deposit_event(RawEvent::AuctionCreated(index, owner, ends));
+			Ok(())
+		}
+
+		/// Outbid the current highest bidder. The bid stays reserved until it is outbid or the
+		/// auction ends.
+		fn bid(origin, index: AuctionIndex, value: BalanceOf<T>) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let mut auction = Self::auction(index).ok_or("no such auction")?;
+			ensure!(who != auction.owner, "cannot bid on own auction");
+			let minimum = match &auction.best {
+				Some(best) => best.value.saturating_add(T::MinIncrement::get()),
+				None => auction.min_bid,
+			};
+			ensure!(value >= minimum, "bid is too low");
+
+			// Raising one's own bid only reserves the difference
+			match auction.best.take() {
+				Some(best) if best.who == who => {
+					T::Currency::reserve(&who, value.saturating_sub(best.value))
+						.map_err(|_| "cannot afford the bid")?;
+				}
+				previous => {
+					T::Currency::reserve(&who, value).map_err(|_| "cannot afford the bid")?;
+					if let Some(outbid) = previous {
+						T::Currency::unreserve(&outbid.who, outbid.value);
+					}
+				}
+			}
+			auction.best = Some(Bid { who: who.clone(), value });
+
+			let now = <system::Module<T>>::block_number();
+			let extended = now + T::ExtensionPeriod::get();
+			if extended > auction.ends {
+				auction.ends = extended;
+				<Deadlines<T>>::mutate(extended, |due| due.push(index));
+				Self::deposit_event(RawEvent::AuctionExtended(index, extended));
+			}
+			<Auctions<T>>::insert(index, auction);
+
+			Self::deposit_event(RawEvent::BidPlaced(index, who, value));
+			Ok(())
+		}
+
+		/// Withdraw an item from auction. Only possible before the first bid.
+		fn cancel(origin, index: AuctionIndex) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let auction = Self::auction(index).ok_or("no such auction")?;
+			ensure!(who == auction.owner, "not the seller");
+			ensure!(auction.best.is_none(), "auction already has bids");
+
+			T::Items::transfer(&auction.item, &auction.owner)?;
+			<Auctions<T>>::remove(index);
+
+			Self::deposit_event(RawEvent::AuctionCancelled(index));
+			Ok(())
+		}
+
+		fn on_finalize(n: T::BlockNumber) {
+			for index in <Deadlines<T>>::take(n) {
+				match Self::auction(index) {
+					Some(auction) if auction.ends == n => Self::settle(index, auction),
+					// Cancelled, or extended to a later block
+					_ => (),
+				}
+			}
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// The account that holds items while they are on auction
+	pub fn account_id() -> T::AccountId {
+		PALLET_ID.into_account()
+	}
+
+	/// Hand over the item and pay the seller. The item moves first, so if the items pallet
+	/// refuses the transfer, no money has moved yet.
+	fn settle(index: AuctionIndex, auction: AuctionOf<T>) {
+		<Auctions<T>>::remove(index);
+		if let Some(best) = auction.best {
+			if T::Items::transfer(&auction.item, &best.who).is_ok() {
+				let (payment, _) = T::Currency::slash_reserved(&best.who, best.value);
+				T::Currency::resolve_creating(&auction.owner, payment);
+				Self::deposit_event(RawEvent::AuctionSettled(index, best.who, best.value));
+				return;
+			}
+			T::Currency::unreserve(&best.who, best.value);
+			Self::deposit_event(RawEvent::DeliveryFailed(index, best.who));
+		}
+
+		if T::Items::transfer(&auction.item, &auction.owner).is_ok() {
+			Self::deposit_event(RawEvent::AuctionUnsold(index));
+		} else {
+			Self::deposit_event(RawEvent::ItemHeld(index));
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use std::{cell::RefCell, collections::BTreeMap};
+	use sp_core::H256;
+	use frame_support::{
+		assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types
+	};
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup, OnFinalize},
+		Perbill,
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const ExistentialDeposit: u64 = 0;
+		pub const TransferFee: u64 = 0;
+		pub const CreationFee: u64 = 0;
+
+		pub const AuctionPeriod: u64 = 10;
+		pub const ExtensionPeriod: u64 = 3;
+		pub const MinIncrement: u64 = 5;
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	impl balances::Trait for TestRuntime {
+		type Balance = u64;
+		type OnFreeBalanceZero = ();
+		type OnNewAccount = ();
+		type Event = TestEvent;
+		type TransferPayment = ();
+		type DustRemoval = ();
+		type ExistentialDeposit = ExistentialDeposit;
+		type TransferFee = TransferFee;
+		type CreationFee = CreationFee;
+	}
+
+	thread_local! {
+		static OWNERS: RefCell<BTreeMap<u32, u64>> = RefCell::new(BTreeMap::new());
+		static REFUSING: RefCell<Vec<u64>> = RefCell::new(Vec::new());
+	}
+
+	/// A stand-in for an NFT pallet
+	pub struct TestItems;
+	impl UniqueItems<u64> for TestItems {
+		type ItemId = u32;
+
+		fn owner_of(item: &u32) -> Option<u64> {
+			OWNERS.with(|owners| owners.borrow().get(item).cloned())
+		}
+
+		fn transfer(item: &u32, to: &u64) -> DispatchResult {
+			OWNERS.with(|owners| {
+				let mut owners = owners.borrow_mut();
+				let owner = owners.get_mut(item).ok_or("no such item")?;
+				ensure!(!REFUSING.with(|refusing| refusing.borrow().contains(to)), "refuses items");
+				*owner = *to;
+				Ok(())
+			})
+		}
+	}
+
+	mod english_auction {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			balances<T>,
+			english_auction<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type Currency = balances::Module<Self>;
+		type Items = TestItems;
+		type AuctionPeriod = AuctionPeriod;
+		type ExtensionPeriod = ExtensionPeriod;
+		type MinIncrement = MinIncrement;
+	}
+
+	pub type System = system::Module<TestRuntime>;
+	pub type Balances = balances::Module<TestRuntime>;
+	pub type EnglishAuction = Module<TestRuntime>;
+
+	/// Accounts 1 to 4 have 100 each. Account 1 owns item 7.
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		let mut t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		balances::GenesisConfig::<TestRuntime> {
+			balances: vec![(1, 100), (2, 100), (3, 100), (4, 100)],
+			vesting: vec![],
+		}
+		.assimilate_storage(&mut t)
+		.unwrap();
+		OWNERS.with(|owners| *owners.borrow_mut() = vec![(7, 1)].into_iter().collect());
+		REFUSING.with(|refusing| refusing.borrow_mut().clear());
+		let mut ext: sp_io::TestExternalities = t.into();
+		ext.execute_with(|| System::set_block_number(1));
+		ext
+	}
+
+	fn run_to_block(n: u64) {
+		while System::block_number() < n {
+			System::set_block_number(System::block_number() + 1);
+			EnglishAuction::on_finalize(System::block_number());
+		}
+	}
+
+	fn event_emitted(event: RawEvent<u64, u64, u64>) -> bool {
+		let expected = TestEvent::english_auction(event);
+		System::events().iter().any(|a| a.event == expected)
+	}
+
+	/// Auction item 7 from block 1. It ends at block 11 unless extended.
+	fn create_auction() {
+		assert_ok!(EnglishAuction::create(Origin::signed(1), 7, 10));
+	}
+
+	#[test]
+	fn create_takes_custody() {
+		new_test_ext().execute_with(|| {
+			create_auction();
+			assert_eq!(TestItems::owner_of(&7), Some(EnglishAuction::account_id()));
+			assert_eq!(EnglishAuction::auction(0).map(|a| a.ends), Some(11));
+			assert!(event_emitted(RawEvent::AuctionCreated(0, 1, 11)));
+		})
+	}
+
+	#[test]
+	fn only_owner_can_auction() {
+		new_test_ext().execute_with(|| {
+			assert_err!(EnglishAuction::create(Origin::signed(2), 7, 10), "not the owner of the item");
+			assert_err!(EnglishAuction::create(Origin::signed(1), 8, 10), "not the owner of the item");
+		})
+	}
+
+	#[test]
+	fn outbid_bidder_is_refunded() {
+		new_test_ext().execute_with(|| {
+			create_auction();
+			assert_ok!(EnglishAuction::bid(Origin::signed(2), 0, 20));
+			assert_eq!(Balances::reserved_balance(&2), 20);
+
+			assert_ok!(EnglishAuction::bid(Origin::signed(3), 0, 25));
+			assert_eq!(Balances::reserved_balance(&2), 0);
+			assert_eq!(Balances::reserved_balance(&3), 25);
+			assert!(event_emitted(RawEvent::BidPlaced(0, 3, 25)));
+		})
+	}
+
+	#[test]
+	fn raising_own_bid_reserves_difference() {
+		new_test_ext().execute_with(|| {
+			create_auction();
+			assert_ok!(EnglishAuction::bid(Origin::signed(2), 0, 20));
+			assert_ok!(EnglishAuction::bid(Origin::signed(2), 0, 90));
+			assert_eq!(Balances::reserved_balance(&2), 90);
+			assert_eq!(Balances::free_balance(&2), 10);
+		})
+	}
+
+	#[test]
+	fn bids_must_beat_minimum() {
+		new_test_ext().execute_with(|| {
+			create_auction();
+			assert_err!(EnglishAuction::bid(Origin::signed(2), 0, 9), "bid is too low");
+			assert_ok!(EnglishAuction::bid(Origin::signed(2), 0, 10));
+			assert_err!(EnglishAuction::bid(Origin::signed(3), 0, 14), "bid is too low");
+			assert_err!(EnglishAuction::bid(Origin::signed(3), 0, 200), "cannot afford the bid");
+			assert_err!(EnglishAuction::bid(Origin::signed(1), 0, 50), "cannot bid on own auction");
+			assert_err!(EnglishAuction::bid(Origin::signed(2), 1, 50), "no such auction");
+		})
+	}
+
+	#[test]
+	fn late_bid_extends_auction() {
+		new_test_ext().execute_with(|| {
+			create_auction();
+			// Early bids leave the end alone
+			assert_ok!(EnglishAuction::bid(Origin::signed(2), 0, 10));
+			assert_eq!(EnglishAuction::auction(0).map(|a| a.ends), Some(11));
+
+			run_to_block(10);
+			assert_ok!(EnglishAuction::bid(Origin::signed(3), 0, 15));
+			assert_eq!(EnglishAuction::auction(0).map(|a| a.ends), Some(13));
+			assert!(event_emitted(RawEvent::AuctionExtended(0, 13)));
+
+			// Still running after the original end
+			run_to_block(11);
+			assert!(EnglishAuction::auction(0).is_some());
+			run_to_block(13);
+			assert!(EnglishAuction::auction(0).is_none());
+		})
+	}
+
+	#[test]
+	fn winner_pays_and_receives_item() {
+		new_test_ext().execute_with(|| {
+			create_auction();
+			assert_ok!(EnglishAuction::bid(Origin::signed(2), 0, 20));
+			assert_ok!(EnglishAuction::bid(Origin::signed(3), 0, 40));
+			run_to_block(11);
+
+			assert_eq!(TestItems::owner_of(&7), Some(3));
+			assert_eq!(Balances::free_balance(&1), 140);
+			assert_eq!(Balances::total_balance(&3), 60);
+			assert_eq!(Balances::free_balance(&2), 100);
+			assert!(event_emitted(RawEvent::AuctionSettled(0, 3, 40)));
+		})
+	}
+
+	#[test]
+	fn undeliverable_item_returns_to_seller() {
+		new_test_ext().execute_with(|| {
+			create_auction();
+			assert_ok!(EnglishAuction::bid(Origin::signed(3), 0, 40));
+			REFUSING.with(|refusing| refusing.borrow_mut().push(3));
+			run_to_block(11);
+
+			// Nobody paid for the item that was not delivered
+			assert_eq!(TestItems::owner_of(&7), Some(1));
+			assert_eq!(Balances::free_balance(&1), 100);
+			assert_eq!(Balances::free_balance(&3), 100);
+			assert_eq!(Balances::reserved_balance(&3), 0);
+			assert!(event_emitted(RawEvent::DeliveryFailed(0, 3)));
+			assert!(event_emitted(RawEvent::AuctionUnsold(0)));
+		})
+	}
+
+	#[test]
+	fn unreturnable_item_stays_with_the_pallet() {
+		new_test_ext().execute_with(|| {
+			create_auction();
+			REFUSING.with(|refusing| refusing.borrow_mut().push(1));
+			run_to_block(11);
+			assert_eq!(TestItems::owner_of(&7), Some(EnglishAuction::account_id()));
+			assert!(event_emitted(RawEvent::ItemHeld(0)));
+		})
+	}
+
+	#[test]
+	fn unsold_item_returns_to_seller() {
+		new_test_ext().execute_with(|| {
+			create_auction();
+			run_to_block(11);
+			assert_eq!(TestItems::owner_of(&7), Some(1));
+			assert!(EnglishAuction::auction(0).is_none());
+			assert!(event_emitted(RawEvent::AuctionUnsold(0)));
+		})
+	}
+
+	#[test]
+	fn cancel_before_bids_only() {
+		new_test_ext().execute_with(|| {
+			create_auction();
+			assert_err!(EnglishAuction::cancel(Origin::signed(2), 0), "not the seller");
+			assert_ok!(EnglishAuction::cancel(Origin::signed(1), 0));
+			assert_eq!(TestItems::owner_of(&7), Some(1));
+
+			assert_ok!(EnglishAuction::create(Origin::signed(1), 7, 10));
+			assert_ok!(EnglishAuction::bid(Origin::signed(2), 1, 10));
+			assert_err!(EnglishAuction::cancel(Origin::signed(1), 1), "auction already has bids");
+
+			// The cancelled auction's deadline passes without effect
+			run_to_block(11);
+			assert!(!event_emitted(RawEvent::AuctionUnsold(0)));
+			assert_eq!(TestItems::owner_of(&7), Some(2));
+		})
+	}
+}
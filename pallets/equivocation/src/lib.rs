@@ -0,0 +1,514 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! Penalizing block authors who equivocate
+//!
+//! An author equivocates when it produces two different blocks at the same height. Only the node
+//! can notice this, because only the node sees competing forks. The node hands the two headers to
+//! the runtime as an inherent, this pallet checks them, and the runtime's `OnEquivocation` handler
+//! carries out the penalty (the tests wire it to the slashing recipe).
+//!
+//! Authors are identified with `FindAuthor` from the headers' pre-runtime digests. `PowAuthor`
+//! reads the author account that a proof of work node places in the `pow_` digest. The runtime
+//! checks both headers' seals and that they extend its own chain, so nobody can frame an author
+//! with made-up headers.
+
+use codec::{Decode, Encode};
+use sp_std::prelude::*;
+use sp_std::marker::PhantomData;
+use sp_inherents::{InherentData, InherentIdentifier, MakeFatalError, ProvideInherent};
+use sp_runtime::{
+	traits::{Header as HeaderT, Hash as HashT, One, Zero},
+	ConsensusEngineId, RuntimeDebug,
+};
+use frame_support::{
+	decl_event, decl_module, decl_storage,
+	dispatch::DispatchResult,
+	ensure,
+	traits::FindAuthor,
+};
+use frame_system::{self as system, ensure_none};
+use uncles::VerifySeal;
+
+/// The identifier of the equivocation inherent
+pub const INHERENT_IDENTIFIER: InherentIdentifier = *b"equivoc0";
+
+/// The engine id under which proof of work nodes put the author in the pre-runtime digest
+pub const POW_ENGINE_ID: ConsensusEngineId = *b"pow_";
+
+/// Two headers that the node claims were built by the same author at the same height
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct EquivocationProof<Header> {
+	pub first: Header,
+	pub second: Header,
+}
+
+/// What to do with an author who equivocated at the given height
+pub trait OnEquivocation<AccountId, BlockNumber> {
+	fn on_equivocation(offender: &AccountId, at: BlockNumber);
+}
+
+impl<AccountId, BlockNumber> OnEquivocation<AccountId, BlockNumber> for () {
+	fn on_equivocation(_offender: &AccountId, _at: BlockNumber) {}
+}
+
+/// Finds the author account that a proof of work node encoded in the `pow_` pre-runtime digest
+pub struct PowAuthor<AccountId>(PhantomData<AccountId>);
+
+impl<AccountId: Decode> FindAuthor<AccountId> for PowAuthor<AccountId> {
+	fn find_author<'a, I>(digests: I) -> Option<AccountId>
+	where
+		I: 'a + IntoIterator<Item = (ConsensusEngineId, &'a [u8])>,
+	{
+		digests
+			.into_iter()
+			.find(|(id, _)| *id == POW_ENGINE_ID)
+			.and_then(|(_, mut data)| AccountId::decode(&mut data).ok())
+	}
+}
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// Identifies the author of a header
+	type FindAuthor: FindAuthor<Self::AccountId>;
+
+	/// Checks the proof of work seal of a header
+	type VerifySeal: VerifySeal<Self::Header>;
+
+	/// Carries out the penalty
+	type OnEquivocation: OnEquivocation<Self::AccountId, Self::BlockNumber>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as Equivocation {
+		/// Heights at which an author has already been punished, so one offence is punished once
+		Punished get(fn punished): map (T::AccountId, T::BlockNumber) => bool;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as system::Trait>::AccountId,
+		BlockNumber = <T as system::Trait>::BlockNumber,
+	{
+		/// An author built two blocks at the given height
+		Equivocated(AccountId, BlockNumber),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		/// Punish the authors of the given equivocations. This is an inherent, submitted by the
+		/// block author.
+		fn report_equivocations(origin, proofs: Vec<EquivocationProof<T::Header>>) -> DispatchResult {
+			ensure_none(origin)?;
+
+			// Check everything before punishing anyone
+			let offences = proofs
+				.iter()
+				.map(Self::check_proof)
+				.collect::<Result<Vec<_>, _>>()?;
+
+			for (offender, at) in offences {
+				<Punished<T>>::insert((offender.clone(), at), true);
+				T::OnEquivocation::on_equivocation(&offender, at);
+				Self::deposit_event(RawEvent::Equivocated(offender, at));
+			}
+			Ok(())
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// The author of a header, according to `T::FindAuthor`
+	fn author(header: &T::Header) -> Option<T::AccountId> {
+		let digests = header.digest().logs().iter().filter_map(|d| d.as_pre_runtime());
+		T::FindAuthor::find_author(digests)
+	}
+
+	/// Check a proof, returning the offender and the height of the offence
+	pub fn check_proof(
+		proof: &EquivocationProof<T::Header>,
+	) -> Result<(T::AccountId, T::BlockNumber), &'static str> {
+		let at = *proof.first.number();
+		ensure!(at == *proof.second.number(), "headers are at different heights");
+		ensure!(
+			T::Hashing::hash_of(&proof.first) != T::Hashing::hash_of(&proof.second),
+			"headers are identical"
+		);
+
+		// Both headers must branch off this chain, and carry real work
+		let now = <system::Module<T>>::block_number();
+		ensure!(at > Zero::zero() && at < now, "headers must be older than this block");
+		let parent = <system::Module<T>>::block_hash(at - One::one());
+		ensure!(
+			*proof.first.parent_hash() == parent && *proof.second.parent_hash() == parent,
+			"header's parent is not canonical"
+		);
+		ensure!(
+			T::VerifySeal::verify_seal(&proof.first) && T::VerifySeal::verify_seal(&proof.second),
+			"header has an invalid seal"
+		);
+
+		let offender = Self::author(&proof.first).ok_or("first header has no author")?;
+		let second = Self::author(&proof.second).ok_or("second header has no author")?;
+		ensure!(offender == second, "headers have different authors");
+		ensure!(!Self::punished((offender.clone(), at)), "equivocation already punished");
+
+		Ok((offender, at))
+	}
+}
+
+impl<T: Trait> ProvideInherent for Module<T> {
+	type Call = Call<T>;
+	type Error = MakeFatalError<()>;
+	const INHERENT_IDENTIFIER: InherentIdentifier = INHERENT_IDENTIFIER;
+
+	fn create_inherent(data: &InherentData) -> Option<Self::Call> {
+		let proofs = data
+			.get_data::<Vec<EquivocationProof<T::Header>>>(&INHERENT_IDENTIFIER)
+			.ok()??;
+
+		// Only include proofs that will be accepted, so a bad report from the node can't make
+		// the block invalid. The first of two proofs for the same offence wins.
+		let mut seen = Vec::new();
+		let proofs = proofs
+			.into_iter()
+			.filter(|proof| match Self::check_proof(proof) {
+				Ok(offence) if !seen.contains(&offence) => {
+					seen.push(offence);
+					true
+				}
+				_ => false,
+			})
+			.collect::<Vec<_>>();
+
+		if proofs.is_empty() {
+			None
+		} else {
+			Some(Call::report_equivocations(proofs))
+		}
+	}
+}
+
+/// Node-side queue of equivocations waiting to be put into a block
+#[cfg(feature = "std")]
+pub struct InherentDataProvider<Header> {
+	pending: std::sync::Arc<std::sync::Mutex<Vec<EquivocationProof<Header>>>>,
+}
+
+#[cfg(feature = "std")]
+impl<Header> InherentDataProvider<Header> {
+	pub fn new() -> Self {
+		Self { pending: Default::default() }
+	}
+
+	/// Queue a proof. The node calls this when it imports a second block from the same author at
+	/// the same height.
+	pub fn report(&self, proof: EquivocationProof<Header>) {
+		self.pending.lock().expect("equivocation queue poisoned").push(proof);
+	}
+}
+
+#[cfg(feature = "std")]
+impl<Header> Default for InherentDataProvider<Header> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+#[cfg(feature = "std")]
+impl<Header> Clone for InherentDataProvider<Header> {
+	fn clone(&self) -> Self {
+		Self { pending: self.pending.clone() }
+	}
+}
+
+#[cfg(feature = "std")]
+impl<Header: Encode> sp_inherents::ProvideInherentData for InherentDataProvider<Header> {
+	fn inherent_identifier(&self) -> &'static InherentIdentifier {
+		&INHERENT_IDENTIFIER
+	}
+
+	fn provide_inherent_data(&self, inherent_data: &mut InherentData) -> Result<(), sp_inherents::Error> {
+		// Each proof is offered to one block. If that block is never imported the proof is lost,
+		// which only means one fewer punishment.
+		let pending = std::mem::replace(
+			&mut *self.pending.lock().expect("equivocation queue poisoned"),
+			Vec::new(),
+		);
+		if pending.is_empty() {
+			return Ok(());
+		}
+		inherent_data.put_data(INHERENT_IDENTIFIER, &pending)
+	}
+
+	fn error_to_string(&self, _error: &[u8]) -> Option<String> {
+		None
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use sp_core::H256;
+	use frame_support::{
+		assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types,
+		traits::OnFinalize,
+	};
+	use frame_system::RawOrigin;
+	use sp_runtime::{
+		generic::{Digest, DigestItem},
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup},
+		Perbill,
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const ExistentialDeposit: u64 = 0;
+		pub const TransferFee: u64 = 0;
+		pub const CreationFee: u64 = 0;
+
+		pub const ChallengePeriod: u64 = 5;
+		pub const SlashFraction: Perbill = Perbill::from_percent(50);
+		pub const ReporterShare: Perbill = Perbill::from_percent(10);
+
+		// Any seal is valid
+		pub const Difficulty: u128 = 1;
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	impl balances::Trait for TestRuntime {
+		type Balance = u64;
+		type OnFreeBalanceZero = ();
+		type OnNewAccount = ();
+		type Event = TestEvent;
+		type TransferPayment = ();
+		type DustRemoval = ();
+		type ExistentialDeposit = ExistentialDeposit;
+		type TransferFee = TransferFee;
+		type CreationFee = CreationFee;
+	}
+
+	// The companion pallet that carries out the penalty
+	impl slashing::Trait for TestRuntime {
+		type Event = TestEvent;
+		type Currency = balances::Module<Self>;
+		type ChallengePeriod = ChallengePeriod;
+		type SlashFraction = SlashFraction;
+		type ReporterShare = ReporterShare;
+		type Slash = ();
+	}
+
+	mod equivocation {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			balances<T>,
+			slashing<T>,
+			equivocation<T>,
+		}
+	}
+
+	/// Glue between the two pallets, as a runtime would write it
+	pub struct SlashEquivocator;
+	impl OnEquivocation<u64, u64> for SlashEquivocator {
+		fn on_equivocation(offender: &u64, _at: u64) {
+			Slashing::slash_offender(offender);
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type FindAuthor = PowAuthor<u64>;
+		type VerifySeal = uncles::Blake2Seal<Difficulty>;
+		type OnEquivocation = SlashEquivocator;
+	}
+
+	pub type System = system::Module<TestRuntime>;
+	pub type Balances = balances::Module<TestRuntime>;
+	pub type Slashing = slashing::Module<TestRuntime>;
+	pub type Equivocation = Module<TestRuntime>;
+
+	/// Account 1 has 100 staked with the slashing pallet, and the chain is at block 10
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		let mut t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		balances::GenesisConfig::<TestRuntime> {
+			balances: vec![(1, 200), (2, 100)],
+			vesting: vec![],
+		}
+		.assimilate_storage(&mut t)
+		.unwrap();
+		let mut ext: sp_io::TestExternalities = t.into();
+		ext.execute_with(|| {
+			assert_ok!(Slashing::stake(Origin::signed(1), 100));
+			run_to_block(10);
+		});
+		ext
+	}
+
+	/// The (made up) hash of canonical block `n`
+	fn canonical(n: u64) -> H256 {
+		H256::repeat_byte(n as u8 + 1)
+	}
+
+	/// Finish the current block and build canonical blocks up to `n`
+	fn run_to_block(n: u64) {
+		while System::block_number() < n {
+			Slashing::on_finalize(System::block_number());
+			let next = System::block_number() + 1;
+			System::initialize(&next, &canonical(next - 1), &Default::default(), &Default::default());
+		}
+	}
+
+	/// A sealed header at `number` on top of the canonical chain, authored by `author`; `salt`
+	/// makes otherwise equal headers differ
+	fn header(number: u64, author: u64, salt: u8) -> Header {
+		let digest = Digest {
+			logs: vec![
+				DigestItem::PreRuntime(POW_ENGINE_ID, author.encode()),
+				DigestItem::Seal(POW_ENGINE_ID, 0u64.encode()),
+			],
+		};
+		Header::new(number, H256::repeat_byte(salt), H256::default(), canonical(number - 1), digest)
+	}
+
+	fn proof(first: Header, second: Header) -> EquivocationProof<Header> {
+		EquivocationProof { first, second }
+	}
+
+	#[test]
+	fn equivocation_is_slashed() {
+		new_test_ext().execute_with(|| {
+			let issuance = Balances::total_issuance();
+			assert_ok!(Equivocation::report_equivocations(
+				RawOrigin::None.into(),
+				vec![proof(header(7, 1, 0), header(7, 1, 1))]
+			));
+
+			assert_eq!(Balances::reserved_balance(&1), 50);
+			assert_eq!(Balances::total_issuance(), issuance - 50);
+			assert!(Equivocation::punished((1, 7)));
+			let expected_event = TestEvent::equivocation(RawEvent::Equivocated(1, 7));
+			assert!(System::events().iter().any(|a| a.event == expected_event));
+		})
+	}
+
+	#[test]
+	fn only_inherent_origin() {
+		new_test_ext().execute_with(|| {
+			assert!(Equivocation::report_equivocations(
+				Origin::signed(2),
+				vec![proof(header(7, 1, 0), header(7, 1, 1))]
+			)
+			.is_err());
+		})
+	}
+
+	#[test]
+	fn bad_proofs_are_rejected() {
+		new_test_ext().execute_with(|| {
+			let check = |first, second| Equivocation::check_proof(&proof(first, second));
+			assert_err!(check(header(7, 1, 0), header(8, 1, 1)), "headers are at different heights");
+			assert_err!(check(header(7, 1, 0), header(7, 1, 0)), "headers are identical");
+			assert_err!(check(header(7, 1, 0), header(7, 2, 1)), "headers have different authors");
+
+			let mut unauthored = header(7, 1, 0);
+			unauthored.digest.logs.remove(0);
+			assert_err!(check(unauthored, header(7, 1, 1)), "first header has no author");
+		})
+	}
+
+	#[test]
+	fn headers_must_extend_this_chain() {
+		new_test_ext().execute_with(|| {
+			let check = |first, second| Equivocation::check_proof(&proof(first, second));
+			assert_err!(
+				check(header(10, 1, 0), header(10, 1, 1)),
+				"headers must be older than this block"
+			);
+
+			let mut orphan = header(7, 1, 1);
+			orphan.parent_hash = H256::repeat_byte(0xff);
+			assert_err!(check(header(7, 1, 0), orphan), "header's parent is not canonical");
+
+			let mut unsealed = header(7, 1, 1);
+			unsealed.digest.pop();
+			assert_err!(check(header(7, 1, 0), unsealed), "header has an invalid seal");
+		})
+	}
+
+	#[test]
+	fn offence_is_punished_once() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(Equivocation::report_equivocations(
+				RawOrigin::None.into(),
+				vec![proof(header(7, 1, 0), header(7, 1, 1))]
+			));
+			assert_err!(
+				Equivocation::report_equivocations(
+					RawOrigin::None.into(),
+					vec![proof(header(7, 1, 0), header(7, 1, 2))]
+				),
+				"equivocation already punished"
+			);
+			assert_eq!(Balances::reserved_balance(&1), 50);
+		})
+	}
+
+	#[test]
+	fn inherent_keeps_only_valid_proofs() {
+		new_test_ext().execute_with(|| {
+			let valid = proof(header(7, 1, 0), header(7, 1, 1));
+			let duplicate = proof(header(7, 1, 0), header(7, 1, 2));
+			let invalid = proof(header(7, 1, 0), header(7, 2, 1));
+
+			let mut data = InherentData::new();
+			data.put_data(INHERENT_IDENTIFIER, &vec![valid.clone(), duplicate, invalid]).unwrap();
+
+			assert_eq!(
+				Equivocation::create_inherent(&data),
+				Some(Call::report_equivocations(vec![valid]))
+			);
+			assert_eq!(Equivocation::create_inherent(&InherentData::new()), None);
+		})
+	}
+}
@@ -11,6 +11,18 @@ use frame_support::{
 };
 use frame_system::{self as system, ensure_signed};
 
+/// Enter a span named `$name` under the `execution-schedule` target until the end of the current
+/// scope. Spans are only recorded when the runtime is executed natively, because the Wasm runtime
+/// cannot reach the node's tracing subscriber.
+macro_rules! trace_span {
+    ($name:expr) => {
+        #[cfg(feature = "std")]
+        let span = tracing::span!(target: "execution-schedule", tracing::Level::INFO, $name);
+        #[cfg(feature = "std")]
+        let _enter = span.enter();
+    };
+}
+
 pub type TaskId = Vec<u8>;
 pub type PriorityScore = u32;
 pub type RoundIndex = u32;
@@ -89,6 +101,7 @@ decl_module! {
         fn on_initialize(n: T::BlockNumber) {
             let batch_frequency = T::ExecutionFrequency::get();
             if ((n - 1.into()) % batch_frequency).is_zero() {
+                trace_span!("refresh_signal");
                 let last_era = Era::get();
                 // clean up the previous double_map with this last_era group index
                 <SignalBank<T>>::remove_prefix(&last_era);
@@ -186,10 +199,14 @@ impl<T: Trait> Module<T> {
     ///
     /// - exhaustively executes tasks in the order of their priority
     pub fn execute_tasks(n: T::BlockNumber) {
+        trace_span!("execute_tasks");
         // task limit in terms of priority allowed to be executed every period
         let mut task_allowance = T::TaskLimit::get();
         let mut execution_q = <ExecutionQueue>::get().clone();
-        execution_q.sort_unstable();
+        {
+            trace_span!("sort_queue");
+            execution_q.sort_unstable();
+        }
         execution_q.into_iter().for_each(|task_id| {
             if let Some(task) = <PendingTasks<T>>::get(&task_id) {
                 if task.score <= task_allowance {
@@ -0,0 +1,778 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! A testnet faucet that drips funds to new accounts, with cooldowns and optional captchas
+//!
+//! New accounts have no funds to pay fees with, so they ask for a drip with an unsigned
+//! transaction. Each account gets `DripAmount` from the faucet's pot at most once every
+//! `Cooldown` blocks, and `validate_unsigned` enforces the cooldown before a request even reaches
+//! the pool, as the device-telemetry pallet does for readings.
+//!
+//! Cooldowns limit each account, but accounts cost nothing to create. A faucet that must not be
+//! drained by scripts can require a captcha: `ManageOrigin` sets a verifier key, and from then on
+//! a request carries a captcha token that a person solved in their browser, and is only queued.
+//! The offchain worker of a node that holds the verifier key sends each queued token to
+//! `CaptchaEndpoint`, signs a `Verdict` with the key, and submits it as an unsigned transaction,
+//! like the heartbeats of the im-online-lite pallet. An approving verdict pays the drip. Without a
+//! verifier key, requests are paid immediately and their tokens are ignored.
+
+use codec::{Decode, Encode};
+use sp_std::prelude::*;
+use sp_core::offchain::{Duration, StorageKind};
+use sp_runtime::{
+	offchain::http,
+	traits::{AccountIdConversion, Member, Saturating},
+	transaction_validity::{
+		InvalidTransaction, TransactionPriority, TransactionValidity, ValidTransaction,
+	},
+	ModuleId, RuntimeAppPublic, RuntimeDebug,
+};
+use frame_support::{
+	debug, decl_event, decl_module, decl_storage,
+	dispatch::DispatchResult,
+	ensure,
+	traits::{Currency, EnsureOrigin, ExistenceRequirement::AllowDeath, Get},
+	Parameter,
+};
+use frame_system::{
+	self as system, ensure_none, ensure_signed,
+	offchain::SubmitUnsignedTransaction,
+};
+
+/// The key type of the key that signs verdicts
+pub const KEY_TYPE: sp_application_crypto::KeyTypeId = sp_application_crypto::KeyTypeId(*b"fcet");
+
+/// The target of this pallet's log messages
+const LOG_TARGET: &str = "faucet";
+
+/// How long checking a token may take, in milliseconds
+pub const CHECK_TIMEOUT_MS: u64 = 2_000;
+
+/// The prefix of the offchain storage keys that hold the verdicts a node has reached
+const VERDICT_PREFIX: &[u8] = b"faucet::verdict::";
+
+/// Hardcoded pallet ID; used to create the account that holds the faucet's funds
+/// Must be exactly 8 characters long
+const PALLET_ID: ModuleId = ModuleId(*b"faucet!!");
+
+/// The application-specific crypto the verifier signs verdicts with
+pub mod crypto {
+	use super::KEY_TYPE;
+	use sp_application_crypto::{app_crypto, sr25519};
+	app_crypto!(sr25519, KEY_TYPE);
+}
+
+/// A verifier identifier that a runtime can use for `Trait::VerifierId`
+pub type VerifierId = crypto::Public;
+
+type BalanceOf<T> = <<T as Trait>::Currency as Currency<<T as system::Trait>::AccountId>>::Balance;
+
+/// A request that waits for the verifier
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct DripRequest<BlockNumber> {
+	/// The captcha token, as the captcha service issued it
+	pub token: Vec<u8>,
+	/// The block in which the request was queued
+	pub requested_at: BlockNumber,
+}
+
+/// Whether the token of a request was valid, as the verifier signs it
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct Verdict<AccountId, BlockNumber> {
+	pub recipient: AccountId,
+	/// The block of the request, so that the verdict can't be replayed for a later request
+	pub requested_at: BlockNumber,
+	pub approved: bool,
+}
+
+/// Why a request or a verdict is rejected
+#[derive(Clone, Copy, PartialEq, Eq, RuntimeDebug)]
+pub enum FaucetError {
+	/// The token is longer than `MaxTokenLength`
+	TokenTooLong,
+	/// The account got a drip less than `Cooldown` blocks ago
+	CoolingDown,
+	/// The account already has a request in the queue
+	AlreadyRequested,
+	/// The queue holds `MaxQueued` requests
+	QueueFull,
+	/// The pot can't cover a drip
+	Dry,
+	/// There is no verifier, or it did not sign the verdict
+	BadSignature,
+	/// The verdict is not for the account's queued request, for example a replay
+	UnknownRequest,
+}
+
+impl From<FaucetError> for InvalidTransaction {
+	fn from(error: FaucetError) -> Self {
+		match error {
+			FaucetError::TokenTooLong => InvalidTransaction::Call,
+			FaucetError::CoolingDown | FaucetError::QueueFull | FaucetError::Dry => {
+				InvalidTransaction::ExhaustsResources
+			}
+			FaucetError::AlreadyRequested | FaucetError::UnknownRequest => {
+				InvalidTransaction::Stale
+			}
+			FaucetError::BadSignature => InvalidTransaction::BadProof,
+		}
+	}
+}
+
+impl From<FaucetError> for &'static str {
+	fn from(error: FaucetError) -> Self {
+		match error {
+			FaucetError::TokenTooLong => "token too long",
+			FaucetError::CoolingDown => "drip too soon after the last",
+			FaucetError::AlreadyRequested => "drip already requested",
+			FaucetError::QueueFull => "too many requests queued",
+			FaucetError::Dry => "faucet is dry",
+			FaucetError::BadSignature => "invalid verifier signature",
+			FaucetError::UnknownRequest => "no such request",
+		}
+	}
+}
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The currency the faucet drips
+	type Currency: Currency<Self::AccountId>;
+
+	/// The origin that sets the verifier key
+	type ManageOrigin: EnsureOrigin<Self::Origin>;
+
+	/// The key that signs verdicts
+	type VerifierId: Member + Parameter + RuntimeAppPublic + Default;
+
+	/// The overarching call type, which the offchain worker wraps verdicts in
+	type Call: From<Call<Self>>;
+
+	/// Submits verdicts from the offchain worker as unsigned transactions
+	type SubmitTransaction: SubmitUnsignedTransaction<Self, <Self as Trait>::Call>;
+
+	/// The URL of the captcha service. The worker posts each token to it, and the service answers
+	/// 200 for a valid token and a 4xx status for an invalid one.
+	type CaptchaEndpoint: Get<&'static str>;
+
+	/// The amount of each drip
+	type DripAmount: Get<BalanceOf<Self>>;
+
+	/// The fewest blocks between two drips to the same account
+	type Cooldown: Get<Self::BlockNumber>;
+
+	/// The longest captcha token accepted
+	type MaxTokenLength: Get<u32>;
+
+	/// The most requests that wait for the verifier at once
+	type MaxQueued: Get<u32>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as Faucet {
+		/// The key that signs verdicts. Without one, requests are paid without a captcha.
+		Verifier get(fn verifier): Option<T::VerifierId>;
+
+		/// The block in which each account last got a drip
+		LastDrip get(fn last_drip): map T::AccountId => Option<T::BlockNumber>;
+
+		/// The queued request of each account
+		Requests get(fn request): map T::AccountId => Option<DripRequest<T::BlockNumber>>;
+
+		/// The accounts with a queued request, oldest first, for the worker to go through
+		Queue get(fn queue): Vec<T::AccountId>;
+
+		/// The number of the latest block that started. The system pallet forgets the block number
+		/// when a block is finalized, and the pool validates transactions on a finalized state.
+		LastBlock get(fn last_block): T::BlockNumber;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as system::Trait>::AccountId,
+		Balance = BalanceOf<T>,
+		VerifierId = <T as Trait>::VerifierId,
+	{
+		/// Someone added funds to the pot
+		Funded(AccountId, Balance),
+		/// The verifier key changed. `None` turns captchas off.
+		VerifierSet(Option<VerifierId>),
+		/// An account's request waits for the verifier
+		Requested(AccountId),
+		/// An account got a drip
+		Dripped(AccountId, Balance),
+		/// The verifier rejected an account's token
+		Rejected(AccountId),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		const DripAmount: BalanceOf<T> = T::DripAmount::get();
+
+		const Cooldown: T::BlockNumber = T::Cooldown::get();
+
+		const MaxTokenLength: u32 = T::MaxTokenLength::get();
+
+		const MaxQueued: u32 = T::MaxQueued::get();
+
+		/// Add funds to the pot
+		fn fund(origin, amount: BalanceOf<T>) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			T::Currency::transfer(&who, &Self::account_id(), amount, AllowDeath)?;
+			Self::deposit_event(RawEvent::Funded(who, amount));
+			Ok(())
+		}
+
+		/// Set the key that signs verdicts, or remove it to turn captchas off. Requests queued for
+		/// the old key are dropped, since it no longer decides them.
+		fn set_verifier(origin, verifier: Option<T::VerifierId>) -> DispatchResult {
+			T::ManageOrigin::ensure_origin(origin)?;
+
+			for recipient in <Queue<T>>::take() {
+				<Requests<T>>::remove(&recipient);
+			}
+			match &verifier {
+				Some(key) => <Verifier<T>>::put(key),
+				None => <Verifier<T>>::kill(),
+			}
+
+			Self::deposit_event(RawEvent::VerifierSet(verifier));
+			Ok(())
+		}
+
+		/// Ask for a drip to `recipient`. With a verifier, the request is queued until the
+		/// verifier has checked `token`. `validate_unsigned` has checked the request already, but
+		/// the checks are repeated so that the call is safe on its own.
+		fn request_drip(origin, recipient: T::AccountId, token: Vec<u8>) -> DispatchResult {
+			ensure_none(origin)?;
+			Self::check_request(&recipient, &token).map_err(<&'static str>::from)?;
+
+			if Self::verifier().is_none() {
+				return Self::drip(&recipient);
+			}
+
+			let requested_at = <system::Module<T>>::block_number();
+			<Requests<T>>::insert(&recipient, DripRequest { token, requested_at });
+			<Queue<T>>::mutate(|queue| queue.push(recipient.clone()));
+			Self::deposit_event(RawEvent::Requested(recipient));
+			Ok(())
+		}
+
+		/// Decide a queued request, as the verifier signed it. The checks of `validate_unsigned`
+		/// are repeated so that the call is safe on its own.
+		fn submit_verdict(
+			origin,
+			verdict: Verdict<T::AccountId, T::BlockNumber>,
+			signature: <T::VerifierId as RuntimeAppPublic>::Signature,
+		) -> DispatchResult {
+			ensure_none(origin)?;
+			Self::check_verdict(&verdict, &signature).map_err(<&'static str>::from)?;
+
+			if verdict.approved {
+				// If the pot ran dry, the request stays queued until it is refilled
+				Self::drip(&verdict.recipient)?;
+			} else {
+				Self::deposit_event(RawEvent::Rejected(verdict.recipient.clone()));
+			}
+			<Requests<T>>::remove(&verdict.recipient);
+			<Queue<T>>::mutate(|queue| queue.retain(|queued| *queued != verdict.recipient));
+			Ok(())
+		}
+
+		fn on_initialize(now: T::BlockNumber) {
+			<LastBlock<T>>::put(now);
+		}
+
+		fn offchain_worker(_now: T::BlockNumber) {
+			// Only the node that holds the verifier key can sign verdicts
+			let verifier = match Self::verifier() {
+				Some(verifier) if T::VerifierId::all().contains(&verifier) => verifier,
+				_ => return,
+			};
+			debug::RuntimeLogger::init();
+			Self::send_verdicts(&verifier, Self::check_token);
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// The account that holds the funds to drip
+	pub fn account_id() -> T::AccountId {
+		PALLET_ID.into_account()
+	}
+
+	/// The funds left to drip
+	pub fn pot() -> BalanceOf<T> {
+		T::Currency::free_balance(&Self::account_id())
+	}
+
+	/// Pay a drip from the pot, and start the recipient's cooldown
+	fn drip(recipient: &T::AccountId) -> DispatchResult {
+		let amount = T::DripAmount::get();
+		T::Currency::transfer(&Self::account_id(), recipient, amount, AllowDeath)?;
+		<LastDrip<T>>::insert(recipient, <system::Module<T>>::block_number());
+		Self::deposit_event(RawEvent::Dripped(recipient.clone(), amount));
+		Ok(())
+	}
+
+	fn check_request(recipient: &T::AccountId, token: &[u8]) -> Result<(), FaucetError> {
+		if token.len() as u32 > T::MaxTokenLength::get() {
+			return Err(FaucetError::TokenTooLong);
+		}
+		if let Some(last) = Self::last_drip(recipient) {
+			if Self::last_block() < last.saturating_add(T::Cooldown::get()) {
+				return Err(FaucetError::CoolingDown);
+			}
+		}
+		if <Requests<T>>::exists(recipient) {
+			return Err(FaucetError::AlreadyRequested);
+		}
+		if Self::verifier().is_some() && Self::queue().len() as u32 >= T::MaxQueued::get() {
+			return Err(FaucetError::QueueFull);
+		}
+		if Self::pot() < T::DripAmount::get() {
+			return Err(FaucetError::Dry);
+		}
+		Ok(())
+	}
+
+	fn check_verdict(
+		verdict: &Verdict<T::AccountId, T::BlockNumber>,
+		signature: &<T::VerifierId as RuntimeAppPublic>::Signature,
+	) -> Result<(), FaucetError> {
+		let verifier = Self::verifier().ok_or(FaucetError::BadSignature)?;
+		if !verifier.verify(&verdict.encode(), signature) {
+			return Err(FaucetError::BadSignature);
+		}
+		match Self::request(&verdict.recipient) {
+			Some(request) if request.requested_at == verdict.requested_at => Ok(()),
+			_ => Err(FaucetError::UnknownRequest),
+		}
+	}
+
+	/// Sign and submit a verdict for every queued request. The worker runs after every block, so
+	/// a verdict that does not make it into a block is sent again. Captcha tokens can usually be
+	/// checked only once, so each verdict is kept in the node's offchain storage and reused.
+	/// `check` asks the captcha service about a token, so that tests can replace it.
+	pub fn send_verdicts(
+		verifier: &T::VerifierId,
+		check: impl Fn(&[u8]) -> Result<bool, http::Error>,
+	) {
+		for recipient in Self::queue() {
+			let request = match Self::request(&recipient) {
+				Some(request) => request,
+				None => continue,
+			};
+			let approved = match Self::verdict_for(&recipient, &request, &check) {
+				Some(approved) => approved,
+				None => continue,
+			};
+
+			let verdict = Verdict { recipient, requested_at: request.requested_at, approved };
+			let signature = match verifier.sign(&verdict.encode()) {
+				Some(signature) => signature,
+				None => {
+					log::warn!(target: LOG_TARGET, "could not sign verdict");
+					return;
+				}
+			};
+			let call = Call::submit_verdict(verdict, signature);
+			if T::SubmitTransaction::submit_unsigned(call).is_err() {
+				// Usually the pool already holds this verdict
+				log::debug!(target: LOG_TARGET, "verdict not submitted");
+			}
+		}
+	}
+
+	/// Whether `request`'s token is valid, from this node's offchain storage or else from
+	/// `check`. `None` if the captcha service could not be asked, to try again after the next
+	/// block.
+	pub fn verdict_for(
+		recipient: &T::AccountId,
+		request: &DripRequest<T::BlockNumber>,
+		check: impl Fn(&[u8]) -> Result<bool, http::Error>,
+	) -> Option<bool> {
+		let mut key = VERDICT_PREFIX.to_vec();
+		(recipient, &request.requested_at).encode_to(&mut key);
+		let stored = sp_io::offchain::local_storage_get(StorageKind::PERSISTENT, &key)
+			.and_then(|raw| bool::decode(&mut &raw[..]).ok());
+		if stored.is_some() {
+			return stored;
+		}
+
+		match check(&request.token) {
+			Ok(approved) => {
+				sp_io::offchain::local_storage_set(
+					StorageKind::PERSISTENT,
+					&key,
+					&approved.encode(),
+				);
+				Some(approved)
+			}
+			Err(error) => {
+				log::warn!(target: LOG_TARGET, "captcha service failed with {:?}", error);
+				None
+			}
+		}
+	}
+
+	/// Post `token` to the captcha service, giving up after `CHECK_TIMEOUT_MS`
+	fn check_token(token: &[u8]) -> Result<bool, http::Error> {
+		let deadline = sp_io::offchain::timestamp().add(Duration::from_millis(CHECK_TIMEOUT_MS));
+		let pending = http::Request::post(T::CaptchaEndpoint::get(), vec![token])
+			.deadline(deadline)
+			.send()
+			.map_err(|_| http::Error::IoError)?;
+		let response = pending
+			.try_wait(deadline)
+			.map_err(|_| http::Error::DeadlineReached)??;
+		match response.code {
+			200 => Ok(true),
+			400..=499 => Ok(false),
+			_ => Err(http::Error::Unknown),
+		}
+	}
+}
+
+impl<T: Trait> frame_support::unsigned::ValidateUnsigned for Module<T> {
+	type Call = Call<T>;
+
+	fn validate_unsigned(call: &Self::Call) -> TransactionValidity {
+		match call {
+			Call::request_drip(recipient, token) => {
+				if let Err(error) = Self::check_request(recipient, token) {
+					return InvalidTransaction::from(error).into();
+				}
+				Ok(ValidTransaction {
+					priority: 0,
+					requires: vec![],
+					// One request per account at a time, whatever its token
+					provides: vec![(b"faucet-request", recipient).encode()],
+					longevity: 64,
+					propagate: true,
+				})
+			}
+			Call::submit_verdict(verdict, signature) => {
+				if let Err(error) = Self::check_verdict(verdict, signature) {
+					return InvalidTransaction::from(error).into();
+				}
+				Ok(ValidTransaction {
+					priority: TransactionPriority::max_value(),
+					requires: vec![],
+					provides: vec![(b"faucet-verdict", &verdict.recipient).encode()],
+					longevity: 64,
+					propagate: true,
+				})
+			}
+			_ => InvalidTransaction::Call.into(),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use event_assert::assert_last_event;
+	use sp_core::{
+		offchain::{testing::TestOffchainExt, OffchainExt},
+		H256,
+	};
+	use frame_support::{
+		assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types,
+		unsigned::ValidateUnsigned,
+	};
+	use frame_system::{offchain::TransactionSubmitter, EnsureRoot, RawOrigin};
+	use sp_runtime::{
+		testing::{Header, TestXt, UintAuthorityId},
+		traits::{BlakeTwo256, IdentityLookup, OnInitialize},
+		Perbill,
+	};
+	use std::cell::Cell;
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const ExistentialDeposit: u64 = 0;
+		pub const TransferFee: u64 = 0;
+		pub const CreationFee: u64 = 0;
+
+		pub const CaptchaEndpoint: &'static str = "http://captcha.test/verify";
+		pub const DripAmount: u64 = 10;
+		pub const Cooldown: u64 = 5;
+		pub const MaxTokenLength: u32 = 8;
+		pub const MaxQueued: u32 = 2;
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	impl balances::Trait for TestRuntime {
+		type Balance = u64;
+		type OnFreeBalanceZero = ();
+		type OnNewAccount = ();
+		type Event = TestEvent;
+		type TransferPayment = ();
+		type DustRemoval = ();
+		type ExistentialDeposit = ExistentialDeposit;
+		type TransferFee = TransferFee;
+		type CreationFee = CreationFee;
+	}
+
+	mod faucet {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			balances<T>,
+			faucet<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type Currency = balances::Module<Self>;
+		type ManageOrigin = EnsureRoot<u64>;
+		type VerifierId = UintAuthorityId;
+		type Call = Call<TestRuntime>;
+		type SubmitTransaction =
+			TransactionSubmitter<(), TestRuntime, TestXt<Call<TestRuntime>, ()>>;
+		type CaptchaEndpoint = CaptchaEndpoint;
+		type DripAmount = DripAmount;
+		type Cooldown = Cooldown;
+		type MaxTokenLength = MaxTokenLength;
+		type MaxQueued = MaxQueued;
+	}
+
+	pub type System = system::Module<TestRuntime>;
+	pub type Balances = balances::Module<TestRuntime>;
+	pub type Faucet = Module<TestRuntime>;
+
+	/// Account 9 put `pot` into the pot. The tests start in block 1, without a verifier.
+	fn new_test_ext(pot: u64) -> sp_io::TestExternalities {
+		let mut t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		balances::GenesisConfig::<TestRuntime> {
+			balances: vec![(9, 1_000)],
+			vesting: vec![],
+		}
+		.assimilate_storage(&mut t)
+		.unwrap();
+		let mut ext: sp_io::TestExternalities = t.into();
+		let (offchain, _state) = TestOffchainExt::new();
+		ext.register_extension(OffchainExt::new(offchain));
+		ext.execute_with(|| {
+			run_to_block(1);
+			assert_ok!(Faucet::fund(Origin::signed(9), pot));
+		});
+		ext
+	}
+
+	fn run_to_block(n: u64) {
+		System::set_block_number(n);
+		Faucet::on_initialize(n);
+	}
+
+	fn invalid(error: InvalidTransaction) -> TransactionValidity {
+		Err(error.into())
+	}
+
+	/// Validates a request like the pool would, and dispatches it if it is valid
+	fn request(recipient: u64, token: &[u8]) -> TransactionValidity {
+		let call = Call::request_drip(recipient, token.to_vec());
+		let validity = Faucet::validate_unsigned(&call);
+		if validity.is_ok() {
+			assert_ok!(Faucet::request_drip(RawOrigin::None.into(), recipient, token.to_vec()));
+		}
+		validity
+	}
+
+	/// A verdict on `recipient`'s queued request, signed with `signer`'s key
+	fn signed_verdict(recipient: u64, approved: bool, signer: u64) -> Call<TestRuntime> {
+		let requested_at = Faucet::request(&recipient).map_or(0, |r| r.requested_at);
+		let verdict = Verdict { recipient, requested_at, approved };
+		let signature = UintAuthorityId(signer).sign(&verdict.encode()).unwrap();
+		Call::submit_verdict(verdict, signature)
+	}
+
+	/// Validates a verdict like the pool would, and dispatches it if it is valid
+	fn submit(call: Call<TestRuntime>) -> TransactionValidity {
+		let validity = Faucet::validate_unsigned(&call);
+		if validity.is_ok() {
+			if let Call::submit_verdict(verdict, signature) = call {
+				assert_ok!(Faucet::submit_verdict(RawOrigin::None.into(), verdict, signature));
+			}
+		}
+		validity
+	}
+
+	fn set_verifier(verifier: u64) {
+		let verifier = Some(UintAuthorityId(verifier));
+		assert_ok!(Faucet::set_verifier(RawOrigin::Root.into(), verifier));
+	}
+
+	#[test]
+	fn drips_without_a_verifier() {
+		new_test_ext(100).execute_with(|| {
+			assert!(request(1, b"").is_ok());
+			assert_last_event::<TestRuntime>(RawEvent::Dripped(1, 10));
+			assert_eq!(Balances::free_balance(&1), 10);
+			assert_eq!(Faucet::last_drip(&1), Some(1));
+			assert_eq!(Faucet::pot(), 90);
+		})
+	}
+
+	#[test]
+	fn cooldown_limits_each_account() {
+		new_test_ext(100).execute_with(|| {
+			assert!(request(1, b"").is_ok());
+			run_to_block(5);
+			assert_eq!(request(1, b""), invalid(InvalidTransaction::ExhaustsResources));
+			assert_err!(
+				Faucet::request_drip(RawOrigin::None.into(), 1, vec![]),
+				"drip too soon after the last"
+			);
+			assert!(request(2, b"").is_ok());
+
+			run_to_block(6);
+			assert!(request(1, b"").is_ok());
+			assert_eq!(Balances::free_balance(&1), 20);
+		})
+	}
+
+	#[test]
+	fn invalid_requests_are_rejected() {
+		new_test_ext(15).execute_with(|| {
+			assert_eq!(request(1, b"too-long!"), invalid(InvalidTransaction::Call));
+			assert!(request(1, b"").is_ok());
+			assert_eq!(request(2, b""), invalid(InvalidTransaction::ExhaustsResources));
+			assert_err!(
+				Faucet::request_drip(RawOrigin::None.into(), 2, vec![]),
+				"faucet is dry"
+			);
+			assert!(Faucet::request_drip(Origin::signed(2), 2, vec![]).is_err());
+		})
+	}
+
+	#[test]
+	fn verifier_approves_queued_requests() {
+		new_test_ext(100).execute_with(|| {
+			set_verifier(7);
+			assert!(request(1, b"solved").is_ok());
+			assert_last_event::<TestRuntime>(RawEvent::Requested(1));
+			assert_eq!(Balances::free_balance(&1), 0);
+			assert_eq!(Faucet::queue(), vec![1]);
+			assert_eq!(request(1, b"again"), invalid(InvalidTransaction::Stale));
+
+			assert!(submit(signed_verdict(1, true, 7)).is_ok());
+			assert_last_event::<TestRuntime>(RawEvent::Dripped(1, 10));
+			assert_eq!(Balances::free_balance(&1), 10);
+			assert_eq!(Faucet::request(&1), None);
+			assert!(Faucet::queue().is_empty());
+		})
+	}
+
+	#[test]
+	fn verifier_rejects_invalid_tokens() {
+		new_test_ext(100).execute_with(|| {
+			set_verifier(7);
+			assert!(request(1, b"guessed").is_ok());
+			assert!(submit(signed_verdict(1, false, 7)).is_ok());
+			assert_last_event::<TestRuntime>(RawEvent::Rejected(1));
+			assert_eq!(Balances::free_balance(&1), 0);
+			assert_eq!(Faucet::last_drip(&1), None);
+
+			// A rejection starts no cooldown, so the account can try again
+			assert!(request(1, b"solved").is_ok());
+		})
+	}
+
+	#[test]
+	fn only_the_verifier_decides_current_requests() {
+		new_test_ext(100).execute_with(|| {
+			set_verifier(7);
+			assert!(request(1, b"solved").is_ok());
+			assert_eq!(submit(signed_verdict(1, true, 8)), invalid(InvalidTransaction::BadProof));
+			assert_eq!(submit(signed_verdict(2, true, 7)), invalid(InvalidTransaction::Stale));
+
+			// A verdict for an earlier request can't be replayed for a later one
+			let old_verdict = signed_verdict(1, true, 7);
+			assert!(submit(old_verdict.clone()).is_ok());
+			run_to_block(6);
+			assert!(request(1, b"solved").is_ok());
+			assert_eq!(submit(old_verdict), invalid(InvalidTransaction::Stale));
+		})
+	}
+
+	#[test]
+	fn queue_is_bounded() {
+		new_test_ext(100).execute_with(|| {
+			set_verifier(7);
+			assert!(request(1, b"a").is_ok());
+			assert!(request(2, b"b").is_ok());
+			assert_eq!(request(3, b"c"), invalid(InvalidTransaction::ExhaustsResources));
+
+			// A new verifier drops the requests queued for the old one
+			set_verifier(8);
+			assert!(Faucet::queue().is_empty());
+			assert_eq!(Faucet::request(&1), None);
+			assert!(request(3, b"c").is_ok());
+		})
+	}
+
+	#[test]
+	fn verdicts_are_checked_once_per_request() {
+		new_test_ext(100).execute_with(|| {
+			set_verifier(7);
+			assert!(request(1, b"solved").is_ok());
+			let request = Faucet::request(&1).unwrap();
+
+			let checks = Cell::new(0);
+			let check = |token: &[u8]| {
+				checks.set(checks.get() + 1);
+				Ok(token == b"solved")
+			};
+			assert_eq!(Faucet::verdict_for(&1, &request, &check), Some(true));
+			assert_eq!(Faucet::verdict_for(&1, &request, &check), Some(true));
+			assert_eq!(checks.get(), 1);
+
+			// A failed check is not remembered, and tried again later
+			let down = |_: &[u8]| Err(http::Error::DeadlineReached);
+			assert_eq!(Faucet::verdict_for(&2, &request, down), None);
+			assert_eq!(Faucet::verdict_for(&2, &request, &check), Some(true));
+			assert_eq!(checks.get(), 2);
+		})
+	}
+}
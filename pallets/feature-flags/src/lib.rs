@@ -0,0 +1,279 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! Feature flags with gradual rollout
+//!
+//! New behavior in a runtime usually goes live for everybody at once, with the runtime upgrade
+//! that ships it. Feature flags separate shipping from enabling. The code for a new behavior is
+//! deployed switched off, and `ManageOrigin` switches it on later, for a growing share of
+//! accounts.
+//!
+//! A flag has an activation block and a rollout percentage. From the activation block on, it is
+//! enabled for an account if the account's bucket is below the rollout percentage. The bucket is
+//! the hash of the flag's name and the account, modulo 100. It never changes, so raising the
+//! percentage only ever adds accounts. Hashing the name along with the account puts each flag's
+//! early adopters in a different group of accounts.
+//!
+//! Pallets ask for `FeatureFlags` in their `Trait`. The runtime picks this pallet, or `()`, which
+//! keeps every flag switched off.
+
+use codec::{Decode, Encode};
+use sp_std::prelude::*;
+use sp_runtime::{traits::Hash, RuntimeDebug};
+use frame_support::{
+	decl_event, decl_module, decl_storage,
+	dispatch::DispatchResult,
+	ensure,
+	traits::EnsureOrigin,
+};
+use frame_system::{self as system};
+
+/// Something that knows which features are enabled for which accounts
+pub trait FeatureFlags<AccountId> {
+	fn is_enabled(flag: &[u8], who: &AccountId) -> bool;
+}
+
+impl<AccountId> FeatureFlags<AccountId> for () {
+	fn is_enabled(_flag: &[u8], _who: &AccountId) -> bool {
+		false
+	}
+}
+
+/// When and for whom a feature is enabled
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct Flag<BlockNumber> {
+	/// The first block in which the feature is enabled
+	pub activation: BlockNumber,
+	/// The percentage of accounts the feature is enabled for, from 0 to 100
+	pub rollout: u8,
+}
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The origin that sets and removes flags
+	type ManageOrigin: EnsureOrigin<Self::Origin>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as FeatureFlags {
+		/// The flags that are set, by name. Features without a flag are disabled.
+		Flags get(fn flag): map Vec<u8> => Option<Flag<T::BlockNumber>>;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		BlockNumber = <T as system::Trait>::BlockNumber,
+	{
+		/// A flag was set: name, activation block, rollout percentage
+		FlagSet(Vec<u8>, BlockNumber, u8),
+		/// A flag was removed, disabling its feature for everybody
+		FlagRemoved(Vec<u8>),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		/// Enable a feature from `activation` on, for `rollout` percent of accounts
+		fn set_flag(
+			origin,
+			name: Vec<u8>,
+			activation: T::BlockNumber,
+			rollout: u8,
+		) -> DispatchResult {
+			T::ManageOrigin::ensure_origin(origin)?;
+			ensure!(rollout <= 100, "rollout is a percentage");
+
+			<Flags<T>>::insert(&name, Flag { activation, rollout });
+			Self::deposit_event(RawEvent::FlagSet(name, activation, rollout));
+			Ok(())
+		}
+
+		fn remove_flag(origin, name: Vec<u8>) -> DispatchResult {
+			T::ManageOrigin::ensure_origin(origin)?;
+			ensure!(<Flags<T>>::exists(&name), "no such flag");
+
+			<Flags<T>>::remove(&name);
+			Self::deposit_event(RawEvent::FlagRemoved(name));
+			Ok(())
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// The bucket of `who` for the flag `name`, from 0 to 99
+	pub fn bucket(name: &[u8], who: &T::AccountId) -> u8 {
+		let hash = T::Hashing::hash_of(&(name, who));
+		let mut bytes = [0u8; 8];
+		bytes.copy_from_slice(&hash.as_ref()[..8]);
+		(u64::from_le_bytes(bytes) % 100) as u8
+	}
+}
+
+impl<T: Trait> FeatureFlags<T::AccountId> for Module<T> {
+	fn is_enabled(name: &[u8], who: &T::AccountId) -> bool {
+		match Self::flag(name.to_vec()) {
+			Some(flag) => {
+				<system::Module<T>>::block_number() >= flag.activation
+					&& Self::bucket(name, who) < flag.rollout
+			}
+			None => false,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use event_assert::assert_has_event;
+	use sp_core::H256;
+	use frame_support::{
+		assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types
+	};
+	use frame_system::{EnsureRoot, RawOrigin};
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup},
+		Perbill,
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	mod feature_flags {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			feature_flags<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type ManageOrigin = EnsureRoot<u64>;
+	}
+
+	pub type System = system::Module<TestRuntime>;
+	pub type Flags = Module<TestRuntime>;
+
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap()
+			.into()
+	}
+
+	const FEATURE: &[u8] = b"feature";
+
+	fn set(activation: u64, rollout: u8) -> DispatchResult {
+		Flags::set_flag(RawOrigin::Root.into(), FEATURE.to_vec(), activation, rollout)
+	}
+
+	/// The accounts among 0 to 999 that the feature is enabled for
+	fn enabled() -> Vec<u64> {
+		(0..1000).filter(|who| Flags::is_enabled(FEATURE, who)).collect()
+	}
+
+	#[test]
+	fn disabled_until_activation() {
+		new_test_ext().execute_with(|| {
+			assert!(enabled().is_empty());
+			assert_ok!(set(10, 100));
+
+			System::set_block_number(9);
+			assert!(enabled().is_empty());
+			System::set_block_number(10);
+			assert_eq!(enabled().len(), 1000);
+		})
+	}
+
+	#[test]
+	fn rollout_only_adds_accounts() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(set(0, 0));
+			assert!(enabled().is_empty());
+
+			assert_ok!(set(0, 20));
+			let early = enabled();
+			// Roughly a fifth of the accounts, and always the same ones
+			assert!(early.len() > 150 && early.len() < 250, "{} accounts", early.len());
+			assert_eq!(enabled(), early);
+
+			assert_ok!(set(0, 50));
+			let later = enabled();
+			assert!(later.len() > 400 && later.len() < 600, "{} accounts", later.len());
+			assert!(early.iter().all(|who| later.contains(who)));
+		})
+	}
+
+	#[test]
+	fn buckets_differ_between_flags() {
+		new_test_ext().execute_with(|| {
+			let buckets = |name: &[u8]| {
+				(0..100).map(|who| Flags::bucket(name, &who)).collect::<Vec<_>>()
+			};
+			assert!(buckets(b"feature").iter().all(|bucket| *bucket < 100));
+			assert_ne!(buckets(b"feature"), buckets(b"other feature"));
+		})
+	}
+
+	#[test]
+	fn only_manage_origin_sets_flags() {
+		new_test_ext().execute_with(|| {
+			assert!(Flags::set_flag(Origin::signed(1), FEATURE.to_vec(), 0, 100).is_err());
+			assert_err!(set(0, 101), "rollout is a percentage");
+
+			assert_ok!(set(0, 100));
+			assert_has_event::<TestRuntime>(RawEvent::FlagSet(FEATURE.to_vec(), 0, 100));
+
+			assert!(Flags::remove_flag(Origin::signed(1), FEATURE.to_vec()).is_err());
+			assert_ok!(Flags::remove_flag(RawOrigin::Root.into(), FEATURE.to_vec()));
+			assert!(enabled().is_empty());
+			assert_err!(
+				Flags::remove_flag(RawOrigin::Root.into(), FEATURE.to_vec()),
+				"no such flag"
+			);
+		})
+	}
+
+	#[test]
+	fn unit_keeps_features_off() {
+		assert!(!<() as FeatureFlags<u64>>::is_enabled(FEATURE, &1));
+	}
+}
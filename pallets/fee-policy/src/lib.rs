@@ -0,0 +1,362 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! Where transaction fees go, as chosen by governance
+//!
+//! A runtime usually fixes what happens to fees in its configuration: `OnTransactionPayment` burns
+//! them, pays them to a treasury, or pays them to the block author. Changing that takes a runtime
+//! upgrade. This pallet makes the choice a storage item instead. `ManageOrigin` sets the
+//! `Destination`, and the pallet, used as the runtime's `OnUnbalanced` fee handler, consults it
+//! for every fee:
+//!
+//! * `Burn` drops the fee, which lowers the total issuance.
+//! * `ToTreasury` hands the fee to the `Treasury` handler.
+//! * `ToAuthor` pays the fee to the author of the current block. Fees paid when no author is
+//!   known, e.g. in genesis, are burned.
+//!
+//! A change takes effect with the next fee, even within the same block.
+
+use codec::{Decode, Encode};
+use sp_runtime::RuntimeDebug;
+use frame_support::{
+	decl_event, decl_module, decl_storage,
+	dispatch::DispatchResult,
+	traits::{Currency, EnsureOrigin, FindAuthor, Imbalance, OnUnbalanced},
+};
+use frame_system as system;
+
+type BalanceOf<T> = <<T as Trait>::Currency as Currency<<T as system::Trait>::AccountId>>::Balance;
+type NegativeImbalanceOf<T> =
+	<<T as Trait>::Currency as Currency<<T as system::Trait>::AccountId>>::NegativeImbalance;
+
+/// What happens to transaction fees
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, RuntimeDebug)]
+pub enum FeeDestination {
+	Burn,
+	ToTreasury,
+	ToAuthor,
+}
+
+impl Default for FeeDestination {
+	fn default() -> Self {
+		FeeDestination::Burn
+	}
+}
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The currency in which fees are paid
+	type Currency: Currency<Self::AccountId>;
+
+	/// Identifies the author of the current block from its pre-runtime digests
+	type FindAuthor: FindAuthor<Self::AccountId>;
+
+	/// Receives the fees while the destination is `ToTreasury`
+	type Treasury: OnUnbalanced<NegativeImbalanceOf<Self>>;
+
+	/// The origin that chooses the destination
+	type ManageOrigin: EnsureOrigin<Self::Origin>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as FeePolicy {
+		/// Where fees go. Burned unless governance decided otherwise.
+		Destination get(fn destination): FeeDestination;
+
+		/// The author of the current block. Only set while a block is being executed.
+		Author get(fn author): Option<T::AccountId>;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as system::Trait>::AccountId,
+		Balance = BalanceOf<T>,
+	{
+		/// Fees go to a new destination from now on
+		DestinationChanged(FeeDestination),
+		/// A fee was burned
+		FeeBurned(Balance),
+		/// A fee was handed to the treasury
+		FeeToTreasury(Balance),
+		/// A fee was paid to the block author
+		FeeToAuthor(AccountId, Balance),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		/// Send fees to `destination` from now on
+		fn set_destination(origin, destination: FeeDestination) -> DispatchResult {
+			T::ManageOrigin::ensure_origin(origin)?;
+			Destination::put(destination);
+			Self::deposit_event(RawEvent::DestinationChanged(destination));
+			Ok(())
+		}
+
+		fn on_initialize(_n: T::BlockNumber) {
+			let digest = <system::Module<T>>::digest();
+			let pre_runtime_digests = digest.logs().iter().filter_map(|d| d.as_pre_runtime());
+			if let Some(author) = T::FindAuthor::find_author(pre_runtime_digests) {
+				<Author<T>>::put(author);
+			}
+		}
+
+		fn on_finalize(_n: T::BlockNumber) {
+			<Author<T>>::kill();
+		}
+	}
+}
+
+/// Sends each fee where `Destination` says
+impl<T: Trait> OnUnbalanced<NegativeImbalanceOf<T>> for Module<T> {
+	fn on_unbalanced(fee: NegativeImbalanceOf<T>) {
+		let amount = fee.peek();
+		match (Self::destination(), Self::author()) {
+			(FeeDestination::ToTreasury, _) => {
+				T::Treasury::on_unbalanced(fee);
+				Self::deposit_event(RawEvent::FeeToTreasury(amount));
+			}
+			(FeeDestination::ToAuthor, Some(author)) => {
+				T::Currency::resolve_creating(&author, fee);
+				Self::deposit_event(RawEvent::FeeToAuthor(author, amount));
+			}
+			// Dropping the imbalance burns it
+			(FeeDestination::Burn, _) | (FeeDestination::ToAuthor, None) => {
+				drop(fee);
+				Self::deposit_event(RawEvent::FeeBurned(amount));
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use event_assert::assert_last_event;
+
+	use std::cell::RefCell;
+	use sp_core::H256;
+	use frame_support::{
+		assert_ok, impl_outer_event, impl_outer_origin, parameter_types,
+		traits::{ExistenceRequirement, WithdrawReason},
+	};
+	use frame_system::{EnsureRoot, RawOrigin};
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup, OnFinalize, OnInitialize},
+		ConsensusEngineId, Perbill,
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	thread_local! {
+		static AUTHOR: RefCell<Option<u64>> = RefCell::new(None);
+	}
+
+	/// Pretends the author of every block is whoever `set_author` named last
+	pub struct TestAuthor;
+	impl FindAuthor<u64> for TestAuthor {
+		fn find_author<'a, I>(_digests: I) -> Option<u64>
+		where
+			I: 'a + IntoIterator<Item = (ConsensusEngineId, &'a [u8])>,
+		{
+			AUTHOR.with(|a| *a.borrow())
+		}
+	}
+
+	fn set_author(author: Option<u64>) {
+		AUTHOR.with(|a| *a.borrow_mut() = author);
+	}
+
+	const TREASURY: u64 = 99;
+
+	/// Pays fees into the `TREASURY` account
+	pub struct TestTreasury;
+	impl OnUnbalanced<NegativeImbalanceOf<TestRuntime>> for TestTreasury {
+		fn on_unbalanced(fee: NegativeImbalanceOf<TestRuntime>) {
+			Balances::resolve_creating(&TREASURY, fee);
+		}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const ExistentialDeposit: u64 = 0;
+		pub const TransferFee: u64 = 0;
+		pub const CreationFee: u64 = 0;
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	impl balances::Trait for TestRuntime {
+		type Balance = u64;
+		type OnFreeBalanceZero = ();
+		type OnNewAccount = ();
+		type Event = TestEvent;
+		type TransferPayment = ();
+		type DustRemoval = ();
+		type ExistentialDeposit = ExistentialDeposit;
+		type TransferFee = TransferFee;
+		type CreationFee = CreationFee;
+	}
+
+	mod fee_policy {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			balances<T>,
+			fee_policy<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type Currency = balances::Module<Self>;
+		type FindAuthor = TestAuthor;
+		type Treasury = TestTreasury;
+		type ManageOrigin = EnsureRoot<u64>;
+	}
+
+	pub type Balances = balances::Module<TestRuntime>;
+	pub type FeePolicy = Module<TestRuntime>;
+
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		set_author(None);
+		let mut t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		balances::GenesisConfig::<TestRuntime> {
+			balances: vec![(1, 100), (2, 100)],
+			vesting: vec![],
+		}
+		.assimilate_storage(&mut t)
+		.unwrap();
+		t.into()
+	}
+
+	/// Charge account 2 a fee of `amount` and hand it to the pallet, as transaction payment would
+	fn pay_fee(amount: u64) {
+		let fee = Balances::withdraw(
+			&2,
+			amount,
+			WithdrawReason::TransactionPayment.into(),
+			ExistenceRequirement::KeepAlive,
+		)
+		.expect("account can pay the fee");
+		FeePolicy::on_unbalanced(fee);
+	}
+
+	fn set_destination(destination: FeeDestination) -> DispatchResult {
+		FeePolicy::set_destination(RawOrigin::Root.into(), destination)
+	}
+
+	#[test]
+	fn fees_are_burned_by_default() {
+		new_test_ext().execute_with(|| {
+			set_author(Some(1));
+			FeePolicy::on_initialize(1);
+			pay_fee(10);
+
+			assert_eq!(FeePolicy::destination(), FeeDestination::Burn);
+			assert_eq!(Balances::total_issuance(), 190);
+			assert_eq!(Balances::free_balance(&1), 100);
+			assert_last_event::<TestRuntime>(RawEvent::FeeBurned(10));
+		})
+	}
+
+	#[test]
+	fn fees_go_to_the_treasury() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(set_destination(FeeDestination::ToTreasury));
+			pay_fee(10);
+
+			assert_eq!(Balances::free_balance(&TREASURY), 10);
+			assert_eq!(Balances::total_issuance(), 200);
+			assert_last_event::<TestRuntime>(RawEvent::FeeToTreasury(10));
+		})
+	}
+
+	#[test]
+	fn fees_go_to_the_author() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(set_destination(FeeDestination::ToAuthor));
+			set_author(Some(1));
+			FeePolicy::on_initialize(1);
+			pay_fee(10);
+
+			assert_eq!(Balances::free_balance(&1), 110);
+			assert_last_event::<TestRuntime>(RawEvent::FeeToAuthor(1, 10));
+
+			// After the block, the author is forgotten, and fees without an author are burned
+			FeePolicy::on_finalize(1);
+			pay_fee(10);
+			assert_eq!(Balances::free_balance(&1), 110);
+			assert_eq!(Balances::total_issuance(), 190);
+		})
+	}
+
+	#[test]
+	fn destination_switches_mid_chain() {
+		new_test_ext().execute_with(|| {
+			set_author(Some(1));
+			FeePolicy::on_initialize(1);
+
+			pay_fee(10);
+			assert_ok!(set_destination(FeeDestination::ToTreasury));
+			assert_last_event::<TestRuntime>(RawEvent::DestinationChanged(FeeDestination::ToTreasury));
+			pay_fee(20);
+			FeePolicy::on_finalize(1);
+
+			FeePolicy::on_initialize(2);
+			assert_ok!(set_destination(FeeDestination::ToAuthor));
+			pay_fee(30);
+			assert_ok!(set_destination(FeeDestination::Burn));
+			pay_fee(40);
+
+			// Each fee went where the destination pointed when it was paid
+			assert_eq!(Balances::free_balance(&TREASURY), 20);
+			assert_eq!(Balances::free_balance(&1), 130);
+			assert_eq!(Balances::total_issuance(), 200 - 10 - 40);
+		})
+	}
+
+	#[test]
+	fn only_manage_origin_sets_destination() {
+		new_test_ext().execute_with(|| {
+			let destination = FeeDestination::ToAuthor;
+			assert!(FeePolicy::set_destination(Origin::signed(1), destination).is_err());
+			assert_eq!(FeePolicy::destination(), FeeDestination::Burn);
+		})
+	}
+}
@@ -0,0 +1,394 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! Fee sponsorship
+//!
+//! A sponsor registers beneficiaries, and from then on pays the transaction fees of those
+//! beneficiaries. This lets an application onboard users who hold no tokens yet.
+//!
+//! Fees are withdrawn by a signed extension. `ChargeSponsored` takes the place of
+//! `transaction_payment::ChargeTransactionPayment` in the runtime's `SignedExtra`, computes the
+//! fee the same way, and only changes whom it is withdrawn from:
+//! * A sponsored signer's fee is withdrawn from their sponsor. The signer still pays their own tip,
+//!   so that a beneficiary cannot spend the sponsor's funds on priority.
+//! * When the sponsor cannot afford the fee, the signer pays it themselves. If they cannot either,
+//!   the transaction is invalid.
+
+use codec::{Decode, Encode};
+use sp_runtime::{
+	traits::{CheckedSub, SaturatedConversion, Saturating, SignedExtension, Zero},
+	transaction_validity::{
+		InvalidTransaction, TransactionPriority, TransactionValidity, TransactionValidityError,
+		ValidTransaction,
+	},
+};
+use frame_support::{
+	decl_event, decl_module, decl_storage,
+	dispatch::DispatchResult,
+	ensure,
+	traits::{Currency, ExistenceRequirement, Imbalance, OnUnbalanced, WithdrawReason},
+	weights::DispatchInfo,
+};
+use frame_system::{self as system, ensure_signed};
+use transaction_payment::ChargeTransactionPayment;
+
+type BalanceOf<T> = <<T as transaction_payment::Trait>::Currency as Currency<
+	<T as system::Trait>::AccountId,
+>>::Balance;
+type NegativeImbalanceOf<T> = <<T as transaction_payment::Trait>::Currency as Currency<
+	<T as system::Trait>::AccountId,
+>>::NegativeImbalance;
+
+pub trait Trait: system::Trait + transaction_payment::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as FeeSponsor {
+		/// The account that pays each beneficiary's fees
+		SponsorOf get(fn sponsor_of): map T::AccountId => Option<T::AccountId>;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as system::Trait>::AccountId,
+	{
+		/// A sponsor (first) now pays the fees of a beneficiary (second)
+		Sponsored(AccountId, AccountId),
+		/// A sponsor (first) no longer pays the fees of a beneficiary (second)
+		SponsorshipEnded(AccountId, AccountId),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		/// Pay the fees of `beneficiary` from now on
+		fn sponsor(origin, beneficiary: T::AccountId) -> DispatchResult {
+			let sponsor = ensure_signed(origin)?;
+			ensure!(sponsor != beneficiary, "cannot sponsor oneself");
+			ensure!(!<SponsorOf<T>>::exists(&beneficiary), "already sponsored");
+
+			<SponsorOf<T>>::insert(&beneficiary, &sponsor);
+			Self::deposit_event(RawEvent::Sponsored(sponsor, beneficiary));
+			Ok(())
+		}
+
+		/// Stop paying the fees of `beneficiary`
+		fn end_sponsorship(origin, beneficiary: T::AccountId) -> DispatchResult {
+			let sponsor = ensure_signed(origin)?;
+			ensure!(Self::sponsor_of(&beneficiary) == Some(sponsor.clone()), "not the sponsor");
+
+			<SponsorOf<T>>::remove(&beneficiary);
+			Self::deposit_event(RawEvent::SponsorshipEnded(sponsor, beneficiary));
+			Ok(())
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// The account that pays `fee` for a transaction signed by `who`
+	pub fn fee_payer(who: &T::AccountId, fee: BalanceOf<T>) -> T::AccountId {
+		Self::sponsor_of(who)
+			.filter(|sponsor| Self::can_pay(sponsor, fee))
+			.unwrap_or_else(|| who.clone())
+	}
+
+	/// Whether `who` can pay `amount` in fees and keep their account alive
+	fn can_pay(who: &T::AccountId, amount: BalanceOf<T>) -> bool {
+		T::Currency::free_balance(who)
+			.checked_sub(&amount)
+			.filter(|left| *left >= T::Currency::minimum_balance())
+			.map_or(false, |left| {
+				T::Currency::ensure_can_withdraw(
+					who,
+					amount,
+					WithdrawReason::TransactionPayment.into(),
+					left,
+				)
+				.is_ok()
+			})
+	}
+
+	/// Withdraw `fee` and `tip` for a transaction signed by `who`. Nothing is withdrawn if either
+	/// cannot be paid.
+	fn charge(
+		who: &T::AccountId,
+		fee: BalanceOf<T>,
+		tip: BalanceOf<T>,
+	) -> Option<NegativeImbalanceOf<T>> {
+		let payer = Self::fee_payer(who, fee);
+		if payer == *who {
+			return T::Currency::withdraw(
+				who,
+				fee.saturating_add(tip),
+				WithdrawReason::TransactionPayment | WithdrawReason::Tip,
+				ExistenceRequirement::KeepAlive,
+			)
+			.ok();
+		}
+
+		// The tip is withdrawn first, because it is the one that may fail. The sponsor was just
+		// checked to afford the fee. Beneficiaries may not have an account, so a zero tip is not
+		// withdrawn at all.
+		let tip = if tip.is_zero() {
+			NegativeImbalanceOf::<T>::zero()
+		} else {
+			T::Currency::withdraw(
+				who,
+				tip,
+				WithdrawReason::Tip.into(),
+				ExistenceRequirement::KeepAlive,
+			)
+			.ok()?
+		};
+		let fee = T::Currency::withdraw(
+			&payer,
+			fee,
+			WithdrawReason::TransactionPayment.into(),
+			ExistenceRequirement::KeepAlive,
+		)
+		.ok()?;
+		Some(fee.merge(tip))
+	}
+}
+
+/// Charges transaction fees to the signer's sponsor, if they have one that can afford them. The
+/// tip is always paid by the signer.
+#[derive(Encode, Decode, Clone, Eq, PartialEq)]
+pub struct ChargeSponsored<T: Trait + Send + Sync>(#[codec(compact)] BalanceOf<T>);
+
+impl<T: Trait + Send + Sync> ChargeSponsored<T> {
+	/// Pay `tip` on top of the fee
+	pub fn from(tip: BalanceOf<T>) -> Self {
+		Self(tip)
+	}
+}
+
+impl<T: Trait + Send + Sync> sp_std::fmt::Debug for ChargeSponsored<T> {
+	#[cfg(feature = "std")]
+	fn fmt(&self, f: &mut sp_std::fmt::Formatter) -> sp_std::fmt::Result {
+		write!(f, "ChargeSponsored<{:?}>", self.0)
+	}
+	#[cfg(not(feature = "std"))]
+	fn fmt(&self, _: &mut sp_std::fmt::Formatter) -> sp_std::fmt::Result {
+		Ok(())
+	}
+}
+
+impl<T: Trait + Send + Sync> SignedExtension for ChargeSponsored<T> {
+	type AccountId = T::AccountId;
+	type Call = <T as system::Trait>::Call;
+	type AdditionalSigned = ();
+	type DispatchInfo = DispatchInfo;
+	type Pre = ();
+
+	fn additional_signed(&self) -> Result<(), TransactionValidityError> {
+		Ok(())
+	}
+
+	fn validate(
+		&self,
+		who: &Self::AccountId,
+		_call: &Self::Call,
+		info: Self::DispatchInfo,
+		len: usize,
+	) -> TransactionValidity {
+		let tip = self.0;
+		// The fee without the tip, computed exactly as `ChargeTransactionPayment` does
+		let fee = ChargeTransactionPayment::<T>::compute_fee(len as u32, info, Zero::zero());
+
+		let imbalance = Module::<T>::charge(who, fee, tip).ok_or(InvalidTransaction::Payment)?;
+		T::OnTransactionPayment::on_unbalanced(imbalance);
+
+		let mut valid = ValidTransaction::default();
+		valid.priority = fee.saturating_add(tip).saturated_into::<TransactionPriority>();
+		Ok(valid)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use event_assert::assert_last_event;
+	use sp_core::H256;
+	use frame_support::{
+		assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types,
+		weights::DispatchClass,
+	};
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, ConvertInto, IdentityLookup},
+		Perbill,
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const ExistentialDeposit: u64 = 1;
+		pub const TransferFee: u64 = 0;
+		pub const CreationFee: u64 = 0;
+
+		pub const TransactionBaseFee: u64 = 5;
+		pub const TransactionByteFee: u64 = 1;
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	impl balances::Trait for TestRuntime {
+		type Balance = u64;
+		type OnFreeBalanceZero = ();
+		type OnNewAccount = ();
+		type Event = TestEvent;
+		type TransferPayment = ();
+		type DustRemoval = ();
+		type ExistentialDeposit = ExistentialDeposit;
+		type TransferFee = TransferFee;
+		type CreationFee = CreationFee;
+	}
+
+	impl transaction_payment::Trait for TestRuntime {
+		type Currency = Balances;
+		type OnTransactionPayment = ();
+		type TransactionBaseFee = TransactionBaseFee;
+		type TransactionByteFee = TransactionByteFee;
+		type WeightToFee = ConvertInto;
+		type FeeMultiplierUpdate = ();
+	}
+
+	mod fee_sponsor {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			balances<T>,
+			fee_sponsor<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+	}
+
+	pub type Balances = balances::Module<TestRuntime>;
+	pub type FeeSponsor = Module<TestRuntime>;
+
+	/// Account 1 is a sponsor with 100 tokens, account 2 has 30 and account 3 has none
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		let mut t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		balances::GenesisConfig::<TestRuntime> {
+			balances: vec![(1, 100), (2, 30)],
+			vesting: vec![],
+		}
+		.assimilate_storage(&mut t)
+		.unwrap();
+		t.into()
+	}
+
+	/// Charge a transaction signed by `who` with a fee of 5 (base) + 10 (length) + 5 (weight) = 20
+	fn charge(who: u64, tip: u64) -> TransactionValidity {
+		let info = DispatchInfo { weight: 5, class: DispatchClass::Normal, pays_fee: true };
+		ChargeSponsored::<TestRuntime>::from(tip).validate(&who, &(), info, 10)
+	}
+
+	#[test]
+	fn unsponsored_accounts_pay_their_own_fees() {
+		new_test_ext().execute_with(|| {
+			assert!(charge(2, 3).is_ok());
+			assert_eq!(Balances::free_balance(&2), 7);
+			assert_eq!(Balances::free_balance(&1), 100);
+
+			let payment: TransactionValidity = InvalidTransaction::Payment.into();
+			assert_eq!(charge(3, 0), payment);
+		})
+	}
+
+	#[test]
+	fn sponsors_pay_fees_but_not_tips() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(FeeSponsor::sponsor(Origin::signed(1), 3));
+			assert_last_event::<TestRuntime>(RawEvent::Sponsored(1, 3));
+
+			// Account 3 has nothing, so it can be charged, but cannot tip
+			assert!(charge(3, 0).is_ok());
+			assert_eq!(Balances::free_balance(&1), 80);
+			let payment: TransactionValidity = InvalidTransaction::Payment.into();
+			assert_eq!(charge(3, 1), payment);
+			assert_eq!(Balances::free_balance(&1), 80);
+
+			assert_ok!(FeeSponsor::sponsor(Origin::signed(1), 2));
+			assert!(charge(2, 3).is_ok());
+			assert_eq!(Balances::free_balance(&1), 60);
+			assert_eq!(Balances::free_balance(&2), 27);
+		})
+	}
+
+	#[test]
+	fn beneficiaries_pay_when_sponsor_is_exhausted() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(FeeSponsor::sponsor(Origin::signed(1), 2));
+			for _ in 0..4 {
+				assert!(charge(2, 0).is_ok());
+			}
+			// The sponsor has 20 left, and would not keep the existential deposit
+			assert_eq!(Balances::free_balance(&1), 20);
+			assert_eq!(FeeSponsor::fee_payer(&2, 20), 2);
+
+			assert!(charge(2, 0).is_ok());
+			assert_eq!(Balances::free_balance(&1), 20);
+			assert_eq!(Balances::free_balance(&2), 10);
+
+			// Neither can pay now
+			let payment: TransactionValidity = InvalidTransaction::Payment.into();
+			assert_eq!(charge(2, 0), payment);
+		})
+	}
+
+	#[test]
+	fn only_sponsors_end_sponsorships() {
+		new_test_ext().execute_with(|| {
+			assert_err!(FeeSponsor::sponsor(Origin::signed(1), 1), "cannot sponsor oneself");
+			assert_ok!(FeeSponsor::sponsor(Origin::signed(1), 3));
+			assert_err!(FeeSponsor::sponsor(Origin::signed(2), 3), "already sponsored");
+			assert_err!(FeeSponsor::end_sponsorship(Origin::signed(2), 3), "not the sponsor");
+
+			assert_ok!(FeeSponsor::end_sponsorship(Origin::signed(1), 3));
+			assert_last_event::<TestRuntime>(RawEvent::SponsorshipEnded(1, 3));
+			assert_eq!(FeeSponsor::sponsor_of(&3), None);
+			assert_ok!(FeeSponsor::sponsor(Origin::signed(2), 3));
+		})
+	}
+}
@@ -0,0 +1,482 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! Anonymous feedback, paid for with work
+//!
+//! Anyone may leave feedback without an account, as an unsigned transaction. Unsigned transactions
+//! pay no fees, so the pool needs another way to turn away spam. Each submission carries a small
+//! proof of work: a nonce such that the hash of the feedback, the hash of a recent block, and the
+//! nonce starts with `Difficulty` zero bits. Finding the nonce takes the client about
+//! `2^Difficulty` hashes, checking it takes the runtime one.
+//!
+//! The block the proof is built on is its *anchor*. A proof is only valid for `ProofLifetime`
+//! blocks after its anchor, so work cannot be done ahead of time and stockpiled. Every proof is
+//! accepted once. The pallet remembers the proofs it has seen until their anchor expires, and
+//! forgets them afterwards.
+//!
+//! All of this is checked in `validate_unsigned`, so that spam is rejected by the pool before it
+//! is gossiped or reaches a block, and checked again when the call is dispatched.
+
+use frame_support::{decl_event, decl_module, decl_storage, dispatch::DispatchResult, traits::Get};
+use frame_system::{self as system, ensure_none};
+use sp_runtime::{
+	traits::{Hash, SaturatedConversion, Saturating},
+	transaction_validity::{InvalidTransaction, TransactionValidity, ValidTransaction},
+	RuntimeDebug,
+};
+use sp_std::prelude::*;
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event> + Into<<Self as system::Trait>::Event>;
+
+	/// How many leading zero bits the hash of a proof must have
+	type Difficulty: Get<u32>;
+
+	/// The longest feedback accepted, in bytes
+	type MaxLength: Get<u32>;
+
+	/// For how many blocks after its anchor a proof is valid. Must be less than
+	/// `system::Trait::BlockHashCount`, or the hashes of the oldest anchors are already forgotten.
+	type ProofLifetime: Get<Self::BlockNumber>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as Feedback {
+		/// The feedback received, by index
+		Feedback get(fn feedback): map u32 => Vec<u8>;
+
+		/// The number of items in `Feedback`
+		FeedbackCount get(fn feedback_count): u32;
+
+		/// The proofs accepted whose anchor has not expired, by their hash
+		Seen get(fn seen): map T::Hash => bool;
+
+		/// The hashes in `Seen`, by anchor, to forget them when the anchor expires
+		SeenByAnchor: map T::BlockNumber => Vec<T::Hash>;
+
+		/// The number of the latest block that started. The system pallet forgets the block number
+		/// when a block is finalized, and the pool validates transactions on a finalized state.
+		LastBlock get(fn last_block): T::BlockNumber;
+	}
+}
+
+decl_event!(
+	pub enum Event {
+		/// Feedback was received and stored at this index
+		FeedbackReceived(u32),
+	}
+);
+
+/// Why a proof of work is rejected
+#[derive(Clone, Copy, PartialEq, Eq, RuntimeDebug)]
+pub enum ProofError {
+	/// The feedback is longer than `MaxLength`
+	TooLong,
+	/// The anchor is the current block or a later one
+	Future,
+	/// The anchor is more than `ProofLifetime` blocks old
+	Expired,
+	/// The hash has too few leading zero bits
+	Insufficient,
+	/// The same proof was accepted before
+	Used,
+}
+
+impl From<ProofError> for InvalidTransaction {
+	fn from(error: ProofError) -> Self {
+		match error {
+			ProofError::TooLong => InvalidTransaction::ExhaustsResources,
+			ProofError::Future => InvalidTransaction::Future,
+			ProofError::Expired | ProofError::Used => InvalidTransaction::Stale,
+			ProofError::Insufficient => InvalidTransaction::BadProof,
+		}
+	}
+}
+
+impl From<ProofError> for &'static str {
+	fn from(error: ProofError) -> Self {
+		match error {
+			ProofError::TooLong => "feedback too long",
+			ProofError::Future => "anchor not yet known",
+			ProofError::Expired => "anchor expired",
+			ProofError::Insufficient => "insufficient work",
+			ProofError::Used => "proof already used",
+		}
+	}
+}
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		const Difficulty: u32 = T::Difficulty::get();
+		const MaxLength: u32 = T::MaxLength::get();
+		const ProofLifetime: T::BlockNumber = T::ProofLifetime::get();
+
+		/// Leave feedback. `nonce` is the proof of work, built on the block `anchor`.
+		fn submit_feedback(
+			origin,
+			feedback: Vec<u8>,
+			anchor: T::BlockNumber,
+			nonce: u64
+		) -> DispatchResult {
+			ensure_none(origin)?;
+			let proof = Self::check_proof(&feedback, anchor, nonce)
+				.map_err(<&'static str>::from)?;
+
+			<Seen<T>>::insert(proof, true);
+			<SeenByAnchor<T>>::mutate(anchor, |seen| seen.push(proof));
+
+			let index = Self::feedback_count();
+			Feedback::insert(index, feedback);
+			FeedbackCount::put(index.saturating_add(1));
+
+			Self::deposit_event(Event::FeedbackReceived(index));
+			Ok(())
+		}
+
+		fn on_initialize(now: T::BlockNumber) {
+			<LastBlock<T>>::put(now);
+
+			// Proofs anchored here expire in this block
+			let expired = now.saturating_sub(T::ProofLifetime::get());
+			for proof in <SeenByAnchor<T>>::take(expired) {
+				<Seen<T>>::remove(proof);
+			}
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// The hash that a proof of work is judged by
+	pub fn work(feedback: &[u8], anchor_hash: &T::Hash, nonce: u64) -> T::Hash {
+		T::Hashing::hash_of(&(feedback, anchor_hash, nonce))
+	}
+
+	/// Find a nonce for `feedback` built on the block with hash `anchor_hash`. This is what clients
+	/// do before they submit.
+	pub fn solve(feedback: &[u8], anchor_hash: &T::Hash) -> u64 {
+		let mut nonce = 0;
+		while !Self::is_enough(&Self::work(feedback, anchor_hash, nonce)) {
+			nonce += 1;
+		}
+		nonce
+	}
+
+	/// Whether `proof` has at least `Difficulty` leading zero bits
+	fn is_enough(proof: &T::Hash) -> bool {
+		leading_zeros(proof.as_ref()) >= T::Difficulty::get()
+	}
+
+	/// Check a submission against the current block, and return the hash of its proof
+	fn check_proof(
+		feedback: &[u8],
+		anchor: T::BlockNumber,
+		nonce: u64,
+	) -> Result<T::Hash, ProofError> {
+		if feedback.len() > T::MaxLength::get() as usize {
+			return Err(ProofError::TooLong);
+		}
+
+		// The hash of the current block is only stored once the next block starts
+		let now = Self::last_block();
+		if anchor >= now {
+			return Err(ProofError::Future);
+		}
+		if now - anchor >= T::ProofLifetime::get() {
+			return Err(ProofError::Expired);
+		}
+
+		let anchor_hash = <system::Module<T>>::block_hash(anchor);
+		let proof = Self::work(feedback, &anchor_hash, nonce);
+		if !Self::is_enough(&proof) {
+			return Err(ProofError::Insufficient);
+		}
+		if Self::seen(proof) {
+			return Err(ProofError::Used);
+		}
+		Ok(proof)
+	}
+}
+
+/// The number of zero bits at the start of `bytes`
+fn leading_zeros(bytes: &[u8]) -> u32 {
+	let mut zeros = 0;
+	for byte in bytes {
+		zeros += byte.leading_zeros();
+		if *byte != 0 {
+			break;
+		}
+	}
+	zeros
+}
+
+impl<T: Trait> frame_support::unsigned::ValidateUnsigned for Module<T> {
+	type Call = Call<T>;
+
+	fn validate_unsigned(call: &Self::Call) -> TransactionValidity {
+		let (feedback, anchor, nonce) = match call {
+			Call::submit_feedback(feedback, anchor, nonce) => (feedback, *anchor, *nonce),
+			_ => return InvalidTransaction::Call.into(),
+		};
+
+		let proof = match Self::check_proof(feedback, anchor, nonce) {
+			Ok(proof) => proof,
+			Err(error) => return InvalidTransaction::from(error).into(),
+		};
+
+		let now = Self::last_block();
+		let remaining = anchor.saturating_add(T::ProofLifetime::get()).saturating_sub(now);
+		Ok(ValidTransaction {
+			// Work beyond the difficulty buys priority
+			priority: leading_zeros(proof.as_ref()).into(),
+			requires: vec![],
+			// Two copies of one proof cannot both be included
+			provides: vec![proof.as_ref().to_vec()],
+			longevity: remaining.saturated_into::<u64>(),
+			propagate: true,
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use event_assert::assert_last_event;
+	use frame_support::{
+		assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types,
+		unsigned::ValidateUnsigned,
+	};
+	use frame_system::RawOrigin;
+	use sp_core::H256;
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup, OnInitialize},
+		Perbill,
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const Difficulty: u32 = 8;
+		pub const MaxLength: u32 = 64;
+		pub const ProofLifetime: u64 = 5;
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	mod feedback {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			feedback,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type Difficulty = Difficulty;
+		type MaxLength = MaxLength;
+		type ProofLifetime = ProofLifetime;
+	}
+
+	pub type System = system::Module<TestRuntime>;
+	pub type Feedback = Module<TestRuntime>;
+
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		let t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		t.into()
+	}
+
+	/// The test chain does not store block hashes itself, so each block gets a made-up one
+	fn hash_of(n: u64) -> H256 {
+		H256::repeat_byte(n as u8)
+	}
+
+	/// Moves the chain to block `n`, the way block execution does
+	fn run_to_block(n: u64) {
+		while System::block_number() < n {
+			let next = System::block_number() + 1;
+			<system::BlockHash<TestRuntime>>::insert(next - 1, hash_of(next - 1));
+			System::set_block_number(next);
+			Feedback::on_initialize(next);
+		}
+	}
+
+	/// A valid submission of `feedback` built on block `anchor`
+	fn solved(feedback: &[u8], anchor: u64) -> Call<TestRuntime> {
+		let nonce = Feedback::solve(feedback, &hash_of(anchor));
+		Call::submit_feedback(feedback.to_vec(), anchor, nonce)
+	}
+
+	/// Validates `call` like the pool would, and dispatches it if it is valid
+	fn submit(call: Call<TestRuntime>) -> TransactionValidity {
+		let validity = Feedback::validate_unsigned(&call);
+		if validity.is_ok() {
+			if let Call::submit_feedback(feedback, anchor, nonce) = call {
+				assert_ok!(Feedback::submit_feedback(
+					RawOrigin::None.into(),
+					feedback,
+					anchor,
+					nonce
+				));
+			}
+		}
+		validity
+	}
+
+	fn invalid(error: InvalidTransaction) -> TransactionValidity {
+		error.into()
+	}
+
+	#[test]
+	fn leading_zeros_works() {
+		assert_eq!(leading_zeros(&[0xff, 0]), 0);
+		assert_eq!(leading_zeros(&[0, 0x10, 0]), 11);
+		assert_eq!(leading_zeros(&[0, 0]), 16);
+	}
+
+	#[test]
+	fn solved_feedback_is_accepted() {
+		new_test_ext().execute_with(|| {
+			run_to_block(5);
+			let validity = submit(solved(b"great chain", 4)).unwrap();
+
+			// The proof is valid until block 9, four more blocks
+			assert_eq!(validity.longevity, 4);
+			assert!(validity.priority >= 8);
+			assert_eq!(Feedback::feedback(0), b"great chain".to_vec());
+			assert_eq!(Feedback::feedback_count(), 1);
+			assert_last_event::<TestRuntime>(Event::FeedbackReceived(0));
+		})
+	}
+
+	#[test]
+	fn insufficient_work_is_rejected() {
+		new_test_ext().execute_with(|| {
+			run_to_block(5);
+			let nonce = (0..)
+				.find(|nonce| !Feedback::is_enough(&Feedback::work(b"spam", &hash_of(4), *nonce)))
+				.unwrap();
+
+			let call = Call::submit_feedback(b"spam".to_vec(), 4, nonce);
+			assert_eq!(submit(call), invalid(InvalidTransaction::BadProof));
+			assert_err!(
+				Feedback::submit_feedback(RawOrigin::None.into(), b"spam".to_vec(), 4, nonce),
+				"insufficient work"
+			);
+			assert_eq!(Feedback::feedback_count(), 0);
+		})
+	}
+
+	#[test]
+	fn proof_is_bound_to_its_feedback_and_anchor() {
+		new_test_ext().execute_with(|| {
+			run_to_block(5);
+			let nonce = Feedback::solve(b"nice", &hash_of(4));
+
+			// The same nonce proves nothing for other feedback, or for another block
+			let other_feedback = Call::submit_feedback(b"mean".to_vec(), 4, nonce);
+			let other_anchor = Call::submit_feedback(b"nice".to_vec(), 3, nonce);
+			for call in vec![other_feedback, other_anchor] {
+				if Feedback::validate_unsigned(&call).is_ok() {
+					// Only by chance, one in 256
+					continue;
+				}
+				assert_eq!(submit(call), invalid(InvalidTransaction::BadProof));
+			}
+		})
+	}
+
+	#[test]
+	fn proof_is_used_once() {
+		new_test_ext().execute_with(|| {
+			run_to_block(5);
+			let call = solved(b"great chain", 4);
+			assert!(submit(call.clone()).is_ok());
+
+			assert_eq!(submit(call), invalid(InvalidTransaction::Stale));
+			assert_eq!(Feedback::feedback_count(), 1);
+		})
+	}
+
+	#[test]
+	fn anchor_must_be_recent() {
+		new_test_ext().execute_with(|| {
+			run_to_block(10);
+			// Block 10's hash is not known yet
+			assert_eq!(submit(solved(b"hi", 10)), invalid(InvalidTransaction::Future));
+			// Block 5 expired in block 10
+			assert_eq!(submit(solved(b"hi", 5)), invalid(InvalidTransaction::Stale));
+			assert_err!(
+				Feedback::submit_feedback(RawOrigin::None.into(), b"hi".to_vec(), 5, 0),
+				"anchor expired"
+			);
+
+			let validity = submit(solved(b"hi", 6)).unwrap();
+			assert_eq!(validity.longevity, 1);
+		})
+	}
+
+	#[test]
+	fn seen_proofs_are_forgotten_when_they_expire() {
+		new_test_ext().execute_with(|| {
+			run_to_block(5);
+			let nonce = Feedback::solve(b"great chain", &hash_of(4));
+			let call = Call::submit_feedback(b"great chain".to_vec(), 4, nonce);
+			assert!(submit(call.clone()).is_ok());
+			let proof = Feedback::work(b"great chain", &hash_of(4), nonce);
+			assert!(Feedback::seen(proof));
+
+			run_to_block(8);
+			assert!(Feedback::seen(proof));
+			run_to_block(9);
+			assert!(!Feedback::seen(proof));
+			// The proof is forgotten, but it has expired
+			assert_eq!(submit(call), invalid(InvalidTransaction::Stale));
+		})
+	}
+
+	#[test]
+	fn only_short_unsigned_feedback() {
+		new_test_ext().execute_with(|| {
+			run_to_block(5);
+			let long = vec![b'!'; 65];
+			assert_eq!(submit(solved(&long, 4)), invalid(InvalidTransaction::ExhaustsResources));
+
+			let nonce = Feedback::solve(b"hi", &hash_of(4));
+			assert!(Feedback::submit_feedback(Origin::signed(1), b"hi".to_vec(), 4, nonce).is_err());
+		})
+	}
+}
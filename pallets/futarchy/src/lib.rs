@@ -0,0 +1,620 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! A futarchy-lite prediction market, in which traders bet on whether proposals pass
+//!
+//! Anyone can `create_market` for a proposal, identified by its hash, with a liquidity parameter
+//! `b`. Traders `buy` YES or NO shares from the market, and once `ResolveOrigin` reports whether
+//! the proposal passed, each winning share `claim`s one unit of the currency. The price of a YES
+//! share is the chance the market gives the proposal of passing, which governance can read before
+//! it decides.
+//!
+//! The market maker is Hanson's logarithmic market scoring rule (LMSR). With `q_yes` and `q_no`
+//! shares sold, the market has been paid
+//!
+//! ```text
+//! C(q_yes, q_no) = b · ln(e^(q_yes / b) + e^(q_no / b))
+//! ```
+//!
+//! and buying shares costs the difference they make to `C`. The more shares of an outcome are
+//! sold, the more its next share costs. The creator pays `C(0, 0) = b · ln 2` to open the market,
+//! which is the most the market can lose, and gets back whatever is left after the winners are
+//! paid.
+//!
+//! The runtime can't use floating point, so `C` is computed in fixed point with `ONE` parts per
+//! unit by `cost`. It is rewritten as
+//!
+//! ```text
+//! C(q_yes, q_no) = max(q_yes, q_no) + b · ln(1 + e^(-|q_yes - q_no| / b))
+//! ```
+//!
+//! so that only `e^-x` for `x ≥ 0` and `ln(1 + y)` for `0 ≤ y ≤ 1` are needed, both of which
+//! are short series. `cost` rounds up to a whole unit and is never below `max(q_yes, q_no)`, so
+//! however the series round, the market always holds enough to pay the winners.
+
+use codec::{Decode, Encode};
+use sp_std::prelude::*;
+use sp_runtime::{
+	traits::{AccountIdConversion, SaturatedConversion, Saturating, Zero},
+	ModuleId, Perbill, RuntimeDebug,
+};
+use frame_support::{
+	decl_event, decl_module, decl_storage,
+	dispatch::DispatchResult,
+	ensure,
+	traits::{Currency, EnsureOrigin, ExistenceRequirement::AllowDeath, Get},
+};
+use frame_system::{self as system, ensure_signed};
+
+type BalanceOf<T> = <<T as Trait>::Currency as Currency<<T as system::Trait>::AccountId>>::Balance;
+type MarketOf<T> = Market<<T as system::Trait>::AccountId, BalanceOf<T>>;
+
+/// Hardcoded pallet ID; used to create the account that holds the markets' funds
+/// Must be exactly 8 characters long
+const PALLET_ID: ModuleId = ModuleId(*b"futarchy");
+
+/// Numbers in fixed point have this many parts per unit
+pub const ONE: u128 = 1_000_000_000_000_000_000;
+
+/// `e^-1` in fixed point, rounded down
+const E_INV: u128 = 367_879_441_171_442_321;
+
+/// `e^-x` is below a part in `ONE` for `x` of this or more, and is taken as zero
+const MAX_EXPONENT: u128 = 45;
+
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct Market<AccountId, Balance> {
+	/// Who paid the subsidy, and gets back what is left after the winners are paid
+	pub creator: AccountId,
+	/// The LMSR's `b`. The larger it is, the more shares it takes to move the price.
+	pub liquidity: Balance,
+	/// YES shares sold
+	pub yes: Balance,
+	/// NO shares sold
+	pub no: Balance,
+	/// Whether the proposal passed, once the market is resolved
+	pub outcome: Option<bool>,
+}
+
+/// The shares an account holds in a market
+#[derive(Encode, Decode, Clone, Default, PartialEq, Eq, RuntimeDebug)]
+pub struct Holding<Balance> {
+	pub yes: Balance,
+	pub no: Balance,
+}
+
+/// `a · b` of two fixed-point numbers, rounded down
+fn mul(a: u128, b: u128) -> u128 {
+	a.saturating_mul(b) / ONE
+}
+
+/// `n / d` in fixed point, rounded down. `d` must not be zero.
+fn ratio(n: u128, d: u128) -> u128 {
+	let whole = (n / d).saturating_mul(ONE);
+	let rest = n % d;
+	// `rest · ONE` overflows for a `d` above about 3 · 10^20, so both are scaled down first
+	let shift = d / (u128::max_value() / ONE) + 1;
+	whole.saturating_add(rest / shift * ONE / (d / shift))
+}
+
+/// `e^f` for `0 ≤ f < 1` in fixed point, by its Taylor series `1 + f + f²/2! + …`
+fn exp_fraction(f: u128) -> u128 {
+	let mut sum = ONE;
+	let mut term = ONE;
+	let mut k = 1;
+	loop {
+		term = mul(term, f) / k;
+		if term == 0 {
+			return sum;
+		}
+		sum += term;
+		k += 1;
+	}
+}
+
+/// `e^-x` for `x ≥ 0` in fixed point. The whole part of `x` is taken as powers of `e^-1`, and the
+/// fraction by its series.
+pub fn exp_neg(x: u128) -> u128 {
+	let whole = x / ONE;
+	if whole >= MAX_EXPONENT {
+		return 0;
+	}
+	let mut result = ONE * ONE / exp_fraction(x % ONE);
+	for _ in 0..whole {
+		result = mul(result, E_INV);
+	}
+	result
+}
+
+/// `ln(1 + y)` for `0 ≤ y ≤ 1` in fixed point, by the series `2 · (z + z³/3 + z⁵/5 + …)`
+/// with `z = y / (2 + y)`. As `z ≤ 1/3`, each term is at most a ninth of the last.
+pub fn ln_1p(y: u128) -> u128 {
+	let z = ratio(y, 2 * ONE + y);
+	let z_squared = mul(z, z);
+	let mut sum = 0;
+	let mut term = z;
+	let mut k = 1;
+	while term > 0 {
+		sum += term / k;
+		term = mul(term, z_squared);
+		k += 2;
+	}
+	2 * sum
+}
+
+/// The LMSR cost function `C(yes, no)` with liquidity `b`, rounded up to a whole unit. It is
+/// never below `max(yes, no)`, which is what the winners of the market are owed.
+pub fn cost(yes: u128, no: u128, liquidity: u128) -> u128 {
+	let (high, low) = if yes >= no { (yes, no) } else { (no, yes) };
+	let difference = high - low;
+	if liquidity == 0 || difference / liquidity >= MAX_EXPONENT {
+		return high;
+	}
+	let log = ln_1p(exp_neg(ratio(difference, liquidity)));
+	// `b · log` with `b` split into whole and fractional units, so neither product overflows
+	let whole = (liquidity / ONE).saturating_mul(log);
+	let fraction = ((liquidity % ONE) * log + ONE - 1) / ONE;
+	high.saturating_add(whole).saturating_add(fraction)
+}
+
+/// The price of a YES share, `e^(yes / b) / (e^(yes / b) + e^(no / b))`, which is the chance the
+/// market gives the proposal of passing. A NO share costs the rest, so swapping `yes` and `no`
+/// gives the price of a NO share.
+pub fn price_of_yes(yes: u128, no: u128, liquidity: u128) -> Perbill {
+	let (high, low) = if yes >= no { (yes, no) } else { (no, yes) };
+	let difference = high - low;
+	let e = if liquidity == 0 || difference / liquidity >= MAX_EXPONENT {
+		0
+	} else {
+		exp_neg(ratio(difference, liquidity))
+	};
+	// The price of the outcome with more shares sold is `1 / (1 + e^(-|yes - no| / b))`
+	let favourite = Perbill::from_rational_approximation(ONE, ONE + e);
+	if yes >= no {
+		favourite
+	} else {
+		Perbill::from_parts(Perbill::one().deconstruct() - favourite.deconstruct())
+	}
+}
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The currency that shares are bought with and pay out in
+	type Currency: Currency<Self::AccountId>;
+
+	/// Origin that reports whether a proposal passed
+	type ResolveOrigin: EnsureOrigin<Self::Origin>;
+
+	/// The smallest liquidity parameter of a market. A tiny `b` lets the first trader move the
+	/// price almost for free.
+	type MinLiquidity: Get<BalanceOf<Self>>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as Futarchy {
+		/// The market on each proposal, by the proposal's hash
+		Markets get(fn market): map T::Hash => Option<MarketOf<T>>;
+
+		/// The shares each account holds in a market, until they are claimed
+		Holdings get(fn holding):
+			double_map T::Hash, blake2_256(T::AccountId) => Holding<BalanceOf<T>>;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as system::Trait>::AccountId,
+		Balance = BalanceOf<T>,
+		Hash = <T as system::Trait>::Hash,
+	{
+		/// A market on a proposal was created with a liquidity, for a subsidy
+		MarketCreated(Hash, AccountId, Balance, Balance),
+		/// Shares were bought in a market; `true` for YES. Carries the shares and their cost.
+		SharesBought(Hash, AccountId, bool, Balance, Balance),
+		/// A market was resolved with whether its proposal passed, and its creator got this back
+		MarketResolved(Hash, bool, Balance),
+		/// A trader was paid for their winning shares
+		Claimed(Hash, AccountId, Balance),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		const MinLiquidity: BalanceOf<T> = T::MinLiquidity::get();
+
+		/// Open a market on a proposal. The caller pays the subsidy, `b · ln 2`.
+		fn create_market(origin, proposal: T::Hash, liquidity: BalanceOf<T>) -> DispatchResult {
+			let creator = ensure_signed(origin)?;
+			ensure!(!<Markets<T>>::exists(proposal), "market exists");
+			ensure!(liquidity >= T::MinLiquidity::get(), "liquidity too low");
+
+			let subsidy = cost(0, 0, liquidity.saturated_into()).saturated_into();
+			T::Currency::transfer(&creator, &Self::account_id(), subsidy, AllowDeath)?;
+
+			let market = Market {
+				creator: creator.clone(),
+				liquidity,
+				yes: Zero::zero(),
+				no: Zero::zero(),
+				outcome: None,
+			};
+			<Markets<T>>::insert(proposal, market);
+			Self::deposit_event(RawEvent::MarketCreated(proposal, creator, liquidity, subsidy));
+			Ok(())
+		}
+
+		/// Buy `shares` of an outcome, `true` for YES, paying at most `max_cost` for them. The
+		/// limit protects the buyer from trades that move the price before theirs.
+		fn buy(
+			origin,
+			proposal: T::Hash,
+			outcome: bool,
+			shares: BalanceOf<T>,
+			max_cost: BalanceOf<T>,
+		) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let mut market = Self::market(proposal).ok_or("no such market")?;
+			ensure!(market.outcome.is_none(), "market resolved");
+			ensure!(!shares.is_zero(), "no shares");
+
+			let before = Self::market_cost(&market);
+			if outcome {
+				market.yes = market.yes.saturating_add(shares);
+			} else {
+				market.no = market.no.saturating_add(shares);
+			}
+			let price = Self::market_cost(&market).saturating_sub(before).saturated_into();
+			ensure!(price <= max_cost, "cost exceeds limit");
+			T::Currency::transfer(&who, &Self::account_id(), price, AllowDeath)?;
+
+			<Holdings<T>>::mutate(proposal, &who, |holding| if outcome {
+				holding.yes = holding.yes.saturating_add(shares);
+			} else {
+				holding.no = holding.no.saturating_add(shares);
+			});
+			<Markets<T>>::insert(proposal, market);
+			Self::deposit_event(RawEvent::SharesBought(proposal, who, outcome, shares, price));
+			Ok(())
+		}
+
+		/// Report whether a proposal passed. Trading stops, and the creator is paid what the market
+		/// holds beyond the winning shares.
+		fn resolve(origin, proposal: T::Hash, passed: bool) -> DispatchResult {
+			T::ResolveOrigin::ensure_origin(origin)?;
+			let mut market = Self::market(proposal).ok_or("no such market")?;
+			ensure!(market.outcome.is_none(), "market resolved");
+
+			// The market holds its subsidy and every payment, which add up to its cost
+			let held: BalanceOf<T> = Self::market_cost(&market).saturated_into();
+			let owed = if passed { market.yes } else { market.no };
+			let surplus = held.saturating_sub(owed);
+			T::Currency::transfer(&Self::account_id(), &market.creator, surplus, AllowDeath)?;
+
+			market.outcome = Some(passed);
+			<Markets<T>>::insert(proposal, market);
+			Self::deposit_event(RawEvent::MarketResolved(proposal, passed, surplus));
+			Ok(())
+		}
+
+		/// Pay the caller one unit for each of their winning shares in a resolved market
+		fn claim(origin, proposal: T::Hash) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let market = Self::market(proposal).ok_or("no such market")?;
+			let passed = market.outcome.ok_or("market not resolved")?;
+			let holding = Self::holding(proposal, &who);
+			let amount = if passed { holding.yes } else { holding.no };
+			ensure!(!amount.is_zero(), "nothing to claim");
+
+			T::Currency::transfer(&Self::account_id(), &who, amount, AllowDeath)?;
+			<Holdings<T>>::remove(proposal, &who);
+			Self::deposit_event(RawEvent::Claimed(proposal, who, amount));
+			Ok(())
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// The account ID that holds the subsidies and payments of all markets
+	pub fn account_id() -> T::AccountId {
+		PALLET_ID.into_account()
+	}
+
+	/// The chance a market gives its proposal of passing, if there is a market
+	pub fn chance_of_passing(proposal: T::Hash) -> Option<Perbill> {
+		Self::market(proposal).map(|market| price_of_yes(
+			market.yes.saturated_into(),
+			market.no.saturated_into(),
+			market.liquidity.saturated_into(),
+		))
+	}
+
+	fn market_cost(market: &MarketOf<T>) -> u128 {
+		cost(
+			market.yes.saturated_into(),
+			market.no.saturated_into(),
+			market.liquidity.saturated_into(),
+		)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use event_assert::assert_last_event;
+
+	use sp_core::H256;
+	use frame_support::{
+		assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types,
+	};
+	use frame_system::{EnsureRoot, RawOrigin};
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup},
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const ExistentialDeposit: u64 = 0;
+		pub const TransferFee: u64 = 0;
+		pub const CreationFee: u64 = 0;
+
+		pub const MinLiquidity: u64 = 100;
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	impl balances::Trait for TestRuntime {
+		type Balance = u64;
+		type OnFreeBalanceZero = ();
+		type OnNewAccount = ();
+		type Event = TestEvent;
+		type TransferPayment = ();
+		type DustRemoval = ();
+		type ExistentialDeposit = ExistentialDeposit;
+		type TransferFee = TransferFee;
+		type CreationFee = CreationFee;
+	}
+
+	mod futarchy {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			balances<T>,
+			futarchy<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type Currency = balances::Module<Self>;
+		type ResolveOrigin = EnsureRoot<u64>;
+		type MinLiquidity = MinLiquidity;
+	}
+
+	pub type Balances = balances::Module<TestRuntime>;
+	pub type Futarchy = Module<TestRuntime>;
+
+	/// Accounts 1 to 4 have 10_000 each, and account 1 opened a market on `proposal()` with a
+	/// liquidity of 1_000, for a subsidy of 694
+	fn new_test_ext() -> sp_io::TestExternalities {
+		let mut t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		balances::GenesisConfig::<TestRuntime> {
+			balances: (1..=4).map(|who| (who, 10_000)).collect(),
+			vesting: vec![],
+		}
+		.assimilate_storage(&mut t)
+		.unwrap();
+		let mut ext: sp_io::TestExternalities = t.into();
+		ext.execute_with(|| {
+			assert_ok!(Futarchy::create_market(Origin::signed(1), proposal(), 1_000));
+		});
+		ext
+	}
+
+	fn proposal() -> H256 {
+		H256::repeat_byte(1)
+	}
+
+	fn pot() -> u64 {
+		Balances::free_balance(&Futarchy::account_id())
+	}
+
+	/// The LMSR cost function in floating point, to check the fixed-point implementation against
+	fn reference(yes: f64, no: f64, liquidity: f64) -> f64 {
+		liquidity * ((yes / liquidity).exp() + (no / liquidity).exp()).ln()
+	}
+
+	#[test]
+	fn fixed_point_series_are_accurate() {
+		let one = ONE as f64;
+		for x in &[0, 1, ONE / 10, ONE / 2, ONE, 3 * ONE + 123_456_789, 10 * ONE, 44 * ONE] {
+			let e = exp_neg(*x);
+			assert!((e as f64 / one - (-(*x as f64) / one).exp()).abs() < 1e-15);
+			let l = ln_1p(e);
+			assert!((l as f64 / one - (e as f64 / one).ln_1p()).abs() < 1e-15);
+		}
+		assert_eq!(exp_neg(0), ONE);
+		assert_eq!(exp_neg(ONE), E_INV);
+		assert_eq!(exp_neg(MAX_EXPONENT * ONE), 0);
+		// ln 2 = 0.693147180559945309...
+		assert_eq!(ln_1p(ONE) / 1_000_000, 693_147_180_559);
+	}
+
+	#[test]
+	fn cost_agrees_with_the_reference() {
+		for liquidity in &[100u128, 1_000, 12_345, 1_000_000_000_000] {
+			for (yes, no) in &[(0u128, 0u128), (100, 0), (0, 777), (5_000, 4_999), (10, 2_000)] {
+				let (yes, no) = (yes * liquidity / 100, no * liquidity / 100);
+				let expected = reference(yes as f64, no as f64, *liquidity as f64);
+				let actual = cost(yes, no, *liquidity);
+				// Rounded up, and off by a part in 10^12 at most
+				assert!(actual as f64 >= expected - expected / 1e12, "{} < {}", actual, expected);
+				assert!(actual as f64 <= expected + 1.0 + expected / 1e12);
+			}
+		}
+		// `b · ln 2` is the most a market can lose
+		assert_eq!(cost(0, 0, 1_000), 694);
+		// Far from balanced, the cost is the winners' payout
+		assert_eq!(cost(1_000_000, 0, 1_000), 1_000_000);
+	}
+
+	#[test]
+	fn cost_covers_the_winners() {
+		// Awkward trades from a linear congruential generator
+		let mut seed = 7u64;
+		let mut next = || {
+			seed = seed
+				.wrapping_mul(6_364_136_223_846_793_005)
+				.wrapping_add(1_442_695_040_888_963_407);
+			u128::from(seed >> 40)
+		};
+		for _ in 0..20 {
+			let liquidity = next() % 10_000 + 1;
+			let (mut yes, mut no) = (0, 0);
+			let mut held = cost(0, 0, liquidity);
+			for _ in 0..50 {
+				let before = cost(yes, no, liquidity);
+				if next() % 2 == 0 { yes += next() % 5_000 } else { no += next() % 5_000 }
+				held += cost(yes, no, liquidity) - before;
+				assert!(held >= yes.max(no));
+			}
+		}
+	}
+
+	#[test]
+	fn prices_follow_the_shares_sold() {
+		assert_eq!(price_of_yes(0, 0, 1_000), Perbill::from_percent(50));
+		assert_eq!(price_of_yes(500, 500, 1_000), Perbill::from_percent(50));
+		// 1 / (1 + e^-0.2) = 0.549833997...
+		assert_eq!(price_of_yes(300, 100, 1_000), Perbill::from_parts(549_833_997));
+		assert_eq!(price_of_yes(100, 300, 1_000), Perbill::from_parts(450_166_003));
+		let prices = [price_of_yes(100, 300, 1_000), price_of_yes(300, 100, 1_000)];
+		assert_eq!(prices[0].deconstruct() + prices[1].deconstruct(), 1_000_000_000);
+		assert_eq!(price_of_yes(100_000, 0, 1_000), Perbill::one());
+		assert_eq!(price_of_yes(0, 100_000, 1_000), Perbill::zero());
+	}
+
+	#[test]
+	fn creating_a_market_takes_the_subsidy() {
+		new_test_ext().execute_with(|| {
+			assert_eq!(Balances::free_balance(&1), 10_000 - 694);
+			assert_eq!(pot(), 694);
+			assert_last_event::<TestRuntime>(RawEvent::MarketCreated(proposal(), 1, 1_000, 694));
+			assert_eq!(Futarchy::chance_of_passing(proposal()), Some(Perbill::from_percent(50)));
+
+			assert_err!(
+				Futarchy::create_market(Origin::signed(2), proposal(), 1_000),
+				"market exists"
+			);
+			assert_err!(
+				Futarchy::create_market(Origin::signed(2), H256::repeat_byte(2), 99),
+				"liquidity too low"
+			);
+		})
+	}
+
+	#[test]
+	fn buying_moves_the_price() {
+		new_test_ext().execute_with(|| {
+			// C(100, 0) - C(0, 0) = 745 - 694
+			assert_ok!(Futarchy::buy(Origin::signed(2), proposal(), true, 100, 51));
+			assert_eq!(Balances::free_balance(&2), 10_000 - 51);
+			assert_eq!(Futarchy::holding(proposal(), 2), Holding { yes: 100, no: 0 });
+			assert_last_event::<TestRuntime>(RawEvent::SharesBought(proposal(), 2, true, 100, 51));
+			let chance = Futarchy::chance_of_passing(proposal()).unwrap();
+			assert!(chance > Perbill::from_percent(52));
+
+			// The next 100 YES shares cost more
+			assert_err!(
+				Futarchy::buy(Origin::signed(3), proposal(), true, 100, 51),
+				"cost exceeds limit"
+			);
+			assert_eq!(Balances::free_balance(&3), 10_000);
+			assert_ok!(Futarchy::buy(Origin::signed(3), proposal(), true, 100, 54));
+
+			// Buying NO brings it back down
+			assert_ok!(Futarchy::buy(Origin::signed(4), proposal(), false, 200, 1_000));
+			assert_eq!(
+				Futarchy::chance_of_passing(proposal()),
+				Some(Perbill::from_percent(50))
+			);
+			assert_eq!(Futarchy::holding(proposal(), 4), Holding { yes: 0, no: 200 });
+
+			assert_err!(Futarchy::buy(Origin::signed(2), proposal(), true, 0, 0), "no shares");
+			assert_err!(
+				Futarchy::buy(Origin::signed(2), H256::repeat_byte(2), true, 1, 1),
+				"no such market"
+			);
+		})
+	}
+
+	#[test]
+	fn winners_are_paid_and_the_creator_gets_the_rest() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(Futarchy::buy(Origin::signed(2), proposal(), true, 300, 1_000));
+			assert_ok!(Futarchy::buy(Origin::signed(3), proposal(), false, 100, 1_000));
+			assert_eq!(Balances::free_balance(&2), 10_000 - 161);
+			assert_eq!(Balances::free_balance(&3), 10_000 - 44);
+			assert_eq!(pot(), 899);
+
+			assert_err!(Futarchy::claim(Origin::signed(2), proposal()), "market not resolved");
+			assert!(Futarchy::resolve(Origin::signed(1), proposal(), true).is_err());
+			assert_ok!(Futarchy::resolve(RawOrigin::Root.into(), proposal(), true));
+			assert_last_event::<TestRuntime>(RawEvent::MarketResolved(proposal(), true, 599));
+			assert_err!(
+				Futarchy::resolve(RawOrigin::Root.into(), proposal(), false),
+				"market resolved"
+			);
+			assert_err!(
+				Futarchy::buy(Origin::signed(4), proposal(), true, 1, 1_000),
+				"market resolved"
+			);
+
+			// The pot keeps exactly the winning shares
+			assert_eq!(Balances::free_balance(&1), 10_000 - 694 + 599);
+			assert_eq!(pot(), 300);
+
+			assert_ok!(Futarchy::claim(Origin::signed(2), proposal()));
+			assert_eq!(Balances::free_balance(&2), 10_000 - 161 + 300);
+			assert_last_event::<TestRuntime>(RawEvent::Claimed(proposal(), 2, 300));
+			assert_eq!(pot(), 0);
+			assert_err!(Futarchy::claim(Origin::signed(2), proposal()), "nothing to claim");
+			assert_err!(Futarchy::claim(Origin::signed(3), proposal()), "nothing to claim");
+		})
+	}
+}
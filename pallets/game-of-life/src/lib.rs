@@ -0,0 +1,395 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! Conway's Game of Life on a bounded grid, evolved lazily
+//!
+//! The grid moves on by one generation every `GenerationPeriod` blocks, but nothing happens in
+//! those blocks. Storage holds the grid of some past generation, and the generation that is due
+//! follows from the block number and the block the grid was seeded in. Anyone can `poke` the grid
+//! to compute the generations it is behind, and pays for the work.
+//!
+//! A poke computes at most `MaxGenerationsPerPoke` generations, so a grid left alone for long
+//! catches up over several pokes. Every generation visits every cell, so the weight of a poke is
+//! tied to the size of the grid, which is fixed by the runtime.
+//!
+//! Rows are stored as bitmasks, bit `x` of row `y` being the cell at column `x`, so the grid is at
+//! most 64 cells wide. Cells beyond the edges are always dead.
+
+use sp_std::prelude::*;
+use sp_runtime::traits::SaturatedConversion;
+use frame_support::{
+	decl_event, decl_module, decl_storage,
+	dispatch::DispatchResult,
+	ensure,
+	traits::{EnsureOrigin, Get},
+	weights::SimpleDispatchInfo,
+};
+use frame_system::{self as system, ensure_signed};
+
+pub mod weights;
+pub use weights::WeightInfo;
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The origin that seeds the grid
+	type ManageOrigin: EnsureOrigin<Self::Origin>;
+
+	/// The number of columns, at most 64
+	type Width: Get<u32>;
+
+	/// The number of rows
+	type Height: Get<u32>;
+
+	/// The number of blocks per generation
+	type GenerationPeriod: Get<Self::BlockNumber>;
+
+	/// The most generations one poke computes
+	type MaxGenerationsPerPoke: Get<u32>;
+
+	/// Weight information for the dispatchables in this pallet
+	type WeightInfo: WeightInfo;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as GameOfLife {
+		/// The rows of the grid as of `Generation`
+		Grid get(fn grid): Vec<u64>;
+
+		/// The generation the stored grid is at
+		Generation get(fn generation): u64;
+
+		/// The block the grid was seeded in, at generation 0
+		SeededAt get(fn seeded_at): T::BlockNumber;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as system::Trait>::AccountId,
+	{
+		/// The grid was seeded with this many live cells
+		Seeded(u32),
+		/// An account evolved the grid from one generation to another
+		Evolved(AccountId, u64, u64),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		const Width: u32 = T::Width::get();
+		const Height: u32 = T::Height::get();
+		const GenerationPeriod: T::BlockNumber = T::GenerationPeriod::get();
+		const MaxGenerationsPerPoke: u32 = T::MaxGenerationsPerPoke::get();
+
+		/// Replace the grid with `rows`, and start counting generations from this block
+		#[weight = SimpleDispatchInfo::FixedNormal(T::WeightInfo::seed(<Module<T>>::cells()))]
+		fn seed(origin, rows: Vec<u64>) -> DispatchResult {
+			T::ManageOrigin::ensure_origin(origin)?;
+			ensure!(rows.len() as u32 == T::Height::get(), "wrong number of rows");
+			let outside = !Self::row_mask();
+			ensure!(rows.iter().all(|row| row & outside == 0), "cell outside the grid");
+
+			let live = rows.iter().map(|row| row.count_ones()).sum();
+			Grid::put(rows);
+			Generation::put(0);
+			<SeededAt<T>>::put(<system::Module<T>>::block_number());
+
+			Self::deposit_event(RawEvent::Seeded(live));
+			Ok(())
+		}
+
+		/// Compute the generations the grid is behind, up to `MaxGenerationsPerPoke` of them
+		#[weight = SimpleDispatchInfo::FixedNormal(
+			T::WeightInfo::poke(T::MaxGenerationsPerPoke::get(), <Module<T>>::cells())
+		)]
+		fn poke(origin) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let from = Self::generation();
+			let due = Self::due_generation();
+			ensure!(from < due, "grid is up to date");
+
+			let to = due.min(from.saturating_add(T::MaxGenerationsPerPoke::get().into()));
+			let mut grid = Self::grid();
+			let mut generation = from;
+			while generation < to {
+				let next = Self::step(&grid);
+				if next == grid {
+					// A grid that does not change never will, so it is at every later generation
+					generation = due;
+					break;
+				}
+				grid = next;
+				generation += 1;
+			}
+			Grid::put(grid);
+			Generation::put(generation);
+
+			Self::deposit_event(RawEvent::Evolved(who, from, generation));
+			Ok(())
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// The number of cells in the grid
+	pub fn cells() -> u32 {
+		T::Width::get().saturating_mul(T::Height::get())
+	}
+
+	/// The generation the grid should be at in the current block
+	pub fn due_generation() -> u64 {
+		let elapsed = <system::Module<T>>::block_number() - Self::seeded_at();
+		let period = T::GenerationPeriod::get().saturated_into::<u64>().max(1);
+		elapsed.saturated_into::<u64>() / period
+	}
+
+	/// Whether the stored grid is at the generation that is due
+	pub fn is_current() -> bool {
+		Self::generation() >= Self::due_generation()
+	}
+
+	/// The bits of a row that are inside the grid
+	fn row_mask() -> u64 {
+		match T::Width::get() {
+			width if width >= 64 => u64::max_value(),
+			width => (1 << width) - 1,
+		}
+	}
+
+	/// Whether the cell at column `x` and row `y` is alive. Cells beyond the edges are not.
+	fn is_alive(grid: &[u64], x: i64, y: i64) -> bool {
+		if x < 0 || y < 0 || x >= 64 {
+			return false;
+		}
+		grid.get(y as usize).map_or(false, |row| row & (1 << x) != 0)
+	}
+
+	/// The generation after `grid`. A live cell with two or three live neighbours survives, and a
+	/// dead cell with exactly three comes alive.
+	fn step(grid: &[u64]) -> Vec<u64> {
+		let width = T::Width::get().min(64) as i64;
+		(0..grid.len() as i64)
+			.map(|y| {
+				let mut row = 0;
+				for x in 0..width {
+					let mut neighbours = 0;
+					for (dx, dy) in NEIGHBOURS.iter() {
+						if Self::is_alive(grid, x + dx, y + dy) {
+							neighbours += 1;
+						}
+					}
+					let alive = Self::is_alive(grid, x, y);
+					if neighbours == 3 || (alive && neighbours == 2) {
+						row |= 1 << x;
+					}
+				}
+				row
+			})
+			.collect()
+	}
+}
+
+/// The offsets of the eight cells around a cell
+const NEIGHBOURS: [(i64, i64); 8] =
+	[(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)];
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use event_assert::assert_last_event;
+	use frame_support::{
+		assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types,
+		weights::GetDispatchInfo,
+	};
+	use frame_system::{EnsureRoot, RawOrigin};
+	use sp_core::H256;
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup},
+		Perbill,
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const Width: u32 = 8;
+		pub const Height: u32 = 8;
+		pub const GenerationPeriod: u64 = 2;
+		pub const MaxGenerationsPerPoke: u32 = 3;
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	mod game_of_life {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			game_of_life<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type ManageOrigin = EnsureRoot<u64>;
+		type Width = Width;
+		type Height = Height;
+		type GenerationPeriod = GenerationPeriod;
+		type MaxGenerationsPerPoke = MaxGenerationsPerPoke;
+		type WeightInfo = ();
+	}
+
+	pub type System = system::Module<TestRuntime>;
+	pub type GameOfLife = Module<TestRuntime>;
+
+	/// An 8 by 8 grid that moves on every two blocks, seeded in block 1 with `cells`
+	pub fn new_test_ext(cells: &[(u32, u32)]) -> sp_io::TestExternalities {
+		let t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		let mut ext: sp_io::TestExternalities = t.into();
+		ext.execute_with(|| {
+			System::set_block_number(1);
+			assert_ok!(GameOfLife::seed(RawOrigin::Root.into(), rows(cells)));
+		});
+		ext
+	}
+
+	/// The rows of an 8 by 8 grid in which `cells`, given as (column, row), are alive
+	fn rows(cells: &[(u32, u32)]) -> Vec<u64> {
+		let mut rows = vec![0; 8];
+		for (x, y) in cells {
+			rows[*y as usize] |= 1 << x;
+		}
+		rows
+	}
+
+	const BLINKER: [(u32, u32); 3] = [(1, 2), (2, 2), (3, 2)];
+	const GLIDER: [(u32, u32); 5] = [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)];
+
+	#[test]
+	fn seeding_checks_the_grid() {
+		new_test_ext(&[]).execute_with(|| {
+			assert!(GameOfLife::seed(Origin::signed(1), rows(&BLINKER)).is_err());
+			let root = || -> Origin { RawOrigin::Root.into() };
+			assert_err!(GameOfLife::seed(root(), vec![0; 7]), "wrong number of rows");
+			let mut too_wide = rows(&BLINKER);
+			too_wide[3] |= 1 << 8;
+			assert_err!(GameOfLife::seed(root(), too_wide), "cell outside the grid");
+
+			System::set_block_number(10);
+			assert_ok!(GameOfLife::seed(root(), rows(&BLINKER)));
+			assert_last_event::<TestRuntime>(RawEvent::Seeded(3));
+			assert_eq!(GameOfLife::seeded_at(), 10);
+			assert_eq!(GameOfLife::generation(), 0);
+		})
+	}
+
+	#[test]
+	fn generations_are_due_with_time_but_computed_when_poked() {
+		new_test_ext(&BLINKER).execute_with(|| {
+			assert_err!(GameOfLife::poke(Origin::signed(2)), "grid is up to date");
+
+			System::set_block_number(3);
+			assert_eq!(GameOfLife::due_generation(), 1);
+			assert!(!GameOfLife::is_current());
+			// Nothing changed in storage until someone pokes
+			assert_eq!(GameOfLife::grid(), rows(&BLINKER));
+
+			assert_ok!(GameOfLife::poke(Origin::signed(2)));
+			assert_last_event::<TestRuntime>(RawEvent::Evolved(2, 0, 1));
+			assert_eq!(GameOfLife::grid(), rows(&[(2, 1), (2, 2), (2, 3)]));
+			assert!(GameOfLife::is_current());
+
+			System::set_block_number(5);
+			assert_ok!(GameOfLife::poke(Origin::signed(2)));
+			assert_eq!(GameOfLife::grid(), rows(&BLINKER));
+		})
+	}
+
+	#[test]
+	fn long_absences_take_several_pokes() {
+		new_test_ext(&GLIDER).execute_with(|| {
+			System::set_block_number(9);
+			assert_eq!(GameOfLife::due_generation(), 4);
+
+			assert_ok!(GameOfLife::poke(Origin::signed(2)));
+			assert_last_event::<TestRuntime>(RawEvent::Evolved(2, 0, 3));
+			assert!(!GameOfLife::is_current());
+
+			assert_ok!(GameOfLife::poke(Origin::signed(2)));
+			assert_last_event::<TestRuntime>(RawEvent::Evolved(2, 3, 4));
+			// After four generations, the glider has moved one cell right and one down
+			let moved: Vec<_> = GLIDER.iter().map(|(x, y)| (x + 1, y + 1)).collect();
+			assert_eq!(GameOfLife::grid(), rows(&moved));
+		})
+	}
+
+	#[test]
+	fn still_lifes_catch_up_at_once() {
+		let block = [(3, 3), (4, 3), (3, 4), (4, 4)];
+		new_test_ext(&block).execute_with(|| {
+			System::set_block_number(101);
+			assert_ok!(GameOfLife::poke(Origin::signed(2)));
+			assert_last_event::<TestRuntime>(RawEvent::Evolved(2, 0, 50));
+			assert_eq!(GameOfLife::grid(), rows(&block));
+		});
+
+		// A lone cell dies, and the empty grid stays empty
+		new_test_ext(&[(0, 0)]).execute_with(|| {
+			System::set_block_number(101);
+			assert_ok!(GameOfLife::poke(Origin::signed(2)));
+			assert_last_event::<TestRuntime>(RawEvent::Evolved(2, 0, 50));
+			assert_eq!(GameOfLife::grid(), vec![0; 8]);
+		})
+	}
+
+	#[test]
+	fn cells_beyond_the_edges_are_dead() {
+		// A blinker against the left edge loses the cell that would leave the grid
+		new_test_ext(&[(0, 0), (0, 1), (0, 2)]).execute_with(|| {
+			System::set_block_number(3);
+			assert_ok!(GameOfLife::poke(Origin::signed(2)));
+			assert_eq!(GameOfLife::grid(), rows(&[(0, 1), (1, 1)]));
+		})
+	}
+
+	#[test]
+	fn weight_depends_on_grid_size() {
+		assert_eq!(GameOfLife::cells(), 64);
+		let call = Call::<TestRuntime>::poke();
+		assert_eq!(call.get_dispatch_info().weight, <() as WeightInfo>::poke(3, 64));
+	}
+}
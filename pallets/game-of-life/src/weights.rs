@@ -0,0 +1,38 @@
+//! Weights for the game-of-life pallet
+//!
+//! Every generation visits every cell of the grid once, so the weights are a function of the
+//! number of cells. `poke` is charged for the most generations it may compute.
+
+use sp_std::marker::PhantomData;
+use frame_support::weights::Weight;
+
+/// Weight functions needed by the game-of-life pallet
+pub trait WeightInfo {
+    fn seed(cells: u32) -> Weight;
+    fn poke(generations: u32, cells: u32) -> Weight;
+}
+
+/// Hand-picked estimates of the game-of-life pallet's weights
+pub struct EstimatedWeight<T>(PhantomData<T>);
+impl<T: frame_system::Trait> WeightInfo for EstimatedWeight<T> {
+    // writes of the grid, the generation and the block it was seeded in, plus a check of every
+    // row
+    fn seed(cells: u32) -> Weight {
+        20_000u32.saturating_add(10u32.saturating_mul(cells))
+    }
+    // Fixed: reads and writes of the grid and the generation, and a read of the seed block.
+    // Per generation and cell: counting eight neighbours in memory.
+    fn poke(generations: u32, cells: u32) -> Weight {
+        30_000u32.saturating_add(100u32.saturating_mul(generations).saturating_mul(cells))
+    }
+}
+
+// For backwards compatibility and tests
+impl WeightInfo for () {
+    fn seed(cells: u32) -> Weight {
+        10_000u32.saturating_add(cells)
+    }
+    fn poke(generations: u32, cells: u32) -> Weight {
+        10_000u32.saturating_add(generations.saturating_mul(cells))
+    }
+}
@@ -1,11 +1,16 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 
 /// Event uses types from the pallet's configuration trait
-use support::{decl_event, decl_module, dispatch::DispatchResult};
+use support::{decl_event, decl_module, dispatch::DispatchResult, traits::Currency};
 use system::ensure_signed;
 
+type BalanceOf<T> = <<T as Trait>::Currency as Currency<<T as system::Trait>::AccountId>>::Balance;
+
 pub trait Trait: system::Trait {
     type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+    // The currency whose balances the pallet reports
+    type Currency: Currency<Self::AccountId>;
 }
 
 decl_module! {
@@ -21,16 +26,27 @@ decl_module! {
             Self::deposit_event(RawEvent::EmitInput(user, new_number));
             Ok(())
         }
+
+        fn report_balance(origin) -> DispatchResult {
+            let user = ensure_signed(origin)?;
+            let balance = T::Currency::free_balance(&user);
+
+            Self::deposit_event(RawEvent::EmitBalance(user, balance));
+            Ok(())
+        }
     }
 }
 
-// AccountId, u32 both are inputs `=>` declaration with `<T>`
+// AccountId and Balance are both types from the runtime, so they are declared after `where`.
+// Balance comes from the pallet's own configuration trait rather than the system trait.
 decl_event!(
     pub enum Event<T>
     where
         AccountId = <T as system::Trait>::AccountId,
+        Balance = BalanceOf<T>,
     {
         EmitInput(AccountId, u32),
+        EmitBalance(AccountId, Balance),
     }
 );
 
@@ -38,6 +54,7 @@ decl_event!(
 mod tests {
     use super::RawEvent;
     use crate::{Module, Trait};
+    use event_assert::{assert_has_event, assert_last_event};
     use primitives::H256;
     use runtime_io;
     use runtime_primitives::{
@@ -59,6 +76,10 @@ mod tests {
         pub const MaximumBlockWeight: u32 = 1024;
         pub const MaximumBlockLength: u32 = 2 * 1024;
         pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+        pub const ExistentialDeposit: u64 = 0;
+        pub const TransferFee: u64 = 0;
+        pub const CreationFee: u64 = 0;
     }
     impl system::Trait for TestRuntime {
         type Origin = Origin;
@@ -79,6 +100,18 @@ mod tests {
         type ModuleToIndex = ();
     }
 
+    impl balances::Trait for TestRuntime {
+        type Balance = u64;
+        type OnFreeBalanceZero = ();
+        type OnNewAccount = ();
+        type Event = TestEvent;
+        type TransferPayment = ();
+        type DustRemoval = ();
+        type ExistentialDeposit = ExistentialDeposit;
+        type TransferFee = TransferFee;
+        type CreationFee = CreationFee;
+    }
+
     mod generic_event {
         pub use crate::Event;
     }
@@ -86,23 +119,31 @@ mod tests {
     impl_outer_event! {
         pub enum TestEvent for TestRuntime {
             generic_event<T>,
+            balances<T>,
         }
     }
 
     impl Trait for TestRuntime {
         type Event = TestEvent;
+        type Currency = balances::Module<Self>;
     }
 
-    pub type System = system::Module<TestRuntime>;
+    pub type Balances = balances::Module<TestRuntime>;
     pub type GenericEvent = Module<TestRuntime>;
 
     pub struct ExtBuilder;
 
     impl ExtBuilder {
         pub fn build() -> runtime_io::TestExternalities {
-            let storage = system::GenesisConfig::default()
+            let mut storage = system::GenesisConfig::default()
                 .build_storage::<TestRuntime>()
                 .unwrap();
+            balances::GenesisConfig::<TestRuntime> {
+                balances: vec![(1, 100), (2, 50)],
+                vesting: vec![],
+            }
+            .assimilate_storage(&mut storage)
+            .unwrap();
             runtime_io::TestExternalities::from(storage)
         }
     }
@@ -112,11 +153,31 @@ mod tests {
         ExtBuilder::build().execute_with(|| {
             assert_ok!(GenericEvent::do_something(Origin::signed(1), 32));
 
-            // construct event that should be emitted in the method call directly above
-            let expected_event = TestEvent::generic_event(RawEvent::EmitInput(1, 32));
+            // the pallet's own event is converted into the runtime's `TestEvent` by the helper
+            assert_last_event::<TestRuntime>(RawEvent::EmitInput(1, 32));
+        })
+    }
+
+    #[test]
+    fn event_carries_balance() {
+        ExtBuilder::build().execute_with(|| {
+            assert_ok!(GenericEvent::report_balance(Origin::signed(1)));
+            assert_ok!(GenericEvent::report_balance(Origin::signed(2)));
+
+            assert_last_event::<TestRuntime>(RawEvent::EmitBalance(2, 50));
+            assert_has_event::<TestRuntime>(RawEvent::EmitBalance(1, 100));
+        })
+    }
+
+    #[test]
+    fn events_from_other_pallets() {
+        ExtBuilder::build().execute_with(|| {
+            assert_ok!(Balances::transfer(Origin::signed(1), 3, 10));
+            assert_ok!(GenericEvent::report_balance(Origin::signed(3)));
 
-            // iterate through array of `EventRecord`s
-            assert!(System::events().iter().any(|a| a.event == expected_event));
+            // the transfer created account 3, which the balances pallet announced
+            assert_has_event::<TestRuntime>(balances::RawEvent::NewAccount(3, 10));
+            assert_last_event::<TestRuntime>(RawEvent::EmitBalance(3, 10));
         })
     }
 }
@@ -0,0 +1,18 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use codec::Codec;
+use sp_std::vec::Vec;
+
+sp_api::decl_runtime_apis! {
+	pub trait HalvingRewardApi<BlockNumber, Balance> where
+		BlockNumber: Codec,
+		Balance: Codec,
+	{
+		/// The reward per block from each block on, as (first block, reward), ending with the block
+		/// from which the reward is zero
+		fn emission_curve() -> Vec<(BlockNumber, Balance)>;
+
+		/// The total issuance beyond which no rewards are minted
+		fn max_supply() -> Balance;
+	}
+}
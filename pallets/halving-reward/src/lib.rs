@@ -0,0 +1,326 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! Block rewards that halve on a schedule, up to a supply cap
+//!
+//! Like `block-reward`, this pallet finds the author of each block with the runtime's `FindAuthor`
+//! and mints a reward to them in `on_finalize`. The reward is not fixed though. It starts at
+//! `InitialReward`, and halves every `HalvingPeriod` blocks until it rounds down to zero, as in
+//! Bitcoin. The sum of all rewards is bounded: at most twice the initial reward for every block
+//! of a period.
+//!
+//! Rewards never lift the total issuance above `MaxSupply`. The last reward before the cap is cut
+//! short, and nothing is minted while the issuance is at the cap. Fees that are burned lower the
+//! issuance again, and make room for further rewards.
+//!
+//! `emission_curve` lists the reward of each period, so that wallets and explorers can show the
+//! schedule through the `HalvingRewardApi`.
+
+use sp_std::prelude::*;
+use sp_runtime::traits::{CheckedShr, SaturatedConversion, Saturating, Zero};
+use frame_support::{
+	decl_event, decl_module, decl_storage,
+	traits::{Currency, FindAuthor, Get, Imbalance},
+};
+use frame_system as system;
+
+type BalanceOf<T> = <<T as Trait>::Currency as Currency<<T as system::Trait>::AccountId>>::Balance;
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The currency in which rewards are paid
+	type Currency: Currency<Self::AccountId>;
+
+	/// Identifies the author of the current block from its pre-runtime digests
+	type FindAuthor: FindAuthor<Self::AccountId>;
+
+	/// The reward for each block of the first period
+	type InitialReward: Get<BalanceOf<Self>>;
+
+	/// The number of blocks after which the reward halves. Zero means it never does.
+	type HalvingPeriod: Get<Self::BlockNumber>;
+
+	/// The total issuance beyond which no rewards are minted
+	type MaxSupply: Get<BalanceOf<Self>>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as HalvingReward {
+		/// The author of the current block. Only set while a block is being executed.
+		Author get(fn author): Option<T::AccountId>;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as system::Trait>::AccountId,
+		Balance = BalanceOf<T>,
+	{
+		/// An author was paid the block reward
+		BlockRewarded(AccountId, Balance),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		const InitialReward: BalanceOf<T> = T::InitialReward::get();
+
+		const HalvingPeriod: T::BlockNumber = T::HalvingPeriod::get();
+
+		const MaxSupply: BalanceOf<T> = T::MaxSupply::get();
+
+		fn on_initialize(_n: T::BlockNumber) {
+			let digest = <system::Module<T>>::digest();
+			let pre_runtime_digests = digest.logs().iter().filter_map(|d| d.as_pre_runtime());
+			if let Some(author) = T::FindAuthor::find_author(pre_runtime_digests) {
+				<Author<T>>::put(author);
+			}
+		}
+
+		fn on_finalize(n: T::BlockNumber) {
+			if let Some(author) = <Author<T>>::take() {
+				let room = T::MaxSupply::get().saturating_sub(T::Currency::total_issuance());
+				let reward = Self::reward_at(n).min(room);
+				if reward.is_zero() {
+					return;
+				}
+				// Dropping the positive imbalance increases total issuance
+				let minted = T::Currency::deposit_creating(&author, reward).peek();
+				Self::deposit_event(RawEvent::BlockRewarded(author, minted));
+			}
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// The scheduled reward for block `n`, before the supply cap
+	pub fn reward_at(n: T::BlockNumber) -> BalanceOf<T> {
+		let period = T::HalvingPeriod::get();
+		if period.is_zero() {
+			return T::InitialReward::get();
+		}
+		let halvings = (n / period).saturated_into::<u32>();
+		// Shifting by the width of the balance type or more leaves nothing
+		T::InitialReward::get().checked_shr(halvings).unwrap_or_else(Zero::zero)
+	}
+
+	/// The reward of each period as (first block, reward), ending with the first block without
+	/// a reward. Without halvings, that is just the initial reward from block zero on.
+	pub fn emission_curve() -> Vec<(T::BlockNumber, BalanceOf<T>)> {
+		let period = T::HalvingPeriod::get();
+		let mut curve = vec![(Zero::zero(), T::InitialReward::get())];
+		if period.is_zero() {
+			return curve;
+		}
+
+		let mut halvings = 1u32;
+		while let Some(&(_, reward)) = curve.last() {
+			if reward.is_zero() {
+				break;
+			}
+			let start = period.saturating_mul(halvings.into());
+			curve.push((start, Self::reward_at(start)));
+			halvings += 1;
+		}
+		curve
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use event_assert::{assert_has_event, assert_last_event, assert_no_events};
+
+	use std::cell::RefCell;
+	use sp_core::H256;
+	use frame_support::{impl_outer_event, impl_outer_origin, parameter_types};
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup, OnFinalize, OnInitialize},
+		ConsensusEngineId, Perbill,
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	thread_local! {
+		static AUTHOR: RefCell<Option<u64>> = RefCell::new(None);
+	}
+
+	/// Pretends the author of every block is whoever `set_author` named last
+	pub struct TestAuthor;
+	impl FindAuthor<u64> for TestAuthor {
+		fn find_author<'a, I>(_digests: I) -> Option<u64>
+		where
+			I: 'a + IntoIterator<Item = (ConsensusEngineId, &'a [u8])>,
+		{
+			AUTHOR.with(|a| *a.borrow())
+		}
+	}
+
+	fn set_author(author: Option<u64>) {
+		AUTHOR.with(|a| *a.borrow_mut() = author);
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const ExistentialDeposit: u64 = 0;
+		pub const TransferFee: u64 = 0;
+		pub const CreationFee: u64 = 0;
+
+		pub const InitialReward: u64 = 16;
+		pub const HalvingPeriod: u64 = 10;
+		pub const MaxSupply: u64 = 300;
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	impl balances::Trait for TestRuntime {
+		type Balance = u64;
+		type OnFreeBalanceZero = ();
+		type OnNewAccount = ();
+		type Event = TestEvent;
+		type TransferPayment = ();
+		type DustRemoval = ();
+		type ExistentialDeposit = ExistentialDeposit;
+		type TransferFee = TransferFee;
+		type CreationFee = CreationFee;
+	}
+
+	mod halving_reward {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			balances<T>,
+			halving_reward<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type Currency = balances::Module<Self>;
+		type FindAuthor = TestAuthor;
+		type InitialReward = InitialReward;
+		type HalvingPeriod = HalvingPeriod;
+		type MaxSupply = MaxSupply;
+	}
+
+	pub type System = system::Module<TestRuntime>;
+	pub type Balances = balances::Module<TestRuntime>;
+	pub type HalvingReward = Module<TestRuntime>;
+
+	/// Starts with a total issuance of 200
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		set_author(None);
+		let mut t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		balances::GenesisConfig::<TestRuntime> {
+			balances: vec![(1, 100), (2, 100)],
+			vesting: vec![],
+		}
+		.assimilate_storage(&mut t)
+		.unwrap();
+		t.into()
+	}
+
+	/// Execute block `n`, authored by `author`
+	fn author_block(n: u64, author: u64) {
+		set_author(Some(author));
+		HalvingReward::on_initialize(n);
+		HalvingReward::on_finalize(n);
+	}
+
+	#[test]
+	fn reward_halves_every_period() {
+		new_test_ext().execute_with(|| {
+			assert_eq!(HalvingReward::reward_at(0), 16);
+			assert_eq!(HalvingReward::reward_at(9), 16);
+			assert_eq!(HalvingReward::reward_at(10), 8);
+			assert_eq!(HalvingReward::reward_at(25), 4);
+			assert_eq!(HalvingReward::reward_at(49), 1);
+			assert_eq!(HalvingReward::reward_at(50), 0);
+			// Far beyond the width of the balance type
+			assert_eq!(HalvingReward::reward_at(10_000), 0);
+		})
+	}
+
+	#[test]
+	fn author_is_rewarded() {
+		new_test_ext().execute_with(|| {
+			author_block(10, 1);
+			assert_eq!(Balances::free_balance(&1), 108);
+			assert_eq!(HalvingReward::author(), None);
+			assert_has_event::<TestRuntime>(RawEvent::BlockRewarded(1, 8));
+		})
+	}
+
+	#[test]
+	fn no_author_no_reward() {
+		new_test_ext().execute_with(|| {
+			HalvingReward::on_initialize(1);
+			HalvingReward::on_finalize(1);
+			assert_eq!(Balances::total_issuance(), 200);
+		})
+	}
+
+	#[test]
+	fn supply_is_capped() {
+		new_test_ext().execute_with(|| {
+			for n in 1..=6 {
+				author_block(n, 1);
+			}
+			assert_eq!(Balances::total_issuance(), 296);
+
+			// Only the room left under the cap is minted
+			author_block(7, 1);
+			assert_eq!(Balances::total_issuance(), 300);
+			assert_last_event::<TestRuntime>(RawEvent::BlockRewarded(1, 4));
+
+			System::reset_events();
+			author_block(8, 1);
+			assert_eq!(Balances::total_issuance(), 300);
+			assert_no_events::<TestRuntime>();
+		})
+	}
+
+	#[test]
+	fn emission_curve_ends_with_zero() {
+		new_test_ext().execute_with(|| {
+			assert_eq!(
+				HalvingReward::emission_curve(),
+				vec![(0, 16), (10, 8), (20, 4), (30, 2), (40, 1), (50, 0)],
+			);
+		})
+	}
+}
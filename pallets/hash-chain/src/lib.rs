@@ -0,0 +1,191 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! Extending the runtime with a host function
+//!
+//! A hash chain applies a hash function to a seed over and over. Checking a long chain is slow in
+//! Wasm, so this crate declares a custom host function that computes it natively in the node. The
+//! `#[runtime_interface]` macro generates both sides of the boundary: in the Wasm runtime the
+//! function is an import from the host, and in the node it is the body written below.
+//!
+//! Host functions become part of the consensus rules. Every node that imports blocks of a runtime
+//! using this pallet must register `host_hashing::HostFunctions` with its executor, or it will not
+//! be able to execute those blocks.
+
+use frame_support::{decl_event, decl_module, decl_storage, dispatch::DispatchResult, ensure, traits::Get};
+use frame_system::{self as system, ensure_signed};
+use sp_runtime_interface::runtime_interface;
+
+/// Hashing functions that run natively in the node
+#[runtime_interface]
+pub trait HostHashing {
+	/// Hash `seed` with Blake2-256, then hash the result, `iterations` times in total
+	fn hash_chain(seed: &[u8], iterations: u32) -> [u8; 32] {
+		let mut hash = sp_core::hashing::blake2_256(seed);
+		for _ in 1..iterations {
+			hash = sp_core::hashing::blake2_256(&hash);
+		}
+		hash
+	}
+}
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The longest chain that may be checked in one call
+	type MaxIterations: Get<u32>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as HashChain {
+		/// Checked chains: the end of the chain by its seed and length
+		Checked get(fn checked): map ([u8; 32], u32) => Option<[u8; 32]>;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as system::Trait>::AccountId,
+	{
+		/// An account showed the end of the chain of this length from this seed
+		ChainChecked(AccountId, [u8; 32], u32, [u8; 32]),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		const MaxIterations: u32 = T::MaxIterations::get();
+
+		/// Claim that hashing `seed` `iterations` times gives `end`
+		fn check_chain(origin, seed: [u8; 32], iterations: u32, end: [u8; 32]) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			ensure!(iterations > 0, "empty chain");
+			ensure!(iterations <= T::MaxIterations::get(), "chain too long");
+
+			// Runs natively, even when the runtime is executed in Wasm
+			ensure!(host_hashing::hash_chain(&seed, iterations) == end, "wrong end of chain");
+			Checked::insert((seed, iterations), end);
+
+			Self::deposit_event(RawEvent::ChainChecked(who, seed, iterations, end));
+			Ok(())
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use sp_core::H256;
+	use frame_support::{assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types};
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup},
+		Perbill,
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const MaxIterations: u32 = 1000;
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	mod hash_chain {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			hash_chain<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type MaxIterations = MaxIterations;
+	}
+
+	pub type System = system::Module<TestRuntime>;
+	pub type HashChain = Module<TestRuntime>;
+
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		let t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		t.into()
+	}
+
+	fn event_emitted(event: RawEvent<u64>) -> bool {
+		let expected = TestEvent::hash_chain(event);
+		System::events().iter().any(|a| a.event == expected)
+	}
+
+	const SEED: [u8; 32] = [7; 32];
+
+	#[test]
+	fn host_function_hashes_repeatedly() {
+		let once = sp_core::hashing::blake2_256(&SEED);
+		let twice = sp_core::hashing::blake2_256(&once);
+		assert_eq!(host_hashing::hash_chain(&SEED, 1), once);
+		assert_eq!(host_hashing::hash_chain(&SEED, 2), twice);
+	}
+
+	#[test]
+	fn correct_chains_are_recorded() {
+		new_test_ext().execute_with(|| {
+			let end = host_hashing::hash_chain(&SEED, 500);
+			assert_ok!(HashChain::check_chain(Origin::signed(1), SEED, 500, end));
+
+			assert_eq!(HashChain::checked((SEED, 500)), Some(end));
+			assert!(event_emitted(RawEvent::ChainChecked(1, SEED, 500, end)));
+		})
+	}
+
+	#[test]
+	fn wrong_chains_are_rejected() {
+		new_test_ext().execute_with(|| {
+			let end = host_hashing::hash_chain(&SEED, 500);
+			assert_err!(
+				HashChain::check_chain(Origin::signed(1), SEED, 499, end),
+				"wrong end of chain"
+			);
+			assert_err!(HashChain::check_chain(Origin::signed(1), SEED, 0, end), "empty chain");
+			assert_err!(
+				HashChain::check_chain(Origin::signed(1), SEED, 1001, end),
+				"chain too long"
+			);
+			assert_eq!(HashChain::checked((SEED, 499)), None);
+		})
+	}
+}
@@ -0,0 +1,401 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! A light client of a remote proof of work chain, the skeleton of a bridge
+//!
+//! Relayers submit the remote chain's headers one at a time. The runtime checks each header
+//! itself, so relayers need not be trusted:
+//!
+//! * The header must extend the best known header: its parent hash and number must follow on.
+//! * Its proof of work must meet the `Difficulty`.
+//!
+//! Root sets the first header, a trusted checkpoint. Only the last `HeadersToKeep` headers are
+//! stored. A header counts as final once `Confirmations` headers have been built on top of it,
+//! which is how proof of work chains usually define finality.
+
+use codec::{Decode, Encode};
+use sp_core::{H256, U256};
+use sp_io::hashing::blake2_256;
+use sp_runtime::RuntimeDebug;
+use frame_support::{
+	decl_event, decl_module, decl_storage,
+	dispatch::DispatchResult,
+	ensure,
+	traits::Get,
+};
+use frame_system::{self as system, ensure_root, ensure_signed};
+
+pub type RemoteBlockNumber = u32;
+
+/// A header of the remote chain
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct RemoteHeader {
+	pub number: RemoteBlockNumber,
+	pub parent_hash: H256,
+	/// The root of the remote chain's state, against which storage proofs can be checked
+	pub state_root: H256,
+	/// Chosen by the miner so that the header's hash meets the difficulty
+	pub nonce: u64,
+}
+
+impl RemoteHeader {
+	pub fn hash(&self) -> H256 {
+		blake2_256(&self.encode()).into()
+	}
+
+	/// The hash, read as a number, times `difficulty` must not overflow 256 bits
+	pub fn meets_difficulty(&self, difficulty: u128) -> bool {
+		U256::from(self.hash().as_bytes()).checked_mul(U256::from(difficulty)).is_some()
+	}
+}
+
+/// What is kept of each imported header
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct ImportedHeader {
+	pub hash: H256,
+	pub state_root: H256,
+}
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The remote chain's proof of work difficulty
+	type Difficulty: Get<u128>;
+
+	/// Number of headers to build on a header before it is final
+	type Confirmations: Get<RemoteBlockNumber>;
+
+	/// Number of recent headers to store. Must be more than `Confirmations`.
+	type HeadersToKeep: Get<RemoteBlockNumber>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as HeaderBridge {
+		/// Recent headers of the remote chain by number
+		Headers get(fn header): map RemoteBlockNumber => Option<ImportedHeader>;
+
+		/// The number of the best header, once the bridge is initialized
+		BestNumber get(fn best_number): Option<RemoteBlockNumber>;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as system::Trait>::AccountId,
+	{
+		/// The bridge was initialized with a checkpoint
+		Initialized(RemoteBlockNumber, H256),
+		/// A relayer submitted a new best header
+		HeaderImported(RemoteBlockNumber, H256, AccountId),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		const Difficulty: u128 = T::Difficulty::get();
+
+		const Confirmations: RemoteBlockNumber = T::Confirmations::get();
+
+		const HeadersToKeep: RemoteBlockNumber = T::HeadersToKeep::get();
+
+		/// Start tracking the remote chain from a header that is trusted to be canonical
+		fn initialize(origin, header: RemoteHeader) -> DispatchResult {
+			ensure_root(origin)?;
+			ensure!(Self::best_number().is_none(), "already initialized");
+
+			let (number, hash) = (header.number, header.hash());
+			Self::import(header);
+
+			Self::deposit_event(RawEvent::Initialized(number, hash));
+			Ok(())
+		}
+
+		/// Submit the header that follows the best known header
+		fn submit_header(origin, header: RemoteHeader) -> DispatchResult {
+			let relayer = ensure_signed(origin)?;
+			let best_number = Self::best_number().ok_or("not initialized")?;
+			ensure!(header.number == best_number.wrapping_add(1), "header does not follow best");
+			let best = Self::header(best_number).ok_or("best header missing")?;
+			ensure!(header.parent_hash == best.hash, "parent is not the best header");
+			ensure!(header.meets_difficulty(T::Difficulty::get()), "insufficient proof of work");
+
+			let (number, hash) = (header.number, header.hash());
+			Self::import(header);
+			if let Some(stale) = number.checked_sub(T::HeadersToKeep::get()) {
+				Headers::remove(stale);
+			}
+
+			Self::deposit_event(RawEvent::HeaderImported(number, hash, relayer));
+			Ok(())
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// The number of the latest final header
+	pub fn finalized_number() -> Option<RemoteBlockNumber> {
+		Self::best_number()?.checked_sub(T::Confirmations::get())
+	}
+
+	/// The latest final header
+	pub fn finalized_header() -> Option<ImportedHeader> {
+		Self::header(Self::finalized_number()?)
+	}
+
+	/// Whether a header is final. Headers older than `HeadersToKeep` are no longer known.
+	pub fn is_finalized(number: RemoteBlockNumber, hash: &H256) -> bool {
+		match (Self::finalized_number(), Self::header(number)) {
+			(Some(finalized), Some(header)) => number <= finalized && header.hash == *hash,
+			_ => false,
+		}
+	}
+
+	fn import(header: RemoteHeader) {
+		let imported = ImportedHeader { hash: header.hash(), state_root: header.state_root };
+		Headers::insert(header.number, imported);
+		BestNumber::put(header.number);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use std::cell::RefCell;
+	use frame_support::{assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types};
+	use frame_system::RawOrigin;
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup},
+		Perbill,
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	thread_local! {
+		static DIFFICULTY: RefCell<u128> = RefCell::new(1);
+	}
+
+	pub struct Difficulty;
+	impl Get<u128> for Difficulty {
+		fn get() -> u128 {
+			DIFFICULTY.with(|d| *d.borrow())
+		}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const Confirmations: u32 = 2;
+		pub const HeadersToKeep: u32 = 4;
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	mod header_bridge {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			header_bridge<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type Difficulty = Difficulty;
+		type Confirmations = Confirmations;
+		type HeadersToKeep = HeadersToKeep;
+	}
+
+	pub type System = system::Module<TestRuntime>;
+	pub type HeaderBridge = Module<TestRuntime>;
+
+	/// A fresh chain with an easy difficulty, initialized with header 10
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		DIFFICULTY.with(|d| *d.borrow_mut() = 1);
+		let t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		let mut ext: sp_io::TestExternalities = t.into();
+		ext.execute_with(|| {
+			assert_ok!(HeaderBridge::initialize(RawOrigin::Root.into(), checkpoint()));
+		});
+		ext
+	}
+
+	fn checkpoint() -> RemoteHeader {
+		RemoteHeader { number: 10, parent_hash: H256::zero(), state_root: H256::zero(), nonce: 0 }
+	}
+
+	fn event_emitted(event: RawEvent<u64>) -> bool {
+		let expected = TestEvent::header_bridge(event);
+		System::events().iter().any(|a| a.event == expected)
+	}
+
+	/// Mine the header that follows the best one, trying nonces until the difficulty is met
+	fn mine_next() -> RemoteHeader {
+		let best = HeaderBridge::best_number().expect("initialized");
+		let mut header = RemoteHeader {
+			number: best + 1,
+			parent_hash: HeaderBridge::header(best).expect("best is stored").hash,
+			state_root: H256::repeat_byte(best as u8),
+			nonce: 0,
+		};
+		while !header.meets_difficulty(Difficulty::get()) {
+			header.nonce += 1;
+		}
+		header
+	}
+
+	fn import_next() -> RemoteHeader {
+		let header = mine_next();
+		assert_ok!(HeaderBridge::submit_header(Origin::signed(1), header.clone()));
+		header
+	}
+
+	#[test]
+	fn initialize_sets_checkpoint() {
+		new_test_ext().execute_with(|| {
+			assert_eq!(HeaderBridge::best_number(), Some(10));
+			assert_eq!(HeaderBridge::header(10).map(|h| h.hash), Some(checkpoint().hash()));
+			assert!(event_emitted(RawEvent::Initialized(10, checkpoint().hash())));
+			assert_err!(
+				HeaderBridge::initialize(RawOrigin::Root.into(), checkpoint()),
+				"already initialized"
+			);
+		})
+	}
+
+	#[test]
+	fn initialize_requires_root() {
+		new_test_ext().execute_with(|| {
+			assert!(HeaderBridge::initialize(Origin::signed(1), checkpoint()).is_err());
+		})
+	}
+
+	#[test]
+	fn submit_requires_initialization() {
+		let t = system::GenesisConfig::default().build_storage::<TestRuntime>().unwrap();
+		let mut ext: sp_io::TestExternalities = t.into();
+		ext.execute_with(|| {
+			assert_err!(
+				HeaderBridge::submit_header(Origin::signed(1), checkpoint()),
+				"not initialized"
+			);
+		})
+	}
+
+	#[test]
+	fn sequential_headers_are_imported() {
+		new_test_ext().execute_with(|| {
+			let header = import_next();
+			assert_eq!(HeaderBridge::best_number(), Some(11));
+			assert_eq!(HeaderBridge::header(11).map(|h| h.state_root), Some(header.state_root));
+			assert!(event_emitted(RawEvent::HeaderImported(11, header.hash(), 1)));
+		})
+	}
+
+	#[test]
+	fn headers_must_follow_best() {
+		new_test_ext().execute_with(|| {
+			let mut header = mine_next();
+			header.number = 12;
+			assert_err!(
+				HeaderBridge::submit_header(Origin::signed(1), header),
+				"header does not follow best"
+			);
+
+			let mut header = mine_next();
+			header.parent_hash = H256::repeat_byte(1);
+			assert_err!(
+				HeaderBridge::submit_header(Origin::signed(1), header),
+				"parent is not the best header"
+			);
+
+			// The same header cannot be imported twice
+			let header = import_next();
+			assert_err!(
+				HeaderBridge::submit_header(Origin::signed(1), header),
+				"header does not follow best"
+			);
+		})
+	}
+
+	#[test]
+	fn proof_of_work_is_checked() {
+		new_test_ext().execute_with(|| {
+			DIFFICULTY.with(|d| *d.borrow_mut() = 64);
+			let mut header = mine_next();
+			// Find a nonce that does not meet the difficulty
+			while header.meets_difficulty(64) {
+				header.nonce += 1;
+			}
+			assert_err!(
+				HeaderBridge::submit_header(Origin::signed(1), header),
+				"insufficient proof of work"
+			);
+			import_next();
+		})
+	}
+
+	#[test]
+	fn only_recent_headers_are_kept() {
+		new_test_ext().execute_with(|| {
+			for _ in 0..4 {
+				import_next();
+			}
+			assert_eq!(HeaderBridge::best_number(), Some(14));
+			assert!(HeaderBridge::header(10).is_none());
+			assert!(HeaderBridge::header(11).is_some());
+		})
+	}
+
+	#[test]
+	fn finality_follows_confirmations() {
+		new_test_ext().execute_with(|| {
+			assert_eq!(HeaderBridge::finalized_number(), Some(8));
+			// Header 8 was never imported
+			assert_eq!(HeaderBridge::finalized_header(), None);
+
+			let first = import_next();
+			import_next();
+			assert_eq!(HeaderBridge::finalized_number(), Some(10));
+			assert!(HeaderBridge::is_finalized(10, &checkpoint().hash()));
+			assert!(!HeaderBridge::is_finalized(11, &first.hash()));
+
+			import_next();
+			assert!(HeaderBridge::is_finalized(11, &first.hash()));
+			assert!(!HeaderBridge::is_finalized(11, &H256::zero()));
+			assert_eq!(HeaderBridge::finalized_header().map(|h| h.hash), Some(first.hash()));
+		})
+	}
+}
@@ -1,10 +1,15 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
 /// A very simple substrate runtime
 use support::{
-	decl_module, decl_event, decl_storage, StorageValue,
+	debug, decl_module, decl_event, decl_storage, runtime_print, StorageValue,
 	dispatch::DispatchResult,
 };
 use system::ensure_signed;
 
+/// The target of this pallet's log messages. Nodes show them with `-lhello=info`.
+const LOG_TARGET: &str = "hello";
+
 pub trait Trait: system::Trait {
 	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
 }
@@ -30,8 +35,21 @@ decl_module! {
 
 		pub fn set_value(origin, value: u64) -> DispatchResult {
 			let setter = ensure_signed(origin)?;
+
+			// Inside the Wasm runtime, `log` macros go nowhere until a logger forwards them to
+			// the node. Initializing it again in a later call does nothing.
+			debug::RuntimeLogger::init();
+			log::info!(target: LOG_TARGET, "{:?} replaces {} with {}", setter, Self::last_value(), value);
+			// Printing needs no logger, and always appears under the `runtime` target
+			runtime_print!("hello-substrate: value set to {}", value);
+
 			LastValue::put(value);
 			UserValue::<T>::insert(&setter, value);
+			// Reading the value back costs a storage read. `debug_assert!` only runs in debug
+			// builds, and the Wasm runtime is always built in release mode, so on chain this
+			// line compiles to nothing.
+			debug_assert_eq!(Self::user_value(&setter), value, "the value just written must be stored");
+
 			Self::deposit_event(RawEvent::ValueSet(setter, value));
 			Ok(())
 		}
@@ -0,0 +1,400 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! Hash time-locked contracts (HTLCs) for atomic swaps with other chains
+//!
+//! A sender locks funds for a recipient behind a hashlock and a timelock:
+//!
+//! * Until the end of block `expires_at`, anyone who knows the preimage of the hashlock can
+//!   release the funds to the recipient.
+//! * From block `expires_at + 1` on, anyone can return the funds to the sender.
+//!
+//! Hashlocks are SHA-256 hashes of 32 byte secrets, which is what Bitcoin-style HTLCs use, so the
+//! same secret can unlock a matching contract on the other chain. The `Claimed` event publishes
+//! the secret, which is all a relayer needs to complete the swap there.
+//!
+//! Contracts are keyed by their sender and hashlock, so nobody can block a swap by locking dust
+//! behind the same hashlock first. Once a secret is revealed, its hashlock can't be locked again.
+
+use codec::{Decode, Encode};
+use sp_runtime::{traits::Zero, RuntimeDebug};
+use frame_support::{
+	decl_event, decl_module, decl_storage,
+	dispatch::DispatchResult,
+	ensure,
+	traits::{Currency, Get, ReservableCurrency},
+};
+use frame_system::{self as system, ensure_signed};
+
+/// The SHA-256 hash of a secret
+pub type HashLock = [u8; 32];
+
+/// The secret that unlocks a contract
+pub type Secret = [u8; 32];
+
+type BalanceOf<T> = <<T as Trait>::Currency as Currency<<T as system::Trait>::AccountId>>::Balance;
+type ContractOf<T> = Contract<
+	<T as system::Trait>::AccountId,
+	BalanceOf<T>,
+	<T as system::Trait>::BlockNumber,
+>;
+
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct Contract<AccountId, Balance, BlockNumber> {
+	/// Who locked the funds, and gets them back after expiry
+	pub sender: AccountId,
+	/// Who gets the funds when the secret is revealed
+	pub recipient: AccountId,
+	/// Funds reserved from the sender
+	pub amount: Balance,
+	/// The last block in which the contract can be claimed
+	pub expires_at: BlockNumber,
+}
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The currency that is locked
+	type Currency: ReservableCurrency<Self::AccountId>;
+
+	/// The shortest time a contract can be claimable for
+	type MinLockPeriod: Get<Self::BlockNumber>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as Htlc {
+		/// Open contracts by their sender and hashlock
+		Contracts get(fn contract):
+			double_map T::AccountId, blake2_256(HashLock) => Option<ContractOf<T>>;
+
+		/// Hashlocks whose secret was revealed by a claim
+		Revealed get(fn revealed): map HashLock => bool;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as system::Trait>::AccountId,
+		Balance = BalanceOf<T>,
+		BlockNumber = <T as system::Trait>::BlockNumber,
+	{
+		/// Funds were locked: sender, recipient, amount and last block to claim
+		Locked(HashLock, AccountId, AccountId, Balance, BlockNumber),
+		/// The secret was revealed and the funds of the sender's contract went to the recipient
+		Claimed(AccountId, HashLock, Secret),
+		/// The sender's contract expired and the funds went back to the sender
+		Refunded(AccountId, HashLock),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		const MinLockPeriod: T::BlockNumber = T::MinLockPeriod::get();
+
+		/// Lock `amount` for `recipient` until the end of block `expires_at`
+		fn lock(
+			origin,
+			recipient: T::AccountId,
+			hashlock: HashLock,
+			amount: BalanceOf<T>,
+			expires_at: T::BlockNumber
+		) -> DispatchResult {
+			let sender = ensure_signed(origin)?;
+			ensure!(!amount.is_zero(), "amount must not be zero");
+			ensure!(!Revealed::get(&hashlock), "secret already revealed");
+			ensure!(!<Contracts<T>>::exists(&sender, &hashlock), "hashlock already in use");
+			let now = <system::Module<T>>::block_number();
+			ensure!(expires_at >= now + T::MinLockPeriod::get(), "expiry is too soon");
+
+			T::Currency::reserve(&sender, amount).map_err(|_| "cannot afford the amount")?;
+			<Contracts<T>>::insert(&sender, &hashlock, Contract {
+				sender: sender.clone(),
+				recipient: recipient.clone(),
+				amount,
+				expires_at,
+			});
+
+			Self::deposit_event(RawEvent::Locked(hashlock, sender, recipient, amount, expires_at));
+			Ok(())
+		}
+
+		/// Release the funds to the recipient by revealing the secret. Anyone may do this, so a
+		/// relayer can complete a swap on the recipient's behalf.
+		fn claim(origin, sender: T::AccountId, secret: Secret) -> DispatchResult {
+			ensure_signed(origin)?;
+			let hashlock = sp_io::hashing::sha2_256(&secret);
+			let contract = Self::contract(&sender, &hashlock).ok_or("no contract for this secret")?;
+			ensure!(
+				<system::Module<T>>::block_number() <= contract.expires_at,
+				"contract has expired"
+			);
+
+			let (funds, _) = T::Currency::slash_reserved(&contract.sender, contract.amount);
+			T::Currency::resolve_creating(&contract.recipient, funds);
+			<Contracts<T>>::remove(&sender, &hashlock);
+			Revealed::insert(&hashlock, true);
+
+			Self::deposit_event(RawEvent::Claimed(sender, hashlock, secret));
+			Ok(())
+		}
+
+		/// Return the funds of an expired contract to its sender. Anyone may do this.
+		fn refund(origin, sender: T::AccountId, hashlock: HashLock) -> DispatchResult {
+			ensure_signed(origin)?;
+			let contract = Self::contract(&sender, &hashlock).ok_or("no such contract")?;
+			ensure!(
+				<system::Module<T>>::block_number() > contract.expires_at,
+				"contract has not expired"
+			);
+
+			T::Currency::unreserve(&contract.sender, contract.amount);
+			<Contracts<T>>::remove(&sender, &hashlock);
+
+			Self::deposit_event(RawEvent::Refunded(sender, hashlock));
+			Ok(())
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use sp_core::H256;
+	use frame_support::{
+		assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types
+	};
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup},
+		Perbill,
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const ExistentialDeposit: u64 = 0;
+		pub const TransferFee: u64 = 0;
+		pub const CreationFee: u64 = 0;
+
+		pub const MinLockPeriod: u64 = 3;
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	impl balances::Trait for TestRuntime {
+		type Balance = u64;
+		type OnFreeBalanceZero = ();
+		type OnNewAccount = ();
+		type Event = TestEvent;
+		type TransferPayment = ();
+		type DustRemoval = ();
+		type ExistentialDeposit = ExistentialDeposit;
+		type TransferFee = TransferFee;
+		type CreationFee = CreationFee;
+	}
+
+	mod htlc {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			balances<T>,
+			htlc<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type Currency = balances::Module<Self>;
+		type MinLockPeriod = MinLockPeriod;
+	}
+
+	pub type System = system::Module<TestRuntime>;
+	pub type Balances = balances::Module<TestRuntime>;
+	pub type Htlc = Module<TestRuntime>;
+
+	const SECRET: Secret = [7; 32];
+
+	fn hashlock() -> HashLock {
+		sp_io::hashing::sha2_256(&SECRET)
+	}
+
+	/// Accounts 1 to 3 have 100 each. Account 1 locks 30 for account 2 at block 1, claimable
+	/// until the end of block 10.
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		let mut t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		balances::GenesisConfig::<TestRuntime> {
+			balances: vec![(1, 100), (2, 100), (3, 100)],
+			vesting: vec![],
+		}
+		.assimilate_storage(&mut t)
+		.unwrap();
+		let mut ext: sp_io::TestExternalities = t.into();
+		ext.execute_with(|| {
+			System::set_block_number(1);
+			assert_ok!(Htlc::lock(Origin::signed(1), 2, hashlock(), 30, 10));
+		});
+		ext
+	}
+
+	fn event_emitted(event: RawEvent<u64, u64, u64>) -> bool {
+		let expected = TestEvent::htlc(event);
+		System::events().iter().any(|a| a.event == expected)
+	}
+
+	#[test]
+	fn lock_reserves_funds() {
+		new_test_ext().execute_with(|| {
+			assert_eq!(Balances::reserved_balance(&1), 30);
+			assert!(event_emitted(RawEvent::Locked(hashlock(), 1, 2, 30, 10)));
+		})
+	}
+
+	#[test]
+	fn lock_is_validated() {
+		new_test_ext().execute_with(|| {
+			assert_err!(Htlc::lock(Origin::signed(1), 2, hashlock(), 30, 10), "hashlock already in use");
+			assert_err!(Htlc::lock(Origin::signed(1), 2, [0; 32], 0, 10), "amount must not be zero");
+			assert_err!(Htlc::lock(Origin::signed(1), 2, [0; 32], 80, 10), "cannot afford the amount");
+		})
+	}
+
+	#[test]
+	fn expiry_must_leave_min_lock_period() {
+		new_test_ext().execute_with(|| {
+			assert_err!(Htlc::lock(Origin::signed(2), 1, [0; 32], 10, 3), "expiry is too soon");
+			assert_ok!(Htlc::lock(Origin::signed(2), 1, [0; 32], 10, 4));
+		})
+	}
+
+	#[test]
+	fn claim_pays_recipient_and_publishes_secret() {
+		new_test_ext().execute_with(|| {
+			// Anyone can claim, the funds still go to the recipient
+			assert_ok!(Htlc::claim(Origin::signed(3), 1, SECRET));
+			assert_eq!(Balances::free_balance(&2), 130);
+			assert_eq!(Balances::total_balance(&1), 70);
+			assert_eq!(Htlc::contract(1, hashlock()), None);
+			assert!(event_emitted(RawEvent::Claimed(1, hashlock(), SECRET)));
+		})
+	}
+
+	#[test]
+	fn other_senders_cannot_block_a_hashlock() {
+		new_test_ext().execute_with(|| {
+			// Account 2 front-runs a lock of account 3 with dust behind the same hashlock
+			assert_ok!(Htlc::lock(Origin::signed(2), 2, hashlock(), 1, 10));
+			assert_ok!(Htlc::lock(Origin::signed(3), 1, hashlock(), 30, 10));
+			assert_ok!(Htlc::claim(Origin::signed(1), 3, SECRET));
+			assert_eq!(Balances::free_balance(&1), 100);
+			assert_eq!(Htlc::contract(2, hashlock()).map(|c| c.amount), Some(1));
+		})
+	}
+
+	#[test]
+	fn revealed_hashlocks_cannot_be_reused() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(Htlc::claim(Origin::signed(2), 1, SECRET));
+			assert!(Htlc::revealed(hashlock()));
+			assert_err!(
+				Htlc::lock(Origin::signed(1), 2, hashlock(), 30, 20),
+				"secret already revealed"
+			);
+		})
+	}
+
+	#[test]
+	fn wrong_secret_cannot_claim() {
+		new_test_ext().execute_with(|| {
+			assert_err!(Htlc::claim(Origin::signed(2), 1, [8; 32]), "no contract for this secret");
+		})
+	}
+
+	#[test]
+	fn claim_in_last_block() {
+		new_test_ext().execute_with(|| {
+			System::set_block_number(10);
+			assert_err!(Htlc::refund(Origin::signed(1), 1, hashlock()), "contract has not expired");
+			assert_ok!(Htlc::claim(Origin::signed(2), 1, SECRET));
+		})
+	}
+
+	#[test]
+	fn no_claim_after_expiry() {
+		new_test_ext().execute_with(|| {
+			System::set_block_number(11);
+			assert_err!(Htlc::claim(Origin::signed(2), 1, SECRET), "contract has expired");
+		})
+	}
+
+	#[test]
+	fn refund_first_block_after_expiry() {
+		new_test_ext().execute_with(|| {
+			System::set_block_number(11);
+			// Anyone can refund, the funds still go to the sender
+			assert_ok!(Htlc::refund(Origin::signed(2), 1, hashlock()));
+			assert_eq!(Balances::free_balance(&1), 100);
+			assert_eq!(Balances::reserved_balance(&1), 0);
+			assert!(event_emitted(RawEvent::Refunded(1, hashlock())));
+		})
+	}
+
+	#[test]
+	fn no_refund_before_expiry() {
+		new_test_ext().execute_with(|| {
+			assert_err!(Htlc::refund(Origin::signed(1), 1, hashlock()), "contract has not expired");
+		})
+	}
+
+	#[test]
+	fn settled_contracts_are_gone() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(Htlc::claim(Origin::signed(2), 1, SECRET));
+			System::set_block_number(11);
+			assert_err!(Htlc::refund(Origin::signed(1), 1, hashlock()), "no such contract");
+			assert_err!(Htlc::claim(Origin::signed(2), 1, SECRET), "no contract for this secret");
+		})
+	}
+
+	#[test]
+	fn hashlock_reusable_after_refund() {
+		new_test_ext().execute_with(|| {
+			System::set_block_number(11);
+			assert_ok!(Htlc::refund(Origin::signed(1), 1, hashlock()));
+			assert_ok!(Htlc::lock(Origin::signed(1), 2, hashlock(), 30, 20));
+		})
+	}
+}
@@ -0,0 +1,461 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! A pallet in which session authorities prove that they are online
+//!
+//! Every session, the offchain worker of each authority signs a heartbeat with the authority's
+//! session key and submits it as an unsigned transaction. Nobody pays a fee for it, so the
+//! pallet's `ValidateUnsigned` implementation decides which heartbeats may enter the pool: only
+//! heartbeats for the current session, signed by a current authority, and at most one per
+//! authority and session. When the session ends, the pallet reports every authority that it has
+//! not heard from.
+//!
+//! This is a much smaller version of Substrate's `pallet-im-online`, which also records the
+//! network state of each authority and reports offline authorities to the offences pallet so
+//! they can be slashed.
+
+use codec::{Decode, Encode};
+use sp_std::prelude::*;
+use sp_runtime::{
+	traits::Member,
+	transaction_validity::{
+		InvalidTransaction, TransactionPriority, TransactionValidity, ValidTransaction,
+	},
+	RuntimeAppPublic, RuntimeDebug,
+};
+use frame_support::{
+	debug, decl_event, decl_module, decl_storage,
+	dispatch::DispatchResult,
+	ensure, Parameter,
+};
+use frame_system::{
+	self as system, ensure_none,
+	offchain::SubmitUnsignedTransaction,
+};
+use pallet_session::{OneSessionHandler, SessionIndex};
+
+/// The key type used for this pallet's session keys.
+pub const KEY_TYPE: sp_application_crypto::KeyTypeId = sp_application_crypto::KeyTypeId(*b"rchb");
+
+/// The target of this pallet's log messages
+const LOG_TARGET: &str = "im-online-lite";
+
+/// The application-specific crypto used to sign heartbeats.
+pub mod crypto {
+	use super::KEY_TYPE;
+	use sp_application_crypto::{app_crypto, sr25519};
+	app_crypto!(sr25519, KEY_TYPE);
+}
+
+/// An authority identifier that a runtime can use for `Trait::AuthorityId`.
+pub type AuthorityId = crypto::Public;
+
+/// The position of an authority in the current session's list of keys
+pub type AuthIndex = u32;
+
+/// The statement an authority signs to prove that it is online
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct Heartbeat<BlockNumber> {
+	/// The block in which the offchain worker created the heartbeat
+	pub block_number: BlockNumber,
+	/// The session the heartbeat is for. A heartbeat is only valid during that session.
+	pub session_index: SessionIndex,
+	/// Which of the session's authorities sent the heartbeat
+	pub authority_index: AuthIndex,
+}
+
+pub trait Trait: system::Trait + pallet_session::Trait {
+	/// The overarching event type.
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The identifier type for an authority, used as this pallet's session key.
+	type AuthorityId: Member + Parameter + RuntimeAppPublic + Default;
+
+	/// The overarching call type, which the offchain worker wraps heartbeats in.
+	type Call: From<Call<Self>>;
+
+	/// Submits heartbeats from the offchain worker as unsigned transactions.
+	type SubmitTransaction: SubmitUnsignedTransaction<Self, <Self as Trait>::Call>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as ImOnlineLite {
+		/// Keys of the authorities for the current session, in session order.
+		Keys get(fn keys): Vec<T::AuthorityId>;
+
+		/// Whether a heartbeat arrived from the authority at the given index in the given session.
+		/// Entries are removed when their session ends.
+		ReceivedHeartbeats get(fn received_heartbeats): map (SessionIndex, AuthIndex) => bool;
+	}
+}
+
+decl_event!(
+	pub enum Event<T> where <T as Trait>::AuthorityId {
+		/// A heartbeat arrived from this authority
+		HeartbeatReceived(AuthorityId),
+		/// Every authority sent a heartbeat during the session that just ended
+		AllGood(SessionIndex),
+		/// These authorities sent no heartbeat during the session that just ended
+		SomeOffline(SessionIndex, Vec<AuthorityId>),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		/// Record a heartbeat. `validate_unsigned` has already checked it, both in the pool and
+		/// when the block was applied. The checks that protect storage are repeated here so the
+		/// call is safe on its own, for example when tests dispatch it directly.
+		fn heartbeat(
+			origin,
+			heartbeat: Heartbeat<T::BlockNumber>,
+			_signature: <T::AuthorityId as RuntimeAppPublic>::Signature,
+		) -> DispatchResult {
+			ensure_none(origin)?;
+
+			let current_session = <pallet_session::Module<T>>::current_index();
+			ensure!(heartbeat.session_index == current_session, "heartbeat for another session");
+			let key = Self::keys()
+				.get(heartbeat.authority_index as usize)
+				.cloned()
+				.ok_or("unknown authority")?;
+			let received_key = (current_session, heartbeat.authority_index);
+			ensure!(!ReceivedHeartbeats::get(received_key), "duplicate heartbeat");
+
+			ReceivedHeartbeats::insert(received_key, true);
+			Self::deposit_event(RawEvent::HeartbeatReceived(key));
+			Ok(())
+		}
+
+		fn offchain_worker(now: T::BlockNumber) {
+			// Only validators have session keys to sign with
+			if sp_io::offchain::is_validator() {
+				debug::RuntimeLogger::init();
+				Self::send_heartbeats(now);
+			}
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// Sign and submit a heartbeat for every local session key that has not been heard from in
+	/// this session yet. The worker runs after every block, so a heartbeat that does not make it
+	/// into a block is simply sent again. Copies already in the pool are rejected by the pool.
+	fn send_heartbeats(now: T::BlockNumber) {
+		let session_index = <pallet_session::Module<T>>::current_index();
+		// The keys of this node that are in the keystore
+		let local_keys = T::AuthorityId::all();
+
+		for (index, key) in Self::keys().into_iter().enumerate() {
+			let authority_index = index as AuthIndex;
+			if !local_keys.contains(&key) || ReceivedHeartbeats::get((session_index, authority_index)) {
+				continue;
+			}
+
+			let heartbeat = Heartbeat { block_number: now, session_index, authority_index };
+			let signature = match key.sign(&heartbeat.encode()) {
+				Some(signature) => signature,
+				None => {
+					log::warn!(target: LOG_TARGET, "could not sign heartbeat for authority {}", authority_index);
+					continue;
+				}
+			};
+
+			let call = Call::heartbeat(heartbeat, signature);
+			if T::SubmitTransaction::submit_unsigned(call).is_err() {
+				// Usually the pool already holds this heartbeat
+				log::debug!(target: LOG_TARGET, "heartbeat for authority {} not submitted", authority_index);
+			}
+		}
+	}
+
+	/// Report the authorities that sent no heartbeat this session, and forget the session's
+	/// heartbeats.
+	fn report_offline() {
+		let session_index = <pallet_session::Module<T>>::current_index();
+		let offline = Self::keys()
+			.into_iter()
+			.enumerate()
+			.filter(|(index, _)| !ReceivedHeartbeats::take((session_index, *index as AuthIndex)))
+			.map(|(_, key)| key)
+			.collect::<Vec<_>>();
+
+		if offline.is_empty() {
+			Self::deposit_event(RawEvent::AllGood(session_index));
+		} else {
+			Self::deposit_event(RawEvent::SomeOffline(session_index, offline));
+		}
+	}
+}
+
+/// The transaction pool asks this before it accepts an unsigned `heartbeat` transaction.
+impl<T: Trait> frame_support::unsigned::ValidateUnsigned for Module<T> {
+	type Call = Call<T>;
+
+	fn validate_unsigned(call: &Self::Call) -> TransactionValidity {
+		let (heartbeat, signature) = match call {
+			Call::heartbeat(heartbeat, signature) => (heartbeat, signature),
+			_ => return InvalidTransaction::Call.into(),
+		};
+
+		// Heartbeats for other sessions are useless, and old ones could be replayed forever
+		let current_session = <pallet_session::Module<T>>::current_index();
+		if heartbeat.session_index != current_session {
+			return InvalidTransaction::Stale.into();
+		}
+		if ReceivedHeartbeats::get((current_session, heartbeat.authority_index)) {
+			return InvalidTransaction::Stale.into();
+		}
+
+		// Without a fee, the signature is the only thing that stops anyone from filling blocks
+		// with heartbeats
+		let key = match Self::keys().get(heartbeat.authority_index as usize) {
+			Some(key) => key.clone(),
+			None => return InvalidTransaction::BadProof.into(),
+		};
+		if !key.verify(&heartbeat.encode(), signature) {
+			return InvalidTransaction::BadProof.into();
+		}
+
+		Ok(ValidTransaction {
+			priority: TransactionPriority::max_value(),
+			requires: vec![],
+			// The pool keeps only one transaction with the same tag, which de-duplicates
+			// heartbeats that differ only in their block number
+			provides: vec![(current_session, heartbeat.authority_index).encode()],
+			// Long enough to reach a block, short enough not to linger past the session
+			longevity: 64,
+			propagate: true,
+		})
+	}
+}
+
+/// `pallet_session` tells this pallet which keys belong to the current authorities, and when a
+/// session is about to end.
+impl<T: Trait> OneSessionHandler<T::AccountId> for Module<T> {
+	type Key = T::AuthorityId;
+
+	fn on_genesis_session<'a, I: 'a>(validators: I)
+		where I: Iterator<Item=(&'a T::AccountId, T::AuthorityId)>
+	{
+		Keys::<T>::put(validators.map(|(_, key)| key).collect::<Vec<_>>());
+	}
+
+	fn on_new_session<'a, I: 'a>(_changed: bool, validators: I, _queued_validators: I)
+		where I: Iterator<Item=(&'a T::AccountId, T::AuthorityId)>
+	{
+		Keys::<T>::put(validators.map(|(_, key)| key).collect::<Vec<_>>());
+	}
+
+	// Called while the ending session is still the current one, so `Keys` still matches the
+	// indices in `ReceivedHeartbeats`
+	fn on_before_session_ending() {
+		Self::report_offline();
+	}
+
+	fn on_disabled(_validator_index: usize) {}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use event_assert::{assert_has_event, assert_last_event};
+	use sp_core::H256;
+	use frame_support::{
+		assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types,
+		unsigned::ValidateUnsigned, weights::Weight,
+	};
+	use sp_runtime::{
+		testing::{Header, TestXt, UintAuthorityId},
+		traits::{BlakeTwo256, ConvertInto, IdentityLookup},
+		Perbill,
+	};
+	use frame_system::{offchain::TransactionSubmitter, RawOrigin};
+	use pallet_session::PeriodicSessions;
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: Weight = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const Period: u64 = 3;
+		pub const Offset: u64 = 0;
+		pub const DisabledValidatorsThreshold: Perbill = Perbill::from_percent(33);
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	impl pallet_session::Trait for TestRuntime {
+		type Event = TestEvent;
+		type ValidatorId = u64;
+		type ValidatorIdOf = ConvertInto;
+		type ShouldEndSession = PeriodicSessions<Period, Offset>;
+		type SessionManager = ();
+		type SessionHandler = (ImOnlineLite,);
+		type Keys = UintAuthorityId;
+		type DisabledValidatorsThreshold = DisabledValidatorsThreshold;
+	}
+
+	mod im_online_lite {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			pallet_session,
+			im_online_lite<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type AuthorityId = UintAuthorityId;
+		type Call = Call<TestRuntime>;
+		type SubmitTransaction = TransactionSubmitter<(), TestRuntime, TestXt<Call<TestRuntime>, ()>>;
+	}
+
+	pub type Session = pallet_session::Module<TestRuntime>;
+	pub type ImOnlineLite = Module<TestRuntime>;
+
+	/// Accounts 1, 2 and 3 are the authorities, with session keys 1, 2 and 3
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		let mut storage = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		pallet_session::GenesisConfig::<TestRuntime> {
+			keys: (1..=3).map(|i| (i, UintAuthorityId(i))).collect(),
+		}
+		.assimilate_storage(&mut storage)
+		.unwrap();
+		storage.into()
+	}
+
+	/// A heartbeat from the authority at `index`, signed with `signer`'s key
+	fn signed_heartbeat(session_index: SessionIndex, index: AuthIndex, signer: u64) -> Call<TestRuntime> {
+		let heartbeat = Heartbeat { block_number: 1, session_index, authority_index: index };
+		let signature = UintAuthorityId(signer).sign(&heartbeat.encode()).unwrap();
+		Call::heartbeat(heartbeat, signature)
+	}
+
+	fn invalid(error: InvalidTransaction) -> TransactionValidity {
+		Err(error.into())
+	}
+
+	/// Validates `call` like the pool would, and dispatches it if it is valid
+	fn submit(call: Call<TestRuntime>) -> TransactionValidity {
+		let validity = ImOnlineLite::validate_unsigned(&call);
+		if validity.is_ok() {
+			if let Call::heartbeat(heartbeat, signature) = call {
+				assert_ok!(ImOnlineLite::heartbeat(RawOrigin::None.into(), heartbeat, signature));
+			}
+		}
+		validity
+	}
+
+	#[test]
+	fn heartbeat_is_recorded() {
+		new_test_ext().execute_with(|| {
+			assert_eq!(ImOnlineLite::keys(), vec![UintAuthorityId(1), UintAuthorityId(2), UintAuthorityId(3)]);
+
+			assert!(submit(signed_heartbeat(0, 1, 2)).is_ok());
+			assert!(ImOnlineLite::received_heartbeats((0, 1)));
+			assert_last_event::<TestRuntime>(RawEvent::HeartbeatReceived(UintAuthorityId(2)));
+		})
+	}
+
+	#[test]
+	fn second_heartbeat_is_rejected() {
+		new_test_ext().execute_with(|| {
+			assert!(submit(signed_heartbeat(0, 0, 1)).is_ok());
+			assert_eq!(submit(signed_heartbeat(0, 0, 1)), invalid(InvalidTransaction::Stale));
+
+			// The call itself repeats the check, for heartbeats that bypass the pool
+			let heartbeat = Heartbeat { block_number: 2, session_index: 0, authority_index: 0 };
+			let signature = UintAuthorityId(1).sign(&heartbeat.encode()).unwrap();
+			assert_err!(
+				ImOnlineLite::heartbeat(RawOrigin::None.into(), heartbeat, signature),
+				"duplicate heartbeat"
+			);
+		})
+	}
+
+	#[test]
+	fn heartbeats_share_a_tag_per_session_and_authority() {
+		new_test_ext().execute_with(|| {
+			let tag = |call| ImOnlineLite::validate_unsigned(&call).unwrap().provides;
+			let mut later = signed_heartbeat(0, 0, 1);
+			if let Call::heartbeat(heartbeat, signature) = &mut later {
+				heartbeat.block_number = 2;
+				*signature = UintAuthorityId(1).sign(&heartbeat.encode()).unwrap();
+			}
+			assert_eq!(tag(signed_heartbeat(0, 0, 1)), tag(later));
+			assert_ne!(tag(signed_heartbeat(0, 0, 1)), tag(signed_heartbeat(0, 1, 2)));
+		})
+	}
+
+	#[test]
+	fn invalid_heartbeats_are_rejected() {
+		new_test_ext().execute_with(|| {
+			// Signed with the key of another authority
+			assert_eq!(submit(signed_heartbeat(0, 0, 2)), invalid(InvalidTransaction::BadProof));
+			// From an index without an authority
+			assert_eq!(submit(signed_heartbeat(0, 3, 4)), invalid(InvalidTransaction::BadProof));
+			// For another session
+			assert_eq!(submit(signed_heartbeat(1, 0, 1)), invalid(InvalidTransaction::Stale));
+			assert!(!ImOnlineLite::received_heartbeats((0, 0)));
+		})
+	}
+
+	#[test]
+	fn only_unsigned_heartbeats() {
+		new_test_ext().execute_with(|| {
+			let heartbeat = Heartbeat { block_number: 1, session_index: 0, authority_index: 0 };
+			let signature = UintAuthorityId(1).sign(&heartbeat.encode()).unwrap();
+			assert!(ImOnlineLite::heartbeat(Origin::signed(1), heartbeat, signature).is_err());
+		})
+	}
+
+	#[test]
+	fn silent_authorities_are_reported() {
+		new_test_ext().execute_with(|| {
+			assert!(submit(signed_heartbeat(0, 0, 1)).is_ok());
+			assert!(submit(signed_heartbeat(0, 2, 3)).is_ok());
+
+			Session::rotate_session();
+			assert_has_event::<TestRuntime>(RawEvent::SomeOffline(0, vec![UintAuthorityId(2)]));
+			// The ended session's heartbeats are forgotten
+			assert!(!ImOnlineLite::received_heartbeats((0, 0)));
+
+			for index in 0..3 {
+				assert!(submit(signed_heartbeat(1, index, index as u64 + 1)).is_ok());
+			}
+			Session::rotate_session();
+			assert_has_event::<TestRuntime>(RawEvent::AllGood(1));
+		})
+	}
+}
@@ -0,0 +1,385 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! The receiving half of a message lane between two chains
+//!
+//! Relayers bring batches of messages from the `outbound-lane` pallet on the other chain,
+//! together with a proof that the other chain really queued them. This pallet accepts messages
+//! strictly in nonce order, so none is skipped or delivered twice, and hands each one to the
+//! runtime's `OnMessage` handler.
+//!
+//! `LastDelivered` is the proof of delivery: relayers prove its value to the sending chain, which
+//! then drops the delivered messages from its queue.
+
+use sp_std::prelude::*;
+use frame_support::{
+	decl_event, decl_module, decl_storage,
+	dispatch::{DispatchResult, PaysFee, WeighData},
+	ensure,
+	traits::Get,
+	weights::{ClassifyDispatch, DispatchClass, Weight},
+	Parameter,
+};
+use frame_system::{self as system, ensure_signed};
+pub use outbound_lane::{Message, MessageNonce};
+
+/// Checks a proof, made on the sending chain, that it queued some messages
+pub trait VerifyMessages<Proof> {
+	fn verify(proof: &Proof) -> Result<Vec<Message>, &'static str>;
+}
+
+/// Acts on delivered messages
+pub trait OnMessage {
+	/// Dispatch a message. Its weight was paid for as declared by the sender.
+	fn on_message(message: &Message) -> DispatchResult;
+}
+
+/// Weighs `receive_messages` as a fixed cost for checking the proof, plus the dispatch weight
+/// the relayer declared. The call checks that the messages do not weigh more than declared.
+pub struct DeclaredWeight(Weight);
+
+impl<P> WeighData<(&P, &Weight)> for DeclaredWeight {
+	fn weigh_data(&self, (_, declared): (&P, &Weight)) -> Weight {
+		self.0.saturating_add(*declared)
+	}
+}
+
+impl<T> ClassifyDispatch<T> for DeclaredWeight {
+	fn classify_dispatch(&self, _: T) -> DispatchClass {
+		Default::default()
+	}
+}
+
+impl PaysFee for DeclaredWeight {
+	fn pays_fee(&self) -> bool {
+		true
+	}
+}
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// Proof of queued messages, as brought by relayers
+	type MessagesProof: Parameter;
+
+	/// Checks message proofs and extracts the messages
+	type VerifyMessages: VerifyMessages<Self::MessagesProof>;
+
+	/// Handles delivered messages
+	type OnMessage: OnMessage;
+
+	/// The most messages a relayer may deliver at once
+	type MaxMessagesPerDelivery: Get<u32>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as InboundLane {
+		/// The nonce of the latest delivered message
+		LastDelivered get(fn last_delivered): MessageNonce;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as system::Trait>::AccountId,
+	{
+		/// A relayer delivered the messages in the given range, inclusive
+		MessagesReceived(MessageNonce, MessageNonce, AccountId),
+		/// A message was dispatched, successfully or not
+		MessageDispatched(MessageNonce, bool),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		const MaxMessagesPerDelivery: u32 = T::MaxMessagesPerDelivery::get();
+
+		/// Deliver the messages in `proof`, which must directly follow those delivered before.
+		/// `dispatch_weight` must cover the declared weight of all of them.
+		#[weight = DeclaredWeight(10_000)]
+		fn receive_messages(origin, proof: T::MessagesProof, dispatch_weight: Weight) -> DispatchResult {
+			let relayer = ensure_signed(origin)?;
+			let messages = T::VerifyMessages::verify(&proof)?;
+			ensure!(!messages.is_empty(), "no messages");
+			ensure!(
+				messages.len() as u32 <= T::MaxMessagesPerDelivery::get(),
+				"too many messages"
+			);
+
+			let first = Self::last_delivered() + 1;
+			let mut total_weight: Weight = 0;
+			for (expected, message) in (first..).zip(messages.iter()) {
+				ensure!(message.nonce == expected, "messages out of order");
+				total_weight = total_weight.saturating_add(message.weight);
+			}
+			ensure!(total_weight <= dispatch_weight, "dispatch weight too low");
+
+			// A failed message is still delivered, or it would block the lane forever
+			for message in messages.iter() {
+				let ok = T::OnMessage::on_message(message).is_ok();
+				Self::deposit_event(RawEvent::MessageDispatched(message.nonce, ok));
+			}
+			let last = first + messages.len() as MessageNonce - 1;
+			LastDelivered::put(last);
+
+			Self::deposit_event(RawEvent::MessagesReceived(first, last, relayer));
+			Ok(())
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use std::cell::RefCell;
+	use sp_core::H256;
+	use frame_support::{
+		assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types,
+		weights::GetDispatchInfo,
+	};
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup},
+		Perbill,
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const MaxPendingMessages: u64 = 10;
+		pub const MaxPayloadLength: u32 = 32;
+		pub const MaxMessagesPerDelivery: u32 = 3;
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	// Both ends of the lane live in this runtime, so a relayer only has to copy messages and
+	// nonces across. Proofs are taken at face value.
+	pub struct Trusted;
+	impl VerifyMessages<Vec<Message>> for Trusted {
+		fn verify(proof: &Vec<Message>) -> Result<Vec<Message>, &'static str> {
+			Ok(proof.clone())
+		}
+	}
+	impl outbound_lane::VerifyDelivery<MessageNonce> for Trusted {
+		fn verify(proof: &MessageNonce) -> Result<MessageNonce, &'static str> {
+			Ok(*proof)
+		}
+	}
+
+	thread_local! {
+		static DISPATCHED: RefCell<Vec<Vec<u8>>> = RefCell::new(Vec::new());
+	}
+
+	/// Records payloads, and fails on the payload `fail`
+	pub struct RecordMessages;
+	impl OnMessage for RecordMessages {
+		fn on_message(message: &Message) -> DispatchResult {
+			ensure!(message.payload != b"fail".to_vec(), "failed");
+			DISPATCHED.with(|d| d.borrow_mut().push(message.payload.clone()));
+			Ok(())
+		}
+	}
+
+	mod inbound_lane {
+		pub use crate::Event;
+	}
+
+	mod outbound_lane_events {
+		pub use outbound_lane::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			inbound_lane<T>,
+			outbound_lane_events<T>,
+		}
+	}
+
+	impl outbound_lane::Trait for TestRuntime {
+		type Event = TestEvent;
+		type DeliveryProof = MessageNonce;
+		type VerifyDelivery = Trusted;
+		type MaxPendingMessages = MaxPendingMessages;
+		type MaxPayloadLength = MaxPayloadLength;
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type MessagesProof = Vec<Message>;
+		type VerifyMessages = Trusted;
+		type OnMessage = RecordMessages;
+		type MaxMessagesPerDelivery = MaxMessagesPerDelivery;
+	}
+
+	pub type System = system::Module<TestRuntime>;
+	pub type OutboundLane = outbound_lane::Module<TestRuntime>;
+	pub type InboundLane = Module<TestRuntime>;
+
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		DISPATCHED.with(|d| d.borrow_mut().clear());
+		let t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		t.into()
+	}
+
+	fn event_emitted(event: RawEvent<u64>) -> bool {
+		let expected = TestEvent::inbound_lane(event);
+		System::events().iter().any(|a| a.event == expected)
+	}
+
+	fn dispatched() -> Vec<Vec<u8>> {
+		DISPATCHED.with(|d| d.borrow().clone())
+	}
+
+	fn send(payload: &[u8]) {
+		assert_ok!(OutboundLane::send_message(Origin::signed(1), payload.to_vec(), 100));
+	}
+
+	/// The undelivered messages in the outbound queue, at most `MaxMessagesPerDelivery`
+	fn pending() -> Vec<Message> {
+		let first = OutboundLane::latest_delivered() + 1;
+		(first..=OutboundLane::latest_generated())
+			.take(3)
+			.filter_map(OutboundLane::message)
+			.collect()
+	}
+
+	/// What a relayer does: deliver pending messages, then prove their delivery
+	fn relay() {
+		let messages = pending();
+		let weight = messages.iter().map(|m| m.weight).sum();
+		assert_ok!(InboundLane::receive_messages(Origin::signed(9), messages, weight));
+		assert_ok!(OutboundLane::receive_delivery_proof(
+			Origin::signed(9),
+			InboundLane::last_delivered()
+		));
+	}
+
+	#[test]
+	fn messages_travel_the_lane() {
+		new_test_ext().execute_with(|| {
+			send(b"one");
+			send(b"two");
+			relay();
+
+			assert_eq!(dispatched(), vec![b"one".to_vec(), b"two".to_vec()]);
+			assert_eq!(InboundLane::last_delivered(), 2);
+			assert!(event_emitted(RawEvent::MessagesReceived(1, 2, 9)));
+			// The sending side has forgotten the delivered messages
+			assert_eq!(OutboundLane::latest_delivered(), 2);
+			assert_eq!(OutboundLane::message(1), None);
+		})
+	}
+
+	#[test]
+	fn large_backlogs_take_several_deliveries() {
+		new_test_ext().execute_with(|| {
+			for payload in [b"1", b"2", b"3", b"4", b"5"].iter() {
+				send(*payload);
+			}
+			relay();
+			assert_eq!(InboundLane::last_delivered(), 3);
+			relay();
+			assert_eq!(InboundLane::last_delivered(), 5);
+			assert_eq!(dispatched().len(), 5);
+			assert!(pending().is_empty());
+		})
+	}
+
+	#[test]
+	fn messages_are_delivered_in_order_once() {
+		new_test_ext().execute_with(|| {
+			send(b"one");
+			send(b"two");
+			let messages = pending();
+
+			// Skipping the first message
+			assert_err!(
+				InboundLane::receive_messages(Origin::signed(9), messages[1..].to_vec(), 100),
+				"messages out of order"
+			);
+
+			assert_ok!(InboundLane::receive_messages(Origin::signed(9), messages.clone(), 200));
+			// Replaying the same batch
+			assert_err!(
+				InboundLane::receive_messages(Origin::signed(9), messages, 200),
+				"messages out of order"
+			);
+			assert_eq!(dispatched().len(), 2);
+		})
+	}
+
+	#[test]
+	fn deliveries_are_bounded() {
+		new_test_ext().execute_with(|| {
+			for _ in 0..4 {
+				send(b"x");
+			}
+			let messages: Vec<_> = (1..=4).filter_map(OutboundLane::message).collect();
+			assert_err!(
+				InboundLane::receive_messages(Origin::signed(9), messages, 400),
+				"too many messages"
+			);
+			assert_err!(
+				InboundLane::receive_messages(Origin::signed(9), vec![], 0),
+				"no messages"
+			);
+			assert_err!(
+				InboundLane::receive_messages(Origin::signed(9), pending(), 299),
+				"dispatch weight too low"
+			);
+		})
+	}
+
+	#[test]
+	fn failed_dispatch_does_not_block_lane() {
+		new_test_ext().execute_with(|| {
+			send(b"fail");
+			send(b"ok");
+			relay();
+
+			assert!(event_emitted(RawEvent::MessageDispatched(1, false)));
+			assert!(event_emitted(RawEvent::MessageDispatched(2, true)));
+			assert_eq!(dispatched(), vec![b"ok".to_vec()]);
+			assert_eq!(InboundLane::last_delivered(), 2);
+		})
+	}
+
+	#[test]
+	fn weight_includes_declared_dispatch_weight() {
+		let call = Call::<TestRuntime>::receive_messages(vec![], 500);
+		assert_eq!(call.get_dispatch_info().weight, 10_500);
+	}
+}
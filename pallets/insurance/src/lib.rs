@@ -0,0 +1,584 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! An insurance pool, whose claims are judged by an adjudicator
+//!
+//! Anyone can `fund` the pool with capital. An account can `buy_cover` of an amount for
+//! `CoverPeriod` blocks, paying `PremiumRate` of it into the pool. While its policy lasts, it can
+//! `submit_claim`s of up to the cover it has left, with the hash of off-chain evidence, and
+//! reserves `ClaimDeposit` for each. `AdjudicatorOrigin` approves or rejects each claim. An
+//! approved claim is paid from the pool and its deposit returned, while a rejected claim's deposit
+//! goes to the pool, and its amount back to the policy's cover.
+//!
+//! The pool is a fractional reserve: it doesn't hold all the cover it sold, as not every policy
+//! will claim. Its liabilities are the cover its policies have left, the claims that wait for a
+//! ruling, and the approved claims it still owes. Cover is only sold while the pool holds at least
+//! `ReserveRatio` of its liabilities, including the new cover.
+//!
+//! Claims can still exceed the pool. An approved claim is then paid what the pool holds, and the
+//! rest is owed. While the pool owes anything it is insolvent, and sells no cover. Once it is
+//! funded again, anyone can `settle` the claims it owes.
+
+use codec::{Decode, Encode};
+use sp_std::prelude::*;
+use sp_runtime::{
+	traits::{AccountIdConversion, Saturating, Zero},
+	ModuleId, Perbill, RuntimeDebug,
+};
+use frame_support::{
+	decl_event, decl_module, decl_storage,
+	dispatch::DispatchResult,
+	ensure,
+	traits::{Currency, EnsureOrigin, ExistenceRequirement::AllowDeath, Get, ReservableCurrency},
+};
+use frame_system::{self as system, ensure_signed};
+
+pub type ClaimIndex = u32;
+
+type BalanceOf<T> = <<T as Trait>::Currency as Currency<<T as system::Trait>::AccountId>>::Balance;
+type PolicyOf<T> = Policy<BalanceOf<T>, <T as system::Trait>::BlockNumber>;
+type ClaimOf<T> = Claim<<T as system::Trait>::AccountId, BalanceOf<T>, <T as system::Trait>::Hash>;
+
+/// Hardcoded pallet ID; used to create the account that holds the pool
+/// Must be exactly 8 characters long
+const PALLET_ID: ModuleId = ModuleId(*b"insurpol");
+
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct Policy<Balance, BlockNumber> {
+	/// The most that may be claimed in total
+	pub coverage: Balance,
+	/// What has been claimed so far, excluding rejected claims
+	pub claimed: Balance,
+	/// The block whose `on_finalize` ends the policy
+	pub expires: BlockNumber,
+}
+
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub enum ClaimStatus<Balance> {
+	/// Waiting for the adjudicator
+	Pending,
+	/// Approved, but the pool could only pay this much so far
+	Approved { paid: Balance },
+}
+
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct Claim<AccountId, Balance, Hash> {
+	pub claimant: AccountId,
+	pub amount: Balance,
+	/// The hash of the evidence, which the adjudicator gets off-chain
+	pub evidence: Hash,
+	pub status: ClaimStatus<Balance>,
+}
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The currency of premiums, payouts and claim deposits
+	type Currency: ReservableCurrency<Self::AccountId>;
+
+	/// The origin that approves and rejects claims
+	type AdjudicatorOrigin: EnsureOrigin<Self::Origin>;
+
+	/// The premium, as a fraction of the cover bought
+	type PremiumRate: Get<Perbill>;
+
+	/// How many blocks a policy lasts
+	type CoverPeriod: Get<Self::BlockNumber>;
+
+	/// The fraction of its liabilities the pool must hold to sell cover
+	type ReserveRatio: Get<Perbill>;
+
+	/// Reserved with each claim, and lost to the pool if the claim is rejected
+	type ClaimDeposit: Get<BalanceOf<Self>>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as Insurance {
+		/// The policy of each insured account, until it expires
+		Policies get(fn policy): map T::AccountId => Option<PolicyOf<T>>;
+
+		/// Accounts whose policies expire in each block
+		Expiring get(fn expiring): map T::BlockNumber => Vec<T::AccountId>;
+
+		/// Claims that are pending, or approved but not paid in full
+		Claims get(fn claim): map ClaimIndex => Option<ClaimOf<T>>;
+
+		/// Number of claims ever submitted; the next claim's index
+		ClaimCount get(fn claim_count): ClaimIndex;
+
+		/// The cover that active policies have not claimed
+		Exposure get(fn exposure): BalanceOf<T>;
+
+		/// The sum of pending claims
+		PendingClaims get(fn pending_claims): BalanceOf<T>;
+
+		/// What approved claims are still owed. The pool is insolvent while this is not zero.
+		Owed get(fn owed): BalanceOf<T>;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as system::Trait>::AccountId,
+		Balance = BalanceOf<T>,
+		BlockNumber = <T as system::Trait>::BlockNumber,
+		Hash = <T as system::Trait>::Hash,
+	{
+		/// Capital was added to the pool
+		Funded(AccountId, Balance),
+		/// An account bought cover, for a premium, until a block
+		CoverBought(AccountId, Balance, Balance, BlockNumber),
+		/// A policy expired
+		CoverExpired(AccountId),
+		/// A claim was submitted for an amount, with the hash of its evidence
+		ClaimSubmitted(ClaimIndex, AccountId, Balance, Hash),
+		/// A claim was approved: paid now, still owed
+		ClaimApproved(ClaimIndex, Balance, Balance),
+		/// A claim was rejected, and its deposit went to the pool
+		ClaimRejected(ClaimIndex),
+		/// More of an approved claim was paid: paid now, still owed
+		ClaimSettled(ClaimIndex, Balance, Balance),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		const PremiumRate: Perbill = T::PremiumRate::get();
+
+		const CoverPeriod: T::BlockNumber = T::CoverPeriod::get();
+
+		const ReserveRatio: Perbill = T::ReserveRatio::get();
+
+		const ClaimDeposit: BalanceOf<T> = T::ClaimDeposit::get();
+
+		/// Add capital to the pool. It can't be withdrawn.
+		fn fund(origin, amount: BalanceOf<T>) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			T::Currency::transfer(&who, &Self::account_id(), amount, AllowDeath)?;
+			Self::deposit_event(RawEvent::Funded(who, amount));
+			Ok(())
+		}
+
+		/// Buy cover for `CoverPeriod` blocks, paying the premium into the pool
+		fn buy_cover(origin, coverage: BalanceOf<T>) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			ensure!(!coverage.is_zero(), "must buy some cover");
+			ensure!(!<Policies<T>>::exists(&who), "already insured");
+			ensure!(Self::owed().is_zero(), "pool insolvent");
+
+			let premium = T::PremiumRate::get() * coverage;
+			let liabilities = Self::liabilities().saturating_add(coverage);
+			let reserves = Self::reserves().saturating_add(premium);
+			ensure!(reserves >= T::ReserveRatio::get() * liabilities, "not enough reserves");
+			T::Currency::transfer(&who, &Self::account_id(), premium, AllowDeath)?;
+
+			let expires = <system::Module<T>>::block_number() + T::CoverPeriod::get();
+			let policy = Policy { coverage, claimed: Zero::zero(), expires };
+			<Policies<T>>::insert(&who, policy);
+			<Expiring<T>>::mutate(expires, |accounts| accounts.push(who.clone()));
+			<Exposure<T>>::mutate(|exposure| *exposure += coverage);
+			Self::deposit_event(RawEvent::CoverBought(who, coverage, premium, expires));
+			Ok(())
+		}
+
+		/// Claim part of the caller's cover, reserving `ClaimDeposit`
+		fn submit_claim(origin, amount: BalanceOf<T>, evidence: T::Hash) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let mut policy = Self::policy(&who).ok_or("no active policy")?;
+			ensure!(!amount.is_zero(), "must claim something");
+			ensure!(amount <= policy.coverage - policy.claimed, "more than the remaining cover");
+			T::Currency::reserve(&who, T::ClaimDeposit::get())
+				.map_err(|_| "cannot afford the claim deposit")?;
+
+			let index = Self::claim_count();
+			let claim = Claim {
+				claimant: who.clone(),
+				amount,
+				evidence,
+				status: ClaimStatus::Pending,
+			};
+			<Claims<T>>::insert(index, claim);
+			ClaimCount::put(index + 1);
+			policy.claimed += amount;
+			<Policies<T>>::insert(&who, policy);
+			<Exposure<T>>::mutate(|exposure| *exposure -= amount);
+			<PendingClaims<T>>::mutate(|pending| *pending += amount);
+			Self::deposit_event(RawEvent::ClaimSubmitted(index, who, amount, evidence));
+			Ok(())
+		}
+
+		/// Rule on a pending claim. An approved claim is paid as far as the pool can.
+		fn adjudicate(origin, index: ClaimIndex, approve: bool) -> DispatchResult {
+			T::AdjudicatorOrigin::ensure_origin(origin)?;
+			let mut claim = Self::claim(index).ok_or("no such claim")?;
+			ensure!(claim.status == ClaimStatus::Pending, "claim already approved");
+			<PendingClaims<T>>::mutate(|pending| *pending -= claim.amount);
+
+			let deposit = T::ClaimDeposit::get();
+			if approve {
+				T::Currency::unreserve(&claim.claimant, deposit);
+				let paid = Self::pay(&claim.claimant, claim.amount)?;
+				let owed = claim.amount - paid;
+				if owed.is_zero() {
+					<Claims<T>>::remove(index);
+				} else {
+					<Owed<T>>::mutate(|total| *total += owed);
+					claim.status = ClaimStatus::Approved { paid };
+					<Claims<T>>::insert(index, claim);
+				}
+				Self::deposit_event(RawEvent::ClaimApproved(index, paid, owed));
+			} else {
+				let (forfeit, _) = T::Currency::slash_reserved(&claim.claimant, deposit);
+				T::Currency::resolve_creating(&Self::account_id(), forfeit);
+				// The amount is cover again, unless the policy expired in the meantime
+				if let Some(mut policy) = Self::policy(&claim.claimant) {
+					policy.claimed -= claim.amount;
+					<Policies<T>>::insert(&claim.claimant, policy);
+					<Exposure<T>>::mutate(|exposure| *exposure += claim.amount);
+				}
+				<Claims<T>>::remove(index);
+				Self::deposit_event(RawEvent::ClaimRejected(index));
+			}
+			Ok(())
+		}
+
+		/// Pay an approved claim more of what it is owed, as far as the pool can
+		fn settle(origin, index: ClaimIndex) -> DispatchResult {
+			let _ = ensure_signed(origin)?;
+			let mut claim = Self::claim(index).ok_or("no such claim")?;
+			let already_paid = match claim.status {
+				ClaimStatus::Approved { paid } => paid,
+				ClaimStatus::Pending => return Err("claim not approved".into()),
+			};
+			ensure!(!Self::reserves().is_zero(), "pool is empty");
+
+			let paid = Self::pay(&claim.claimant, claim.amount - already_paid)?;
+			<Owed<T>>::mutate(|total| *total -= paid);
+			let total_paid = already_paid + paid;
+			let owed = claim.amount - total_paid;
+			if owed.is_zero() {
+				<Claims<T>>::remove(index);
+			} else {
+				claim.status = ClaimStatus::Approved { paid: total_paid };
+				<Claims<T>>::insert(index, claim);
+			}
+			Self::deposit_event(RawEvent::ClaimSettled(index, paid, owed));
+			Ok(())
+		}
+
+		fn on_finalize(n: T::BlockNumber) {
+			for who in <Expiring<T>>::take(n) {
+				if let Some(policy) = Self::policy(&who) {
+					if policy.expires == n {
+						<Policies<T>>::remove(&who);
+						<Exposure<T>>::mutate(|exposure| {
+							*exposure -= policy.coverage - policy.claimed
+						});
+						Self::deposit_event(RawEvent::CoverExpired(who));
+					}
+				}
+			}
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// The account ID that holds the pool
+	pub fn account_id() -> T::AccountId {
+		PALLET_ID.into_account()
+	}
+
+	/// What the pool holds
+	pub fn reserves() -> BalanceOf<T> {
+		T::Currency::free_balance(&Self::account_id())
+	}
+
+	/// What the pool may have to pay: unclaimed cover, pending claims, and what it owes
+	pub fn liabilities() -> BalanceOf<T> {
+		Self::exposure()
+			.saturating_add(Self::pending_claims())
+			.saturating_add(Self::owed())
+	}
+
+	/// Pay up to `amount` from the pool, as much as it holds. Returns what was paid.
+	fn pay(who: &T::AccountId, amount: BalanceOf<T>) -> Result<BalanceOf<T>, &'static str> {
+		let paid = amount.min(Self::reserves());
+		T::Currency::transfer(&Self::account_id(), who, paid, AllowDeath)?;
+		Ok(paid)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use event_assert::{assert_has_event, assert_last_event};
+
+	use sp_core::H256;
+	use frame_support::{
+		assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types,
+	};
+	use frame_system::{EnsureRoot, RawOrigin};
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup, OnFinalize},
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const ExistentialDeposit: u64 = 0;
+		pub const TransferFee: u64 = 0;
+		pub const CreationFee: u64 = 0;
+
+		pub const PremiumRate: Perbill = Perbill::from_percent(10);
+		pub const CoverPeriod: u64 = 10;
+		pub const ReserveRatio: Perbill = Perbill::from_percent(50);
+		pub const ClaimDeposit: u64 = 5;
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	impl balances::Trait for TestRuntime {
+		type Balance = u64;
+		type OnFreeBalanceZero = ();
+		type OnNewAccount = ();
+		type Event = TestEvent;
+		type TransferPayment = ();
+		type DustRemoval = ();
+		type ExistentialDeposit = ExistentialDeposit;
+		type TransferFee = TransferFee;
+		type CreationFee = CreationFee;
+	}
+
+	mod insurance {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			balances<T>,
+			insurance<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type Currency = balances::Module<Self>;
+		type AdjudicatorOrigin = EnsureRoot<u64>;
+		type PremiumRate = PremiumRate;
+		type CoverPeriod = CoverPeriod;
+		type ReserveRatio = ReserveRatio;
+		type ClaimDeposit = ClaimDeposit;
+	}
+
+	pub type System = system::Module<TestRuntime>;
+	pub type Balances = balances::Module<TestRuntime>;
+	pub type Insurance = Module<TestRuntime>;
+
+	/// Accounts 1 to 4 have 10_000 each, and account 1 funded the pool with `capital` in block 1
+	fn new_test_ext(capital: u64) -> sp_io::TestExternalities {
+		let mut t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		balances::GenesisConfig::<TestRuntime> {
+			balances: (1..=4).map(|who| (who, 10_000)).collect(),
+			vesting: vec![],
+		}
+		.assimilate_storage(&mut t)
+		.unwrap();
+		let mut ext: sp_io::TestExternalities = t.into();
+		ext.execute_with(|| {
+			System::set_block_number(1);
+			assert_ok!(Insurance::fund(Origin::signed(1), capital));
+		});
+		ext
+	}
+
+	fn evidence() -> H256 {
+		H256::repeat_byte(7)
+	}
+
+	fn claim(who: u64, amount: u64) -> ClaimIndex {
+		assert_ok!(Insurance::submit_claim(Origin::signed(who), amount, evidence()));
+		Insurance::claim_count() - 1
+	}
+
+	fn adjudicate(index: ClaimIndex, approve: bool) {
+		assert_ok!(Insurance::adjudicate(RawOrigin::Root.into(), index, approve));
+	}
+
+	#[test]
+	fn cover_is_sold_while_the_reserves_allow() {
+		new_test_ext(1_000).execute_with(|| {
+			assert_has_event::<TestRuntime>(RawEvent::Funded(1, 1_000));
+			assert_ok!(Insurance::buy_cover(Origin::signed(2), 1_000));
+			assert_eq!(Balances::free_balance(&2), 10_000 - 100);
+			assert_eq!(Insurance::policy(&2).unwrap().expires, 11);
+			assert_last_event::<TestRuntime>(RawEvent::CoverBought(2, 1_000, 100, 11));
+			assert_err!(Insurance::buy_cover(Origin::signed(2), 1), "already insured");
+
+			// The pool holds 1250, which is half of 2500
+			assert_ok!(Insurance::buy_cover(Origin::signed(3), 1_500));
+			assert_eq!(Insurance::reserves(), 1_250);
+			assert_eq!(Insurance::liabilities(), 2_500);
+
+			// 1260 is not half of 2600
+			assert_err!(Insurance::buy_cover(Origin::signed(4), 100), "not enough reserves");
+			assert_eq!(Balances::free_balance(&4), 10_000);
+			assert_ok!(Insurance::fund(Origin::signed(1), 40));
+			assert_ok!(Insurance::buy_cover(Origin::signed(4), 100));
+		})
+	}
+
+	#[test]
+	fn approved_claims_are_paid() {
+		new_test_ext(1_000).execute_with(|| {
+			assert_ok!(Insurance::buy_cover(Origin::signed(2), 1_000));
+			let index = claim(2, 400);
+			assert_last_event::<TestRuntime>(RawEvent::ClaimSubmitted(index, 2, 400, evidence()));
+			assert_eq!(Balances::reserved_balance(&2), 5);
+			assert_eq!(Insurance::claim(index).unwrap().evidence, evidence());
+			assert_eq!(Insurance::pending_claims(), 400);
+			assert_eq!(Insurance::exposure(), 600);
+			assert_err!(
+				Insurance::submit_claim(Origin::signed(2), 601, evidence()),
+				"more than the remaining cover"
+			);
+
+			assert!(Insurance::adjudicate(Origin::signed(2), index, true).is_err());
+			adjudicate(index, true);
+			assert_eq!(Balances::free_balance(&2), 10_000 - 100 + 400);
+			assert_last_event::<TestRuntime>(RawEvent::ClaimApproved(index, 400, 0));
+			assert_eq!(Balances::reserved_balance(&2), 0);
+			assert_eq!(Insurance::reserves(), 1_100 - 400);
+			assert_eq!(Insurance::claim(index), None);
+			assert_eq!(Insurance::liabilities(), 600);
+			assert_err!(
+				Insurance::adjudicate(RawOrigin::Root.into(), index, true),
+				"no such claim"
+			);
+		})
+	}
+
+	#[test]
+	fn rejected_claims_forfeit_their_deposit() {
+		new_test_ext(1_000).execute_with(|| {
+			assert_ok!(Insurance::buy_cover(Origin::signed(3), 500));
+			let index = claim(3, 500);
+			assert_eq!(Insurance::exposure(), 0);
+			adjudicate(index, false);
+			assert_last_event::<TestRuntime>(RawEvent::ClaimRejected(index));
+
+			assert_eq!(Balances::free_balance(&3), 10_000 - 50 - 5);
+			assert_eq!(Balances::reserved_balance(&3), 0);
+			assert_eq!(Insurance::reserves(), 1_000 + 50 + 5);
+			// The cover can be claimed again
+			assert_eq!(Insurance::policy(&3).unwrap().claimed, 0);
+			assert_eq!(Insurance::exposure(), 500);
+			assert_eq!(Insurance::pending_claims(), 0);
+			claim(3, 500);
+		})
+	}
+
+	#[test]
+	fn an_insolvent_pool_owes_the_rest() {
+		new_test_ext(100).execute_with(|| {
+			// 120 is more than half of 200
+			assert_ok!(Insurance::buy_cover(Origin::signed(2), 200));
+			let index = claim(2, 200);
+			adjudicate(index, true);
+
+			// The pool paid all it had
+			assert_eq!(Balances::free_balance(&2), 10_000 - 20 + 120);
+			assert_eq!(Insurance::reserves(), 0);
+			assert_eq!(Insurance::owed(), 80);
+			assert_last_event::<TestRuntime>(RawEvent::ClaimApproved(index, 120, 80));
+			assert_eq!(
+				Insurance::claim(index).unwrap().status,
+				ClaimStatus::Approved { paid: 120 }
+			);
+			assert_err!(Insurance::buy_cover(Origin::signed(3), 100), "pool insolvent");
+			assert_err!(Insurance::settle(Origin::signed(3), index), "pool is empty");
+
+			// Anyone can settle once there are funds again
+			assert_ok!(Insurance::fund(Origin::signed(1), 50));
+			assert_ok!(Insurance::settle(Origin::signed(3), index));
+			assert_eq!(Insurance::owed(), 30);
+			assert_last_event::<TestRuntime>(RawEvent::ClaimSettled(index, 50, 30));
+			assert_ok!(Insurance::fund(Origin::signed(1), 100));
+			assert_ok!(Insurance::settle(Origin::signed(3), index));
+			assert_eq!(Balances::free_balance(&2), 10_000 - 20 + 200);
+			assert_last_event::<TestRuntime>(RawEvent::ClaimSettled(index, 30, 0));
+			assert_eq!(Insurance::owed(), 0);
+			assert_eq!(Insurance::reserves(), 70);
+			assert_eq!(Insurance::claim(index), None);
+
+			// Solvent again
+			assert_ok!(Insurance::buy_cover(Origin::signed(3), 100));
+		})
+	}
+
+	#[test]
+	fn pending_claims_cannot_be_settled() {
+		new_test_ext(1_000).execute_with(|| {
+			assert_ok!(Insurance::buy_cover(Origin::signed(2), 100));
+			let index = claim(2, 100);
+			assert_err!(Insurance::settle(Origin::signed(2), index), "claim not approved");
+			adjudicate(index, true);
+			assert_err!(Insurance::settle(Origin::signed(2), index), "no such claim");
+		})
+	}
+
+	#[test]
+	fn policies_expire() {
+		new_test_ext(1_000).execute_with(|| {
+			assert_ok!(Insurance::buy_cover(Origin::signed(2), 300));
+			let index = claim(2, 100);
+			Insurance::on_finalize(10);
+			assert!(Insurance::policy(&2).is_some());
+
+			Insurance::on_finalize(11);
+			assert_eq!(Insurance::policy(&2), None);
+			assert_last_event::<TestRuntime>(RawEvent::CoverExpired(2));
+			assert_eq!(Insurance::exposure(), 0);
+			assert_err!(
+				Insurance::submit_claim(Origin::signed(2), 100, evidence()),
+				"no active policy"
+			);
+
+			// A claim made in time is still judged, but a rejected one doesn't become cover
+			adjudicate(index, false);
+			assert_eq!(Insurance::liabilities(), 0);
+			assert_ok!(Insurance::buy_cover(Origin::signed(2), 300));
+		})
+	}
+}
@@ -0,0 +1,16 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use codec::Codec;
+use sp_std::vec::Vec;
+
+// The history is read through a runtime API rather than raw storage queries, so clients need not
+// know how the pallet lays out its storage, nor which instance of it they are talking to.
+sp_api::decl_runtime_apis! {
+	pub trait LastCallerApi<AccountId, BlockNumber> where
+		AccountId: Codec,
+		BlockNumber: Codec,
+	{
+		/// The most recent calls of `who`, oldest first, as (block number, extrinsic index)
+		fn history(who: AccountId) -> Vec<(BlockNumber, u32)>;
+	}
+}
@@ -1,7 +1,8 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 
 /// An example instantiable pallet (without default instance)
-use support::{decl_event, decl_module, decl_storage, dispatch::DispatchResult, StorageValue};
+use rstd::prelude::*;
+use support::{decl_event, decl_module, decl_storage, dispatch::DispatchResult, traits::Get, StorageMap, StorageValue};
 use system::{self, ensure_signed};
 
 // The pallet's configuration trait takes an instance as a type parameter. The instance type is
@@ -9,6 +10,9 @@ use system::{self, ensure_signed};
 pub trait Trait<I: Instance>: system::Trait {
     // The ubiquitous event type's From bound needs updated to support the instance.
     type Event: From<Event<Self, I>> + Into<<Self as system::Trait>::Event>;
+
+    // The number of calls to remember per account. Older calls are forgotten first.
+    type MaxHistory: Get<u32>;
 }
 
 // It is necessary for instantiable pallets to call `decl_storage!` even if no storage items
@@ -20,6 +24,10 @@ decl_storage! {
         // A single storage item that keeps track of
         // which account last called its only dispatchable call.
         Caller: T::AccountId;
+
+        // The most recent calls of each account, oldest first, as (block number, extrinsic index).
+        // Bounded by `MaxHistory`, so the entry of a busy account cannot grow forever.
+        History get(fn history): map T::AccountId => Vec<(T::BlockNumber, u32)>;
     }
 }
 
@@ -38,6 +46,8 @@ decl_module! {
     pub struct Module<T: Trait<I>, I: Instance> for enum Call where origin: T::Origin {
         fn deposit_event() = default;
 
+        const MaxHistory: u32 = T::MaxHistory::get();
+
         // The only dispatchable call, updates the single storage item,
         // and emits an event.
         fn call(origin) -> DispatchResult {
@@ -46,8 +56,144 @@ decl_module! {
             // When writing to storage, we supply, not only a configuration T, but also an
             // instance, I.
             <Caller<T, I>>::put(&caller);
+            Self::record_call(&caller);
             Self::deposit_event(RawEvent::Called(caller));
             Ok(())
         }
     }
 }
+
+impl<T: Trait<I>, I: Instance> Module<T, I> {
+    // Add the current call to the caller's history, forgetting the oldest call when it is full
+    fn record_call(caller: &T::AccountId) {
+        let now = <system::Module<T>>::block_number();
+        let index = <system::Module<T>>::extrinsic_index().unwrap_or_default();
+        <History<T, I>>::mutate(caller, |history| {
+            history.push((now, index));
+            // The history was at most `MaxHistory` long before the push, so one removal is enough
+            if history.len() as u32 > T::MaxHistory::get() {
+                history.remove(0);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use primitives::H256;
+    use runtime_primitives::{
+        testing::Header,
+        traits::{BlakeTwo256, IdentityLookup},
+        Perbill,
+    };
+    use support::{assert_ok, impl_outer_event, impl_outer_origin, parameter_types};
+
+    impl_outer_origin! {
+        pub enum Origin for TestRuntime {}
+    }
+
+    // Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+    #[derive(Clone, PartialEq, Eq, Debug)]
+    pub struct TestRuntime;
+    parameter_types! {
+        pub const BlockHashCount: u64 = 250;
+        pub const MaximumBlockWeight: u32 = 1024;
+        pub const MaximumBlockLength: u32 = 2 * 1024;
+        pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+        pub const MaxHistory: u32 = 3;
+        pub const LongHistory: u32 = 10;
+    }
+    impl system::Trait for TestRuntime {
+        type Origin = Origin;
+        type Index = u64;
+        type Call = ();
+        type BlockNumber = u64;
+        type Hash = H256;
+        type Hashing = BlakeTwo256;
+        type AccountId = u64;
+        type Lookup = IdentityLookup<Self::AccountId>;
+        type Header = Header;
+        type Event = TestEvent;
+        type BlockHashCount = BlockHashCount;
+        type MaximumBlockWeight = MaximumBlockWeight;
+        type MaximumBlockLength = MaximumBlockLength;
+        type AvailableBlockRatio = AvailableBlockRatio;
+        type Version = ();
+        type ModuleToIndex = ();
+    }
+
+    mod last_caller {
+        pub use crate::Event;
+    }
+
+    impl_outer_event! {
+        pub enum TestEvent for TestRuntime {
+            last_caller<T, I>,
+        }
+    }
+
+    impl Trait<Instance1> for TestRuntime {
+        type Event = TestEvent;
+        type MaxHistory = MaxHistory;
+    }
+
+    impl Trait<Instance2> for TestRuntime {
+        type Event = TestEvent;
+        type MaxHistory = LongHistory;
+    }
+
+    pub type System = system::Module<TestRuntime>;
+    pub type LastCaller1 = Module<TestRuntime, Instance1>;
+    pub type LastCaller2 = Module<TestRuntime, Instance2>;
+
+    pub fn new_test_ext() -> runtime_io::TestExternalities {
+        let t = system::GenesisConfig::default()
+            .build_storage::<TestRuntime>()
+            .unwrap();
+        t.into()
+    }
+
+    /// Makes a call as `who` in block `block`, as extrinsic number `index`
+    fn call_at(who: u64, block: u64, index: u32) {
+        System::set_block_number(block);
+        System::set_extrinsic_index(index);
+        assert_ok!(LastCaller1::call(Origin::signed(who)));
+    }
+
+    #[test]
+    fn history_records_block_and_index() {
+        new_test_ext().execute_with(|| {
+            call_at(1, 1, 0);
+            call_at(1, 2, 3);
+            call_at(2, 2, 4);
+
+            assert_eq!(LastCaller1::history(1), vec![(1, 0), (2, 3)]);
+            assert_eq!(LastCaller1::history(2), vec![(2, 4)]);
+        })
+    }
+
+    #[test]
+    fn history_forgets_oldest_first() {
+        new_test_ext().execute_with(|| {
+            for block in 1..=5 {
+                call_at(1, block, 0);
+            }
+            assert_eq!(LastCaller1::history(1), vec![(3, 0), (4, 0), (5, 0)]);
+        })
+    }
+
+    #[test]
+    fn instances_keep_separate_histories() {
+        new_test_ext().execute_with(|| {
+            for block in 1..=5 {
+                call_at(1, block, 0);
+                assert_ok!(LastCaller2::call(Origin::signed(1)));
+            }
+            assert_eq!(LastCaller1::history(1).len(), 3);
+            assert_eq!(LastCaller2::history(1).len(), 5);
+        })
+    }
+}
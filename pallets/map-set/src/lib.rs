@@ -0,0 +1,265 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! A set of accounts stored as a map, with its size counted alongside
+//!
+//! Unlike the `Vec` in the vec-set pallet, checking membership in a map reads a single entry,
+//! however large the set is. What a map cannot do cheaply is say how many entries it has, so the
+//! pallet keeps a counter next to it. Newer versions of FRAME offer this pairing as a
+//! `CountedStorageMap`; here it is spelled out by hand.
+//!
+//! The counter is only correct if it is updated on every path that changes the set. All changes
+//! therefore go through `add_to_set` and `remove_from_set`, and no other code writes to
+//! `Members` directly.
+//!
+//! The cheap count pays off in `clear_members`, whose weight depends on the size of the set: it
+//! is computed from the counter before the call runs, without enumerating the set.
+
+use sp_std::prelude::*;
+use frame_support::{
+	decl_event, decl_module, decl_storage,
+	dispatch::DispatchResult,
+	ensure,
+	traits::Get,
+	weights::SimpleDispatchInfo,
+};
+use frame_system::{self as system, ensure_root, ensure_signed};
+
+pub mod weights;
+pub use weights::WeightInfo;
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The most members the set can hold
+	type MaxMembers: Get<u32>;
+
+	/// Weight information for the dispatchables in this pallet
+	type WeightInfo: WeightInfo;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as MapSet {
+		/// The members of the set. A linked map, so that the set can be enumerated.
+		Members: linked_map T::AccountId => ();
+
+		/// The number of members. Only changed together with `Members`.
+		MemberCount get(fn member_count): u32;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as system::Trait>::AccountId,
+	{
+		/// An account joined the set
+		MemberAdded(AccountId),
+		/// An account left the set
+		MemberRemoved(AccountId),
+		/// Root removed this many members
+		MembersCleared(u32),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		const MaxMembers: u32 = T::MaxMembers::get();
+
+		#[weight = SimpleDispatchInfo::FixedNormal(T::WeightInfo::add_member())]
+		fn add_member(origin) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			ensure!(!Self::is_member(&who), "already a member");
+			// One read, where a vec-set would decode the whole list
+			ensure!(Self::member_count() < T::MaxMembers::get(), "membership limit reached");
+
+			Self::add_to_set(&who);
+			Self::deposit_event(RawEvent::MemberAdded(who));
+			Ok(())
+		}
+
+		#[weight = SimpleDispatchInfo::FixedNormal(T::WeightInfo::remove_member())]
+		fn remove_member(origin) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			ensure!(Self::is_member(&who), "not a member");
+
+			Self::remove_from_set(&who);
+			Self::deposit_event(RawEvent::MemberRemoved(who));
+			Ok(())
+		}
+
+		/// Remove every member. The weight is charged for the current number of members.
+		#[weight = SimpleDispatchInfo::FixedOperational(
+			T::WeightInfo::clear_members(<Module<T>>::member_count())
+		)]
+		fn clear_members(origin) -> DispatchResult {
+			ensure_root(origin)?;
+			let members: Vec<T::AccountId> = <Members<T>>::enumerate().map(|(who, _)| who).collect();
+			for who in members.iter() {
+				Self::remove_from_set(who);
+			}
+
+			Self::deposit_event(RawEvent::MembersCleared(members.len() as u32));
+			Ok(())
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	pub fn is_member(who: &T::AccountId) -> bool {
+		<Members<T>>::exists(who)
+	}
+
+	fn add_to_set(who: &T::AccountId) {
+		<Members<T>>::insert(who, ());
+		MemberCount::mutate(|count| *count += 1);
+	}
+
+	fn remove_from_set(who: &T::AccountId) {
+		<Members<T>>::remove(who);
+		MemberCount::mutate(|count| *count -= 1);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use event_assert::assert_has_event;
+	use sp_core::H256;
+	use frame_support::{
+		assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types,
+		weights::GetDispatchInfo,
+	};
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup},
+		Perbill,
+	};
+	use system::RawOrigin;
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const MaxMembers: u32 = 5;
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	mod map_set {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			map_set<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type MaxMembers = MaxMembers;
+		type WeightInfo = ();
+	}
+
+	pub type MapSet = Module<TestRuntime>;
+
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		let t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		t.into()
+	}
+
+	#[test]
+	fn count_follows_membership() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(MapSet::add_member(Origin::signed(1)));
+			assert_ok!(MapSet::add_member(Origin::signed(2)));
+			assert!(MapSet::is_member(&1));
+			assert_eq!(MapSet::member_count(), 2);
+			assert_has_event::<TestRuntime>(RawEvent::MemberAdded(2));
+
+			assert_ok!(MapSet::remove_member(Origin::signed(1)));
+			assert!(!MapSet::is_member(&1));
+			assert_eq!(MapSet::member_count(), 1);
+			assert_has_event::<TestRuntime>(RawEvent::MemberRemoved(1));
+		})
+	}
+
+	#[test]
+	fn failed_calls_leave_count_alone() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(MapSet::add_member(Origin::signed(1)));
+			assert_err!(MapSet::add_member(Origin::signed(1)), "already a member");
+			assert_err!(MapSet::remove_member(Origin::signed(2)), "not a member");
+			assert_eq!(MapSet::member_count(), 1);
+		})
+	}
+
+	#[test]
+	fn membership_is_limited() {
+		new_test_ext().execute_with(|| {
+			for who in 1..=5 {
+				assert_ok!(MapSet::add_member(Origin::signed(who)));
+			}
+			assert_err!(MapSet::add_member(Origin::signed(6)), "membership limit reached");
+		})
+	}
+
+	#[test]
+	fn root_clears_members() {
+		new_test_ext().execute_with(|| {
+			for who in 1..=3 {
+				assert_ok!(MapSet::add_member(Origin::signed(who)));
+			}
+			assert!(MapSet::clear_members(Origin::signed(1)).is_err());
+			assert_ok!(MapSet::clear_members(RawOrigin::Root.into()));
+
+			assert_eq!(MapSet::member_count(), 0);
+			assert!((1..=3).all(|who| !MapSet::is_member(&who)));
+			assert_has_event::<TestRuntime>(RawEvent::MembersCleared(3));
+		})
+	}
+
+	#[test]
+	fn clearing_weight_grows_with_count() {
+		new_test_ext().execute_with(|| {
+			let weight = || Call::<TestRuntime>::clear_members().get_dispatch_info().weight;
+			assert_eq!(weight(), <() as WeightInfo>::clear_members(0));
+
+			for who in 1..=4 {
+				assert_ok!(MapSet::add_member(Origin::signed(who)));
+			}
+			assert_eq!(weight(), <() as WeightInfo>::clear_members(4));
+		})
+	}
+}
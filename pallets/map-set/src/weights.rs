@@ -0,0 +1,44 @@
+//! Weights for the map-set pallet
+//!
+//! Clearing the set removes every member, so its weight is a function of the member count. The
+//! count is stored next to the set, which makes it cheap to read when computing the weight.
+
+use sp_std::marker::PhantomData;
+use frame_support::weights::Weight;
+
+/// Weight functions needed by the map-set pallet
+pub trait WeightInfo {
+    fn add_member() -> Weight;
+    fn remove_member() -> Weight;
+    fn clear_members(members: u32) -> Weight;
+}
+
+/// Hand-picked estimates of the map-set pallet's weights
+pub struct EstimatedWeight<T>(PhantomData<T>);
+impl<T: frame_system::Trait> WeightInfo for EstimatedWeight<T> {
+    // a membership check, an insertion into the linked map, and a count read and write
+    fn add_member() -> Weight {
+        30_000
+    }
+    // a membership check, a removal from the linked map, and a count read and write
+    fn remove_member() -> Weight {
+        30_000
+    }
+    // a count read and write, and per member a read while enumerating and a removal
+    fn clear_members(members: u32) -> Weight {
+        10_000u32.saturating_add(20_000u32.saturating_mul(members))
+    }
+}
+
+// For backwards compatibility and tests
+impl WeightInfo for () {
+    fn add_member() -> Weight {
+        10_000
+    }
+    fn remove_member() -> Weight {
+        10_000
+    }
+    fn clear_members(members: u32) -> Weight {
+        10_000u32.saturating_add(1_000u32.saturating_mul(members))
+    }
+}
@@ -0,0 +1,250 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! Ordered storage migrations
+//!
+//! A runtime upgrade may need to migrate the storage of several pallets, and one migration may
+//! depend on another: a pallet that stores amounts of another pallet's token must rescale them
+//! only after the token itself has changed. This pallet runs a runtime's migrations in a fixed
+//! order, each exactly once.
+//!
+//! The runtime lists its migrations as a tuple in `Trait::Migrations`. Tuples of `OnInitialize`
+//! types run their elements left to right, so the tuple's order is the order of the migrations.
+//! Each migration is wrapped in `Once`, which records the migration's `ID` in `Applied` after it
+//! ran, and skips it from then on. A migration also names the migrations it must run `AFTER`. If
+//! one of them has not been applied yet, the migration is deferred to a later block instead of
+//! running on storage it does not understand.
+//!
+//! Migrations run in this pallet's `on_initialize`, so the pallet should come right after `System`
+//! in `construct_runtime!`, before any pallet whose storage they touch.
+
+use sp_std::{marker::PhantomData, prelude::*};
+use sp_runtime::traits::OnInitialize;
+use frame_support::{decl_event, decl_module, decl_storage};
+use frame_system as system;
+
+/// Identifies a migration in `Applied`
+pub type MigrationId = [u8; 8];
+
+/// A one-off change to storage
+pub trait Migration {
+	/// Unique among the migrations of a runtime
+	const ID: MigrationId;
+
+	/// The migrations that must be applied before this one
+	const AFTER: &'static [MigrationId] = &[];
+
+	/// Change the storage
+	fn migrate();
+}
+
+/// Runs the migration `M` in the first block in which all migrations it comes after have been
+/// applied, and never again
+pub struct Once<T, M>(PhantomData<(T, M)>);
+
+impl<T: Trait, M: Migration> OnInitialize<T::BlockNumber> for Once<T, M> {
+	fn on_initialize(_n: T::BlockNumber) {
+		Module::<T>::apply::<M>();
+	}
+}
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event> + Into<<Self as system::Trait>::Event>;
+
+	/// The migrations to run, in order. Usually a tuple of `Once`.
+	type Migrations: OnInitialize<Self::BlockNumber>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as MigrationOrder {
+		/// The migrations that have run, in the order they ran
+		Applied get(fn applied): Vec<MigrationId>;
+	}
+}
+
+decl_event!(
+	pub enum Event {
+		/// A migration ran
+		MigrationApplied(MigrationId),
+		/// A migration (first) waits for another (second)
+		MigrationDeferred(MigrationId, MigrationId),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		fn on_initialize(n: T::BlockNumber) {
+			T::Migrations::on_initialize(n);
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// Run `M` unless it has run already, or a migration it comes after has not
+	fn apply<M: Migration>() {
+		let applied = Self::applied();
+		if applied.contains(&M::ID) {
+			return;
+		}
+		if let Some(missing) = M::AFTER.iter().find(|id| !applied.contains(id)) {
+			Self::deposit_event(Event::MigrationDeferred(M::ID, *missing));
+			return;
+		}
+
+		M::migrate();
+		Applied::mutate(|applied| applied.push(M::ID));
+		Self::deposit_event(Event::MigrationApplied(M::ID));
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use event_assert::{assert_has_event, assert_last_event};
+	use frame_support::{impl_outer_event, impl_outer_origin, parameter_types, storage::unhashed};
+	use sp_core::H256;
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup},
+		Perbill,
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	mod migration_order {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			migration_order,
+		}
+	}
+
+	// Storage of two imaginary pallets. A token pallet stores a balance, and a fee pallet stores a
+	// fee in units of that token.
+	const BALANCE: &[u8] = b":token:balance";
+	const DECIMALS: &[u8] = b":token:decimals";
+	const FEE: &[u8] = b":fees:fee";
+
+	/// The token now counts in hundredths, and its balance is a `u64` instead of a `u32`
+	pub struct TokenCents;
+	impl Migration for TokenCents {
+		const ID: MigrationId = *b"tokencts";
+
+		fn migrate() {
+			let balance = unhashed::get::<u32>(BALANCE).unwrap_or_default();
+			unhashed::put(BALANCE, &(u64::from(balance) * 100));
+			unhashed::put(DECIMALS, &2u32);
+		}
+	}
+
+	/// The fee is rescaled to the token's decimals. Before `TokenCents`, the token has none, and
+	/// the fee would be left unchanged.
+	pub struct FeeCents;
+	impl Migration for FeeCents {
+		const ID: MigrationId = *b"feecents";
+		const AFTER: &'static [MigrationId] = &[TokenCents::ID];
+
+		fn migrate() {
+			let decimals = unhashed::get::<u32>(DECIMALS).unwrap_or_default();
+			let fee = unhashed::get::<u32>(FEE).unwrap_or_default();
+			unhashed::put(FEE, &(u64::from(fee) * 10u64.pow(decimals)));
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type Migrations = (Once<Self, TokenCents>, Once<Self, FeeCents>);
+	}
+
+	pub type MigrationOrder = Module<TestRuntime>;
+
+	/// Storage as the old versions of both pallets left it
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		let t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		let mut ext: sp_io::TestExternalities = t.into();
+		ext.execute_with(|| {
+			unhashed::put(BALANCE, &7u32);
+			unhashed::put(FEE, &3u32);
+		});
+		ext
+	}
+
+	#[test]
+	fn migrations_run_in_tuple_order() {
+		new_test_ext().execute_with(|| {
+			MigrationOrder::on_initialize(1);
+
+			assert_eq!(MigrationOrder::applied(), vec![TokenCents::ID, FeeCents::ID]);
+			assert_last_event::<TestRuntime>(Event::MigrationApplied(FeeCents::ID));
+			assert_eq!(unhashed::get::<u64>(BALANCE), Some(700));
+			assert_eq!(unhashed::get::<u64>(FEE), Some(300));
+		})
+	}
+
+	#[test]
+	fn migrations_run_once() {
+		new_test_ext().execute_with(|| {
+			MigrationOrder::on_initialize(1);
+			MigrationOrder::on_initialize(2);
+
+			assert_eq!(MigrationOrder::applied(), vec![TokenCents::ID, FeeCents::ID]);
+			assert_eq!(unhashed::get::<u64>(BALANCE), Some(700));
+			assert_eq!(unhashed::get::<u64>(FEE), Some(300));
+		})
+	}
+
+	#[test]
+	fn dependent_migration_waits() {
+		type Misordered = (Once<TestRuntime, FeeCents>, Once<TestRuntime, TokenCents>);
+
+		new_test_ext().execute_with(|| {
+			// The fee migration is deferred, and the token migration runs after it
+			<Misordered as OnInitialize<u64>>::on_initialize(1);
+			assert_eq!(MigrationOrder::applied(), vec![TokenCents::ID]);
+			assert_eq!(unhashed::get::<u32>(FEE), Some(3));
+			assert_has_event::<TestRuntime>(Event::MigrationDeferred(FeeCents::ID, TokenCents::ID));
+
+			// In the next block, the fee migration finds the token migrated
+			<Misordered as OnInitialize<u64>>::on_initialize(2);
+			assert_eq!(MigrationOrder::applied(), vec![TokenCents::ID, FeeCents::ID]);
+			assert_eq!(unhashed::get::<u64>(FEE), Some(300));
+		})
+	}
+}
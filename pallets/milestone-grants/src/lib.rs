@@ -0,0 +1,507 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! A treasury that pays its grants in tranches, as milestones are reached
+//!
+//! The treasury is a pot of funds in the pallet's own account, which anyone can `fund`.
+//! `ManageOrigin` approves a grant as a list of tranches, one per milestone, and appoints the
+//! reviewers who judge them. The treasury never promises more than it holds: the unpaid tranches
+//! of all grants are tracked in `Committed`.
+//!
+//! The grantee works through the milestones in order. For each one, it `submit_evidence`s the hash
+//! of its deliverables, such as a report or a release, which stay off-chain. A reviewer then
+//! `release`s the milestone's tranche, or `reject`s the evidence, so the grantee can submit more.
+//!
+//! Evidence is anchored in the manner of a proof of existence: the first account to submit a
+//! hash is recorded with the block, and the hash can never be submitted again. Anyone holding the
+//! deliverables can later prove they existed at that block, and nobody can pass off evidence
+//! already submitted, by themselves or someone else, as new.
+
+use codec::{Decode, Encode};
+use sp_std::prelude::*;
+use sp_runtime::{
+	traits::{AccountIdConversion, Saturating, Zero},
+	ModuleId, RuntimeDebug,
+};
+use frame_support::{
+	decl_event, decl_module, decl_storage,
+	dispatch::{DispatchError, DispatchResult},
+	ensure,
+	traits::{Currency, EnsureOrigin, ExistenceRequirement::AllowDeath, Get},
+};
+use frame_system::{self as system, ensure_signed};
+
+pub type GrantIndex = u32;
+
+type BalanceOf<T> = <<T as Trait>::Currency as Currency<<T as system::Trait>::AccountId>>::Balance;
+type GrantOf<T> =
+	Grant<<T as system::Trait>::AccountId, BalanceOf<T>, <T as system::Trait>::Hash>;
+
+/// Hardcoded pallet ID; used to create the treasury's account
+/// Must be exactly 8 characters long
+const PALLET_ID: ModuleId = ModuleId(*b"MileGrnt");
+
+/// A grant that pays a tranche per milestone
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct Grant<AccountId, Balance, Hash> {
+	pub grantee: AccountId,
+	/// The amount paid for each milestone, in order
+	pub tranches: Vec<Balance>,
+	/// The milestone the grantee works on; the tranches before it were paid
+	pub next: u32,
+	/// The evidence for the next milestone, while it waits for review
+	pub evidence: Option<Hash>,
+}
+
+impl<AccountId, Balance: Copy + Saturating + Zero, Hash> Grant<AccountId, Balance, Hash> {
+	/// The sum of the tranches that have not been paid
+	fn unpaid(&self) -> Balance {
+		self.tranches[self.next as usize..]
+			.iter()
+			.fold(Zero::zero(), |sum: Balance, tranche| sum.saturating_add(*tranche))
+	}
+}
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The currency the treasury holds
+	type Currency: Currency<Self::AccountId>;
+
+	/// The origin that approves and cancels grants, and appoints reviewers
+	type ManageOrigin: EnsureOrigin<Self::Origin>;
+
+	/// The most milestones in a grant
+	type MaxMilestones: Get<u32>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as MilestoneGrants {
+		/// Grants that have not been paid in full
+		Grants get(fn grant): map GrantIndex => Option<GrantOf<T>>;
+
+		/// Number of grants ever approved; the next grant's index
+		GrantCount get(fn grant_count): GrantIndex;
+
+		/// The unpaid tranches of all grants, which the treasury must keep
+		Committed get(fn committed): BalanceOf<T>;
+
+		/// Accounts that may release and reject milestones
+		Reviewers get(fn is_reviewer): map T::AccountId => bool;
+
+		/// Who first submitted each piece of evidence, and in which block
+		Anchors get(fn anchor): map T::Hash => Option<(T::AccountId, T::BlockNumber)>;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as system::Trait>::AccountId,
+		Balance = BalanceOf<T>,
+		Hash = <T as system::Trait>::Hash,
+	{
+		/// Someone added funds to the treasury
+		Funded(AccountId, Balance),
+		/// A grant was approved for a grantee, for a total over a number of milestones
+		GrantApproved(GrantIndex, AccountId, Balance, u32),
+		ReviewerAdded(AccountId),
+		ReviewerRemoved(AccountId),
+		/// A grantee submitted evidence for a milestone
+		EvidenceSubmitted(GrantIndex, u32, Hash),
+		/// A reviewer rejected the evidence for a milestone
+		EvidenceRejected(GrantIndex, u32, AccountId),
+		/// A reviewer released the tranche of a milestone
+		TrancheReleased(GrantIndex, u32, AccountId, Balance),
+		/// A grant was paid in full
+		GrantCompleted(GrantIndex),
+		/// A grant was cancelled, and its unpaid tranches stay in the treasury
+		GrantCancelled(GrantIndex, Balance),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		const MaxMilestones: u32 = T::MaxMilestones::get();
+
+		/// Add funds to the treasury
+		fn fund(origin, amount: BalanceOf<T>) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			T::Currency::transfer(&who, &Self::account_id(), amount, AllowDeath)?;
+			Self::deposit_event(RawEvent::Funded(who, amount));
+			Ok(())
+		}
+
+		fn add_reviewer(origin, who: T::AccountId) -> DispatchResult {
+			T::ManageOrigin::ensure_origin(origin)?;
+			<Reviewers<T>>::insert(&who, true);
+			Self::deposit_event(RawEvent::ReviewerAdded(who));
+			Ok(())
+		}
+
+		fn remove_reviewer(origin, who: T::AccountId) -> DispatchResult {
+			T::ManageOrigin::ensure_origin(origin)?;
+			<Reviewers<T>>::remove(&who);
+			Self::deposit_event(RawEvent::ReviewerRemoved(who));
+			Ok(())
+		}
+
+		/// Grant `grantee` a tranche for each milestone
+		fn approve_grant(
+			origin,
+			grantee: T::AccountId,
+			tranches: Vec<BalanceOf<T>>,
+		) -> DispatchResult {
+			T::ManageOrigin::ensure_origin(origin)?;
+			ensure!(!tranches.is_empty(), "no milestones");
+			let milestones = tranches.len() as u32;
+			ensure!(milestones <= T::MaxMilestones::get(), "too many milestones");
+
+			let grant = Grant { grantee: grantee.clone(), tranches, next: 0, evidence: None };
+			let total = grant.unpaid();
+			ensure!(
+				Self::committed().saturating_add(total) <= Self::pot(),
+				"treasury cannot cover the grant"
+			);
+
+			let index = Self::grant_count();
+			<Grants<T>>::insert(index, grant);
+			GrantCount::put(index + 1);
+			<Committed<T>>::mutate(|committed| *committed = committed.saturating_add(total));
+			Self::deposit_event(RawEvent::GrantApproved(index, grantee, total, milestones));
+			Ok(())
+		}
+
+		/// Submit the hash of the evidence for the grant's next milestone, anchoring it
+		fn submit_evidence(origin, index: GrantIndex, evidence: T::Hash) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let mut grant = Self::grant(index).ok_or("no such grant")?;
+			ensure!(who == grant.grantee, "not the grantee");
+			ensure!(grant.evidence.is_none(), "evidence awaits review");
+			ensure!(!<Anchors<T>>::exists(&evidence), "evidence already anchored");
+
+			let now = <system::Module<T>>::block_number();
+			<Anchors<T>>::insert(&evidence, (who, now));
+			grant.evidence = Some(evidence);
+			let milestone = grant.next;
+			<Grants<T>>::insert(index, grant);
+			Self::deposit_event(RawEvent::EvidenceSubmitted(index, milestone, evidence));
+			Ok(())
+		}
+
+		/// Pay the tranche of the milestone whose evidence awaits review
+		fn release(origin, index: GrantIndex) -> DispatchResult {
+			let reviewer = Self::ensure_reviewer(origin)?;
+			let mut grant = Self::grant(index).ok_or("no such grant")?;
+			Self::ensure_reviewable(&grant, &reviewer)?;
+
+			let milestone = grant.next;
+			let tranche = grant.tranches[milestone as usize];
+			T::Currency::transfer(&Self::account_id(), &grant.grantee, tranche, AllowDeath)?;
+			<Committed<T>>::mutate(|committed| *committed = committed.saturating_sub(tranche));
+			Self::deposit_event(RawEvent::TrancheReleased(index, milestone, reviewer, tranche));
+
+			grant.next += 1;
+			grant.evidence = None;
+			if grant.next as usize == grant.tranches.len() {
+				<Grants<T>>::remove(index);
+				Self::deposit_event(RawEvent::GrantCompleted(index));
+			} else {
+				<Grants<T>>::insert(index, grant);
+			}
+			Ok(())
+		}
+
+		/// Turn down the evidence that awaits review. It stays anchored, and the grantee must
+		/// submit new evidence.
+		fn reject(origin, index: GrantIndex) -> DispatchResult {
+			let reviewer = Self::ensure_reviewer(origin)?;
+			let mut grant = Self::grant(index).ok_or("no such grant")?;
+			Self::ensure_reviewable(&grant, &reviewer)?;
+
+			grant.evidence = None;
+			let milestone = grant.next;
+			<Grants<T>>::insert(index, grant);
+			Self::deposit_event(RawEvent::EvidenceRejected(index, milestone, reviewer));
+			Ok(())
+		}
+
+		/// Stop a grant. Its unpaid tranches stay in the treasury.
+		fn cancel(origin, index: GrantIndex) -> DispatchResult {
+			T::ManageOrigin::ensure_origin(origin)?;
+			let grant = <Grants<T>>::take(index).ok_or("no such grant")?;
+			let returned = grant.unpaid();
+			<Committed<T>>::mutate(|committed| *committed = committed.saturating_sub(returned));
+			Self::deposit_event(RawEvent::GrantCancelled(index, returned));
+			Ok(())
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// The account ID that holds the treasury's funds
+	pub fn account_id() -> T::AccountId {
+		PALLET_ID.into_account()
+	}
+
+	/// The treasury's funds, including those committed to grants
+	pub fn pot() -> BalanceOf<T> {
+		T::Currency::free_balance(&Self::account_id())
+	}
+
+	fn ensure_reviewer(origin: T::Origin) -> Result<T::AccountId, DispatchError> {
+		let who = ensure_signed(origin)?;
+		ensure!(Self::is_reviewer(&who), "not a reviewer");
+		Ok(who)
+	}
+
+	fn ensure_reviewable(grant: &GrantOf<T>, reviewer: &T::AccountId) -> DispatchResult {
+		ensure!(grant.evidence.is_some(), "no evidence to review");
+		ensure!(*reviewer != grant.grantee, "cannot review own grant");
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use event_assert::{assert_has_event, assert_last_event};
+
+	use sp_core::H256;
+	use frame_support::{
+		assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types,
+	};
+	use frame_system::{EnsureRoot, RawOrigin};
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup},
+		Perbill,
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const ExistentialDeposit: u64 = 0;
+		pub const TransferFee: u64 = 0;
+		pub const CreationFee: u64 = 0;
+
+		pub const MaxMilestones: u32 = 3;
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	impl balances::Trait for TestRuntime {
+		type Balance = u64;
+		type OnFreeBalanceZero = ();
+		type OnNewAccount = ();
+		type Event = TestEvent;
+		type TransferPayment = ();
+		type DustRemoval = ();
+		type ExistentialDeposit = ExistentialDeposit;
+		type TransferFee = TransferFee;
+		type CreationFee = CreationFee;
+	}
+
+	mod milestone_grants {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			balances<T>,
+			milestone_grants<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type Currency = balances::Module<Self>;
+		type ManageOrigin = EnsureRoot<u64>;
+		type MaxMilestones = MaxMilestones;
+	}
+
+	pub type System = system::Module<TestRuntime>;
+	pub type Balances = balances::Module<TestRuntime>;
+	pub type MilestoneGrants = Module<TestRuntime>;
+
+	/// Account 1 funded the treasury with 1000 in block 1, and account 9 is a reviewer. Grant 0
+	/// pays account 2 100, 200 and 300 for three milestones.
+	fn new_test_ext() -> sp_io::TestExternalities {
+		let mut t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		balances::GenesisConfig::<TestRuntime> {
+			balances: vec![(1, 1_000)],
+			vesting: vec![],
+		}
+		.assimilate_storage(&mut t)
+		.unwrap();
+		let mut ext: sp_io::TestExternalities = t.into();
+		ext.execute_with(|| {
+			System::set_block_number(1);
+			assert_ok!(MilestoneGrants::fund(Origin::signed(1), 1_000));
+			assert_ok!(MilestoneGrants::add_reviewer(RawOrigin::Root.into(), 9));
+			assert_ok!(approve(2, vec![100, 200, 300]));
+		});
+		ext
+	}
+
+	fn approve(grantee: u64, tranches: Vec<u64>) -> DispatchResult {
+		MilestoneGrants::approve_grant(RawOrigin::Root.into(), grantee, tranches)
+	}
+
+	fn evidence(n: u8) -> H256 {
+		H256::repeat_byte(n)
+	}
+
+	fn submit(n: u8) {
+		assert_ok!(MilestoneGrants::submit_evidence(Origin::signed(2), 0, evidence(n)));
+	}
+
+	fn release() {
+		assert_ok!(MilestoneGrants::release(Origin::signed(9), 0));
+	}
+
+	#[test]
+	fn grants_are_committed() {
+		new_test_ext().execute_with(|| {
+			assert_has_event::<TestRuntime>(RawEvent::Funded(1, 1_000));
+			assert_last_event::<TestRuntime>(RawEvent::GrantApproved(0, 2, 600, 3));
+			assert_eq!(MilestoneGrants::committed(), 600);
+			assert_err!(approve(3, vec![100, 301]), "treasury cannot cover the grant");
+			assert_err!(approve(3, vec![]), "no milestones");
+			assert_err!(approve(3, vec![1, 1, 1, 1]), "too many milestones");
+			assert!(MilestoneGrants::approve_grant(Origin::signed(1), 3, vec![1]).is_err());
+			assert_ok!(approve(3, vec![400]));
+			assert_eq!(MilestoneGrants::committed(), 1_000);
+		})
+	}
+
+	#[test]
+	fn tranches_are_released_milestone_by_milestone() {
+		new_test_ext().execute_with(|| {
+			assert_err!(
+				MilestoneGrants::release(Origin::signed(9), 0),
+				"no evidence to review"
+			);
+			submit(1);
+			assert_last_event::<TestRuntime>(RawEvent::EvidenceSubmitted(0, 0, evidence(1)));
+			assert_err!(
+				MilestoneGrants::submit_evidence(Origin::signed(2), 0, evidence(2)),
+				"evidence awaits review"
+			);
+			release();
+			assert_last_event::<TestRuntime>(RawEvent::TrancheReleased(0, 0, 9, 100));
+			assert_eq!(Balances::free_balance(&2), 100);
+			assert_eq!(MilestoneGrants::grant(0).unwrap().next, 1);
+
+			submit(2);
+			release();
+			System::set_block_number(5);
+			submit(3);
+			release();
+			assert_eq!(Balances::free_balance(&2), 600);
+			assert_eq!(MilestoneGrants::committed(), 0);
+			assert_eq!(MilestoneGrants::pot(), 400);
+			assert_eq!(MilestoneGrants::grant(0), None);
+			assert_has_event::<TestRuntime>(RawEvent::TrancheReleased(0, 2, 9, 300));
+			assert_last_event::<TestRuntime>(RawEvent::GrantCompleted(0));
+
+			// The evidence stays anchored after the grant
+			assert_eq!(MilestoneGrants::anchor(evidence(3)), Some((2, 5)));
+		})
+	}
+
+	#[test]
+	fn only_reviewers_release_other_grants() {
+		new_test_ext().execute_with(|| {
+			submit(1);
+			assert_err!(MilestoneGrants::release(Origin::signed(1), 0), "not a reviewer");
+			assert_err!(
+				MilestoneGrants::submit_evidence(Origin::signed(1), 0, evidence(9)),
+				"not the grantee"
+			);
+
+			// A grantee who reviews can't release their own tranches
+			assert_ok!(MilestoneGrants::add_reviewer(RawOrigin::Root.into(), 2));
+			assert_last_event::<TestRuntime>(RawEvent::ReviewerAdded(2));
+			assert_err!(MilestoneGrants::release(Origin::signed(2), 0), "cannot review own grant");
+
+			assert_ok!(MilestoneGrants::remove_reviewer(RawOrigin::Root.into(), 9));
+			assert_last_event::<TestRuntime>(RawEvent::ReviewerRemoved(9));
+			assert_err!(MilestoneGrants::release(Origin::signed(9), 0), "not a reviewer");
+		})
+	}
+
+	#[test]
+	fn rejected_evidence_stays_anchored() {
+		new_test_ext().execute_with(|| {
+			submit(1);
+			assert_ok!(MilestoneGrants::reject(Origin::signed(9), 0));
+			assert_last_event::<TestRuntime>(RawEvent::EvidenceRejected(0, 0, 9));
+			assert_eq!(MilestoneGrants::grant(0).unwrap().evidence, None);
+			assert_eq!(Balances::free_balance(&2), 0);
+
+			// The same evidence can't be passed off as new, by the grantee or anyone else
+			assert_err!(
+				MilestoneGrants::submit_evidence(Origin::signed(2), 0, evidence(1)),
+				"evidence already anchored"
+			);
+			assert_ok!(approve(3, vec![100]));
+			assert_err!(
+				MilestoneGrants::submit_evidence(Origin::signed(3), 1, evidence(1)),
+				"evidence already anchored"
+			);
+			assert_eq!(MilestoneGrants::anchor(evidence(1)), Some((2, 1)));
+
+			submit(2);
+			release();
+			assert_eq!(Balances::free_balance(&2), 100);
+		})
+	}
+
+	#[test]
+	fn cancelling_returns_the_unpaid_tranches() {
+		new_test_ext().execute_with(|| {
+			submit(1);
+			release();
+			assert_ok!(MilestoneGrants::cancel(RawOrigin::Root.into(), 0));
+			assert_last_event::<TestRuntime>(RawEvent::GrantCancelled(0, 500));
+			assert_eq!(MilestoneGrants::committed(), 0);
+			assert_eq!(MilestoneGrants::pot(), 900);
+			assert_err!(
+				MilestoneGrants::submit_evidence(Origin::signed(2), 0, evidence(2)),
+				"no such grant"
+			);
+			assert_ok!(approve(3, vec![900]));
+		})
+	}
+}
@@ -0,0 +1,363 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! A computation spread over many blocks
+//!
+//! The pallet computes the Merkle root of a list of items stored on chain. For a long list that is
+//! far too much work for one block, so the computation is split into steps of at most one hash each, and
+//! every block's `on_initialize` performs at most `StepsPerBlock` of them.
+//!
+//! Everything the computation needs to continue is in storage: the `Task` enum says which phase it
+//! is in and where in that phase, and `Nodes` holds the hashes computed so far. Nothing is kept in
+//! memory between blocks, so the computation continues where it stopped even if every node
+//! restarts in between.
+
+use codec::{Decode, Encode};
+use frame_support::{decl_event, decl_module, decl_storage, dispatch::DispatchResult, ensure, traits::Get};
+use frame_system::{self as system, ensure_signed};
+use sp_runtime::{traits::Hash, RuntimeDebug};
+use sp_std::prelude::*;
+
+/// The state of the computation
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub enum TaskState {
+	/// Nothing to do. Items can be added.
+	Idle,
+	/// Hashing the items into the leaves of the tree. `next` is the next item to hash.
+	HashingLeaves { next: u32 },
+	/// Hashing pairs of nodes on `level` into the level above. The level has `width` nodes, and
+	/// `next` is the first node of the next pair.
+	Combining { level: u32, next: u32, width: u32 },
+}
+
+impl Default for TaskState {
+	fn default() -> Self {
+		TaskState::Idle
+	}
+}
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The most steps performed in one block
+	type StepsPerBlock: Get<u32>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as MultiBlockTask {
+		/// The items, by position
+		Items get(fn item): map u32 => u64;
+
+		/// The number of items
+		ItemCount get(fn item_count): u32;
+
+		/// What the computation is doing
+		Task get(fn task): TaskState;
+
+		/// Nodes of the tree that have been computed but not combined yet, by (level, position).
+		/// Level 0 holds the leaves.
+		Nodes get(fn node): map (u32, u32) => Option<T::Hash>;
+
+		/// The root of the last completed computation
+		Root get(fn root): Option<T::Hash>;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as system::Trait>::AccountId,
+		Hash = <T as system::Trait>::Hash,
+	{
+		/// An account started computing the root of this many items
+		Started(AccountId, u32),
+		/// The computation completed with this root
+		Completed(Hash),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		const StepsPerBlock: u32 = T::StepsPerBlock::get();
+
+		/// Append items to the list
+		fn add_items(origin, items: Vec<u64>) -> DispatchResult {
+			ensure_signed(origin)?;
+			// The computation reads the items as it goes, so they must not change underneath it
+			ensure!(Self::task() == TaskState::Idle, "computation in progress");
+
+			let count = Self::item_count();
+			let new_count = count.checked_add(items.len() as u32).ok_or("too many items")?;
+			for (index, item) in (count..new_count).zip(items) {
+				Items::insert(index, item);
+			}
+			ItemCount::put(new_count);
+			Ok(())
+		}
+
+		/// Start computing the root of all items. The work is done in the following blocks.
+		fn start(origin) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			ensure!(Self::task() == TaskState::Idle, "computation in progress");
+			let count = Self::item_count();
+			ensure!(count > 0, "no items");
+
+			Task::put(TaskState::HashingLeaves { next: 0 });
+			Self::deposit_event(RawEvent::Started(who, count));
+			Ok(())
+		}
+
+		fn on_initialize(_n: T::BlockNumber) {
+			let mut task = Self::task();
+			if task == TaskState::Idle {
+				return;
+			}
+			for _ in 0..T::StepsPerBlock::get() {
+				task = Self::step(task);
+				if task == TaskState::Idle {
+					break;
+				}
+			}
+			Task::put(task);
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// Compute one hash, and return the state after it
+	fn step(task: TaskState) -> TaskState {
+		match task {
+			TaskState::Idle => TaskState::Idle,
+			TaskState::HashingLeaves { next } => {
+				<Nodes<T>>::insert((0, next), T::Hashing::hash_of(&Self::item(next)));
+				let next = next + 1;
+				let count = Self::item_count();
+				if next < count {
+					TaskState::HashingLeaves { next }
+				} else {
+					Self::next_level(0, count)
+				}
+			}
+			TaskState::Combining { level, next, width } => {
+				// Each node is read exactly once, so it is removed as it is read
+				let left = <Nodes<T>>::take((level, next)).unwrap_or_default();
+				let parent = if next + 1 < width {
+					let right = <Nodes<T>>::take((level, next + 1)).unwrap_or_default();
+					T::Hashing::hash_of(&(left, right))
+				} else {
+					// The last node of an odd level moves up unchanged
+					left
+				};
+				<Nodes<T>>::insert((level + 1, next / 2), parent);
+
+				let next = next + 2;
+				if next < width {
+					TaskState::Combining { level, next, width }
+				} else {
+					Self::next_level(level + 1, (width + 1) / 2)
+				}
+			}
+		}
+	}
+
+	/// The state after `level`, which has `width` nodes, is complete. A single node is the root.
+	fn next_level(level: u32, width: u32) -> TaskState {
+		if width > 1 {
+			return TaskState::Combining { level, next: 0, width };
+		}
+		let root = <Nodes<T>>::take((level, 0)).unwrap_or_default();
+		<Root<T>>::put(root);
+		Self::deposit_event(RawEvent::Completed(root));
+		TaskState::Idle
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use event_assert::assert_last_event;
+	use frame_support::{assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types};
+	use sp_core::H256;
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup, OnInitialize},
+		Perbill,
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const StepsPerBlock: u32 = 4;
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	mod multi_block_task {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			multi_block_task<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type StepsPerBlock = StepsPerBlock;
+	}
+
+	pub type MultiBlockTask = Module<TestRuntime>;
+
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		let t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		t.into()
+	}
+
+	/// The root computed in one go, to compare the pallet's result with
+	fn expected_root(items: &[u64]) -> H256 {
+		let mut level: Vec<H256> = items.iter().map(BlakeTwo256::hash_of).collect();
+		while level.len() > 1 {
+			level = level
+				.chunks(2)
+				.map(|pair| match pair {
+					[left, right] => BlakeTwo256::hash_of(&(left, right)),
+					[single] => *single,
+					_ => unreachable!(),
+				})
+				.collect();
+		}
+		level[0]
+	}
+
+	/// Runs blocks until the computation is idle, and returns how many it took
+	fn run_to_completion() -> u64 {
+		let mut blocks = 0;
+		while MultiBlockTask::task() != TaskState::Idle {
+			blocks += 1;
+			MultiBlockTask::on_initialize(blocks);
+		}
+		blocks
+	}
+
+	fn start_with(items: Vec<u64>) {
+		assert_ok!(MultiBlockTask::add_items(Origin::signed(1), items));
+		assert_ok!(MultiBlockTask::start(Origin::signed(1)));
+	}
+
+	#[test]
+	fn computes_the_merkle_root() {
+		for len in &[1u64, 2, 5, 8, 13] {
+			new_test_ext().execute_with(|| {
+				let items: Vec<u64> = (0..*len).collect();
+				start_with(items.clone());
+				run_to_completion();
+
+				let root = expected_root(&items);
+				assert_eq!(MultiBlockTask::root(), Some(root));
+				assert_last_event::<TestRuntime>(RawEvent::Completed(root));
+			})
+		}
+	}
+
+	#[test]
+	fn work_per_block_is_bounded() {
+		new_test_ext().execute_with(|| {
+			start_with((0..10).collect());
+
+			MultiBlockTask::on_initialize(1);
+			assert_eq!(MultiBlockTask::task(), TaskState::HashingLeaves { next: 4 });
+			MultiBlockTask::on_initialize(2);
+			assert_eq!(MultiBlockTask::task(), TaskState::HashingLeaves { next: 8 });
+			// The last two leaves, then the first two pairs of level 0
+			MultiBlockTask::on_initialize(3);
+			assert_eq!(MultiBlockTask::task(), TaskState::Combining { level: 0, next: 4, width: 10 });
+
+			// 10 leaves, then 5, 3, 2 and 1 nodes on the levels above are 21 steps. 12 are done,
+			// and the other 9 take three more blocks.
+			assert_eq!(run_to_completion(), 3);
+		})
+	}
+
+	#[test]
+	fn resumes_after_restart() {
+		let items: Vec<u64> = (100..117).collect();
+		let mut ext = new_test_ext();
+
+		ext.execute_with(|| {
+			start_with(items.clone());
+			for block in 1..=5 {
+				MultiBlockTask::on_initialize(block);
+			}
+		});
+
+		// A new `execute_with` shares nothing with the last one except storage, like a node that
+		// was restarted between two blocks
+		ext.execute_with(|| {
+			assert_eq!(MultiBlockTask::task(), TaskState::Combining { level: 0, next: 6, width: 17 });
+			assert_eq!(MultiBlockTask::root(), None);
+		});
+
+		ext.execute_with(|| {
+			run_to_completion();
+			assert_eq!(MultiBlockTask::root(), Some(expected_root(&items)));
+			// All intermediate nodes have been cleaned up
+			for level in 0..6 {
+				for position in 0..17 {
+					assert_eq!(MultiBlockTask::node((level, position)), None);
+				}
+			}
+		});
+	}
+
+	#[test]
+	fn items_are_frozen_while_computing() {
+		new_test_ext().execute_with(|| {
+			assert_err!(MultiBlockTask::start(Origin::signed(1)), "no items");
+			start_with(vec![1, 2, 3, 4, 5]);
+			assert_err!(MultiBlockTask::add_items(Origin::signed(1), vec![6]), "computation in progress");
+			assert_err!(MultiBlockTask::start(Origin::signed(1)), "computation in progress");
+
+			run_to_completion();
+			let first_root = MultiBlockTask::root();
+
+			// Once idle, items can be added and the root recomputed
+			assert_ok!(MultiBlockTask::add_items(Origin::signed(1), vec![6]));
+			assert_ok!(MultiBlockTask::start(Origin::signed(1)));
+			run_to_completion();
+			assert_eq!(MultiBlockTask::root(), Some(expected_root(&[1, 2, 3, 4, 5, 6])));
+			assert_ne!(MultiBlockTask::root(), first_root);
+		})
+	}
+}
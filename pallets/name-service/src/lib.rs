@@ -0,0 +1,492 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! A name service with commit-reveal registration
+//!
+//! Registering a name in a single extrinsic invites frontrunning: anyone watching the transaction
+//! pool can see the name and submit their own registration with a higher tip. Here registration
+//! takes two steps. First the user commits to the hash of their account, the name and a secret
+//! salt. Nothing about the name is revealed, and the commitment is useless to anyone else because
+//! it includes the committer's account. After at least `MinCommitmentAge` blocks the user reveals
+//! the name and salt to claim it. By then a frontrunner who only now learns the name is too late.
+//!
+//! Both steps reserve a deposit. Commitments that are not revealed within `MaxCommitmentAge`
+//! blocks expire, and anyone may clear them to collect the forfeited deposit.
+
+use codec::{Decode, Encode};
+use sp_std::prelude::*;
+use sp_runtime::{
+	traits::{Hash, Saturating},
+	RuntimeDebug,
+};
+use frame_support::{
+	decl_event, decl_module, decl_storage,
+	dispatch::DispatchResult,
+	ensure,
+	traits::{Currency, Get, ReservableCurrency},
+};
+use frame_system::{self as system, ensure_signed};
+
+type BalanceOf<T> = <<T as Trait>::Currency as Currency<<T as system::Trait>::AccountId>>::Balance;
+
+/// A pending claim on a name that has not been revealed yet
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct Commitment<AccountId, Balance, BlockNumber> {
+	pub who: AccountId,
+	pub deposit: Balance,
+	/// The block in which the commitment was made
+	pub made_at: BlockNumber,
+}
+
+/// A registered name
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct NameRecord<AccountId, Balance> {
+	pub owner: AccountId,
+	pub deposit: Balance,
+}
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The currency in which deposits are reserved
+	type Currency: ReservableCurrency<Self::AccountId>;
+
+	/// Deposit reserved for each commitment until it is revealed or cleared
+	type CommitmentDeposit: Get<BalanceOf<Self>>;
+
+	/// Deposit reserved for each name while it is registered
+	type NameDeposit: Get<BalanceOf<Self>>;
+
+	/// Number of blocks that must pass between committing and revealing
+	type MinCommitmentAge: Get<Self::BlockNumber>;
+
+	/// Number of blocks after which an unrevealed commitment expires
+	type MaxCommitmentAge: Get<Self::BlockNumber>;
+
+	/// The longest name that may be registered
+	type MaxNameLength: Get<u32>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as NameService {
+		/// Unrevealed commitments, keyed by the committed hash
+		Commitments get(fn commitment):
+			map T::Hash => Option<Commitment<T::AccountId, BalanceOf<T>, T::BlockNumber>>;
+
+		/// Registered names
+		Names get(fn name): map Vec<u8> => Option<NameRecord<T::AccountId, BalanceOf<T>>>;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as system::Trait>::AccountId,
+		Hash = <T as system::Trait>::Hash,
+	{
+		/// An account committed to a name without revealing it
+		Committed(AccountId, Hash),
+		/// An account withdrew its commitment
+		CommitmentCancelled(AccountId, Hash),
+		/// An expired commitment was cleared; its deposit went to the given account
+		CommitmentExpired(Hash, AccountId),
+		/// An account revealed its commitment and registered a name
+		NameRegistered(AccountId, Vec<u8>),
+		/// An account gave up a name
+		NameReleased(AccountId, Vec<u8>),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		const CommitmentDeposit: BalanceOf<T> = T::CommitmentDeposit::get();
+
+		const NameDeposit: BalanceOf<T> = T::NameDeposit::get();
+
+		const MinCommitmentAge: T::BlockNumber = T::MinCommitmentAge::get();
+
+		const MaxCommitmentAge: T::BlockNumber = T::MaxCommitmentAge::get();
+
+		const MaxNameLength: u32 = T::MaxNameLength::get();
+
+		/// Commit to a name. The commitment is computed off chain with `commitment_of`.
+		fn commit(origin, commitment: T::Hash) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			ensure!(!<Commitments<T>>::exists(&commitment), "commitment already exists");
+
+			let deposit = T::CommitmentDeposit::get();
+			T::Currency::reserve(&who, deposit)
+				.map_err(|_| "cannot afford the commitment deposit")?;
+
+			let made_at = <system::Module<T>>::block_number();
+			<Commitments<T>>::insert(&commitment, Commitment { who: who.clone(), deposit, made_at });
+
+			Self::deposit_event(RawEvent::Committed(who, commitment));
+			Ok(())
+		}
+
+		/// Withdraw an unexpired commitment and get its deposit back
+		fn cancel(origin, commitment: T::Hash) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let pending = Self::commitment(&commitment).ok_or("no such commitment")?;
+			ensure!(pending.who == who, "not the committer");
+			ensure!(!Self::is_expired(&pending), "commitment has expired");
+
+			T::Currency::unreserve(&who, pending.deposit);
+			<Commitments<T>>::remove(&commitment);
+
+			Self::deposit_event(RawEvent::CommitmentCancelled(who, commitment));
+			Ok(())
+		}
+
+		/// Reveal a commitment and register the name it committed to
+		fn reveal(origin, name: Vec<u8>, salt: T::Hash) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let commitment = Self::commitment_of(&who, &name, &salt);
+			let pending = Self::commitment(&commitment).ok_or("no matching commitment")?;
+
+			let now = <system::Module<T>>::block_number();
+			ensure!(
+				now.saturating_sub(pending.made_at) >= T::MinCommitmentAge::get(),
+				"commitment is too new to reveal"
+			);
+			ensure!(!Self::is_expired(&pending), "commitment has expired");
+			ensure!(!name.is_empty(), "name is empty");
+			ensure!(name.len() as u32 <= T::MaxNameLength::get(), "name is too long");
+			ensure!(!<Names<T>>::exists(&name), "name is already registered");
+
+			// Swap the commitment deposit for the name deposit
+			T::Currency::unreserve(&who, pending.deposit);
+			let deposit = T::NameDeposit::get();
+			if T::Currency::reserve(&who, deposit).is_err() {
+				// Put things back the way they were. Re-reserving cannot fail as the funds were
+				// reserved a moment ago.
+				let _ = T::Currency::reserve(&who, pending.deposit);
+				return Err("cannot afford the name deposit".into());
+			}
+
+			<Commitments<T>>::remove(&commitment);
+			<Names<T>>::insert(&name, NameRecord { owner: who.clone(), deposit });
+
+			Self::deposit_event(RawEvent::NameRegistered(who, name));
+			Ok(())
+		}
+
+		/// Give up a name and get its deposit back
+		fn release(origin, name: Vec<u8>) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let record = Self::name(&name).ok_or("name is not registered")?;
+			ensure!(record.owner == who, "not the owner");
+
+			T::Currency::unreserve(&who, record.deposit);
+			<Names<T>>::remove(&name);
+
+			Self::deposit_event(RawEvent::NameReleased(who, name));
+			Ok(())
+		}
+
+		/// Clear an expired commitment. The caller collects the forfeited deposit.
+		fn clear_expired(origin, commitment: T::Hash) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let pending = Self::commitment(&commitment).ok_or("no such commitment")?;
+			ensure!(Self::is_expired(&pending), "commitment has not expired");
+
+			let (forfeit, _) = T::Currency::slash_reserved(&pending.who, pending.deposit);
+			T::Currency::resolve_creating(&who, forfeit);
+			<Commitments<T>>::remove(&commitment);
+
+			Self::deposit_event(RawEvent::CommitmentExpired(commitment, who));
+			Ok(())
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// The commitment an account makes to register `name`. Including the account means a copied
+	/// commitment cannot be revealed by anyone else.
+	pub fn commitment_of(who: &T::AccountId, name: &[u8], salt: &T::Hash) -> T::Hash {
+		T::Hashing::hash_of(&(who, name, salt))
+	}
+
+	fn is_expired(pending: &Commitment<T::AccountId, BalanceOf<T>, T::BlockNumber>) -> bool {
+		let now = <system::Module<T>>::block_number();
+		now.saturating_sub(pending.made_at) > T::MaxCommitmentAge::get()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use sp_core::H256;
+	use frame_support::{
+		assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types
+	};
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup},
+		Perbill,
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const ExistentialDeposit: u64 = 0;
+		pub const TransferFee: u64 = 0;
+		pub const CreationFee: u64 = 0;
+
+		pub const CommitmentDeposit: u64 = 10;
+		pub const NameDeposit: u64 = 50;
+		pub const MinCommitmentAge: u64 = 2;
+		pub const MaxCommitmentAge: u64 = 10;
+		pub const MaxNameLength: u32 = 8;
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	impl balances::Trait for TestRuntime {
+		type Balance = u64;
+		type OnFreeBalanceZero = ();
+		type OnNewAccount = ();
+		type Event = TestEvent;
+		type TransferPayment = ();
+		type DustRemoval = ();
+		type ExistentialDeposit = ExistentialDeposit;
+		type TransferFee = TransferFee;
+		type CreationFee = CreationFee;
+	}
+
+	mod name_service {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			balances<T>,
+			name_service<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type Currency = balances::Module<Self>;
+		type CommitmentDeposit = CommitmentDeposit;
+		type NameDeposit = NameDeposit;
+		type MinCommitmentAge = MinCommitmentAge;
+		type MaxCommitmentAge = MaxCommitmentAge;
+		type MaxNameLength = MaxNameLength;
+	}
+
+	pub type System = system::Module<TestRuntime>;
+	pub type Balances = balances::Module<TestRuntime>;
+	pub type NameService = Module<TestRuntime>;
+
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		let mut t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		balances::GenesisConfig::<TestRuntime> {
+			balances: vec![(1, 100), (2, 100), (3, 20)],
+			vesting: vec![],
+		}
+		.assimilate_storage(&mut t)
+		.unwrap();
+		let mut ext: sp_io::TestExternalities = t.into();
+		ext.execute_with(|| System::set_block_number(1));
+		ext
+	}
+
+	fn salt() -> H256 {
+		H256::repeat_byte(42)
+	}
+
+	/// Commit to `name` as `who` in the current block
+	fn commit(who: u64, name: &[u8]) -> H256 {
+		let commitment = NameService::commitment_of(&who, name, &salt());
+		assert_ok!(NameService::commit(Origin::signed(who), commitment));
+		commitment
+	}
+
+	#[test]
+	fn commit_then_reveal_registers() {
+		new_test_ext().execute_with(|| {
+			let commitment = commit(1, b"alice");
+			assert_eq!(Balances::reserved_balance(&1), 10);
+
+			System::set_block_number(3);
+			assert_ok!(NameService::reveal(Origin::signed(1), b"alice".to_vec(), salt()));
+			assert_eq!(NameService::name(b"alice".to_vec()), Some(NameRecord { owner: 1, deposit: 50 }));
+			assert_eq!(NameService::commitment(commitment), None);
+			// The commitment deposit was swapped for the name deposit
+			assert_eq!(Balances::reserved_balance(&1), 50);
+
+			let expected_event = TestEvent::name_service(RawEvent::NameRegistered(1, b"alice".to_vec()));
+			assert!(System::events().iter().any(|a| a.event == expected_event));
+		})
+	}
+
+	#[test]
+	fn cannot_reveal_too_early() {
+		new_test_ext().execute_with(|| {
+			commit(1, b"alice");
+			System::set_block_number(2);
+			assert_err!(
+				NameService::reveal(Origin::signed(1), b"alice".to_vec(), salt()),
+				"commitment is too new to reveal"
+			);
+		})
+	}
+
+	#[test]
+	fn reveal_must_match_commitment() {
+		new_test_ext().execute_with(|| {
+			commit(1, b"alice");
+			System::set_block_number(3);
+			assert_err!(
+				NameService::reveal(Origin::signed(1), b"alice".to_vec(), H256::repeat_byte(7)),
+				"no matching commitment"
+			);
+			assert_err!(
+				NameService::reveal(Origin::signed(1), b"bob".to_vec(), salt()),
+				"no matching commitment"
+			);
+		})
+	}
+
+	#[test]
+	fn frontrunner_cannot_steal_name() {
+		new_test_ext().execute_with(|| {
+			let commitment = commit(1, b"alice");
+			// Copying the commitment doesn't help: it is already taken
+			assert!(NameService::commit(Origin::signed(2), commitment).is_err());
+
+			// Having seen the reveal, the frontrunner's own commitment is too new
+			System::set_block_number(3);
+			commit(2, b"alice");
+			assert_ok!(NameService::reveal(Origin::signed(1), b"alice".to_vec(), salt()));
+			assert!(NameService::reveal(Origin::signed(2), b"alice".to_vec(), salt()).is_err());
+
+			// Even after waiting, the name is taken
+			System::set_block_number(5);
+			assert_err!(
+				NameService::reveal(Origin::signed(2), b"alice".to_vec(), salt()),
+				"name is already registered"
+			);
+		})
+	}
+
+	#[test]
+	fn names_are_checked() {
+		new_test_ext().execute_with(|| {
+			commit(1, b"");
+			commit(1, b"toolongname");
+			System::set_block_number(3);
+			assert_err!(NameService::reveal(Origin::signed(1), vec![], salt()), "name is empty");
+			assert_err!(
+				NameService::reveal(Origin::signed(1), b"toolongname".to_vec(), salt()),
+				"name is too long"
+			);
+		})
+	}
+
+	#[test]
+	fn reveal_needs_name_deposit() {
+		new_test_ext().execute_with(|| {
+			let commitment = commit(3, b"carol");
+			System::set_block_number(3);
+			assert_err!(
+				NameService::reveal(Origin::signed(3), b"carol".to_vec(), salt()),
+				"cannot afford the name deposit"
+			);
+			// The commitment and its deposit are untouched
+			assert!(NameService::commitment(commitment).is_some());
+			assert_eq!(Balances::reserved_balance(&3), 10);
+		})
+	}
+
+	#[test]
+	fn expired_commitments_cannot_be_revealed() {
+		new_test_ext().execute_with(|| {
+			commit(1, b"alice");
+			System::set_block_number(12);
+			assert_err!(
+				NameService::reveal(Origin::signed(1), b"alice".to_vec(), salt()),
+				"commitment has expired"
+			);
+		})
+	}
+
+	#[test]
+	fn anyone_can_clear_expired_commitments() {
+		new_test_ext().execute_with(|| {
+			let commitment = commit(1, b"alice");
+			System::set_block_number(11);
+			assert_err!(
+				NameService::clear_expired(Origin::signed(2), commitment),
+				"commitment has not expired"
+			);
+
+			System::set_block_number(12);
+			assert_ok!(NameService::clear_expired(Origin::signed(2), commitment));
+			assert_eq!(NameService::commitment(commitment), None);
+			assert_eq!(Balances::total_balance(&1), 90);
+			assert_eq!(Balances::free_balance(&2), 110);
+
+			let expected_event = TestEvent::name_service(RawEvent::CommitmentExpired(commitment, 2));
+			assert!(System::events().iter().any(|a| a.event == expected_event));
+		})
+	}
+
+	#[test]
+	fn cancel_refunds_deposit() {
+		new_test_ext().execute_with(|| {
+			let commitment = commit(1, b"alice");
+			assert_err!(NameService::cancel(Origin::signed(2), commitment), "not the committer");
+			assert_ok!(NameService::cancel(Origin::signed(1), commitment));
+			assert_eq!(NameService::commitment(commitment), None);
+			assert_eq!(Balances::free_balance(&1), 100);
+		})
+	}
+
+	#[test]
+	fn release_refunds_deposit() {
+		new_test_ext().execute_with(|| {
+			commit(1, b"alice");
+			System::set_block_number(3);
+			assert_ok!(NameService::reveal(Origin::signed(1), b"alice".to_vec(), salt()));
+
+			assert_err!(NameService::release(Origin::signed(2), b"alice".to_vec()), "not the owner");
+			assert_ok!(NameService::release(Origin::signed(1), b"alice".to_vec()));
+			assert_eq!(NameService::name(b"alice".to_vec()), None);
+			assert_eq!(Balances::free_balance(&1), 100);
+		})
+	}
+}
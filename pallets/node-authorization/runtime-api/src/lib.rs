@@ -0,0 +1,10 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use sp_std::vec::Vec;
+
+sp_api::decl_runtime_apis! {
+	pub trait NodeAuthorizationApi {
+		/// The peers that `node` may connect to. Nodes and peers are the bytes of libp2p peer ids.
+		fn authorized_peers(node: Vec<u8>) -> Vec<Vec<u8>>;
+	}
+}
@@ -0,0 +1,449 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! Node authorization for permissioned networks
+//!
+//! A permissioned chain also wants to control who joins its network, not only who authors its
+//! blocks. This pallet records which nodes, identified by their libp2p peer ids, may connect to
+//! which. Nodes read it through the `NodeAuthorizationApi` runtime API and reserve connections for
+//! the authorized peers.
+//!
+//! * Well-known nodes are added and removed by `ManageOrigin`. They may all connect to each other.
+//! * Anybody may claim an unknown node, to become its owner. Governance owns no nodes, but names an
+//!   owner for every well-known node it adds.
+//! * The owner of a node adds further connections for it, e.g. for a sentry or an observer that
+//!   should sync from it without being well-known.
+
+use sp_std::prelude::*;
+use frame_support::{
+	decl_event, decl_module, decl_storage,
+	dispatch::DispatchResult,
+	ensure,
+	traits::{EnsureOrigin, Get},
+	StorageMap, StorageValue,
+};
+use frame_system::{self as system, ensure_signed};
+
+/// The bytes of a libp2p peer id
+pub type PeerId = Vec<u8>;
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The origin that adds and removes well-known nodes
+	type ManageOrigin: EnsureOrigin<Self::Origin>;
+
+	/// The maximum number of well-known nodes
+	type MaxWellKnownNodes: Get<u32>;
+
+	/// The maximum number of additional connections of a node
+	type MaxConnections: Get<u32>;
+
+	/// The maximum length of a peer id, in bytes
+	type MaxPeerIdLength: Get<u32>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as NodeAuthorization {
+		/// The well-known nodes, sorted
+		WellKnownNodes get(fn well_known_nodes): Vec<PeerId>;
+
+		/// The account that owns a node
+		Owners get(fn owner): map PeerId => Option<T::AccountId>;
+
+		/// The peers a node may connect to besides the well-known nodes
+		AdditionalConnections get(fn additional_connections): map PeerId => Vec<PeerId>;
+	}
+	add_extra_genesis {
+		/// The initial well-known nodes and their owners
+		config(nodes): Vec<(PeerId, T::AccountId)>;
+		build(|config: &GenesisConfig<T>| {
+			let mut nodes = config.nodes.iter().map(|(node, _)| node.clone()).collect::<Vec<_>>();
+			nodes.sort();
+			nodes.dedup();
+			WellKnownNodes::put(nodes);
+			for (node, owner) in &config.nodes {
+				<Owners<T>>::insert(node, owner);
+			}
+		});
+	}
+}
+
+decl_event!(
+	pub enum Event<T> where AccountId = <T as system::Trait>::AccountId {
+		/// A node became well-known, with its owner
+		NodeAdded(PeerId, AccountId),
+		/// A node is no longer well-known, and lost its owner and connections
+		NodeRemoved(PeerId),
+		/// An account claimed a node
+		NodeClaimed(PeerId, AccountId),
+		/// An owner gave up its claim on a node
+		ClaimRemoved(PeerId, AccountId),
+		/// A node was transferred to a new owner
+		NodeTransferred(PeerId, AccountId),
+		/// The additional connections of a node changed
+		ConnectionsChanged(PeerId),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		/// The maximum number of well-known nodes
+		const MaxWellKnownNodes: u32 = T::MaxWellKnownNodes::get();
+
+		/// The maximum number of additional connections of a node
+		const MaxConnections: u32 = T::MaxConnections::get();
+
+		/// The maximum length of a peer id, in bytes
+		const MaxPeerIdLength: u32 = T::MaxPeerIdLength::get();
+
+		/// Make `node` well-known, owned by `owner`
+		fn add_well_known_node(origin, node: PeerId, owner: T::AccountId) -> DispatchResult {
+			T::ManageOrigin::ensure_origin(origin)?;
+			Self::ensure_valid(&node)?;
+			let mut nodes = Self::well_known_nodes();
+			let index = nodes.binary_search(&node).err().ok_or("already a well-known node")?;
+			ensure!(
+				(nodes.len() as u32) < T::MaxWellKnownNodes::get(),
+				"too many well-known nodes"
+			);
+			if let Some(claimant) = Self::owner(&node) {
+				ensure!(claimant == owner, "node is claimed by another account");
+			}
+
+			nodes.insert(index, node.clone());
+			WellKnownNodes::put(nodes);
+			<Owners<T>>::insert(&node, &owner);
+			Self::deposit_event(RawEvent::NodeAdded(node, owner));
+			Ok(())
+		}
+
+		/// Make `node` an unknown node again, without owner or connections
+		fn remove_well_known_node(origin, node: PeerId) -> DispatchResult {
+			T::ManageOrigin::ensure_origin(origin)?;
+			let mut nodes = Self::well_known_nodes();
+			let index = nodes.binary_search(&node).map_err(|_| "not a well-known node")?;
+
+			nodes.remove(index);
+			WellKnownNodes::put(nodes);
+			<Owners<T>>::remove(&node);
+			AdditionalConnections::remove(&node);
+			Self::deposit_event(RawEvent::NodeRemoved(node));
+			Ok(())
+		}
+
+		/// Become the owner of a node that nobody owns
+		fn claim_node(origin, node: PeerId) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			Self::ensure_valid(&node)?;
+			ensure!(!<Owners<T>>::exists(&node), "node already claimed");
+
+			<Owners<T>>::insert(&node, &who);
+			Self::deposit_event(RawEvent::NodeClaimed(node, who));
+			Ok(())
+		}
+
+		/// Give up the claim on a node, and its connections
+		///
+		/// Well-known nodes keep their owner until governance removes them.
+		fn remove_claim(origin, node: PeerId) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			Self::ensure_owner(&who, &node)?;
+			ensure!(
+				Self::well_known_nodes().binary_search(&node).is_err(),
+				"well-known nodes are removed by governance"
+			);
+
+			<Owners<T>>::remove(&node);
+			AdditionalConnections::remove(&node);
+			Self::deposit_event(RawEvent::ClaimRemoved(node, who));
+			Ok(())
+		}
+
+		/// Hand a node over to another owner
+		fn transfer_node(origin, node: PeerId, owner: T::AccountId) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			Self::ensure_owner(&who, &node)?;
+
+			<Owners<T>>::insert(&node, &owner);
+			Self::deposit_event(RawEvent::NodeTransferred(node, owner));
+			Ok(())
+		}
+
+		/// Let `node` connect to `peers`
+		fn add_connections(origin, node: PeerId, peers: Vec<PeerId>) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			Self::ensure_owner(&who, &node)?;
+			let mut connections = Self::additional_connections(&node);
+			for peer in peers {
+				Self::ensure_valid(&peer)?;
+				if peer != node && !connections.contains(&peer) {
+					connections.push(peer);
+				}
+			}
+			ensure!(
+				(connections.len() as u32) <= T::MaxConnections::get(),
+				"too many connections"
+			);
+
+			AdditionalConnections::insert(&node, connections);
+			Self::deposit_event(RawEvent::ConnectionsChanged(node));
+			Ok(())
+		}
+
+		/// No longer let `node` connect to `peers`, unless they are well-known
+		fn remove_connections(origin, node: PeerId, peers: Vec<PeerId>) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			Self::ensure_owner(&who, &node)?;
+
+			AdditionalConnections::mutate(&node, |connections| {
+				connections.retain(|peer| !peers.contains(peer))
+			});
+			Self::deposit_event(RawEvent::ConnectionsChanged(node));
+			Ok(())
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// The peers that `node` may connect to: the other well-known nodes if it is well-known
+	/// itself, and its additional connections
+	pub fn authorized_peers(node: &PeerId) -> Vec<PeerId> {
+		let well_known = Self::well_known_nodes();
+		let mut peers = if well_known.binary_search(node).is_ok() {
+			well_known.into_iter().filter(|peer| peer != node).collect()
+		} else {
+			Vec::new()
+		};
+		for peer in Self::additional_connections(node) {
+			if !peers.contains(&peer) {
+				peers.push(peer);
+			}
+		}
+		peers
+	}
+
+	fn ensure_valid(node: &PeerId) -> DispatchResult {
+		ensure!(!node.is_empty(), "empty peer id");
+		ensure!(node.len() as u32 <= T::MaxPeerIdLength::get(), "peer id too long");
+		Ok(())
+	}
+
+	fn ensure_owner(who: &T::AccountId, node: &PeerId) -> DispatchResult {
+		ensure!(Self::owner(node).as_ref() == Some(who), "not the owner of the node");
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use event_assert::assert_last_event;
+
+	use sp_core::H256;
+	use frame_support::{
+		assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types
+	};
+	use frame_system::{EnsureRoot, RawOrigin};
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup},
+		Perbill,
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	mod node_authorization {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			node_authorization<T>,
+		}
+	}
+
+	parameter_types! {
+		pub const MaxWellKnownNodes: u32 = 3;
+		pub const MaxConnections: u32 = 2;
+		pub const MaxPeerIdLength: u32 = 8;
+	}
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type ManageOrigin = EnsureRoot<u64>;
+		type MaxWellKnownNodes = MaxWellKnownNodes;
+		type MaxConnections = MaxConnections;
+		type MaxPeerIdLength = MaxPeerIdLength;
+	}
+
+	pub type NodeAuthorization = Module<TestRuntime>;
+
+	/// Nodes 10 and 20 are well-known, owned by accounts 1 and 2
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		let mut t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		GenesisConfig::<TestRuntime> {
+			nodes: vec![(node(20), 2), (node(10), 1)],
+		}
+		.assimilate_storage(&mut t)
+		.unwrap();
+		t.into()
+	}
+
+	fn node(id: u8) -> PeerId {
+		vec![id]
+	}
+
+	fn root() -> Origin {
+		RawOrigin::Root.into()
+	}
+
+	#[test]
+	fn genesis_nodes_know_each_other() {
+		new_test_ext().execute_with(|| {
+			assert_eq!(NodeAuthorization::well_known_nodes(), vec![node(10), node(20)]);
+			assert_eq!(NodeAuthorization::owner(node(20)), Some(2));
+			assert_eq!(NodeAuthorization::authorized_peers(&node(10)), vec![node(20)]);
+			assert!(NodeAuthorization::authorized_peers(&node(30)).is_empty());
+		})
+	}
+
+	#[test]
+	fn governance_adds_and_removes_well_known_nodes() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(NodeAuthorization::add_well_known_node(root(), node(15), 3));
+			assert_eq!(NodeAuthorization::well_known_nodes(), vec![node(10), node(15), node(20)]);
+			assert_eq!(NodeAuthorization::authorized_peers(&node(15)), vec![node(10), node(20)]);
+			assert_err!(
+				NodeAuthorization::add_well_known_node(root(), node(15), 3),
+				"already a well-known node"
+			);
+			assert_err!(
+				NodeAuthorization::add_well_known_node(root(), node(30), 3),
+				"too many well-known nodes"
+			);
+
+			assert_ok!(NodeAuthorization::remove_well_known_node(root(), node(10)));
+			assert_eq!(NodeAuthorization::owner(node(10)), None);
+			assert_eq!(NodeAuthorization::authorized_peers(&node(15)), vec![node(20)]);
+			assert_last_event::<TestRuntime>(RawEvent::NodeRemoved(node(10)));
+			assert_err!(
+				NodeAuthorization::remove_well_known_node(root(), node(10)),
+				"not a well-known node"
+			);
+		})
+	}
+
+	#[test]
+	fn only_manage_origin_changes_well_known_nodes() {
+		new_test_ext().execute_with(|| {
+			let signed = || Origin::signed(1);
+			assert!(NodeAuthorization::add_well_known_node(signed(), node(30), 1).is_err());
+			assert!(NodeAuthorization::remove_well_known_node(signed(), node(10)).is_err());
+			assert_eq!(NodeAuthorization::well_known_nodes(), vec![node(10), node(20)]);
+		})
+	}
+
+	#[test]
+	fn claims_can_be_made_transferred_and_removed() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(NodeAuthorization::claim_node(Origin::signed(3), node(30)));
+			assert_eq!(NodeAuthorization::owner(node(30)), Some(3));
+			assert_err!(
+				NodeAuthorization::claim_node(Origin::signed(4), node(30)),
+				"node already claimed"
+			);
+			assert_err!(
+				NodeAuthorization::claim_node(Origin::signed(4), vec![0; 9]),
+				"peer id too long"
+			);
+
+			assert_err!(
+				NodeAuthorization::transfer_node(Origin::signed(4), node(30), 4),
+				"not the owner of the node"
+			);
+			assert_ok!(NodeAuthorization::transfer_node(Origin::signed(3), node(30), 4));
+			assert_eq!(NodeAuthorization::owner(node(30)), Some(4));
+
+			assert_ok!(NodeAuthorization::remove_claim(Origin::signed(4), node(30)));
+			assert_eq!(NodeAuthorization::owner(node(30)), None);
+			assert_err!(
+				NodeAuthorization::remove_claim(Origin::signed(1), node(10)),
+				"well-known nodes are removed by governance"
+			);
+		})
+	}
+
+	#[test]
+	fn claimed_nodes_only_become_well_known_for_their_owner() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(NodeAuthorization::claim_node(Origin::signed(3), node(30)));
+			assert_err!(
+				NodeAuthorization::add_well_known_node(root(), node(30), 4),
+				"node is claimed by another account"
+			);
+			assert_ok!(NodeAuthorization::add_well_known_node(root(), node(30), 3));
+		})
+	}
+
+	#[test]
+	fn owners_add_and_remove_connections() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(NodeAuthorization::add_connections(
+				Origin::signed(1),
+				node(10),
+				vec![node(30), node(20), node(10)],
+			));
+			assert_eq!(NodeAuthorization::authorized_peers(&node(10)), vec![node(20), node(30)]);
+			assert_err!(
+				NodeAuthorization::add_connections(Origin::signed(1), node(10), vec![node(40)]),
+				"too many connections"
+			);
+			assert_err!(
+				NodeAuthorization::add_connections(Origin::signed(2), node(10), vec![node(40)]),
+				"not the owner of the node"
+			);
+
+			assert_ok!(NodeAuthorization::remove_connections(
+				Origin::signed(1),
+				node(10),
+				vec![node(20), node(30)],
+			));
+			// Node 20 is still well-known
+			assert_eq!(NodeAuthorization::authorized_peers(&node(10)), vec![node(20)]);
+		})
+	}
+}
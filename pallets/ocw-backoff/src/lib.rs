@@ -0,0 +1,458 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! An offchain worker that copes with flaky HTTP endpoints
+//!
+//! After every block the offchain worker fetches a price from the first of several endpoints that
+//! is willing to answer, and submits it as an unsigned transaction. Endpoints fail, and a worker
+//! that keeps hammering a failing endpoint wastes its time and the endpoint's. So the worker
+//! tracks the health of each endpoint in the node's offchain storage:
+//! * After a failure, it waits before trying that endpoint again, twice as long after every
+//!   further failure: 1, 2, 4, ... blocks. This is *exponential backoff*.
+//! * After `FailureThreshold` failures in a row, the *circuit breaker* opens and the endpoint is
+//!   skipped for `Cooldown` blocks. Then it gets one trial request, which either closes the
+//!   circuit or opens it for another cooldown.
+//! * A success forgets all failures.
+//!
+//! Offchain storage belongs to the node, not to the chain, so every node keeps its own view of
+//! the endpoints' health, and none of it costs any on-chain storage.
+//!
+//! The price can be submitted by anyone, because the transaction is unsigned. A real price feed
+//! would accept prices only from known accounts or keys, as the `im-online-lite` pallet does.
+
+use codec::{Decode, Encode};
+use frame_support::{
+	debug, decl_event, decl_module, decl_storage, dispatch::DispatchResult, ensure, traits::Get,
+};
+use frame_system::{self as system, ensure_none, offchain::SubmitUnsignedTransaction};
+use ocw_json::PricePoint;
+use sp_core::offchain::{Duration, StorageKind};
+use sp_runtime::{
+	offchain::http,
+	transaction_validity::{InvalidTransaction, TransactionValidity, ValidTransaction},
+	RuntimeDebug,
+};
+use sp_std::prelude::*;
+
+/// The target of this pallet's log messages
+const LOG_TARGET: &str = "ocw-backoff";
+
+/// How long a single request may take, in milliseconds
+pub const FETCH_TIMEOUT_MS: u64 = 2_000;
+
+/// The prefix of the offchain storage keys that hold the health of each endpoint
+const HEALTH_PREFIX: &[u8] = b"ocw-backoff::health::";
+
+/// Why fetching from an endpoint failed
+#[derive(Clone, Copy, PartialEq, Eq, RuntimeDebug)]
+pub enum FetchError {
+	/// The request could not be sent, or the connection failed
+	Http,
+	/// No response before the deadline
+	Timeout,
+	/// The endpoint answered with a status other than 200
+	Status(u16),
+	/// The body is not a price
+	BadPayload,
+}
+
+/// What a node remembers about an endpoint, in its offchain storage
+#[derive(Encode, Decode, Clone, Default, PartialEq, Eq, RuntimeDebug)]
+pub struct EndpointHealth<BlockNumber> {
+	/// Failures since the last success
+	pub failures: u32,
+	/// The endpoint is not asked again before this block
+	pub retry_at: BlockNumber,
+}
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The overarching call type, which the offchain worker wraps prices in
+	type Call: From<Call<Self>>;
+
+	/// Submits prices from the offchain worker as unsigned transactions
+	type SubmitTransaction: SubmitUnsignedTransaction<Self, <Self as Trait>::Call>;
+
+	/// The URLs to fetch prices from, most preferred first
+	type Endpoints: Get<&'static [&'static str]>;
+
+	/// The number of failures in a row after which an endpoint's circuit opens
+	type FailureThreshold: Get<u32>;
+
+	/// How many blocks an endpoint is skipped while its circuit is open
+	type Cooldown: Get<Self::BlockNumber>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as OcwBackoff {
+		/// The most recently submitted price
+		LatestPrice get(fn latest_price): Option<PricePoint>;
+
+		/// The block in which `LatestPrice` was submitted
+		UpdatedAt get(fn updated_at): Option<T::BlockNumber>;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		BlockNumber = <T as system::Trait>::BlockNumber,
+	{
+		/// A new price was submitted in this block, as (symbol, price, block)
+		PriceUpdated(Vec<u8>, u128, BlockNumber),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		const FailureThreshold: u32 = T::FailureThreshold::get();
+		const Cooldown: T::BlockNumber = T::Cooldown::get();
+
+		/// Record a price fetched by an offchain worker. At most one price per block.
+		fn submit_price(origin, point: PricePoint) -> DispatchResult {
+			ensure_none(origin)?;
+			let now = <system::Module<T>>::block_number();
+			ensure!(Self::updated_at() != Some(now), "price already submitted in this block");
+
+			LatestPrice::put(&point);
+			<UpdatedAt<T>>::put(now);
+			Self::deposit_event(RawEvent::PriceUpdated(point.symbol, point.price, now));
+			Ok(())
+		}
+
+		fn offchain_worker(now: T::BlockNumber) {
+			debug::RuntimeLogger::init();
+			match Self::fetch_with_backoff(now, Self::fetch_price) {
+				Some(point) => {
+					if T::SubmitTransaction::submit_unsigned(Call::submit_price(point)).is_err() {
+						log::debug!(target: LOG_TARGET, "price not submitted");
+					}
+				}
+				None => log::warn!(target: LOG_TARGET, "no endpoint delivered a price in block {:?}", now),
+			}
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// Ask the endpoints in order until one answers, skipping those that are backing off or whose
+	/// circuit is open, and update their health. `fetch` does the actual request, so that tests
+	/// can replace it.
+	pub fn fetch_with_backoff<R>(
+		now: T::BlockNumber,
+		fetch: impl Fn(&str) -> Result<R, FetchError>,
+	) -> Option<R> {
+		for &endpoint in T::Endpoints::get() {
+			let mut health = Self::endpoint_health(endpoint);
+			if now < health.retry_at {
+				continue;
+			}
+
+			match fetch(endpoint) {
+				Ok(result) => {
+					if health.failures > 0 {
+						Self::set_endpoint_health(endpoint, &Default::default());
+					}
+					return Some(result);
+				}
+				Err(error) => {
+					health.failures = health.failures.saturating_add(1);
+					health.retry_at = now + Self::delay_after(health.failures);
+					log::info!(
+						target: LOG_TARGET,
+						"{} failed with {:?}, {} failures in a row, next try in block {:?}",
+						endpoint, error, health.failures, health.retry_at,
+					);
+					Self::set_endpoint_health(endpoint, &health);
+				}
+			}
+		}
+		None
+	}
+
+	/// How long to leave an endpoint alone after its `failures`th failure in a row. Doubles with
+	/// every failure until the circuit opens, so the cooldown also caps the backoff.
+	pub fn delay_after(failures: u32) -> T::BlockNumber {
+		if failures >= T::FailureThreshold::get() {
+			T::Cooldown::get()
+		} else {
+			2u32.saturating_pow(failures.saturating_sub(1)).into()
+		}
+	}
+
+	/// The health of `endpoint` as this node last saw it
+	pub fn endpoint_health(endpoint: &str) -> EndpointHealth<T::BlockNumber> {
+		sp_io::offchain::local_storage_get(StorageKind::PERSISTENT, &Self::health_key(endpoint))
+			.and_then(|raw| Decode::decode(&mut &raw[..]).ok())
+			.unwrap_or_default()
+	}
+
+	fn set_endpoint_health(endpoint: &str, health: &EndpointHealth<T::BlockNumber>) {
+		sp_io::offchain::local_storage_set(
+			StorageKind::PERSISTENT,
+			&Self::health_key(endpoint),
+			&health.encode(),
+		);
+	}
+
+	fn health_key(endpoint: &str) -> Vec<u8> {
+		let mut key = HEALTH_PREFIX.to_vec();
+		key.extend_from_slice(endpoint.as_bytes());
+		key
+	}
+
+	/// Fetch a price over HTTP, giving up after `FETCH_TIMEOUT_MS`
+	fn fetch_price(endpoint: &str) -> Result<PricePoint, FetchError> {
+		let deadline = sp_io::offchain::timestamp().add(Duration::from_millis(FETCH_TIMEOUT_MS));
+		let pending = http::Request::get(endpoint)
+			.deadline(deadline)
+			.send()
+			.map_err(|_| FetchError::Http)?;
+		let response = pending
+			.try_wait(deadline)
+			.map_err(|_| FetchError::Timeout)?
+			.map_err(|_| FetchError::Http)?;
+		if response.code != 200 {
+			return Err(FetchError::Status(response.code));
+		}
+
+		let body = response.body().collect::<Vec<u8>>();
+		PricePoint::from_json(&body).map_err(|_| FetchError::BadPayload)
+	}
+}
+
+impl<T: Trait> frame_support::unsigned::ValidateUnsigned for Module<T> {
+	type Call = Call<T>;
+
+	fn validate_unsigned(call: &Self::Call) -> TransactionValidity {
+		match call {
+			Call::submit_price(_) => {
+				let now = <system::Module<T>>::block_number();
+				if Self::updated_at() == Some(now) {
+					return InvalidTransaction::Stale.into();
+				}
+				Ok(ValidTransaction {
+					priority: 0,
+					requires: vec![],
+					// Every worker submits a price after every block. The tag lets the pool keep
+					// only one of them per block.
+					provides: vec![(b"ocw-backoff", now).encode()],
+					longevity: 5,
+					propagate: true,
+				})
+			}
+			_ => InvalidTransaction::Call.into(),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use event_assert::assert_last_event;
+	use frame_support::{
+		assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types,
+		unsigned::ValidateUnsigned,
+	};
+	use frame_system::{offchain::TransactionSubmitter, RawOrigin};
+	use sp_core::{
+		offchain::{testing::TestOffchainExt, OffchainExt},
+		H256,
+	};
+	use sp_runtime::{
+		testing::{Header, TestXt},
+		traits::{BlakeTwo256, IdentityLookup},
+		Perbill,
+	};
+	use std::cell::RefCell;
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const Endpoints: &'static [&'static str] = &[PRIMARY, BACKUP];
+		pub const FailureThreshold: u32 = 4;
+		pub const Cooldown: u64 = 20;
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	mod ocw_backoff {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			ocw_backoff<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type Call = Call<TestRuntime>;
+		type SubmitTransaction = TransactionSubmitter<(), TestRuntime, TestXt<Call<TestRuntime>, ()>>;
+		type Endpoints = Endpoints;
+		type FailureThreshold = FailureThreshold;
+		type Cooldown = Cooldown;
+	}
+
+	pub type System = system::Module<TestRuntime>;
+	pub type OcwBackoff = Module<TestRuntime>;
+
+	const PRIMARY: &str = "http://primary.test/price";
+	const BACKUP: &str = "http://backup.test/price";
+
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		let t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		let mut ext: sp_io::TestExternalities = t.into();
+		let (offchain, _state) = TestOffchainExt::new();
+		ext.register_extension(OffchainExt::new(offchain));
+		ext
+	}
+
+	/// Runs the worker's fetch loop in block `now` against endpoints that answer as `up` says,
+	/// and returns the endpoints it asked and the endpoint that answered
+	fn run(now: u64, up: impl Fn(&str) -> bool) -> (Vec<&'static str>, Option<&'static str>) {
+		let asked = RefCell::new(Vec::new());
+		let answered = OcwBackoff::fetch_with_backoff(now, |endpoint| {
+			let endpoint = if endpoint == PRIMARY { PRIMARY } else { BACKUP };
+			asked.borrow_mut().push(endpoint);
+			if up(endpoint) {
+				Ok(endpoint)
+			} else {
+				Err(FetchError::Timeout)
+			}
+		});
+		(asked.into_inner(), answered)
+	}
+
+	fn price(price: u128) -> PricePoint {
+		PricePoint { symbol: b"DOT".to_vec(), price, timestamp: 0 }
+	}
+
+	#[test]
+	fn healthy_primary_is_used() {
+		new_test_ext().execute_with(|| {
+			assert_eq!(run(1, |_| true), (vec![PRIMARY], Some(PRIMARY)));
+			assert_eq!(OcwBackoff::endpoint_health(PRIMARY), EndpointHealth::default());
+		})
+	}
+
+	#[test]
+	fn failure_falls_back_to_next_endpoint() {
+		new_test_ext().execute_with(|| {
+			assert_eq!(run(1, |e| e == BACKUP), (vec![PRIMARY, BACKUP], Some(BACKUP)));
+			assert_eq!(OcwBackoff::endpoint_health(PRIMARY), EndpointHealth { failures: 1, retry_at: 2 });
+			assert_eq!(OcwBackoff::endpoint_health(BACKUP), EndpointHealth::default());
+
+			assert_eq!(run(1, |_| false), (vec![BACKUP], None));
+		})
+	}
+
+	#[test]
+	fn backoff_doubles_with_every_failure() {
+		new_test_ext().execute_with(|| {
+			let primary_down = |e: &str| e == BACKUP;
+			// Fails in block 10, skipped until 11, fails, skipped until 13, fails, ...
+			let mut tried_in = vec![];
+			for now in 10..=25 {
+				if run(now, primary_down).0.contains(&PRIMARY) {
+					tried_in.push(now);
+				}
+			}
+			assert_eq!(tried_in, vec![10, 11, 13, 17]);
+			// The fourth failure opened the circuit
+			assert_eq!(OcwBackoff::endpoint_health(PRIMARY), EndpointHealth { failures: 4, retry_at: 37 });
+		})
+	}
+
+	#[test]
+	fn circuit_breaker_skips_then_tries_once() {
+		new_test_ext().execute_with(|| {
+			for now in &[1, 2, 4, 8] {
+				run(*now, |e| e == BACKUP);
+			}
+			// Open: the primary is not asked during the cooldown, even though it is up again
+			assert_eq!(OcwBackoff::endpoint_health(PRIMARY).retry_at, 28);
+			assert_eq!(run(27, |_| true), (vec![BACKUP], Some(BACKUP)));
+
+			// A failed trial opens the circuit for another cooldown
+			assert_eq!(run(28, |e| e == BACKUP).1, Some(BACKUP));
+			assert_eq!(OcwBackoff::endpoint_health(PRIMARY), EndpointHealth { failures: 5, retry_at: 48 });
+
+			// A successful trial closes it
+			assert_eq!(run(48, |_| true), (vec![PRIMARY], Some(PRIMARY)));
+			assert_eq!(OcwBackoff::endpoint_health(PRIMARY), EndpointHealth::default());
+			assert_eq!(run(49, |_| true).1, Some(PRIMARY));
+		})
+	}
+
+	#[test]
+	fn delays_are_capped_by_cooldown() {
+		assert_eq!(
+			(1..=6).map(OcwBackoff::delay_after).collect::<Vec<_>>(),
+			vec![1, 2, 4, 20, 20, 20]
+		);
+		assert_eq!(OcwBackoff::delay_after(u32::max_value()), 20);
+	}
+
+	#[test]
+	fn one_price_per_block() {
+		new_test_ext().execute_with(|| {
+			System::set_block_number(3);
+			let call = Call::submit_price(price(7));
+			assert!(OcwBackoff::validate_unsigned(&call).is_ok());
+			assert_ok!(OcwBackoff::submit_price(RawOrigin::None.into(), price(7)));
+			assert_eq!(OcwBackoff::latest_price(), Some(price(7)));
+			assert_last_event::<TestRuntime>(RawEvent::PriceUpdated(b"DOT".to_vec(), 7, 3));
+
+			let stale: TransactionValidity = InvalidTransaction::Stale.into();
+			assert_eq!(OcwBackoff::validate_unsigned(&call), stale);
+			assert_err!(
+				OcwBackoff::submit_price(RawOrigin::None.into(), price(8)),
+				"price already submitted in this block"
+			);
+
+			System::set_block_number(4);
+			assert!(OcwBackoff::validate_unsigned(&call).is_ok());
+		})
+	}
+
+	#[test]
+	fn prices_are_unsigned() {
+		new_test_ext().execute_with(|| {
+			assert!(OcwBackoff::submit_price(Origin::signed(1), price(7)).is_err());
+		})
+	}
+}
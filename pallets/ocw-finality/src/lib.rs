@@ -0,0 +1,463 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! An offchain worker that does not trust the latest block
+//!
+//! Users request a computation on chain, and offchain workers compute the result and submit it as
+//! an unsigned transaction. Offchain workers run on the node's best block, which is not final: a
+//! reorg can replace it, together with any request it contained. A worker that acts on every best
+//! block submits results for requests that are later reorged out, and submits again when the
+//! request is included in the new fork.
+//!
+//! At this version of Substrate the runtime cannot see which block is finalized, or whether the
+//! node is still syncing, so this pallet protects itself with what the runtime does know:
+//! * The worker waits until a request is `Confirmations` blocks deep before it acts. The deeper a
+//!   block, the less likely it is to be reorged out.
+//! * Every result names the hash of the block that contained the request, its *anchor*. A result
+//!   whose anchor is not on the chain it is submitted to is stale, so a result computed on an
+//!   abandoned fork can never fulfil the request of the same id on the new fork.
+//! * The worker remembers in offchain storage which requests it has submitted for, and for which
+//!   anchor, so it submits once per request and fork instead of once per block.
+
+use codec::{Decode, Encode};
+use frame_support::{
+	debug, decl_event, decl_module, decl_storage, dispatch::DispatchResult, ensure, traits::Get,
+};
+use frame_system::{self as system, ensure_none, ensure_signed, offchain::SubmitUnsignedTransaction};
+use sp_core::offchain::StorageKind;
+use sp_runtime::{
+	traits::Saturating,
+	transaction_validity::{InvalidTransaction, TransactionValidity, ValidTransaction},
+	RuntimeDebug,
+};
+use sp_std::prelude::*;
+
+/// The target of this pallet's log messages
+const LOG_TARGET: &str = "ocw-finality";
+
+/// The prefix of the offchain storage keys that record submissions
+const SUBMITTED_PREFIX: &[u8] = b"ocw-finality::submitted::";
+
+/// How many blocks a worker waits for its result to be included before it submits again
+pub const RESUBMIT_AFTER: u32 = 20;
+
+/// How many blocks a result stays in the pool. Shorter than `RESUBMIT_AFTER`, so that a result is
+/// never submitted again while the previous copy is still waiting.
+const LONGEVITY: u64 = 16;
+
+/// Identifies a request
+pub type RequestId = u32;
+
+/// A request for the worker to compute
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct Request<BlockNumber> {
+	/// The block that contains the request
+	pub made_at: BlockNumber,
+	/// Compute the `input`th Fibonacci number
+	pub input: u32,
+}
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The overarching call type, which the offchain worker wraps results in
+	type Call: From<Call<Self>>;
+
+	/// Submits results from the offchain worker as unsigned transactions
+	type SubmitTransaction: SubmitUnsignedTransaction<Self, <Self as Trait>::Call>;
+
+	/// How many blocks must follow the block of a request before the worker acts on it. At least
+	/// one, because the hash of a block is only known once the next block is built.
+	type Confirmations: Get<Self::BlockNumber>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as OcwFinality {
+		/// The id of the next request
+		NextRequestId get(fn next_request_id): RequestId;
+
+		/// Requests that have not been fulfilled yet
+		Requests get(fn requests): map RequestId => Option<Request<T::BlockNumber>>;
+
+		/// The ids of all requests in `Requests`, oldest first
+		Open get(fn open): Vec<RequestId>;
+
+		/// The results of fulfilled requests
+		Results get(fn results): map RequestId => Option<u64>;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as system::Trait>::AccountId,
+	{
+		/// An account requested the Fibonacci number of this input
+		Requested(AccountId, RequestId, u32),
+		/// A request was fulfilled with this result
+		Fulfilled(RequestId, u64),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		const Confirmations: T::BlockNumber = T::Confirmations::get();
+
+		/// Ask the offchain workers to compute the `input`th Fibonacci number
+		fn request(origin, input: u32) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let id = Self::next_request_id();
+			let next_id = id.checked_add(1).ok_or("request ids exhausted")?;
+
+			let made_at = <system::Module<T>>::block_number();
+			<Requests<T>>::insert(id, Request { made_at, input });
+			Open::mutate(|open| open.push(id));
+			NextRequestId::put(next_id);
+
+			Self::deposit_event(RawEvent::Requested(who, id, input));
+			Ok(())
+		}
+
+		/// Fulfil a request with a result computed by an offchain worker. `anchor` is the hash of
+		/// the block that contained the request, as the worker saw it.
+		fn fulfil(origin, id: RequestId, anchor: T::Hash, result: u64) -> DispatchResult {
+			ensure_none(origin)?;
+			let request = Self::requests(id).ok_or("unknown or fulfilled request")?;
+			ensure!(Self::is_on_chain(&request, &anchor), "request made on another fork");
+
+			<Requests<T>>::remove(id);
+			Open::mutate(|open| open.retain(|open_id| *open_id != id));
+			Results::insert(id, result);
+
+			Self::deposit_event(RawEvent::Fulfilled(id, result));
+			Ok(())
+		}
+
+		fn offchain_worker(now: T::BlockNumber) {
+			debug::RuntimeLogger::init();
+			let submissions = Self::prepare_submissions(now, |n| <system::Module<T>>::block_hash(n));
+			for call in submissions {
+				if T::SubmitTransaction::submit_unsigned(call).is_err() {
+					log::debug!(target: LOG_TARGET, "result not submitted");
+				}
+			}
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// The results this node should submit in block `now`. Skips requests that are not deep
+	/// enough yet, and requests it has already submitted for on the chain that `hash_of` describes,
+	/// and records the rest as submitted.
+	///
+	/// `hash_of` gives the hash of a block of the chain the worker runs on. The offchain worker
+	/// reads it from `frame_system`, tests use it to simulate reorgs.
+	pub fn prepare_submissions(
+		now: T::BlockNumber,
+		hash_of: impl Fn(T::BlockNumber) -> T::Hash,
+	) -> Vec<Call<T>> {
+		let settled = now.saturating_sub(T::Confirmations::get());
+		let mut calls = Vec::new();
+		for id in Self::open() {
+			let request = match Self::requests(id) {
+				Some(request) => request,
+				None => continue,
+			};
+			if request.made_at > settled {
+				continue;
+			}
+
+			let anchor = hash_of(request.made_at);
+			if let Some((submitted_anchor, submitted_at)) = Self::submission(id) {
+				// Submitting twice for the same request on the same fork is a waste, unless the
+				// first result had plenty of time and did not make it
+				let waited = now.saturating_sub(submitted_at);
+				if submitted_anchor == anchor && waited < RESUBMIT_AFTER.into() {
+					continue;
+				}
+			}
+
+			Self::set_submission(id, &anchor, now);
+			calls.push(Call::fulfil(id, anchor, fibonacci(request.input)));
+		}
+		calls
+	}
+
+	/// Whether the block that contained `request` has the hash `anchor` on this chain
+	fn is_on_chain(request: &Request<T::BlockNumber>, anchor: &T::Hash) -> bool {
+		<system::Module<T>>::block_hash(request.made_at) == *anchor
+	}
+
+	/// The anchor and block of this node's last submission for request `id`
+	pub fn submission(id: RequestId) -> Option<(T::Hash, T::BlockNumber)> {
+		sp_io::offchain::local_storage_get(StorageKind::PERSISTENT, &Self::submission_key(id))
+			.and_then(|raw| Decode::decode(&mut &raw[..]).ok())
+	}
+
+	fn set_submission(id: RequestId, anchor: &T::Hash, now: T::BlockNumber) {
+		sp_io::offchain::local_storage_set(
+			StorageKind::PERSISTENT,
+			&Self::submission_key(id),
+			&(anchor, now).encode(),
+		);
+	}
+
+	fn submission_key(id: RequestId) -> Vec<u8> {
+		let mut key = SUBMITTED_PREFIX.to_vec();
+		key.extend_from_slice(&id.encode());
+		key
+	}
+}
+
+/// The `n`th Fibonacci number, modulo `2^64`. Cheap enough here, but it stands in for work that is
+/// too expensive for the runtime.
+pub fn fibonacci(n: u32) -> u64 {
+	let (mut a, mut b) = (0u64, 1u64);
+	for _ in 0..n {
+		let next = a.wrapping_add(b);
+		a = b;
+		b = next;
+	}
+	a
+}
+
+impl<T: Trait> frame_support::unsigned::ValidateUnsigned for Module<T> {
+	type Call = Call<T>;
+
+	fn validate_unsigned(call: &Self::Call) -> TransactionValidity {
+		let (id, anchor) = match call {
+			Call::fulfil(id, anchor, _) => (id, anchor),
+			_ => return InvalidTransaction::Call.into(),
+		};
+
+		let request = match Self::requests(id) {
+			Some(request) => request,
+			None => return InvalidTransaction::Stale.into(),
+		};
+		// A result computed on a fork that has been abandoned
+		if !Self::is_on_chain(&request, anchor) {
+			return InvalidTransaction::Stale.into();
+		}
+
+		Ok(ValidTransaction {
+			priority: 0,
+			requires: vec![],
+			// Every worker submits the same result. The pool keeps one of them.
+			provides: vec![(b"ocw-finality", id, anchor).encode()],
+			longevity: LONGEVITY,
+			propagate: true,
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use event_assert::assert_last_event;
+	use frame_support::{
+		assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types,
+		unsigned::ValidateUnsigned,
+	};
+	use frame_system::{offchain::TransactionSubmitter, RawOrigin};
+	use sp_core::{
+		offchain::{testing::TestOffchainExt, OffchainExt},
+		H256,
+	};
+	use sp_runtime::{
+		testing::{Header, TestXt},
+		traits::{BlakeTwo256, IdentityLookup},
+		Perbill,
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const Confirmations: u64 = 3;
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	mod ocw_finality {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			ocw_finality<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type Call = Call<TestRuntime>;
+		type SubmitTransaction = TransactionSubmitter<(), TestRuntime, TestXt<Call<TestRuntime>, ()>>;
+		type Confirmations = Confirmations;
+	}
+
+	pub type System = system::Module<TestRuntime>;
+	pub type OcwFinality = Module<TestRuntime>;
+
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		let t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		let mut ext: sp_io::TestExternalities = t.into();
+		let (offchain, _state) = TestOffchainExt::new();
+		ext.register_extension(OffchainExt::new(offchain));
+		ext
+	}
+
+	// The test chain never stores the hashes of the blocks after genesis, so it reads them all as
+	// zero. That is the fork the runtime is on, fork A. Fork B stands for a fork that the node saw and then abandoned.
+	fn fork_a(_n: u64) -> H256 {
+		H256::zero()
+	}
+
+	fn fork_b(_n: u64) -> H256 {
+		H256::repeat_byte(0xb)
+	}
+
+	/// Makes a request for `input` in block `block`
+	fn request_at(block: u64, input: u32) {
+		System::set_block_number(block);
+		assert_ok!(OcwFinality::request(Origin::signed(1), input));
+	}
+
+	/// Validates `call` like the pool would, and dispatches it if it is valid
+	fn submit(call: Call<TestRuntime>) -> TransactionValidity {
+		let validity = OcwFinality::validate_unsigned(&call);
+		if validity.is_ok() {
+			if let Call::fulfil(id, anchor, result) = call {
+				assert_ok!(OcwFinality::fulfil(RawOrigin::None.into(), id, anchor, result));
+			}
+		}
+		validity
+	}
+
+	#[test]
+	fn fibonacci_works() {
+		let first: Vec<u64> = (0..10).map(fibonacci).collect();
+		assert_eq!(first, vec![0, 1, 1, 2, 3, 5, 8, 13, 21, 34]);
+		assert_eq!(fibonacci(93), 12_200_160_415_121_876_738);
+	}
+
+	#[test]
+	fn worker_waits_for_confirmations() {
+		new_test_ext().execute_with(|| {
+			request_at(1, 10);
+			assert!(OcwFinality::prepare_submissions(1, fork_a).is_empty());
+			assert!(OcwFinality::prepare_submissions(3, fork_a).is_empty());
+			assert_eq!(
+				OcwFinality::prepare_submissions(4, fork_a),
+				vec![Call::fulfil(0, fork_a(1), 55)]
+			);
+		})
+	}
+
+	#[test]
+	fn worker_submits_once_per_fork() {
+		new_test_ext().execute_with(|| {
+			request_at(1, 10);
+			assert_eq!(OcwFinality::prepare_submissions(4, fork_a).len(), 1);
+			assert_eq!(OcwFinality::submission(0), Some((fork_a(1), 4)));
+
+			// The result has not been included yet, but it is still on its way
+			for now in 5..4 + RESUBMIT_AFTER as u64 {
+				assert!(OcwFinality::prepare_submissions(now, fork_a).is_empty());
+			}
+			// It must have been lost, so it is submitted again
+			assert_eq!(OcwFinality::prepare_submissions(4 + RESUBMIT_AFTER as u64, fork_a).len(), 1);
+		})
+	}
+
+	#[test]
+	fn reorg_is_survived() {
+		new_test_ext().execute_with(|| {
+			request_at(1, 10);
+
+			// The node first follows fork B, and submits a result anchored there
+			let on_fork_b = OcwFinality::prepare_submissions(4, fork_b);
+			assert_eq!(on_fork_b, vec![Call::fulfil(0, fork_b(1), 55)]);
+
+			// Then it switches to fork A, where request 0 is in a different block. The worker
+			// notices the new anchor and submits again, without waiting.
+			let on_fork_a = OcwFinality::prepare_submissions(5, fork_a);
+			assert_eq!(on_fork_a, vec![Call::fulfil(0, fork_a(1), 55)]);
+
+			// The chain is on fork A, so the result from fork B is stale
+			let stale: TransactionValidity = InvalidTransaction::Stale.into();
+			assert_eq!(submit(on_fork_b[0].clone()), stale);
+			assert_err!(
+				OcwFinality::fulfil(RawOrigin::None.into(), 0, fork_b(1), 55),
+				"request made on another fork"
+			);
+
+			assert!(submit(on_fork_a[0].clone()).is_ok());
+			assert_eq!(OcwFinality::results(0), Some(55));
+		})
+	}
+
+	#[test]
+	fn fulfilled_requests_are_closed() {
+		new_test_ext().execute_with(|| {
+			request_at(1, 10);
+			request_at(2, 20);
+			assert_eq!(OcwFinality::open(), vec![0, 1]);
+
+			let calls = OcwFinality::prepare_submissions(5, fork_a);
+			assert_eq!(calls.len(), 2);
+			for call in calls.clone() {
+				assert!(submit(call).is_ok());
+			}
+			assert_last_event::<TestRuntime>(RawEvent::Fulfilled(1, 6765));
+			assert!(OcwFinality::open().is_empty());
+			assert_eq!(OcwFinality::requests(0), None);
+
+			// A second copy of a result is rejected, and the worker has nothing left to do
+			let stale: TransactionValidity = InvalidTransaction::Stale.into();
+			assert_eq!(submit(calls[0].clone()), stale);
+			assert!(OcwFinality::prepare_submissions(100, fork_a).is_empty());
+		})
+	}
+
+	#[test]
+	fn only_unsigned_results() {
+		new_test_ext().execute_with(|| {
+			request_at(1, 10);
+			assert!(OcwFinality::fulfil(Origin::signed(1), 0, fork_a(1), 55).is_err());
+			assert_err!(
+				OcwFinality::fulfil(RawOrigin::None.into(), 7, fork_a(1), 55),
+				"unknown or fulfilled request"
+			);
+		})
+	}
+}
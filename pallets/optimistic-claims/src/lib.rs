@@ -0,0 +1,433 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! Optimistic claims with a challenge period
+//!
+//! Anyone may submit a claim, a hash committing to some off-chain result, without proving it. The
+//! chain optimistically assumes the claim is correct, but only accepts it as final after
+//! `ChallengePeriod` blocks. Until then, anyone may dispute it. Both sides back their word with a
+//! bond: the submitter reserves `SubmissionBond`, the challenger reserves `DisputeBond`.
+//!
+//! A disputed claim waits for `ResolveOrigin`, e.g. a council or root, to rule on it. The loser's
+//! bond goes to the winner. An undisputed claim becomes final in the `on_finalize` of the last
+//! block of its challenge period, and its submitter's bond is released.
+//!
+//! This is the pattern of optimistic rollups in miniature: checking a claim is left to whoever
+//! cares, and it only costs the chain anything when someone does.
+
+use codec::{Decode, Encode};
+use sp_std::prelude::*;
+use sp_runtime::RuntimeDebug;
+use frame_support::{
+	decl_event, decl_module, decl_storage,
+	dispatch::DispatchResult,
+	ensure,
+	traits::{Currency, EnsureOrigin, Get, ReservableCurrency},
+};
+use frame_system::{self as system, ensure_signed};
+
+pub type ClaimIndex = u32;
+
+type BalanceOf<T> = <<T as Trait>::Currency as Currency<<T as system::Trait>::AccountId>>::Balance;
+
+/// A claim that is not final yet
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct Claim<AccountId, Hash, BlockNumber> {
+	pub submitter: AccountId,
+	/// What the claim commits to
+	pub commitment: Hash,
+	/// The block whose `on_finalize` makes the claim final, unless it is disputed before
+	pub final_at: BlockNumber,
+	/// Who disputed the claim, if anybody did
+	pub challenger: Option<AccountId>,
+}
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The currency in which bonds are reserved
+	type Currency: ReservableCurrency<Self::AccountId>;
+
+	/// Number of blocks during which a claim may be disputed
+	type ChallengePeriod: Get<Self::BlockNumber>;
+
+	/// Reserved from the submitter of a claim, and lost if the claim is ruled invalid
+	type SubmissionBond: Get<BalanceOf<Self>>;
+
+	/// Reserved from the challenger of a claim, and lost if the claim is ruled valid
+	type DisputeBond: Get<BalanceOf<Self>>;
+
+	/// The origin that rules on disputed claims
+	type ResolveOrigin: EnsureOrigin<Self::Origin>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as OptimisticClaims {
+		/// Claims that are neither final nor rejected
+		Claims get(fn claims):
+			map ClaimIndex => Option<Claim<T::AccountId, T::Hash, T::BlockNumber>>;
+
+		/// Number of claims ever submitted; the next claim's index
+		ClaimCount get(fn claim_count): ClaimIndex;
+
+		/// Claims whose challenge period ends in each block
+		DueClaims get(fn due_claims): map T::BlockNumber => Vec<ClaimIndex>;
+
+		/// The commitments of final claims
+		Finalized get(fn finalized): map ClaimIndex => Option<T::Hash>;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as system::Trait>::AccountId,
+		Hash = <T as system::Trait>::Hash,
+		BlockNumber = <T as system::Trait>::BlockNumber,
+	{
+		/// A claim was submitted; it becomes final at the given block unless disputed
+		Submitted(ClaimIndex, AccountId, Hash, BlockNumber),
+		/// A claim was disputed by the given account
+		Disputed(ClaimIndex, AccountId),
+		/// A claim became final
+		Finalized(ClaimIndex, Hash),
+		/// A disputed claim was ruled invalid and thrown out
+		Rejected(ClaimIndex),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		const ChallengePeriod: T::BlockNumber = T::ChallengePeriod::get();
+
+		const SubmissionBond: BalanceOf<T> = T::SubmissionBond::get();
+
+		const DisputeBond: BalanceOf<T> = T::DisputeBond::get();
+
+		/// Submit a claim, reserving `SubmissionBond`
+		fn submit(origin, commitment: T::Hash) -> DispatchResult {
+			let submitter = ensure_signed(origin)?;
+			T::Currency::reserve(&submitter, T::SubmissionBond::get())
+				.map_err(|_| "cannot afford the submission bond")?;
+
+			let index = ClaimCount::get();
+			let final_at = <system::Module<T>>::block_number() + T::ChallengePeriod::get();
+			let claim = Claim {
+				submitter: submitter.clone(),
+				commitment,
+				final_at,
+				challenger: None,
+			};
+
+			<Claims<T>>::insert(index, claim);
+			ClaimCount::put(index + 1);
+			<DueClaims<T>>::mutate(final_at, |due| due.push(index));
+
+			Self::deposit_event(RawEvent::Submitted(index, submitter, commitment, final_at));
+			Ok(())
+		}
+
+		/// Dispute a claim during its challenge period, reserving `DisputeBond`
+		fn dispute(origin, index: ClaimIndex) -> DispatchResult {
+			let challenger = ensure_signed(origin)?;
+			let mut claim = Self::claims(index).ok_or("no such open claim")?;
+			ensure!(claim.challenger.is_none(), "claim is already disputed");
+			// The claim becomes final at the end of block `final_at`, so a dispute in that block
+			// is still in time
+			ensure!(
+				<system::Module<T>>::block_number() <= claim.final_at,
+				"challenge period is over"
+			);
+			ensure!(challenger != claim.submitter, "cannot dispute your own claim");
+
+			T::Currency::reserve(&challenger, T::DisputeBond::get())
+				.map_err(|_| "cannot afford the dispute bond")?;
+			claim.challenger = Some(challenger.clone());
+			<Claims<T>>::insert(index, claim);
+
+			Self::deposit_event(RawEvent::Disputed(index, challenger));
+			Ok(())
+		}
+
+		/// Rule on a disputed claim. The loser's bond goes to the winner.
+		fn resolve(origin, index: ClaimIndex, valid: bool) -> DispatchResult {
+			T::ResolveOrigin::ensure_origin(origin)?;
+			let claim = Self::claims(index).ok_or("no such open claim")?;
+			let challenger = claim.challenger.clone().ok_or("claim is not disputed")?;
+
+			// The index stays in `DueClaims`; `on_finalize` skips claims that no longer exist.
+			<Claims<T>>::remove(index);
+			if valid {
+				let _ = T::Currency::repatriate_reserved(
+					&challenger,
+					&claim.submitter,
+					T::DisputeBond::get(),
+				);
+				Self::finalize(index, claim);
+			} else {
+				let _ = T::Currency::repatriate_reserved(
+					&claim.submitter,
+					&challenger,
+					T::SubmissionBond::get(),
+				);
+				T::Currency::unreserve(&challenger, T::DisputeBond::get());
+				Self::deposit_event(RawEvent::Rejected(index));
+			}
+			Ok(())
+		}
+
+		fn on_finalize(n: T::BlockNumber) {
+			for index in <DueClaims<T>>::take(n) {
+				match Self::claims(index) {
+					// Disputed claims wait for a ruling
+					Some(claim) if claim.challenger.is_none() => {
+						<Claims<T>>::remove(index);
+						Self::finalize(index, claim);
+					}
+					_ => {}
+				}
+			}
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// Release the submitter's bond and record the claim as final
+	fn finalize(index: ClaimIndex, claim: Claim<T::AccountId, T::Hash, T::BlockNumber>) {
+		T::Currency::unreserve(&claim.submitter, T::SubmissionBond::get());
+		<Finalized<T>>::insert(index, claim.commitment);
+		Self::deposit_event(RawEvent::Finalized(index, claim.commitment));
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use event_assert::assert_has_event;
+	use sp_core::H256;
+	use frame_support::{
+		assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types
+	};
+	use frame_system::{EnsureRoot, RawOrigin};
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup, OnFinalize},
+		Perbill,
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const ExistentialDeposit: u64 = 0;
+		pub const TransferFee: u64 = 0;
+		pub const CreationFee: u64 = 0;
+
+		pub const ChallengePeriod: u64 = 5;
+		pub const SubmissionBond: u64 = 50;
+		pub const DisputeBond: u64 = 20;
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	impl balances::Trait for TestRuntime {
+		type Balance = u64;
+		type OnFreeBalanceZero = ();
+		type OnNewAccount = ();
+		type Event = TestEvent;
+		type TransferPayment = ();
+		type DustRemoval = ();
+		type ExistentialDeposit = ExistentialDeposit;
+		type TransferFee = TransferFee;
+		type CreationFee = CreationFee;
+	}
+
+	mod optimistic_claims {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			balances<T>,
+			optimistic_claims<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type Currency = balances::Module<Self>;
+		type ChallengePeriod = ChallengePeriod;
+		type SubmissionBond = SubmissionBond;
+		type DisputeBond = DisputeBond;
+		type ResolveOrigin = EnsureRoot<u64>;
+	}
+
+	pub type System = system::Module<TestRuntime>;
+	pub type Balances = balances::Module<TestRuntime>;
+	pub type Claims = Module<TestRuntime>;
+
+	/// Account 1 submits claims, account 2 disputes them. Account 3 cannot afford a bond.
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		let mut t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		balances::GenesisConfig::<TestRuntime> {
+			balances: vec![(1, 100), (2, 100), (3, 10)],
+			vesting: vec![],
+		}
+		.assimilate_storage(&mut t)
+		.unwrap();
+		let mut ext: sp_io::TestExternalities = t.into();
+		ext.execute_with(|| System::set_block_number(1));
+		ext
+	}
+
+	/// Finalize the current block and every block after it, up to and including `n`
+	fn run_to_block(n: u64) {
+		loop {
+			Claims::on_finalize(System::block_number());
+			if System::block_number() >= n {
+				break;
+			}
+			System::set_block_number(System::block_number() + 1);
+		}
+	}
+
+	fn commitment() -> H256 {
+		H256::repeat_byte(7)
+	}
+
+	#[test]
+	fn undisputed_claim_becomes_final_after_the_challenge_period() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(Claims::submit(Origin::signed(1), commitment()));
+			assert_has_event::<TestRuntime>(RawEvent::Submitted(0, 1, commitment(), 6));
+			assert_eq!(Balances::reserved_balance(&1), 50);
+
+			run_to_block(5);
+			assert_eq!(Claims::finalized(0), None);
+
+			run_to_block(6);
+			assert_eq!(Claims::finalized(0), Some(commitment()));
+			assert_eq!(Claims::claims(0), None);
+			assert_eq!(Balances::reserved_balance(&1), 0);
+			assert_has_event::<TestRuntime>(RawEvent::Finalized(0, commitment()));
+		})
+	}
+
+	#[test]
+	fn disputes_only_during_the_challenge_period() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(Claims::submit(Origin::signed(1), commitment()));
+			assert_ok!(Claims::submit(Origin::signed(1), commitment()));
+			assert_err!(Claims::dispute(Origin::signed(1), 0), "cannot dispute your own claim");
+			assert_err!(Claims::dispute(Origin::signed(3), 0), "cannot afford the dispute bond");
+
+			// The last block of the period is still in time
+			System::set_block_number(6);
+			assert_ok!(Claims::dispute(Origin::signed(2), 0));
+			assert_err!(Claims::dispute(Origin::signed(2), 0), "claim is already disputed");
+			assert_has_event::<TestRuntime>(RawEvent::Disputed(0, 2));
+
+			run_to_block(7);
+			assert_err!(Claims::dispute(Origin::signed(2), 1), "no such open claim");
+			assert_eq!(Claims::finalized(1), Some(commitment()));
+		})
+	}
+
+	#[test]
+	fn disputed_claim_waits_for_a_ruling() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(Claims::submit(Origin::signed(1), commitment()));
+			assert_ok!(Claims::dispute(Origin::signed(2), 0));
+
+			run_to_block(20);
+			assert_eq!(Claims::finalized(0), None);
+			assert!(Claims::claims(0).is_some());
+			assert_eq!(Balances::reserved_balance(&1), 50);
+			assert_eq!(Balances::reserved_balance(&2), 20);
+		})
+	}
+
+	#[test]
+	fn valid_claim_takes_the_dispute_bond() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(Claims::submit(Origin::signed(1), commitment()));
+			assert_ok!(Claims::dispute(Origin::signed(2), 0));
+			assert!(Claims::resolve(Origin::signed(1), 0, true).is_err());
+
+			assert_ok!(Claims::resolve(RawOrigin::Root.into(), 0, true));
+			assert_eq!(Claims::finalized(0), Some(commitment()));
+			assert_eq!(Balances::free_balance(&1), 120);
+			assert_eq!(Balances::reserved_balance(&1), 0);
+			assert_eq!(Balances::free_balance(&2), 80);
+			assert_eq!(Balances::reserved_balance(&2), 0);
+		})
+	}
+
+	#[test]
+	fn invalid_claim_loses_the_submission_bond() {
+		new_test_ext().execute_with(|| {
+			let issuance = Balances::total_issuance();
+			assert_ok!(Claims::submit(Origin::signed(1), commitment()));
+			assert_ok!(Claims::dispute(Origin::signed(2), 0));
+
+			assert_ok!(Claims::resolve(RawOrigin::Root.into(), 0, false));
+			assert_has_event::<TestRuntime>(RawEvent::Rejected(0));
+			assert_eq!(Claims::claims(0), None);
+			assert_eq!(Balances::free_balance(&1), 50);
+			assert_eq!(Balances::reserved_balance(&1), 0);
+			assert_eq!(Balances::free_balance(&2), 150);
+			assert_eq!(Balances::reserved_balance(&2), 0);
+			assert_eq!(Balances::total_issuance(), issuance);
+
+			// The claim is gone, even when its challenge period ends
+			run_to_block(6);
+			assert_eq!(Claims::finalized(0), None);
+		})
+	}
+
+	#[test]
+	fn only_disputed_claims_are_resolved() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(Claims::submit(Origin::signed(1), commitment()));
+			assert_err!(
+				Claims::resolve(RawOrigin::Root.into(), 0, false),
+				"claim is not disputed"
+			);
+			assert_err!(Claims::resolve(RawOrigin::Root.into(), 1, true), "no such open claim");
+			assert_err!(
+				Claims::submit(Origin::signed(3), commitment()),
+				"cannot afford the submission bond"
+			);
+		})
+	}
+}
@@ -0,0 +1,482 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! An oracle that aggregates values from staked providers, and slashes outliers
+//!
+//! `ManageOrigin` whitelists the providers of a value, such as a price. A whitelisted provider
+//! reserves `Stake` before it may submit, and submits at most one value per round. Every
+//! `RoundLength` blocks the round closes in `on_finalize`:
+//!
+//! * With at least `MinSubmissions` values, the median becomes the oracle's value for the round.
+//!   A median can't be moved far by a minority of dishonest providers.
+//! * Providers whose value deviates from the median by more than `Tolerance` of it lose
+//!   `SlashFraction` of their stake to the `Slash` handler. Honest providers should agree within
+//!   the tolerance, so a value far off is either a mistake or an attack.
+//! * With fewer values, the round ends without a value, and nobody is slashed.
+//!
+//! A provider whose stake was slashed must top it up before it submits again. Stake can only be
+//! withdrawn while the provider has no value in the current round, so nobody escapes a slash.
+
+use sp_std::prelude::*;
+use sp_runtime::{
+	traits::{Saturating, Zero},
+	Perbill, Permill,
+};
+use frame_support::{
+	decl_event, decl_module, decl_storage,
+	dispatch::DispatchResult,
+	ensure,
+	traits::{Currency, EnsureOrigin, Get, Imbalance, OnUnbalanced, ReservableCurrency},
+};
+use frame_system::{self as system, ensure_signed};
+
+pub type RoundIndex = u32;
+
+type BalanceOf<T> = <<T as Trait>::Currency as Currency<<T as system::Trait>::AccountId>>::Balance;
+type NegativeImbalanceOf<T> =
+	<<T as Trait>::Currency as Currency<<T as system::Trait>::AccountId>>::NegativeImbalance;
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The currency in which stake is reserved
+	type Currency: ReservableCurrency<Self::AccountId>;
+
+	/// The origin that whitelists providers
+	type ManageOrigin: EnsureOrigin<Self::Origin>;
+
+	/// The stake a provider reserves to submit values
+	type Stake: Get<BalanceOf<Self>>;
+
+	/// The number of blocks in a round. Zero means rounds never close.
+	type RoundLength: Get<Self::BlockNumber>;
+
+	/// The fewest values from which a round's median is taken
+	type MinSubmissions: Get<u32>;
+
+	/// How far, as a fraction of the median, a value may be from it without being slashed
+	type Tolerance: Get<Permill>;
+
+	/// The fraction of an outlier's stake that is slashed
+	type SlashFraction: Get<Perbill>;
+
+	/// Handler for slashed stake
+	type Slash: OnUnbalanced<NegativeImbalanceOf<Self>>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as Oracle {
+		/// Accounts that may stake and provide values
+		Whitelist get(fn is_whitelisted): map T::AccountId => bool;
+
+		/// The stake each provider has reserved
+		Stakes get(fn stake_of): map T::AccountId => BalanceOf<T>;
+
+		/// The round that is open for submissions
+		CurrentRound get(fn current_round): RoundIndex;
+
+		/// The values submitted in the current round, in the order they arrived
+		Submissions get(fn submissions): Vec<(T::AccountId, u64)>;
+
+		/// The value of each round that reached `MinSubmissions`
+		Values get(fn value): map RoundIndex => Option<u64>;
+
+		/// The most recent value, and the round it is from
+		Latest get(fn latest): Option<(RoundIndex, u64)>;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as system::Trait>::AccountId,
+		Balance = BalanceOf<T>,
+	{
+		Whitelisted(AccountId),
+		Delisted(AccountId),
+		/// A provider's stake is now this much
+		Staked(AccountId, Balance),
+		Unstaked(AccountId, Balance),
+		/// A provider submitted a value for a round
+		Submitted(RoundIndex, AccountId, u64),
+		/// A round closed with this value
+		RoundClosed(RoundIndex, u64),
+		/// A round closed with fewer values than `MinSubmissions`; how many it had
+		RoundFailed(RoundIndex, u32),
+		/// A provider's value was outside the tolerance, and it lost this much stake
+		Slashed(RoundIndex, AccountId, Balance),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		const Stake: BalanceOf<T> = T::Stake::get();
+
+		const RoundLength: T::BlockNumber = T::RoundLength::get();
+
+		const MinSubmissions: u32 = T::MinSubmissions::get();
+
+		const Tolerance: Permill = T::Tolerance::get();
+
+		const SlashFraction: Perbill = T::SlashFraction::get();
+
+		fn whitelist(origin, who: T::AccountId) -> DispatchResult {
+			T::ManageOrigin::ensure_origin(origin)?;
+			<Whitelist<T>>::insert(&who, true);
+			Self::deposit_event(RawEvent::Whitelisted(who));
+			Ok(())
+		}
+
+		/// Stop an account from submitting values. Its stake stays until it unstakes.
+		fn delist(origin, who: T::AccountId) -> DispatchResult {
+			T::ManageOrigin::ensure_origin(origin)?;
+			<Whitelist<T>>::remove(&who);
+			Self::deposit_event(RawEvent::Delisted(who));
+			Ok(())
+		}
+
+		/// Reserve stake up to `Stake`, for a new provider or after a slash
+		fn stake(origin) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			ensure!(Self::is_whitelisted(&who), "not whitelisted");
+			let stake = Self::stake_of(&who);
+			let missing = T::Stake::get().saturating_sub(stake);
+			ensure!(!missing.is_zero(), "already staked");
+			T::Currency::reserve(&who, missing).map_err(|_| "cannot afford the stake")?;
+
+			let stake = stake + missing;
+			<Stakes<T>>::insert(&who, stake);
+			Self::deposit_event(RawEvent::Staked(who, stake));
+			Ok(())
+		}
+
+		/// Withdraw all stake. Not possible while the caller has a value in the current round.
+		fn unstake(origin) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let stake = Self::stake_of(&who);
+			ensure!(!stake.is_zero(), "not staked");
+			ensure!(!Self::has_submitted(&who), "value in the current round");
+
+			T::Currency::unreserve(&who, stake);
+			<Stakes<T>>::remove(&who);
+			Self::deposit_event(RawEvent::Unstaked(who, stake));
+			Ok(())
+		}
+
+		/// Submit a value for the current round
+		fn submit(origin, value: u64) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			ensure!(Self::is_whitelisted(&who), "not whitelisted");
+			ensure!(Self::stake_of(&who) >= T::Stake::get(), "not enough stake");
+			ensure!(!Self::has_submitted(&who), "already submitted");
+
+			<Submissions<T>>::mutate(|submissions| submissions.push((who.clone(), value)));
+			Self::deposit_event(RawEvent::Submitted(Self::current_round(), who, value));
+			Ok(())
+		}
+
+		fn on_finalize(n: T::BlockNumber) {
+			let length = T::RoundLength::get();
+			if !length.is_zero() && (n % length).is_zero() {
+				Self::close_round();
+			}
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	fn has_submitted(who: &T::AccountId) -> bool {
+		Self::submissions().iter().any(|(provider, _)| provider == who)
+	}
+
+	/// Aggregate the current round's values, slash the outliers, and open the next round
+	fn close_round() {
+		let round = Self::current_round();
+		let submissions = <Submissions<T>>::take();
+		CurrentRound::put(round.wrapping_add(1));
+
+		if (submissions.len() as u32) < T::MinSubmissions::get() {
+			Self::deposit_event(RawEvent::RoundFailed(round, submissions.len() as u32));
+			return;
+		}
+		let mut values = submissions.iter().map(|(_, value)| *value).collect::<Vec<_>>();
+		let median = match median(&mut values) {
+			Some(median) => median,
+			// `MinSubmissions` is zero, and nobody submitted
+			None => return Self::deposit_event(RawEvent::RoundFailed(round, 0)),
+		};
+
+		Values::insert(round, median);
+		<Latest>::put((round, median));
+		Self::deposit_event(RawEvent::RoundClosed(round, median));
+
+		for (provider, value) in submissions {
+			if !within_tolerance(value, median, T::Tolerance::get()) {
+				Self::slash(round, provider);
+			}
+		}
+	}
+
+	fn slash(round: RoundIndex, provider: T::AccountId) {
+		let stake = Self::stake_of(&provider);
+		let amount = T::SlashFraction::get() * stake;
+		let (imbalance, _) = T::Currency::slash_reserved(&provider, amount);
+		let slashed = imbalance.peek();
+		T::Slash::on_unbalanced(imbalance);
+
+		<Stakes<T>>::insert(&provider, stake.saturating_sub(slashed));
+		Self::deposit_event(RawEvent::Slashed(round, provider, slashed));
+	}
+}
+
+/// The median of `values`, which it sorts. For an even number of values, the mean of the middle
+/// two, rounded down.
+pub fn median(values: &mut [u64]) -> Option<u64> {
+	if values.is_empty() {
+		return None;
+	}
+	values.sort_unstable();
+	let middle = values.len() / 2;
+	if values.len() % 2 == 1 {
+		Some(values[middle])
+	} else {
+		let (lower, upper) = (values[middle - 1], values[middle]);
+		// Without overflowing, unlike `(lower + upper) / 2`
+		Some(lower + (upper - lower) / 2)
+	}
+}
+
+/// Whether `value` is within `tolerance` of `median`, as a fraction of the median
+pub fn within_tolerance(value: u64, median: u64, tolerance: Permill) -> bool {
+	let deviation = value.max(median) - value.min(median);
+	deviation <= tolerance * median
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use event_assert::assert_has_event;
+	use sp_core::H256;
+	use frame_support::{
+		assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types,
+	};
+	use frame_system::{EnsureRoot, RawOrigin};
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup, OnFinalize},
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const ExistentialDeposit: u64 = 0;
+		pub const TransferFee: u64 = 0;
+		pub const CreationFee: u64 = 0;
+
+		pub const Stake: u64 = 50;
+		pub const RoundLength: u64 = 10;
+		pub const MinSubmissions: u32 = 3;
+		pub const Tolerance: Permill = Permill::from_percent(10);
+		pub const SlashFraction: Perbill = Perbill::from_percent(20);
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	impl balances::Trait for TestRuntime {
+		type Balance = u64;
+		type OnFreeBalanceZero = ();
+		type OnNewAccount = ();
+		type Event = TestEvent;
+		type TransferPayment = ();
+		type DustRemoval = ();
+		type ExistentialDeposit = ExistentialDeposit;
+		type TransferFee = TransferFee;
+		type CreationFee = CreationFee;
+	}
+
+	mod oracle {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			balances<T>,
+			oracle<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type Currency = balances::Module<Self>;
+		type ManageOrigin = EnsureRoot<u64>;
+		type Stake = Stake;
+		type RoundLength = RoundLength;
+		type MinSubmissions = MinSubmissions;
+		type Tolerance = Tolerance;
+		type SlashFraction = SlashFraction;
+		// Slashed stake is burned
+		type Slash = ();
+	}
+
+	pub type Balances = balances::Module<TestRuntime>;
+	pub type Oracle = Module<TestRuntime>;
+
+	/// Accounts 1 to 4 have 100 each. Accounts 1 to 3 are staked providers.
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		let mut t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		balances::GenesisConfig::<TestRuntime> {
+			balances: (1..=4).map(|who| (who, 100)).collect(),
+			vesting: vec![],
+		}
+		.assimilate_storage(&mut t)
+		.unwrap();
+		let mut ext: sp_io::TestExternalities = t.into();
+		ext.execute_with(|| {
+			for who in 1..=3 {
+				assert_ok!(Oracle::whitelist(RawOrigin::Root.into(), who));
+				assert_ok!(Oracle::stake(Origin::signed(who)));
+			}
+		});
+		ext
+	}
+
+	/// Submit a value from each provider, and close the round
+	fn round(values: &[(u64, u64)]) {
+		for (who, value) in values {
+			assert_ok!(Oracle::submit(Origin::signed(*who), *value));
+		}
+		Oracle::on_finalize(10 * u64::from(Oracle::current_round() + 1));
+	}
+
+	#[test]
+	fn median_works() {
+		assert_eq!(median(&mut []), None);
+		assert_eq!(median(&mut [7]), Some(7));
+		assert_eq!(median(&mut [9, 1, 5]), Some(5));
+		assert_eq!(median(&mut [4, 1, 8, 2]), Some(3));
+		let max = u64::max_value();
+		assert_eq!(median(&mut [max, max - 1]), Some(max - 1));
+	}
+
+	#[test]
+	fn tolerance_is_relative_to_the_median() {
+		let tolerance = Permill::from_percent(10);
+		assert!(within_tolerance(110, 100, tolerance));
+		assert!(within_tolerance(90, 100, tolerance));
+		assert!(!within_tolerance(111, 100, tolerance));
+		assert!(!within_tolerance(89, 100, tolerance));
+		assert!(within_tolerance(0, 0, tolerance));
+	}
+
+	#[test]
+	fn rounds_close_with_the_median() {
+		new_test_ext().execute_with(|| {
+			// Not the end of a round
+			Oracle::on_finalize(5);
+			assert_eq!(Oracle::current_round(), 0);
+
+			round(&[(1, 100), (2, 104), (3, 98)]);
+			assert_eq!(Oracle::value(0), Some(100));
+			assert_eq!(Oracle::latest(), Some((0, 100)));
+			assert_eq!(Oracle::current_round(), 1);
+			assert!(Oracle::submissions().is_empty());
+			// Everyone was within the tolerance
+			assert_eq!(Balances::total_issuance(), 400);
+		})
+	}
+
+	#[test]
+	fn outliers_are_slashed() {
+		new_test_ext().execute_with(|| {
+			round(&[(1, 100), (2, 102), (3, 150)]);
+			assert_eq!(Oracle::value(0), Some(102));
+
+			// 20% of the stake of 50 is burned
+			assert_eq!(Oracle::stake_of(&3), 40);
+			assert_eq!(Balances::reserved_balance(&3), 40);
+			assert_eq!(Balances::total_issuance(), 390);
+			assert_has_event::<TestRuntime>(RawEvent::Slashed(0, 3, 10));
+
+			// The slashed provider must top up its stake before it submits again
+			assert_err!(Oracle::submit(Origin::signed(3), 101), "not enough stake");
+			assert_ok!(Oracle::stake(Origin::signed(3)));
+			assert_eq!(Balances::reserved_balance(&3), 50);
+			assert_ok!(Oracle::submit(Origin::signed(3), 101));
+		})
+	}
+
+	#[test]
+	fn rounds_without_quorum_fail() {
+		new_test_ext().execute_with(|| {
+			round(&[(1, 100), (3, 1_000)]);
+			assert_eq!(Oracle::value(0), None);
+			assert_eq!(Oracle::latest(), None);
+			assert_eq!(Oracle::current_round(), 1);
+			// Without a median, there are no outliers
+			assert_eq!(Oracle::stake_of(&3), 50);
+		})
+	}
+
+	#[test]
+	fn only_staked_providers_submit() {
+		new_test_ext().execute_with(|| {
+			assert_err!(Oracle::stake(Origin::signed(4)), "not whitelisted");
+			assert_err!(Oracle::submit(Origin::signed(4), 100), "not whitelisted");
+			assert!(Oracle::whitelist(Origin::signed(1), 4).is_err());
+
+			assert_ok!(Oracle::whitelist(RawOrigin::Root.into(), 4));
+			assert_err!(Oracle::submit(Origin::signed(4), 100), "not enough stake");
+			assert_ok!(Oracle::stake(Origin::signed(4)));
+			assert_err!(Oracle::stake(Origin::signed(4)), "already staked");
+			assert_ok!(Oracle::submit(Origin::signed(4), 100));
+			assert_err!(Oracle::submit(Origin::signed(4), 100), "already submitted");
+
+			assert_ok!(Oracle::delist(RawOrigin::Root.into(), 1));
+			assert_err!(Oracle::submit(Origin::signed(1), 100), "not whitelisted");
+		})
+	}
+
+	#[test]
+	fn stake_is_locked_while_a_value_is_pending() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(Oracle::submit(Origin::signed(1), 100));
+			assert_err!(Oracle::unstake(Origin::signed(1)), "value in the current round");
+
+			Oracle::on_finalize(10);
+			assert_ok!(Oracle::unstake(Origin::signed(1)));
+			assert_eq!(Balances::reserved_balance(&1), 0);
+			assert_err!(Oracle::unstake(Origin::signed(1)), "not staked");
+		})
+	}
+}
@@ -0,0 +1,616 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! A limit order book for trading a base currency against a quote currency
+//!
+//! Prices are quoted in units of the quote currency per unit of the base currency.
+//!
+//! * A buy order reserves `amount * price` of the quote currency; a sell order reserves `amount`
+//!   of the base currency.
+//! * A new order is matched right away against the best resting orders on the other side, at the
+//!   resting order's price. Whatever remains rests in the book.
+//! * Orders fill partially; a resting order stays at the front of its price level until it is
+//!   filled or cancelled.
+//! * Matching stops after `MaxFills` fills, which bounds the weight of `place_order`.
+//! * Each side has at most `MaxPriceLevels` prices, and each price at most `MaxOrdersPerLevel`
+//!   resting orders. An order that could not rest within these limits is rejected, so the lists
+//!   that every order reads and writes can't grow without bound.
+//!
+//! The book is stored as a double map from side and price to a FIFO queue of order ids, plus a
+//! sorted list of the active prices on each side.
+
+use codec::{Decode, Encode};
+use sp_std::prelude::*;
+use sp_runtime::{
+	traits::{CheckedMul, Saturating, Zero},
+	RuntimeDebug,
+};
+use frame_support::{
+	decl_event, decl_module, decl_storage,
+	dispatch::DispatchResult,
+	ensure,
+	traits::{Currency, Get, ReservableCurrency},
+	weights::SimpleDispatchInfo,
+};
+use frame_system::{self as system, ensure_signed};
+
+pub mod weights;
+pub use weights::WeightInfo;
+
+pub type OrderId = u64;
+
+type BalanceOf<T> = <<T as Trait>::Base as Currency<<T as system::Trait>::AccountId>>::Balance;
+type OrderOf<T> = Order<<T as system::Trait>::AccountId, BalanceOf<T>>;
+
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, RuntimeDebug)]
+pub enum Side {
+	Buy,
+	Sell,
+}
+
+impl Side {
+	fn opposite(self) -> Side {
+		match self {
+			Side::Buy => Side::Sell,
+			Side::Sell => Side::Buy,
+		}
+	}
+
+	/// Whether a price on this side is better than another one
+	fn better<P: PartialOrd>(self, price: &P, than: &P) -> bool {
+		match self {
+			Side::Buy => price > than,
+			Side::Sell => price < than,
+		}
+	}
+}
+
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct Order<AccountId, Balance> {
+	pub owner: AccountId,
+	pub side: Side,
+	pub price: Balance,
+	/// The amount of the base currency still to be traded
+	pub remaining: Balance,
+}
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The currency being bought and sold
+	type Base: ReservableCurrency<Self::AccountId>;
+
+	/// The currency in which prices are quoted
+	type Quote: ReservableCurrency<Self::AccountId, Balance = BalanceOf<Self>>;
+
+	/// The most resting orders a single new order may fill
+	type MaxFills: Get<u32>;
+
+	/// The most prices with resting orders on each side of the book
+	type MaxPriceLevels: Get<u32>;
+
+	/// The most resting orders at a single side and price
+	type MaxOrdersPerLevel: Get<u32>;
+
+	/// Weight information for the dispatchables in this pallet
+	type WeightInfo: WeightInfo;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as OrderBook {
+		/// Resting orders
+		Orders get(fn order): map OrderId => Option<OrderOf<T>>;
+
+		/// The id of the next order
+		NextOrderId get(fn next_order_id): OrderId;
+
+		/// Resting orders at each side and price, oldest first
+		PriceLevels get(fn price_level):
+			double_map Side, blake2_256(BalanceOf<T>) => Vec<OrderId>;
+
+		/// Prices that have resting orders on each side, best first
+		Prices get(fn prices): map Side => Vec<BalanceOf<T>>;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as system::Trait>::AccountId,
+		Balance = BalanceOf<T>,
+	{
+		/// An order was placed: side, price and amount
+		OrderPlaced(OrderId, AccountId, Side, Balance, Balance),
+		/// A resting order traded with a new order: maker, taker, amount and price
+		Trade(OrderId, OrderId, Balance, Balance),
+		/// An order was cancelled
+		OrderCancelled(OrderId),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		const MaxFills: u32 = T::MaxFills::get();
+
+		const MaxPriceLevels: u32 = T::MaxPriceLevels::get();
+
+		const MaxOrdersPerLevel: u32 = T::MaxOrdersPerLevel::get();
+
+		/// Place a limit order. It trades with resting orders at `price` or better, and the rest
+		/// stays in the book.
+		///
+		/// The weight covers `MaxFills` fills, the most this call can do. The order must be able to
+		/// rest in the book, even if it ends up filled.
+		#[weight = SimpleDispatchInfo::FixedNormal(T::WeightInfo::place_order(T::MaxFills::get()))]
+		fn place_order(origin, side: Side, price: BalanceOf<T>, amount: BalanceOf<T>) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			ensure!(!price.is_zero() && !amount.is_zero(), "price and amount must not be zero");
+			let prices = Self::prices(side);
+			ensure!(
+				prices.contains(&price) || prices.len() < T::MaxPriceLevels::get() as usize,
+				"too many prices on this side"
+			);
+			ensure!(
+				Self::price_level(side, price).len() < T::MaxOrdersPerLevel::get() as usize,
+				"too many orders at this price"
+			);
+
+			match side {
+				Side::Buy => {
+					let value = amount.checked_mul(&price).ok_or("order value overflow")?;
+					T::Quote::reserve(&who, value).map_err(|_| "cannot afford the order")?;
+				}
+				Side::Sell => {
+					T::Base::reserve(&who, amount).map_err(|_| "cannot afford the order")?;
+				}
+			}
+
+			let id = Self::next_order_id();
+			NextOrderId::put(id.wrapping_add(1));
+			Self::deposit_event(RawEvent::OrderPlaced(id, who.clone(), side, price, amount));
+
+			let order = Order { owner: who, side, price, remaining: amount };
+			let order = Self::match_order(id, order);
+			if !order.remaining.is_zero() {
+				Self::rest(id, order);
+			}
+			Ok(())
+		}
+
+		/// Cancel a resting order and release what it still reserves
+		#[weight = SimpleDispatchInfo::FixedNormal(T::WeightInfo::cancel_order())]
+		fn cancel_order(origin, id: OrderId) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let order = Self::order(id).ok_or("no such order")?;
+			ensure!(order.owner == who, "not the owner of the order");
+
+			let mut level = Self::price_level(order.side, order.price);
+			level.retain(|queued| *queued != id);
+			if level.is_empty() {
+				<PriceLevels<T>>::remove(order.side, order.price);
+				<Prices<T>>::mutate(order.side, |prices| prices.retain(|p| *p != order.price));
+			} else {
+				<PriceLevels<T>>::insert(order.side, order.price, level);
+			}
+			<Orders<T>>::remove(id);
+			Self::release(&order.owner, order.side, order.price, order.remaining);
+
+			Self::deposit_event(RawEvent::OrderCancelled(id));
+			Ok(())
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// Trade a new order against the book until it is filled, the book no longer crosses its
+	/// price, or `MaxFills` fills were made. Returns what is left of the order.
+	fn match_order(id: OrderId, mut order: OrderOf<T>) -> OrderOf<T> {
+		let opposite = order.side.opposite();
+		let mut prices = Self::prices(opposite);
+		let mut fills = 0;
+
+		while !order.remaining.is_zero() && fills < T::MaxFills::get() {
+			let best = match prices.first() {
+				Some(best) if !opposite.better(&order.price, best) => *best,
+				_ => break,
+			};
+			let mut level = Self::price_level(opposite, best);
+
+			while !order.remaining.is_zero() && fills < T::MaxFills::get() && !level.is_empty() {
+				let maker_id = level[0];
+				let mut maker = match Self::order(maker_id) {
+					Some(maker) => maker,
+					None => {
+						level.remove(0);
+						continue;
+					}
+				};
+				let amount = order.remaining.min(maker.remaining);
+				match order.side {
+					Side::Buy => Self::trade(&order.owner, &maker.owner, amount, best, order.price),
+					Side::Sell => Self::trade(&maker.owner, &order.owner, amount, best, best),
+				}
+				order.remaining -= amount;
+				maker.remaining -= amount;
+				fills += 1;
+
+				if maker.remaining.is_zero() {
+					<Orders<T>>::remove(maker_id);
+					level.remove(0);
+				} else {
+					<Orders<T>>::insert(maker_id, maker);
+				}
+				Self::deposit_event(RawEvent::Trade(maker_id, id, amount, best));
+			}
+
+			if level.is_empty() {
+				<PriceLevels<T>>::remove(opposite, best);
+				prices.remove(0);
+			} else {
+				<PriceLevels<T>>::insert(opposite, best, level);
+			}
+		}
+
+		<Prices<T>>::insert(opposite, prices);
+		order
+	}
+
+	/// Add an order to the back of its price level
+	fn rest(id: OrderId, order: OrderOf<T>) {
+		let (side, price) = (order.side, order.price);
+		<Prices<T>>::mutate(side, |prices| {
+			if !prices.contains(&price) {
+				let at = prices.iter().position(|p| side.better(&price, p)).unwrap_or(prices.len());
+				prices.insert(at, price);
+			}
+		});
+		<PriceLevels<T>>::mutate(side, price, |level| level.push(id));
+		<Orders<T>>::insert(id, order);
+	}
+
+	/// Swap `amount` of the base currency from the seller for `amount * price` of the quote
+	/// currency from the buyer. Both are taken from reserved funds. The buyer reserved at
+	/// `buyer_limit`, and gets back the difference to the trade price.
+	fn trade(
+		buyer: &T::AccountId,
+		seller: &T::AccountId,
+		amount: BalanceOf<T>,
+		price: BalanceOf<T>,
+		buyer_limit: BalanceOf<T>,
+	) {
+		// Cannot overflow: the buyer reserved `amount * buyer_limit` and `price <= buyer_limit`
+		let value = amount.saturating_mul(price);
+		let surplus = amount.saturating_mul(buyer_limit.saturating_sub(price));
+
+		let (base, _) = T::Base::slash_reserved(seller, amount);
+		T::Base::resolve_creating(buyer, base);
+		let (quote, _) = T::Quote::slash_reserved(buyer, value);
+		T::Quote::resolve_creating(seller, quote);
+		T::Quote::unreserve(buyer, surplus);
+	}
+
+	/// Release the funds reserved for the unfilled part of an order
+	fn release(who: &T::AccountId, side: Side, price: BalanceOf<T>, remaining: BalanceOf<T>) {
+		match side {
+			Side::Buy => {
+				T::Quote::unreserve(who, remaining.saturating_mul(price));
+			}
+			Side::Sell => {
+				T::Base::unreserve(who, remaining);
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use sp_core::H256;
+	use frame_support::{
+		assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types,
+		weights::GetDispatchInfo,
+	};
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup},
+		Perbill,
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const ExistentialDeposit: u64 = 0;
+		pub const TransferFee: u64 = 0;
+		pub const CreationFee: u64 = 0;
+
+		pub const MaxFills: u32 = 3;
+		pub const MaxPriceLevels: u32 = 3;
+		pub const MaxOrdersPerLevel: u32 = 4;
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	impl balances::Trait for TestRuntime {
+		type Balance = u64;
+		type OnFreeBalanceZero = ();
+		type OnNewAccount = ();
+		type Event = TestEvent;
+		type TransferPayment = ();
+		type DustRemoval = ();
+		type ExistentialDeposit = ExistentialDeposit;
+		type TransferFee = TransferFee;
+		type CreationFee = CreationFee;
+	}
+
+	// A second instance of balances serves as the quote currency
+	impl balances::Trait<balances::Instance1> for TestRuntime {
+		type Balance = u64;
+		type OnFreeBalanceZero = ();
+		type OnNewAccount = ();
+		type Event = ();
+		type TransferPayment = ();
+		type DustRemoval = ();
+		type ExistentialDeposit = ExistentialDeposit;
+		type TransferFee = TransferFee;
+		type CreationFee = CreationFee;
+	}
+
+	mod order_book {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			balances<T>,
+			order_book<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type Base = Base;
+		type Quote = Quote;
+		type MaxFills = MaxFills;
+		type MaxPriceLevels = MaxPriceLevels;
+		type MaxOrdersPerLevel = MaxOrdersPerLevel;
+		type WeightInfo = ();
+	}
+
+	pub type System = system::Module<TestRuntime>;
+	pub type Base = balances::Module<TestRuntime>;
+	pub type Quote = balances::Module<TestRuntime, balances::Instance1>;
+	pub type OrderBook = Module<TestRuntime>;
+
+	/// Accounts 1 to 4 have 100 of the base currency and 1000 of the quote currency
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		let mut t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		balances::GenesisConfig::<TestRuntime> {
+			balances: vec![(1, 100), (2, 100), (3, 100), (4, 100)],
+			vesting: vec![],
+		}
+		.assimilate_storage(&mut t)
+		.unwrap();
+		balances::GenesisConfig::<TestRuntime, balances::Instance1> {
+			balances: vec![(1, 1000), (2, 1000), (3, 1000), (4, 1000)],
+			vesting: vec![],
+		}
+		.assimilate_storage(&mut t)
+		.unwrap();
+		t.into()
+	}
+
+	fn event_emitted(event: RawEvent<u64, u64>) -> bool {
+		let expected = TestEvent::order_book(event);
+		System::events().iter().any(|a| a.event == expected)
+	}
+
+	fn buy(who: u64, price: u64, amount: u64) {
+		assert_ok!(OrderBook::place_order(Origin::signed(who), Side::Buy, price, amount));
+	}
+
+	fn sell(who: u64, price: u64, amount: u64) {
+		assert_ok!(OrderBook::place_order(Origin::signed(who), Side::Sell, price, amount));
+	}
+
+	#[test]
+	fn orders_rest_when_nothing_crosses() {
+		new_test_ext().execute_with(|| {
+			buy(1, 9, 10);
+			buy(1, 8, 10);
+			buy(2, 10, 10);
+			sell(3, 12, 5);
+			sell(3, 11, 5);
+
+			assert_eq!(OrderBook::prices(Side::Buy), vec![10, 9, 8]);
+			assert_eq!(OrderBook::prices(Side::Sell), vec![11, 12]);
+			assert_eq!(OrderBook::price_level(Side::Buy, 9), vec![0]);
+			assert_eq!(Quote::reserved_balance(&1), 170);
+			assert_eq!(Base::reserved_balance(&3), 10);
+			assert!(event_emitted(RawEvent::OrderPlaced(0, 1, Side::Buy, 9, 10)));
+		})
+	}
+
+	#[test]
+	fn full_fill_at_maker_price() {
+		new_test_ext().execute_with(|| {
+			sell(1, 10, 5);
+			// The buyer is willing to pay 12 but trades at the resting price of 10
+			buy(2, 12, 5);
+
+			assert!(event_emitted(RawEvent::Trade(0, 1, 5, 10)));
+			assert_eq!(OrderBook::order(0), None);
+			assert_eq!(OrderBook::order(1), None);
+			assert!(OrderBook::prices(Side::Sell).is_empty());
+			assert!(OrderBook::prices(Side::Buy).is_empty());
+
+			assert_eq!(Base::free_balance(&1), 95);
+			assert_eq!(Quote::free_balance(&1), 1050);
+			assert_eq!(Base::free_balance(&2), 105);
+			assert_eq!(Quote::free_balance(&2), 950);
+			assert_eq!(Quote::reserved_balance(&2), 0);
+		})
+	}
+
+	#[test]
+	fn partial_fill_leaves_maker_resting() {
+		new_test_ext().execute_with(|| {
+			buy(1, 10, 8);
+			sell(2, 10, 3);
+
+			let maker = OrderBook::order(0).expect("maker still rests");
+			assert_eq!(maker.remaining, 5);
+			assert_eq!(Quote::reserved_balance(&1), 50);
+			assert_eq!(Base::free_balance(&1), 103);
+			assert_eq!(Quote::free_balance(&2), 1030);
+			assert_eq!(OrderBook::order(1), None);
+		})
+	}
+
+	#[test]
+	fn partial_fill_leaves_taker_resting() {
+		new_test_ext().execute_with(|| {
+			sell(1, 10, 3);
+			buy(2, 11, 8);
+
+			let taker = OrderBook::order(1).expect("taker rests");
+			assert_eq!(taker.remaining, 5);
+			assert_eq!(OrderBook::prices(Side::Buy), vec![11]);
+			assert!(OrderBook::prices(Side::Sell).is_empty());
+			// 30 paid, 55 reserved for the rest, the surplus of 3 returned
+			assert_eq!(Quote::free_balance(&2), 915);
+			assert_eq!(Quote::reserved_balance(&2), 55);
+		})
+	}
+
+	#[test]
+	fn matches_best_price_then_oldest() {
+		new_test_ext().execute_with(|| {
+			sell(1, 11, 2);
+			sell(2, 10, 2);
+			sell(3, 10, 2);
+			buy(4, 11, 5);
+
+			assert!(event_emitted(RawEvent::Trade(1, 3, 2, 10)));
+			assert!(event_emitted(RawEvent::Trade(2, 3, 2, 10)));
+			assert!(event_emitted(RawEvent::Trade(0, 3, 1, 11)));
+			assert_eq!(OrderBook::order(0).map(|o| o.remaining), Some(1));
+			assert_eq!(OrderBook::prices(Side::Sell), vec![11]);
+		})
+	}
+
+	#[test]
+	fn matching_stops_at_max_fills() {
+		new_test_ext().execute_with(|| {
+			for _ in 0..4 {
+				sell(1, 10, 1);
+			}
+			buy(2, 10, 4);
+
+			// Three fills, then the rest of the buy order rests at a crossing price
+			assert_eq!(OrderBook::price_level(Side::Sell, 10), vec![3]);
+			assert_eq!(OrderBook::order(4).map(|o| o.remaining), Some(1));
+			assert_eq!(Base::free_balance(&2), 103);
+		})
+	}
+
+	#[test]
+	fn book_size_is_bounded() {
+		new_test_ext().execute_with(|| {
+			buy(1, 10, 1);
+			buy(1, 9, 1);
+			buy(1, 8, 1);
+			assert_err!(
+				OrderBook::place_order(Origin::signed(1), Side::Buy, 7, 1),
+				"too many prices on this side"
+			);
+			// Known prices and the other side are still open
+			buy(1, 8, 1);
+			sell(2, 12, 1);
+
+			buy(1, 8, 1);
+			buy(1, 8, 1);
+			assert_err!(
+				OrderBook::place_order(Origin::signed(1), Side::Buy, 8, 1),
+				"too many orders at this price"
+			);
+			assert_eq!(Quote::reserved_balance(&1), 10 + 9 + 4 * 8);
+
+			// Cancelling makes room again
+			assert_ok!(OrderBook::cancel_order(Origin::signed(1), 0));
+			buy(1, 7, 1);
+		})
+	}
+
+	#[test]
+	fn cancel_releases_funds() {
+		new_test_ext().execute_with(|| {
+			buy(1, 10, 5);
+			buy(2, 10, 5);
+			assert_err!(OrderBook::cancel_order(Origin::signed(2), 0), "not the owner of the order");
+
+			assert_ok!(OrderBook::cancel_order(Origin::signed(1), 0));
+			assert_eq!(Quote::reserved_balance(&1), 0);
+			assert_eq!(OrderBook::price_level(Side::Buy, 10), vec![1]);
+			assert!(event_emitted(RawEvent::OrderCancelled(0)));
+
+			assert_ok!(OrderBook::cancel_order(Origin::signed(2), 1));
+			assert!(OrderBook::prices(Side::Buy).is_empty());
+			assert_err!(OrderBook::cancel_order(Origin::signed(2), 1), "no such order");
+		})
+	}
+
+	#[test]
+	fn invalid_orders_are_rejected() {
+		new_test_ext().execute_with(|| {
+			assert_err!(
+				OrderBook::place_order(Origin::signed(1), Side::Buy, 0, 5),
+				"price and amount must not be zero"
+			);
+			assert_err!(
+				OrderBook::place_order(Origin::signed(1), Side::Sell, 5, 101),
+				"cannot afford the order"
+			);
+			assert_err!(
+				OrderBook::place_order(Origin::signed(1), Side::Buy, u64::max_value(), 2),
+				"order value overflow"
+			);
+		})
+	}
+
+	#[test]
+	fn weight_covers_max_fills() {
+		let call = Call::<TestRuntime>::place_order(Side::Buy, 1, 1);
+		assert_eq!(call.get_dispatch_info().weight, <() as WeightInfo>::place_order(3));
+	}
+}
@@ -0,0 +1,41 @@
+//! Weights for the order-book pallet
+//!
+//! Placing an order may match against many resting orders, so its weight is a function of the
+//! number of fills. The pallet charges for the worst case, `MaxFills` fills, up front. The price
+//! list and the price levels it reads are bounded by `MaxPriceLevels` and `MaxOrdersPerLevel`, so
+//! the fixed part covers them at their largest.
+
+use sp_std::marker::PhantomData;
+use frame_support::weights::Weight;
+
+/// Weight functions needed by the order-book pallet
+pub trait WeightInfo {
+	fn place_order(fills: u32) -> Weight;
+	fn cancel_order() -> Weight;
+}
+
+/// Hand-picked estimates of the order-book pallet's weights
+pub struct EstimatedWeight<T>(PhantomData<T>);
+impl<T: frame_system::Trait> WeightInfo for EstimatedWeight<T> {
+	// Fixed: one reserve, reads and writes of the price lists, a price level, the order id and the
+	// new order.
+	// Per fill: reads of the resting order and its price level, two slashes, two deposits and
+	// a write of the resting order.
+	fn place_order(fills: u32) -> Weight {
+		50_000u32.saturating_add(40_000u32.saturating_mul(fills))
+	}
+	// one order read, a price level read and write, one unreserve and one order removal
+	fn cancel_order() -> Weight {
+		40_000
+	}
+}
+
+// For backwards compatibility and tests
+impl WeightInfo for () {
+	fn place_order(fills: u32) -> Weight {
+		10_000u32.saturating_add(10_000u32.saturating_mul(fills))
+	}
+	fn cancel_order() -> Weight {
+		10_000
+	}
+}
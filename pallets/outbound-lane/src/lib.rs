@@ -0,0 +1,293 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! The sending half of a message lane between two chains
+//!
+//! A lane is an ordered, reliable channel of messages from this chain to another one. This pallet
+//! queues outbound messages under consecutive nonces. Off-chain relayers read the queue, deliver
+//! the messages to the `inbound-lane` pallet on the other chain, and bring back a proof of how
+//! far delivery got. Delivered messages are then removed from the queue.
+//!
+//! The pallet does not decide what counts as proof. A bridge checks delivery proofs against the
+//! other chain's finalized state, for example with a header bridge; the runtime plugs that check
+//! in through `VerifyDelivery`.
+
+use codec::{Decode, Encode};
+use sp_std::prelude::*;
+use sp_runtime::RuntimeDebug;
+use frame_support::{
+	decl_event, decl_module, decl_storage,
+	dispatch::DispatchResult,
+	ensure,
+	traits::Get,
+	weights::Weight,
+	Parameter,
+};
+use frame_system::{self as system, ensure_signed};
+
+/// The position of a message in its lane. The first message has nonce 1.
+pub type MessageNonce = u64;
+
+/// A message as it travels between the lanes
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct Message {
+	pub nonce: MessageNonce,
+	/// The weight the sender declared for dispatching the message on the other chain
+	pub weight: Weight,
+	pub payload: Vec<u8>,
+}
+
+/// Checks a proof, made on the receiving chain, of the latest message delivered there
+pub trait VerifyDelivery<Proof> {
+	fn verify(proof: &Proof) -> Result<MessageNonce, &'static str>;
+}
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// Proof of delivery, as brought back by relayers
+	type DeliveryProof: Parameter;
+
+	/// Checks delivery proofs
+	type VerifyDelivery: VerifyDelivery<Self::DeliveryProof>;
+
+	/// The most messages that may wait for delivery
+	type MaxPendingMessages: Get<MessageNonce>;
+
+	/// The largest payload a message may carry
+	type MaxPayloadLength: Get<u32>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as OutboundLane {
+		/// Messages waiting for delivery
+		Messages get(fn message): map MessageNonce => Option<Message>;
+
+		/// The nonce of the latest queued message
+		LatestGenerated get(fn latest_generated): MessageNonce;
+
+		/// The nonce of the latest message known to be delivered
+		LatestDelivered get(fn latest_delivered): MessageNonce;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as system::Trait>::AccountId,
+	{
+		/// A message was queued by an account
+		MessageQueued(MessageNonce, AccountId),
+		/// Delivery of the messages in the given range, inclusive, was confirmed
+		MessagesDelivered(MessageNonce, MessageNonce),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		const MaxPendingMessages: MessageNonce = T::MaxPendingMessages::get();
+
+		const MaxPayloadLength: u32 = T::MaxPayloadLength::get();
+
+		/// Queue a message for the other chain, declaring the weight it takes to dispatch there
+		fn send_message(origin, payload: Vec<u8>, weight: Weight) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			ensure!(payload.len() as u32 <= T::MaxPayloadLength::get(), "payload too long");
+			let latest = Self::latest_generated();
+			ensure!(
+				latest - Self::latest_delivered() < T::MaxPendingMessages::get(),
+				"too many pending messages"
+			);
+
+			let nonce = latest.checked_add(1).ok_or("nonce overflow")?;
+			Messages::insert(nonce, Message { nonce, weight, payload });
+			LatestGenerated::put(nonce);
+
+			Self::deposit_event(RawEvent::MessageQueued(nonce, who));
+			Ok(())
+		}
+
+		/// Confirm delivery of messages up to the nonce proven by `proof`, and drop them from
+		/// the queue
+		fn receive_delivery_proof(origin, proof: T::DeliveryProof) -> DispatchResult {
+			ensure_signed(origin)?;
+			let delivered = T::VerifyDelivery::verify(&proof)?;
+			let previous = Self::latest_delivered();
+			ensure!(delivered > previous, "no new deliveries");
+			ensure!(delivered <= Self::latest_generated(), "delivered a message never sent");
+
+			// At most `MaxPendingMessages` removals
+			for nonce in previous + 1..=delivered {
+				Messages::remove(nonce);
+			}
+			LatestDelivered::put(delivered);
+
+			Self::deposit_event(RawEvent::MessagesDelivered(previous + 1, delivered));
+			Ok(())
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use sp_core::H256;
+	use frame_support::{assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types};
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup},
+		Perbill,
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const MaxPendingMessages: u64 = 3;
+		pub const MaxPayloadLength: u32 = 8;
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	/// Takes the delivered nonce at face value. A bridge would check a storage proof instead.
+	pub struct TrustedDelivery;
+	impl VerifyDelivery<MessageNonce> for TrustedDelivery {
+		fn verify(proof: &MessageNonce) -> Result<MessageNonce, &'static str> {
+			Ok(*proof)
+		}
+	}
+
+	mod outbound_lane {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			outbound_lane<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type DeliveryProof = MessageNonce;
+		type VerifyDelivery = TrustedDelivery;
+		type MaxPendingMessages = MaxPendingMessages;
+		type MaxPayloadLength = MaxPayloadLength;
+	}
+
+	pub type System = system::Module<TestRuntime>;
+	pub type OutboundLane = Module<TestRuntime>;
+
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		let t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		t.into()
+	}
+
+	fn event_emitted(event: RawEvent<u64>) -> bool {
+		let expected = TestEvent::outbound_lane(event);
+		System::events().iter().any(|a| a.event == expected)
+	}
+
+	#[test]
+	fn messages_get_consecutive_nonces() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(OutboundLane::send_message(Origin::signed(1), b"hello".to_vec(), 10));
+			assert_ok!(OutboundLane::send_message(Origin::signed(2), b"world".to_vec(), 20));
+
+			assert_eq!(OutboundLane::latest_generated(), 2);
+			assert_eq!(
+				OutboundLane::message(2),
+				Some(Message { nonce: 2, weight: 20, payload: b"world".to_vec() })
+			);
+			assert!(event_emitted(RawEvent::MessageQueued(1, 1)));
+			assert!(event_emitted(RawEvent::MessageQueued(2, 2)));
+		})
+	}
+
+	#[test]
+	fn queue_is_bounded() {
+		new_test_ext().execute_with(|| {
+			assert_err!(
+				OutboundLane::send_message(Origin::signed(1), vec![0; 9], 10),
+				"payload too long"
+			);
+			for _ in 0..3 {
+				assert_ok!(OutboundLane::send_message(Origin::signed(1), vec![0; 8], 10));
+			}
+			assert_err!(
+				OutboundLane::send_message(Origin::signed(1), vec![], 10),
+				"too many pending messages"
+			);
+
+			// Delivery makes room again
+			assert_ok!(OutboundLane::receive_delivery_proof(Origin::signed(3), 1));
+			assert_ok!(OutboundLane::send_message(Origin::signed(1), vec![], 10));
+		})
+	}
+
+	#[test]
+	fn delivery_prunes_queue() {
+		new_test_ext().execute_with(|| {
+			for _ in 0..3 {
+				assert_ok!(OutboundLane::send_message(Origin::signed(1), vec![], 10));
+			}
+			assert_ok!(OutboundLane::receive_delivery_proof(Origin::signed(3), 2));
+
+			assert_eq!(OutboundLane::latest_delivered(), 2);
+			assert_eq!(OutboundLane::message(1), None);
+			assert_eq!(OutboundLane::message(2), None);
+			assert!(OutboundLane::message(3).is_some());
+			assert!(event_emitted(RawEvent::MessagesDelivered(1, 2)));
+		})
+	}
+
+	#[test]
+	fn delivery_proofs_must_make_progress() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(OutboundLane::send_message(Origin::signed(1), vec![], 10));
+			assert_err!(
+				OutboundLane::receive_delivery_proof(Origin::signed(3), 0),
+				"no new deliveries"
+			);
+			assert_err!(
+				OutboundLane::receive_delivery_proof(Origin::signed(3), 2),
+				"delivered a message never sent"
+			);
+			assert_ok!(OutboundLane::receive_delivery_proof(Origin::signed(3), 1));
+			assert_err!(
+				OutboundLane::receive_delivery_proof(Origin::signed(3), 1),
+				"no new deliveries"
+			);
+		})
+	}
+}
@@ -0,0 +1,355 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! Runtime parameters that governance can change
+//!
+//! Most pallets read their configuration through `Get` types in their `Trait`, which the runtime
+//! fills with `parameter_types!` constants. Changing such a constant takes a runtime upgrade. This
+//! pallet keeps parameters in storage instead, under byte-string keys, where `SetOrigin` can change
+//! them with a transaction.
+//!
+//! Other pallets read the parameters through the `ParameterStore` trait, with one getter per type.
+//! The `U32Parameter`, `PermillParameter` and `AccountParameter` adapters turn a key and a default
+//! into a `Get` type, so a pallet that expects a constant reads a stored parameter without any
+//! change to its code.
+
+use codec::{Decode, Encode};
+use sp_std::{marker::PhantomData, prelude::*};
+use sp_runtime::{Permill, RuntimeDebug};
+use frame_support::{
+	decl_event, decl_module, decl_storage,
+	dispatch::DispatchResult,
+	ensure,
+	traits::{EnsureOrigin, Get},
+};
+use frame_system::{self as system};
+
+/// A stored parameter
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub enum ParameterValue<AccountId> {
+	U32(u32),
+	Permill(Permill),
+	AccountId(AccountId),
+}
+
+impl<AccountId> ParameterValue<AccountId> {
+	fn same_type(&self, other: &Self) -> bool {
+		sp_std::mem::discriminant(self) == sp_std::mem::discriminant(other)
+	}
+}
+
+/// Typed access to parameters. Each getter returns `None` if the key is not set, or if it holds
+/// a parameter of another type.
+pub trait ParameterStore {
+	type AccountId;
+
+	fn get_u32(key: &[u8]) -> Option<u32>;
+
+	fn get_permill(key: &[u8]) -> Option<Permill>;
+
+	fn get_account(key: &[u8]) -> Option<Self::AccountId>;
+}
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The origin that sets and removes parameters, e.g. root or a council
+	type SetOrigin: EnsureOrigin<Self::Origin>;
+
+	/// The longest key, in bytes
+	type MaxKeyLength: Get<u32>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as ParameterRegistry {
+		/// The parameters that are set, by key
+		Parameters get(fn parameter): map Vec<u8> => Option<ParameterValue<T::AccountId>>;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		Value = ParameterValue<<T as system::Trait>::AccountId>,
+	{
+		/// A parameter was set to a value
+		ParameterSet(Vec<u8>, Value),
+		/// A parameter was removed; readers fall back to their defaults
+		ParameterRemoved(Vec<u8>),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		const MaxKeyLength: u32 = T::MaxKeyLength::get();
+
+		/// Set a parameter. A parameter keeps the type it was first set with, so that its readers
+		/// do not suddenly find nothing.
+		fn set_parameter(
+			origin,
+			key: Vec<u8>,
+			value: ParameterValue<T::AccountId>,
+		) -> DispatchResult {
+			T::SetOrigin::ensure_origin(origin)?;
+			ensure!(key.len() as u32 <= T::MaxKeyLength::get(), "key is too long");
+			if let Some(current) = Self::parameter(&key) {
+				ensure!(current.same_type(&value), "parameter has a different type");
+			}
+
+			<Parameters<T>>::insert(&key, &value);
+			Self::deposit_event(RawEvent::ParameterSet(key, value));
+			Ok(())
+		}
+
+		/// Remove a parameter, e.g. to change its type
+		fn remove_parameter(origin, key: Vec<u8>) -> DispatchResult {
+			T::SetOrigin::ensure_origin(origin)?;
+			ensure!(<Parameters<T>>::exists(&key), "no such parameter");
+
+			<Parameters<T>>::remove(&key);
+			Self::deposit_event(RawEvent::ParameterRemoved(key));
+			Ok(())
+		}
+	}
+}
+
+impl<T: Trait> ParameterStore for Module<T> {
+	type AccountId = T::AccountId;
+
+	fn get_u32(key: &[u8]) -> Option<u32> {
+		match Self::parameter(key.to_vec()) {
+			Some(ParameterValue::U32(value)) => Some(value),
+			_ => None,
+		}
+	}
+
+	fn get_permill(key: &[u8]) -> Option<Permill> {
+		match Self::parameter(key.to_vec()) {
+			Some(ParameterValue::Permill(value)) => Some(value),
+			_ => None,
+		}
+	}
+
+	fn get_account(key: &[u8]) -> Option<T::AccountId> {
+		match Self::parameter(key.to_vec()) {
+			Some(ParameterValue::AccountId(value)) => Some(value),
+			_ => None,
+		}
+	}
+}
+
+/// A `u32` parameter under `Key`, or `Default` if it is not set
+pub struct U32Parameter<Store, Key, Default>(PhantomData<(Store, Key, Default)>);
+
+impl<S, K, D> Get<u32> for U32Parameter<S, K, D>
+where
+	S: ParameterStore,
+	K: Get<&'static [u8]>,
+	D: Get<u32>,
+{
+	fn get() -> u32 {
+		S::get_u32(K::get()).unwrap_or_else(D::get)
+	}
+}
+
+/// A `Permill` parameter under `Key`, or `Default` if it is not set
+pub struct PermillParameter<Store, Key, Default>(PhantomData<(Store, Key, Default)>);
+
+impl<S, K, D> Get<Permill> for PermillParameter<S, K, D>
+where
+	S: ParameterStore,
+	K: Get<&'static [u8]>,
+	D: Get<Permill>,
+{
+	fn get() -> Permill {
+		S::get_permill(K::get()).unwrap_or_else(D::get)
+	}
+}
+
+/// An account parameter under `Key`, or `Default` if it is not set
+pub struct AccountParameter<Store, Key, Default>(PhantomData<(Store, Key, Default)>);
+
+impl<S, K, D> Get<S::AccountId> for AccountParameter<S, K, D>
+where
+	S: ParameterStore,
+	K: Get<&'static [u8]>,
+	D: Get<S::AccountId>,
+{
+	fn get() -> S::AccountId {
+		S::get_account(K::get()).unwrap_or_else(D::get)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use event_assert::assert_has_event;
+	use sp_core::H256;
+	use frame_support::{
+		assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types
+	};
+	use frame_system::{EnsureRoot, RawOrigin};
+	use rate_limiter::RateLimiter;
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup},
+		Perbill,
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const MaxKeyLength: u32 = 16;
+
+		pub const MaxActionsKey: &'static [u8] = b"max-actions";
+		pub const DefaultMaxActions: u32 = 1;
+		pub const Period: u64 = 10;
+
+		pub const CommissionKey: &'static [u8] = b"commission";
+		pub const DefaultCommission: Permill = Permill::from_percent(5);
+		pub const TreasuryKey: &'static [u8] = b"treasury";
+		pub const DefaultTreasury: u64 = 100;
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	mod parameter_registry {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			parameter_registry<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type SetOrigin = EnsureRoot<u64>;
+		type MaxKeyLength = MaxKeyLength;
+	}
+
+	/// The rate limiter recipe, with a limit that governance can change
+	impl rate_limiter::Trait for TestRuntime {
+		type MaxActions = U32Parameter<Registry, MaxActionsKey, DefaultMaxActions>;
+		type Period = Period;
+	}
+
+	pub type System = system::Module<TestRuntime>;
+	pub type Registry = Module<TestRuntime>;
+	pub type Limiter = rate_limiter::Module<TestRuntime>;
+
+	type Commission = PermillParameter<Registry, CommissionKey, DefaultCommission>;
+	type Treasury = AccountParameter<Registry, TreasuryKey, DefaultTreasury>;
+
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap()
+			.into()
+	}
+
+	fn set(key: &[u8], value: ParameterValue<u64>) -> DispatchResult {
+		Registry::set_parameter(RawOrigin::Root.into(), key.to_vec(), value)
+	}
+
+	#[test]
+	fn getters_are_typed() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(set(b"a", ParameterValue::U32(7)));
+			assert_ok!(set(b"b", ParameterValue::Permill(Permill::from_percent(20))));
+			assert_ok!(set(b"c", ParameterValue::AccountId(42)));
+
+			assert_eq!(Registry::get_u32(b"a"), Some(7));
+			assert_eq!(Registry::get_permill(b"b"), Some(Permill::from_percent(20)));
+			assert_eq!(Registry::get_account(b"c"), Some(42));
+
+			// Another type, or no parameter at all
+			assert_eq!(Registry::get_u32(b"b"), None);
+			assert_eq!(Registry::get_account(b"a"), None);
+			assert_eq!(Registry::get_u32(b"d"), None);
+		})
+	}
+
+	#[test]
+	fn only_set_origin_changes_parameters() {
+		new_test_ext().execute_with(|| {
+			let value = ParameterValue::U32(7);
+			assert!(Registry::set_parameter(Origin::signed(1), b"a".to_vec(), value).is_err());
+			assert!(Registry::remove_parameter(Origin::signed(1), b"a".to_vec()).is_err());
+			assert_err!(set(&[0; 17], ParameterValue::U32(7)), "key is too long");
+
+			assert_ok!(set(b"a", ParameterValue::U32(7)));
+			assert_has_event::<TestRuntime>(RawEvent::ParameterSet(b"a".to_vec(), ParameterValue::U32(7)));
+		})
+	}
+
+	#[test]
+	fn parameters_keep_their_type() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(set(b"a", ParameterValue::U32(7)));
+			assert_ok!(set(b"a", ParameterValue::U32(8)));
+			assert_err!(set(b"a", ParameterValue::AccountId(8)), "parameter has a different type");
+
+			assert_ok!(Registry::remove_parameter(RawOrigin::Root.into(), b"a".to_vec()));
+			assert_eq!(Registry::get_u32(b"a"), None);
+			assert_ok!(set(b"a", ParameterValue::AccountId(8)));
+		})
+	}
+
+	#[test]
+	fn adapters_fall_back_to_defaults() {
+		new_test_ext().execute_with(|| {
+			assert_eq!(Commission::get(), Permill::from_percent(5));
+			assert_eq!(Treasury::get(), 100);
+
+			assert_ok!(set(b"commission", ParameterValue::Permill(Permill::from_percent(1))));
+			assert_ok!(set(b"treasury", ParameterValue::AccountId(7)));
+			assert_eq!(Commission::get(), Permill::from_percent(1));
+			assert_eq!(Treasury::get(), 7);
+		})
+	}
+
+	#[test]
+	fn another_pallet_reads_the_parameter() {
+		new_test_ext().execute_with(|| {
+			System::set_block_number(1);
+			assert_ok!(Limiter::try_act(&1));
+			assert!(Limiter::try_act(&1).is_err());
+
+			// Governance raises the limit, and it applies right away
+			assert_ok!(set(b"max-actions", ParameterValue::U32(3)));
+			assert_eq!(Limiter::remaining(&1), 2);
+			assert_ok!(Limiter::try_act(&1));
+		})
+	}
+}
@@ -0,0 +1,488 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! A unidirectional payment channel
+//!
+//! A sender locks a deposit on chain once, and then pays a recipient any number of times off
+//! chain by signing vouchers. A voucher is the sender's signature over `(channel, amount)`, where
+//! `amount` is the total paid so far, so only the latest voucher matters. The recipient redeems a
+//! voucher on chain whenever they like. The signed message starts with `VOUCHER_TAG` and the
+//! chain's genesis hash, so a voucher can't be replayed on another chain, or with another pallet
+//! that signs the same numbers.
+//!
+//! * The recipient can close the channel at any time, returning the rest of the deposit.
+//! * The sender can only request to close. The recipient then has `ChallengePeriod` blocks to
+//!   redeem their latest voucher before the sender can take the rest of the deposit back.
+
+use codec::{Decode, Encode};
+use sp_std::prelude::*;
+use sp_runtime::{
+	traits::{IdentifyAccount, Member, Verify, Zero},
+	RuntimeDebug,
+};
+use frame_support::{
+	decl_event, decl_module, decl_storage,
+	dispatch::DispatchResult,
+	ensure,
+	traits::{Currency, Get, Imbalance, ReservableCurrency},
+	Parameter,
+};
+use frame_system::{self as system, ensure_signed};
+
+pub type ChannelId = u32;
+
+/// The start of every voucher, which sets them apart from messages other pallets have signed
+pub const VOUCHER_TAG: [u8; 8] = *b"paychan:";
+
+type BalanceOf<T> = <<T as Trait>::Currency as Currency<<T as system::Trait>::AccountId>>::Balance;
+type ChannelOf<T> = Channel<
+	<T as system::Trait>::AccountId,
+	BalanceOf<T>,
+	<T as system::Trait>::BlockNumber,
+>;
+
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct Channel<AccountId, Balance, BlockNumber> {
+	/// Who pays, and signs the vouchers
+	pub sender: AccountId,
+	/// Who is paid, and redeems the vouchers
+	pub recipient: AccountId,
+	/// Funds reserved from the sender, including what has been redeemed
+	pub deposit: Balance,
+	/// Total amount redeemed so far
+	pub redeemed: Balance,
+	/// The block after which the sender may close, once they asked to
+	pub closes_at: Option<BlockNumber>,
+}
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The currency in which payments are made
+	type Currency: ReservableCurrency<Self::AccountId>;
+
+	/// The signature senders put on vouchers
+	type Signature: Verify<Signer = Self::Public> + Parameter;
+
+	/// The public key that verifies a voucher, which must identify the sender's account
+	type Public: IdentifyAccount<AccountId = Self::AccountId> + Member;
+
+	/// Number of blocks the recipient has to redeem vouchers after the sender asks to close
+	type ChallengePeriod: Get<Self::BlockNumber>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as PaymentChannel {
+		/// Open channels
+		Channels get(fn channel): map ChannelId => Option<ChannelOf<T>>;
+
+		/// Number of channels ever opened; the next channel's id
+		ChannelCount get(fn channel_count): ChannelId;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as system::Trait>::AccountId,
+		Balance = BalanceOf<T>,
+		BlockNumber = <T as system::Trait>::BlockNumber,
+	{
+		/// A channel was opened from a sender to a recipient with a deposit
+		ChannelOpened(ChannelId, AccountId, AccountId, Balance),
+		/// A voucher was redeemed; the new total paid
+		VoucherRedeemed(ChannelId, Balance),
+		/// The sender asked to close the channel after the given block
+		CloseRequested(ChannelId, BlockNumber),
+		/// The channel was closed and the unpaid deposit returned to the sender
+		ChannelClosed(ChannelId, Balance),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		const ChallengePeriod: T::BlockNumber = T::ChallengePeriod::get();
+
+		/// Open a channel to `recipient`, reserving `deposit` to back the vouchers
+		fn open(origin, recipient: T::AccountId, deposit: BalanceOf<T>) -> DispatchResult {
+			let sender = ensure_signed(origin)?;
+			ensure!(sender != recipient, "cannot open a channel to oneself");
+			ensure!(!deposit.is_zero(), "deposit must not be zero");
+
+			let id = Self::channel_count();
+			let next = id.checked_add(1).ok_or("channel id overflow")?;
+			T::Currency::reserve(&sender, deposit).map_err(|_| "cannot afford the deposit")?;
+
+			<Channels<T>>::insert(id, Channel {
+				sender: sender.clone(),
+				recipient: recipient.clone(),
+				deposit,
+				redeemed: Zero::zero(),
+				closes_at: None,
+			});
+			ChannelCount::put(next);
+
+			Self::deposit_event(RawEvent::ChannelOpened(id, sender, recipient, deposit));
+			Ok(())
+		}
+
+		/// Redeem a voucher for a total of `amount`. Only the difference to what was redeemed
+		/// before is paid.
+		fn redeem(origin, id: ChannelId, amount: BalanceOf<T>, signature: T::Signature) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let mut channel = Self::channel(id).ok_or("no such channel")?;
+			ensure!(who == channel.recipient, "not the recipient");
+			ensure!(
+				signature.verify(&Self::voucher(id, amount)[..], &channel.sender),
+				"invalid voucher signature"
+			);
+			ensure!(amount > channel.redeemed, "voucher already redeemed");
+			ensure!(amount <= channel.deposit, "voucher exceeds deposit");
+
+			// Other pallets may have slashed the sender's reserve, so less may be paid. The rest of
+			// the voucher can be redeemed later, if the reserve grows again.
+			let (payment, _) = T::Currency::slash_reserved(&channel.sender, amount - channel.redeemed);
+			let redeemed = channel.redeemed + payment.peek();
+			T::Currency::resolve_creating(&channel.recipient, payment);
+			channel.redeemed = redeemed;
+			<Channels<T>>::insert(id, channel);
+
+			Self::deposit_event(RawEvent::VoucherRedeemed(id, redeemed));
+			Ok(())
+		}
+
+		/// Close a channel right away. Only the recipient may do this, as they are the one
+		/// who gives up unredeemed vouchers.
+		fn close(origin, id: ChannelId) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let channel = Self::channel(id).ok_or("no such channel")?;
+			ensure!(who == channel.recipient, "not the recipient");
+
+			Self::settle(id, channel);
+			Ok(())
+		}
+
+		/// Start the challenge period, after which the sender may close the channel
+		fn request_close(origin, id: ChannelId) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let mut channel = Self::channel(id).ok_or("no such channel")?;
+			ensure!(who == channel.sender, "not the sender");
+			ensure!(channel.closes_at.is_none(), "close already requested");
+
+			let closes_at = <system::Module<T>>::block_number() + T::ChallengePeriod::get();
+			channel.closes_at = Some(closes_at);
+			<Channels<T>>::insert(id, channel);
+
+			Self::deposit_event(RawEvent::CloseRequested(id, closes_at));
+			Ok(())
+		}
+
+		/// Close a channel once its challenge period is over
+		fn finalize_close(origin, id: ChannelId) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let channel = Self::channel(id).ok_or("no such channel")?;
+			ensure!(who == channel.sender, "not the sender");
+			let closes_at = channel.closes_at.ok_or("close not requested")?;
+			ensure!(
+				<system::Module<T>>::block_number() > closes_at,
+				"challenge period not over"
+			);
+
+			Self::settle(id, channel);
+			Ok(())
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// The message a sender signs to pay a total of `amount` through channel `id`
+	pub fn voucher(id: ChannelId, amount: BalanceOf<T>) -> Vec<u8> {
+		let genesis = <system::Module<T>>::block_hash(T::BlockNumber::zero());
+		(VOUCHER_TAG, genesis, id, amount).encode()
+	}
+
+	/// Return the unpaid deposit to the sender and remove the channel
+	fn settle(id: ChannelId, channel: ChannelOf<T>) {
+		let refund = channel.deposit - channel.redeemed;
+		T::Currency::unreserve(&channel.sender, refund);
+		<Channels<T>>::remove(id);
+
+		Self::deposit_event(RawEvent::ChannelClosed(id, refund));
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use sp_core::{sr25519, Pair, H256};
+	use frame_support::{
+		assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types
+	};
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup},
+		Perbill,
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const ExistentialDeposit: u64 = 0;
+		pub const TransferFee: u64 = 0;
+		pub const CreationFee: u64 = 0;
+
+		pub const ChallengePeriod: u64 = 5;
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		// Accounts are public keys, so that vouchers can be signed
+		type AccountId = sr25519::Public;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	impl balances::Trait for TestRuntime {
+		type Balance = u64;
+		type OnFreeBalanceZero = ();
+		type OnNewAccount = ();
+		type Event = TestEvent;
+		type TransferPayment = ();
+		type DustRemoval = ();
+		type ExistentialDeposit = ExistentialDeposit;
+		type TransferFee = TransferFee;
+		type CreationFee = CreationFee;
+	}
+
+	mod payment_channel {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			balances<T>,
+			payment_channel<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type Currency = balances::Module<Self>;
+		type Signature = sr25519::Signature;
+		type Public = sr25519::Public;
+		type ChallengePeriod = ChallengePeriod;
+	}
+
+	pub type System = system::Module<TestRuntime>;
+	pub type Balances = balances::Module<TestRuntime>;
+	pub type PaymentChannel = Module<TestRuntime>;
+
+	fn sender() -> sr25519::Pair {
+		sr25519::Pair::from_seed(&[1; 32])
+	}
+
+	fn recipient() -> sr25519::Public {
+		sr25519::Pair::from_seed(&[2; 32]).public()
+	}
+
+	fn sign_voucher(id: ChannelId, amount: u64) -> sr25519::Signature {
+		sender().sign(&PaymentChannel::voucher(id, amount))
+	}
+
+	/// The sender and the recipient have 100 each. The sender has a channel with a deposit of 50.
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		let mut t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		balances::GenesisConfig::<TestRuntime> {
+			balances: vec![(sender().public(), 100), (recipient(), 100)],
+			vesting: vec![],
+		}
+		.assimilate_storage(&mut t)
+		.unwrap();
+		let mut ext: sp_io::TestExternalities = t.into();
+		ext.execute_with(|| {
+			System::set_block_number(1);
+			assert_ok!(PaymentChannel::open(Origin::signed(sender().public()), recipient(), 50));
+		});
+		ext
+	}
+
+	fn event_emitted(event: RawEvent<sr25519::Public, u64, u64>) -> bool {
+		let expected = TestEvent::payment_channel(event);
+		System::events().iter().any(|a| a.event == expected)
+	}
+
+	#[test]
+	fn open_reserves_deposit() {
+		new_test_ext().execute_with(|| {
+			assert_eq!(Balances::reserved_balance(&sender().public()), 50);
+			assert_eq!(PaymentChannel::channel_count(), 1);
+			assert!(event_emitted(RawEvent::ChannelOpened(0, sender().public(), recipient(), 50)));
+			assert_err!(
+				PaymentChannel::open(Origin::signed(recipient()), recipient(), 10),
+				"cannot open a channel to oneself"
+			);
+		})
+	}
+
+	#[test]
+	fn redeem_pays_difference() {
+		new_test_ext().execute_with(|| {
+			let to = Origin::signed(recipient());
+			assert_ok!(PaymentChannel::redeem(to.clone(), 0, 10, sign_voucher(0, 10)));
+			assert_ok!(PaymentChannel::redeem(to, 0, 25, sign_voucher(0, 25)));
+
+			assert_eq!(Balances::free_balance(&recipient()), 125);
+			assert_eq!(Balances::reserved_balance(&sender().public()), 25);
+			assert_eq!(PaymentChannel::channel(0).map(|c| c.redeemed), Some(25));
+			assert!(event_emitted(RawEvent::VoucherRedeemed(0, 25)));
+		})
+	}
+
+	#[test]
+	fn redeem_records_what_was_paid() {
+		new_test_ext().execute_with(|| {
+			// Another pallet slashes most of the reserve
+			Balances::slash_reserved(&sender().public(), 40);
+			let to = Origin::signed(recipient());
+			assert_ok!(PaymentChannel::redeem(to, 0, 25, sign_voucher(0, 25)));
+
+			assert_eq!(Balances::free_balance(&recipient()), 110);
+			assert_eq!(PaymentChannel::channel(0).map(|c| c.redeemed), Some(10));
+			assert!(event_emitted(RawEvent::VoucherRedeemed(0, 10)));
+		})
+	}
+
+	#[test]
+	fn vouchers_are_bound_to_the_chain() {
+		new_test_ext().execute_with(|| {
+			// The same numbers, signed without the tag and the genesis hash
+			let bare = sender().sign(&(0u32, 10u64).encode());
+			assert_err!(
+				PaymentChannel::redeem(Origin::signed(recipient()), 0, 10, bare),
+				"invalid voucher signature"
+			);
+			let voucher = PaymentChannel::voucher(0, 10);
+			assert_eq!(&voucher[..8], &VOUCHER_TAG[..]);
+			assert_eq!(&voucher[8..40], System::block_hash(0).as_ref());
+		})
+	}
+
+	#[test]
+	fn stale_vouchers_are_rejected() {
+		new_test_ext().execute_with(|| {
+			let to = Origin::signed(recipient());
+			assert_ok!(PaymentChannel::redeem(to.clone(), 0, 25, sign_voucher(0, 25)));
+			assert_err!(
+				PaymentChannel::redeem(to.clone(), 0, 25, sign_voucher(0, 25)),
+				"voucher already redeemed"
+			);
+			assert_err!(
+				PaymentChannel::redeem(to, 0, 10, sign_voucher(0, 10)),
+				"voucher already redeemed"
+			);
+		})
+	}
+
+	#[test]
+	fn forged_vouchers_are_rejected() {
+		new_test_ext().execute_with(|| {
+			let to = Origin::signed(recipient());
+			// Signed for a different amount
+			assert_err!(
+				PaymentChannel::redeem(to.clone(), 0, 30, sign_voucher(0, 10)),
+				"invalid voucher signature"
+			);
+			// Signed for a different channel
+			assert_err!(
+				PaymentChannel::redeem(to.clone(), 0, 10, sign_voucher(1, 10)),
+				"invalid voucher signature"
+			);
+			// Signed by the wrong key
+			let forged = sr25519::Pair::from_seed(&[3; 32]).sign(&PaymentChannel::voucher(0, 10));
+			assert_err!(
+				PaymentChannel::redeem(to.clone(), 0, 10, forged),
+				"invalid voucher signature"
+			);
+			assert_err!(
+				PaymentChannel::redeem(to, 0, 60, sign_voucher(0, 60)),
+				"voucher exceeds deposit"
+			);
+		})
+	}
+
+	#[test]
+	fn only_recipient_redeems() {
+		new_test_ext().execute_with(|| {
+			assert_err!(
+				PaymentChannel::redeem(Origin::signed(sender().public()), 0, 10, sign_voucher(0, 10)),
+				"not the recipient"
+			);
+		})
+	}
+
+	#[test]
+	fn recipient_closes_immediately() {
+		new_test_ext().execute_with(|| {
+			let to = Origin::signed(recipient());
+			assert_ok!(PaymentChannel::redeem(to.clone(), 0, 20, sign_voucher(0, 20)));
+			assert_ok!(PaymentChannel::close(to, 0));
+
+			assert_eq!(PaymentChannel::channel(0), None);
+			assert_eq!(Balances::free_balance(&sender().public()), 80);
+			assert_eq!(Balances::reserved_balance(&sender().public()), 0);
+			assert!(event_emitted(RawEvent::ChannelClosed(0, 30)));
+		})
+	}
+
+	#[test]
+	fn sender_close_waits_for_challenge_period() {
+		new_test_ext().execute_with(|| {
+			let from = Origin::signed(sender().public());
+			assert_err!(PaymentChannel::close(from.clone(), 0), "not the recipient");
+			assert_err!(PaymentChannel::finalize_close(from.clone(), 0), "close not requested");
+
+			assert_ok!(PaymentChannel::request_close(from.clone(), 0));
+			assert!(event_emitted(RawEvent::CloseRequested(0, 6)));
+			assert_err!(PaymentChannel::request_close(from.clone(), 0), "close already requested");
+
+			System::set_block_number(6);
+			assert_err!(PaymentChannel::finalize_close(from.clone(), 0), "challenge period not over");
+
+			// The recipient can still redeem during the challenge period
+			assert_ok!(PaymentChannel::redeem(
+				Origin::signed(recipient()), 0, 15, sign_voucher(0, 15)
+			));
+
+			System::set_block_number(7);
+			assert_ok!(PaymentChannel::finalize_close(from, 0));
+			assert_eq!(Balances::free_balance(&sender().public()), 85);
+			assert_eq!(Balances::free_balance(&recipient()), 115);
+		})
+	}
+}
@@ -0,0 +1,328 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! A priority queue stored as a binary heap
+//!
+//! The heap lives in a map from position to item, with the number of items as a separate value.
+//! The item at position `i` has its children at `2i + 1` and `2i + 2`, and no child has a higher
+//! priority than its parent, so the highest priority item is always at position 0.
+//!
+//! * `push` adds an item at the end and moves it up until its parent's priority is at least as
+//!   high.
+//! * `pop` removes the root, puts the last item in its place and moves it down below any child
+//!   with a higher priority.
+//!
+//! Either walks a single path through the heap, so it reads and writes O(log n) entries. Items of
+//! equal priority leave the queue in no particular order.
+
+use codec::{Decode, Encode};
+use sp_runtime::RuntimeDebug;
+use frame_support::{
+	decl_event, decl_module, decl_storage,
+	dispatch::DispatchResult,
+	ensure,
+	traits::Get,
+	weights::SimpleDispatchInfo,
+};
+use frame_system::{self as system, ensure_signed};
+
+pub mod weights;
+pub use weights::WeightInfo;
+
+#[derive(Encode, Decode, Default, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct HeapItem {
+	pub priority: u32,
+	pub value: u32,
+}
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The most items the queue can hold
+	type MaxLength: Get<u32>;
+
+	/// Weight information for the dispatchables in this pallet
+	type WeightInfo: WeightInfo;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as PriorityQueue {
+		/// The number of items in the heap
+		Length get(fn len): u32;
+
+		/// The items of the heap by position. Positions from `Length` on are empty.
+		Heap get(fn item): map u32 => HeapItem;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as system::Trait>::AccountId,
+	{
+		/// An account pushed an item with this priority and value
+		Pushed(AccountId, u32, u32),
+		/// An account popped the item with the highest priority
+		Popped(AccountId, u32, u32),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		const MaxLength: u32 = T::MaxLength::get();
+
+		/// Add an item to the queue
+		#[weight = SimpleDispatchInfo::FixedNormal(T::WeightInfo::push(<Module<T>>::max_depth()))]
+		fn push(origin, priority: u32, value: u32) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let len = Self::len();
+			ensure!(len < T::MaxLength::get(), "queue is full");
+
+			Self::sift_up(len, HeapItem { priority, value });
+			Length::put(len + 1);
+
+			Self::deposit_event(RawEvent::Pushed(who, priority, value));
+			Ok(())
+		}
+
+		/// Remove the item with the highest priority from the queue
+		#[weight = SimpleDispatchInfo::FixedNormal(T::WeightInfo::pop(<Module<T>>::max_depth()))]
+		fn pop(origin) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let len = Self::len();
+			ensure!(len > 0, "queue is empty");
+
+			let top = Heap::get(0);
+			let new_len = len - 1;
+			let last = Heap::take(new_len);
+			if new_len > 0 {
+				Self::sift_down(0, last, new_len);
+			}
+			Length::put(new_len);
+
+			Self::deposit_event(RawEvent::Popped(who, top.priority, top.value));
+			Ok(())
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// The item with the highest priority, if any
+	pub fn peek() -> Option<HeapItem> {
+		if Self::len() > 0 {
+			Some(Heap::get(0))
+		} else {
+			None
+		}
+	}
+
+	/// The number of levels of a full heap
+	pub fn max_depth() -> u32 {
+		32 - T::MaxLength::get().leading_zeros()
+	}
+
+	/// Place `item` at the empty position `index` or above it, moving lower priority parents
+	/// down. Each parent is written once, instead of swapping the item up level by level.
+	fn sift_up(mut index: u32, item: HeapItem) {
+		while index > 0 {
+			let parent = (index - 1) / 2;
+			let parent_item = Heap::get(parent);
+			if parent_item.priority >= item.priority {
+				break;
+			}
+			Heap::insert(index, parent_item);
+			index = parent;
+		}
+		Heap::insert(index, item);
+	}
+
+	/// Place `item` at position `index` or below it, moving higher priority children up
+	fn sift_down(mut index: u32, item: HeapItem, len: u32) {
+		loop {
+			let left = 2 * index + 1;
+			if left >= len {
+				break;
+			}
+			let right = left + 1;
+			let mut child = left;
+			let mut child_item = Heap::get(left);
+			if right < len {
+				let right_item = Heap::get(right);
+				if right_item.priority > child_item.priority {
+					child = right;
+					child_item = right_item;
+				}
+			}
+			if child_item.priority <= item.priority {
+				break;
+			}
+			Heap::insert(index, child_item);
+			index = child;
+		}
+		Heap::insert(index, item);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use event_assert::assert_last_event;
+	use sp_core::H256;
+	use frame_support::{
+		assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types,
+		weights::GetDispatchInfo,
+	};
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup},
+		Perbill,
+	};
+	use rand::{rngs::StdRng, Rng, SeedableRng};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const MaxLength: u32 = 100;
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	mod priority_queue {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			priority_queue<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type MaxLength = MaxLength;
+		type WeightInfo = ();
+	}
+
+	pub type PriorityQueue = Module<TestRuntime>;
+
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		let t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		t.into()
+	}
+
+	/// Pops an item and returns its priority
+	fn pop_priority() -> u32 {
+		let top = PriorityQueue::peek().expect("queue is not empty");
+		assert_ok!(PriorityQueue::pop(Origin::signed(1)));
+		top.priority
+	}
+
+	/// Whether every item has no higher priority than its parent
+	fn is_heap() -> bool {
+		(1..PriorityQueue::len()).all(|i| {
+			PriorityQueue::item((i - 1) / 2).priority >= PriorityQueue::item(i).priority
+		})
+	}
+
+	#[test]
+	fn pops_highest_priority_first() {
+		new_test_ext().execute_with(|| {
+			for (priority, value) in [(3, 30), (7, 70), (1, 10), (5, 50)].iter() {
+				assert_ok!(PriorityQueue::push(Origin::signed(1), *priority, *value));
+			}
+			assert_eq!(PriorityQueue::peek(), Some(HeapItem { priority: 7, value: 70 }));
+
+			assert_ok!(PriorityQueue::pop(Origin::signed(2)));
+			assert_last_event::<TestRuntime>(RawEvent::Popped(2, 7, 70));
+			assert_eq!(pop_priority(), 5);
+			assert_eq!(pop_priority(), 3);
+			assert_eq!(pop_priority(), 1);
+			assert_eq!(PriorityQueue::peek(), None);
+		})
+	}
+
+	#[test]
+	fn popping_clears_storage() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(PriorityQueue::push(Origin::signed(1), 1, 1));
+			assert_ok!(PriorityQueue::push(Origin::signed(1), 2, 2));
+			assert_ok!(PriorityQueue::pop(Origin::signed(1)));
+			assert_ok!(PriorityQueue::pop(Origin::signed(1)));
+
+			assert_eq!(PriorityQueue::len(), 0);
+			assert!(!Heap::exists(0));
+			assert!(!Heap::exists(1));
+		})
+	}
+
+	#[test]
+	fn queue_is_bounded() {
+		new_test_ext().execute_with(|| {
+			assert_err!(PriorityQueue::pop(Origin::signed(1)), "queue is empty");
+			for i in 0..100 {
+				assert_ok!(PriorityQueue::push(Origin::signed(1), i, i));
+			}
+			assert_err!(PriorityQueue::push(Origin::signed(1), 0, 0), "queue is full");
+		})
+	}
+
+	#[test]
+	fn random_operations_keep_heap_order() {
+		new_test_ext().execute_with(|| {
+			let mut rng = StdRng::seed_from_u64(42);
+			let mut expected: Vec<u32> = Vec::new();
+			for _ in 0..500 {
+				let len = PriorityQueue::len();
+				if len < 100 && (len == 0 || rng.gen_bool(0.6)) {
+					let priority = rng.gen_range(0, 50);
+					assert_ok!(PriorityQueue::push(Origin::signed(1), priority, 0));
+					expected.push(priority);
+				} else {
+					expected.sort();
+					assert_eq!(pop_priority(), expected.pop().unwrap());
+				}
+				assert!(is_heap());
+				assert_eq!(PriorityQueue::len() as usize, expected.len());
+			}
+		})
+	}
+
+	#[test]
+	fn weight_depends_on_depth() {
+		// 100 items fill 7 levels
+		assert_eq!(PriorityQueue::max_depth(), 7);
+		let call = Call::<TestRuntime>::push(1, 1);
+		assert_eq!(call.get_dispatch_info().weight, <() as WeightInfo>::push(7));
+	}
+}
@@ -0,0 +1,39 @@
+//! Weights for the priority-queue pallet
+//!
+//! Pushing and popping walk one path between the root and the bottom of the heap, so their weight
+//! is a function of the heap's depth. The pallet charges for the depth of a full queue.
+
+use sp_std::marker::PhantomData;
+use frame_support::weights::Weight;
+
+/// Weight functions needed by the priority-queue pallet
+pub trait WeightInfo {
+    fn push(depth: u32) -> Weight;
+    fn pop(depth: u32) -> Weight;
+}
+
+/// Hand-picked estimates of the priority-queue pallet's weights
+pub struct EstimatedWeight<T>(PhantomData<T>);
+impl<T: frame_system::Trait> WeightInfo for EstimatedWeight<T> {
+    // Fixed: a read and write of the length and a write of the new item.
+    // Per level: a read of the parent and a write moving it down.
+    fn push(depth: u32) -> Weight {
+        15_000u32.saturating_add(10_000u32.saturating_mul(depth))
+    }
+    // Fixed: a read and write of the length, reads of the root and the last item, and removal of
+    // the last item.
+    // Per level: reads of two children and a write moving one of them up.
+    fn pop(depth: u32) -> Weight {
+        25_000u32.saturating_add(15_000u32.saturating_mul(depth))
+    }
+}
+
+// For backwards compatibility and tests
+impl WeightInfo for () {
+    fn push(depth: u32) -> Weight {
+        10_000u32.saturating_add(1_000u32.saturating_mul(depth))
+    }
+    fn pop(depth: u32) -> Weight {
+        10_000u32.saturating_add(1_000u32.saturating_mul(depth))
+    }
+}
@@ -0,0 +1,613 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! Quadratic funding rounds, in which a matching pool favours projects with many contributors
+//!
+//! A sponsor `create_round`s with a matching pool and an end. Until the end, anyone can `register`
+//! a project in the round, and anyone can `contribute` to its projects. Once the round ended,
+//! anyone can `close` it, which splits the matching pool among the projects by the quadratic
+//! funding (CLR) formula. Each project is matched
+//!
+//! ```text
+//! (√c₁ + √c₂ + … + √cₙ)² - (c₁ + c₂ + … + cₙ)
+//! ```
+//!
+//! where `cᵢ` is the total of the `i`th contributor. A project backed by one whale is not
+//! matched at all, while many small contributions are matched generously. If the pool can't cover
+//! every project's match, it is shared in proportion to them, and whatever the pool has left after
+//! matching goes back to the sponsor. Finally anyone can `claim` a project's contributions and
+//! match for its owner.
+//!
+//! Square roots are kept in fixed point with `SQRT_SCALE` parts per unit. Each contribution keeps
+//! the sum of its project's square roots up to date, so closing a round only visits the projects,
+//! of which there are at most `MaxProjects`. Shares of the pool are `Perbill`s, so each project's
+//! match is off by about a billionth of the pool at most.
+
+use codec::{Decode, Encode};
+use sp_std::prelude::*;
+use sp_runtime::{
+	traits::{AccountIdConversion, IntegerSquareRoot, SaturatedConversion, Saturating, Zero},
+	ModuleId, Perbill, RuntimeDebug,
+};
+use frame_support::{
+	decl_event, decl_module, decl_storage,
+	dispatch::DispatchResult,
+	ensure,
+	traits::{Currency, ExistenceRequirement::AllowDeath, Get},
+};
+use frame_system::{self as system, ensure_signed};
+
+pub type RoundIndex = u32;
+pub type ProjectIndex = u32;
+
+type BalanceOf<T> = <<T as Trait>::Currency as Currency<<T as system::Trait>::AccountId>>::Balance;
+type RoundOf<T> =
+	Round<<T as system::Trait>::AccountId, BalanceOf<T>, <T as system::Trait>::BlockNumber>;
+type ProjectOf<T> = Project<<T as system::Trait>::AccountId, BalanceOf<T>>;
+
+/// Hardcoded pallet ID; used to create the account that holds pools and contributions
+/// Must be exactly 8 characters long
+const PALLET_ID: ModuleId = ModuleId(*b"QuadFund");
+
+/// Square roots are kept in fixed point, with this many parts per unit
+pub const SQRT_SCALE: u128 = 1_000_000;
+
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct Round<AccountId, Balance, BlockNumber> {
+	/// Who provided the matching pool, and gets back what is left of it
+	pub sponsor: AccountId,
+	pub matching: Balance,
+	/// Contributions are accepted before this block
+	pub end: BlockNumber,
+	/// Number of projects registered; the next project's index
+	pub project_count: ProjectIndex,
+	pub closed: bool,
+}
+
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct Project<AccountId, Balance> {
+	/// Who is paid the contributions and the match
+	pub owner: AccountId,
+	/// The total of all contributions
+	pub contributed: Balance,
+	/// The sum of the square roots of each contributor's total, in fixed point
+	pub sqrt_sum: u128,
+	/// The project's share of the matching pool, once the round closed
+	pub matched: Balance,
+}
+
+/// `√amount` in fixed point with `SQRT_SCALE` parts per unit, rounded down
+pub fn fixed_sqrt(amount: u128) -> u128 {
+	amount.saturating_mul(SQRT_SCALE * SQRT_SCALE).integer_sqrt()
+}
+
+/// The match a project is owed, `(Σ √cᵢ)² - Σ cᵢ`, given the fixed-point sum of the square
+/// roots of its contributions and their total. In fixed point with `SQRT_SCALE²` parts per unit.
+pub fn match_weight(sqrt_sum: u128, contributed: u128) -> u128 {
+	let contributed = contributed.saturating_mul(SQRT_SCALE * SQRT_SCALE);
+	sqrt_sum.saturating_mul(sqrt_sum).saturating_sub(contributed)
+}
+
+/// Split `matching` among projects with the given `match_weight`s. If the pool covers every
+/// match, each project gets its match in full. Otherwise the pool is shared in proportion to them.
+pub fn matches(weights: &[u128], matching: u128) -> Vec<u128> {
+	let unit = SQRT_SCALE * SQRT_SCALE;
+	let full = weights.iter().map(|weight| weight / unit).collect::<Vec<_>>();
+	if full.iter().fold(0u128, |sum, owed| sum.saturating_add(*owed)) <= matching {
+		return full;
+	}
+
+	let total = weights.iter().fold(0u128, |sum, weight| sum.saturating_add(*weight));
+	// A `Perbill` rounds to the nearest part, so the shares could add up to slightly more than
+	// the pool. No project gets more than what is left.
+	let mut remaining = matching;
+	weights
+		.iter()
+		.map(|weight| {
+			let share = Perbill::from_rational_approximation(*weight, total) * matching;
+			let share = share.min(remaining);
+			remaining -= share;
+			share
+		})
+		.collect()
+}
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The currency of pools and contributions
+	type Currency: Currency<Self::AccountId>;
+
+	/// The most projects in a round, which bounds the work of closing it
+	type MaxProjects: Get<u32>;
+
+	/// The smallest contribution. Without it, splitting a contribution among many accounts costs
+	/// nothing, and would raise a project's match.
+	type MinContribution: Get<BalanceOf<Self>>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as QuadraticFunding {
+		Rounds get(fn round): map RoundIndex => Option<RoundOf<T>>;
+
+		/// Number of rounds ever created; the next round's index
+		RoundCount get(fn round_count): RoundIndex;
+
+		/// Projects that have not been paid yet
+		Projects get(fn project):
+			double_map RoundIndex, blake2_256(ProjectIndex) => Option<ProjectOf<T>>;
+
+		/// Each contributor's total for a project, while its round is open
+		Contributions get(fn contribution):
+			double_map RoundIndex, blake2_256((ProjectIndex, T::AccountId)) => BalanceOf<T>;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as system::Trait>::AccountId,
+		Balance = BalanceOf<T>,
+		BlockNumber = <T as system::Trait>::BlockNumber,
+	{
+		/// A sponsor created a round with a matching pool, open until a block
+		RoundCreated(RoundIndex, AccountId, Balance, BlockNumber),
+		/// A project was registered in a round for an owner
+		ProjectRegistered(RoundIndex, ProjectIndex, AccountId),
+		/// Someone contributed to a project
+		Contributed(RoundIndex, ProjectIndex, AccountId, Balance),
+		/// A round closed, and this much of the pool went back to the sponsor
+		RoundClosed(RoundIndex, Balance),
+		/// A project was matched this much
+		ProjectMatched(RoundIndex, ProjectIndex, Balance),
+		/// A project's owner was paid its contributions and match
+		ProjectPaid(RoundIndex, ProjectIndex, AccountId, Balance),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		const MaxProjects: u32 = T::MaxProjects::get();
+
+		const MinContribution: BalanceOf<T> = T::MinContribution::get();
+
+		/// Start a round, funding its matching pool. Contributions are accepted before `end`.
+		fn create_round(origin, matching: BalanceOf<T>, end: T::BlockNumber) -> DispatchResult {
+			let sponsor = ensure_signed(origin)?;
+			ensure!(end > <system::Module<T>>::block_number(), "end must be in the future");
+			T::Currency::transfer(&sponsor, &Self::account_id(), matching, AllowDeath)?;
+
+			let index = Self::round_count();
+			let round = Round {
+				sponsor: sponsor.clone(),
+				matching,
+				end,
+				project_count: 0,
+				closed: false,
+			};
+			<Rounds<T>>::insert(index, round);
+			RoundCount::put(index + 1);
+			Self::deposit_event(RawEvent::RoundCreated(index, sponsor, matching, end));
+			Ok(())
+		}
+
+		/// Register a project, owned by the caller, in an open round
+		fn register(origin, round_index: RoundIndex) -> DispatchResult {
+			let owner = ensure_signed(origin)?;
+			let mut round = Self::open_round(round_index)?;
+			ensure!(round.project_count < T::MaxProjects::get(), "too many projects");
+
+			let index = round.project_count;
+			let project = Project {
+				owner: owner.clone(),
+				contributed: Zero::zero(),
+				sqrt_sum: 0,
+				matched: Zero::zero(),
+			};
+			<Projects<T>>::insert(round_index, index, project);
+			round.project_count += 1;
+			<Rounds<T>>::insert(round_index, round);
+			Self::deposit_event(RawEvent::ProjectRegistered(round_index, index, owner));
+			Ok(())
+		}
+
+		fn contribute(
+			origin,
+			round_index: RoundIndex,
+			project_index: ProjectIndex,
+			amount: BalanceOf<T>,
+		) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			Self::open_round(round_index)?;
+			let mut project = Self::project(round_index, project_index).ok_or("no such project")?;
+			ensure!(amount >= T::MinContribution::get(), "contribution too small");
+			T::Currency::transfer(&who, &Self::account_id(), amount, AllowDeath)?;
+
+			// The contributor's square root changes with their total, so the old one is replaced
+			let key = (project_index, who.clone());
+			let before = Self::contribution(round_index, &key);
+			let after = before.saturating_add(amount);
+			project.sqrt_sum = project.sqrt_sum
+				.saturating_sub(fixed_sqrt(before.saturated_into()))
+				.saturating_add(fixed_sqrt(after.saturated_into()));
+			project.contributed = project.contributed.saturating_add(amount);
+
+			<Contributions<T>>::insert(round_index, &key, after);
+			<Projects<T>>::insert(round_index, project_index, project);
+			Self::deposit_event(RawEvent::Contributed(round_index, project_index, who, amount));
+			Ok(())
+		}
+
+		/// Split the matching pool of an ended round among its projects
+		fn close(origin, round_index: RoundIndex) -> DispatchResult {
+			let _ = ensure_signed(origin)?;
+			let mut round = Self::round(round_index).ok_or("no such round")?;
+			ensure!(!round.closed, "round closed");
+			ensure!(<system::Module<T>>::block_number() >= round.end, "round still open");
+
+			let projects = (0..round.project_count)
+				.filter_map(|index| Self::project(round_index, index).map(|p| (index, p)))
+				.collect::<Vec<_>>();
+			let weights = projects
+				.iter()
+				.map(|(_, p)| match_weight(p.sqrt_sum, p.contributed.saturated_into()))
+				.collect::<Vec<_>>();
+			let shares = matches(&weights, round.matching.saturated_into());
+			let matched = shares.iter().fold(0u128, |sum, share| sum.saturating_add(*share));
+			let leftover = round.matching.saturating_sub(matched.saturated_into());
+			T::Currency::transfer(&Self::account_id(), &round.sponsor, leftover, AllowDeath)?;
+
+			for ((index, mut project), share) in projects.into_iter().zip(shares) {
+				project.matched = share.saturated_into();
+				Self::deposit_event(RawEvent::ProjectMatched(round_index, index, project.matched));
+				<Projects<T>>::insert(round_index, index, project);
+			}
+			// The individual totals only mattered for the square roots
+			<Contributions<T>>::remove_prefix(&round_index);
+			round.closed = true;
+			<Rounds<T>>::insert(round_index, round);
+			Self::deposit_event(RawEvent::RoundClosed(round_index, leftover));
+			Ok(())
+		}
+
+		/// Pay a project of a closed round its contributions and match. Anyone may call this, but
+		/// the owner is paid.
+		fn claim(origin, round_index: RoundIndex, project_index: ProjectIndex) -> DispatchResult {
+			let _ = ensure_signed(origin)?;
+			let round = Self::round(round_index).ok_or("no such round")?;
+			ensure!(round.closed, "round not closed");
+			let project = Self::project(round_index, project_index).ok_or("no such project")?;
+
+			let amount = project.contributed.saturating_add(project.matched);
+			T::Currency::transfer(&Self::account_id(), &project.owner, amount, AllowDeath)?;
+			<Projects<T>>::remove(round_index, project_index);
+			Self::deposit_event(
+				RawEvent::ProjectPaid(round_index, project_index, project.owner, amount)
+			);
+			Ok(())
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// The account ID that holds the pools and contributions of all rounds
+	pub fn account_id() -> T::AccountId {
+		PALLET_ID.into_account()
+	}
+
+	fn open_round(index: RoundIndex) -> Result<RoundOf<T>, &'static str> {
+		let round = Self::round(index).ok_or("no such round")?;
+		ensure!(<system::Module<T>>::block_number() < round.end, "round ended");
+		Ok(round)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use event_assert::assert_has_event;
+	use sp_core::H256;
+	use frame_support::{
+		assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types,
+	};
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup},
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const ExistentialDeposit: u64 = 0;
+		pub const TransferFee: u64 = 0;
+		pub const CreationFee: u64 = 0;
+
+		pub const MaxProjects: u32 = 3;
+		pub const MinContribution: u64 = 10;
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	impl balances::Trait for TestRuntime {
+		type Balance = u64;
+		type OnFreeBalanceZero = ();
+		type OnNewAccount = ();
+		type Event = TestEvent;
+		type TransferPayment = ();
+		type DustRemoval = ();
+		type ExistentialDeposit = ExistentialDeposit;
+		type TransferFee = TransferFee;
+		type CreationFee = CreationFee;
+	}
+
+	mod quadratic_funding {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			balances<T>,
+			quadratic_funding<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type Currency = balances::Module<Self>;
+		type MaxProjects = MaxProjects;
+		type MinContribution = MinContribution;
+	}
+
+	pub type System = system::Module<TestRuntime>;
+	pub type Balances = balances::Module<TestRuntime>;
+	pub type QuadraticFunding = Module<TestRuntime>;
+
+	/// Accounts 1 to 9 have 10_000 each. In block 1, account 1 sponsors round 0, which ends in
+	/// block 10, and accounts 7, 8 and 9 register projects 0, 1 and 2 in it.
+	fn new_test_ext(matching: u64) -> sp_io::TestExternalities {
+		let mut t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		balances::GenesisConfig::<TestRuntime> {
+			balances: (1..=9).map(|who| (who, 10_000)).collect(),
+			vesting: vec![],
+		}
+		.assimilate_storage(&mut t)
+		.unwrap();
+		let mut ext: sp_io::TestExternalities = t.into();
+		ext.execute_with(|| {
+			System::set_block_number(1);
+			assert_ok!(QuadraticFunding::create_round(Origin::signed(1), matching, 10));
+			for owner in 7..=9 {
+				assert_ok!(QuadraticFunding::register(Origin::signed(owner), 0));
+			}
+		});
+		ext
+	}
+
+	/// The quadratic funding formula in floating point, to check the fixed-point implementation
+	/// against. Each project is given as the totals of its contributors.
+	fn reference(projects: &[Vec<u64>], matching: u64) -> Vec<f64> {
+		let owed = projects
+			.iter()
+			.map(|totals| {
+				let sqrt_sum: f64 = totals.iter().map(|total| (*total as f64).sqrt()).sum();
+				let contributed: f64 = totals.iter().map(|total| *total as f64).sum();
+				sqrt_sum * sqrt_sum - contributed
+			})
+			.collect::<Vec<_>>();
+		let total: f64 = owed.iter().sum();
+		let scale = if total <= matching as f64 { 1.0 } else { matching as f64 / total };
+		owed.iter().map(|owed| owed * scale).collect()
+	}
+
+	fn fixed_point(projects: &[Vec<u64>], matching: u64) -> Vec<u128> {
+		let weights = projects
+			.iter()
+			.map(|totals| {
+				let sqrt_sum = totals.iter().map(|total| fixed_sqrt(u128::from(*total))).sum();
+				match_weight(sqrt_sum, totals.iter().map(|total| u128::from(*total)).sum())
+			})
+			.collect::<Vec<_>>();
+		matches(&weights, u128::from(matching))
+	}
+
+	/// Assert that the fixed-point matches are within a unit and a billionth of the pool of the
+	/// reference's
+	fn assert_close(projects: &[Vec<u64>], matching: u64) {
+		let tolerance = 1.0 + matching as f64 / 1e9;
+		let expected = reference(projects, matching);
+		let actual = fixed_point(projects, matching);
+		for (expected, actual) in expected.iter().zip(actual.iter()) {
+			assert!(
+				(*actual as f64 - expected).abs() <= tolerance,
+				"matched {} instead of {} in {:?}", actual, expected, projects,
+			);
+		}
+		assert!(actual.iter().sum::<u128>() <= u128::from(matching));
+	}
+
+	fn contribute(who: u64, project: ProjectIndex, amount: u64) {
+		assert_ok!(QuadraticFunding::contribute(Origin::signed(who), 0, project, amount));
+	}
+
+	fn close() -> DispatchResult {
+		QuadraticFunding::close(Origin::signed(1), 0)
+	}
+
+	fn matched(project: ProjectIndex) -> u64 {
+		QuadraticFunding::project(0, project).unwrap().matched
+	}
+
+	#[test]
+	fn fixed_sqrt_works() {
+		assert_eq!(fixed_sqrt(0), 0);
+		assert_eq!(fixed_sqrt(4), 2 * SQRT_SCALE);
+		// √2 = 1.414213562...
+		assert_eq!(fixed_sqrt(2), 1_414_213);
+		assert_eq!(fixed_sqrt(u128::max_value()), u128::max_value().integer_sqrt());
+	}
+
+	#[test]
+	fn match_weight_follows_the_formula() {
+		let unit = SQRT_SCALE * SQRT_SCALE;
+		// A single contributor is never matched
+		assert_eq!(match_weight(fixed_sqrt(900), 900), 0);
+		// (√100 + √100 + √100 + √100)² - 400 = 1200
+		assert_eq!(match_weight(4 * fixed_sqrt(100), 400), 1200 * unit);
+		// (√400 + √900)² - 1300 = 1200
+		assert_eq!(match_weight(fixed_sqrt(400) + fixed_sqrt(900), 1300), 1200 * unit);
+	}
+
+	#[test]
+	fn matches_agree_with_the_reference() {
+		// Pools that cover every match, and pools that don't
+		for matching in &[10, 1_000, 5_000, 1_000_000, 1_000_000_000] {
+			assert_close(&[vec![100; 4], vec![400], vec![400, 900]], *matching);
+			assert_close(&[vec![1, 2, 3, 5, 7, 11, 13], vec![99_999, 1], vec![]], *matching);
+		}
+
+		// Awkward contributions from a linear congruential generator
+		let mut seed = 42u64;
+		let mut next = || {
+			seed = seed
+				.wrapping_mul(6_364_136_223_846_793_005)
+				.wrapping_add(1_442_695_040_888_963_407);
+			seed >> 40
+		};
+		for _ in 0..50 {
+			let projects = (0..5)
+				.map(|_| (0..next() % 20).map(|_| next() % 1_000_000 + 1).collect())
+				.collect::<Vec<Vec<u64>>>();
+			let matching = next() * 1_000;
+			assert_close(&projects, matching);
+		}
+	}
+
+	#[test]
+	fn large_balances_keep_their_precision() {
+		let projects = [
+			vec![1_000_000_000_000; 10],
+			vec![3_333_333_333_333, 7_777_777_777_777],
+			vec![123_456_789_012, 987_654_321_098, 555_555_555_555],
+		];
+		assert_close(&projects, 1_000_000_000_000_000);
+	}
+
+	#[test]
+	fn many_small_contributions_are_matched_more() {
+		new_test_ext(1_000).execute_with(|| {
+			// Four contributors of 100 to project 0, one of 400 to project 1, and two of 400 and
+			// 900 to project 2. Projects 0 and 2 are owed 1200 each, project 1 nothing.
+			for who in 2..=5 {
+				contribute(who, 0, 100);
+			}
+			contribute(2, 1, 400);
+			contribute(3, 2, 400);
+			contribute(4, 2, 400);
+			// Topping up counts as one contribution of the total
+			contribute(4, 2, 500);
+
+			System::set_block_number(10);
+			assert_ok!(close());
+			assert_eq!(matched(0), 500);
+			assert_eq!(matched(1), 0);
+			assert_eq!(matched(2), 500);
+			// The pool was used up
+			assert_eq!(Balances::free_balance(&1), 9_000);
+
+			assert_ok!(QuadraticFunding::claim(Origin::signed(1), 0, 0));
+			assert_ok!(QuadraticFunding::claim(Origin::signed(1), 0, 1));
+			assert_ok!(QuadraticFunding::claim(Origin::signed(1), 0, 2));
+			assert_eq!(Balances::free_balance(&7), 10_900);
+			assert_eq!(Balances::free_balance(&8), 10_400);
+			assert_eq!(Balances::free_balance(&9), 11_800);
+			assert_eq!(Balances::free_balance(&QuadraticFunding::account_id()), 0);
+		})
+	}
+
+	#[test]
+	fn unused_matching_returns_to_the_sponsor() {
+		new_test_ext(5_000).execute_with(|| {
+			for who in 2..=5 {
+				contribute(who, 0, 100);
+			}
+
+			System::set_block_number(10);
+			assert_ok!(close());
+			assert_eq!(matched(0), 1_200);
+			assert_eq!(Balances::free_balance(&1), 8_800);
+			assert_has_event::<TestRuntime>(RawEvent::RoundClosed(0, 3_800));
+		})
+	}
+
+	#[test]
+	fn rounds_close_once_after_their_end() {
+		new_test_ext(1_000).execute_with(|| {
+			contribute(2, 0, 100);
+			assert_err!(close(), "round still open");
+			assert_err!(QuadraticFunding::claim(Origin::signed(1), 0, 0), "round not closed");
+
+			System::set_block_number(10);
+			assert_err!(
+				QuadraticFunding::contribute(Origin::signed(2), 0, 0, 100),
+				"round ended"
+			);
+			assert_err!(QuadraticFunding::register(Origin::signed(2), 0), "round ended");
+			assert_ok!(close());
+			assert_err!(close(), "round closed");
+			assert_eq!(QuadraticFunding::contribution(0, (0u32, 2u64)), 0);
+
+			assert_ok!(QuadraticFunding::claim(Origin::signed(1), 0, 0));
+			assert_err!(QuadraticFunding::claim(Origin::signed(1), 0, 0), "no such project");
+		})
+	}
+
+	#[test]
+	fn rounds_are_bounded() {
+		new_test_ext(1_000).execute_with(|| {
+			assert_err!(QuadraticFunding::register(Origin::signed(2), 0), "too many projects");
+			assert_err!(
+				QuadraticFunding::contribute(Origin::signed(2), 0, 3, 100),
+				"no such project"
+			);
+			assert_err!(
+				QuadraticFunding::contribute(Origin::signed(2), 0, 0, 9),
+				"contribution too small"
+			);
+			assert_err!(
+				QuadraticFunding::create_round(Origin::signed(1), 1_000, 1),
+				"end must be in the future"
+			);
+		})
+	}
+}
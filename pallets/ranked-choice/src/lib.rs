@@ -0,0 +1,558 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! A committee elected by ranked ballots, tallied by instant runoff
+//!
+//! Up to `MaxCandidates` accounts `submit_candidacy`. Up to `MaxVoters` accounts `vote` with a
+//! ballot that ranks some of the candidates, most preferred first. Once `TermDuration` blocks
+//! passed since the last election, anyone can call `elect`, which fills `Seats` seats one after
+//! another by instant runoff:
+//!
+//! * Each ballot counts for its most preferred hopeful candidate.
+//! * A candidate with a majority of the counted ballots wins the seat.
+//! * Otherwise the candidate with the fewest ballots is eliminated, and its ballots move on to
+//!   their next preference. Ballots without further preferences are exhausted.
+//!
+//! For the next seat, every candidate except the winners is hopeful again. Ties are broken in
+//! favour of the earlier candidacy.
+//!
+//! A ballot only ever moves forward while a seat is filled, so a seat takes at most one step per
+//! rank of every ballot, plus one step per candidate for each of at most as many rounds as there
+//! are candidates. `max_tally_steps` is that bound, and `elect` is weighed with it. Its caller
+//! witnesses the number of candidates and voters, and pays for them up front.
+
+use sp_std::{marker::PhantomData, prelude::*};
+use sp_runtime::traits::Saturating;
+use frame_support::{
+	decl_event, decl_module, decl_storage,
+	dispatch::{DispatchResult, PaysFee, WeighData},
+	ensure,
+	traits::Get,
+	weights::{ClassifyDispatch, DispatchClass, SimpleDispatchInfo, Weight},
+};
+use frame_system::{self as system, ensure_signed};
+
+pub mod weights;
+pub use weights::WeightInfo;
+
+/// A candidate's position in the candidate list
+pub type CandidateIndex = u32;
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The number of seats on the committee
+	type Seats: Get<u32>;
+
+	/// The most candidates in an election
+	type MaxCandidates: Get<u32>;
+
+	/// The most voters in an election
+	type MaxVoters: Get<u32>;
+
+	/// The number of blocks between elections
+	type TermDuration: Get<Self::BlockNumber>;
+
+	/// Weight information for the dispatchables in this pallet
+	type WeightInfo: WeightInfo;
+}
+
+/// Weighs `elect` by the candidate and voter counts its caller witnesses
+pub struct ElectionWeight<T>(PhantomData<T>);
+
+impl<T: Trait> WeighData<(&u32, &u32)> for ElectionWeight<T> {
+	fn weigh_data(&self, (candidates, voters): (&u32, &u32)) -> Weight {
+		T::WeightInfo::elect(*candidates, *voters, T::Seats::get())
+	}
+}
+
+impl<T> PaysFee for ElectionWeight<T> {
+	fn pays_fee(&self) -> bool {
+		true
+	}
+}
+
+impl<T, Args> ClassifyDispatch<Args> for ElectionWeight<T> {
+	fn classify_dispatch(&self, _: Args) -> DispatchClass {
+		Default::default()
+	}
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as RankedChoice {
+		/// The committee elected last
+		Members get(fn members): Vec<T::AccountId>;
+
+		/// The candidates of the coming election, in the order they submitted
+		Candidates get(fn candidates): Vec<T::AccountId>;
+
+		/// Each voter's ranking of the candidates, most preferred first
+		Ballots get(fn ballot_of): linked_map T::AccountId => Option<Vec<CandidateIndex>>;
+
+		/// The number of entries in `Ballots`
+		VoterCount get(fn voter_count): u32;
+
+		/// The block of the last election
+		LastElection get(fn last_election): T::BlockNumber;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as system::Trait>::AccountId,
+	{
+		CandidacySubmitted(AccountId),
+		Voted(AccountId),
+		/// A committee was elected, in the order its members won their seats
+		NewTerm(Vec<AccountId>),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		const Seats: u32 = T::Seats::get();
+
+		const MaxCandidates: u32 = T::MaxCandidates::get();
+
+		const MaxVoters: u32 = T::MaxVoters::get();
+
+		const TermDuration: T::BlockNumber = T::TermDuration::get();
+
+		#[weight = SimpleDispatchInfo::FixedNormal(
+			T::WeightInfo::submit_candidacy(T::MaxCandidates::get())
+		)]
+		fn submit_candidacy(origin) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let mut candidates = Self::candidates();
+			ensure!(!candidates.contains(&who), "already a candidate");
+			ensure!((candidates.len() as u32) < T::MaxCandidates::get(), "too many candidates");
+
+			candidates.push(who.clone());
+			<Candidates<T>>::put(candidates);
+			Self::deposit_event(RawEvent::CandidacySubmitted(who));
+			Ok(())
+		}
+
+		/// Rank candidates by their index, most preferred first. Voting again replaces the
+		/// ballot.
+		#[weight = SimpleDispatchInfo::FixedNormal(T::WeightInfo::vote(T::MaxCandidates::get()))]
+		fn vote(origin, ranking: Vec<CandidateIndex>) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			ensure!(!ranking.is_empty(), "empty ballot");
+			let candidates = Self::candidates().len();
+			let mut ranked = vec![false; candidates];
+			for candidate in &ranking {
+				let seen = ranked.get_mut(*candidate as usize).ok_or("no such candidate")?;
+				ensure!(!*seen, "candidate ranked twice");
+				*seen = true;
+			}
+
+			if !<Ballots<T>>::exists(&who) {
+				let voters = Self::voter_count();
+				ensure!(voters < T::MaxVoters::get(), "too many voters");
+				VoterCount::put(voters + 1);
+			}
+			<Ballots<T>>::insert(&who, ranking);
+			Self::deposit_event(RawEvent::Voted(who));
+			Ok(())
+		}
+
+		/// Elect a new committee, and open the next election. `candidates` and `voters` must be
+		/// at least the numbers of candidates and voters, and the call is weighed by them.
+		#[weight = ElectionWeight::<T>(PhantomData)]
+		fn elect(origin, candidates: u32, voters: u32) -> DispatchResult {
+			let _ = ensure_signed(origin)?;
+			let now = <system::Module<T>>::block_number();
+			let next = Self::last_election().saturating_add(T::TermDuration::get());
+			ensure!(now >= next, "too early");
+			let accounts = Self::candidates();
+			ensure!(accounts.len() as u32 <= candidates, "more candidates than witnessed");
+			ensure!(Self::voter_count() <= voters, "more voters than witnessed");
+
+			let (voter_accounts, ballots): (Vec<_>, Vec<_>) = <Ballots<T>>::enumerate().unzip();
+			let (winners, _) = instant_runoff(&ballots, accounts.len() as u32, T::Seats::get());
+			let members = winners
+				.into_iter()
+				.map(|winner| accounts[winner as usize].clone())
+				.collect::<Vec<_>>();
+
+			for voter in voter_accounts {
+				<Ballots<T>>::remove(&voter);
+			}
+			VoterCount::kill();
+			<Candidates<T>>::kill();
+			<Members<T>>::put(&members);
+			<LastElection<T>>::put(now);
+			Self::deposit_event(RawEvent::NewTerm(members));
+			Ok(())
+		}
+	}
+}
+
+/// The most steps `instant_runoff` takes to fill `seats` seats with `candidates` candidates and
+/// `voters` ballots. Each seat visits every rank of every ballot at most once, and compares every
+/// candidate in each of at most `candidates` rounds.
+pub fn max_tally_steps(candidates: u32, voters: u32, seats: u32) -> u32 {
+	let ranks = candidates.saturating_mul(voters);
+	let rounds = candidates.saturating_mul(candidates);
+	seats.saturating_mul(ranks.saturating_add(rounds))
+}
+
+/// Move a ballot forward from `position` to its most preferred hopeful candidate, if any
+fn next_hopeful(
+	ballot: &[CandidateIndex],
+	position: &mut usize,
+	hopeful: &[bool],
+	steps: &mut u64,
+) -> Option<usize> {
+	while let Some(candidate) = ballot.get(*position) {
+		*steps += 1;
+		let candidate = *candidate as usize;
+		if hopeful.get(candidate) == Some(&true) {
+			return Some(candidate);
+		}
+		*position += 1;
+	}
+	None
+}
+
+/// Fill up to `seats` seats from `candidates` candidates by instant runoff, one seat after
+/// another. Each ballot ranks candidates by index, most preferred first. Returns the winners in
+/// the order they won, and the number of steps the tally took. Fewer seats are filled once every
+/// ballot is exhausted.
+pub fn instant_runoff(
+	ballots: &[Vec<CandidateIndex>],
+	candidates: u32,
+	seats: u32,
+) -> (Vec<CandidateIndex>, u64) {
+	let candidates = candidates as usize;
+	let mut elected = vec![false; candidates];
+	let mut winners = Vec::new();
+	let mut steps = 0;
+
+	for _ in 0..seats {
+		let mut hopeful = elected.iter().map(|elected| !elected).collect::<Vec<_>>();
+		// Where each ballot is in its ranking, and the ballots that count for each candidate
+		let mut positions = vec![0; ballots.len()];
+		let mut piles = vec![Vec::new(); candidates];
+		let mut counted = 0;
+		for (index, ballot) in ballots.iter().enumerate() {
+			let position = &mut positions[index];
+			if let Some(candidate) = next_hopeful(ballot, position, &hopeful, &mut steps) {
+				piles[candidate].push(index);
+				counted += 1;
+			}
+		}
+
+		loop {
+			if counted == 0 {
+				// Every ballot is exhausted, so no further seat can be won
+				return (winners, steps);
+			}
+			// The hopefuls with the most and the fewest ballots. Ties favour the earlier
+			// candidacy, so the leader is the first and the loser the last of equals.
+			let (mut leader, mut loser) = (None, None);
+			for candidate in 0..candidates {
+				steps += 1;
+				if !hopeful[candidate] {
+					continue;
+				}
+				let count = piles[candidate].len();
+				if leader.map_or(true, |leader: usize| count > piles[leader].len()) {
+					leader = Some(candidate);
+				}
+				if loser.map_or(true, |loser: usize| count <= piles[loser].len()) {
+					loser = Some(candidate);
+				}
+			}
+			let (leader, loser) = match (leader, loser) {
+				(Some(leader), Some(loser)) => (leader, loser),
+				// Counted ballots always count for a hopeful
+				_ => return (winners, steps),
+			};
+
+			if piles[leader].len() * 2 > counted {
+				elected[leader] = true;
+				winners.push(leader as CandidateIndex);
+				break;
+			}
+
+			hopeful[loser] = false;
+			for index in sp_std::mem::replace(&mut piles[loser], Vec::new()) {
+				positions[index] += 1;
+				match next_hopeful(&ballots[index], &mut positions[index], &hopeful, &mut steps) {
+					Some(candidate) => piles[candidate].push(index),
+					None => counted -= 1,
+				}
+			}
+		}
+	}
+	(winners, steps)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use event_assert::assert_last_event;
+	use sp_core::H256;
+	use frame_support::{
+		assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types,
+		weights::GetDispatchInfo,
+	};
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup},
+		Perbill,
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const Seats: u32 = 2;
+		pub const MaxCandidates: u32 = 4;
+		pub const MaxVoters: u32 = 10;
+		pub const TermDuration: u64 = 10;
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	mod ranked_choice {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			ranked_choice<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type Seats = Seats;
+		type MaxCandidates = MaxCandidates;
+		type MaxVoters = MaxVoters;
+		type TermDuration = TermDuration;
+		type WeightInfo = ();
+	}
+
+	pub type System = system::Module<TestRuntime>;
+	pub type RankedChoice = Module<TestRuntime>;
+
+	/// Accounts 10, 11 and 12 are candidates 0, 1 and 2 in block 10
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		let t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		let mut ext: sp_io::TestExternalities = t.into();
+		ext.execute_with(|| {
+			System::set_block_number(10);
+			for candidate in 10..=12 {
+				assert_ok!(RankedChoice::submit_candidacy(Origin::signed(candidate)));
+			}
+		});
+		ext
+	}
+
+	/// `count` copies of `ballot`
+	fn ballots(groups: &[(usize, &[CandidateIndex])]) -> Vec<Vec<CandidateIndex>> {
+		groups
+			.iter()
+			.flat_map(|(count, ballot)| std::iter::repeat(ballot.to_vec()).take(*count))
+			.collect()
+	}
+
+	fn winners(ballots: &[Vec<CandidateIndex>], candidates: u32, seats: u32) -> Vec<u32> {
+		instant_runoff(ballots, candidates, seats).0
+	}
+
+	/// Four first preferences for 0, three for 1, and two for 2 that move on to 1
+	fn transfers() -> Vec<Vec<CandidateIndex>> {
+		ballots(&[(4, &[0, 1]), (3, &[1, 0]), (2, &[2, 1])])
+	}
+
+	#[test]
+	fn a_majority_wins_outright() {
+		assert_eq!(winners(&ballots(&[(2, &[0]), (1, &[1])]), 2, 1), vec![0]);
+	}
+
+	#[test]
+	fn eliminated_candidates_transfer_their_ballots() {
+		// Candidate 0 leads, but 2 is eliminated and its ballots give 1 a majority
+		assert_eq!(winners(&transfers(), 3, 1), vec![1]);
+	}
+
+	#[test]
+	fn seats_are_filled_one_after_another() {
+		// With 1 elected, its ballots count for their next preference, 0
+		assert_eq!(winners(&transfers(), 3, 2), vec![1, 0]);
+		assert_eq!(winners(&transfers(), 3, 3), vec![1, 0, 2]);
+	}
+
+	#[test]
+	fn exhausted_ballots_stop_counting() {
+		// 0 is eliminated and its ballot exhausted. 1 and 2 tie on two ballots each, and the
+		// later candidacy, 2, is eliminated.
+		let ballots = ballots(&[(1, &[0]), (2, &[1]), (2, &[2])]);
+		assert_eq!(winners(&ballots, 3, 1), vec![1]);
+		// For the next seats, 2 and then 0 are hopeful again
+		assert_eq!(winners(&ballots, 3, 3), vec![1, 2, 0]);
+		// Once every ballot is exhausted, the remaining seats stay empty
+		assert_eq!(winners(&ballots[..1], 3, 2), vec![0]);
+		assert_eq!(winners(&[], 3, 1), vec![]);
+	}
+
+	#[test]
+	fn ties_favour_the_earlier_candidacy() {
+		assert_eq!(winners(&ballots(&[(1, &[1]), (1, &[0])]), 2, 1), vec![0]);
+	}
+
+	/// Without a benchmarking framework at this Substrate version, these count the steps of the
+	/// tally over a range of candidate and voter counts, and check them against the bound that
+	/// `elect` is weighed with.
+	#[test]
+	fn tally_steps_stay_within_the_weighed_bound() {
+		let mut seed = 7u64;
+		let mut next = move || {
+			seed = seed
+				.wrapping_mul(6_364_136_223_846_793_005)
+				.wrapping_add(1_442_695_040_888_963_407);
+			(seed >> 33) as u32
+		};
+		for &candidates in &[1u32, 2, 5, 10, 20] {
+			for &voters in &[1u32, 10, 100, 500] {
+				// Full rankings with the first preferences spread evenly, so that seats take many
+				// rounds of eliminations
+				let spread = (0..voters)
+					.map(|voter| (0..candidates).map(|rank| (voter + rank) % candidates).collect())
+					.collect::<Vec<Vec<_>>>();
+				// Random full and partial rankings
+				let random = (0..voters)
+					.map(|_| {
+						let mut ranking = (0..candidates).collect::<Vec<_>>();
+						for i in (1..ranking.len()).rev() {
+							ranking.swap(i, next() as usize % (i + 1));
+						}
+						ranking.truncate(1 + next() as usize % candidates as usize);
+						ranking
+					})
+					.collect::<Vec<_>>();
+
+				for &seats in &[1u32, 3] {
+					let bound = u64::from(max_tally_steps(candidates, voters, seats));
+					for ballots in &[&spread, &random] {
+						let (_, steps) = instant_runoff(ballots, candidates, seats);
+						assert!(
+							steps <= bound,
+							"{} steps for {} candidates, {} voters and {} seats",
+							steps, candidates, voters, seats,
+						);
+					}
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn elect_is_weighed_by_its_witnesses() {
+		let weight = |candidates, voters| {
+			Call::<TestRuntime>::elect(candidates, voters).get_dispatch_info().weight
+		};
+		assert_eq!(weight(4, 10), <() as WeightInfo>::elect(4, 10, 2));
+		assert!(weight(4, 10) > weight(4, 5));
+		assert!(weight(4, 10) > weight(2, 10));
+	}
+
+	#[test]
+	fn elections_seat_a_committee() {
+		new_test_ext().execute_with(|| {
+			for (voter, ranking) in transfers().into_iter().enumerate() {
+				assert_ok!(RankedChoice::vote(Origin::signed(voter as u64), ranking));
+			}
+			// Voting again replaces the ballot
+			assert_ok!(RankedChoice::vote(Origin::signed(0), vec![0, 1]));
+			assert_eq!(RankedChoice::voter_count(), 9);
+
+			assert_ok!(RankedChoice::elect(Origin::signed(1), 3, 9));
+			assert_eq!(RankedChoice::members(), vec![11, 10]);
+			assert_last_event::<TestRuntime>(RawEvent::NewTerm(vec![11, 10]));
+
+			// The next election starts afresh
+			assert!(RankedChoice::candidates().is_empty());
+			assert_eq!(RankedChoice::voter_count(), 0);
+			assert_eq!(RankedChoice::ballot_of(0), None);
+			assert_err!(RankedChoice::elect(Origin::signed(1), 0, 0), "too early");
+			System::set_block_number(20);
+			assert_ok!(RankedChoice::elect(Origin::signed(1), 0, 0));
+			assert!(RankedChoice::members().is_empty());
+		})
+	}
+
+	#[test]
+	fn witnesses_must_cover_the_election() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(RankedChoice::vote(Origin::signed(1), vec![0]));
+			assert_ok!(RankedChoice::vote(Origin::signed(2), vec![1]));
+			assert_err!(
+				RankedChoice::elect(Origin::signed(1), 2, 2),
+				"more candidates than witnessed"
+			);
+			assert_err!(
+				RankedChoice::elect(Origin::signed(1), 3, 1),
+				"more voters than witnessed"
+			);
+			assert_ok!(RankedChoice::elect(Origin::signed(1), 4, 10));
+		})
+	}
+
+	#[test]
+	fn ballots_and_candidacies_are_checked() {
+		new_test_ext().execute_with(|| {
+			assert_err!(RankedChoice::vote(Origin::signed(1), vec![]), "empty ballot");
+			assert_err!(RankedChoice::vote(Origin::signed(1), vec![0, 3]), "no such candidate");
+			assert_err!(
+				RankedChoice::vote(Origin::signed(1), vec![2, 0, 2]),
+				"candidate ranked twice"
+			);
+
+			assert_err!(RankedChoice::submit_candidacy(Origin::signed(10)), "already a candidate");
+			assert_ok!(RankedChoice::submit_candidacy(Origin::signed(13)));
+			assert_err!(RankedChoice::submit_candidacy(Origin::signed(14)), "too many candidates");
+			assert_ok!(RankedChoice::vote(Origin::signed(1), vec![3]));
+
+			for voter in 2..=10 {
+				assert_ok!(RankedChoice::vote(Origin::signed(voter), vec![0]));
+			}
+			assert_err!(RankedChoice::vote(Origin::signed(11), vec![0]), "too many voters");
+		})
+	}
+}
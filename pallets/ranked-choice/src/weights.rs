@@ -0,0 +1,50 @@
+//! Weights for the ranked-choice pallet
+//!
+//! Ballots and candidates are bounded, so every call has a worst case. `elect` is charged for the
+//! candidate and voter counts its caller witnesses, and for the most steps the tally can take with
+//! them, `max_tally_steps`.
+
+use sp_std::marker::PhantomData;
+use frame_support::weights::Weight;
+use crate::max_tally_steps;
+
+/// Weight functions needed by the ranked-choice pallet
+pub trait WeightInfo {
+    fn submit_candidacy(candidates: u32) -> Weight;
+    fn vote(candidates: u32) -> Weight;
+    fn elect(candidates: u32, voters: u32, seats: u32) -> Weight;
+}
+
+/// Hand-picked estimates of the ranked-choice pallet's weights
+pub struct EstimatedWeight<T>(PhantomData<T>);
+impl<T: frame_system::Trait> WeightInfo for EstimatedWeight<T> {
+    // decodes the candidate list to check for the caller, then appends
+    fn submit_candidacy(candidates: u32) -> Weight {
+        20_000u32.saturating_add(500u32.saturating_mul(candidates))
+    }
+    // reads the candidate count, checks every rank, and writes the ballot and voter count
+    fn vote(candidates: u32) -> Weight {
+        30_000u32.saturating_add(500u32.saturating_mul(candidates))
+    }
+    // Fixed: reads and writes of the candidates, members, voter count and election block.
+    // Per voter: a ballot read and removal. Per tally step: a comparison in memory.
+    fn elect(candidates: u32, voters: u32, seats: u32) -> Weight {
+        50_000u32
+            .saturating_add(2_000u32.saturating_mul(voters))
+            .saturating_add(500u32.saturating_mul(candidates))
+            .saturating_add(10u32.saturating_mul(max_tally_steps(candidates, voters, seats)))
+    }
+}
+
+// For backwards compatibility and tests
+impl WeightInfo for () {
+    fn submit_candidacy(candidates: u32) -> Weight {
+        10_000u32.saturating_add(100u32.saturating_mul(candidates))
+    }
+    fn vote(candidates: u32) -> Weight {
+        10_000u32.saturating_add(100u32.saturating_mul(candidates))
+    }
+    fn elect(candidates: u32, voters: u32, seats: u32) -> Weight {
+        10_000u32.saturating_add(max_tally_steps(candidates, voters, seats))
+    }
+}
@@ -0,0 +1,248 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! Rate limits per account
+//!
+//! Limits how often each account may act: at most `MaxActions` actions in every window of
+//! `Period` blocks. Windows are aligned to block numbers, so the window of block `n` starts at
+//! `n - n % Period`. Each account has a single counter, tagged with the window it counts. An action
+//! in a later window finds a stale tag and starts counting from zero again, so windows roll over
+//! without any work in `on_initialize`.
+//!
+//! Other pallets don't depend on this one directly. They ask for a `RateLimiter` in their `Trait`,
+//! and the runtime decides whether that is this pallet or `()`, which never limits anything.
+//!
+//! Fixed windows are cheap but coarse: an account may act `MaxActions` times at the end of one
+//! window and `MaxActions` times again at the start of the next.
+
+use codec::{Decode, Encode};
+use frame_support::{decl_module, decl_storage, dispatch::DispatchResult, ensure, traits::Get};
+use frame_system::{self as system};
+use sp_runtime::{traits::Zero, RuntimeDebug};
+
+/// Something that limits how often an account may act
+pub trait RateLimiter<AccountId> {
+	/// Count an action of `who`, or fail without counting it if `who` has no actions left
+	fn try_act(who: &AccountId) -> DispatchResult;
+
+	/// How many more actions `who` may take in the current window
+	fn remaining(who: &AccountId) -> u32;
+}
+
+impl<AccountId> RateLimiter<AccountId> for () {
+	fn try_act(_who: &AccountId) -> DispatchResult {
+		Ok(())
+	}
+
+	fn remaining(_who: &AccountId) -> u32 {
+		u32::max_value()
+	}
+}
+
+/// The actions an account took in a window
+#[derive(Encode, Decode, Clone, Default, PartialEq, Eq, RuntimeDebug)]
+pub struct Usage<BlockNumber> {
+	/// The first block of the window
+	pub window: BlockNumber,
+	pub actions: u32,
+}
+
+pub trait Trait: system::Trait {
+	/// How many actions an account may take in one window
+	type MaxActions: Get<u32>;
+
+	/// The length of a window, in blocks. Must not be zero.
+	type Period: Get<Self::BlockNumber>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as RateLimiter {
+		/// The actions of each account in the last window it acted in. Entries of earlier windows
+		/// are not removed, but count as zero.
+		Usages get(fn usage): map T::AccountId => Usage<T::BlockNumber>;
+	}
+}
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		const MaxActions: u32 = T::MaxActions::get();
+
+		const Period: T::BlockNumber = T::Period::get();
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// The first block of the window that `now` is in
+	pub fn window_of(now: T::BlockNumber) -> T::BlockNumber {
+		let period = T::Period::get();
+		// A zero period would divide by zero, so it is treated as windows of a single block
+		if period.is_zero() {
+			return now;
+		}
+		now - now % period
+	}
+
+	/// The actions `who` took in the current window
+	fn actions_in_window(who: &T::AccountId, window: T::BlockNumber) -> u32 {
+		let usage = Self::usage(who);
+		if usage.window == window {
+			usage.actions
+		} else {
+			0
+		}
+	}
+}
+
+impl<T: Trait> RateLimiter<T::AccountId> for Module<T> {
+	fn try_act(who: &T::AccountId) -> DispatchResult {
+		let window = Self::window_of(<system::Module<T>>::block_number());
+		let actions = Self::actions_in_window(who, window);
+		ensure!(actions < T::MaxActions::get(), "rate limit reached, wait for the next window");
+
+		<Usages<T>>::insert(who, Usage { window, actions: actions + 1 });
+		Ok(())
+	}
+
+	fn remaining(who: &T::AccountId) -> u32 {
+		let window = Self::window_of(<system::Module<T>>::block_number());
+		T::MaxActions::get().saturating_sub(Self::actions_in_window(who, window))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use frame_support::{assert_noop, assert_ok, impl_outer_origin, parameter_types};
+	use sp_core::H256;
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup},
+		Perbill,
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const MaxActions: u32 = 2;
+		pub const Period: u64 = 10;
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = ();
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	impl Trait for TestRuntime {
+		type MaxActions = MaxActions;
+		type Period = Period;
+	}
+
+	pub type System = system::Module<TestRuntime>;
+	pub type Limiter = Module<TestRuntime>;
+
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap()
+			.into()
+	}
+
+	fn act_at(block: u64, who: u64) -> DispatchResult {
+		System::set_block_number(block);
+		Limiter::try_act(&who)
+	}
+
+	#[test]
+	fn windows_are_aligned_to_the_period() {
+		new_test_ext().execute_with(|| {
+			assert_eq!(Limiter::window_of(0), 0);
+			assert_eq!(Limiter::window_of(9), 0);
+			assert_eq!(Limiter::window_of(10), 10);
+			assert_eq!(Limiter::window_of(25), 20);
+		})
+	}
+
+	#[test]
+	fn allows_max_actions_per_window() {
+		new_test_ext().execute_with(|| {
+			assert_eq!(Limiter::remaining(&1), 2);
+			assert_ok!(act_at(3, 1));
+			assert_ok!(act_at(5, 1));
+			assert_eq!(Limiter::remaining(&1), 0);
+			assert_noop!(act_at(7, 1), "rate limit reached, wait for the next window");
+
+			// Accounts are limited separately
+			assert_ok!(act_at(7, 2));
+			assert_eq!(Limiter::remaining(&2), 1);
+		})
+	}
+
+	#[test]
+	fn last_block_of_a_window_still_counts_toward_it() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(act_at(8, 1));
+			assert_ok!(act_at(9, 1));
+			assert_noop!(act_at(9, 1), "rate limit reached, wait for the next window");
+		})
+	}
+
+	#[test]
+	fn counter_resets_at_rollover() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(act_at(9, 1));
+			assert_ok!(act_at(9, 1));
+
+			// Block 10 starts a new window
+			System::set_block_number(10);
+			assert_eq!(Limiter::remaining(&1), 2);
+			assert_ok!(act_at(10, 1));
+			assert_eq!(Limiter::usage(&1), Usage { window: 10, actions: 1 });
+		})
+	}
+
+	#[test]
+	fn skipped_windows_do_not_carry_over() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(act_at(1, 1));
+			assert_ok!(act_at(1, 1));
+
+			// No actions between block 10 and 39; the stale entry of window 0 counts as zero
+			System::set_block_number(42);
+			assert_eq!(Limiter::remaining(&1), 2);
+			assert_ok!(act_at(42, 1));
+			assert_ok!(act_at(49, 1));
+			assert_noop!(act_at(49, 1), "rate limit reached, wait for the next window");
+		})
+	}
+
+	#[test]
+	fn unit_never_limits() {
+		for _ in 0..100 {
+			assert_ok!(<() as RateLimiter<u64>>::try_act(&1));
+		}
+		assert_eq!(<() as RateLimiter<u64>>::remaining(&1), u32::max_value());
+	}
+}
@@ -0,0 +1,385 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! A referral program that rewards several levels of referrers
+//!
+//! Each account may name the account that referred it, once. When an account takes a qualifying
+//! action, its referrer is rewarded, and so is the referrer's referrer, and so on up the chain.
+//! `Rates` holds the reward for each level as a fraction of the action's value, and has at most
+//! `MaxDepth` entries, so no action ever rewards more than `MaxDepth` referrers. Rewards are paid
+//! from a pot in the pallet's own account, which anyone can `fund`.
+//!
+//! The pallet doesn't decide which actions qualify. Other pallets report them through the
+//! `ReferralRewards` trait, and add its `max_weight` to the weight of the calls that report.
+//!
+//! An account can only pick a referrer while nobody has picked it. It is then the bottom of its
+//! chain, so it can't be anywhere above its new referrer, and the chains never form a cycle.
+
+use sp_std::prelude::*;
+use sp_runtime::{
+	traits::{AccountIdConversion, Zero},
+	ModuleId, Perbill,
+};
+use frame_support::{
+	decl_event, decl_module, decl_storage,
+	dispatch::DispatchResult,
+	ensure,
+	traits::{Currency, EnsureOrigin, ExistenceRequirement::AllowDeath, Get},
+	weights::{SimpleDispatchInfo, Weight},
+};
+use frame_system::{self as system, ensure_signed};
+
+pub mod weights;
+pub use weights::WeightInfo;
+
+type BalanceOf<T> = <<T as Trait>::Currency as Currency<<T as system::Trait>::AccountId>>::Balance;
+
+/// Hardcoded pallet ID; used to create the account that holds the rewards
+/// Must be exactly 8 characters long
+const PALLET_ID: ModuleId = ModuleId(*b"referral");
+
+/// Something that rewards the referrers of accounts that take qualifying actions
+pub trait ReferralRewards<AccountId, Balance> {
+	/// Reward the referrers of `who` for an action worth `amount`
+	fn reward_referrers(who: &AccountId, amount: Balance);
+
+	/// The most weight `reward_referrers` takes, for the calls that report actions to include
+	fn max_weight() -> Weight;
+}
+
+impl<AccountId, Balance> ReferralRewards<AccountId, Balance> for () {
+	fn reward_referrers(_who: &AccountId, _amount: Balance) {}
+
+	fn max_weight() -> Weight {
+		0
+	}
+}
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The currency rewards are paid in
+	type Currency: Currency<Self::AccountId>;
+
+	/// The origin that sets the reward rates
+	type ManageOrigin: EnsureOrigin<Self::Origin>;
+
+	/// The most levels of referrers an action rewards
+	type MaxDepth: Get<u32>;
+
+	/// Weight information for the dispatchables in this pallet
+	type WeightInfo: WeightInfo;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as Referrals {
+		/// The account that referred each account
+		Referrers get(fn referrer): map T::AccountId => Option<T::AccountId>;
+
+		/// The number of accounts each account referred
+		RefereeCount get(fn referee_count): map T::AccountId => u32;
+
+		/// The reward of the referrer at each level, from the direct referrer up
+		Rates get(fn rates): Vec<Perbill>;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as system::Trait>::AccountId,
+		Balance = BalanceOf<T>,
+	{
+		/// Someone added funds to the pot
+		Funded(AccountId, Balance),
+		/// An account named the account that referred it
+		ReferrerSet(AccountId, AccountId),
+		/// The reward rates changed
+		RatesSet(Vec<Perbill>),
+		/// A referrer was rewarded for an action of an account this many levels below it
+		RewardPaid(AccountId, AccountId, u32, Balance),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		const MaxDepth: u32 = T::MaxDepth::get();
+
+		/// Add funds to the pot
+		#[weight = SimpleDispatchInfo::FixedNormal(T::WeightInfo::fund())]
+		fn fund(origin, amount: BalanceOf<T>) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			T::Currency::transfer(&who, &Self::account_id(), amount, AllowDeath)?;
+			Self::deposit_event(RawEvent::Funded(who, amount));
+			Ok(())
+		}
+
+		/// Name the account that referred the caller. Only possible before anyone names the caller.
+		#[weight = SimpleDispatchInfo::FixedNormal(T::WeightInfo::set_referrer())]
+		fn set_referrer(origin, referrer: T::AccountId) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			ensure!(who != referrer, "cannot refer yourself");
+			ensure!(!<Referrers<T>>::exists(&who), "referrer already set");
+			// Nobody is below `who`, so `who` is not above `referrer`, and no cycle can form
+			ensure!(
+				Self::referee_count(&who) == 0,
+				"accounts with referees cannot pick a referrer"
+			);
+
+			<Referrers<T>>::insert(&who, &referrer);
+			<RefereeCount<T>>::mutate(&referrer, |count| *count = count.saturating_add(1));
+
+			Self::deposit_event(RawEvent::ReferrerSet(who, referrer));
+			Ok(())
+		}
+
+		/// Set the reward of each level of referrers, from the direct referrer up
+		#[weight = SimpleDispatchInfo::FixedNormal(T::WeightInfo::set_rates(T::MaxDepth::get()))]
+		fn set_rates(origin, rates: Vec<Perbill>) -> DispatchResult {
+			T::ManageOrigin::ensure_origin(origin)?;
+			ensure!(rates.len() as u32 <= T::MaxDepth::get(), "too many levels");
+
+			Rates::put(&rates);
+
+			Self::deposit_event(RawEvent::RatesSet(rates));
+			Ok(())
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// The account that holds the rewards
+	pub fn account_id() -> T::AccountId {
+		PALLET_ID.into_account()
+	}
+
+	/// The funds left for rewards
+	pub fn pot() -> BalanceOf<T> {
+		T::Currency::free_balance(&Self::account_id())
+	}
+}
+
+impl<T: Trait> ReferralRewards<T::AccountId, BalanceOf<T>> for Module<T> {
+	fn reward_referrers(who: &T::AccountId, amount: BalanceOf<T>) {
+		let pot = Self::account_id();
+		let mut referee = who.clone();
+		// `Rates` has at most `MaxDepth` entries, which bounds the walk up the chain
+		for (level, rate) in (1..).zip(Self::rates()) {
+			let referrer = match Self::referrer(&referee) {
+				Some(referrer) => referrer,
+				None => break,
+			};
+			let reward = rate * amount;
+			// A reward the pot can't cover is skipped, but the action that earned it still stands
+			let paid = !reward.is_zero()
+				&& T::Currency::transfer(&pot, &referrer, reward, AllowDeath).is_ok();
+			if paid {
+				let actor = who.clone();
+				Self::deposit_event(RawEvent::RewardPaid(referrer.clone(), actor, level, reward));
+			}
+			referee = referrer;
+		}
+	}
+
+	fn max_weight() -> Weight {
+		T::WeightInfo::reward(T::MaxDepth::get())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use event_assert::{assert_has_event, assert_last_event};
+	use sp_core::H256;
+	use frame_support::{
+		assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types,
+	};
+	use frame_system::{EnsureRoot, RawOrigin};
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup},
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const ExistentialDeposit: u64 = 0;
+		pub const TransferFee: u64 = 0;
+		pub const CreationFee: u64 = 0;
+
+		pub const MaxDepth: u32 = 3;
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	impl balances::Trait for TestRuntime {
+		type Balance = u64;
+		type OnFreeBalanceZero = ();
+		type OnNewAccount = ();
+		type Event = TestEvent;
+		type TransferPayment = ();
+		type DustRemoval = ();
+		type ExistentialDeposit = ExistentialDeposit;
+		type TransferFee = TransferFee;
+		type CreationFee = CreationFee;
+	}
+
+	mod referrals {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			balances<T>,
+			referrals<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type Currency = balances::Module<Self>;
+		type ManageOrigin = EnsureRoot<u64>;
+		type MaxDepth = MaxDepth;
+		type WeightInfo = ();
+	}
+
+	pub type System = system::Module<TestRuntime>;
+	pub type Balances = balances::Module<TestRuntime>;
+	pub type Referrals = Module<TestRuntime>;
+
+	/// Account 9 put `pot` into the pot in block 1. Account 1 referred 2, who referred 3, and so on
+	/// up to 5. Direct referrers get 10%, the level above 5%, and the one above that 2%.
+	fn new_test_ext(pot: u64) -> sp_io::TestExternalities {
+		let mut t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		balances::GenesisConfig::<TestRuntime> {
+			balances: vec![(9, 1_000)],
+			vesting: vec![],
+		}
+		.assimilate_storage(&mut t)
+		.unwrap();
+		let mut ext: sp_io::TestExternalities = t.into();
+		ext.execute_with(|| {
+			System::set_block_number(1);
+			assert_ok!(Referrals::fund(Origin::signed(9), pot));
+			for who in 2..=5 {
+				assert_ok!(Referrals::set_referrer(Origin::signed(who), who - 1));
+			}
+			assert_ok!(set_rates(vec![10, 5, 2]));
+		});
+		ext
+	}
+
+	fn set_rates(percents: Vec<u32>) -> DispatchResult {
+		let rates = percents.into_iter().map(Perbill::from_percent).collect();
+		Referrals::set_rates(RawOrigin::Root.into(), rates)
+	}
+
+	#[test]
+	fn rewards_are_split_up_the_chain() {
+		new_test_ext(1_000).execute_with(|| {
+			Referrals::reward_referrers(&4, 1_000);
+			assert_eq!(Balances::free_balance(&3), 100);
+			assert_eq!(Balances::free_balance(&2), 50);
+			assert_eq!(Balances::free_balance(&1), 20);
+			assert_eq!(Referrals::pot(), 830);
+			assert_has_event::<TestRuntime>(RawEvent::RewardPaid(3, 4, 1, 100));
+			assert_last_event::<TestRuntime>(RawEvent::RewardPaid(1, 4, 3, 20));
+		})
+	}
+
+	#[test]
+	fn rewards_stop_at_the_last_rate() {
+		new_test_ext(1_000).execute_with(|| {
+			// 5 has four referrers above it, but only three levels are rewarded
+			Referrals::reward_referrers(&5, 100);
+			assert_eq!(Balances::free_balance(&4), 10);
+			assert_eq!(Balances::free_balance(&3), 5);
+			assert_eq!(Balances::free_balance(&2), 2);
+			assert_eq!(Balances::free_balance(&1), 0);
+
+			// Without a referrer, there is nobody to reward
+			Referrals::reward_referrers(&1, 100);
+			assert_eq!(Referrals::pot(), 983);
+		})
+	}
+
+	#[test]
+	fn cycles_cannot_form() {
+		new_test_ext(1_000).execute_with(|| {
+			assert_err!(Referrals::set_referrer(Origin::signed(6), 6), "cannot refer yourself");
+			assert_err!(Referrals::set_referrer(Origin::signed(5), 6), "referrer already set");
+			// 1 is at the top of the chain, and naming 5 would close a cycle
+			assert_err!(
+				Referrals::set_referrer(Origin::signed(1), 5),
+				"accounts with referees cannot pick a referrer"
+			);
+
+			assert_ok!(Referrals::set_referrer(Origin::signed(6), 5));
+			assert_last_event::<TestRuntime>(RawEvent::ReferrerSet(6, 5));
+			assert_eq!(Referrals::referee_count(&5), 1);
+		})
+	}
+
+	#[test]
+	fn rates_are_bounded_by_depth() {
+		new_test_ext(1_000).execute_with(|| {
+			assert_err!(set_rates(vec![10, 5, 2, 1]), "too many levels");
+			let rates = vec![Perbill::from_percent(1)];
+			assert!(Referrals::set_rates(Origin::signed(1), rates.clone()).is_err());
+
+			assert_ok!(Referrals::set_rates(RawOrigin::Root.into(), rates.clone()));
+			assert_last_event::<TestRuntime>(RawEvent::RatesSet(rates));
+			Referrals::reward_referrers(&4, 1_000);
+			assert_eq!(Balances::free_balance(&3), 10);
+			assert_eq!(Balances::free_balance(&2), 0);
+		})
+	}
+
+	#[test]
+	fn rewards_the_pot_cannot_cover_are_skipped() {
+		new_test_ext(30).execute_with(|| {
+			Referrals::reward_referrers(&4, 1_000);
+			assert_eq!(Balances::free_balance(&3), 0);
+			assert_eq!(Balances::free_balance(&2), 0);
+			assert_eq!(Balances::free_balance(&1), 20);
+			assert_eq!(Referrals::pot(), 10);
+		})
+	}
+
+	#[test]
+	fn weight_depends_on_depth() {
+		assert_eq!(Referrals::max_weight(), <() as WeightInfo>::reward(3));
+		assert_eq!(<() as ReferralRewards<u64, u64>>::max_weight(), 0);
+	}
+}
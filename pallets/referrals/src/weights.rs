@@ -0,0 +1,54 @@
+//! Weights for the referrals pallet
+//!
+//! Rewarding referrers walks up the chain of referrers, one level per reward rate, so its weight
+//! is a function of the number of levels. At most `MaxDepth` levels are ever rewarded.
+
+use sp_std::marker::PhantomData;
+use frame_support::weights::Weight;
+
+/// Weight functions needed by the referrals pallet
+pub trait WeightInfo {
+    fn fund() -> Weight;
+    fn set_referrer() -> Weight;
+    fn set_rates(levels: u32) -> Weight;
+    fn reward(levels: u32) -> Weight;
+}
+
+/// Hand-picked estimates of the referrals pallet's weights
+pub struct EstimatedWeight<T>(PhantomData<T>);
+impl<T: frame_system::Trait> WeightInfo for EstimatedWeight<T> {
+    // one transfer
+    fn fund() -> Weight {
+        30_000
+    }
+    // reads of the caller's referrer and referee count, and writes of the referrer and the
+    // referrer's referee count
+    fn set_referrer() -> Weight {
+        25_000
+    }
+    // one write of the rates
+    fn set_rates(levels: u32) -> Weight {
+        10_000u32.saturating_add(100u32.saturating_mul(levels))
+    }
+    // Fixed: a read of the rates.
+    // Per level: a read of the referrer and a transfer from the pot.
+    fn reward(levels: u32) -> Weight {
+        10_000u32.saturating_add(35_000u32.saturating_mul(levels))
+    }
+}
+
+// For backwards compatibility and tests
+impl WeightInfo for () {
+    fn fund() -> Weight {
+        10_000
+    }
+    fn set_referrer() -> Weight {
+        10_000
+    }
+    fn set_rates(levels: u32) -> Weight {
+        10_000u32.saturating_add(levels)
+    }
+    fn reward(levels: u32) -> Weight {
+        10_000u32.saturating_add(1_000u32.saturating_mul(levels))
+    }
+}
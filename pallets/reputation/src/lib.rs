@@ -0,0 +1,370 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! Reputation that accounts earn from attestations, and that decays over time
+//!
+//! An account with at least `MinToAttest` reputation can `attest` another account, which earns
+//! `AttestationPoints` for it. The same pair can only repeat an attestation after `HalfLife`
+//! blocks, so one generous account can't pump another's reputation.
+//!
+//! Reputation halves every `HalfLife` blocks, falling linearly within each half-life. The decay
+//! is lazy: storage holds the points of an account as of the block they last changed, and
+//! `reputation_of` computes the decay since. No block iterates over accounts.
+//!
+//! Other pallets consume reputation through their origins. `EnsureReputation` is an `EnsureOrigin`
+//! that accepts signed origins whose reputation is at least a minimum, so a pallet that lets its
+//! `ProposeOrigin` propose can be limited to reputable accounts by the runtime alone.
+
+use codec::{Decode, Encode};
+use sp_std::{marker::PhantomData, prelude::*};
+use sp_runtime::{traits::SaturatedConversion, RuntimeDebug};
+use frame_support::{
+	decl_event, decl_module, decl_storage,
+	dispatch::DispatchResult,
+	ensure,
+	traits::{EnsureOrigin, Get},
+};
+use frame_system::{self as system, ensure_signed, EnsureSigned, RawOrigin};
+
+/// The points an account had in a block
+#[derive(Encode, Decode, Clone, PartialEq, Eq, Default, RuntimeDebug)]
+pub struct Score<BlockNumber> {
+	pub points: u64,
+	/// The block in which the points were last changed
+	pub updated: BlockNumber,
+}
+
+/// `points` after `elapsed` blocks of decay
+///
+/// The points halve every `half_life` blocks, and fall linearly from one half to the next within
+/// a half-life. A `half_life` of zero means no decay.
+pub fn decayed(points: u64, elapsed: u64, half_life: u64) -> u64 {
+	if half_life == 0 {
+		return points;
+	}
+	let halvings = elapsed / half_life;
+	if halvings >= 64 {
+		return 0;
+	}
+	let start = points >> halvings;
+	let into_period = u128::from(elapsed % half_life);
+	let lost = u128::from(start - start / 2) * into_period / u128::from(half_life);
+	// `lost` is at most `start - start / 2`, so it fits, and it does not exceed `start`
+	start - lost as u64
+}
+
+/// Accepts signed origins with at least `Min` reputation, and yields their account
+pub struct EnsureReputation<T, Min>(PhantomData<(T, Min)>);
+
+impl<T: Trait, Min: Get<u64>> EnsureOrigin<T::Origin> for EnsureReputation<T, Min> {
+	type Success = T::AccountId;
+
+	fn try_origin(o: T::Origin) -> Result<Self::Success, T::Origin> {
+		let who = EnsureSigned::<T::AccountId>::try_origin(o)?;
+		if Module::<T>::reputation_of(&who) >= Min::get() {
+			Ok(who)
+		} else {
+			Err(RawOrigin::Signed(who).into())
+		}
+	}
+}
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The number of blocks in which reputation halves
+	type HalfLife: Get<Self::BlockNumber>;
+
+	/// The points an attestation earns its subject
+	type AttestationPoints: Get<u64>;
+
+	/// The reputation an account needs to attest others
+	type MinToAttest: Get<u64>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as Reputation {
+		/// The points of each account as of their last change, before decay since
+		Scores get(fn score): map T::AccountId => Score<T::BlockNumber>;
+
+		/// The block in which an attester (first key) last attested a subject (second key)
+		LastAttestation get(fn last_attestation):
+			double_map T::AccountId, blake2_256(T::AccountId) => Option<T::BlockNumber>;
+	}
+	add_extra_genesis {
+		/// Reputation to start with, so that somebody can attest
+		config(reputation): Vec<(T::AccountId, u64)>;
+		build(|config: &GenesisConfig<T>| {
+			for (who, points) in &config.reputation {
+				<Scores<T>>::insert(who, Score { points: *points, updated: Default::default() });
+			}
+		});
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as system::Trait>::AccountId,
+	{
+		/// An account (first) attested another (second), whose reputation is now this much
+		Attested(AccountId, AccountId, u64),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		/// The number of blocks in which reputation halves
+		const HalfLife: T::BlockNumber = T::HalfLife::get();
+
+		/// The points an attestation earns its subject
+		const AttestationPoints: u64 = T::AttestationPoints::get();
+
+		/// Vouch for `subject`, which earns it reputation
+		fn attest(origin, subject: T::AccountId) -> DispatchResult {
+			let attester = ensure_signed(origin)?;
+			ensure!(attester != subject, "cannot attest oneself");
+			ensure!(
+				Self::reputation_of(&attester) >= T::MinToAttest::get(),
+				"not enough reputation to attest"
+			);
+			let now = <system::Module<T>>::block_number();
+			if let Some(last) = Self::last_attestation(&attester, &subject) {
+				ensure!(now >= last + T::HalfLife::get(), "attested recently");
+			}
+
+			let points = Self::reputation_of(&subject).saturating_add(T::AttestationPoints::get());
+			<Scores<T>>::insert(&subject, Score { points, updated: now });
+			<LastAttestation<T>>::insert(&attester, &subject, now);
+			Self::deposit_event(RawEvent::Attested(attester, subject, points));
+			Ok(())
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// The reputation of `who` in the current block
+	pub fn reputation_of(who: &T::AccountId) -> u64 {
+		let score = Self::score(who);
+		let now = <system::Module<T>>::block_number();
+		let elapsed = (now - score.updated).saturated_into::<u64>();
+		decayed(score.points, elapsed, T::HalfLife::get().saturated_into::<u64>())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use event_assert::assert_last_event;
+
+	use sp_core::H256;
+	use frame_support::{
+		assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types,
+	};
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup},
+		Perbill,
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	mod reputation {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			reputation<T>,
+		}
+	}
+
+	parameter_types! {
+		pub const HalfLife: u64 = 10;
+		pub const AttestationPoints: u64 = 40;
+		pub const MinToAttest: u64 = 50;
+		pub const MinToPropose: u64 = 30;
+	}
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type HalfLife = HalfLife;
+		type AttestationPoints = AttestationPoints;
+		type MinToAttest = MinToAttest;
+	}
+
+	/// A pallet that lets its `ProposeOrigin` propose, standing in for a treasury or a council
+	mod proposals {
+		use frame_support::{
+			decl_module, decl_storage, dispatch::DispatchResult, traits::EnsureOrigin,
+		};
+		use sp_std::prelude::*;
+
+		pub trait Trait: frame_system::Trait {
+			type ProposeOrigin: EnsureOrigin<Self::Origin, Success = Self::AccountId>;
+		}
+
+		decl_storage! {
+			trait Store for Module<T: Trait> as Proposals {
+				pub Proposals get(fn proposals): Vec<(T::AccountId, u32)>;
+			}
+		}
+
+		decl_module! {
+			pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+				fn propose(origin, proposal: u32) -> DispatchResult {
+					let who = T::ProposeOrigin::ensure_origin(origin)?;
+					<Proposals<T>>::mutate(|proposals| proposals.push((who, proposal)));
+					Ok(())
+				}
+			}
+		}
+	}
+
+	impl proposals::Trait for TestRuntime {
+		type ProposeOrigin = EnsureReputation<TestRuntime, MinToPropose>;
+	}
+
+	pub type System = system::Module<TestRuntime>;
+	pub type Reputation = Module<TestRuntime>;
+	pub type Proposals = proposals::Module<TestRuntime>;
+
+	/// Account 1 starts with 1000 reputation
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		let mut t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		GenesisConfig::<TestRuntime> {
+			reputation: vec![(1, 1000)],
+		}
+		.assimilate_storage(&mut t)
+		.unwrap();
+		t.into()
+	}
+
+	#[test]
+	fn decay_halves_every_half_life() {
+		assert_eq!(decayed(100, 0, 10), 100);
+		assert_eq!(decayed(100, 10, 10), 50);
+		assert_eq!(decayed(100, 20, 10), 25);
+		// Linear within a half-life: halfway from 100 to 50, and from 50 to 25 rounded down
+		assert_eq!(decayed(100, 5, 10), 75);
+		assert_eq!(decayed(100, 15, 10), 38);
+		assert_eq!(decayed(u64::max_value(), 64 * 10, 10), 0);
+		assert_eq!(decayed(100, 1_000, 0), 100);
+	}
+
+	#[test]
+	fn decay_never_increases() {
+		let mut last = decayed(1_000, 0, 7);
+		for elapsed in 1..100 {
+			let now = decayed(1_000, elapsed, 7);
+			assert!(now <= last, "{} > {} after {} blocks", now, last, elapsed);
+			last = now;
+		}
+		assert_eq!(last, 0);
+	}
+
+	#[test]
+	fn lazy_decay_matches_continuous_decay_at_half_lives() {
+		// Storing the decayed points at a half-life and decaying again from there ends up where
+		// decaying all the way does
+		assert_eq!(decayed(decayed(1_000, 10, 10), 20, 10), decayed(1_000, 30, 10));
+	}
+
+	#[test]
+	fn reputation_decays_lazily() {
+		new_test_ext().execute_with(|| {
+			System::set_block_number(15);
+			assert_eq!(Reputation::reputation_of(&1), 375);
+			// Reading does not write
+			assert_eq!(Reputation::score(&1), Score { points: 1000, updated: 0 });
+		})
+	}
+
+	#[test]
+	fn attestations_earn_reputation() {
+		new_test_ext().execute_with(|| {
+			System::set_block_number(1);
+			assert_ok!(Reputation::attest(Origin::signed(1), 2));
+			assert_eq!(Reputation::reputation_of(&2), 40);
+			assert_last_event::<TestRuntime>(RawEvent::Attested(1, 2, 40));
+
+			// Account 2 lacks the reputation to attest, and nobody attests themselves
+			assert_err!(
+				Reputation::attest(Origin::signed(2), 3),
+				"not enough reputation to attest"
+			);
+			assert_err!(Reputation::attest(Origin::signed(1), 1), "cannot attest oneself");
+
+			// A new attestation adds to what is left after decay
+			System::set_block_number(11);
+			assert_eq!(Reputation::reputation_of(&2), 20);
+			assert_ok!(Reputation::attest(Origin::signed(1), 2));
+			assert_eq!(Reputation::score(&2), Score { points: 60, updated: 11 });
+			assert_last_event::<TestRuntime>(RawEvent::Attested(1, 2, 60));
+		})
+	}
+
+	#[test]
+	fn attestations_wait_a_half_life() {
+		new_test_ext().execute_with(|| {
+			System::set_block_number(1);
+			assert_ok!(Reputation::attest(Origin::signed(1), 2));
+			System::set_block_number(10);
+			assert_err!(Reputation::attest(Origin::signed(1), 2), "attested recently");
+			// Other subjects are fine
+			assert_ok!(Reputation::attest(Origin::signed(1), 3));
+		})
+	}
+
+	#[test]
+	fn proposing_needs_reputation() {
+		new_test_ext().execute_with(|| {
+			System::set_block_number(1);
+			assert!(Proposals::propose(Origin::signed(2), 7).is_err());
+
+			assert_ok!(Reputation::attest(Origin::signed(1), 2));
+			assert_ok!(Proposals::propose(Origin::signed(2), 7));
+			assert_eq!(Proposals::proposals(), vec![(2, 7)]);
+
+			// Once the reputation decays below the minimum, the account can't propose anymore
+			System::set_block_number(6);
+			assert_eq!(Reputation::reputation_of(&2), 30);
+			assert_ok!(Proposals::propose(Origin::signed(2), 8));
+			System::set_block_number(7);
+			assert!(Proposals::propose(Origin::signed(2), 9).is_err());
+		})
+	}
+}
@@ -0,0 +1,584 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! Rock-paper-scissors between two accounts, with commit-reveal and forfeits
+//!
+//! Both players must choose their hands at the same time, but transactions are included one after
+//! the other, and a player who sees the other's hand first always wins. So each player commits to
+//! the hash of their account, their hand and a secret salt, as in the name service, and the hands
+//! are only revealed once both commitments are on chain. By then nobody can change their hand.
+//!
+//! * The challenger names an opponent and a wager, which may be zero, and commits to a hand.
+//! * The opponent `accept`s within `CommitPeriod` blocks, matching the wager and committing to a
+//!   hand. Either player can `decline` a challenge that has not been accepted.
+//! * Both players `reveal` their hands within `RevealPeriod` blocks. Once both are revealed, the
+//!   winner takes both wagers, and equal hands are a draw, in which each gets their wager back.
+//!
+//! The second player to reveal knows the first player's hand, and may not reveal a losing one. So
+//! once the reveal period is over, anyone can `resolve` the game: a player who did not reveal
+//! forfeits their wager to one who did. If neither revealed, both get their wagers back.
+//!
+//! A hand that beats the other also earns the `WINNER_BADGE` from the runtime's `BadgeIssuer`.
+
+use codec::{Decode, Encode};
+use sp_runtime::{traits::Hash, RuntimeDebug};
+use frame_support::{
+	decl_event, decl_module, decl_storage,
+	dispatch::DispatchResult,
+	ensure,
+	traits::{Currency, Get, ReservableCurrency},
+};
+use frame_system::{self as system, ensure_signed};
+use badges::{BadgeId, BadgeIssuer};
+
+pub type GameIndex = u32;
+
+type BalanceOf<T> = <<T as Trait>::Currency as Currency<<T as system::Trait>::AccountId>>::Balance;
+type GameOf<T> = Game<
+	<T as system::Trait>::AccountId,
+	BalanceOf<T>,
+	<T as system::Trait>::BlockNumber,
+	<T as system::Trait>::Hash,
+>;
+
+/// The badge for winning a game with a better hand
+pub const WINNER_BADGE: BadgeId = *b"rps-wins";
+
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, RuntimeDebug)]
+pub enum Hand {
+	Rock,
+	Paper,
+	Scissors,
+}
+
+impl Hand {
+	/// Whether this hand beats `other`
+	pub fn beats(self, other: Hand) -> bool {
+		match (self, other) {
+			(Hand::Rock, Hand::Scissors)
+			| (Hand::Paper, Hand::Rock)
+			| (Hand::Scissors, Hand::Paper) => true,
+			_ => false,
+		}
+	}
+}
+
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct Game<AccountId, Balance, BlockNumber, Hash> {
+	pub challenger: AccountId,
+	pub opponent: AccountId,
+	/// The amount each player stakes
+	pub wager: Balance,
+	/// The commitments of the challenger and the opponent. The opponent's is `None` until they
+	/// accept.
+	pub commitments: (Hash, Option<Hash>),
+	/// The hands the challenger and the opponent have revealed
+	pub hands: (Option<Hand>, Option<Hand>),
+	/// The last block in which the opponent can accept or, once they have, the players can reveal
+	pub deadline: BlockNumber,
+}
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The currency in which wagers are made
+	type Currency: ReservableCurrency<Self::AccountId>;
+
+	/// The number of blocks the opponent has to accept a challenge
+	type CommitPeriod: Get<Self::BlockNumber>;
+
+	/// The number of blocks the players have to reveal their hands, once both committed
+	type RevealPeriod: Get<Self::BlockNumber>;
+
+	/// Awards badges to winners
+	type BadgeIssuer: BadgeIssuer<Self::AccountId>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as RockPaperScissors {
+		/// Games that have not ended
+		Games get(fn game): map GameIndex => Option<GameOf<T>>;
+
+		/// Number of games ever started; the next game's index
+		GameCount get(fn game_count): GameIndex;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as system::Trait>::AccountId,
+		Balance = BalanceOf<T>,
+	{
+		/// An account challenged another to a game, wagering this amount
+		Challenged(GameIndex, AccountId, AccountId, Balance),
+		/// The opponent accepted the challenge; hands can be revealed
+		Accepted(GameIndex),
+		/// A challenge was declined or withdrawn
+		Declined(GameIndex),
+		/// A player revealed their hand
+		Revealed(GameIndex, AccountId, Hand),
+		/// A player's hand beat the other's
+		Won(GameIndex, AccountId),
+		/// Both players showed the same hand, or neither revealed
+		Drawn(GameIndex),
+		/// A player did not reveal in time, and lost their wager to the other
+		Forfeited(GameIndex, AccountId),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		const CommitPeriod: T::BlockNumber = T::CommitPeriod::get();
+
+		const RevealPeriod: T::BlockNumber = T::RevealPeriod::get();
+
+		/// Challenge `opponent` to a game, reserving the wager and committing to a hand. The
+		/// commitment is computed off chain with `commitment_of`.
+		fn challenge(
+			origin,
+			opponent: T::AccountId,
+			wager: BalanceOf<T>,
+			commitment: T::Hash
+		) -> DispatchResult {
+			let challenger = ensure_signed(origin)?;
+			ensure!(challenger != opponent, "cannot play against yourself");
+			let index = Self::game_count();
+			let next = index.checked_add(1).ok_or("game index overflow")?;
+
+			T::Currency::reserve(&challenger, wager).map_err(|_| "cannot afford the wager")?;
+
+			<Games<T>>::insert(index, Game {
+				challenger: challenger.clone(),
+				opponent: opponent.clone(),
+				wager,
+				commitments: (commitment, None),
+				hands: (None, None),
+				deadline: <system::Module<T>>::block_number() + T::CommitPeriod::get(),
+			});
+			GameCount::put(next);
+
+			Self::deposit_event(RawEvent::Challenged(index, challenger, opponent, wager));
+			Ok(())
+		}
+
+		/// Accept a challenge, reserving the same wager and committing to a hand
+		fn accept(origin, index: GameIndex, commitment: T::Hash) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let mut game = Self::game(index).ok_or("no such game")?;
+			ensure!(game.commitments.1.is_none(), "game already started");
+			ensure!(who == game.opponent, "not the opponent");
+			let now = <system::Module<T>>::block_number();
+			ensure!(now <= game.deadline, "challenge expired");
+
+			T::Currency::reserve(&who, game.wager).map_err(|_| "cannot afford the wager")?;
+
+			game.commitments.1 = Some(commitment);
+			game.deadline = now + T::RevealPeriod::get();
+			<Games<T>>::insert(index, game);
+
+			Self::deposit_event(RawEvent::Accepted(index));
+			Ok(())
+		}
+
+		/// Decline a challenge, or withdraw one that was not accepted, returning the wager
+		fn decline(origin, index: GameIndex) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let game = Self::game(index).ok_or("no such game")?;
+			ensure!(game.commitments.1.is_none(), "game already started");
+			ensure!(who == game.challenger || who == game.opponent, "not a player");
+
+			T::Currency::unreserve(&game.challenger, game.wager);
+			<Games<T>>::remove(index);
+
+			Self::deposit_event(RawEvent::Declined(index));
+			Ok(())
+		}
+
+		/// Reveal the hand and salt behind a commitment
+		fn reveal(origin, index: GameIndex, hand: Hand, salt: T::Hash) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let mut game = Self::game(index).ok_or("no such game")?;
+			let opponent_commitment = game.commitments.1.ok_or("game not started")?;
+			ensure!(<system::Module<T>>::block_number() <= game.deadline, "reveal period is over");
+
+			let (commitment, revealed) = if who == game.challenger {
+				(game.commitments.0, &mut game.hands.0)
+			} else if who == game.opponent {
+				(opponent_commitment, &mut game.hands.1)
+			} else {
+				return Err("not a player".into());
+			};
+			ensure!(revealed.is_none(), "hand already revealed");
+			ensure!(
+				Self::commitment_of(&who, hand, &salt) == commitment,
+				"hand does not match the commitment"
+			);
+			*revealed = Some(hand);
+			Self::deposit_event(RawEvent::Revealed(index, who, hand));
+
+			match game.hands {
+				(Some(challenger), Some(opponent)) => {
+					if challenger.beats(opponent) {
+						Self::pay_winner(&game.challenger, &game.opponent, game.wager);
+						T::BadgeIssuer::issue(&game.challenger, WINNER_BADGE);
+						Self::deposit_event(RawEvent::Won(index, game.challenger));
+					} else if opponent.beats(challenger) {
+						Self::pay_winner(&game.opponent, &game.challenger, game.wager);
+						T::BadgeIssuer::issue(&game.opponent, WINNER_BADGE);
+						Self::deposit_event(RawEvent::Won(index, game.opponent));
+					} else {
+						Self::refund(&game);
+						Self::deposit_event(RawEvent::Drawn(index));
+					}
+					<Games<T>>::remove(index);
+				}
+				_ => <Games<T>>::insert(index, game),
+			}
+			Ok(())
+		}
+
+		/// End a game whose reveal period is over. A player who did not reveal forfeits.
+		fn resolve(origin, index: GameIndex) -> DispatchResult {
+			ensure_signed(origin)?;
+			let game = Self::game(index).ok_or("no such game")?;
+			ensure!(game.commitments.1.is_some(), "game not started");
+			let now = <system::Module<T>>::block_number();
+			ensure!(now > game.deadline, "reveal period is not over");
+
+			match game.hands {
+				(Some(_), None) => {
+					Self::pay_winner(&game.challenger, &game.opponent, game.wager);
+					Self::deposit_event(RawEvent::Forfeited(index, game.opponent));
+				}
+				(None, Some(_)) => {
+					Self::pay_winner(&game.opponent, &game.challenger, game.wager);
+					Self::deposit_event(RawEvent::Forfeited(index, game.challenger));
+				}
+				// A game in which both revealed has already ended
+				_ => {
+					Self::refund(&game);
+					Self::deposit_event(RawEvent::Drawn(index));
+				}
+			}
+			<Games<T>>::remove(index);
+			Ok(())
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// The commitment to a hand. Including the player means the other player can't copy it to
+	/// force a draw.
+	pub fn commitment_of(who: &T::AccountId, hand: Hand, salt: &T::Hash) -> T::Hash {
+		T::Hashing::hash_of(&(who, hand, salt))
+	}
+
+	/// Give the loser's wager to the winner, and return the winner's own
+	fn pay_winner(winner: &T::AccountId, loser: &T::AccountId, wager: BalanceOf<T>) {
+		let (winnings, _) = T::Currency::slash_reserved(loser, wager);
+		T::Currency::resolve_creating(winner, winnings);
+		T::Currency::unreserve(winner, wager);
+	}
+
+	/// Return both players' wagers
+	fn refund(game: &GameOf<T>) {
+		T::Currency::unreserve(&game.challenger, game.wager);
+		T::Currency::unreserve(&game.opponent, game.wager);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use event_assert::assert_last_event;
+	use sp_core::H256;
+	use frame_support::{
+		assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types
+	};
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup},
+		Perbill,
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const ExistentialDeposit: u64 = 0;
+		pub const TransferFee: u64 = 0;
+		pub const CreationFee: u64 = 0;
+
+		pub const CommitPeriod: u64 = 5;
+		pub const RevealPeriod: u64 = 3;
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	impl balances::Trait for TestRuntime {
+		type Balance = u64;
+		type OnFreeBalanceZero = ();
+		type OnNewAccount = ();
+		type Event = TestEvent;
+		type TransferPayment = ();
+		type DustRemoval = ();
+		type ExistentialDeposit = ExistentialDeposit;
+		type TransferFee = TransferFee;
+		type CreationFee = CreationFee;
+	}
+
+	impl badges::Trait for TestRuntime {
+		type Event = TestEvent;
+	}
+
+	mod rock_paper_scissors {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			balances<T>,
+			badges<T>,
+			rock_paper_scissors<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type Currency = balances::Module<Self>;
+		type CommitPeriod = CommitPeriod;
+		type RevealPeriod = RevealPeriod;
+		type BadgeIssuer = badges::Module<Self>;
+	}
+
+	pub type System = system::Module<TestRuntime>;
+	pub type Balances = balances::Module<TestRuntime>;
+	pub type Badges = badges::Module<TestRuntime>;
+	pub type RockPaperScissors = Module<TestRuntime>;
+
+	/// Accounts 1, 2 and 3 hold 100 each, in block 1
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		let mut t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		balances::GenesisConfig::<TestRuntime> {
+			balances: vec![(1, 100), (2, 100), (3, 100)],
+			vesting: vec![],
+		}
+		.assimilate_storage(&mut t)
+		.unwrap();
+		let mut ext: sp_io::TestExternalities = t.into();
+		ext.execute_with(|| System::set_block_number(1));
+		ext
+	}
+
+	/// Each account's salt
+	fn salt(who: u64) -> H256 {
+		H256::repeat_byte(who as u8)
+	}
+
+	fn commitment(who: u64, hand: Hand) -> H256 {
+		RockPaperScissors::commitment_of(&who, hand, &salt(who))
+	}
+
+	/// Start game 0, in which 1 commits to `first` and 2 to `second`, each wagering 30
+	fn start_game(first: Hand, second: Hand) {
+		assert_ok!(RockPaperScissors::challenge(Origin::signed(1), 2, 30, commitment(1, first)));
+		assert_ok!(RockPaperScissors::accept(Origin::signed(2), 0, commitment(2, second)));
+	}
+
+	#[test]
+	fn hands_beat_one_another_in_a_circle() {
+		use Hand::*;
+		assert!(Rock.beats(Scissors) && Scissors.beats(Paper) && Paper.beats(Rock));
+		assert!(!Scissors.beats(Rock) && !Paper.beats(Scissors) && !Rock.beats(Paper));
+		assert!(!Rock.beats(Rock) && !Paper.beats(Paper) && !Scissors.beats(Scissors));
+	}
+
+	#[test]
+	fn challenges_reserve_wagers() {
+		new_test_ext().execute_with(|| {
+			let rock = commitment(1, Hand::Rock);
+			assert_err!(
+				RockPaperScissors::challenge(Origin::signed(1), 1, 30, rock),
+				"cannot play against yourself"
+			);
+			assert_err!(
+				RockPaperScissors::challenge(Origin::signed(1), 2, 101, rock),
+				"cannot afford the wager"
+			);
+
+			assert_ok!(RockPaperScissors::challenge(Origin::signed(1), 2, 30, rock));
+			assert_last_event::<TestRuntime>(RawEvent::Challenged(0, 1, 2, 30));
+			assert_eq!(Balances::reserved_balance(&1), 30);
+			assert_err!(
+				RockPaperScissors::reveal(Origin::signed(1), 0, Hand::Rock, salt(1)),
+				"game not started"
+			);
+			let paper = commitment(3, Hand::Paper);
+			assert_err!(RockPaperScissors::accept(Origin::signed(3), 0, paper), "not the opponent");
+
+			assert_ok!(RockPaperScissors::accept(Origin::signed(2), 0, commitment(2, Hand::Paper)));
+			assert_last_event::<TestRuntime>(RawEvent::Accepted(0));
+			assert_eq!(Balances::reserved_balance(&2), 30);
+			assert_eq!(RockPaperScissors::game(0).unwrap().deadline, 4);
+			assert_err!(RockPaperScissors::decline(Origin::signed(1), 0), "game already started");
+		})
+	}
+
+	#[test]
+	fn unaccepted_challenges_expire() {
+		new_test_ext().execute_with(|| {
+			let rock = commitment(1, Hand::Rock);
+			assert_ok!(RockPaperScissors::challenge(Origin::signed(1), 2, 30, rock));
+			System::set_block_number(7);
+			assert_err!(
+				RockPaperScissors::accept(Origin::signed(2), 0, commitment(2, Hand::Paper)),
+				"challenge expired"
+			);
+			assert_err!(RockPaperScissors::decline(Origin::signed(3), 0), "not a player");
+			assert_ok!(RockPaperScissors::decline(Origin::signed(1), 0));
+			assert_last_event::<TestRuntime>(RawEvent::Declined(0));
+			assert_eq!(Balances::free_balance(&1), 100);
+			assert_eq!(RockPaperScissors::game(0), None);
+		})
+	}
+
+	#[test]
+	fn reveals_must_match_commitments() {
+		new_test_ext().execute_with(|| {
+			start_game(Hand::Rock, Hand::Paper);
+			assert_err!(
+				RockPaperScissors::reveal(Origin::signed(1), 0, Hand::Scissors, salt(1)),
+				"hand does not match the commitment"
+			);
+			assert_err!(
+				RockPaperScissors::reveal(Origin::signed(1), 0, Hand::Rock, salt(2)),
+				"hand does not match the commitment"
+			);
+			assert_err!(
+				RockPaperScissors::reveal(Origin::signed(3), 0, Hand::Rock, salt(3)),
+				"not a player"
+			);
+
+			assert_ok!(RockPaperScissors::reveal(Origin::signed(1), 0, Hand::Rock, salt(1)));
+			assert_last_event::<TestRuntime>(RawEvent::Revealed(0, 1, Hand::Rock));
+			assert_err!(
+				RockPaperScissors::reveal(Origin::signed(1), 0, Hand::Rock, salt(1)),
+				"hand already revealed"
+			);
+		})
+	}
+
+	#[test]
+	fn copied_commitments_cannot_be_revealed() {
+		new_test_ext().execute_with(|| {
+			// The opponent copies the challenger's commitment, hoping to force a draw
+			let rock = commitment(1, Hand::Rock);
+			assert_ok!(RockPaperScissors::challenge(Origin::signed(1), 2, 30, rock));
+			assert_ok!(RockPaperScissors::accept(Origin::signed(2), 0, rock));
+
+			// Even after learning the challenger's hand and salt, the opponent cannot match it
+			assert_ok!(RockPaperScissors::reveal(Origin::signed(1), 0, Hand::Rock, salt(1)));
+			assert_err!(
+				RockPaperScissors::reveal(Origin::signed(2), 0, Hand::Rock, salt(1)),
+				"hand does not match the commitment"
+			);
+		})
+	}
+
+	#[test]
+	fn the_winner_takes_both_wagers() {
+		new_test_ext().execute_with(|| {
+			start_game(Hand::Rock, Hand::Paper);
+			assert_ok!(RockPaperScissors::reveal(Origin::signed(2), 0, Hand::Paper, salt(2)));
+			assert_ok!(RockPaperScissors::reveal(Origin::signed(1), 0, Hand::Rock, salt(1)));
+			assert_last_event::<TestRuntime>(RawEvent::Won(0, 2));
+
+			assert_eq!(Balances::free_balance(&1), 70);
+			assert_eq!(Balances::free_balance(&2), 130);
+			assert_eq!(Balances::reserved_balance(&1), 0);
+			assert_eq!(Balances::reserved_balance(&2), 0);
+			assert_eq!(RockPaperScissors::game(0), None);
+			assert!(Badges::has_badge(&2, &WINNER_BADGE));
+		})
+	}
+
+	#[test]
+	fn equal_hands_draw() {
+		new_test_ext().execute_with(|| {
+			start_game(Hand::Scissors, Hand::Scissors);
+			assert_ok!(RockPaperScissors::reveal(Origin::signed(1), 0, Hand::Scissors, salt(1)));
+			assert_ok!(RockPaperScissors::reveal(Origin::signed(2), 0, Hand::Scissors, salt(2)));
+			assert_last_event::<TestRuntime>(RawEvent::Drawn(0));
+			assert_eq!(Balances::free_balance(&1), 100);
+			assert_eq!(Balances::free_balance(&2), 100);
+		})
+	}
+
+	#[test]
+	fn players_who_do_not_reveal_forfeit() {
+		new_test_ext().execute_with(|| {
+			start_game(Hand::Rock, Hand::Scissors);
+			// The opponent sees the challenger's rock, and does not reveal their scissors
+			assert_ok!(RockPaperScissors::reveal(Origin::signed(1), 0, Hand::Rock, salt(1)));
+			assert_err!(
+				RockPaperScissors::resolve(Origin::signed(3), 0),
+				"reveal period is not over"
+			);
+
+			System::set_block_number(5);
+			assert_err!(
+				RockPaperScissors::reveal(Origin::signed(2), 0, Hand::Scissors, salt(2)),
+				"reveal period is over"
+			);
+			assert_ok!(RockPaperScissors::resolve(Origin::signed(3), 0));
+			assert_last_event::<TestRuntime>(RawEvent::Forfeited(0, 2));
+			assert!(!Badges::has_badge(&1, &WINNER_BADGE));
+			assert_eq!(Balances::free_balance(&1), 130);
+			assert_eq!(Balances::free_balance(&2), 70);
+			assert_eq!(RockPaperScissors::game(0), None);
+		})
+	}
+
+	#[test]
+	fn nobody_revealing_returns_both_wagers() {
+		new_test_ext().execute_with(|| {
+			start_game(Hand::Rock, Hand::Scissors);
+			System::set_block_number(5);
+			assert_ok!(RockPaperScissors::resolve(Origin::signed(3), 0));
+			assert_last_event::<TestRuntime>(RawEvent::Drawn(0));
+			assert_eq!(Balances::free_balance(&1), 100);
+			assert_eq!(Balances::free_balance(&2), 100);
+		})
+	}
+}
@@ -0,0 +1,665 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! A sealed-bid, second-price (Vickrey) auction
+//!
+//! Each auction moves through the phases of the `Phase` enum, which is kept in storage:
+//!
+//! * `Bidding` - bidders submit the hash of their bid along with a deposit that covers it. The
+//!   deposit hides the bid: it only has to be at least as large.
+//! * `Revealing` - bidders reveal their bid and salt. Revealed bids that are no longer the best
+//!   get their deposit back right away.
+//! * `Settled` or `Unsold` - the highest bidder wins and pays the second highest bid, or the
+//!   reserve price if that is higher. Bids that were never revealed forfeit their deposit to the
+//!   seller.
+//!
+//! Phase changes happen in `on_finalize` at each phase's deadline.
+
+use codec::{Decode, Encode};
+use sp_std::prelude::*;
+use sp_runtime::{
+	traits::{Hash, Saturating, Zero},
+	RuntimeDebug,
+};
+use frame_support::{
+	decl_event, decl_module, decl_storage,
+	dispatch::DispatchResult,
+	ensure,
+	traits::{Currency, Get, ReservableCurrency},
+};
+use frame_system::{self as system, ensure_signed};
+
+pub type AuctionIndex = u32;
+
+type BalanceOf<T> = <<T as Trait>::Currency as Currency<<T as system::Trait>::AccountId>>::Balance;
+type AuctionOf<T> = Auction<
+	<T as system::Trait>::AccountId,
+	BalanceOf<T>,
+	<T as system::Trait>::BlockNumber,
+>;
+
+/// The phases an auction moves through
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub enum Phase<AccountId, Balance, BlockNumber> {
+	/// Sealed bids are accepted until the end of the given block
+	Bidding(BlockNumber),
+	/// Bids may be revealed until the end of the given block
+	Revealing(BlockNumber),
+	/// The auction ended and the winner paid the price
+	Settled { winner: AccountId, price: Balance },
+	/// The auction ended without a bid that met the reserve price
+	Unsold,
+}
+
+/// A bid that has not been revealed yet
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct SealedBid<Hash, Balance> {
+	/// Hash of the bidder, the bid and a salt
+	pub seal: Hash,
+	/// Funds reserved to cover the bid
+	pub deposit: Balance,
+}
+
+/// A bid that has been revealed
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct RevealedBid<AccountId, Balance> {
+	pub who: AccountId,
+	pub value: Balance,
+	pub deposit: Balance,
+}
+
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct Auction<AccountId, Balance, BlockNumber> {
+	/// The seller, who receives the winning payment
+	pub owner: AccountId,
+	/// The lowest price the seller accepts
+	pub reserve_price: Balance,
+	pub phase: Phase<AccountId, Balance, BlockNumber>,
+	/// The highest bid revealed so far
+	pub best: Option<RevealedBid<AccountId, Balance>>,
+	/// The second highest bid revealed so far
+	pub second_price: Balance,
+}
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The currency in which bids are made
+	type Currency: ReservableCurrency<Self::AccountId>;
+
+	/// Number of blocks in which sealed bids are accepted
+	type BiddingPeriod: Get<Self::BlockNumber>;
+
+	/// Number of blocks in which bids may be revealed
+	type RevealPeriod: Get<Self::BlockNumber>;
+
+	/// Maximum number of bids on a single auction
+	type MaxBidders: Get<u32>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as SealedAuction {
+		/// All auctions, including those that have ended
+		Auctions get(fn auction): map AuctionIndex => Option<AuctionOf<T>>;
+
+		/// Number of auctions ever created; the next auction's index
+		AuctionCount get(fn auction_count): AuctionIndex;
+
+		/// Bids that have not been revealed yet
+		SealedBids get(fn sealed_bid):
+			double_map AuctionIndex, twox_128(T::AccountId) => Option<SealedBid<T::Hash, BalanceOf<T>>>;
+
+		/// Everyone who placed a bid on each auction
+		Bidders get(fn bidders): map AuctionIndex => Vec<T::AccountId>;
+
+		/// Auctions whose phase ends at the end of each block
+		Deadlines get(fn deadlines): map T::BlockNumber => Vec<AuctionIndex>;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as system::Trait>::AccountId,
+		Balance = BalanceOf<T>,
+		BlockNumber = <T as system::Trait>::BlockNumber,
+	{
+		/// An auction was created; bidding ends at the given block
+		AuctionCreated(AuctionIndex, AccountId, BlockNumber),
+		/// A sealed bid was placed
+		BidPlaced(AuctionIndex, AccountId),
+		/// Bidding ended; bids may be revealed until the given block
+		RevealStarted(AuctionIndex, BlockNumber),
+		/// A bid was revealed
+		BidRevealed(AuctionIndex, AccountId, Balance),
+		/// A bid was never revealed and its deposit went to the seller
+		BidForfeited(AuctionIndex, AccountId, Balance),
+		/// An auction was won by an account at the given price
+		AuctionSettled(AuctionIndex, AccountId, Balance),
+		/// An auction ended without a winner
+		AuctionUnsold(AuctionIndex),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		const BiddingPeriod: T::BlockNumber = T::BiddingPeriod::get();
+
+		const RevealPeriod: T::BlockNumber = T::RevealPeriod::get();
+
+		const MaxBidders: u32 = T::MaxBidders::get();
+
+		/// Start an auction that accepts bids for `BiddingPeriod` blocks
+		fn create(origin, reserve_price: BalanceOf<T>) -> DispatchResult {
+			let owner = ensure_signed(origin)?;
+
+			let index = Self::auction_count();
+			let next = index.checked_add(1).ok_or("auction index overflow")?;
+			let ends = <system::Module<T>>::block_number() + T::BiddingPeriod::get();
+
+			<Auctions<T>>::insert(index, Auction {
+				owner: owner.clone(),
+				reserve_price,
+				phase: Phase::Bidding(ends),
+				best: None,
+				second_price: Zero::zero(),
+			});
+			<Deadlines<T>>::mutate(ends, |due| due.push(index));
+			AuctionCount::put(next);
+
+			Self::deposit_event(RawEvent::AuctionCreated(index, owner, ends));
+			Ok(())
+		}
+
+		/// Place a sealed bid, computed off chain with `seal`, reserving a deposit that covers it
+		fn bid(origin, index: AuctionIndex, seal: T::Hash, deposit: BalanceOf<T>) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let auction = Self::auction(index).ok_or("no such auction")?;
+			match auction.phase {
+				Phase::Bidding(_) => (),
+				_ => return Err("auction is not accepting bids".into()),
+			}
+			ensure!(!<SealedBids<T>>::exists(index, &who), "already bid on this auction");
+			let mut bidders = Self::bidders(index);
+			ensure!((bidders.len() as u32) < T::MaxBidders::get(), "too many bidders");
+
+			T::Currency::reserve(&who, deposit).map_err(|_| "cannot afford the deposit")?;
+
+			<SealedBids<T>>::insert(index, &who, SealedBid { seal, deposit });
+			bidders.push(who.clone());
+			<Bidders<T>>::insert(index, bidders);
+
+			Self::deposit_event(RawEvent::BidPlaced(index, who));
+			Ok(())
+		}
+
+		/// Reveal a sealed bid
+		fn reveal(origin, index: AuctionIndex, value: BalanceOf<T>, salt: T::Hash) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let mut auction = Self::auction(index).ok_or("no such auction")?;
+			match auction.phase {
+				Phase::Revealing(_) => (),
+				_ => return Err("auction is not accepting reveals".into()),
+			}
+			let sealed = Self::sealed_bid(index, &who).ok_or("no sealed bid to reveal")?;
+			ensure!(sealed.seal == Self::seal(&who, value, &salt), "bid does not match its seal");
+			ensure!(value <= sealed.deposit, "bid is larger than its deposit");
+
+			<SealedBids<T>>::remove(index, &who);
+			let revealed = RevealedBid { who: who.clone(), value, deposit: sealed.deposit };
+			match auction.best.take() {
+				// Ties go to whoever revealed first
+				Some(best) if best.value >= value => {
+					auction.second_price = auction.second_price.max(value);
+					auction.best = Some(best);
+					T::Currency::unreserve(&who, sealed.deposit);
+				}
+				previous => {
+					if let Some(outbid) = previous {
+						auction.second_price = auction.second_price.max(outbid.value);
+						T::Currency::unreserve(&outbid.who, outbid.deposit);
+					}
+					auction.best = Some(revealed);
+				}
+			}
+			<Auctions<T>>::insert(index, auction);
+
+			Self::deposit_event(RawEvent::BidRevealed(index, who, value));
+			Ok(())
+		}
+
+		fn on_finalize(n: T::BlockNumber) {
+			for index in <Deadlines<T>>::take(n) {
+				Self::advance(index, n);
+			}
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// The seal of a bid. Including the bidder means nobody can copy another bidder's seal.
+	pub fn seal(who: &T::AccountId, value: BalanceOf<T>, salt: &T::Hash) -> T::Hash {
+		T::Hashing::hash_of(&(who, value, salt))
+	}
+
+	/// Move an auction to its next phase at the end of block `now`
+	fn advance(index: AuctionIndex, now: T::BlockNumber) {
+		let mut auction = match Self::auction(index) {
+			Some(auction) => auction,
+			None => return,
+		};
+		match auction.phase {
+			Phase::Bidding(_) => {
+				let ends = now + T::RevealPeriod::get();
+				auction.phase = Phase::Revealing(ends);
+				<Deadlines<T>>::mutate(ends, |due| due.push(index));
+				Self::deposit_event(RawEvent::RevealStarted(index, ends));
+			}
+			Phase::Revealing(_) => Self::settle(index, &mut auction),
+			// Ended auctions are never scheduled again
+			Phase::Settled { .. } | Phase::Unsold => return,
+		}
+		<Auctions<T>>::insert(index, auction);
+	}
+
+	/// End an auction: forfeit unrevealed bids and charge the winner
+	fn settle(index: AuctionIndex, auction: &mut AuctionOf<T>) {
+		for bidder in <Bidders<T>>::take(index) {
+			if let Some(sealed) = <SealedBids<T>>::take(index, &bidder) {
+				let (forfeit, _) = T::Currency::slash_reserved(&bidder, sealed.deposit);
+				T::Currency::resolve_creating(&auction.owner, forfeit);
+				Self::deposit_event(RawEvent::BidForfeited(index, bidder, sealed.deposit));
+			}
+		}
+
+		match auction.best.take() {
+			Some(best) if best.value >= auction.reserve_price => {
+				let price = auction.second_price.max(auction.reserve_price);
+				let (payment, _) = T::Currency::slash_reserved(&best.who, price);
+				T::Currency::resolve_creating(&auction.owner, payment);
+				T::Currency::unreserve(&best.who, best.deposit.saturating_sub(price));
+
+				auction.phase = Phase::Settled { winner: best.who.clone(), price };
+				Self::deposit_event(RawEvent::AuctionSettled(index, best.who, price));
+			}
+			best => {
+				if let Some(best) = best {
+					T::Currency::unreserve(&best.who, best.deposit);
+				}
+				auction.phase = Phase::Unsold;
+				Self::deposit_event(RawEvent::AuctionUnsold(index));
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use sp_core::H256;
+	use frame_support::{
+		assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types
+	};
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup, OnFinalize},
+		Perbill,
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const ExistentialDeposit: u64 = 0;
+		pub const TransferFee: u64 = 0;
+		pub const CreationFee: u64 = 0;
+
+		pub const BiddingPeriod: u64 = 5;
+		pub const RevealPeriod: u64 = 3;
+		pub const MaxBidders: u32 = 3;
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	impl balances::Trait for TestRuntime {
+		type Balance = u64;
+		type OnFreeBalanceZero = ();
+		type OnNewAccount = ();
+		type Event = TestEvent;
+		type TransferPayment = ();
+		type DustRemoval = ();
+		type ExistentialDeposit = ExistentialDeposit;
+		type TransferFee = TransferFee;
+		type CreationFee = CreationFee;
+	}
+
+	mod sealed_auction {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			balances<T>,
+			sealed_auction<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type Currency = balances::Module<Self>;
+		type BiddingPeriod = BiddingPeriod;
+		type RevealPeriod = RevealPeriod;
+		type MaxBidders = MaxBidders;
+	}
+
+	pub type System = system::Module<TestRuntime>;
+	pub type Balances = balances::Module<TestRuntime>;
+	pub type SealedAuction = Module<TestRuntime>;
+
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		let mut t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		balances::GenesisConfig::<TestRuntime> {
+			balances: vec![(1, 100), (2, 100), (3, 100), (4, 100), (5, 100)],
+			vesting: vec![],
+		}
+		.assimilate_storage(&mut t)
+		.unwrap();
+		let mut ext: sp_io::TestExternalities = t.into();
+		ext.execute_with(|| System::set_block_number(1));
+		ext
+	}
+
+	fn run_to_block(n: u64) {
+		while System::block_number() < n {
+			System::set_block_number(System::block_number() + 1);
+			SealedAuction::on_finalize(System::block_number());
+		}
+	}
+
+	fn event_emitted(event: RawEvent<u64, u64, u64>) -> bool {
+		let expected = TestEvent::sealed_auction(event);
+		System::events().iter().any(|a| a.event == expected)
+	}
+
+	fn salt(who: u64) -> H256 {
+		H256::repeat_byte(who as u8)
+	}
+
+	fn phase(index: AuctionIndex) -> Phase<u64, u64, u64> {
+		SealedAuction::auction(index).expect("auction exists").phase
+	}
+
+	/// Place a sealed bid of `value` backed by `deposit`
+	fn place_bid(who: u64, value: u64, deposit: u64) {
+		let seal = SealedAuction::seal(&who, value, &salt(who));
+		assert_ok!(SealedAuction::bid(Origin::signed(who), 0, seal, deposit));
+	}
+
+	fn reveal_bid(who: u64, value: u64) {
+		assert_ok!(SealedAuction::reveal(Origin::signed(who), 0, value, salt(who)));
+	}
+
+	/// Create auction 0 at block 1. Bidding ends at 6 and revealing at 9.
+	fn create_auction(reserve_price: u64) {
+		assert_ok!(SealedAuction::create(Origin::signed(1), reserve_price));
+	}
+
+	#[test]
+	fn new_auction_is_bidding() {
+		new_test_ext().execute_with(|| {
+			create_auction(10);
+			assert_eq!(phase(0), Phase::Bidding(6));
+			assert_eq!(SealedAuction::auction_count(), 1);
+			assert_eq!(SealedAuction::deadlines(6), vec![0]);
+			assert!(event_emitted(RawEvent::AuctionCreated(0, 1, 6)));
+		})
+	}
+
+	#[test]
+	fn bidding_becomes_revealing_at_deadline() {
+		new_test_ext().execute_with(|| {
+			create_auction(10);
+			run_to_block(5);
+			assert_eq!(phase(0), Phase::Bidding(6));
+
+			run_to_block(6);
+			assert_eq!(phase(0), Phase::Revealing(9));
+			assert!(event_emitted(RawEvent::RevealStarted(0, 9)));
+		})
+	}
+
+	#[test]
+	fn revealing_becomes_settled_at_second_price() {
+		new_test_ext().execute_with(|| {
+			create_auction(10);
+			place_bid(2, 50, 60);
+			place_bid(3, 30, 30);
+			place_bid(4, 40, 80);
+			run_to_block(6);
+			reveal_bid(2, 50);
+			reveal_bid(3, 30);
+			reveal_bid(4, 40);
+			run_to_block(8);
+			assert_eq!(phase(0), Phase::Revealing(9));
+
+			run_to_block(9);
+			assert_eq!(phase(0), Phase::Settled { winner: 2, price: 40 });
+			assert!(event_emitted(RawEvent::AuctionSettled(0, 2, 40)));
+			assert_eq!(Balances::free_balance(&1), 140);
+			assert_eq!(Balances::free_balance(&2), 60);
+			assert_eq!(Balances::reserved_balance(&2), 0);
+		})
+	}
+
+	#[test]
+	fn lone_bidder_pays_reserve_price() {
+		new_test_ext().execute_with(|| {
+			create_auction(10);
+			place_bid(2, 50, 50);
+			run_to_block(6);
+			reveal_bid(2, 50);
+			run_to_block(9);
+			assert_eq!(phase(0), Phase::Settled { winner: 2, price: 10 });
+			assert_eq!(Balances::free_balance(&2), 90);
+		})
+	}
+
+	#[test]
+	fn revealing_becomes_unsold_without_bids() {
+		new_test_ext().execute_with(|| {
+			create_auction(10);
+			run_to_block(9);
+			assert_eq!(phase(0), Phase::Unsold);
+			assert!(event_emitted(RawEvent::AuctionUnsold(0)));
+		})
+	}
+
+	#[test]
+	fn revealing_becomes_unsold_below_reserve() {
+		new_test_ext().execute_with(|| {
+			create_auction(60);
+			place_bid(2, 50, 50);
+			run_to_block(6);
+			reveal_bid(2, 50);
+			run_to_block(9);
+			assert_eq!(phase(0), Phase::Unsold);
+			assert_eq!(Balances::free_balance(&2), 100);
+			assert_eq!(Balances::free_balance(&1), 100);
+		})
+	}
+
+	#[test]
+	fn ended_auctions_stay_ended() {
+		new_test_ext().execute_with(|| {
+			create_auction(10);
+			run_to_block(20);
+			assert_eq!(phase(0), Phase::Unsold);
+			assert!(SealedAuction::deadlines(9).is_empty());
+		})
+	}
+
+	#[test]
+	fn cannot_reveal_while_bidding() {
+		new_test_ext().execute_with(|| {
+			create_auction(10);
+			place_bid(2, 50, 50);
+			assert_err!(
+				SealedAuction::reveal(Origin::signed(2), 0, 50, salt(2)),
+				"auction is not accepting reveals"
+			);
+		})
+	}
+
+	#[test]
+	fn cannot_bid_while_revealing() {
+		new_test_ext().execute_with(|| {
+			create_auction(10);
+			run_to_block(6);
+			let seal = SealedAuction::seal(&2, 50, &salt(2));
+			assert_err!(
+				SealedAuction::bid(Origin::signed(2), 0, seal, 50),
+				"auction is not accepting bids"
+			);
+		})
+	}
+
+	#[test]
+	fn ended_auctions_accept_nothing() {
+		new_test_ext().execute_with(|| {
+			create_auction(10);
+			place_bid(2, 50, 50);
+			place_bid(3, 20, 20);
+			run_to_block(6);
+			reveal_bid(2, 50);
+			run_to_block(9);
+			assert_eq!(phase(0), Phase::Settled { winner: 2, price: 10 });
+
+			let seal = SealedAuction::seal(&4, 50, &salt(4));
+			assert_err!(
+				SealedAuction::bid(Origin::signed(4), 0, seal, 50),
+				"auction is not accepting bids"
+			);
+			assert_err!(
+				SealedAuction::reveal(Origin::signed(3), 0, 20, salt(3)),
+				"auction is not accepting reveals"
+			);
+		})
+	}
+
+	#[test]
+	fn unknown_auctions_are_rejected() {
+		new_test_ext().execute_with(|| {
+			assert_err!(SealedAuction::bid(Origin::signed(2), 0, H256::zero(), 5), "no such auction");
+			assert_err!(SealedAuction::reveal(Origin::signed(2), 0, 5, H256::zero()), "no such auction");
+		})
+	}
+
+	#[test]
+	fn bids_are_limited() {
+		new_test_ext().execute_with(|| {
+			create_auction(10);
+			place_bid(2, 10, 10);
+			assert_err!(
+				SealedAuction::bid(Origin::signed(2), 0, H256::zero(), 10),
+				"already bid on this auction"
+			);
+			place_bid(3, 10, 10);
+			place_bid(4, 10, 10);
+			assert_err!(
+				SealedAuction::bid(Origin::signed(5), 0, H256::zero(), 10),
+				"too many bidders"
+			);
+			assert_err!(
+				SealedAuction::bid(Origin::signed(1), 0, H256::zero(), 1000),
+				"cannot afford the deposit"
+			);
+		})
+	}
+
+	#[test]
+	fn reveal_must_match_seal_and_deposit() {
+		new_test_ext().execute_with(|| {
+			create_auction(10);
+			place_bid(2, 50, 50);
+			place_bid(3, 70, 60);
+			run_to_block(6);
+			assert_err!(
+				SealedAuction::reveal(Origin::signed(2), 0, 40, salt(2)),
+				"bid does not match its seal"
+			);
+			assert_err!(
+				SealedAuction::reveal(Origin::signed(3), 0, 70, salt(3)),
+				"bid is larger than its deposit"
+			);
+			assert_err!(
+				SealedAuction::reveal(Origin::signed(4), 0, 10, salt(4)),
+				"no sealed bid to reveal"
+			);
+		})
+	}
+
+	#[test]
+	fn losing_deposits_return_on_reveal() {
+		new_test_ext().execute_with(|| {
+			create_auction(10);
+			place_bid(2, 30, 40);
+			place_bid(3, 50, 50);
+			run_to_block(6);
+
+			reveal_bid(2, 30);
+			assert_eq!(Balances::reserved_balance(&2), 40);
+			// Outbid, so the deposit is released
+			reveal_bid(3, 50);
+			assert_eq!(Balances::reserved_balance(&2), 0);
+			assert_eq!(Balances::reserved_balance(&3), 50);
+		})
+	}
+
+	#[test]
+	fn unrevealed_bids_are_forfeited() {
+		new_test_ext().execute_with(|| {
+			create_auction(10);
+			place_bid(2, 30, 30);
+			place_bid(3, 90, 90);
+			run_to_block(6);
+			reveal_bid(2, 30);
+			run_to_block(9);
+
+			assert_eq!(phase(0), Phase::Settled { winner: 2, price: 10 });
+			assert!(event_emitted(RawEvent::BidForfeited(0, 3, 90)));
+			assert_eq!(Balances::total_balance(&3), 10);
+			assert_eq!(Balances::free_balance(&1), 200);
+			assert_eq!(SealedAuction::sealed_bid(0, &3), None);
+		})
+	}
+}
@@ -0,0 +1,360 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! A pallet that keeps its own authority set in step with `pallet_session`.
+//!
+//! Root curates a list of candidate accounts. At every session boundary the pallet hands that list
+//! to `pallet_session` (as its `SessionManager`) and, in return, is told which session keys are
+//! active and which are queued for the next session (as a `OneSessionHandler`). The session keys
+//! use a key type defined right here in the `crypto` module.
+
+use sp_std::prelude::*;
+use sp_runtime::{traits::Member, RuntimeAppPublic};
+use frame_support::{
+	decl_event, decl_module, decl_storage,
+	dispatch::DispatchResult,
+	ensure, Parameter,
+};
+use frame_system::{self as system, ensure_root};
+use pallet_session::{OneSessionHandler, SessionIndex, SessionManager};
+
+/// The key type used for this pallet's session keys.
+pub const KEY_TYPE: sp_application_crypto::KeyTypeId = sp_application_crypto::KeyTypeId(*b"rcpa");
+
+/// The application-specific crypto used by the recipe authorities.
+///
+/// `app_crypto!` wraps sr25519 in new `Public`, `Signature` (and, with `std`, `Pair`) types that
+/// are tagged with `KEY_TYPE`, so keys for this pallet live apart from all other keys in the
+/// keystore.
+pub mod crypto {
+	use super::KEY_TYPE;
+	use sp_application_crypto::{app_crypto, sr25519};
+	app_crypto!(sr25519, KEY_TYPE);
+}
+
+/// An authority identifier that a runtime can use for `Trait::AuthorityId`.
+pub type AuthorityId = crypto::Public;
+
+pub trait Trait: system::Trait + pallet_session::Trait {
+	/// The overarching event type.
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The identifier type for an authority, used as this pallet's session key.
+	type AuthorityId: Member + Parameter + RuntimeAppPublic + Default;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as SessionAuthorities {
+		/// Accounts that will be handed to `pallet_session` at the next session boundary, sorted.
+		Candidates get(fn candidates) config(): Vec<T::AccountId>;
+
+		/// Keys of the authorities for the current session.
+		Authorities get(fn authorities): Vec<T::AuthorityId>;
+
+		/// Keys of the authorities queued for the next session.
+		NextAuthorities get(fn next_authorities): Vec<T::AuthorityId>;
+	}
+}
+
+decl_event!(
+	pub enum Event<T> where AccountId = <T as system::Trait>::AccountId {
+		/// An account will be included in the next queued authority set.
+		CandidateAdded(AccountId),
+		/// An account will be left out of the next queued authority set.
+		CandidateRemoved(AccountId),
+		/// A new authority set, of the given size, took over in the given session.
+		AuthoritiesRotated(SessionIndex, u32),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		/// Add a candidate to the authority set
+		fn add_candidate(origin, who: T::AccountId) -> DispatchResult {
+			ensure_root(origin)?;
+
+			let mut candidates = Candidates::<T>::get();
+			match candidates.binary_search(&who) {
+				Ok(_) => Err("already a candidate".into()),
+				Err(index) => {
+					candidates.insert(index, who.clone());
+					Candidates::<T>::put(candidates);
+					Self::deposit_event(RawEvent::CandidateAdded(who));
+					Ok(())
+				}
+			}
+		}
+
+		/// Remove a candidate from the authority set
+		fn remove_candidate(origin, who: T::AccountId) -> DispatchResult {
+			ensure_root(origin)?;
+
+			let mut candidates = Candidates::<T>::get();
+			let index = candidates.binary_search(&who).map_err(|_| "not a candidate")?;
+			ensure!(candidates.len() > 1, "cannot remove the last candidate");
+
+			candidates.remove(index);
+			Candidates::<T>::put(candidates);
+			Self::deposit_event(RawEvent::CandidateRemoved(who));
+			Ok(())
+		}
+	}
+}
+
+/// `pallet_session` asks this pallet which accounts should validate in upcoming sessions.
+impl<T: Trait> SessionManager<T::AccountId> for Module<T> {
+	fn new_session(_new_index: SessionIndex) -> Option<Vec<T::AccountId>> {
+		let candidates = Candidates::<T>::get();
+		if candidates.is_empty() {
+			// Keep the current set rather than stall the chain with no authorities.
+			None
+		} else {
+			Some(candidates)
+		}
+	}
+
+	fn end_session(_end_index: SessionIndex) {}
+}
+
+/// `pallet_session` tells this pallet which of its keys are active and which are queued.
+impl<T: Trait> OneSessionHandler<T::AccountId> for Module<T> {
+	type Key = T::AuthorityId;
+
+	fn on_genesis_session<'a, I: 'a>(validators: I)
+		where I: Iterator<Item=(&'a T::AccountId, T::AuthorityId)>
+	{
+		let keys = validators.map(|(_, key)| key).collect::<Vec<_>>();
+		Authorities::<T>::put(&keys);
+		NextAuthorities::<T>::put(keys);
+	}
+
+	fn on_new_session<'a, I: 'a>(changed: bool, validators: I, queued_validators: I)
+		where I: Iterator<Item=(&'a T::AccountId, T::AuthorityId)>
+	{
+		// The queued set becomes active one session after it was queued, so the keys passed as
+		// `validators` here are the ones we stored as `NextAuthorities` last session.
+		let keys = validators.map(|(_, key)| key).collect::<Vec<_>>();
+		let count = keys.len() as u32;
+		Authorities::<T>::put(keys);
+		NextAuthorities::<T>::put(queued_validators.map(|(_, key)| key).collect::<Vec<_>>());
+
+		if changed {
+			let index = <pallet_session::Module<T>>::current_index();
+			Self::deposit_event(RawEvent::AuthoritiesRotated(index, count));
+		}
+	}
+
+	fn on_disabled(_validator_index: usize) {}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use sp_core::H256;
+	use frame_support::{
+		assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types, weights::Weight,
+	};
+	use sp_runtime::{
+		testing::{Header, UintAuthorityId},
+		traits::{BlakeTwo256, ConvertInto, IdentityLookup, OnInitialize},
+		Perbill,
+	};
+	use frame_system::RawOrigin;
+	use pallet_session::PeriodicSessions;
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: Weight = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const Period: u64 = 3;
+		pub const Offset: u64 = 0;
+		pub const DisabledValidatorsThreshold: Perbill = Perbill::from_percent(33);
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	impl pallet_session::Trait for TestRuntime {
+		type Event = TestEvent;
+		type ValidatorId = u64;
+		type ValidatorIdOf = ConvertInto;
+		type ShouldEndSession = PeriodicSessions<Period, Offset>;
+		type SessionManager = SessionAuthorities;
+		type SessionHandler = (SessionAuthorities,);
+		type Keys = UintAuthorityId;
+		type DisabledValidatorsThreshold = DisabledValidatorsThreshold;
+	}
+
+	mod session_authorities {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			pallet_session,
+			session_authorities<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type AuthorityId = UintAuthorityId;
+	}
+
+	pub type System = system::Module<TestRuntime>;
+	pub type Session = pallet_session::Module<TestRuntime>;
+	pub type SessionAuthorities = Module<TestRuntime>;
+
+	pub struct ExtBuilder;
+
+	impl ExtBuilder {
+		/// Accounts 1, 2 and 3 start as candidates with registered session keys.
+		pub fn build() -> sp_io::TestExternalities {
+			let mut storage = system::GenesisConfig::default()
+				.build_storage::<TestRuntime>()
+				.unwrap();
+			GenesisConfig::<TestRuntime> {
+				candidates: vec![1, 2, 3],
+			}
+			.assimilate_storage(&mut storage)
+			.unwrap();
+			pallet_session::GenesisConfig::<TestRuntime> {
+				keys: (1..=3).map(|i| (i, UintAuthorityId(i))).collect(),
+			}
+			.assimilate_storage(&mut storage)
+			.unwrap();
+			storage.into()
+		}
+	}
+
+	fn keys(ids: &[u64]) -> Vec<UintAuthorityId> {
+		ids.iter().cloned().map(UintAuthorityId).collect()
+	}
+
+	fn run_to_block(n: u64) {
+		while System::block_number() < n {
+			System::set_block_number(System::block_number() + 1);
+			Session::on_initialize(System::block_number());
+		}
+	}
+
+	#[test]
+	fn genesis_authorities_are_the_candidates() {
+		ExtBuilder::build().execute_with(|| {
+			assert_eq!(SessionAuthorities::authorities(), keys(&[1, 2, 3]));
+			assert_eq!(SessionAuthorities::next_authorities(), keys(&[1, 2, 3]));
+		})
+	}
+
+	#[test]
+	fn only_root_manages_candidates() {
+		ExtBuilder::build().execute_with(|| {
+			assert!(SessionAuthorities::add_candidate(Origin::signed(1), 4).is_err());
+			assert!(SessionAuthorities::remove_candidate(Origin::signed(1), 3).is_err());
+			assert_ok!(SessionAuthorities::add_candidate(RawOrigin::Root.into(), 4));
+			assert_eq!(SessionAuthorities::candidates(), vec![1, 2, 3, 4]);
+		})
+	}
+
+	#[test]
+	fn candidate_list_is_checked() {
+		ExtBuilder::build().execute_with(|| {
+			assert_err!(
+				SessionAuthorities::add_candidate(RawOrigin::Root.into(), 2),
+				"already a candidate"
+			);
+			assert_err!(
+				SessionAuthorities::remove_candidate(RawOrigin::Root.into(), 4),
+				"not a candidate"
+			);
+			assert_ok!(SessionAuthorities::remove_candidate(RawOrigin::Root.into(), 1));
+			assert_ok!(SessionAuthorities::remove_candidate(RawOrigin::Root.into(), 2));
+			assert_err!(
+				SessionAuthorities::remove_candidate(RawOrigin::Root.into(), 3),
+				"cannot remove the last candidate"
+			);
+		})
+	}
+
+	#[test]
+	fn new_candidate_is_queued_then_activated() {
+		ExtBuilder::build().execute_with(|| {
+			assert_ok!(Session::set_keys(Origin::signed(4), UintAuthorityId(4), vec![]));
+			assert_ok!(SessionAuthorities::add_candidate(RawOrigin::Root.into(), 4));
+
+			// Session 1: the new set is only queued
+			Session::rotate_session();
+			assert_eq!(SessionAuthorities::authorities(), keys(&[1, 2, 3]));
+			assert_eq!(SessionAuthorities::next_authorities(), keys(&[1, 2, 3, 4]));
+
+			// Session 2: the queued set takes over
+			Session::rotate_session();
+			assert_eq!(SessionAuthorities::authorities(), keys(&[1, 2, 3, 4]));
+			assert_eq!(SessionAuthorities::next_authorities(), keys(&[1, 2, 3, 4]));
+
+			let expected_event = TestEvent::session_authorities(RawEvent::AuthoritiesRotated(2, 4));
+			assert!(System::events().iter().any(|a| a.event == expected_event));
+		})
+	}
+
+	#[test]
+	fn removed_candidate_hands_over_at_session_boundary() {
+		ExtBuilder::build().execute_with(|| {
+			assert_ok!(SessionAuthorities::remove_candidate(RawOrigin::Root.into(), 2));
+
+			// Nothing changes mid-session
+			run_to_block(2);
+			assert_eq!(Session::current_index(), 0);
+			assert_eq!(SessionAuthorities::next_authorities(), keys(&[1, 2, 3]));
+
+			// Block 3 ends session 0 and queues the smaller set
+			run_to_block(3);
+			assert_eq!(Session::current_index(), 1);
+			assert_eq!(SessionAuthorities::authorities(), keys(&[1, 2, 3]));
+			assert_eq!(SessionAuthorities::next_authorities(), keys(&[1, 3]));
+
+			// Block 6 ends session 1 and hands over to the smaller set
+			run_to_block(6);
+			assert_eq!(Session::current_index(), 2);
+			assert_eq!(SessionAuthorities::authorities(), keys(&[1, 3]));
+		})
+	}
+
+	#[test]
+	fn unchanged_set_emits_no_rotation_event() {
+		ExtBuilder::build().execute_with(|| {
+			Session::rotate_session();
+			Session::rotate_session();
+			assert!(!System::events().iter().any(|a| match a.event {
+				TestEvent::session_authorities(RawEvent::AuthoritiesRotated(..)) => true,
+				_ => false,
+			}));
+		})
+	}
+}
@@ -2,11 +2,28 @@
 
 // Simple Storage Map
 // https://substrate.dev/rustdocs/master/frame_support/storage/trait.StorageMap.html
-use support::{decl_event, decl_module, decl_storage, dispatch::DispatchResult, ensure, StorageMap};
+use support::{
+    decl_event, decl_module, decl_storage, dispatch::DispatchResult, ensure, weights::SimpleDispatchInfo,
+    StorageMap,
+};
 use system::ensure_signed;
+use feature_flags::FeatureFlags;
+use value_hooks::OnValueChanged;
+
+pub mod weights;
+pub use weights::WeightInfo;
+
+/// The feature flag that makes `increase_single_entry` stop at `u32::max_value()` rather than fail
+pub const SATURATING_INCREASE: &[u8] = b"simple-map/saturating-increase";
 
 pub trait Trait: system::Trait {
     type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+    /// Weight information for the dispatchables in this pallet
+    type WeightInfo: WeightInfo;
+    /// Decides for which accounts new behavior is enabled
+    type FeatureFlags: FeatureFlags<Self::AccountId>;
+    /// Reacts to every change of an entry, usually a tuple of handlers in other pallets
+    type OnValueChanged: OnValueChanged<Self::AccountId>;
 }
 
 decl_storage! {
@@ -35,16 +52,20 @@ decl_module! {
     pub struct Module<T: Trait> for enum Call where origin: T::Origin {
         fn deposit_event() = default;
 
+        #[weight = SimpleDispatchInfo::FixedNormal(T::WeightInfo::set_single_entry())]
         fn set_single_entry(origin, entry: u32) -> DispatchResult {
             // only a user can set their entry
             let user = ensure_signed(origin)?;
+            let old_entry = Self::entry(&user);
 
             <SimpleMap<T>>::insert(user.clone(), entry);
+            T::OnValueChanged::on_value_changed(&user, old_entry, Some(entry));
 
             Self::deposit_event(RawEvent::EntrySet(user, entry));
             Ok(())
         }
 
+        #[weight = SimpleDispatchInfo::FixedNormal(T::WeightInfo::get_single_entry())]
         fn get_single_entry(origin, account: T::AccountId) -> DispatchResult {
             // anyone (signed extrinsic) can get an entry
             let getter = ensure_signed(origin)?;
@@ -55,46 +76,70 @@ decl_module! {
             Ok(())
         }
 
+        #[weight = SimpleDispatchInfo::FixedNormal(T::WeightInfo::take_single_entry())]
         fn take_single_entry(origin) -> DispatchResult {
             // only the user can take their own entry
             let user = ensure_signed(origin)?;
 
             ensure!(<SimpleMap<T>>::exists(user.clone()), "an entry does not exist for this user");
             let entry = <SimpleMap<T>>::take(user.clone());
+            T::OnValueChanged::on_value_changed(&user, Some(entry), None);
             Self::deposit_event(RawEvent::EntryTook(user, entry));
             Ok(())
         }
 
+        #[weight = SimpleDispatchInfo::FixedNormal(T::WeightInfo::increase_single_entry())]
         fn increase_single_entry(origin, add_this_val: u32) -> DispatchResult {
             // only the user can mutate their own entry
             let user = ensure_signed(origin)?;
-            let original_value = <SimpleMap<T>>::get(&user);
-            let new_value = original_value.checked_add(add_this_val).ok_or("value overflowed")?;
-            <SimpleMap<T>>::insert(user, new_value);
+            let old_entry = Self::entry(&user);
+            let original_value = old_entry.unwrap_or_default();
+            // new behavior is rolled out gradually, behind a feature flag
+            let new_value = if T::FeatureFlags::is_enabled(SATURATING_INCREASE, &user) {
+                original_value.saturating_add(add_this_val)
+            } else {
+                original_value.checked_add(add_this_val).ok_or("value overflowed")?
+            };
+            <SimpleMap<T>>::insert(user.clone(), new_value);
+            T::OnValueChanged::on_value_changed(&user, old_entry, Some(new_value));
 
             Self::deposit_event(RawEvent::IncreaseEntry(original_value, new_value));
 
             Ok(())
         }
 
+        #[weight = SimpleDispatchInfo::FixedNormal(T::WeightInfo::compare_and_swap_single_entry())]
         fn compare_and_swap_single_entry(origin, old_entry: u32, new_entry: u32) -> DispatchResult {
             // only a user that knows their previous entry can set the new entry
             let user = ensure_signed(origin)?;
 
             // compare
-            ensure!(old_entry == <SimpleMap<T>>::get(user.clone()), "cas failed bc old_entry inputted by user != existing_entry");
+            let existing_entry = Self::entry(&user);
+            ensure!(old_entry == existing_entry.unwrap_or_default(), "cas failed bc old_entry inputted by user != existing_entry");
             // and swap
-            <SimpleMap<T>>::insert(user, new_entry);
+            <SimpleMap<T>>::insert(user.clone(), new_entry);
+            T::OnValueChanged::on_value_changed(&user, existing_entry, Some(new_entry));
             Self::deposit_event(RawEvent::CAS(old_entry, new_entry));
             Ok(())
         }
     }
 }
 
+impl<T: Trait> Module<T> {
+    /// The entry of `who`, or `None` if there is none, unlike the getter's default of zero
+    fn entry(who: &T::AccountId) -> Option<u32> {
+        if <SimpleMap<T>>::exists(who) {
+            Some(<SimpleMap<T>>::get(who))
+        } else {
+            None
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::RawEvent;
-    use crate::{Module, Trait};
+    use crate::{Call, Module, Trait, WeightInfo, SATURATING_INCREASE};
     use primitives::H256;
     use runtime_io;
     use runtime_primitives::{
@@ -102,7 +147,9 @@ mod tests {
         traits::{BlakeTwo256, IdentityLookup},
         Perbill,
     };
-    use support::{assert_ok, assert_err, impl_outer_event, impl_outer_origin, parameter_types};
+    use support::{
+        assert_ok, assert_err, impl_outer_event, impl_outer_origin, parameter_types, weights::GetDispatchInfo,
+    };
 
     impl_outer_origin! {
         pub enum Origin for TestRuntime {}
@@ -143,15 +190,28 @@ mod tests {
     impl_outer_event! {
         pub enum TestEvent for TestRuntime {
             simple_map<T>,
+            feature_flags<T>,
         }
     }
 
     impl Trait for TestRuntime {
         type Event = TestEvent;
+        type WeightInfo = ();
+        type FeatureFlags = feature_flags::Module<Self>;
+        type OnValueChanged = (value_hooks::Module<Self>,);
+    }
+
+    impl value_hooks::Trait for TestRuntime {}
+
+    impl feature_flags::Trait for TestRuntime {
+        type Event = TestEvent;
+        type ManageOrigin = system::EnsureRoot<u64>;
     }
 
     pub type System = system::Module<TestRuntime>;
     pub type SimpleMap = Module<TestRuntime>;
+    pub type FeatureFlags = feature_flags::Module<TestRuntime>;
+    pub type ValueHooks = value_hooks::Module<TestRuntime>;
 
     pub struct ExtBuilder;
 
@@ -221,6 +281,23 @@ mod tests {
         })
     }
 
+    #[test]
+    fn increase_saturates_behind_a_feature_flag() {
+        ExtBuilder::build().execute_with(|| {
+            assert_ok!(SimpleMap::set_single_entry(Origin::signed(2), u32::max_value() - 1));
+            assert_err!(SimpleMap::increase_single_entry(Origin::signed(2), 2), "value overflowed");
+
+            assert_ok!(FeatureFlags::set_flag(
+                system::RawOrigin::Root.into(),
+                SATURATING_INCREASE.to_vec(),
+                0,
+                100,
+            ));
+            assert_ok!(SimpleMap::increase_single_entry(Origin::signed(2), 2));
+            assert_eq!(SimpleMap::simple_map(2), u32::max_value());
+        })
+    }
+
     #[test]
     fn cas_works() {
         ExtBuilder::build().execute_with(|| {
@@ -238,4 +315,33 @@ mod tests {
             assert!(System::events().iter().any(|a| a.event == expected_event));
         })
     }
+
+    #[test]
+    fn hooks_see_every_change() {
+        ExtBuilder::build().execute_with(|| {
+            assert_ok!(SimpleMap::set_single_entry(Origin::signed(1), 19));
+            assert_ok!(SimpleMap::increase_single_entry(Origin::signed(2), 5));
+            assert_eq!((ValueHooks::total(), ValueHooks::entry_count()), (24, 2));
+
+            assert_ok!(SimpleMap::increase_single_entry(Origin::signed(1), 2));
+            assert_ok!(SimpleMap::compare_and_swap_single_entry(Origin::signed(2), 5, 10));
+            assert_eq!((ValueHooks::total(), ValueHooks::entry_count()), (31, 2));
+
+            assert_ok!(SimpleMap::take_single_entry(Origin::signed(1)));
+            assert_eq!((ValueHooks::total(), ValueHooks::entry_count()), (10, 1));
+
+            // Failed calls change nothing, so the hooks don't hear about them
+            assert_err!(
+                SimpleMap::compare_and_swap_single_entry(Origin::signed(2), 5, 10),
+                "cas failed bc old_entry inputted by user != existing_entry"
+            );
+            assert_eq!((ValueHooks::total(), ValueHooks::entry_count()), (10, 1));
+        })
+    }
+
+    #[test]
+    fn weights_come_from_weight_info() {
+        let info = Call::<TestRuntime>::set_single_entry(19).get_dispatch_info();
+        assert_eq!(info.weight, <() as WeightInfo>::set_single_entry());
+    }
 }
@@ -0,0 +1,62 @@
+//! Weights for the simple-map pallet
+//!
+//! The `WeightInfo` trait separates the weight values from the dispatchable logic. The values in
+//! `EstimatedWeight`, one function per dispatchable, are estimates picked by hand. This Substrate
+//! version has no benchmarking framework to measure them. The `()` implementation is a fallback for
+//! tests and mock runtimes.
+
+use rstd::marker::PhantomData;
+use support::weights::Weight;
+
+/// Weight functions needed by the simple-map pallet
+pub trait WeightInfo {
+    fn set_single_entry() -> Weight;
+    fn get_single_entry() -> Weight;
+    fn take_single_entry() -> Weight;
+    fn increase_single_entry() -> Weight;
+    fn compare_and_swap_single_entry() -> Weight;
+}
+
+/// Hand-picked estimates of the simple-map pallet's weights
+pub struct EstimatedWeight<T>(PhantomData<T>);
+impl<T: system::Trait> WeightInfo for EstimatedWeight<T> {
+    // one write
+    fn set_single_entry() -> Weight {
+        10_000
+    }
+    // one existence check and one read
+    fn get_single_entry() -> Weight {
+        10_000
+    }
+    // one existence check and one take
+    fn take_single_entry() -> Weight {
+        15_000
+    }
+    // one read and one write
+    fn increase_single_entry() -> Weight {
+        15_000
+    }
+    // one read and one write
+    fn compare_and_swap_single_entry() -> Weight {
+        15_000
+    }
+}
+
+// For backwards compatibility and tests
+impl WeightInfo for () {
+    fn set_single_entry() -> Weight {
+        10_000
+    }
+    fn get_single_entry() -> Weight {
+        10_000
+    }
+    fn take_single_entry() -> Weight {
+        10_000
+    }
+    fn increase_single_entry() -> Weight {
+        10_000
+    }
+    fn compare_and_swap_single_entry() -> Weight {
+        10_000
+    }
+}
@@ -0,0 +1,474 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! Report-and-slash with deferred execution
+//!
+//! Accounts put up reserved stake. Authorized reporters may report a staker for misbehavior, but
+//! the punishment is not carried out right away: the report waits `ChallengePeriod` blocks, during
+//! which root can dismiss it. Reports that survive are executed in `on_finalize`, slashing a
+//! fraction of the offender's stake. The slashed imbalance is split: part goes to the
+//! reporter, the rest is routed to the runtime's `Slash` handler.
+//!
+//! Other pallets reserve funds of the same accounts for their own purposes, like deposits and
+//! bids. So the pallet records how much each account staked with it, and only ever unreserves or
+//! slashes up to that amount, never the account's whole reserved balance.
+
+use codec::{Decode, Encode};
+use sp_std::prelude::*;
+use sp_runtime::{traits::{Saturating, Zero}, Perbill, RuntimeDebug};
+use frame_support::{
+	decl_event, decl_module, decl_storage,
+	dispatch::DispatchResult,
+	ensure,
+	traits::{Currency, Get, Imbalance, OnUnbalanced, ReservableCurrency},
+};
+use frame_system::{self as system, ensure_root, ensure_signed};
+
+pub type ReportIndex = u32;
+
+type BalanceOf<T> = <<T as Trait>::Currency as Currency<<T as system::Trait>::AccountId>>::Balance;
+type NegativeImbalanceOf<T> =
+	<<T as Trait>::Currency as Currency<<T as system::Trait>::AccountId>>::NegativeImbalance;
+
+/// A misbehavior report awaiting execution
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct Report<AccountId, BlockNumber> {
+	pub reporter: AccountId,
+	pub offender: AccountId,
+	/// The block in which the slash is carried out, unless dismissed first
+	pub execute_at: BlockNumber,
+}
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The currency in which stake is reserved
+	type Currency: ReservableCurrency<Self::AccountId>;
+
+	/// Number of blocks a report waits before it is executed
+	type ChallengePeriod: Get<Self::BlockNumber>;
+
+	/// Fraction of the offender's reserved stake that is slashed
+	type SlashFraction: Get<Perbill>;
+
+	/// Fraction of the slashed amount paid to the reporter
+	type ReporterShare: Get<Perbill>;
+
+	/// Handler for the part of the slash that is not paid to the reporter
+	type Slash: OnUnbalanced<NegativeImbalanceOf<Self>>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as Slashing {
+		/// Accounts allowed to submit reports, sorted
+		Reporters get(fn reporters) config(): Vec<T::AccountId>;
+
+		/// Reports that have not been executed or dismissed
+		Reports get(fn reports): map ReportIndex => Option<Report<T::AccountId, T::BlockNumber>>;
+
+		/// Number of reports ever submitted; the next report's index
+		ReportCount get(fn report_count): ReportIndex;
+
+		/// Reports to execute at the end of each block
+		DueReports get(fn due_reports): map T::BlockNumber => Vec<ReportIndex>;
+
+		/// Number of open reports against each account
+		OpenReports get(fn open_reports): map T::AccountId => u32;
+
+		/// The part of each account's reserved balance that is staked with this pallet
+		Stakes get(fn stake_of): map T::AccountId => BalanceOf<T>;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as system::Trait>::AccountId,
+		Balance = BalanceOf<T>,
+		BlockNumber = <T as system::Trait>::BlockNumber,
+	{
+		/// A reporter (first) reported an offender (second); the slash happens at the given block
+		Reported(ReportIndex, AccountId, AccountId, BlockNumber),
+		/// A report was dismissed during its challenge period
+		Dismissed(ReportIndex),
+		/// An offender was slashed by the given amount
+		Slashed(ReportIndex, AccountId, Balance),
+		/// A reporter was paid from a slash
+		ReporterRewarded(ReportIndex, AccountId, Balance),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		const ChallengePeriod: T::BlockNumber = T::ChallengePeriod::get();
+
+		const SlashFraction: Perbill = T::SlashFraction::get();
+
+		const ReporterShare: Perbill = T::ReporterShare::get();
+
+		/// Authorize an account to submit reports
+		fn add_reporter(origin, who: T::AccountId) -> DispatchResult {
+			ensure_root(origin)?;
+
+			let mut reporters = Reporters::<T>::get();
+			let index = reporters.binary_search(&who).err().ok_or("already a reporter")?;
+			reporters.insert(index, who);
+			Reporters::<T>::put(reporters);
+			Ok(())
+		}
+
+		/// Stop an account from submitting reports
+		fn remove_reporter(origin, who: T::AccountId) -> DispatchResult {
+			ensure_root(origin)?;
+
+			let mut reporters = Reporters::<T>::get();
+			let index = reporters.binary_search(&who).map_err(|_| "not a reporter")?;
+			reporters.remove(index);
+			Reporters::<T>::put(reporters);
+			Ok(())
+		}
+
+		/// Reserve funds as slashable stake
+		fn stake(origin, amount: BalanceOf<T>) -> DispatchResult {
+			let staker = ensure_signed(origin)?;
+			T::Currency::reserve(&staker, amount)
+				.map_err(|_| "cannot afford to stake the requested amount")?;
+			<Stakes<T>>::mutate(&staker, |stake| *stake = stake.saturating_add(amount));
+			Ok(())
+		}
+
+		/// Release stake, as long as no report against the caller is open
+		fn unstake(origin, amount: BalanceOf<T>) -> DispatchResult {
+			let staker = ensure_signed(origin)?;
+			ensure!(Self::open_reports(&staker) == 0, "cannot unstake while reported");
+			let stake = Self::stake_of(&staker);
+			ensure!(stake >= amount, "not enough stake");
+
+			T::Currency::unreserve(&staker, amount);
+			Self::set_stake(&staker, stake - amount);
+			Ok(())
+		}
+
+		/// Report an offender. The slash is deferred by `ChallengePeriod` blocks.
+		fn report(origin, offender: T::AccountId) -> DispatchResult {
+			let reporter = ensure_signed(origin)?;
+			ensure!(Self::reporters().binary_search(&reporter).is_ok(), "not an authorized reporter");
+			ensure!(reporter != offender, "cannot report yourself");
+			ensure!(!Self::stake_of(&offender).is_zero(), "offender has no stake");
+
+			let index = ReportCount::get();
+			let execute_at = <system::Module<T>>::block_number() + T::ChallengePeriod::get();
+			let report = Report { reporter: reporter.clone(), offender: offender.clone(), execute_at };
+
+			<Reports<T>>::insert(index, report);
+			ReportCount::put(index + 1);
+			<DueReports<T>>::mutate(execute_at, |due| due.push(index));
+			<OpenReports<T>>::mutate(&offender, |open| *open += 1);
+
+			Self::deposit_event(RawEvent::Reported(index, reporter, offender, execute_at));
+			Ok(())
+		}
+
+		/// Throw out a report before it is executed
+		fn dismiss(origin, index: ReportIndex) -> DispatchResult {
+			ensure_root(origin)?;
+
+			// The index stays in `DueReports`; `on_finalize` skips reports that no longer exist.
+			let report = <Reports<T>>::take(index).ok_or("no such open report")?;
+			Self::close_report(&report.offender);
+
+			Self::deposit_event(RawEvent::Dismissed(index));
+			Ok(())
+		}
+
+		fn on_finalize(n: T::BlockNumber) {
+			for index in <DueReports<T>>::take(n) {
+				if let Some(report) = <Reports<T>>::take(index) {
+					Self::execute(index, report);
+				}
+			}
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// Slash `SlashFraction` of the offender's stake right away, with no reporter to pay.
+	///
+	/// This is meant for other pallets that prove misbehavior on-chain, so there is nothing to
+	/// challenge. The whole slash goes to the `Slash` handler.
+	pub fn slash_offender(offender: &T::AccountId) -> BalanceOf<T> {
+		let imbalance = Self::slash_stake(offender);
+		let slashed = imbalance.peek();
+		T::Slash::on_unbalanced(imbalance);
+		slashed
+	}
+
+	/// Slash `SlashFraction` of the offender's stake, and forget the slashed part of it
+	fn slash_stake(offender: &T::AccountId) -> NegativeImbalanceOf<T> {
+		let stake = Self::stake_of(offender);
+		let (imbalance, _) = T::Currency::slash_reserved(offender, T::SlashFraction::get() * stake);
+		Self::set_stake(offender, stake - imbalance.peek());
+		imbalance
+	}
+
+	fn set_stake(staker: &T::AccountId, stake: BalanceOf<T>) {
+		if stake.is_zero() {
+			<Stakes<T>>::remove(staker);
+		} else {
+			<Stakes<T>>::insert(staker, stake);
+		}
+	}
+
+	fn close_report(offender: &T::AccountId) {
+		<OpenReports<T>>::mutate(offender, |open| *open = open.saturating_sub(1));
+		if Self::open_reports(offender) == 0 {
+			<OpenReports<T>>::remove(offender);
+		}
+	}
+
+	/// Slash the offender and route the imbalance
+	fn execute(index: ReportIndex, report: Report<T::AccountId, T::BlockNumber>) {
+		Self::close_report(&report.offender);
+
+		let imbalance = Self::slash_stake(&report.offender);
+		let slashed = imbalance.peek();
+
+		let (reward, rest) = imbalance.split(T::ReporterShare::get() * slashed);
+		let paid = reward.peek();
+		T::Currency::resolve_creating(&report.reporter, reward);
+		T::Slash::on_unbalanced(rest);
+
+		Self::deposit_event(RawEvent::Slashed(index, report.offender, slashed));
+		Self::deposit_event(RawEvent::ReporterRewarded(index, report.reporter, paid));
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use sp_core::H256;
+	use frame_support::{
+		assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types
+	};
+	use frame_system::RawOrigin;
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup, OnFinalize},
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const ExistentialDeposit: u64 = 0;
+		pub const TransferFee: u64 = 0;
+		pub const CreationFee: u64 = 0;
+
+		pub const ChallengePeriod: u64 = 5;
+		pub const SlashFraction: Perbill = Perbill::from_percent(50);
+		pub const ReporterShare: Perbill = Perbill::from_percent(10);
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	impl balances::Trait for TestRuntime {
+		type Balance = u64;
+		type OnFreeBalanceZero = ();
+		type OnNewAccount = ();
+		type Event = TestEvent;
+		type TransferPayment = ();
+		type DustRemoval = ();
+		type ExistentialDeposit = ExistentialDeposit;
+		type TransferFee = TransferFee;
+		type CreationFee = CreationFee;
+	}
+
+	mod slashing {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			balances<T>,
+			slashing<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type Currency = balances::Module<Self>;
+		type ChallengePeriod = ChallengePeriod;
+		type SlashFraction = SlashFraction;
+		type ReporterShare = ReporterShare;
+		// Dropping the imbalance burns it
+		type Slash = ();
+	}
+
+	pub type System = system::Module<TestRuntime>;
+	pub type Balances = balances::Module<TestRuntime>;
+	pub type Slashing = Module<TestRuntime>;
+
+	/// Account 1 is an offender with 100 staked. Account 2 is an authorized reporter.
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		let mut t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		balances::GenesisConfig::<TestRuntime> {
+			balances: vec![(1, 200), (2, 100), (3, 100)],
+			vesting: vec![],
+		}
+		.assimilate_storage(&mut t)
+		.unwrap();
+		GenesisConfig::<TestRuntime> {
+			reporters: vec![2],
+		}
+		.assimilate_storage(&mut t)
+		.unwrap();
+		let mut ext: sp_io::TestExternalities = t.into();
+		ext.execute_with(|| {
+			System::set_block_number(1);
+			assert_ok!(Slashing::stake(Origin::signed(1), 100));
+		});
+		ext
+	}
+
+	/// Finalize every block after the current one, up to and including `n`
+	fn run_to_block(n: u64) {
+		while System::block_number() < n {
+			System::set_block_number(System::block_number() + 1);
+			Slashing::on_finalize(System::block_number());
+		}
+	}
+
+	fn event_emitted(event: RawEvent<u64, u64, u64>) -> bool {
+		let expected = TestEvent::slashing(event);
+		System::events().iter().any(|a| a.event == expected)
+	}
+
+	#[test]
+	fn only_reporters_report() {
+		new_test_ext().execute_with(|| {
+			assert_err!(
+				Slashing::report(Origin::signed(3), 1),
+				"not an authorized reporter"
+			);
+			assert_err!(Slashing::report(Origin::signed(2), 3), "offender has no stake");
+
+			assert_ok!(Slashing::add_reporter(RawOrigin::Root.into(), 3));
+			assert_ok!(Slashing::report(Origin::signed(3), 1));
+			assert!(event_emitted(RawEvent::Reported(0, 3, 1, 6)));
+		})
+	}
+
+	#[test]
+	fn slash_waits_for_challenge_period() {
+		new_test_ext().execute_with(|| {
+			let issuance = Balances::total_issuance();
+			assert_ok!(Slashing::report(Origin::signed(2), 1));
+
+			run_to_block(5);
+			assert_eq!(Balances::reserved_balance(&1), 100);
+
+			run_to_block(6);
+			// Half the stake is slashed, the reporter gets a tenth of that, the rest is burned
+			assert_eq!(Balances::reserved_balance(&1), 50);
+			assert_eq!(Balances::free_balance(&2), 105);
+			assert_eq!(Balances::total_issuance(), issuance - 45);
+			assert_eq!(Slashing::reports(0), None);
+			assert!(event_emitted(RawEvent::Slashed(0, 1, 50)));
+			assert!(event_emitted(RawEvent::ReporterRewarded(0, 2, 5)));
+		})
+	}
+
+	#[test]
+	fn dismissed_report_is_not_executed() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(Slashing::report(Origin::signed(2), 1));
+			assert!(Slashing::dismiss(Origin::signed(2), 0).is_err());
+			assert_ok!(Slashing::dismiss(RawOrigin::Root.into(), 0));
+			assert!(event_emitted(RawEvent::Dismissed(0)));
+
+			run_to_block(10);
+			assert_eq!(Balances::reserved_balance(&1), 100);
+			assert_eq!(Balances::free_balance(&2), 100);
+			assert_err!(
+				Slashing::dismiss(RawOrigin::Root.into(), 0),
+				"no such open report"
+			);
+		})
+	}
+
+	#[test]
+	fn reported_stake_cannot_escape() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(Slashing::report(Origin::signed(2), 1));
+			assert_err!(
+				Slashing::unstake(Origin::signed(1), 100),
+				"cannot unstake while reported"
+			);
+
+			run_to_block(6);
+			assert_err!(Slashing::unstake(Origin::signed(1), 51), "not enough stake");
+			assert_ok!(Slashing::unstake(Origin::signed(1), 50));
+			assert_eq!(Balances::free_balance(&1), 150);
+			assert_eq!(Slashing::stake_of(&1), 0);
+		})
+	}
+
+	#[test]
+	fn other_reserves_are_left_alone() {
+		new_test_ext().execute_with(|| {
+			// Account 3 has funds reserved by some other pallet, but nothing staked
+			assert_ok!(<Balances as ReservableCurrency<u64>>::reserve(&3, 60));
+			assert_err!(Slashing::report(Origin::signed(2), 3), "offender has no stake");
+			assert_err!(Slashing::unstake(Origin::signed(3), 60), "not enough stake");
+			assert_eq!(Slashing::slash_offender(&3), 0);
+			assert_eq!(Balances::reserved_balance(&3), 60);
+
+			// Only the staked part of account 1's reserve is slashed
+			assert_ok!(<Balances as ReservableCurrency<u64>>::reserve(&1, 100));
+			assert_eq!(Slashing::slash_offender(&1), 50);
+			assert_eq!(Balances::reserved_balance(&1), 150);
+			assert_eq!(Slashing::stake_of(&1), 50);
+			assert_err!(Slashing::unstake(Origin::signed(1), 100), "not enough stake");
+		})
+	}
+
+	#[test]
+	fn slash_offender_skips_the_challenge() {
+		new_test_ext().execute_with(|| {
+			let issuance = Balances::total_issuance();
+			assert_eq!(Slashing::slash_offender(&1), 50);
+			assert_eq!(Balances::reserved_balance(&1), 50);
+			assert_eq!(Balances::total_issuance(), issuance - 50);
+		})
+	}
+}
@@ -0,0 +1,631 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! A minimal staking pallet
+//!
+//! Accounts bond funds (held in place with a lock), then either offer to validate or nominate a
+//! single validator. At the end of every era a fixed reward is minted and split among everyone
+//! backing a validator in proportion to their bonded stake. Unbonded funds stay locked for
+//! `BondingDuration` eras before they can be withdrawn.
+//!
+//! This is far simpler than `pallet-staking`: there is no election and no slashing. It is meant to
+//! show the mechanics, not to secure a chain. Every staker is paid in the block that ends the era,
+//! so the number of validators and nominators is capped by `MaxValidators` and `MaxNominators` to
+//! keep that block's work bounded. Each staker may have at most `MaxUnlockingChunks` chunks
+//! unbonding at once.
+
+use codec::{Decode, Encode};
+use sp_std::prelude::*;
+use sp_runtime::{
+	traits::{Bounded, Saturating, Zero},
+	Perbill, RuntimeDebug,
+};
+use frame_support::{
+	decl_event, decl_module, decl_storage,
+	dispatch::DispatchResult,
+	ensure,
+	traits::{Currency, Get, LockIdentifier, LockableCurrency, WithdrawReasons},
+};
+use frame_system::{self as system, ensure_signed};
+
+const STAKING_ID: LockIdentifier = *b"stkelite";
+
+pub type EraIndex = u32;
+
+type BalanceOf<T> = <<T as Trait>::Currency as Currency<<T as system::Trait>::AccountId>>::Balance;
+
+/// Funds that have been unbonded and become withdrawable in `era`
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct UnlockChunk<Balance> {
+	pub value: Balance,
+	pub era: EraIndex,
+}
+
+/// Everything an account has bonded
+#[derive(Encode, Decode, Default, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct StakingLedger<Balance> {
+	/// Funds that count as stake and earn rewards
+	pub active: Balance,
+	/// Funds on their way out, still locked
+	pub unlocking: Vec<UnlockChunk<Balance>>,
+}
+
+impl<Balance: Copy + Saturating + Zero> StakingLedger<Balance> {
+	/// Active and unlocking funds together; the amount that must stay locked
+	pub fn total(&self) -> Balance {
+		self.unlocking
+			.iter()
+			.fold(self.active, |total, chunk| total.saturating_add(chunk.value))
+	}
+}
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The currency that is bonded and paid out as rewards
+	type Currency: LockableCurrency<Self::AccountId, Moment = Self::BlockNumber>;
+
+	/// Number of blocks in an era
+	type EraLength: Get<Self::BlockNumber>;
+
+	/// Number of eras that unbonded funds stay locked
+	type BondingDuration: Get<EraIndex>;
+
+	/// Amount minted and shared among stakers at the end of each era
+	type RewardPerEra: Get<BalanceOf<Self>>;
+
+	/// The most chunks a staker may have unbonding at once
+	type MaxUnlockingChunks: Get<u32>;
+
+	/// The most accounts that may validate at once
+	type MaxValidators: Get<u32>;
+
+	/// The most accounts that may nominate at once
+	type MaxNominators: Get<u32>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as StakingLite {
+		/// Bonded funds of each staker
+		Ledger get(fn ledger): map T::AccountId => Option<StakingLedger<BalanceOf<T>>>;
+
+		/// Accounts offering to validate, sorted
+		Validators get(fn validators): Vec<T::AccountId>;
+
+		/// The validator each nominator backs
+		Nominators get(fn nominators): linked_map T::AccountId => Option<T::AccountId>;
+
+		/// The number of entries in `Nominators`
+		NominatorCount get(fn nominator_count): u32;
+
+		/// The current era
+		CurrentEra get(fn current_era): EraIndex;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as system::Trait>::AccountId,
+		Balance = BalanceOf<T>,
+	{
+		/// Funds were bonded
+		Bonded(AccountId, Balance),
+		/// Funds were unbonded and will be withdrawable in the given era
+		Unbonded(AccountId, Balance, EraIndex),
+		/// Unbonded funds were unlocked
+		Withdrawn(AccountId, Balance),
+		/// An account offered to validate
+		Validating(AccountId),
+		/// A nominator (first) backs a validator (second)
+		Nominated(AccountId, AccountId),
+		/// An account stopped validating or nominating
+		Chilled(AccountId),
+		/// A staker was paid an era reward
+		Rewarded(AccountId, Balance),
+		/// An era ended and the next one started
+		NewEra(EraIndex),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		const EraLength: T::BlockNumber = T::EraLength::get();
+
+		const BondingDuration: EraIndex = T::BondingDuration::get();
+
+		const RewardPerEra: BalanceOf<T> = T::RewardPerEra::get();
+
+		const MaxUnlockingChunks: u32 = T::MaxUnlockingChunks::get();
+
+		const MaxValidators: u32 = T::MaxValidators::get();
+
+		const MaxNominators: u32 = T::MaxNominators::get();
+
+		/// Bond some (more) funds
+		fn bond(origin, amount: BalanceOf<T>) -> DispatchResult {
+			let staker = ensure_signed(origin)?;
+			ensure!(!amount.is_zero(), "cannot bond nothing");
+
+			let mut ledger = Self::ledger(&staker).unwrap_or_default();
+			let new_total = ledger.total().saturating_add(amount);
+			ensure!(T::Currency::free_balance(&staker) >= new_total, "not enough free balance to bond");
+
+			ledger.active = ledger.active.saturating_add(amount);
+			Self::update_ledger(&staker, &ledger);
+
+			Self::deposit_event(RawEvent::Bonded(staker, amount));
+			Ok(())
+		}
+
+		/// Offer to validate with the bonded funds
+		fn validate(origin) -> DispatchResult {
+			let staker = ensure_signed(origin)?;
+			Self::ensure_active(&staker)?;
+
+			let mut validators = Validators::<T>::get();
+			if let Err(index) = validators.binary_search(&staker) {
+				ensure!(validators.len() < T::MaxValidators::get() as usize, "too many validators");
+				validators.insert(index, staker.clone());
+				Validators::<T>::put(validators);
+			}
+			Self::remove_nominator(&staker);
+
+			Self::deposit_event(RawEvent::Validating(staker));
+			Ok(())
+		}
+
+		/// Back a single validator with the bonded funds
+		fn nominate(origin, target: T::AccountId) -> DispatchResult {
+			let staker = ensure_signed(origin)?;
+			Self::ensure_active(&staker)?;
+			ensure!(staker != target, "cannot nominate yourself");
+			ensure!(Self::validators().binary_search(&target).is_ok(), "target is not a validator");
+
+			if !<Nominators<T>>::exists(&staker) {
+				let count = Self::nominator_count();
+				ensure!(count < T::MaxNominators::get(), "too many nominators");
+				NominatorCount::put(count + 1);
+			}
+			Self::remove_validator(&staker);
+			<Nominators<T>>::insert(&staker, &target);
+
+			Self::deposit_event(RawEvent::Nominated(staker, target));
+			Ok(())
+		}
+
+		/// Stop validating or nominating. The funds stay bonded.
+		fn chill(origin) -> DispatchResult {
+			let staker = ensure_signed(origin)?;
+			Self::chill_staker(&staker);
+
+			Self::deposit_event(RawEvent::Chilled(staker));
+			Ok(())
+		}
+
+		/// Start unbonding some funds. They stay locked for `BondingDuration` eras.
+		fn unbond(origin, amount: BalanceOf<T>) -> DispatchResult {
+			let staker = ensure_signed(origin)?;
+			let mut ledger = Self::ledger(&staker).ok_or("not bonded")?;
+			ensure!(!amount.is_zero(), "cannot unbond nothing");
+			ensure!(amount <= ledger.active, "cannot unbond more than is bonded");
+			ensure!(
+				ledger.unlocking.len() < T::MaxUnlockingChunks::get() as usize,
+				"too many unlocking chunks, withdraw first"
+			);
+
+			let era = Self::current_era() + T::BondingDuration::get();
+			ledger.active -= amount;
+			ledger.unlocking.push(UnlockChunk { value: amount, era });
+			Self::update_ledger(&staker, &ledger);
+
+			// Nothing left at stake, so stop backing anyone
+			if ledger.active.is_zero() {
+				Self::chill_staker(&staker);
+			}
+
+			Self::deposit_event(RawEvent::Unbonded(staker, amount, era));
+			Ok(())
+		}
+
+		/// Unlock every unbonded chunk whose delay has passed
+		fn withdraw_unbonded(origin) -> DispatchResult {
+			let staker = ensure_signed(origin)?;
+			let mut ledger = Self::ledger(&staker).ok_or("not bonded")?;
+			let current_era = Self::current_era();
+
+			let before = ledger.total();
+			ledger.unlocking.retain(|chunk| chunk.era > current_era);
+			let withdrawn = before - ledger.total();
+			ensure!(!withdrawn.is_zero(), "nothing to withdraw yet");
+
+			if ledger.total().is_zero() {
+				<Ledger<T>>::remove(&staker);
+				T::Currency::remove_lock(STAKING_ID, &staker);
+			} else {
+				Self::update_ledger(&staker, &ledger);
+			}
+
+			Self::deposit_event(RawEvent::Withdrawn(staker, withdrawn));
+			Ok(())
+		}
+
+		fn on_finalize(n: T::BlockNumber) {
+			if (n % T::EraLength::get()).is_zero() {
+				Self::end_era();
+			}
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// Store the ledger and lock its total
+	fn update_ledger(staker: &T::AccountId, ledger: &StakingLedger<BalanceOf<T>>) {
+		T::Currency::set_lock(
+			STAKING_ID,
+			staker,
+			ledger.total(),
+			T::BlockNumber::max_value(),
+			WithdrawReasons::all(),
+		);
+		<Ledger<T>>::insert(staker, ledger);
+	}
+
+	fn ensure_active(staker: &T::AccountId) -> DispatchResult {
+		let ledger = Self::ledger(staker).ok_or("not bonded")?;
+		ensure!(!ledger.active.is_zero(), "no active stake");
+		Ok(())
+	}
+
+	fn remove_validator(staker: &T::AccountId) {
+		let mut validators = Validators::<T>::get();
+		if let Ok(index) = validators.binary_search(staker) {
+			validators.remove(index);
+			Validators::<T>::put(validators);
+		}
+	}
+
+	fn remove_nominator(staker: &T::AccountId) {
+		if <Nominators<T>>::exists(staker) {
+			<Nominators<T>>::remove(staker);
+			NominatorCount::mutate(|count| *count = count.saturating_sub(1));
+		}
+	}
+
+	fn chill_staker(staker: &T::AccountId) {
+		Self::remove_validator(staker);
+		Self::remove_nominator(staker);
+	}
+
+	/// Every account that backs a validator, with its active stake
+	///
+	/// Nominations whose validator has since stopped validating earn nothing.
+	pub fn stakers() -> Vec<(T::AccountId, BalanceOf<T>)> {
+		let validators = Self::validators();
+		let active = |who: &T::AccountId| Self::ledger(who).map(|l| l.active).unwrap_or_default();
+
+		let mut stakers = validators
+			.iter()
+			.map(|v| (v.clone(), active(v)))
+			.collect::<Vec<_>>();
+		stakers.extend(
+			<Nominators<T>>::enumerate()
+				.filter(|(_, target)| validators.binary_search(target).is_ok())
+				.map(|(nominator, _)| {
+					let stake = active(&nominator);
+					(nominator, stake)
+				}),
+		);
+		stakers.retain(|(_, stake)| !stake.is_zero());
+		stakers
+	}
+
+	/// Mint the era reward, share it out by stake, and move to the next era
+	fn end_era() {
+		let stakers = Self::stakers();
+		let total_stake = stakers
+			.iter()
+			.fold(BalanceOf::<T>::zero(), |total, (_, stake)| total.saturating_add(*stake));
+
+		if !total_stake.is_zero() {
+			let reward = T::RewardPerEra::get();
+			for (staker, stake) in stakers {
+				let share = Perbill::from_rational_approximation(stake, total_stake) * reward;
+				// Dropping the positive imbalance increases total issuance
+				let _ = T::Currency::deposit_creating(&staker, share);
+				Self::deposit_event(RawEvent::Rewarded(staker, share));
+			}
+		}
+
+		let next_era = Self::current_era() + 1;
+		CurrentEra::put(next_era);
+		Self::deposit_event(RawEvent::NewEra(next_era));
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use sp_core::H256;
+	use frame_support::{
+		assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types
+	};
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup, OnFinalize},
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const ExistentialDeposit: u64 = 0;
+		pub const TransferFee: u64 = 0;
+		pub const CreationFee: u64 = 0;
+
+		pub const EraLength: u64 = 10;
+		pub const BondingDuration: EraIndex = 2;
+		pub const RewardPerEra: u64 = 100;
+		pub const MaxUnlockingChunks: u32 = 2;
+		pub const MaxValidators: u32 = 1;
+		pub const MaxNominators: u32 = 1;
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	impl balances::Trait for TestRuntime {
+		type Balance = u64;
+		type OnFreeBalanceZero = ();
+		type OnNewAccount = ();
+		type Event = TestEvent;
+		type TransferPayment = ();
+		type DustRemoval = ();
+		type ExistentialDeposit = ExistentialDeposit;
+		type TransferFee = TransferFee;
+		type CreationFee = CreationFee;
+	}
+
+	mod staking_lite {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			balances<T>,
+			staking_lite<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type Currency = balances::Module<Self>;
+		type EraLength = EraLength;
+		type BondingDuration = BondingDuration;
+		type RewardPerEra = RewardPerEra;
+		type MaxUnlockingChunks = MaxUnlockingChunks;
+		type MaxValidators = MaxValidators;
+		type MaxNominators = MaxNominators;
+	}
+
+	pub type System = system::Module<TestRuntime>;
+	pub type Balances = balances::Module<TestRuntime>;
+	pub type StakingLite = Module<TestRuntime>;
+
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		let mut t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		balances::GenesisConfig::<TestRuntime> {
+			balances: vec![(1, 100), (2, 100), (3, 100)],
+			vesting: vec![],
+		}
+		.assimilate_storage(&mut t)
+		.unwrap();
+		let mut ext: sp_io::TestExternalities = t.into();
+		ext.execute_with(|| System::set_block_number(1));
+		ext
+	}
+
+	/// Finalize every block after the current one, up to and including `n`
+	fn run_to_block(n: u64) {
+		while System::block_number() < n {
+			System::set_block_number(System::block_number() + 1);
+			StakingLite::on_finalize(System::block_number());
+		}
+	}
+
+	fn event_emitted(event: RawEvent<u64, u64>) -> bool {
+		let expected = TestEvent::staking_lite(event);
+		System::events().iter().any(|a| a.event == expected)
+	}
+
+	#[test]
+	fn bonding_locks_funds() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(StakingLite::bond(Origin::signed(1), 60));
+			assert_eq!(StakingLite::ledger(1).unwrap().active, 60);
+			assert!(event_emitted(RawEvent::Bonded(1, 60)));
+
+			// Only the unbonded 40 can move
+			assert!(Balances::transfer(Origin::signed(1), 2, 50).is_err());
+			assert_ok!(Balances::transfer(Origin::signed(1), 2, 40));
+		})
+	}
+
+	#[test]
+	fn cannot_bond_more_than_free_balance() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(StakingLite::bond(Origin::signed(1), 60));
+			assert_err!(
+				StakingLite::bond(Origin::signed(1), 50),
+				"not enough free balance to bond"
+			);
+		})
+	}
+
+	#[test]
+	fn nominations_need_a_validator() {
+		new_test_ext().execute_with(|| {
+			assert_err!(StakingLite::nominate(Origin::signed(2), 1), "not bonded");
+			assert_ok!(StakingLite::bond(Origin::signed(2), 10));
+			assert_err!(
+				StakingLite::nominate(Origin::signed(2), 1),
+				"target is not a validator"
+			);
+
+			assert_ok!(StakingLite::bond(Origin::signed(1), 10));
+			assert_ok!(StakingLite::validate(Origin::signed(1)));
+			assert_ok!(StakingLite::nominate(Origin::signed(2), 1));
+			assert_eq!(StakingLite::nominators(2), Some(1));
+			assert!(event_emitted(RawEvent::Nominated(2, 1)));
+		})
+	}
+
+	#[test]
+	fn rewards_are_proportional_to_stake() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(StakingLite::bond(Origin::signed(1), 30));
+			assert_ok!(StakingLite::validate(Origin::signed(1)));
+			assert_ok!(StakingLite::bond(Origin::signed(2), 10));
+			assert_ok!(StakingLite::nominate(Origin::signed(2), 1));
+			// Bonded but idle, so no reward
+			assert_ok!(StakingLite::bond(Origin::signed(3), 50));
+
+			run_to_block(9);
+			assert_eq!(StakingLite::current_era(), 0);
+
+			run_to_block(10);
+			assert_eq!(StakingLite::current_era(), 1);
+			assert_eq!(Balances::free_balance(&1), 175);
+			assert_eq!(Balances::free_balance(&2), 125);
+			assert_eq!(Balances::free_balance(&3), 100);
+			assert!(event_emitted(RawEvent::Rewarded(1, 75)));
+			assert!(event_emitted(RawEvent::NewEra(1)));
+		})
+	}
+
+	#[test]
+	fn chilled_validator_stops_earning() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(StakingLite::bond(Origin::signed(1), 30));
+			assert_ok!(StakingLite::validate(Origin::signed(1)));
+			assert_ok!(StakingLite::bond(Origin::signed(2), 10));
+			assert_ok!(StakingLite::nominate(Origin::signed(2), 1));
+
+			assert_ok!(StakingLite::chill(Origin::signed(1)));
+			run_to_block(10);
+			assert_eq!(Balances::free_balance(&1), 100);
+			assert_eq!(Balances::free_balance(&2), 100);
+		})
+	}
+
+	#[test]
+	fn unbonding_waits_for_bonding_duration() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(StakingLite::bond(Origin::signed(1), 60));
+			assert_ok!(StakingLite::unbond(Origin::signed(1), 20));
+			assert!(event_emitted(RawEvent::Unbonded(1, 20, 2)));
+			assert_eq!(
+				StakingLite::ledger(1),
+				Some(StakingLedger { active: 40, unlocking: vec![UnlockChunk { value: 20, era: 2 }] })
+			);
+
+			// Still locked during era 1
+			run_to_block(10);
+			assert_err!(
+				StakingLite::withdraw_unbonded(Origin::signed(1)),
+				"nothing to withdraw yet"
+			);
+			assert!(Balances::transfer(Origin::signed(1), 2, 50).is_err());
+
+			// Unlocked once era 2 begins
+			run_to_block(20);
+			assert_ok!(StakingLite::withdraw_unbonded(Origin::signed(1)));
+			assert!(event_emitted(RawEvent::Withdrawn(1, 20)));
+			assert_ok!(Balances::transfer(Origin::signed(1), 2, 60));
+		})
+	}
+
+	#[test]
+	fn withdrawing_everything_clears_the_ledger() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(StakingLite::bond(Origin::signed(1), 60));
+			assert_ok!(StakingLite::validate(Origin::signed(1)));
+			assert_ok!(StakingLite::unbond(Origin::signed(1), 60));
+			assert!(StakingLite::validators().is_empty());
+
+			run_to_block(20);
+			assert_ok!(StakingLite::withdraw_unbonded(Origin::signed(1)));
+			assert_eq!(StakingLite::ledger(1), None);
+			assert_ok!(Balances::transfer(Origin::signed(1), 2, 100));
+		})
+	}
+
+	#[test]
+	fn unlocking_chunks_are_capped() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(StakingLite::bond(Origin::signed(1), 60));
+			assert_ok!(StakingLite::unbond(Origin::signed(1), 10));
+			assert_ok!(StakingLite::unbond(Origin::signed(1), 10));
+			assert_err!(
+				StakingLite::unbond(Origin::signed(1), 10),
+				"too many unlocking chunks, withdraw first"
+			);
+
+			run_to_block(20);
+			assert_ok!(StakingLite::withdraw_unbonded(Origin::signed(1)));
+			assert_ok!(StakingLite::unbond(Origin::signed(1), 10));
+		})
+	}
+
+	#[test]
+	fn validators_and_nominators_are_capped() {
+		new_test_ext().execute_with(|| {
+			for staker in 1..=3 {
+				assert_ok!(StakingLite::bond(Origin::signed(staker), 10));
+			}
+			assert_ok!(StakingLite::validate(Origin::signed(1)));
+			assert_err!(StakingLite::validate(Origin::signed(2)), "too many validators");
+			// Validating again is not a new entry
+			assert_ok!(StakingLite::validate(Origin::signed(1)));
+
+			assert_ok!(StakingLite::nominate(Origin::signed(2), 1));
+			assert_err!(StakingLite::nominate(Origin::signed(3), 1), "too many nominators");
+			assert_ok!(StakingLite::nominate(Origin::signed(2), 1));
+			assert_eq!(StakingLite::nominator_count(), 1);
+
+			// Chilling frees the place
+			assert_ok!(StakingLite::chill(Origin::signed(2)));
+			assert_eq!(StakingLite::nominator_count(), 0);
+			assert_ok!(StakingLite::nominate(Origin::signed(3), 1));
+		})
+	}
+}
@@ -3,8 +3,11 @@
 // storage cache example
 // takeaway: minimize calls to runtime storage
 use rstd::prelude::*;
-use support::{decl_event, decl_module, decl_storage, dispatch::DispatchResult, ensure, StorageValue};
-use system::ensure_signed;
+use support::{
+    decl_event, decl_module, decl_storage, dispatch::DispatchResult, ensure, StorageLinkedMap,
+    StorageValue,
+};
+use system::{ensure_root, ensure_signed};
 
 pub trait Trait: system::Trait {
     type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
@@ -18,6 +21,11 @@ decl_storage! {
         // clone type
         KingMember get(fn king_member): T::AccountId;
         GroupMembers get(fn group_members): Vec<T::AccountId>;
+
+        // frequently read aggregate of a map
+        Scores get(fn score): linked_map T::AccountId => u32;
+        // the sum of all `Scores`, updated on every change to them
+        ScoreTotal get(fn score_total): u64;
     }
 }
 
@@ -35,6 +43,10 @@ decl_event!(
         InefficientKingSwap(AccountId, AccountId),
         // '' (old, new)
         BetterKingSwap(AccountId, AccountId),
+        // account set its score (account, new_score, new_total)
+        ScoreSet(AccountId, u32, u64),
+        // recomputed the total of all scores (old_total, new_total)
+        TotalRepaired(u64, u64),
     }
 );
 
@@ -121,6 +133,35 @@ decl_module! {
             Ok(())
         }
 
+        /// Set the caller's score, keeping the total of all scores up to date
+        ///
+        /// Reading the total is then a single storage read, rather than an iteration over the
+        /// whole map. The price is an extra read and write on every change to the map.
+        fn set_score(origin, score: u32) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+            let old_score = <Scores<T>>::get(&who);
+            // cannot overflow: the total is at most `u32::max_value()` times the number of accounts
+            let new_total = Self::score_total().saturating_sub(u64::from(old_score)) + u64::from(score);
+
+            <Scores<T>>::insert(&who, score);
+            <ScoreTotal>::put(new_total);
+            Self::deposit_event(RawEvent::ScoreSet(who, score, new_total));
+            Ok(())
+        }
+
+        /// Recompute the total from the map, in case it ever got out of sync
+        ///
+        /// This iterates over every score, so it is restricted to root
+        fn repair_total(origin) -> DispatchResult {
+            ensure_root(origin)?;
+            let old_total = Self::score_total();
+            let new_total = Self::compute_total();
+
+            <ScoreTotal>::put(new_total);
+            Self::deposit_event(RawEvent::TotalRepaired(old_total, new_total));
+            Ok(())
+        }
+
         // ---- for testing purposes ----
         fn set_copy(origin, val: u32) -> DispatchResult {
             let _ = ensure_signed(origin)?;
@@ -147,12 +188,17 @@ impl<T: Trait> Module<T> {
     pub fn is_member(who: &T::AccountId) -> bool {
         <GroupMembers<T>>::get().contains(who)
     }
+
+    /// The sum of all scores, the slow way
+    pub fn compute_total() -> u64 {
+        <Scores<T>>::enumerate().map(|(_, score)| u64::from(score)).sum()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::RawEvent;
-    use crate::{Module, Trait};
+    use crate::{Module, ScoreTotal, Trait};
     use primitives::H256;
     use runtime_io;
     use runtime_primitives::{
@@ -160,8 +206,8 @@ mod tests {
         traits::{BlakeTwo256, IdentityLookup},
         Perbill,
     };
-    use support::{assert_ok, assert_err, impl_outer_event, impl_outer_origin, parameter_types};
-    use system;
+    use support::{assert_ok, assert_err, impl_outer_event, impl_outer_origin, parameter_types, StorageValue};
+    use system::{self, RawOrigin};
 
     impl_outer_origin! {
         pub enum Origin for TestRuntime {}
@@ -343,4 +389,51 @@ mod tests {
             assert_eq!(StorageCache::king_member(), 3);
         })
     }
+
+    #[test]
+    fn total_follows_scores() {
+        ExtBuilder::build().execute_with(|| {
+            assert_ok!(StorageCache::set_score(Origin::signed(1), 10));
+            assert_ok!(StorageCache::set_score(Origin::signed(2), 5));
+            assert_eq!(StorageCache::score_total(), 15);
+
+            // overwriting replaces the old score in the total
+            assert_ok!(StorageCache::set_score(Origin::signed(1), 3));
+            assert_eq!(StorageCache::score_total(), 8);
+            let expected_event = TestEvent::storage_cache(RawEvent::ScoreSet(1, 3, 8));
+            assert!(System::events().iter().any(|a| a.event == expected_event));
+
+            assert_ok!(StorageCache::set_score(Origin::signed(2), 0));
+            assert_eq!(StorageCache::score_total(), 3);
+        })
+    }
+
+    #[test]
+    fn total_stays_consistent() {
+        ExtBuilder::build().execute_with(|| {
+            // many updates from a handful of accounts, with large scores
+            for round in 0..100u32 {
+                let who = u64::from(round % 7);
+                let score = round.wrapping_mul(2_654_435_761);
+                assert_ok!(StorageCache::set_score(Origin::signed(who), score));
+                assert_eq!(StorageCache::score_total(), StorageCache::compute_total());
+            }
+        })
+    }
+
+    #[test]
+    fn repair_restores_total() {
+        ExtBuilder::build().execute_with(|| {
+            assert_ok!(StorageCache::set_score(Origin::signed(1), 10));
+            assert_ok!(StorageCache::set_score(Origin::signed(2), 5));
+            // simulate a bug that corrupted the cached total
+            <ScoreTotal>::put(99);
+
+            assert!(StorageCache::repair_total(Origin::signed(1)).is_err());
+            assert_ok!(StorageCache::repair_total(RawOrigin::Root.into()));
+            assert_eq!(StorageCache::score_total(), 15);
+            let expected_event = TestEvent::storage_cache(RawEvent::TotalRepaired(99, 15));
+            assert!(System::events().iter().any(|a| a.event == expected_event));
+        })
+    }
 }
@@ -0,0 +1,332 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! Three ways to count the items of a collection in storage, from most to least expensive
+//!
+//! * `count_by_decoding` decodes the whole `Vec` of members and takes its length.
+//! * `count_by_decode_len` uses `StorageValue::decode_len`, which only decodes the length prefix
+//!   of the encoded `Vec`. The runtime still has to read the entire value, and a storage proof
+//!   still contains all of it.
+//! * `count_by_counter` reads a counter that is kept next to a map of scores and updated on every
+//!   insertion and removal. It costs the same however large the map grows. This is the idea
+//!   behind counted storage maps.
+//!
+//! Each counting call emits the count it found.
+
+use codec::{Decode, Encode};
+use sp_std::prelude::*;
+use sp_runtime::RuntimeDebug;
+use frame_support::{
+	decl_event, decl_module, decl_storage,
+	dispatch::DispatchResult,
+	ensure,
+	traits::Get,
+	weights::SimpleDispatchInfo,
+};
+use frame_system::{self as system, ensure_signed};
+
+pub mod weights;
+pub use weights::WeightInfo;
+
+/// How a count was found
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, RuntimeDebug)]
+pub enum CountMethod {
+	Decode,
+	DecodeLen,
+	Counter,
+}
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The most members the member list can hold
+	type MaxMembers: Get<u32>;
+
+	/// Weight information for the dispatchables in this pallet
+	type WeightInfo: WeightInfo;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as StorageLength {
+		/// Members as a single encoded `Vec`
+		Members get(fn members): Vec<T::AccountId>;
+
+		/// A score per account
+		Scores get(fn score): map T::AccountId => Option<u32>;
+
+		/// The number of entries in `Scores`
+		ScoreCount get(fn score_count): u32;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as system::Trait>::AccountId,
+	{
+		/// An account joined the member list
+		Joined(AccountId),
+		/// An account set its score
+		ScoreSet(AccountId, u32),
+		/// An account removed its score
+		ScoreRemoved(AccountId),
+		/// A collection was counted with the given method
+		Counted(CountMethod, u32),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		const MaxMembers: u32 = T::MaxMembers::get();
+
+		#[weight = SimpleDispatchInfo::FixedNormal(T::WeightInfo::join(T::MaxMembers::get()))]
+		fn join(origin) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let members = Self::members();
+			ensure!(!members.contains(&who), "already a member");
+			ensure!((members.len() as u32) < T::MaxMembers::get(), "too many members");
+			<Members<T>>::append(&mut vec![who.clone()])?;
+
+			Self::deposit_event(RawEvent::Joined(who));
+			Ok(())
+		}
+
+		#[weight = SimpleDispatchInfo::FixedNormal(T::WeightInfo::set_score())]
+		fn set_score(origin, score: u32) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			// Only new entries change the count
+			if !<Scores<T>>::exists(&who) {
+				ScoreCount::mutate(|count| *count += 1);
+			}
+			<Scores<T>>::insert(&who, score);
+
+			Self::deposit_event(RawEvent::ScoreSet(who, score));
+			Ok(())
+		}
+
+		#[weight = SimpleDispatchInfo::FixedNormal(T::WeightInfo::remove_score())]
+		fn remove_score(origin) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			ensure!(<Scores<T>>::exists(&who), "no score to remove");
+			<Scores<T>>::remove(&who);
+			ScoreCount::mutate(|count| *count -= 1);
+
+			Self::deposit_event(RawEvent::ScoreRemoved(who));
+			Ok(())
+		}
+
+		/// Count the members by decoding all of them
+		#[weight = SimpleDispatchInfo::FixedNormal(
+			T::WeightInfo::count_by_decoding(T::MaxMembers::get())
+		)]
+		fn count_by_decoding(origin) -> DispatchResult {
+			ensure_signed(origin)?;
+			let count = Self::members().len() as u32;
+
+			Self::deposit_event(RawEvent::Counted(CountMethod::Decode, count));
+			Ok(())
+		}
+
+		/// Count the members by decoding only the length of the list
+		#[weight = SimpleDispatchInfo::FixedNormal(
+			T::WeightInfo::count_by_decode_len(T::MaxMembers::get())
+		)]
+		fn count_by_decode_len(origin) -> DispatchResult {
+			ensure_signed(origin)?;
+			let count = <Members<T>>::decode_len().unwrap_or(0) as u32;
+
+			Self::deposit_event(RawEvent::Counted(CountMethod::DecodeLen, count));
+			Ok(())
+		}
+
+		/// Count the scores by reading their counter
+		#[weight = SimpleDispatchInfo::FixedNormal(T::WeightInfo::count_by_counter())]
+		fn count_by_counter(origin) -> DispatchResult {
+			ensure_signed(origin)?;
+			let count = Self::score_count();
+
+			Self::deposit_event(RawEvent::Counted(CountMethod::Counter, count));
+			Ok(())
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use std::ops::RangeInclusive;
+	use event_assert::assert_has_event;
+	use sp_core::H256;
+	use frame_support::{
+		assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types,
+		weights::GetDispatchInfo,
+		StorageValue,
+	};
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup},
+		Perbill,
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const MaxMembers: u32 = 100;
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	mod storage_length {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			storage_length<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type MaxMembers = MaxMembers;
+		type WeightInfo = ();
+	}
+
+	pub type StorageLength = Module<TestRuntime>;
+
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		let t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		t.into()
+	}
+
+	/// The accounts join the member list and set a score
+	fn populate(accounts: RangeInclusive<u64>) {
+		for who in accounts {
+			assert_ok!(StorageLength::join(Origin::signed(who)));
+			assert_ok!(StorageLength::set_score(Origin::signed(who), who as u32));
+		}
+	}
+
+	/// The size of the value under `key`. A storage proof for the value contains at least this
+	/// many bytes.
+	fn value_size(key: &[u8]) -> usize {
+		sp_io::storage::get(key).map(|v| v.len()).unwrap_or(0)
+	}
+
+	#[test]
+	fn all_methods_agree() {
+		new_test_ext().execute_with(|| {
+			populate(1..=10);
+			assert_ok!(StorageLength::count_by_decoding(Origin::signed(1)));
+			assert_ok!(StorageLength::count_by_decode_len(Origin::signed(1)));
+			assert_ok!(StorageLength::count_by_counter(Origin::signed(1)));
+
+			assert_has_event::<TestRuntime>(RawEvent::Counted(CountMethod::Decode, 10));
+			assert_has_event::<TestRuntime>(RawEvent::Counted(CountMethod::DecodeLen, 10));
+			assert_has_event::<TestRuntime>(RawEvent::Counted(CountMethod::Counter, 10));
+		})
+	}
+
+	#[test]
+	fn empty_collections_count_zero() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(StorageLength::count_by_decode_len(Origin::signed(1)));
+			assert_ok!(StorageLength::count_by_counter(Origin::signed(1)));
+
+			assert_has_event::<TestRuntime>(RawEvent::Counted(CountMethod::DecodeLen, 0));
+			assert_has_event::<TestRuntime>(RawEvent::Counted(CountMethod::Counter, 0));
+		})
+	}
+
+	#[test]
+	fn counter_tracks_map() {
+		new_test_ext().execute_with(|| {
+			populate(1..=3);
+			// Overwriting an entry does not change the count
+			assert_ok!(StorageLength::set_score(Origin::signed(2), 7));
+			assert_eq!(StorageLength::score_count(), 3);
+
+			assert_ok!(StorageLength::remove_score(Origin::signed(2)));
+			assert_eq!(StorageLength::score_count(), 2);
+			assert_err!(
+				StorageLength::remove_score(Origin::signed(2)),
+				"no score to remove"
+			);
+			assert_eq!(StorageLength::score_count(), 2);
+		})
+	}
+
+	#[test]
+	fn member_list_is_bounded() {
+		new_test_ext().execute_with(|| {
+			populate(1..=100);
+			assert_err!(StorageLength::join(Origin::signed(1)), "already a member");
+			assert_err!(StorageLength::join(Origin::signed(101)), "too many members");
+		})
+	}
+
+	#[test]
+	fn counter_read_stays_small() {
+		new_test_ext().execute_with(|| {
+			let members_key = <Members<TestRuntime>>::hashed_key();
+			let counter_key = ScoreCount::hashed_key();
+
+			populate(1..=10);
+			let members_at_10 = value_size(&members_key);
+			let counter_at_10 = value_size(&counter_key);
+			populate(11..=100);
+			let members_at_100 = value_size(&members_key);
+			let counter_at_100 = value_size(&counter_key);
+
+			// Both vec methods read the whole list: a length prefix and 8 bytes per member
+			assert_eq!(members_at_10, 1 + 10 * 8);
+			assert_eq!(members_at_100, 2 + 100 * 8);
+			// The counter is always a `u32`
+			assert_eq!(counter_at_10, 4);
+			assert_eq!(counter_at_100, 4);
+		})
+	}
+
+	#[test]
+	fn weights_reflect_cost() {
+		let weight = |call: Call<TestRuntime>| call.get_dispatch_info().weight;
+		let decoding = weight(Call::count_by_decoding());
+		let decode_len = weight(Call::count_by_decode_len());
+		let counter = weight(Call::count_by_counter());
+
+		assert!(decoding > decode_len);
+		assert!(decode_len > counter);
+		assert_eq!(counter, <() as WeightInfo>::count_by_counter());
+	}
+}
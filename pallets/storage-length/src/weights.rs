@@ -0,0 +1,68 @@
+//! Weights for the storage-length pallet
+//!
+//! The counting calls differ in how much they read and decode. The member list grows with every
+//! member, so the calls that touch it are charged for a full list of `MaxMembers` members.
+
+use sp_std::marker::PhantomData;
+use frame_support::weights::Weight;
+
+/// Weight functions needed by the storage-length pallet
+pub trait WeightInfo {
+    fn join(members: u32) -> Weight;
+    fn set_score() -> Weight;
+    fn remove_score() -> Weight;
+    fn count_by_decoding(members: u32) -> Weight;
+    fn count_by_decode_len(members: u32) -> Weight;
+    fn count_by_counter() -> Weight;
+}
+
+/// Hand-picked estimates of the storage-length pallet's weights
+pub struct EstimatedWeight<T>(PhantomData<T>);
+impl<T: frame_system::Trait> WeightInfo for EstimatedWeight<T> {
+    // decodes the member list to check membership, then appends
+    fn join(members: u32) -> Weight {
+        20_000u32.saturating_add(1_000u32.saturating_mul(members))
+    }
+    // one score read and write, and a counter read and write
+    fn set_score() -> Weight {
+        25_000
+    }
+    // one score removal, and a counter read and write
+    fn remove_score() -> Weight {
+        25_000
+    }
+    // reads and decodes every member
+    fn count_by_decoding(members: u32) -> Weight {
+        10_000u32.saturating_add(1_000u32.saturating_mul(members))
+    }
+    // reads every member from the database, but only decodes the length prefix
+    fn count_by_decode_len(members: u32) -> Weight {
+        10_000u32.saturating_add(100u32.saturating_mul(members))
+    }
+    // reads and decodes a single `u32`
+    fn count_by_counter() -> Weight {
+        10_000
+    }
+}
+
+// For backwards compatibility and tests
+impl WeightInfo for () {
+    fn join(members: u32) -> Weight {
+        10_000u32.saturating_add(1_000u32.saturating_mul(members))
+    }
+    fn set_score() -> Weight {
+        10_000
+    }
+    fn remove_score() -> Weight {
+        10_000
+    }
+    fn count_by_decoding(members: u32) -> Weight {
+        10_000u32.saturating_add(1_000u32.saturating_mul(members))
+    }
+    fn count_by_decode_len(members: u32) -> Weight {
+        10_000u32.saturating_add(100u32.saturating_mul(members))
+    }
+    fn count_by_counter() -> Weight {
+        10_000
+    }
+}
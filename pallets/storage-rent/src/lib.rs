@@ -0,0 +1,404 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! Storage deposits and rent
+//!
+//! Every byte in storage costs every node that keeps the state, for as long as it is kept. A fee
+//! is paid once, so it cannot cover that. This pallet makes accounts back what they store:
+//! * Storing a value reserves a deposit of `DepositPerByte` for each byte from its owner.
+//! * The deposit pays rent. Each block, `RentPerByte` per byte is taken from it, and the deposit
+//!   shrinks until the owner tops it up.
+//! * An owner who removes a value gets back what is left of its deposit.
+//! * A value whose deposit is used up is unpaid. It is no longer readable, and anyone may purge it.
+//!
+//! Rent is collected lazily. Nothing happens in the blocks in between, the pallet works out what
+//! is due whenever an entry is touched, from the block up to which it was paid.
+
+use codec::{Decode, Encode};
+use frame_support::{
+	decl_event, decl_module, decl_storage,
+	dispatch::DispatchResult,
+	ensure,
+	traits::{Currency, Get, OnUnbalanced, ReservableCurrency},
+};
+use frame_system::{self as system, ensure_signed};
+use sp_runtime::{
+	traits::{SaturatedConversion, Saturating, Zero},
+	RuntimeDebug,
+};
+use sp_std::prelude::*;
+
+type BalanceOf<T> = <<T as Trait>::Currency as Currency<<T as system::Trait>::AccountId>>::Balance;
+type NegativeImbalanceOf<T> =
+	<<T as Trait>::Currency as Currency<<T as system::Trait>::AccountId>>::NegativeImbalance;
+
+/// Identifies an entry
+pub type EntryId = u32;
+
+/// A value in storage, and the deposit that pays for it
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct Entry<AccountId, Balance, BlockNumber> {
+	/// The account that stored the value and pays its rent
+	pub owner: AccountId,
+	/// The stored bytes
+	pub value: Vec<u8>,
+	/// What is left of the deposit, reserved from the owner
+	pub deposit: Balance,
+	/// The block up to which rent has been taken from the deposit
+	pub paid_to: BlockNumber,
+}
+
+type EntryOf<T> =
+	Entry<<T as system::Trait>::AccountId, BalanceOf<T>, <T as system::Trait>::BlockNumber>;
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The currency in which deposits are reserved and rent is paid
+	type Currency: ReservableCurrency<Self::AccountId>;
+
+	/// The deposit for each stored byte
+	type DepositPerByte: Get<BalanceOf<Self>>;
+
+	/// The rent for each stored byte and block
+	type RentPerByte: Get<BalanceOf<Self>>;
+
+	/// The longest value accepted, in bytes
+	type MaxLength: Get<u32>;
+
+	/// Receives the rent
+	type RentDestination: OnUnbalanced<NegativeImbalanceOf<Self>>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as StorageRent {
+		/// The id of the next entry
+		NextEntryId get(fn next_entry_id): EntryId;
+
+		/// The stored entries, paid or not
+		Entries get(fn entries): map EntryId => Option<EntryOf<T>>;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as system::Trait>::AccountId,
+		Balance = BalanceOf<T>,
+	{
+		/// An account stored an entry, and reserved this deposit
+		Stored(AccountId, EntryId, Balance),
+		/// The owner added this much to an entry's deposit
+		ToppedUp(EntryId, Balance),
+		/// The owner removed an entry, and got this much of its deposit back
+		Removed(EntryId, Balance),
+		/// An account purged an unpaid entry
+		Purged(EntryId, AccountId),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		const DepositPerByte: BalanceOf<T> = T::DepositPerByte::get();
+		const RentPerByte: BalanceOf<T> = T::RentPerByte::get();
+		const MaxLength: u32 = T::MaxLength::get();
+
+		/// Store `value`, and reserve its deposit
+		fn store(origin, value: Vec<u8>) -> DispatchResult {
+			let owner = ensure_signed(origin)?;
+			ensure!(value.len() <= T::MaxLength::get() as usize, "value too long");
+			let id = Self::next_entry_id();
+			let next_id = id.checked_add(1).ok_or("entry ids exhausted")?;
+
+			let deposit = T::DepositPerByte::get().saturating_mul(Self::bytes(&value));
+			T::Currency::reserve(&owner, deposit).map_err(|_| "cannot afford deposit")?;
+
+			let paid_to = <system::Module<T>>::block_number();
+			<Entries<T>>::insert(id, Entry { owner: owner.clone(), value, deposit, paid_to });
+			NextEntryId::put(next_id);
+
+			Self::deposit_event(RawEvent::Stored(owner, id, deposit));
+			Ok(())
+		}
+
+		/// Add `amount` to the deposit of an entry that is still paid
+		fn top_up(origin, id: EntryId, amount: BalanceOf<T>) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let mut entry = Self::entries(id).ok_or("unknown entry")?;
+			ensure!(entry.owner == who, "not the owner");
+			let now = <system::Module<T>>::block_number();
+			ensure!(Self::is_paid(&entry, now), "entry unpaid");
+
+			T::Currency::reserve(&who, amount).map_err(|_| "cannot afford deposit")?;
+			Self::collect_rent(&mut entry, now);
+			entry.deposit = entry.deposit.saturating_add(amount);
+			<Entries<T>>::insert(id, entry);
+
+			Self::deposit_event(RawEvent::ToppedUp(id, amount));
+			Ok(())
+		}
+
+		/// Remove an entry, and get back what is left of its deposit
+		fn remove(origin, id: EntryId) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let mut entry = Self::entries(id).ok_or("unknown entry")?;
+			ensure!(entry.owner == who, "not the owner");
+
+			Self::collect_rent(&mut entry, <system::Module<T>>::block_number());
+			T::Currency::unreserve(&who, entry.deposit);
+			<Entries<T>>::remove(id);
+
+			Self::deposit_event(RawEvent::Removed(id, entry.deposit));
+			Ok(())
+		}
+
+		/// Remove an entry whose deposit is used up. Anyone may call this.
+		fn purge(origin, id: EntryId) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let mut entry = Self::entries(id).ok_or("unknown entry")?;
+			let now = <system::Module<T>>::block_number();
+			ensure!(!Self::is_paid(&entry, now), "entry still paid");
+
+			Self::collect_rent(&mut entry, now);
+			<Entries<T>>::remove(id);
+
+			Self::deposit_event(RawEvent::Purged(id, who));
+			Ok(())
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// The value of an entry, unless it is unknown or unpaid
+	pub fn value(id: EntryId) -> Option<Vec<u8>> {
+		let now = <system::Module<T>>::block_number();
+		Self::entries(id)
+			.filter(|entry| Self::is_paid(entry, now))
+			.map(|entry| entry.value)
+	}
+
+	/// The rent an entry owes for the blocks since it was last paid, at most its deposit
+	pub fn rent_due(entry: &EntryOf<T>, now: T::BlockNumber) -> BalanceOf<T> {
+		let blocks: BalanceOf<T> = now.saturating_sub(entry.paid_to).saturated_into::<u32>().into();
+		T::RentPerByte::get()
+			.saturating_mul(Self::bytes(&entry.value))
+			.saturating_mul(blocks)
+			.min(entry.deposit)
+	}
+
+	/// Whether anything is left of an entry's deposit after paying its rent up to `now`
+	fn is_paid(entry: &EntryOf<T>, now: T::BlockNumber) -> bool {
+		!entry.deposit.saturating_sub(Self::rent_due(entry, now)).is_zero()
+	}
+
+	/// Take the rent up to `now` from the owner's reserved balance. The caller stores the entry.
+	fn collect_rent(entry: &mut EntryOf<T>, now: T::BlockNumber) {
+		let due = Self::rent_due(entry, now);
+		let (rent, _) = T::Currency::slash_reserved(&entry.owner, due);
+		T::RentDestination::on_unbalanced(rent);
+		entry.deposit = entry.deposit.saturating_sub(due);
+		entry.paid_to = now;
+	}
+
+	fn bytes(value: &[u8]) -> BalanceOf<T> {
+		(value.len() as u32).into()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use event_assert::assert_last_event;
+	use frame_support::{assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types};
+	use sp_core::H256;
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup},
+		Perbill,
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const ExistentialDeposit: u64 = 1;
+		pub const TransferFee: u64 = 0;
+		pub const CreationFee: u64 = 0;
+
+		pub const DepositPerByte: u64 = 10;
+		pub const RentPerByte: u64 = 1;
+		pub const MaxLength: u32 = 32;
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	impl balances::Trait for TestRuntime {
+		type Balance = u64;
+		type OnFreeBalanceZero = ();
+		type OnNewAccount = ();
+		type Event = TestEvent;
+		type TransferPayment = ();
+		type DustRemoval = ();
+		type ExistentialDeposit = ExistentialDeposit;
+		type TransferFee = TransferFee;
+		type CreationFee = CreationFee;
+	}
+
+	mod storage_rent {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			balances<T>,
+			storage_rent<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type Currency = Balances;
+		type DepositPerByte = DepositPerByte;
+		type RentPerByte = RentPerByte;
+		type MaxLength = MaxLength;
+		// The rent is burned
+		type RentDestination = ();
+	}
+
+	pub type System = system::Module<TestRuntime>;
+	pub type Balances = balances::Module<TestRuntime>;
+	pub type StorageRent = Module<TestRuntime>;
+
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		let mut t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		balances::GenesisConfig::<TestRuntime> {
+			balances: vec![(1, 1000), (2, 50)],
+			vesting: vec![],
+		}
+		.assimilate_storage(&mut t)
+		.unwrap();
+		let mut ext: sp_io::TestExternalities = t.into();
+		ext.execute_with(|| System::set_block_number(1));
+		ext
+	}
+
+	/// Ten bytes: a deposit of 100, which pays rent of 10 per block for ten blocks
+	const VALUE: &[u8] = b"ten bytes!";
+
+	fn store_value() -> EntryId {
+		assert_ok!(StorageRent::store(Origin::signed(1), VALUE.to_vec()));
+		StorageRent::next_entry_id() - 1
+	}
+
+	#[test]
+	fn store_reserves_deposit() {
+		new_test_ext().execute_with(|| {
+			let id = store_value();
+
+			assert_eq!((Balances::free_balance(1), Balances::reserved_balance(1)), (900, 100));
+			assert_eq!(StorageRent::value(id), Some(VALUE.to_vec()));
+			assert_last_event::<TestRuntime>(RawEvent::Stored(1, id, 100));
+		})
+	}
+
+	#[test]
+	fn store_checks_length_and_funds() {
+		new_test_ext().execute_with(|| {
+			assert_err!(StorageRent::store(Origin::signed(1), vec![0; 33]), "value too long");
+			// A deposit of 100 is more than account 2 has
+			assert_err!(
+				StorageRent::store(Origin::signed(2), VALUE.to_vec()),
+				"cannot afford deposit"
+			);
+			assert_eq!(StorageRent::next_entry_id(), 0);
+		})
+	}
+
+	#[test]
+	fn remove_refunds_what_is_left() {
+		new_test_ext().execute_with(|| {
+			let id = store_value();
+			System::set_block_number(4);
+
+			assert_err!(StorageRent::remove(Origin::signed(2), id), "not the owner");
+			assert_ok!(StorageRent::remove(Origin::signed(1), id));
+
+			// Three blocks of rent were burned
+			assert_eq!((Balances::free_balance(1), Balances::reserved_balance(1)), (970, 0));
+			assert_eq!(Balances::total_issuance(), 1020);
+			assert_eq!(StorageRent::entries(id), None);
+			assert_last_event::<TestRuntime>(RawEvent::Removed(id, 70));
+		})
+	}
+
+	#[test]
+	fn unpaid_entries_are_purged() {
+		new_test_ext().execute_with(|| {
+			let id = store_value();
+
+			System::set_block_number(10);
+			assert_eq!(StorageRent::value(id), Some(VALUE.to_vec()));
+			assert_err!(StorageRent::purge(Origin::signed(2), id), "entry still paid");
+			assert_eq!(Balances::reserved_balance(1), 100);
+
+			// The deposit ran out, and the value is gone even before it is purged
+			System::set_block_number(11);
+			assert_eq!(StorageRent::value(id), None);
+			assert_ok!(StorageRent::purge(Origin::signed(2), id));
+
+			assert_eq!((Balances::free_balance(1), Balances::reserved_balance(1)), (900, 0));
+			assert_eq!(StorageRent::entries(id), None);
+			assert_last_event::<TestRuntime>(RawEvent::Purged(id, 2));
+		})
+	}
+
+	#[test]
+	fn top_up_extends_entry() {
+		new_test_ext().execute_with(|| {
+			let id = store_value();
+
+			// Forty of the deposit are taken as rent, and fifty added
+			System::set_block_number(5);
+			assert_ok!(StorageRent::top_up(Origin::signed(1), id, 50));
+			let entry = StorageRent::entries(id).unwrap();
+			assert_eq!((entry.deposit, entry.paid_to), (110, 5));
+			assert_eq!(Balances::reserved_balance(1), 110);
+
+			System::set_block_number(15);
+			assert_eq!(StorageRent::value(id), Some(VALUE.to_vec()));
+			System::set_block_number(16);
+			assert_eq!(StorageRent::value(id), None);
+			assert_err!(StorageRent::top_up(Origin::signed(1), id, 50), "entry unpaid");
+		})
+	}
+}
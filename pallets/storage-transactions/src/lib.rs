@@ -0,0 +1,294 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! Storage transactions: what a failed call leaves behind, and how to undo it
+//!
+//! A call does not write to the state trie. Its writes go to the overlay, a layer of changes in
+//! memory that every later read in the block sees. Only when the block is finished are the changes
+//! of all its extrinsics written to the trie, and the storage root computed from them.
+//!
+//! The overlay has no layer per call. A call that returns an error keeps every write it made
+//! before the error, which is why recipes verify first and write last. When a call can't check
+//! everything up front, it needs transactions.
+//!
+//! The Substrate version of the recipes offers no storage transactions, so this pallet builds
+//! them. `with_transaction` takes a snapshot of the raw values under some keys, runs a closure,
+//! and restores the snapshot if the closure asks for a `TransactionOutcome::Rollback`.
+//! Transactions nest, because every level takes a snapshot of its own. Only the keys in the
+//! snapshot are restored, so a transaction must name every item it may write.
+
+use sp_std::prelude::*;
+use frame_support::{
+	decl_event, decl_module, decl_storage,
+	dispatch::DispatchResult,
+	ensure, StorageValue,
+};
+use frame_system::{self as system, ensure_signed};
+
+/// Whether a transaction keeps or discards its writes, with its result
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransactionOutcome<R> {
+	Commit(R),
+	Rollback(R),
+}
+
+/// The raw values under some storage keys at one point in time
+pub struct Snapshot(Vec<(Vec<u8>, Option<Vec<u8>>)>);
+
+impl Snapshot {
+	pub fn take(keys: &[Vec<u8>]) -> Self {
+		Snapshot(keys.iter().map(|key| (key.clone(), sp_io::storage::get(key))).collect())
+	}
+
+	/// Put the values back, and remove the keys that had no value
+	pub fn restore(self) {
+		for (key, value) in self.0 {
+			match value {
+				Some(value) => sp_io::storage::set(&key, &value),
+				None => sp_io::storage::clear(&key),
+			}
+		}
+	}
+}
+
+/// Run `f`, and undo its writes under `keys` if it rolls back
+pub fn with_transaction<R>(keys: &[Vec<u8>], f: impl FnOnce() -> TransactionOutcome<R>) -> R {
+	let snapshot = Snapshot::take(keys);
+	match f() {
+		TransactionOutcome::Commit(result) => result,
+		TransactionOutcome::Rollback(result) => {
+			snapshot.restore();
+			result
+		}
+	}
+}
+
+/// Run `f`, and undo its writes under `keys` if it fails
+pub fn transactional(keys: &[Vec<u8>], f: impl FnOnce() -> DispatchResult) -> DispatchResult {
+	with_transaction(keys, || match f() {
+		Ok(()) => TransactionOutcome::Commit(Ok(())),
+		Err(e) => TransactionOutcome::Rollback(Err(e)),
+	})
+}
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event> + Into<<Self as system::Trait>::Event>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as StorageTransactions {
+		/// The number of steps taken
+		Counter get(fn counter): u32;
+
+		/// The markers of the steps taken, in order
+		Trail get(fn trail): Vec<u32>;
+	}
+}
+
+decl_event!(
+	pub enum Event {
+		/// Steps were committed, and the counter reached this value
+		Committed(u32),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		/// Take a step, then fail if asked to. The step stays taken either way.
+		fn step(origin, fail: bool) -> DispatchResult {
+			ensure_signed(origin)?;
+			Self::record(1);
+			ensure!(!fail, "failed after writing");
+			Ok(())
+		}
+
+		/// Take a step in a transaction, which is undone if the call fails
+		fn atomic_step(origin, fail: bool) -> DispatchResult {
+			ensure_signed(origin)?;
+			transactional(&Self::keys(), || {
+				Self::record(1);
+				ensure!(!fail, "failed after writing");
+				Ok(())
+			})?;
+			Self::deposit_event(Event::Committed(Self::counter()));
+			Ok(())
+		}
+
+		/// Take step 1 in an outer transaction, step 2 in an inner one, and then step 3 in the
+		/// outer one again. An inner failure only undoes step 2, an outer failure undoes all steps.
+		fn nested_steps(origin, fail_inner: bool, fail_outer: bool) -> DispatchResult {
+			ensure_signed(origin)?;
+			with_transaction(&Self::keys(), || {
+				Self::record(1);
+				// The outer transaction carries on whether the inner one commits or not
+				let _ = transactional(&Self::keys(), || {
+					Self::record(2);
+					ensure!(!fail_inner, "inner transaction failed");
+					Ok(())
+				});
+				Self::record(3);
+				if fail_outer {
+					TransactionOutcome::Rollback(Err("outer transaction failed".into()))
+				} else {
+					TransactionOutcome::Commit(Ok(()))
+				}
+			})?;
+			// Events are not in the snapshot, so they are only deposited once the steps are final
+			Self::deposit_event(Event::Committed(Self::counter()));
+			Ok(())
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// The keys of all items the steps write
+	pub fn keys() -> Vec<Vec<u8>> {
+		vec![Counter::hashed_key().to_vec(), Trail::hashed_key().to_vec()]
+	}
+
+	fn record(marker: u32) {
+		Counter::mutate(|count| *count += 1);
+		Trail::mutate(|trail| trail.push(marker));
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use event_assert::{assert_last_event, assert_no_events};
+
+	use sp_core::H256;
+	use frame_support::{
+		assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types
+	};
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup},
+		Perbill,
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	mod storage_transactions {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			storage_transactions,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+	}
+
+	pub type StorageTransactions = Module<TestRuntime>;
+
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap()
+			.into()
+	}
+
+	#[test]
+	fn failed_calls_keep_their_writes() {
+		new_test_ext().execute_with(|| {
+			assert_err!(StorageTransactions::step(Origin::signed(1), true), "failed after writing");
+			assert_eq!(StorageTransactions::counter(), 1);
+			assert_eq!(StorageTransactions::trail(), vec![1]);
+		})
+	}
+
+	#[test]
+	fn committed_transactions_keep_their_writes() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(StorageTransactions::atomic_step(Origin::signed(1), false));
+			assert_eq!(StorageTransactions::counter(), 1);
+			assert_last_event::<TestRuntime>(Event::Committed(1));
+		})
+	}
+
+	#[test]
+	fn rollback_restores_the_state() {
+		new_test_ext().execute_with(|| {
+			let root = sp_io::storage::root();
+			assert_err!(
+				StorageTransactions::atomic_step(Origin::signed(1), true),
+				"failed after writing"
+			);
+
+			assert_eq!(StorageTransactions::counter(), 0);
+			// The items had no value before, so the keys are gone again rather than set to zero
+			let keys = StorageTransactions::keys();
+			assert!(keys.iter().all(|key| sp_io::storage::get(key).is_none()));
+			assert_eq!(sp_io::storage::root(), root);
+			assert_no_events::<TestRuntime>();
+		})
+	}
+
+	#[test]
+	fn inner_rollback_keeps_outer_writes() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(StorageTransactions::nested_steps(Origin::signed(1), true, false));
+			assert_eq!(StorageTransactions::trail(), vec![1, 3]);
+			assert_eq!(StorageTransactions::counter(), 2);
+		})
+	}
+
+	#[test]
+	fn outer_rollback_undoes_committed_inner_writes() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(StorageTransactions::atomic_step(Origin::signed(1), false));
+			assert_err!(
+				StorageTransactions::nested_steps(Origin::signed(1), false, true),
+				"outer transaction failed"
+			);
+			// Only the earlier, separate step remains
+			assert_eq!(StorageTransactions::trail(), vec![1]);
+			assert_eq!(StorageTransactions::counter(), 1);
+		})
+	}
+
+	#[test]
+	fn nested_commits_keep_all_writes() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(StorageTransactions::nested_steps(Origin::signed(1), false, false));
+			assert_eq!(StorageTransactions::trail(), vec![1, 2, 3]);
+			assert_eq!(StorageTransactions::counter(), 3);
+		})
+	}
+}
@@ -0,0 +1,417 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! A treasury that pays its grants as streams
+//!
+//! The treasury is a pot of funds in the pallet's own account, like the charity's. Anyone can
+//! `fund` it. Instead of paying a grant at once, governance approves a grant as a stream: a fixed
+//! amount per block, for a fixed number of blocks. The recipient `claim`s whatever has streamed so
+//! far, whenever they like.
+//!
+//! Streams are computed lazily. A grant only records up to which block it has been paid, and a
+//! claim pays for the blocks since. Nothing happens in blocks without claims, so any number of
+//! grants cost nothing per block.
+//!
+//! Governance can `clawback` a grant. The recipient keeps what streamed up to that block, and the
+//! rest stays in the treasury. The treasury never promises more than it holds: the unpaid part of
+//! all grants is tracked in `Committed`, and a grant is only approved if the uncommitted funds
+//! cover it entirely.
+
+use codec::{Decode, Encode};
+use sp_runtime::{
+	traits::{AccountIdConversion, Convert, Saturating, Zero},
+	ModuleId, RuntimeDebug,
+};
+use frame_support::{
+	decl_event, decl_module, decl_storage,
+	dispatch::{DispatchError, DispatchResult},
+	ensure,
+	traits::{Currency, EnsureOrigin, ExistenceRequirement::AllowDeath},
+};
+use frame_system::{self as system, ensure_signed};
+
+pub type GrantIndex = u32;
+
+type BalanceOf<T> = <<T as Trait>::Currency as Currency<<T as system::Trait>::AccountId>>::Balance;
+type GrantOf<T> =
+	Grant<<T as system::Trait>::AccountId, BalanceOf<T>, <T as system::Trait>::BlockNumber>;
+
+/// Hardcoded pallet ID; used to create the treasury's account
+/// Must be exactly 8 characters long
+const PALLET_ID: ModuleId = ModuleId(*b"GrantTsy");
+
+/// A grant that streams to its recipient
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct Grant<AccountId, Balance, BlockNumber> {
+	pub recipient: AccountId,
+	/// The amount that streams in every block
+	pub per_block: Balance,
+	/// The blocks before this one have been paid
+	pub paid_until: BlockNumber,
+	/// The stream stops at the start of this block
+	pub end: BlockNumber,
+}
+
+impl<AccountId, Balance, BlockNumber> Grant<AccountId, Balance, BlockNumber> where
+	Balance: Copy + Saturating,
+	BlockNumber: Copy + Ord + Saturating,
+{
+	/// The number of blocks that streamed since the last payment, up to `now`
+	fn streamed_blocks(&self, now: BlockNumber) -> BlockNumber {
+		now.min(self.end).saturating_sub(self.paid_until)
+	}
+
+	/// The number of blocks that have yet to be paid, streamed or not
+	fn unpaid_blocks(&self) -> BlockNumber {
+		self.end.saturating_sub(self.paid_until)
+	}
+}
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The currency the treasury holds
+	type Currency: Currency<Self::AccountId>;
+
+	/// Turns a number of blocks into a balance, to multiply it with a grant's rate
+	type BlockNumberToBalance: Convert<Self::BlockNumber, BalanceOf<Self>>;
+
+	/// The origin that approves grants and claws them back
+	type ManageOrigin: EnsureOrigin<Self::Origin>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as StreamingGrants {
+		/// Grants that have not been paid in full
+		Grants get(fn grant): map GrantIndex => Option<GrantOf<T>>;
+
+		/// Number of grants ever approved; the next grant's index
+		GrantCount get(fn grant_count): GrantIndex;
+
+		/// The unpaid part of all grants, which the treasury must keep
+		Committed get(fn committed): BalanceOf<T>;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as system::Trait>::AccountId,
+		Balance = BalanceOf<T>,
+		BlockNumber = <T as system::Trait>::BlockNumber,
+	{
+		/// Someone added funds to the treasury
+		Funded(AccountId, Balance),
+		/// A grant was approved for a recipient, paying an amount per block until a block
+		GrantApproved(GrantIndex, AccountId, Balance, BlockNumber),
+		/// A recipient claimed what their grant streamed
+		GrantClaimed(GrantIndex, AccountId, Balance),
+		/// A grant was paid in full
+		GrantCompleted(GrantIndex),
+		/// A grant was clawed back. The recipient was paid the first amount, and the second
+		/// amount stays in the treasury.
+		GrantClawedBack(GrantIndex, Balance, Balance),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		/// Add funds to the treasury
+		fn fund(origin, amount: BalanceOf<T>) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			T::Currency::transfer(&who, &Self::account_id(), amount, AllowDeath)?;
+			Self::deposit_event(RawEvent::Funded(who, amount));
+			Ok(())
+		}
+
+		/// Stream `per_block` to `recipient` for `duration` blocks, starting now
+		fn approve_grant(
+			origin,
+			recipient: T::AccountId,
+			per_block: BalanceOf<T>,
+			duration: T::BlockNumber,
+		) -> DispatchResult {
+			T::ManageOrigin::ensure_origin(origin)?;
+			ensure!(!per_block.is_zero() && !duration.is_zero(), "empty grant");
+			let total = per_block.saturating_mul(T::BlockNumberToBalance::convert(duration));
+			ensure!(
+				Self::committed().saturating_add(total) <= Self::pot(),
+				"treasury cannot cover the grant"
+			);
+
+			let now = <system::Module<T>>::block_number();
+			let end = now.saturating_add(duration);
+			let index = Self::grant_count();
+			let grant = Grant { recipient: recipient.clone(), per_block, paid_until: now, end };
+			<Grants<T>>::insert(index, grant);
+			GrantCount::put(index + 1);
+			<Committed<T>>::mutate(|committed| *committed = committed.saturating_add(total));
+			Self::deposit_event(RawEvent::GrantApproved(index, recipient, per_block, end));
+			Ok(())
+		}
+
+		/// Pay the recipient of grant `index` what it streamed so far
+		fn claim(origin, index: GrantIndex) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let grant = Self::grant(index).ok_or("no such grant")?;
+			ensure!(who == grant.recipient, "not the recipient");
+			let paid = Self::pay_streamed(index, grant)?;
+			ensure!(!paid.is_zero(), "nothing to claim");
+			Ok(())
+		}
+
+		/// Stop grant `index`. What streamed so far is paid, and the rest stays in the treasury.
+		fn clawback(origin, index: GrantIndex) -> DispatchResult {
+			T::ManageOrigin::ensure_origin(origin)?;
+			let grant = Self::grant(index).ok_or("no such grant")?;
+			let paid = Self::pay_streamed(index, grant)?;
+
+			// The recipient was paid up to now, so whatever the grant still holds is returned
+			let returned = match <Grants<T>>::take(index) {
+				Some(grant) => grant.per_block
+					.saturating_mul(T::BlockNumberToBalance::convert(grant.unpaid_blocks())),
+				None => Zero::zero(),
+			};
+			<Committed<T>>::mutate(|committed| *committed = committed.saturating_sub(returned));
+			Self::deposit_event(RawEvent::GrantClawedBack(index, paid, returned));
+			Ok(())
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// The account ID that holds the treasury's funds
+	pub fn account_id() -> T::AccountId {
+		PALLET_ID.into_account()
+	}
+
+	/// The treasury's funds, including those committed to grants
+	pub fn pot() -> BalanceOf<T> {
+		T::Currency::free_balance(&Self::account_id())
+	}
+
+	/// What grant `index` streamed and has not been paid yet
+	pub fn claimable(index: GrantIndex) -> BalanceOf<T> {
+		let now = <system::Module<T>>::block_number();
+		Self::grant(index)
+			.map(|grant| Self::streamed(&grant, now))
+			.unwrap_or_else(Zero::zero)
+	}
+
+	fn streamed(grant: &GrantOf<T>, now: T::BlockNumber) -> BalanceOf<T> {
+		grant.per_block.saturating_mul(T::BlockNumberToBalance::convert(grant.streamed_blocks(now)))
+	}
+
+	/// Pay what `grant` streamed up to now, and remove it once it is paid in full. Returns the
+	/// amount paid.
+	fn pay_streamed(
+		index: GrantIndex,
+		mut grant: GrantOf<T>,
+	) -> Result<BalanceOf<T>, DispatchError> {
+		let now = <system::Module<T>>::block_number();
+		let amount = Self::streamed(&grant, now);
+		if amount.is_zero() {
+			return Ok(amount);
+		}
+		T::Currency::transfer(&Self::account_id(), &grant.recipient, amount, AllowDeath)?;
+
+		<Committed<T>>::mutate(|committed| *committed = committed.saturating_sub(amount));
+		grant.paid_until = now.min(grant.end);
+		Self::deposit_event(RawEvent::GrantClaimed(index, grant.recipient.clone(), amount));
+		if grant.unpaid_blocks().is_zero() {
+			<Grants<T>>::remove(index);
+			Self::deposit_event(RawEvent::GrantCompleted(index));
+		} else {
+			<Grants<T>>::insert(index, grant);
+		}
+		Ok(amount)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use event_assert::assert_has_event;
+	use sp_core::H256;
+	use frame_support::{
+		assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types,
+	};
+	use frame_system::{EnsureRoot, RawOrigin};
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, ConvertInto, IdentityLookup},
+		Perbill,
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const ExistentialDeposit: u64 = 0;
+		pub const TransferFee: u64 = 0;
+		pub const CreationFee: u64 = 0;
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	impl balances::Trait for TestRuntime {
+		type Balance = u64;
+		type OnFreeBalanceZero = ();
+		type OnNewAccount = ();
+		type Event = TestEvent;
+		type TransferPayment = ();
+		type DustRemoval = ();
+		type ExistentialDeposit = ExistentialDeposit;
+		type TransferFee = TransferFee;
+		type CreationFee = CreationFee;
+	}
+
+	mod streaming_grants {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			balances<T>,
+			streaming_grants<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type Currency = balances::Module<Self>;
+		type BlockNumberToBalance = ConvertInto;
+		type ManageOrigin = EnsureRoot<u64>;
+	}
+
+	pub type System = system::Module<TestRuntime>;
+	pub type Balances = balances::Module<TestRuntime>;
+	pub type StreamingGrants = Module<TestRuntime>;
+
+	/// Account 1 funds the treasury with 100 in block 1
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		let mut t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		balances::GenesisConfig::<TestRuntime> {
+			balances: vec![(1, 1000)],
+			vesting: vec![],
+		}
+		.assimilate_storage(&mut t)
+		.unwrap();
+		let mut ext: sp_io::TestExternalities = t.into();
+		ext.execute_with(|| {
+			System::set_block_number(1);
+			assert_ok!(StreamingGrants::fund(Origin::signed(1), 100));
+		});
+		ext
+	}
+
+	fn approve(recipient: u64, per_block: u64, duration: u64) -> DispatchResult {
+		StreamingGrants::approve_grant(RawOrigin::Root.into(), recipient, per_block, duration)
+	}
+
+	#[test]
+	fn grants_stream_per_block() {
+		new_test_ext().execute_with(|| {
+			// 5 per block in blocks 1 to 10
+			assert_ok!(approve(2, 5, 10));
+			assert_eq!(StreamingGrants::committed(), 50);
+
+			System::set_block_number(4);
+			assert_eq!(StreamingGrants::claimable(0), 15);
+			assert_ok!(StreamingGrants::claim(Origin::signed(2), 0));
+			assert_eq!(Balances::free_balance(&2), 15);
+			assert_eq!(StreamingGrants::committed(), 35);
+
+			// The stream stops at its end, however late the claim
+			System::set_block_number(20);
+			assert_ok!(StreamingGrants::claim(Origin::signed(2), 0));
+			assert_eq!(Balances::free_balance(&2), 50);
+			assert_eq!(StreamingGrants::pot(), 50);
+			assert_eq!(StreamingGrants::committed(), 0);
+			assert_eq!(StreamingGrants::grant(0), None);
+			assert_has_event::<TestRuntime>(RawEvent::GrantCompleted(0));
+		})
+	}
+
+	#[test]
+	fn grants_need_uncommitted_funds() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(approve(2, 5, 10));
+			assert_err!(approve(3, 6, 10), "treasury cannot cover the grant");
+			assert_ok!(approve(3, 5, 10));
+			assert_eq!(StreamingGrants::committed(), 100);
+			assert_err!(approve(4, 0, 10), "empty grant");
+		})
+	}
+
+	#[test]
+	fn clawback_pays_what_streamed() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(approve(2, 5, 10));
+			System::set_block_number(5);
+			assert_ok!(StreamingGrants::clawback(RawOrigin::Root.into(), 0));
+
+			assert_eq!(Balances::free_balance(&2), 20);
+			assert_eq!(StreamingGrants::pot(), 80);
+			assert_eq!(StreamingGrants::committed(), 0);
+			assert_eq!(StreamingGrants::grant(0), None);
+			assert_has_event::<TestRuntime>(RawEvent::GrantClawedBack(0, 20, 30));
+
+			// The freed funds can go to a new grant
+			assert_ok!(approve(3, 8, 10));
+		})
+	}
+
+	#[test]
+	fn only_the_recipient_claims() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(approve(2, 5, 10));
+			assert_err!(StreamingGrants::claim(Origin::signed(2), 0), "nothing to claim");
+
+			System::set_block_number(2);
+			assert_err!(StreamingGrants::claim(Origin::signed(3), 0), "not the recipient");
+			assert_err!(StreamingGrants::claim(Origin::signed(2), 1), "no such grant");
+			assert_ok!(StreamingGrants::claim(Origin::signed(2), 0));
+		})
+	}
+
+	#[test]
+	fn only_manage_origin_approves_and_claws_back() {
+		new_test_ext().execute_with(|| {
+			assert!(StreamingGrants::approve_grant(Origin::signed(1), 1, 5, 10).is_err());
+			assert_ok!(approve(2, 5, 10));
+			assert!(StreamingGrants::clawback(Origin::signed(1), 0).is_err());
+			assert!(StreamingGrants::grant(0).is_some());
+		})
+	}
+}
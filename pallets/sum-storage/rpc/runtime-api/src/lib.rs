@@ -16,10 +16,18 @@
 
 #![cfg_attr(not(feature = "std"), no_std)]
 
+use sp_std::vec::Vec;
+
 // Here we declare the runtime API. It is implemented it the `impl` block in
 // runtime amalgamator file (the `runtime/src/lib.rs`)
 sp_api::decl_runtime_apis! {
 	pub trait SumStorageApi {
 		fn get_sum() -> u32;
 	}
+
+	/// Information a light client needs to verify the summed values itself
+	pub trait SumStorageProofApi {
+		/// The raw storage keys of the two summed values, in order
+		fn storage_keys() -> Vec<Vec<u8>>;
+	}
 }
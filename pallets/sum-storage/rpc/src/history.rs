@@ -0,0 +1,217 @@
+//! Reading the summed values of old blocks.
+//!
+//! A full node only keeps the state of its most recent blocks, unless it runs with
+//! `--pruning archive`. It still has the headers of all blocks, so it knows an old block, but it
+//! can no longer read the block's storage. `sumStorage_getSumAt` tells the two cases apart, so
+//! that a dApp can point its user to an archive node instead of reporting a generic failure.
+
+use jsonrpc_core::{Error as RpcError, ErrorCode};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sp_core::{
+	storage::{StorageData, StorageKey},
+	Blake2Hasher, H256,
+};
+use sp_runtime::{generic::BlockId, traits::Block as BlockT};
+
+/// The answer to `sumStorage_getSumAt`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SumAt<Hash> {
+	/// The block whose state was read
+	pub at: Hash,
+	/// The value of `Thing1`
+	pub thing1: u32,
+	/// The value of `Thing2`
+	pub thing2: u32,
+	/// The sum of both
+	pub sum: u32,
+}
+
+/// Why the state of a block could not be read
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HistoryError {
+	/// The node knows no block with this hash
+	UnknownBlock,
+	/// The node knows the block, but has discarded its state
+	StatePruned,
+	/// The state is there, but a value in it is not a `u32`
+	UndecodableValue,
+}
+
+impl From<HistoryError> for i64 {
+	fn from(e: HistoryError) -> i64 {
+		match e {
+			HistoryError::UnknownBlock => 9878,
+			HistoryError::StatePruned => 9879,
+			HistoryError::UndecodableValue => 9880,
+		}
+	}
+}
+
+impl HistoryError {
+	/// The RPC error for block `at`. Its data is an object, so clients can match on `reason`
+	/// rather than parse the message.
+	pub fn to_rpc_error<Hash: Serialize>(self, at: Hash) -> RpcError {
+		let (message, reason, hint) = match self {
+			HistoryError::UnknownBlock => (
+				"Unknown block",
+				"unknownBlock",
+				"check the block hash, or wait until the node has synced it",
+			),
+			HistoryError::StatePruned => (
+				"State of the block has been pruned",
+				"statePruned",
+				"ask a node that runs with --pruning archive",
+			),
+			HistoryError::UndecodableValue => (
+				"Stored value is not a u32",
+				"undecodableValue",
+				"the block may use a different runtime",
+			),
+		};
+		RpcError {
+			code: ErrorCode::ServerError(self.into()),
+			message: message.into(),
+			data: Some(json!({ "block": at, "reason": reason, "hint": hint })),
+		}
+	}
+}
+
+/// A client that can read raw storage and headers of any block it knows
+pub trait ReadStorage<Block: BlockT> {
+	/// Whether the client has the header of the block
+	fn has_header(&self, at: &BlockId<Block>) -> bool;
+
+	/// Read a value from the state of the block. Fails if the state is not available.
+	fn read_storage(
+		&self,
+		at: &BlockId<Block>,
+		key: &StorageKey,
+	) -> sp_blockchain::Result<Option<StorageData>>;
+}
+
+impl<B, E, Block, RA> ReadStorage<Block> for sc_client::Client<B, E, Block, RA> where
+	Block: BlockT<Hash = H256>,
+	B: sc_client_api::backend::Backend<Block, Blake2Hasher>,
+	E: sc_client::CallExecutor<Block, Blake2Hasher>,
+{
+	fn has_header(&self, at: &BlockId<Block>) -> bool {
+		match sc_client::Client::header(self, at) {
+			Ok(header) => header.is_some(),
+			Err(_) => false,
+		}
+	}
+
+	fn read_storage(
+		&self,
+		at: &BlockId<Block>,
+		key: &StorageKey,
+	) -> sp_blockchain::Result<Option<StorageData>> {
+		sc_client::Client::storage(self, at, key)
+	}
+}
+
+/// Read `(thing1, thing2)` from the state of block `at`, under the raw storage `keys`
+pub fn read_things<Block, C>(
+	client: &C,
+	at: Block::Hash,
+	keys: &[Vec<u8>],
+) -> Result<(u32, u32), HistoryError>
+where
+	Block: BlockT,
+	C: ReadStorage<Block>,
+{
+	let id = BlockId::hash(at);
+	let mut values = Vec::with_capacity(keys.len());
+	for key in keys {
+		let raw = client.read_storage(&id, &StorageKey(key.clone())).map_err(|_| {
+			// The client only fails to open the state of a block it has a header for when the
+			// state was pruned
+			if client.has_header(&id) {
+				HistoryError::StatePruned
+			} else {
+				HistoryError::UnknownBlock
+			}
+		})?;
+		values.push(match raw {
+			Some(data) => codec::Decode::decode(&mut &data.0[..])
+				.map_err(|_| HistoryError::UndecodableValue)?,
+			// `ValueQuery` storage reads as zero when it was never set
+			None => 0,
+		});
+	}
+	match values[..] {
+		[thing1, thing2] => Ok((thing1, thing2)),
+		_ => Err(HistoryError::UndecodableValue),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use codec::Encode;
+	use sp_runtime::testing::{Block as TestBlock, ExtrinsicWrapper};
+	use std::collections::HashMap;
+
+	type Block = TestBlock<ExtrinsicWrapper<u64>>;
+
+	/// A node that knows blocks 1 and 2, and only has the state of block 2
+	struct PrunedNode {
+		state: HashMap<Vec<u8>, Vec<u8>>,
+	}
+
+	fn block(n: u8) -> H256 {
+		H256::repeat_byte(n)
+	}
+
+	impl ReadStorage<Block> for PrunedNode {
+		fn has_header(&self, at: &BlockId<Block>) -> bool {
+			*at == BlockId::hash(block(1)) || *at == BlockId::hash(block(2))
+		}
+
+		fn read_storage(
+			&self,
+			at: &BlockId<Block>,
+			key: &StorageKey,
+		) -> sp_blockchain::Result<Option<StorageData>> {
+			if *at == BlockId::hash(block(2)) {
+				Ok(self.state.get(&key.0).cloned().map(StorageData))
+			} else {
+				let message = format!("State already discarded for {:?}", at);
+				Err(sp_blockchain::Error::UnknownBlock(message))
+			}
+		}
+	}
+
+	fn node() -> PrunedNode {
+		let mut state = HashMap::new();
+		state.insert(b"thing1".to_vec(), 7u32.encode());
+		PrunedNode { state }
+	}
+
+	fn keys() -> Vec<Vec<u8>> {
+		vec![b"thing1".to_vec(), b"thing2".to_vec()]
+	}
+
+	#[test]
+	fn reads_available_state() {
+		assert_eq!(read_things::<Block, _>(&node(), block(2), &keys()), Ok((7, 0)));
+	}
+
+	#[test]
+	fn tells_pruned_state_from_unknown_blocks() {
+		let read = |at| read_things::<Block, _>(&node(), at, &keys());
+		assert_eq!(read(block(1)), Err(HistoryError::StatePruned));
+		assert_eq!(read(block(3)), Err(HistoryError::UnknownBlock));
+	}
+
+	#[test]
+	fn errors_are_structured() {
+		let error = HistoryError::StatePruned.to_rpc_error(block(1));
+		assert_eq!(error.code, ErrorCode::ServerError(9879));
+		let data = error.data.unwrap();
+		assert_eq!(data["reason"], "statePruned");
+		assert_eq!(data["block"], json!(block(1)));
+	}
+}
@@ -25,6 +25,15 @@ use sp_runtime::{
 	traits::{Block as BlockT, ProvideRuntimeApi},
 };
 use sum_storage_rpc_runtime_api::SumStorageApi as SumStorageRuntimeApi;
+use sum_storage_rpc_runtime_api::SumStorageProofApi as SumStorageProofRuntimeApi;
+use sp_core::{Bytes, H256};
+use sp_runtime::traits::Header as HeaderT;
+
+pub mod proof;
+use proof::{verify_sum_proof, ReadProof, SumWithProof};
+
+pub mod history;
+use history::{read_things, ReadStorage, SumAt};
 
 #[rpc]
 pub trait SumStorageApi<BlockHash> {
@@ -33,6 +42,22 @@ pub trait SumStorageApi<BlockHash> {
 		&self,
 		at: Option<BlockHash>
 	) -> Result<u32>;
+
+	/// The summed values together with a proof of them that light clients can check with
+	/// `proof::verify_sum_proof`
+	#[rpc(name = "sumStorage_getSumWithProof")]
+	fn get_sum_with_proof(
+		&self,
+		at: Option<BlockHash>
+	) -> Result<SumWithProof<BlockHash>>;
+
+	/// The summed values at an older block. If the node has pruned the block's state, the error's
+	/// data says so, see `history::HistoryError`.
+	#[rpc(name = "sumStorage_getSumAt")]
+	fn get_sum_at(
+		&self,
+		at: BlockHash
+	) -> Result<SumAt<BlockHash>>;
 }
 
 /// A struct that implements the `SumStorageApi`.
@@ -70,11 +95,14 @@ impl<C, M> SumStorage<C, M> {
 impl<C, Block> SumStorageApi<<Block as BlockT>::Hash>
 	for SumStorage<C, Block>
 where
-	Block: BlockT,
+	Block: BlockT<Hash = H256>,
 	C: Send + Sync + 'static,
 	C: ProvideRuntimeApi,
 	C: HeaderBackend<Block>,
+	C: ReadProof<Block>,
+	C: ReadStorage<Block>,
 	C::Api: SumStorageRuntimeApi<Block>,
+	C::Api: SumStorageProofRuntimeApi<Block>,
 {
 	fn get_sum(
 		&self,
@@ -94,4 +122,64 @@ where
 			data: Some(format!("{:?}", e).into()),
 		})
 	}
+
+	fn get_sum_with_proof(
+		&self,
+		at: Option<<Block as BlockT>::Hash>
+	) -> Result<SumWithProof<<Block as BlockT>::Hash>> {
+		self.sum_with_proof(at)
+	}
+
+	fn get_sum_at(
+		&self,
+		at: <Block as BlockT>::Hash
+	) -> Result<SumAt<<Block as BlockT>::Hash>> {
+		// The keys are the same in every block, so the best block, whose state is never pruned,
+		// can tell them
+		let best = BlockId::hash(self.client.info().best_hash);
+		let keys = self.client.runtime_api().storage_keys(&best)
+			.map_err(|e| rpc_error("Unable to query storage keys", e))?;
+
+		let (thing1, thing2) = read_things::<Block, _>(&*self.client, at, &keys)
+			.map_err(|e| e.to_rpc_error(at))?;
+		Ok(SumAt { at, thing1, thing2, sum: thing1.saturating_add(thing2) })
+	}
+}
+
+/// Turn any error into an RPC error
+fn rpc_error(message: &str, e: impl std::fmt::Debug) -> RpcError {
+	RpcError {
+		code: ErrorCode::ServerError(9877),
+		message: message.into(),
+		data: Some(format!("{:?}", e).into()),
+	}
+}
+
+impl<C, Block> SumStorage<C, Block>
+where
+	Block: BlockT<Hash = H256>,
+	C: Send + Sync + 'static,
+	C: HeaderBackend<Block>,
+	C: ReadProof<Block>,
+{
+	/// Build a proof of both values and check it like a light client would, which also decodes
+	/// the values
+	pub fn sum_with_proof(&self, at: Option<H256>) -> Result<SumWithProof<H256>> {
+		let at = at.unwrap_or_else(|| self.client.info().best_hash);
+		let id = BlockId::hash(at);
+
+		// The same keys a light client derives to check the proof
+		let keys = proof::storage_keys();
+		let proof = self.client.read_proof(&id, &keys)
+			.map_err(|e| rpc_error("Unable to prove storage", e))?;
+		let header = self.client.header(id)
+			.map_err(|e| rpc_error("Unable to read header", e))?
+			.ok_or_else(|| rpc_error("Unknown block", at))?;
+
+		let proof: Vec<Bytes> = proof.iter_nodes().map(Bytes).collect();
+		let (thing1, thing2) = verify_sum_proof(*header.state_root(), &proof)
+			.map_err(|e| rpc_error("Generated an invalid proof", e))?;
+
+		Ok(SumWithProof { at, thing1, thing2, proof })
+	}
 }
@@ -0,0 +1,145 @@
+//! Storage proofs of the summed values, for clients that don't trust the node they ask.
+//!
+//! A full node answers `sumStorage_getSumWithProof` with the two values *and* the trie nodes that
+//! lead from the block's state root to them. A light client, which only knows headers, checks the
+//! proof against the state root in the header it already trusts. If the node lied about either
+//! value, the check fails.
+//!
+//! The client derives the storage keys of the values itself. Had it taken them from the node, a
+//! lying node could send a valid proof of any two other `u32` storage items instead.
+
+use codec::Decode;
+use serde::{Deserialize, Serialize};
+use sp_core::{hashing::twox_128, Blake2Hasher, Bytes, H256};
+use sp_runtime::{generic::BlockId, traits::Block as BlockT};
+use sp_state_machine::{read_proof_check, StorageProof};
+
+/// The answer to `sumStorage_getSumWithProof`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SumWithProof<Hash> {
+	/// The block whose state was proven
+	pub at: Hash,
+	/// The value of `Thing1`
+	pub thing1: u32,
+	/// The value of `Thing2`
+	pub thing2: u32,
+	/// The trie nodes proving both values
+	pub proof: Vec<Bytes>,
+}
+
+/// Reasons a proof can be rejected
+#[derive(Debug, PartialEq)]
+pub enum VerifyError {
+	/// The proof does not lead to the state root
+	InvalidProof(String),
+	/// The proof is valid, but a value in it is not a `u32`
+	UndecodableValue,
+}
+
+/// The raw storage keys of `Thing1` and `Thing2`, in that order.
+///
+/// `decl_storage!` stores a plain value under the 128 bit xxHash of the pallet's storage name and
+/// the item's name, joined by a space.
+pub fn storage_keys() -> Vec<Vec<u8>> {
+	vec![
+		twox_128(b"TemplateModule Thing1").to_vec(),
+		twox_128(b"TemplateModule Thing2").to_vec(),
+	]
+}
+
+/// Check a proof against a trusted state root and return the proven `(thing1, thing2)`.
+///
+/// Values missing from the state are proven to be absent, and count as zero just like the
+/// `ValueQuery` default of the pallet.
+pub fn verify_sum_proof(state_root: H256, proof: &[Bytes]) -> Result<(u32, u32), VerifyError> {
+	let keys = storage_keys();
+	let proof = StorageProof::new(proof.iter().map(|node| node.0.clone()).collect());
+	let values = read_proof_check::<Blake2Hasher, _>(state_root, proof, keys.iter())
+		.map_err(|e| VerifyError::InvalidProof(format!("{:?}", e)))?;
+
+	let value_of = |key: &Vec<u8>| -> Result<u32, VerifyError> {
+		match values.get(key) {
+			Some(Some(raw)) => u32::decode(&mut &raw[..]).map_err(|_| VerifyError::UndecodableValue),
+			_ => Ok(0),
+		}
+	};
+	Ok((value_of(&keys[0])?, value_of(&keys[1])?))
+}
+
+/// A client that can produce storage proofs
+pub trait ReadProof<Block: BlockT> {
+	fn read_proof(&self, at: &BlockId<Block>, keys: &[Vec<u8>]) -> sp_blockchain::Result<StorageProof>;
+}
+
+impl<B, E, Block, RA> ReadProof<Block> for sc_client::Client<B, E, Block, RA> where
+	Block: BlockT<Hash = H256>,
+	B: sc_client_api::backend::Backend<Block, Blake2Hasher>,
+	E: sc_client::CallExecutor<Block, Blake2Hasher>,
+{
+	fn read_proof(&self, at: &BlockId<Block>, keys: &[Vec<u8>]) -> sp_blockchain::Result<StorageProof> {
+		sc_client::Client::read_proof(self, at, keys)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use codec::Encode;
+	use sp_state_machine::{prove_read, InMemoryBackend};
+
+	/// A state with the given entries, and a proof of the values under `proven`
+	fn prove(entries: Vec<(Vec<u8>, Option<u32>)>, proven: &[Vec<u8>]) -> (H256, Vec<Bytes>) {
+		let pairs = entries
+			.into_iter()
+			.filter_map(|(k, v)| v.map(|v| (k, v.encode())))
+			.collect::<Vec<_>>();
+		let backend = InMemoryBackend::<Blake2Hasher>::from(vec![(None, pairs)]);
+		let root = backend.root().clone();
+		let proof = prove_read(backend, proven).unwrap();
+		(root, proof.iter_nodes().map(Bytes).collect())
+	}
+
+	fn prove_things(thing1: Option<u32>, thing2: Option<u32>) -> (H256, Vec<Bytes>) {
+		let keys = storage_keys();
+		prove(vec![(keys[0].clone(), thing1), (keys[1].clone(), thing2)], &keys)
+	}
+
+	#[test]
+	fn valid_proof_is_accepted() {
+		let (root, proof) = prove_things(Some(42), Some(43));
+		assert_eq!(verify_sum_proof(root, &proof), Ok((42, 43)));
+	}
+
+	#[test]
+	fn absent_values_count_as_zero() {
+		let (root, proof) = prove_things(Some(42), None);
+		assert_eq!(verify_sum_proof(root, &proof), Ok((42, 0)));
+	}
+
+	#[test]
+	fn proof_for_another_root_is_rejected() {
+		let (_, proof) = prove_things(Some(42), Some(43));
+		let (other_root, _) = prove_things(Some(1), Some(43));
+		match verify_sum_proof(other_root, &proof) {
+			Err(VerifyError::InvalidProof(_)) => (),
+			other => panic!("unexpected result {:?}", other),
+		}
+	}
+
+	#[test]
+	fn proof_of_other_values_does_not_prove_the_things() {
+		let keys = storage_keys();
+		let others = vec![b"other1".to_vec(), b"other2".to_vec()];
+		let (root, proof) = prove(
+			vec![
+				(keys[0].clone(), Some(1)),
+				(keys[1].clone(), Some(2)),
+				(others[0].clone(), Some(42)),
+				(others[1].clone(), Some(43)),
+			],
+			&others,
+		);
+		assert_ne!(verify_sum_proof(root, &proof), Ok((42, 43)));
+	}
+}
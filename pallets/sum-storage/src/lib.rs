@@ -10,6 +10,7 @@
 /// For more guidance on Substrate modules, see the example module
 /// https://github.com/paritytech/substrate/blob/master/frame/example/src/lib.rs
 
+use sp_std::prelude::*;
 use frame_support::{decl_module, decl_storage, decl_event, dispatch};
 use frame_system::{self as system, ensure_signed};
 
@@ -56,6 +57,15 @@ impl<T: Trait> Module<T> {
 	pub fn get_sum() -> u32 {
 		Thing1::get() + Thing2::get()
 	}
+
+	/// The raw storage keys of `Thing1` and `Thing2`, in that order.
+	///
+	/// Clients that want to prove the values (rather than trust a node) request and check a storage
+	/// proof of these keys. They must derive the keys themselves, as `sum_storage_rpc::proof` does,
+	/// so the layout is fixed by the `storage_keys_follow_decl_storage_layout` test.
+	pub fn storage_keys() -> Vec<Vec<u8>> {
+		vec![Thing1::hashed_key().to_vec(), Thing2::hashed_key().to_vec()]
+	}
 }
 
 decl_event!(
@@ -150,4 +160,27 @@ mod tests {
 			assert_eq!(TemplateModule::get_sum(), 85);
 		});
 	}
+
+	#[test]
+	fn storage_keys_locate_values() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(TemplateModule::set_thing_1(Origin::signed(1), 42));
+			assert_ok!(TemplateModule::set_thing_2(Origin::signed(1), 43));
+			let keys = TemplateModule::storage_keys();
+			assert_eq!(sp_io::storage::get(&keys[0]), Some(codec::Encode::encode(&42u32)));
+			assert_eq!(sp_io::storage::get(&keys[1]), Some(codec::Encode::encode(&43u32)));
+		});
+	}
+
+	#[test]
+	fn storage_keys_follow_decl_storage_layout() {
+		use sp_core::hashing::twox_128;
+		assert_eq!(
+			TemplateModule::storage_keys(),
+			vec![
+				twox_128(b"TemplateModule Thing1").to_vec(),
+				twox_128(b"TemplateModule Thing2").to_vec(),
+			],
+		);
+	}
 }
@@ -0,0 +1,419 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! Tracking products through a supply chain
+//!
+//! Each product moves through a small state machine:
+//!
+//! ```text
+//! Created ──ship──> Shipped ──receive──> Received ──sell──> Sold
+//!                      ^                    │
+//!                      └───────ship─────────┘
+//! ```
+//!
+//! Only accounts with the right role may make each transition. Manufacturers create products.
+//! Whoever holds a product ships it to a distributor or a retailer, who receives it, and retailers
+//! sell what they hold. `ManageOrigin` grants and revokes roles. Any other transition fails with
+//! "invalid transition", so a product can't be sold twice, nor received before it was shipped.
+//!
+//! Every transition is recorded in the product's history. A product can be forwarded any number of
+//! times, so the history keeps only the latest `MaxHistory` entries.
+
+use codec::{Decode, Encode};
+use sp_std::prelude::*;
+use sp_runtime::RuntimeDebug;
+use frame_support::{
+	decl_event, decl_module, decl_storage,
+	dispatch::DispatchResult,
+	ensure,
+	traits::{EnsureOrigin, Get},
+};
+use frame_system::{self as system, ensure_signed};
+
+pub type ProductId = u32;
+
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, RuntimeDebug)]
+pub enum Role {
+	Manufacturer,
+	Distributor,
+	Retailer,
+}
+
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, RuntimeDebug)]
+pub enum Status {
+	Created,
+	Shipped,
+	Received,
+	Sold,
+}
+
+impl Status {
+	/// Whether a product with this status may move on to `next`
+	pub fn can_become(self, next: Status) -> bool {
+		match (self, next) {
+			(Status::Created, Status::Shipped)
+			| (Status::Shipped, Status::Received)
+			| (Status::Received, Status::Shipped)
+			| (Status::Received, Status::Sold) => true,
+			_ => false,
+		}
+	}
+}
+
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct Product<AccountId> {
+	pub manufacturer: AccountId,
+	/// Who holds the product, or shipped it while it is on its way
+	pub custodian: AccountId,
+	/// Who the product is on its way to, while it is shipped
+	pub recipient: Option<AccountId>,
+	pub status: Status,
+}
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The origin that grants and revokes roles
+	type ManageOrigin: EnsureOrigin<Self::Origin>;
+
+	/// The number of transitions to remember per product. Older ones are forgotten first.
+	type MaxHistory: Get<u32>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as SupplyChain {
+		/// Whether an account has a role
+		Roles get(fn has_role): double_map T::AccountId, blake2_256(Role) => bool;
+
+		Products get(fn product): map ProductId => Option<Product<T::AccountId>>;
+
+		/// Number of products ever created; the next product's id
+		ProductCount get(fn product_count): ProductId;
+
+		/// The latest transitions of each product, oldest first, as (status, account, block)
+		History get(fn history): map ProductId => Vec<(Status, T::AccountId, T::BlockNumber)>;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as system::Trait>::AccountId,
+	{
+		RoleGranted(AccountId, Role),
+		RoleRevoked(AccountId, Role),
+		/// A product changed its status; the account that changed it
+		StatusChanged(ProductId, Status, AccountId),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		const MaxHistory: u32 = T::MaxHistory::get();
+
+		fn grant_role(origin, who: T::AccountId, role: Role) -> DispatchResult {
+			T::ManageOrigin::ensure_origin(origin)?;
+			<Roles<T>>::insert(&who, &role, true);
+			Self::deposit_event(RawEvent::RoleGranted(who, role));
+			Ok(())
+		}
+
+		fn revoke_role(origin, who: T::AccountId, role: Role) -> DispatchResult {
+			T::ManageOrigin::ensure_origin(origin)?;
+			<Roles<T>>::remove(&who, &role);
+			Self::deposit_event(RawEvent::RoleRevoked(who, role));
+			Ok(())
+		}
+
+		/// Create a product, held by the manufacturer
+		fn create(origin) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			ensure!(Self::has_role(&who, &Role::Manufacturer), "not a manufacturer");
+
+			let id = Self::product_count();
+			let next = id.checked_add(1).ok_or("product id overflow")?;
+			<Products<T>>::insert(id, Product {
+				manufacturer: who.clone(),
+				custodian: who.clone(),
+				recipient: None,
+				status: Status::Created,
+			});
+			ProductCount::put(next);
+			Self::record(id, Status::Created, who);
+			Ok(())
+		}
+
+		/// Ship a product the caller holds to `recipient`
+		fn ship(origin, id: ProductId, recipient: T::AccountId) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let mut product = Self::product(id).ok_or("no such product")?;
+			ensure!(product.custodian == who, "not the custodian");
+			ensure!(product.status.can_become(Status::Shipped), "invalid transition");
+
+			product.recipient = Some(recipient);
+			Self::transition(id, product, Status::Shipped, who);
+			Ok(())
+		}
+
+		/// Receive a product shipped to the caller
+		fn receive(origin, id: ProductId) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			ensure!(
+				Self::has_role(&who, &Role::Distributor) || Self::has_role(&who, &Role::Retailer),
+				"not a distributor or retailer"
+			);
+			let mut product = Self::product(id).ok_or("no such product")?;
+			ensure!(product.status.can_become(Status::Received), "invalid transition");
+			ensure!(product.recipient.as_ref() == Some(&who), "not the recipient");
+
+			product.custodian = who.clone();
+			product.recipient = None;
+			Self::transition(id, product, Status::Received, who);
+			Ok(())
+		}
+
+		/// Sell a product the caller holds
+		fn sell(origin, id: ProductId) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			ensure!(Self::has_role(&who, &Role::Retailer), "not a retailer");
+			let product = Self::product(id).ok_or("no such product")?;
+			ensure!(product.custodian == who, "not the custodian");
+			ensure!(product.status.can_become(Status::Sold), "invalid transition");
+
+			Self::transition(id, product, Status::Sold, who);
+			Ok(())
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	fn transition(
+		id: ProductId,
+		mut product: Product<T::AccountId>,
+		status: Status,
+		who: T::AccountId,
+	) {
+		product.status = status;
+		<Products<T>>::insert(id, product);
+		Self::record(id, status, who);
+	}
+
+	// Add a transition to the product's history, forgetting the oldest one when it is full
+	fn record(id: ProductId, status: Status, who: T::AccountId) {
+		let now = <system::Module<T>>::block_number();
+		<History<T>>::mutate(id, |history| {
+			history.push((status, who.clone(), now));
+			// The history was at most `MaxHistory` long before the push, so one removal is enough
+			if history.len() as u32 > T::MaxHistory::get() {
+				history.remove(0);
+			}
+		});
+		Self::deposit_event(RawEvent::StatusChanged(id, status, who));
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use event_assert::{assert_has_event, assert_last_event};
+
+	use sp_core::H256;
+	use frame_support::{
+		assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types,
+	};
+	use frame_system::{EnsureRoot, RawOrigin};
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup},
+		Perbill,
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const MaxHistory: u32 = 4;
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	mod supply_chain {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			supply_chain<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type ManageOrigin = EnsureRoot<u64>;
+		type MaxHistory = MaxHistory;
+	}
+
+	pub type System = system::Module<TestRuntime>;
+	pub type SupplyChain = Module<TestRuntime>;
+
+	const MANUFACTURER: u64 = 1;
+	const DISTRIBUTOR: u64 = 2;
+	const RETAILER: u64 = 3;
+
+	/// Accounts 1, 2 and 3 are a manufacturer, a distributor and a retailer. The manufacturer
+	/// created product 0.
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		let mut ext: sp_io::TestExternalities = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap()
+			.into();
+		ext.execute_with(|| {
+			System::set_block_number(1);
+			for (who, role) in &[
+				(MANUFACTURER, Role::Manufacturer),
+				(DISTRIBUTOR, Role::Distributor),
+				(RETAILER, Role::Retailer),
+			] {
+				assert_ok!(SupplyChain::grant_role(RawOrigin::Root.into(), *who, *role));
+			}
+			assert_ok!(SupplyChain::create(Origin::signed(MANUFACTURER)));
+		});
+		ext
+	}
+
+	#[test]
+	fn transitions_follow_the_state_machine() {
+		use Status::*;
+		let all = [Created, Shipped, Received, Sold];
+		let allowed = [
+			(Created, Shipped),
+			(Shipped, Received),
+			(Received, Shipped),
+			(Received, Sold),
+		];
+		for &from in &all {
+			for &to in &all {
+				let expected = allowed.contains(&(from, to));
+				assert_eq!(from.can_become(to), expected, "{:?} to {:?}", from, to);
+			}
+		}
+	}
+
+	#[test]
+	fn products_go_from_manufacturer_to_sale() {
+		new_test_ext().execute_with(|| {
+			let created = RawEvent::StatusChanged(0, Status::Created, MANUFACTURER);
+			assert_has_event::<TestRuntime>(created);
+			assert_ok!(SupplyChain::ship(Origin::signed(MANUFACTURER), 0, DISTRIBUTOR));
+			System::set_block_number(2);
+			assert_ok!(SupplyChain::receive(Origin::signed(DISTRIBUTOR), 0));
+			assert_ok!(SupplyChain::ship(Origin::signed(DISTRIBUTOR), 0, RETAILER));
+			System::set_block_number(3);
+			assert_ok!(SupplyChain::receive(Origin::signed(RETAILER), 0));
+			assert_ok!(SupplyChain::sell(Origin::signed(RETAILER), 0));
+
+			let product = SupplyChain::product(0).unwrap();
+			assert_eq!(product.status, Status::Sold);
+			assert_eq!(product.custodian, RETAILER);
+			assert_eq!(product.manufacturer, MANUFACTURER);
+			assert_last_event::<TestRuntime>(RawEvent::StatusChanged(0, Status::Sold, RETAILER));
+			// The first two transitions were forgotten
+			assert_eq!(
+				SupplyChain::history(0),
+				vec![
+					(Status::Received, DISTRIBUTOR, 2),
+					(Status::Shipped, DISTRIBUTOR, 2),
+					(Status::Received, RETAILER, 3),
+					(Status::Sold, RETAILER, 3),
+				],
+			);
+		})
+	}
+
+	#[test]
+	fn invalid_transitions_fail() {
+		new_test_ext().execute_with(|| {
+			assert_err!(SupplyChain::receive(Origin::signed(DISTRIBUTOR), 0), "invalid transition");
+			assert_err!(SupplyChain::sell(Origin::signed(RETAILER), 0), "not the custodian");
+
+			assert_ok!(SupplyChain::ship(Origin::signed(MANUFACTURER), 0, RETAILER));
+			assert_err!(
+				SupplyChain::ship(Origin::signed(MANUFACTURER), 0, RETAILER),
+				"invalid transition"
+			);
+			assert_ok!(SupplyChain::receive(Origin::signed(RETAILER), 0));
+			assert_ok!(SupplyChain::sell(Origin::signed(RETAILER), 0));
+			assert_err!(SupplyChain::sell(Origin::signed(RETAILER), 0), "invalid transition");
+			assert_err!(
+				SupplyChain::ship(Origin::signed(RETAILER), 0, DISTRIBUTOR),
+				"invalid transition"
+			);
+		})
+	}
+
+	#[test]
+	fn transitions_need_roles() {
+		new_test_ext().execute_with(|| {
+			assert_err!(SupplyChain::create(Origin::signed(DISTRIBUTOR)), "not a manufacturer");
+
+			// Only the recipient receives, and only if it may
+			assert_ok!(SupplyChain::ship(Origin::signed(MANUFACTURER), 0, 4));
+			assert_err!(SupplyChain::receive(Origin::signed(DISTRIBUTOR), 0), "not the recipient");
+			assert_err!(
+				SupplyChain::receive(Origin::signed(4), 0),
+				"not a distributor or retailer"
+			);
+			assert_ok!(SupplyChain::grant_role(RawOrigin::Root.into(), 4, Role::Distributor));
+			assert_last_event::<TestRuntime>(RawEvent::RoleGranted(4, Role::Distributor));
+			assert_ok!(SupplyChain::receive(Origin::signed(4), 0));
+
+			// Distributors don't sell
+			assert_err!(SupplyChain::sell(Origin::signed(4), 0), "not a retailer");
+
+			assert_ok!(SupplyChain::revoke_role(
+				RawOrigin::Root.into(),
+				MANUFACTURER,
+				Role::Manufacturer
+			));
+			let revoked = RawEvent::RoleRevoked(MANUFACTURER, Role::Manufacturer);
+			assert_last_event::<TestRuntime>(revoked);
+			assert_err!(SupplyChain::create(Origin::signed(MANUFACTURER)), "not a manufacturer");
+		})
+	}
+
+	#[test]
+	fn only_manage_origin_grants_roles() {
+		new_test_ext().execute_with(|| {
+			let signed = Origin::signed(MANUFACTURER);
+			assert!(SupplyChain::grant_role(signed, 4, Role::Retailer).is_err());
+			assert!(!SupplyChain::has_role(&4, &Role::Retailer));
+		})
+	}
+}
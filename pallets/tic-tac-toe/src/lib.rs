@@ -0,0 +1,550 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! Tic-tac-toe between two accounts, with move timeouts and wagers
+//!
+//! A game is a session between two accounts that lasts over many transactions:
+//!
+//! * The challenger names an opponent and a wager, which may be zero, and plays crosses.
+//! * The opponent `accept`s, matching the wager, and plays noughts. Either player can `decline` a
+//!   challenge that has not been accepted.
+//! * The players take turns to `play` a square, crosses first. Three in a row wins both wagers,
+//!   and a full board without a line is a draw, in which each player gets their wager back.
+//! * Each move, and the acceptance, must come within `MoveTimeout` blocks. If the player to move
+//!   lets the deadline pass, the other player can `claim_timeout` and win as if by three in a row.
+//!
+//! Wagers are held in reserve for the length of the game, so neither player can spend them before
+//! the result is known. A game is removed from storage once it ends.
+//!
+//! Winning with three in a row also earns the `WINNER_BADGE` from the runtime's `BadgeIssuer`.
+
+use codec::{Decode, Encode};
+use sp_runtime::RuntimeDebug;
+use frame_support::{
+	decl_event, decl_module, decl_storage,
+	dispatch::DispatchResult,
+	ensure,
+	traits::{Currency, Get, ReservableCurrency},
+};
+use frame_system::{self as system, ensure_signed};
+use badges::{BadgeId, BadgeIssuer};
+
+pub type GameIndex = u32;
+
+type BalanceOf<T> = <<T as Trait>::Currency as Currency<<T as system::Trait>::AccountId>>::Balance;
+type GameOf<T> = Game<
+	<T as system::Trait>::AccountId,
+	BalanceOf<T>,
+	<T as system::Trait>::BlockNumber,
+>;
+
+/// The squares of each line, as bits of a board numbered row by row from the top left
+const LINES: [u16; 8] = [
+	0b000_000_111, 0b000_111_000, 0b111_000_000,
+	0b001_001_001, 0b010_010_010, 0b100_100_100,
+	0b100_010_001, 0b001_010_100,
+];
+
+/// The badge for winning a game with three in a row
+pub const WINNER_BADGE: BadgeId = *b"ttt-wins";
+
+/// All nine squares
+const FULL_BOARD: u16 = 0b111_111_111;
+
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct Game<AccountId, Balance, BlockNumber> {
+	/// The account that made the challenge, and plays crosses
+	pub challenger: AccountId,
+	/// The account that was challenged, and plays noughts
+	pub opponent: AccountId,
+	/// The amount each player stakes
+	pub wager: Balance,
+	/// Whether the opponent accepted the challenge
+	pub accepted: bool,
+	/// The squares marked with a cross, as in `LINES`
+	pub crosses: u16,
+	/// The squares marked with a nought
+	pub noughts: u16,
+	/// The last block in which the opponent can accept, or the player to move can move
+	pub deadline: BlockNumber,
+}
+
+impl<AccountId, Balance, BlockNumber> Game<AccountId, Balance, BlockNumber> {
+	/// Whether crosses are to move. Crosses move first, so they are whenever both players have
+	/// made the same number of moves.
+	fn crosses_to_move(&self) -> bool {
+		self.crosses.count_ones() == self.noughts.count_ones()
+	}
+
+	/// The player to move
+	fn mover(&self) -> &AccountId {
+		if self.crosses_to_move() { &self.challenger } else { &self.opponent }
+	}
+
+	/// The player waiting for the other to move
+	fn waiting(&self) -> &AccountId {
+		if self.crosses_to_move() { &self.opponent } else { &self.challenger }
+	}
+}
+
+/// Whether `marks` include all three squares of any line
+fn has_line(marks: u16) -> bool {
+	LINES.iter().any(|line| marks & line == *line)
+}
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The currency in which wagers are made
+	type Currency: ReservableCurrency<Self::AccountId>;
+
+	/// The number of blocks a player has to accept a challenge or make a move
+	type MoveTimeout: Get<Self::BlockNumber>;
+
+	/// Awards badges to winners
+	type BadgeIssuer: BadgeIssuer<Self::AccountId>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as TicTacToe {
+		/// Games that have not ended
+		Games get(fn game): map GameIndex => Option<GameOf<T>>;
+
+		/// Number of games ever started; the next game's index
+		GameCount get(fn game_count): GameIndex;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as system::Trait>::AccountId,
+		Balance = BalanceOf<T>,
+	{
+		/// An account challenged another to a game, wagering this amount
+		Challenged(GameIndex, AccountId, AccountId, Balance),
+		/// The opponent accepted the challenge
+		Accepted(GameIndex),
+		/// A challenge was declined or withdrawn
+		Declined(GameIndex),
+		/// A player marked a square
+		Moved(GameIndex, AccountId, u8),
+		/// A player won with three in a row
+		Won(GameIndex, AccountId),
+		/// The board filled up without a line
+		Drawn(GameIndex),
+		/// A player won because the other did not move in time
+		TimedOut(GameIndex, AccountId),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		const MoveTimeout: T::BlockNumber = T::MoveTimeout::get();
+
+		/// Challenge `opponent` to a game, reserving the wager
+		fn challenge(origin, opponent: T::AccountId, wager: BalanceOf<T>) -> DispatchResult {
+			let challenger = ensure_signed(origin)?;
+			ensure!(challenger != opponent, "cannot play against yourself");
+			let index = Self::game_count();
+			let next = index.checked_add(1).ok_or("game index overflow")?;
+
+			T::Currency::reserve(&challenger, wager).map_err(|_| "cannot afford the wager")?;
+
+			<Games<T>>::insert(index, Game {
+				challenger: challenger.clone(),
+				opponent: opponent.clone(),
+				wager,
+				accepted: false,
+				crosses: 0,
+				noughts: 0,
+				deadline: Self::next_deadline(),
+			});
+			GameCount::put(next);
+
+			Self::deposit_event(RawEvent::Challenged(index, challenger, opponent, wager));
+			Ok(())
+		}
+
+		/// Accept a challenge, reserving the same wager
+		fn accept(origin, index: GameIndex) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let mut game = Self::game(index).ok_or("no such game")?;
+			ensure!(!game.accepted, "game already started");
+			ensure!(who == game.opponent, "not the opponent");
+			ensure!(Self::in_time(&game), "challenge expired");
+
+			T::Currency::reserve(&who, game.wager).map_err(|_| "cannot afford the wager")?;
+
+			game.accepted = true;
+			game.deadline = Self::next_deadline();
+			<Games<T>>::insert(index, game);
+
+			Self::deposit_event(RawEvent::Accepted(index));
+			Ok(())
+		}
+
+		/// Decline a challenge, or withdraw one that was not accepted, returning the wager
+		fn decline(origin, index: GameIndex) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let game = Self::game(index).ok_or("no such game")?;
+			ensure!(!game.accepted, "game already started");
+			ensure!(who == game.challenger || who == game.opponent, "not a player");
+
+			T::Currency::unreserve(&game.challenger, game.wager);
+			<Games<T>>::remove(index);
+
+			Self::deposit_event(RawEvent::Declined(index));
+			Ok(())
+		}
+
+		/// Mark a square, numbered 0 to 8 row by row from the top left
+		fn play(origin, index: GameIndex, square: u8) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let mut game = Self::game(index).ok_or("no such game")?;
+			ensure!(game.accepted, "game not started");
+			ensure!(&who == game.mover(), "not your turn");
+			ensure!(Self::in_time(&game), "move timed out");
+			ensure!(square < 9, "no such square");
+			let bit = 1 << square;
+			ensure!((game.crosses | game.noughts) & bit == 0, "square already marked");
+
+			let marks = if game.crosses_to_move() { &mut game.crosses } else { &mut game.noughts };
+			*marks |= bit;
+			let won = has_line(*marks);
+			Self::deposit_event(RawEvent::Moved(index, who.clone(), square));
+
+			if won {
+				Self::pay_winner(index, &game, &who);
+				T::BadgeIssuer::issue(&who, WINNER_BADGE);
+				Self::deposit_event(RawEvent::Won(index, who));
+			} else if game.crosses | game.noughts == FULL_BOARD {
+				T::Currency::unreserve(&game.challenger, game.wager);
+				T::Currency::unreserve(&game.opponent, game.wager);
+				<Games<T>>::remove(index);
+				Self::deposit_event(RawEvent::Drawn(index));
+			} else {
+				game.deadline = Self::next_deadline();
+				<Games<T>>::insert(index, game);
+			}
+			Ok(())
+		}
+
+		/// Win a game in which the other player did not move in time
+		fn claim_timeout(origin, index: GameIndex) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let game = Self::game(index).ok_or("no such game")?;
+			ensure!(game.accepted, "game not started");
+			ensure!(&who == game.waiting(), "not waiting for the other player");
+			ensure!(!Self::in_time(&game), "move not timed out");
+
+			Self::pay_winner(index, &game, &who);
+
+			Self::deposit_event(RawEvent::TimedOut(index, who));
+			Ok(())
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// The deadline for a move made next, counting from the current block
+	fn next_deadline() -> T::BlockNumber {
+		<system::Module<T>>::block_number() + T::MoveTimeout::get()
+	}
+
+	/// Whether the game's deadline has not passed
+	fn in_time(game: &GameOf<T>) -> bool {
+		<system::Module<T>>::block_number() <= game.deadline
+	}
+
+	/// End a game, giving the winner both wagers
+	fn pay_winner(index: GameIndex, game: &GameOf<T>, winner: &T::AccountId) {
+		let loser = if winner == &game.challenger { &game.opponent } else { &game.challenger };
+		let (winnings, _) = T::Currency::slash_reserved(loser, game.wager);
+		T::Currency::resolve_creating(winner, winnings);
+		T::Currency::unreserve(winner, game.wager);
+		<Games<T>>::remove(index);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use event_assert::assert_last_event;
+	use sp_core::H256;
+	use frame_support::{
+		assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types
+	};
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup},
+		Perbill,
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const ExistentialDeposit: u64 = 0;
+		pub const TransferFee: u64 = 0;
+		pub const CreationFee: u64 = 0;
+
+		pub const MoveTimeout: u64 = 5;
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	impl balances::Trait for TestRuntime {
+		type Balance = u64;
+		type OnFreeBalanceZero = ();
+		type OnNewAccount = ();
+		type Event = TestEvent;
+		type TransferPayment = ();
+		type DustRemoval = ();
+		type ExistentialDeposit = ExistentialDeposit;
+		type TransferFee = TransferFee;
+		type CreationFee = CreationFee;
+	}
+
+	impl badges::Trait for TestRuntime {
+		type Event = TestEvent;
+	}
+
+	mod tic_tac_toe {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			balances<T>,
+			badges<T>,
+			tic_tac_toe<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type Currency = balances::Module<Self>;
+		type MoveTimeout = MoveTimeout;
+		type BadgeIssuer = badges::Module<Self>;
+	}
+
+	pub type System = system::Module<TestRuntime>;
+	pub type Balances = balances::Module<TestRuntime>;
+	pub type Badges = badges::Module<TestRuntime>;
+	pub type TicTacToe = Module<TestRuntime>;
+
+	/// Accounts 1, 2 and 3 hold 100 each, in block 1
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		let mut t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		balances::GenesisConfig::<TestRuntime> {
+			balances: vec![(1, 100), (2, 100), (3, 100)],
+			vesting: vec![],
+		}
+		.assimilate_storage(&mut t)
+		.unwrap();
+		let mut ext: sp_io::TestExternalities = t.into();
+		ext.execute_with(|| System::set_block_number(1));
+		ext
+	}
+
+	/// Start game 0 between 1, playing crosses, and 2, each wagering 30
+	fn start_game() {
+		assert_ok!(TicTacToe::challenge(Origin::signed(1), 2, 30));
+		assert_ok!(TicTacToe::accept(Origin::signed(2), 0));
+	}
+
+	/// Play `squares` in turn, crosses first
+	fn play(squares: &[u8]) {
+		for (turn, square) in squares.iter().enumerate() {
+			let player = if turn % 2 == 0 { 1 } else { 2 };
+			assert_ok!(TicTacToe::play(Origin::signed(player), 0, *square));
+		}
+	}
+
+	#[test]
+	fn challenges_reserve_wagers() {
+		new_test_ext().execute_with(|| {
+			assert_err!(
+				TicTacToe::challenge(Origin::signed(1), 1, 30),
+				"cannot play against yourself"
+			);
+			assert_err!(TicTacToe::challenge(Origin::signed(1), 2, 101), "cannot afford the wager");
+
+			assert_ok!(TicTacToe::challenge(Origin::signed(1), 2, 30));
+			assert_last_event::<TestRuntime>(RawEvent::Challenged(0, 1, 2, 30));
+			assert_eq!(Balances::reserved_balance(&1), 30);
+			assert_err!(TicTacToe::play(Origin::signed(1), 0, 4), "game not started");
+			assert_err!(TicTacToe::accept(Origin::signed(3), 0), "not the opponent");
+
+			assert_ok!(TicTacToe::accept(Origin::signed(2), 0));
+			assert_eq!(Balances::reserved_balance(&2), 30);
+			assert_err!(TicTacToe::decline(Origin::signed(2), 0), "game already started");
+		})
+	}
+
+	#[test]
+	fn declined_challenges_return_the_wager() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(TicTacToe::challenge(Origin::signed(1), 2, 30));
+			assert_err!(TicTacToe::decline(Origin::signed(3), 0), "not a player");
+			assert_ok!(TicTacToe::decline(Origin::signed(2), 0));
+			assert_last_event::<TestRuntime>(RawEvent::Declined(0));
+			assert_eq!(Balances::free_balance(&1), 100);
+			assert_eq!(TicTacToe::game(0), None);
+
+			// A challenge nobody accepts in time expires, and the challenger can withdraw it
+			assert_ok!(TicTacToe::challenge(Origin::signed(1), 2, 30));
+			System::set_block_number(7);
+			assert_err!(TicTacToe::accept(Origin::signed(2), 1), "challenge expired");
+			assert_ok!(TicTacToe::decline(Origin::signed(1), 1));
+			assert_eq!(Balances::free_balance(&1), 100);
+		})
+	}
+
+	#[test]
+	fn moves_are_validated() {
+		new_test_ext().execute_with(|| {
+			start_game();
+			assert_err!(TicTacToe::play(Origin::signed(2), 0, 4), "not your turn");
+			assert_err!(TicTacToe::play(Origin::signed(3), 0, 4), "not your turn");
+			assert_err!(TicTacToe::play(Origin::signed(1), 0, 9), "no such square");
+
+			assert_ok!(TicTacToe::play(Origin::signed(1), 0, 4));
+			assert_last_event::<TestRuntime>(RawEvent::Moved(0, 1, 4));
+			assert_err!(TicTacToe::play(Origin::signed(1), 0, 0), "not your turn");
+			assert_err!(TicTacToe::play(Origin::signed(2), 0, 4), "square already marked");
+
+			let game = TicTacToe::game(0).unwrap();
+			assert_eq!((game.crosses, game.noughts), (1 << 4, 0));
+		})
+	}
+
+	#[test]
+	fn three_in_a_row_wins_both_wagers() {
+		new_test_ext().execute_with(|| {
+			start_game();
+			// Crosses take the diagonal from the top left
+			play(&[0, 1, 4, 2, 8]);
+			assert_last_event::<TestRuntime>(RawEvent::Won(0, 1));
+			assert_eq!(Balances::free_balance(&1), 130);
+			assert_eq!(Balances::free_balance(&2), 70);
+			assert_eq!(Balances::reserved_balance(&1), 0);
+			assert_eq!(Balances::reserved_balance(&2), 0);
+			assert_eq!(TicTacToe::game(0), None);
+			assert!(Badges::has_badge(&1, &WINNER_BADGE));
+			assert!(!Badges::has_badge(&2, &WINNER_BADGE));
+		})
+	}
+
+	#[test]
+	fn noughts_can_win() {
+		new_test_ext().execute_with(|| {
+			start_game();
+			// Noughts take the middle column
+			play(&[0, 1, 2, 4, 6, 7]);
+			assert_last_event::<TestRuntime>(RawEvent::Won(0, 2));
+			assert_eq!(Balances::free_balance(&2), 130);
+		})
+	}
+
+	#[test]
+	fn full_boards_are_drawn() {
+		new_test_ext().execute_with(|| {
+			start_game();
+			// X O X
+			// X O O
+			// O X X
+			play(&[0, 1, 2, 4, 3, 5, 7, 6, 8]);
+			assert_last_event::<TestRuntime>(RawEvent::Drawn(0));
+			assert_eq!(Balances::free_balance(&1), 100);
+			assert_eq!(Balances::free_balance(&2), 100);
+			assert_eq!(TicTacToe::game(0), None);
+		})
+	}
+
+	#[test]
+	fn the_waiting_player_claims_a_timeout() {
+		new_test_ext().execute_with(|| {
+			start_game();
+			play(&[4]);
+			assert_err!(TicTacToe::claim_timeout(Origin::signed(1), 0), "move not timed out");
+
+			// Noughts had until block 6 to move
+			System::set_block_number(6);
+			assert_err!(TicTacToe::claim_timeout(Origin::signed(1), 0), "move not timed out");
+			System::set_block_number(7);
+			assert_err!(TicTacToe::play(Origin::signed(2), 0, 0), "move timed out");
+			assert_err!(
+				TicTacToe::claim_timeout(Origin::signed(2), 0),
+				"not waiting for the other player"
+			);
+
+			assert_ok!(TicTacToe::claim_timeout(Origin::signed(1), 0));
+			assert_last_event::<TestRuntime>(RawEvent::TimedOut(0, 1));
+			assert_eq!(Balances::free_balance(&1), 130);
+			assert_eq!(Balances::free_balance(&2), 70);
+			// Badges are only for wins on the board
+			assert!(!Badges::has_badge(&1, &WINNER_BADGE));
+		})
+	}
+
+	#[test]
+	fn each_move_restarts_the_clock() {
+		new_test_ext().execute_with(|| {
+			start_game();
+			System::set_block_number(5);
+			assert_ok!(TicTacToe::play(Origin::signed(1), 0, 0));
+			System::set_block_number(10);
+			assert_ok!(TicTacToe::play(Origin::signed(2), 0, 1));
+			assert_eq!(TicTacToe::game(0).unwrap().deadline, 15);
+
+			// Crosses had until block 15 to move, not until block 6
+			System::set_block_number(15);
+			assert_err!(TicTacToe::claim_timeout(Origin::signed(2), 0), "move not timed out");
+			assert_ok!(TicTacToe::play(Origin::signed(1), 0, 4));
+		})
+	}
+
+	#[test]
+	fn wagers_are_optional() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(TicTacToe::challenge(Origin::signed(1), 2, 0));
+			assert_ok!(TicTacToe::accept(Origin::signed(2), 0));
+			play(&[0, 1, 4, 2, 8]);
+			assert_last_event::<TestRuntime>(RawEvent::Won(0, 1));
+			assert_eq!(Balances::free_balance(&1), 100);
+			assert_eq!(Balances::free_balance(&2), 100);
+		})
+	}
+}
@@ -0,0 +1,46 @@
+//! RPC interface for retrieving blobs stored with the transaction-storage pallet.
+//!
+//! The pallet's offchain worker writes every blob to the node's persistent offchain storage. This
+//! RPC reads them back from there, so it needs the offchain storage rather than the client.
+
+use jsonrpc_core::{Error as RpcError, ErrorCode, Result};
+use jsonrpc_derive::rpc;
+use sp_core::{offchain::OffchainStorage, Bytes, H256};
+use sp_io::hashing::blake2_256;
+
+#[rpc]
+pub trait TransactionStorageApi {
+	/// The blob with the given blake2 hash, if this node has it in its offchain storage
+	#[rpc(name = "transactionStorage_getBlob")]
+	fn get_blob(&self, hash: H256) -> Result<Option<Bytes>>;
+}
+
+/// A struct that implements the `TransactionStorageApi`.
+pub struct TransactionStorage<S> {
+	storage: S,
+}
+
+impl<S> TransactionStorage<S> {
+	/// Create new `TransactionStorage` instance reading from the given offchain storage.
+	pub fn new(storage: S) -> Self {
+		Self { storage }
+	}
+}
+
+impl<S> TransactionStorageApi for TransactionStorage<S>
+where
+	S: OffchainStorage + 'static,
+{
+	fn get_blob(&self, hash: H256) -> Result<Option<Bytes>> {
+		let key = transaction_storage::offchain_key(&hash);
+		match self.storage.get(sp_offchain::STORAGE_PREFIX, &key) {
+			// Offchain storage is local to this node, so don't serve anything that was tampered with
+			Some(blob) if blake2_256(&blob) != hash.0 => Err(RpcError {
+				code: ErrorCode::ServerError(1),
+				message: "Stored blob does not match its hash".into(),
+				data: None,
+			}),
+			blob => Ok(blob.map(Into::into)),
+		}
+	}
+}
@@ -0,0 +1,412 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! Storing data blobs off the state
+//!
+//! Users submit arbitrary blobs of data with `store`. The weight and an explicit storage fee both
+//! grow with the blob's length. Only the blob's blake2 hash and length are kept in runtime storage.
+//! The blob itself is handed to the offchain worker, which writes it to the node's persistent
+//! offchain storage. From there the `transaction-storage-rpc` crate serves it by hash.
+//!
+//! How often an account may store blobs is up to the runtime's `RateLimiter`.
+//!
+//! The blob has to reach the offchain worker somehow. This pallet keeps it in `Pending` for
+//! exactly one block and clears it at the start of the next. The state of the latest block
+//! therefore holds no old blobs, but the blob is still part of the state of the block that stored
+//! it, and of any proof of that state. Nodes that keep old states keep the blob too.
+//!
+//! A node only gets the blob if its offchain worker runs for the block that stored it. Workers
+//! don't run for blocks a node imports while it syncs, so such a node never has the blob.
+
+use codec::{Decode, Encode};
+use sp_core::{offchain::StorageKind, H256};
+use sp_io::hashing::blake2_256;
+use sp_runtime::RuntimeDebug;
+use sp_std::prelude::*;
+use frame_support::{
+	decl_event, decl_module, decl_storage,
+	dispatch::{DispatchResult, PaysFee, WeighData},
+	ensure,
+	traits::{Currency, ExistenceRequirement, Get, OnUnbalanced, WithdrawReason},
+	weights::{ClassifyDispatch, DispatchClass, Weight},
+};
+use frame_system::{self as system, ensure_signed};
+use rate_limiter::RateLimiter;
+
+/// Prefix of the offchain storage keys under which blobs are written
+pub const OFFCHAIN_PREFIX: &[u8] = b"transaction-storage/";
+
+/// The offchain storage key of the blob with the given hash
+pub fn offchain_key(hash: &H256) -> Vec<u8> {
+	let mut key = OFFCHAIN_PREFIX.to_vec();
+	key.extend_from_slice(hash.as_bytes());
+	key
+}
+
+type BalanceOf<T> = <<T as Trait>::Currency as Currency<<T as system::Trait>::AccountId>>::Balance;
+type NegativeImbalanceOf<T> =
+	<<T as Trait>::Currency as Currency<<T as system::Trait>::AccountId>>::NegativeImbalance;
+
+/// What the chain remembers about a stored blob
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct BlobInfo<AccountId, BlockNumber> {
+	/// Who paid to store the blob
+	pub owner: AccountId,
+	/// Length of the blob in bytes
+	pub len: u32,
+	/// The block in which the blob was stored
+	pub block: BlockNumber,
+}
+
+/// Weighs a call by the length of its single `Vec<u8>` argument. The weight is the byte count
+/// times the field of this struct.
+pub struct PerByte(pub Weight);
+
+impl WeighData<(&Vec<u8>,)> for PerByte {
+	fn weigh_data(&self, (data,): (&Vec<u8>,)) -> Weight {
+		(data.len() as Weight).saturating_mul(self.0)
+	}
+}
+
+impl PaysFee for PerByte {
+	fn pays_fee(&self) -> bool {
+		true
+	}
+}
+
+impl<T> ClassifyDispatch<T> for PerByte {
+	fn classify_dispatch(&self, _: T) -> DispatchClass {
+		Default::default()
+	}
+}
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The currency in which storage fees are paid
+	type Currency: Currency<Self::AccountId>;
+
+	/// Storage fee charged per byte of blob
+	type ByteFee: Get<BalanceOf<Self>>;
+
+	/// The longest blob that may be stored
+	type MaxBlobLength: Get<u32>;
+
+	/// Handler for the storage fees
+	type FeeDestination: OnUnbalanced<NegativeImbalanceOf<Self>>;
+
+	/// Limits how often an account may store blobs. `()` does not limit it at all.
+	type RateLimiter: RateLimiter<Self::AccountId>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as TransactionStorage {
+		/// Hash and length of every stored blob, keyed by the blob's blake2 hash
+		Blobs get(fn blob_info): map H256 => Option<BlobInfo<T::AccountId, T::BlockNumber>>;
+
+		/// Blobs stored in this block, waiting to be written offchain. Cleared every block.
+		Pending get(fn pending): Vec<(H256, Vec<u8>)>;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as system::Trait>::AccountId,
+		Balance = BalanceOf<T>,
+	{
+		/// A blob was stored (owner, blake2 hash, length, fee paid)
+		Stored(AccountId, H256, u32, Balance),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		const ByteFee: BalanceOf<T> = T::ByteFee::get();
+
+		const MaxBlobLength: u32 = T::MaxBlobLength::get();
+
+		fn on_initialize(_n: T::BlockNumber) {
+			// Last block's offchain worker has already seen these
+			<Pending>::kill();
+		}
+
+		/// Store a blob of data, paying `ByteFee` for every byte of it
+		#[weight = PerByte(10)]
+		fn store(origin, data: Vec<u8>) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let len = data.len() as u32;
+			ensure!(len > 0, "blob is empty");
+			ensure!(len <= T::MaxBlobLength::get(), "blob is too long");
+
+			let hash = H256::from(blake2_256(&data));
+			ensure!(!<Blobs<T>>::exists(&hash), "blob is already stored");
+			ensure!(
+				T::RateLimiter::remaining(&who) > 0,
+				"too many blobs, wait for the next window"
+			);
+
+			let fee = T::ByteFee::get() * len.into();
+			let imbalance = T::Currency::withdraw(
+				&who,
+				fee,
+				WithdrawReason::Fee.into(),
+				ExistenceRequirement::KeepAlive,
+			)?;
+			T::FeeDestination::on_unbalanced(imbalance);
+			// Only counted once the blob is paid for. There is an action left, so this succeeds.
+			T::RateLimiter::try_act(&who)?;
+
+			let info = BlobInfo {
+				owner: who.clone(),
+				len,
+				block: <system::Module<T>>::block_number(),
+			};
+			<Blobs<T>>::insert(&hash, info);
+			<Pending>::mutate(|pending| pending.push((hash, data)));
+
+			Self::deposit_event(RawEvent::Stored(who, hash, len, fee));
+			Ok(())
+		}
+
+		fn offchain_worker(_n: T::BlockNumber) {
+			for (hash, data) in Self::pending() {
+				sp_io::offchain::local_storage_set(
+					StorageKind::PERSISTENT,
+					&offchain_key(&hash),
+					&data,
+				);
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use event_assert::{assert_has_event, assert_last_event};
+
+	use frame_support::{
+		assert_ok, impl_outer_event, impl_outer_origin, parameter_types
+	};
+	use frame_system::RawOrigin;
+	use sp_core::offchain::{testing::TestOffchainExt, OffchainExt};
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup, OffchainWorker, OnInitialize},
+		Perbill,
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const ExistentialDeposit: u64 = 0;
+		pub const TransferFee: u64 = 0;
+		pub const CreationFee: u64 = 0;
+
+		pub const ByteFee: u64 = 2;
+		pub const MaxBlobLength: u32 = 32;
+
+		pub const MaxActions: u32 = 3;
+		pub const Period: u64 = 10;
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	impl balances::Trait for TestRuntime {
+		type Balance = u64;
+		type OnFreeBalanceZero = ();
+		type OnNewAccount = ();
+		type Event = TestEvent;
+		type TransferPayment = ();
+		type DustRemoval = ();
+		type ExistentialDeposit = ExistentialDeposit;
+		type TransferFee = TransferFee;
+		type CreationFee = CreationFee;
+	}
+
+	impl rate_limiter::Trait for TestRuntime {
+		type MaxActions = MaxActions;
+		type Period = Period;
+	}
+
+	mod transaction_storage {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			balances<T>,
+			transaction_storage<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type Currency = balances::Module<Self>;
+		type ByteFee = ByteFee;
+		type MaxBlobLength = MaxBlobLength;
+		// Dropping the imbalance burns it
+		type FeeDestination = ();
+		type RateLimiter = rate_limiter::Module<Self>;
+	}
+
+	pub type System = system::Module<TestRuntime>;
+	pub type Balances = balances::Module<TestRuntime>;
+	pub type TransactionStorage = Module<TestRuntime>;
+
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		let mut t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		balances::GenesisConfig::<TestRuntime> {
+			balances: vec![(1, 100), (2, 10)],
+			vesting: vec![],
+		}
+		.assimilate_storage(&mut t)
+		.unwrap();
+		let mut ext: sp_io::TestExternalities = t.into();
+		let (offchain, _state) = TestOffchainExt::new();
+		ext.register_extension(OffchainExt::new(offchain));
+		ext
+	}
+
+	#[test]
+	fn store_keeps_hash_and_length() {
+		new_test_ext().execute_with(|| {
+			System::set_block_number(1);
+			let data = b"hello recipes".to_vec();
+			let hash = H256::from(blake2_256(&data));
+
+			assert_ok!(TransactionStorage::store(Origin::signed(1), data.clone()));
+			assert_eq!(
+				TransactionStorage::blob_info(hash),
+				Some(BlobInfo { owner: 1, len: 13, block: 1 })
+			);
+			assert_eq!(TransactionStorage::pending(), vec![(hash, data)]);
+
+			assert_has_event::<TestRuntime>(RawEvent::Stored(1, hash, 13, 26));
+		})
+	}
+
+	#[test]
+	fn fee_is_proportional_to_length() {
+		new_test_ext().execute_with(|| {
+			let issuance = Balances::total_issuance();
+			assert_ok!(TransactionStorage::store(Origin::signed(1), vec![7; 10]));
+			assert_eq!(Balances::free_balance(&1), 80);
+			assert_ok!(TransactionStorage::store(Origin::signed(1), vec![7; 20]));
+			assert_eq!(Balances::free_balance(&1), 40);
+			assert_eq!(Balances::total_issuance(), issuance - 60);
+		})
+	}
+
+	#[test]
+	fn cannot_store_without_paying() {
+		new_test_ext().execute_with(|| {
+			assert!(TransactionStorage::store(Origin::signed(2), vec![1; 6]).is_err());
+			assert_eq!(TransactionStorage::blob_info(H256::from(blake2_256(&[1; 6]))), None);
+			assert!(TransactionStorage::pending().is_empty());
+		})
+	}
+
+	#[test]
+	fn rejects_bad_blobs() {
+		new_test_ext().execute_with(|| {
+			assert!(TransactionStorage::store(RawOrigin::None.into(), vec![1]).is_err());
+			assert!(TransactionStorage::store(Origin::signed(1), vec![]).is_err());
+			assert!(TransactionStorage::store(Origin::signed(1), vec![1; 33]).is_err());
+
+			assert_ok!(TransactionStorage::store(Origin::signed(1), vec![1; 4]));
+			assert!(TransactionStorage::store(Origin::signed(1), vec![1; 4]).is_err());
+		})
+	}
+
+	#[test]
+	fn stores_are_rate_limited() {
+		new_test_ext().execute_with(|| {
+			System::set_block_number(1);
+			for i in 0..3 {
+				assert_ok!(TransactionStorage::store(Origin::signed(1), vec![i; 4]));
+			}
+			assert!(TransactionStorage::store(Origin::signed(1), vec![3; 4]).is_err());
+			assert_eq!(TransactionStorage::blob_info(H256::from(blake2_256(&[3; 4]))), None);
+
+			// A failed store does not use up an action
+			assert!(TransactionStorage::store(Origin::signed(2), vec![9; 6]).is_err());
+			assert_eq!(rate_limiter::Module::<TestRuntime>::remaining(&2), 3);
+
+			// The last event is still the third store
+			let third = H256::from(blake2_256(&[2; 4]));
+			assert_last_event::<TestRuntime>(RawEvent::Stored(1, third, 4, 8));
+
+			System::set_block_number(10);
+			assert_ok!(TransactionStorage::store(Origin::signed(1), vec![3; 4]));
+			let fourth = H256::from(blake2_256(&[3; 4]));
+			assert_last_event::<TestRuntime>(RawEvent::Stored(1, fourth, 4, 8));
+		})
+	}
+
+	#[test]
+	fn pending_blobs_are_cleared_next_block() {
+		new_test_ext().execute_with(|| {
+			let data = vec![3; 5];
+			let hash = H256::from(blake2_256(&data));
+			assert_ok!(TransactionStorage::store(Origin::signed(1), data));
+
+			TransactionStorage::on_initialize(2);
+			assert!(TransactionStorage::pending().is_empty());
+			// The hash and length stay on chain
+			assert!(TransactionStorage::blob_info(hash).is_some());
+		})
+	}
+
+	#[test]
+	fn offchain_worker_writes_blobs() {
+		new_test_ext().execute_with(|| {
+			let data = b"offchain bytes".to_vec();
+			let hash = H256::from(blake2_256(&data));
+			assert_ok!(TransactionStorage::store(Origin::signed(1), data.clone()));
+
+			TransactionStorage::offchain_worker(1);
+			assert_eq!(
+				sp_io::offchain::local_storage_get(StorageKind::PERSISTENT, &offchain_key(&hash)),
+				Some(data)
+			);
+		})
+	}
+
+	#[test]
+	fn weight_grows_with_length() {
+		assert_eq!(PerByte(10).weigh_data((&vec![0; 0],)), 0);
+		assert_eq!(PerByte(10).weigh_data((&vec![0; 25],)), 250);
+	}
+}
@@ -0,0 +1,478 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! Uncle rewards for proof of work chains
+//!
+//! In proof of work, two miners often find a block at the same height and one of them ends up
+//! off the canonical chain. Including such an *uncle* header in a later block lets its miner
+//! still earn a reduced reward, which makes mining less of a lottery for miners with poor
+//! connectivity.
+//!
+//! The block author submits uncles through an inherent. Before paying anything, the runtime
+//! verifies each header itself: its parent must be canonical, it must be recent, it must not have
+//! been included before, and its proof of work seal must be valid.
+
+use codec::Encode;
+use sp_std::prelude::*;
+use sp_std::marker::PhantomData;
+use sp_core::U256;
+use sp_inherents::{InherentData, InherentIdentifier, MakeFatalError, ProvideInherent};
+use sp_io::hashing::blake2_256;
+use sp_runtime::{
+	generic::DigestItem,
+	traits::{Header as HeaderT, One, Saturating, UniqueSaturatedInto, Zero},
+	ConsensusEngineId, Perbill,
+};
+use frame_support::{
+	decl_event, decl_module, decl_storage,
+	dispatch::DispatchResult,
+	ensure,
+	traits::{Currency, FindAuthor, Get},
+};
+use frame_system::{self as system, ensure_none};
+
+/// The uncles inherent uses the same identifier as `sc-consensus-uncles`, so the node can use its
+/// ready-made inherent data provider.
+pub const INHERENT_IDENTIFIER: InherentIdentifier = sp_authorship::INHERENT_IDENTIFIER;
+
+/// The engine id of proof of work seals and pre-runtime digests
+pub const POW_ENGINE_ID: ConsensusEngineId = *b"pow_";
+
+type BalanceOf<T> = <<T as Trait>::Currency as Currency<<T as system::Trait>::AccountId>>::Balance;
+
+/// Checks the proof of work seal of a header
+pub trait VerifySeal<Header> {
+	fn verify_seal(header: &Header) -> bool;
+}
+
+/// A simple blake2 proof of work: the hash of the pre-seal header hash and the seal, read as a
+/// number, times `Difficulty` must not overflow 256 bits.
+pub struct Blake2Seal<Difficulty>(PhantomData<Difficulty>);
+
+impl<Header: HeaderT, Difficulty: Get<u128>> VerifySeal<Header> for Blake2Seal<Difficulty> {
+	fn verify_seal(header: &Header) -> bool {
+		// The seal is the last digest item, added after the header was hashed for mining
+		let mut header = header.clone();
+		let seal = match header.digest_mut().pop() {
+			Some(DigestItem::Seal(id, seal)) if id == POW_ENGINE_ID => seal,
+			_ => return false,
+		};
+		let pre_hash = header.hash();
+
+		let work = blake2_256(&(pre_hash, seal).encode());
+		U256::from(&work[..]).checked_mul(U256::from(Difficulty::get())).is_some()
+	}
+}
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The currency in which uncle rewards are paid
+	type Currency: Currency<Self::AccountId>;
+
+	/// Identifies the miner of an uncle from its pre-runtime digests
+	type FindAuthor: FindAuthor<Self::AccountId>;
+
+	/// Checks that an uncle really did the work
+	type VerifySeal: VerifySeal<Self::Header>;
+
+	/// How many blocks back an uncle may be
+	type UncleGenerations: Get<Self::BlockNumber>;
+
+	/// How many uncles a block may include
+	type MaxUncles: Get<u32>;
+
+	/// Reward for an uncle one block older than the block that includes it. Older uncles earn
+	/// proportionally less.
+	type UncleReward: Get<BalanceOf<Self>>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as Uncles {
+		/// Recently included uncles, so none is included twice. Pruned once they are too old to
+		/// be included anyway.
+		Included get(fn included): Vec<(T::BlockNumber, T::Hash)>;
+
+		/// Whether uncles were already set in this block
+		DidSetUncles: bool;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as system::Trait>::AccountId,
+		Balance = BalanceOf<T>,
+		Hash = <T as system::Trait>::Hash,
+	{
+		/// An uncle was included and its miner rewarded
+		UncleRewarded(Hash, AccountId, Balance),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		const UncleGenerations: T::BlockNumber = T::UncleGenerations::get();
+
+		const MaxUncles: u32 = T::MaxUncles::get();
+
+		const UncleReward: BalanceOf<T> = T::UncleReward::get();
+
+		/// Include uncles in this block and reward their miners. This is an inherent, submitted
+		/// by the block author.
+		fn set_uncles(origin, uncles: Vec<T::Header>) -> DispatchResult {
+			ensure_none(origin)?;
+			ensure!(!DidSetUncles::get(), "uncles already set in this block");
+			ensure!(uncles.len() as u32 <= T::MaxUncles::get(), "too many uncles");
+
+			// Check every uncle before paying anyone
+			let mut accepted = Vec::new();
+			let mut rewards = Vec::new();
+			for uncle in uncles.iter() {
+				let (miner, reward) = Self::verify_uncle(uncle, &accepted)?;
+				accepted.push(uncle.hash());
+				rewards.push((miner, reward));
+			}
+
+			for ((uncle, hash), (miner, reward)) in uncles.iter().zip(accepted).zip(rewards) {
+				<Included<T>>::mutate(|included| included.push((*uncle.number(), hash)));
+				// Dropping the positive imbalance increases total issuance
+				let _ = T::Currency::deposit_creating(&miner, reward);
+				Self::deposit_event(RawEvent::UncleRewarded(hash, miner, reward));
+			}
+
+			DidSetUncles::put(true);
+			Ok(())
+		}
+
+		fn on_initialize(now: T::BlockNumber) {
+			let oldest = now.saturating_sub(T::UncleGenerations::get());
+			<Included<T>>::mutate(|included| included.retain(|(number, _)| *number >= oldest));
+		}
+
+		fn on_finalize(_n: T::BlockNumber) {
+			DidSetUncles::kill();
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// Verify an uncle header, returning its miner and the reward they earn.
+	///
+	/// `accepted` holds the hashes of uncles already accepted in the same block.
+	pub fn verify_uncle(
+		uncle: &T::Header,
+		accepted: &[T::Hash],
+	) -> Result<(T::AccountId, BalanceOf<T>), &'static str> {
+		let now = <system::Module<T>>::block_number();
+		let number = *uncle.number();
+		ensure!(number > Zero::zero() && number < now, "uncle must be older than this block");
+
+		let age = now - number;
+		let generations = T::UncleGenerations::get();
+		ensure!(age <= generations, "uncle is too old");
+
+		let parent = <system::Module<T>>::block_hash(number - One::one());
+		ensure!(*uncle.parent_hash() == parent, "uncle's parent is not canonical");
+
+		let hash = uncle.hash();
+		ensure!(<system::Module<T>>::block_hash(number) != hash, "uncle is a canonical block");
+		ensure!(
+			!accepted.contains(&hash) && !Self::included().iter().any(|(_, h)| *h == hash),
+			"uncle already included"
+		);
+
+		ensure!(T::VerifySeal::verify_seal(uncle), "uncle has an invalid seal");
+
+		let digests = uncle.digest().logs().iter().filter_map(|d| d.as_pre_runtime());
+		let miner = T::FindAuthor::find_author(digests).ok_or("uncle has no author")?;
+
+		// An uncle `age` blocks old earns (generations + 1 - age) / generations of the reward
+		let generations: u64 = generations.unique_saturated_into();
+		let age: u64 = age.unique_saturated_into();
+		let fraction = Perbill::from_rational_approximation(generations + 1 - age, generations);
+		Ok((miner, fraction * T::UncleReward::get()))
+	}
+}
+
+impl<T: Trait> ProvideInherent for Module<T> {
+	type Call = Call<T>;
+	type Error = MakeFatalError<()>;
+	const INHERENT_IDENTIFIER: InherentIdentifier = INHERENT_IDENTIFIER;
+
+	fn create_inherent(data: &InherentData) -> Option<Self::Call> {
+		let candidates = data.get_data::<Vec<T::Header>>(&INHERENT_IDENTIFIER).ok()??;
+
+		// Offer only uncles that will be accepted, so the block stays valid
+		let mut accepted = Vec::new();
+		let uncles = candidates
+			.into_iter()
+			.filter(|uncle| {
+				if accepted.len() as u32 >= T::MaxUncles::get() {
+					return false;
+				}
+				match Self::verify_uncle(uncle, &accepted) {
+					Ok(_) => {
+						accepted.push(uncle.hash());
+						true
+					}
+					Err(_) => false,
+				}
+			})
+			.collect::<Vec<_>>();
+
+		if uncles.is_empty() {
+			None
+		} else {
+			Some(Call::set_uncles(uncles))
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use std::cell::RefCell;
+	use codec::Decode;
+	use sp_core::H256;
+	use frame_support::{
+		assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types
+	};
+	use frame_system::RawOrigin;
+	use sp_runtime::{
+		generic::Digest,
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup, OnFinalize, OnInitialize},
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	thread_local! {
+		static DIFFICULTY: RefCell<u128> = RefCell::new(1);
+	}
+
+	pub struct Difficulty;
+	impl Get<u128> for Difficulty {
+		fn get() -> u128 {
+			DIFFICULTY.with(|v| *v.borrow())
+		}
+	}
+
+	/// Reads the miner from the `pow_` pre-runtime digest
+	pub struct PowAuthor;
+	impl FindAuthor<u64> for PowAuthor {
+		fn find_author<'a, I>(digests: I) -> Option<u64>
+		where
+			I: 'a + IntoIterator<Item = (ConsensusEngineId, &'a [u8])>,
+		{
+			digests
+				.into_iter()
+				.find(|(id, _)| *id == POW_ENGINE_ID)
+				.and_then(|(_, mut data)| u64::decode(&mut data).ok())
+		}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const ExistentialDeposit: u64 = 0;
+		pub const TransferFee: u64 = 0;
+		pub const CreationFee: u64 = 0;
+
+		pub const UncleGenerations: u64 = 4;
+		pub const MaxUncles: u32 = 2;
+		pub const UncleReward: u64 = 40;
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	impl balances::Trait for TestRuntime {
+		type Balance = u64;
+		type OnFreeBalanceZero = ();
+		type OnNewAccount = ();
+		type Event = TestEvent;
+		type TransferPayment = ();
+		type DustRemoval = ();
+		type ExistentialDeposit = ExistentialDeposit;
+		type TransferFee = TransferFee;
+		type CreationFee = CreationFee;
+	}
+
+	mod uncles {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			balances<T>,
+			uncles<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type Currency = balances::Module<Self>;
+		type FindAuthor = PowAuthor;
+		type VerifySeal = Blake2Seal<Difficulty>;
+		type UncleGenerations = UncleGenerations;
+		type MaxUncles = MaxUncles;
+		type UncleReward = UncleReward;
+	}
+
+	pub type System = system::Module<TestRuntime>;
+	pub type Balances = balances::Module<TestRuntime>;
+	pub type Uncles = Module<TestRuntime>;
+
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		DIFFICULTY.with(|v| *v.borrow_mut() = 1);
+		let t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		t.into()
+	}
+
+	/// The (made up) hash of canonical block `n`
+	fn canonical(n: u64) -> H256 {
+		H256::repeat_byte(n as u8 + 1)
+	}
+
+	/// Finish the current block and build canonical blocks up to `n`
+	fn run_to_block(n: u64) {
+		while System::block_number() < n {
+			Uncles::on_finalize(System::block_number());
+			let next = System::block_number() + 1;
+			System::initialize(&next, &canonical(next - 1), &Default::default(), &Default::default());
+			Uncles::on_initialize(next);
+		}
+	}
+
+	/// An uncle at height `number` on top of the canonical chain, mined by `miner`
+	fn uncle(number: u64, miner: u64) -> Header {
+		let digest = Digest {
+			logs: vec![
+				DigestItem::PreRuntime(POW_ENGINE_ID, miner.encode()),
+				DigestItem::Seal(POW_ENGINE_ID, 0u64.encode()),
+			],
+		};
+		Header::new(number, Default::default(), Default::default(), canonical(number - 1), digest)
+	}
+
+	#[test]
+	fn uncles_are_rewarded_by_age() {
+		new_test_ext().execute_with(|| {
+			run_to_block(10);
+			assert_ok!(Uncles::set_uncles(RawOrigin::None.into(), vec![uncle(9, 1), uncle(6, 2)]));
+
+			// One block old earns the full reward, four blocks old earns a quarter
+			assert_eq!(Balances::free_balance(&1), 40);
+			assert_eq!(Balances::free_balance(&2), 10);
+
+			let expected_event = TestEvent::uncles(RawEvent::UncleRewarded(uncle(9, 1).hash(), 1, 40));
+			assert!(System::events().iter().any(|a| a.event == expected_event));
+		})
+	}
+
+	#[test]
+	fn only_once_per_block() {
+		new_test_ext().execute_with(|| {
+			run_to_block(10);
+			assert!(Uncles::set_uncles(Origin::signed(1), vec![uncle(9, 1)]).is_err());
+			assert_ok!(Uncles::set_uncles(RawOrigin::None.into(), vec![uncle(9, 1)]));
+			assert_err!(
+				Uncles::set_uncles(RawOrigin::None.into(), vec![uncle(8, 1)]),
+				"uncles already set in this block"
+			);
+		})
+	}
+
+	#[test]
+	fn headers_are_verified() {
+		new_test_ext().execute_with(|| {
+			run_to_block(10);
+			let verify = |header| Uncles::verify_uncle(&header, &[]).map(|_| ());
+
+			assert_err!(verify(uncle(10, 1)), "uncle must be older than this block");
+			assert_err!(verify(uncle(5, 1)), "uncle is too old");
+
+			let mut orphan = uncle(9, 1);
+			orphan.parent_hash = H256::repeat_byte(0xff);
+			assert_err!(verify(orphan), "uncle's parent is not canonical");
+
+			let mut unsealed = uncle(9, 1);
+			unsealed.digest.pop();
+			assert_err!(verify(unsealed), "uncle has an invalid seal");
+
+			let mut anonymous = uncle(9, 1);
+			anonymous.digest.logs.remove(0);
+			assert_err!(verify(anonymous), "uncle has no author");
+
+			DIFFICULTY.with(|v| *v.borrow_mut() = u128::max_value());
+			assert_err!(verify(uncle(9, 1)), "uncle has an invalid seal");
+		})
+	}
+
+	#[test]
+	fn uncle_is_included_once() {
+		new_test_ext().execute_with(|| {
+			run_to_block(10);
+			assert_err!(
+				Uncles::set_uncles(RawOrigin::None.into(), vec![uncle(9, 1), uncle(9, 1)]),
+				"uncle already included"
+			);
+			assert_ok!(Uncles::set_uncles(RawOrigin::None.into(), vec![uncle(9, 1)]));
+
+			run_to_block(11);
+			assert_err!(
+				Uncles::set_uncles(RawOrigin::None.into(), vec![uncle(9, 1)]),
+				"uncle already included"
+			);
+
+			// Forgotten once it could no longer be included anyway
+			run_to_block(14);
+			assert_eq!(Uncles::included(), vec![]);
+		})
+	}
+
+	#[test]
+	fn inherent_offers_valid_uncles_only() {
+		new_test_ext().execute_with(|| {
+			run_to_block(10);
+			let candidates = vec![uncle(5, 1), uncle(9, 1), uncle(9, 1), uncle(8, 2), uncle(7, 3)];
+			let mut data = InherentData::new();
+			data.put_data(INHERENT_IDENTIFIER, &candidates).unwrap();
+
+			// Too old, duplicate, and over the limit are left out
+			assert_eq!(
+				Uncles::create_inherent(&data),
+				Some(Call::set_uncles(vec![uncle(9, 1), uncle(8, 2)]))
+			);
+		})
+	}
+}
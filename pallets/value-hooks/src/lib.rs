@@ -0,0 +1,168 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! Hooks that let other pallets react to changes of the simple-map pallet's entries
+//!
+//! The simple-map pallet calls `OnValueChanged::on_value_changed` after every change of an entry,
+//! without knowing who listens. The runtime sets simple-map's `OnValueChanged` to a tuple of
+//! handlers, and the tuple calls each of them in turn, left to right. `()` is the empty tuple,
+//! which ignores every change. A pallet subscribes by implementing the trait and being added to
+//! the tuple, so the publisher and its subscribers only share this crate.
+//!
+//! Handlers run inside simple-map's calls, which can't undo a change once a handler saw it, so a
+//! handler can't fail. Its weight is paid by those calls too, so it should be a few storage
+//! operations at most.
+//!
+//! The pallet's own `Module` is a handler. It keeps the sum and the number of all entries, which
+//! simple-map could only compute by iterating its map.
+
+use frame_support::{decl_module, decl_storage};
+use frame_system as system;
+
+/// Something that reacts to changes of an account's entry
+#[impl_trait_for_tuples::impl_for_tuples(30)]
+pub trait OnValueChanged<AccountId> {
+	/// The entry of `who` changed from `old` to `new`. `None` means there was no entry before,
+	/// or is none anymore.
+	fn on_value_changed(who: &AccountId, old: Option<u32>, new: Option<u32>);
+}
+
+pub trait Trait: system::Trait {}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as ValueHooks {
+		/// The sum of all entries
+		Total get(fn total): u64;
+
+		/// The number of entries
+		EntryCount get(fn entry_count): u32;
+	}
+}
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {}
+}
+
+impl<T: Trait> OnValueChanged<T::AccountId> for Module<T> {
+	fn on_value_changed(_who: &T::AccountId, old: Option<u32>, new: Option<u32>) {
+		let (old_value, new_value) = (old.unwrap_or(0), new.unwrap_or(0));
+		// The sum of at most `u32::max_value()` entries of at most `u32::max_value()` fits
+		Total::mutate(|total| {
+			*total = total.saturating_sub(old_value.into()).saturating_add(new_value.into())
+		});
+		match (old, new) {
+			(None, Some(_)) => EntryCount::mutate(|count| *count = count.saturating_add(1)),
+			(Some(_), None) => EntryCount::mutate(|count| *count = count.saturating_sub(1)),
+			_ => {}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use frame_support::{impl_outer_origin, parameter_types};
+	use sp_core::H256;
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup},
+		Perbill,
+	};
+	use std::cell::RefCell;
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = ();
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	impl Trait for TestRuntime {}
+
+	pub type ValueHooks = Module<TestRuntime>;
+
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap()
+			.into()
+	}
+
+	thread_local! {
+		static CALLS: RefCell<Vec<(&'static str, u64)>> = RefCell::new(Vec::new());
+	}
+
+	/// Handlers that record that they were called, and with which account
+	struct First;
+	struct Second;
+
+	impl OnValueChanged<u64> for First {
+		fn on_value_changed(who: &u64, _old: Option<u32>, _new: Option<u32>) {
+			CALLS.with(|calls| calls.borrow_mut().push(("first", *who)));
+		}
+	}
+
+	impl OnValueChanged<u64> for Second {
+		fn on_value_changed(who: &u64, _old: Option<u32>, _new: Option<u32>) {
+			CALLS.with(|calls| calls.borrow_mut().push(("second", *who)));
+		}
+	}
+
+	#[test]
+	fn tuples_call_every_handler_in_order() {
+		<(First, Second, First)>::on_value_changed(&7, None, Some(1));
+		<()>::on_value_changed(&8, None, Some(1));
+		let calls = CALLS.with(|calls| calls.borrow().clone());
+		assert_eq!(calls, vec![("first", 7), ("second", 7), ("first", 7)]);
+	}
+
+	#[test]
+	fn module_keeps_sum_and_count() {
+		new_test_ext().execute_with(|| {
+			ValueHooks::on_value_changed(&1, None, Some(19));
+			ValueHooks::on_value_changed(&2, None, Some(3));
+			assert_eq!((ValueHooks::total(), ValueHooks::entry_count()), (22, 2));
+
+			ValueHooks::on_value_changed(&1, Some(19), Some(21));
+			assert_eq!((ValueHooks::total(), ValueHooks::entry_count()), (24, 2));
+
+			ValueHooks::on_value_changed(&2, Some(3), None);
+			assert_eq!((ValueHooks::total(), ValueHooks::entry_count()), (21, 1));
+		})
+	}
+
+	#[test]
+	fn module_handles_large_entries() {
+		new_test_ext().execute_with(|| {
+			for who in 0..3 {
+				ValueHooks::on_value_changed(&who, None, Some(u32::max_value()));
+			}
+			assert_eq!(ValueHooks::total(), 3 * u64::from(u32::max_value()));
+		})
+	}
+}
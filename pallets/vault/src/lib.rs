@@ -0,0 +1,593 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! Collateralized debt positions, which mint a stable token against the native currency
+//!
+//! Each account has a vault. It can `deposit` native currency as collateral, which is reserved,
+//! and `mint` the pallet's stable token against it, as long as the collateral is worth at least
+//! `CollateralRatio` percent of the vault's debt. It can `repay` debt by burning stable tokens, and
+//! `withdraw` collateral that the debt doesn't need. Stable tokens can be `transfer`red.
+//!
+//! The collateral is valued at the price from the oracle recipe. This pallet requires the oracle
+//! pallet in the runtime, and reads its latest median, which is the price of a unit of collateral
+//! in millionths of a stable token. A price from more than `MaxPriceAge` failed rounds ago is
+//! rejected, as the market may have moved since.
+//!
+//! If the price drops so far that a vault's collateral is worth less than `LiquidationRatio`
+//! percent of its debt, anyone may `liquidate` it. The liquidator burns stable tokens to pay the
+//! debt, and takes collateral worth the debt plus `LiquidationPenalty` percent. The rest of the
+//! collateral goes back to the owner. The gap between the two ratios leaves vault owners room to
+//! top up their collateral before they can be liquidated.
+
+use codec::{Decode, Encode};
+use sp_runtime::{
+	traits::{SaturatedConversion, Saturating, Zero},
+	RuntimeDebug,
+};
+use frame_support::{
+	decl_event, decl_module, decl_storage,
+	dispatch::DispatchResult,
+	ensure,
+	traits::{Currency, Get, ReservableCurrency},
+};
+use frame_system::{self as system, ensure_signed};
+use oracle::RoundIndex;
+
+type BalanceOf<T> =
+	<<T as Trait>::Collateral as Currency<<T as system::Trait>::AccountId>>::Balance;
+
+/// Prices from the oracle are in this many parts of a stable token
+pub const PRICE_SCALE: u128 = 1_000_000;
+
+/// What an account has in its vault
+#[derive(Encode, Decode, Clone, Default, PartialEq, Eq, RuntimeDebug)]
+pub struct Position<Balance> {
+	/// Native currency reserved as collateral
+	pub collateral: Balance,
+	/// Stable tokens minted against the collateral, and not repaid yet
+	pub debt: Balance,
+}
+
+/// Whether `collateral` at `price` is worth at least `ratio` percent of `debt`
+pub fn is_covered(collateral: u128, debt: u128, price: u128, ratio: u32) -> bool {
+	let value = collateral.saturating_mul(price).saturating_mul(100);
+	value >= debt.saturating_mul(u128::from(ratio)).saturating_mul(PRICE_SCALE)
+}
+
+/// How much of `collateral` at `price` is worth `debt` plus `penalty` percent of it. All of the
+/// collateral, if it is worth less.
+pub fn seized_collateral(collateral: u128, debt: u128, price: u128, penalty: u32) -> u128 {
+	let owed = debt.saturating_mul(u128::from(100 + penalty)).saturating_mul(PRICE_SCALE);
+	owed.checked_div(price.saturating_mul(100)).map_or(collateral, |c| c.min(collateral))
+}
+
+pub trait Trait: system::Trait + oracle::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The currency that is reserved as collateral
+	type Collateral: ReservableCurrency<Self::AccountId>;
+
+	/// The least a vault's collateral must be worth, in percent of its debt, after minting or
+	/// withdrawing
+	type CollateralRatio: Get<u32>;
+
+	/// Below this, in percent of its debt, a vault can be liquidated. Should be lower than
+	/// `CollateralRatio`, and above 100.
+	type LiquidationRatio: Get<u32>;
+
+	/// What a liquidator earns, in percent of the debt it pays. Should be below `LiquidationRatio`
+	/// minus 100, so that the collateral covers it.
+	type LiquidationPenalty: Get<u32>;
+
+	/// How many of the oracle's rounds may have failed since the price was set
+	type MaxPriceAge: Get<RoundIndex>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as Vault {
+		/// The vault of each account that has collateral or debt
+		Vaults get(fn vault): map T::AccountId => Position<BalanceOf<T>>;
+
+		/// Stable tokens held by each account
+		Stable get(fn stable): map T::AccountId => BalanceOf<T>;
+
+		/// The sum of all stable tokens, which is also the sum of all debt
+		StableSupply get(fn stable_supply): BalanceOf<T>;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as system::Trait>::AccountId,
+		Balance = BalanceOf<T>,
+	{
+		/// Collateral was added to a vault
+		Deposited(AccountId, Balance),
+		/// Collateral was taken out of a vault
+		Withdrew(AccountId, Balance),
+		/// Stable tokens were minted against a vault
+		Minted(AccountId, Balance),
+		/// Stable tokens were burned to repay a vault's debt
+		Repaid(AccountId, Balance),
+		/// A vault was liquidated: owner, liquidator, collateral taken, debt paid
+		Liquidated(AccountId, AccountId, Balance, Balance),
+		/// Stable tokens were transferred
+		Transferred(AccountId, AccountId, Balance),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		const CollateralRatio: u32 = T::CollateralRatio::get();
+
+		const LiquidationRatio: u32 = T::LiquidationRatio::get();
+
+		const MaxPriceAge: RoundIndex = T::MaxPriceAge::get();
+
+		/// Reserve native currency as collateral in the caller's vault
+		fn deposit(origin, amount: BalanceOf<T>) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			ensure!(!amount.is_zero(), "must deposit something");
+			T::Collateral::reserve(&who, amount).map_err(|_| "cannot reserve the collateral")?;
+
+			<Vaults<T>>::mutate(&who, |vault| vault.collateral += amount);
+			Self::deposit_event(RawEvent::Deposited(who, amount));
+			Ok(())
+		}
+
+		/// Return collateral from the caller's vault. What is left must cover its debt.
+		fn withdraw(origin, amount: BalanceOf<T>) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let mut vault = Self::vault(&who);
+			ensure!(!amount.is_zero(), "must withdraw something");
+			ensure!(amount <= vault.collateral, "not enough collateral");
+			vault.collateral -= amount;
+			Self::ensure_covered(&vault, T::CollateralRatio::get())?;
+
+			T::Collateral::unreserve(&who, amount);
+			Self::store(&who, vault);
+			Self::deposit_event(RawEvent::Withdrew(who, amount));
+			Ok(())
+		}
+
+		/// Mint stable tokens to the caller against their vault
+		fn mint(origin, amount: BalanceOf<T>) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let mut vault = Self::vault(&who);
+			ensure!(!amount.is_zero(), "must mint something");
+			vault.debt = vault.debt.saturating_add(amount);
+			Self::ensure_covered(&vault, T::CollateralRatio::get())?;
+
+			<Stable<T>>::mutate(&who, |held| *held += amount);
+			<StableSupply<T>>::mutate(|supply| *supply += amount);
+			Self::store(&who, vault);
+			Self::deposit_event(RawEvent::Minted(who, amount));
+			Ok(())
+		}
+
+		/// Burn the caller's stable tokens to repay their vault's debt
+		fn repay(origin, amount: BalanceOf<T>) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let mut vault = Self::vault(&who);
+			ensure!(!amount.is_zero(), "must repay something");
+			ensure!(amount <= vault.debt, "more than the debt");
+			ensure!(amount <= Self::stable(&who), "not enough stable tokens");
+
+			vault.debt -= amount;
+			Self::burn(&who, amount);
+			Self::store(&who, vault);
+			Self::deposit_event(RawEvent::Repaid(who, amount));
+			Ok(())
+		}
+
+		/// Pay the debt of a vault whose collateral no longer covers `LiquidationRatio` of it, and
+		/// take collateral worth the debt plus `LiquidationPenalty`
+		fn liquidate(origin, owner: T::AccountId) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let mut vault = Self::vault(&owner);
+			ensure!(!vault.debt.is_zero(), "no debt");
+			let price = Self::price()?;
+			ensure!(
+				!is_covered(
+					vault.collateral.saturated_into(),
+					vault.debt.saturated_into(),
+					price,
+					T::LiquidationRatio::get(),
+				),
+				"vault is safe"
+			);
+			ensure!(vault.debt <= Self::stable(&who), "not enough stable tokens");
+
+			let seized = seized_collateral(
+				vault.collateral.saturated_into(),
+				vault.debt.saturated_into(),
+				price,
+				T::LiquidationPenalty::get(),
+			);
+			// The collateral becomes part of the liquidator's free balance. Other pallets may have
+			// slashed the owner's reserve, so less than `seized` may be left to move.
+			let missing = T::Collateral::repatriate_reserved(&owner, &who, seized.saturated_into())?;
+			let taken = seized.saturating_sub(missing.saturated_into());
+			// The liquidator only pays for the collateral it got
+			let paid: BalanceOf<T> = if missing.is_zero() {
+				vault.debt
+			} else {
+				(vault.debt.saturated_into::<u128>().saturating_mul(taken) / seized).saturated_into()
+			};
+			let taken: BalanceOf<T> = taken.saturated_into();
+			Self::burn(&who, paid);
+
+			vault.debt -= paid;
+			vault.collateral = vault.collateral
+				.saturating_sub(taken)
+				.min(T::Collateral::reserved_balance(&owner));
+			if vault.debt.is_zero() {
+				// Collateral beyond the debt and the penalty goes back to the owner
+				T::Collateral::unreserve(&owner, vault.collateral);
+				vault.collateral = Zero::zero();
+			}
+			Self::store(&owner, vault);
+			Self::deposit_event(RawEvent::Liquidated(owner, who, taken, paid));
+			Ok(())
+		}
+
+		fn transfer(origin, to: T::AccountId, amount: BalanceOf<T>) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let held = Self::stable(&who);
+			ensure!(amount <= held, "not enough stable tokens");
+
+			<Stable<T>>::insert(&who, held - amount);
+			<Stable<T>>::mutate(&to, |held| *held += amount);
+			Self::deposit_event(RawEvent::Transferred(who, to, amount));
+			Ok(())
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// The oracle's latest price of a unit of collateral, in `PRICE_SCALE` parts of a stable token
+	pub fn price() -> Result<u128, &'static str> {
+		let (round, price) = <oracle::Module<T>>::latest().ok_or("no price")?;
+		// The latest round closed with the price, and any rounds since then failed
+		let failed = <oracle::Module<T>>::current_round().saturating_sub(round + 1);
+		ensure!(failed <= T::MaxPriceAge::get(), "price too old");
+		Ok(u128::from(price))
+	}
+
+	/// Ensure a vault's collateral covers `ratio` percent of its debt. A vault without debt needs
+	/// no price.
+	fn ensure_covered(vault: &Position<BalanceOf<T>>, ratio: u32) -> DispatchResult {
+		if vault.debt.is_zero() {
+			return Ok(());
+		}
+		let collateral = vault.collateral.saturated_into();
+		let covered = is_covered(collateral, vault.debt.saturated_into(), Self::price()?, ratio);
+		ensure!(covered, "not enough collateral for the debt");
+		Ok(())
+	}
+
+	fn burn(who: &T::AccountId, amount: BalanceOf<T>) {
+		<Stable<T>>::mutate(who, |held| *held -= amount);
+		<StableSupply<T>>::mutate(|supply| *supply -= amount);
+	}
+
+	/// Keep a vault, or remove it once it is empty
+	fn store(who: &T::AccountId, vault: Position<BalanceOf<T>>) {
+		if vault.collateral.is_zero() && vault.debt.is_zero() {
+			<Vaults<T>>::remove(who);
+		} else {
+			<Vaults<T>>::insert(who, vault);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use event_assert::assert_has_event;
+	use sp_core::H256;
+	use frame_support::{
+		assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types,
+	};
+	use frame_system::{EnsureRoot, RawOrigin};
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup, OnFinalize},
+		Perbill, Permill,
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const ExistentialDeposit: u64 = 0;
+		pub const TransferFee: u64 = 0;
+		pub const CreationFee: u64 = 0;
+
+		pub const Stake: u64 = 50;
+		pub const RoundLength: u64 = 10;
+		pub const MinSubmissions: u32 = 1;
+		pub const Tolerance: Permill = Permill::from_percent(10);
+		pub const SlashFraction: Perbill = Perbill::from_percent(20);
+
+		pub const CollateralRatio: u32 = 150;
+		pub const LiquidationRatio: u32 = 120;
+		pub const LiquidationPenalty: u32 = 10;
+		pub const MaxPriceAge: RoundIndex = 1;
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	impl balances::Trait for TestRuntime {
+		type Balance = u64;
+		type OnFreeBalanceZero = ();
+		type OnNewAccount = ();
+		type Event = TestEvent;
+		type TransferPayment = ();
+		type DustRemoval = ();
+		type ExistentialDeposit = ExistentialDeposit;
+		type TransferFee = TransferFee;
+		type CreationFee = CreationFee;
+	}
+
+	impl oracle::Trait for TestRuntime {
+		type Event = TestEvent;
+		type Currency = balances::Module<Self>;
+		type ManageOrigin = EnsureRoot<u64>;
+		type Stake = Stake;
+		type RoundLength = RoundLength;
+		type MinSubmissions = MinSubmissions;
+		type Tolerance = Tolerance;
+		type SlashFraction = SlashFraction;
+		type Slash = ();
+	}
+
+	mod vault {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			balances<T>,
+			oracle<T>,
+			vault<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type Collateral = balances::Module<Self>;
+		type CollateralRatio = CollateralRatio;
+		type LiquidationRatio = LiquidationRatio;
+		type LiquidationPenalty = LiquidationPenalty;
+		type MaxPriceAge = MaxPriceAge;
+	}
+
+	pub type Balances = balances::Module<TestRuntime>;
+	pub type Oracle = oracle::Module<TestRuntime>;
+	pub type Vault = Module<TestRuntime>;
+
+	/// Accounts 1 to 3 have 10_000 each. Account 9 is the oracle's only provider.
+	fn new_test_ext() -> sp_io::TestExternalities {
+		let mut t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		balances::GenesisConfig::<TestRuntime> {
+			balances: vec![(1, 10_000), (2, 10_000), (3, 10_000), (9, 100)],
+			vesting: vec![],
+		}
+		.assimilate_storage(&mut t)
+		.unwrap();
+		let mut ext: sp_io::TestExternalities = t.into();
+		ext.execute_with(|| {
+			assert_ok!(Oracle::whitelist(RawOrigin::Root.into(), 9));
+			assert_ok!(Oracle::stake(Origin::signed(9)));
+		});
+		ext
+	}
+
+	/// Close an oracle round, with a price from the provider if one is given
+	fn round(price: Option<u64>) {
+		if let Some(price) = price {
+			assert_ok!(Oracle::submit(Origin::signed(9), price));
+		}
+		Oracle::on_finalize(10 * u64::from(Oracle::current_round() + 1));
+	}
+
+	/// A stable token for each unit of collateral
+	const ONE_TO_ONE: u64 = 1_000_000;
+
+	#[test]
+	fn coverage_is_in_percent_of_the_debt() {
+		let price = 2 * PRICE_SCALE;
+		// 1000 collateral is worth 2000, which is 150% of 1333 but not of 1334
+		assert!(is_covered(1_000, 1_333, price, 150));
+		assert!(!is_covered(1_000, 1_334, price, 150));
+		assert!(is_covered(0, 0, price, 150));
+		assert!(!is_covered(1, 1, 0, 150));
+		let max = u128::max_value();
+		assert!(is_covered(max, max / PRICE_SCALE, PRICE_SCALE, 100));
+	}
+
+	#[test]
+	fn seizing_covers_the_debt_and_the_penalty() {
+		let price = 2 * PRICE_SCALE;
+		// 1000 of debt and 10% are worth 550 collateral
+		assert_eq!(seized_collateral(1_000, 1_000, price, 10), 550);
+		assert_eq!(seized_collateral(500, 1_000, price, 10), 500);
+		assert_eq!(seized_collateral(1_000, 1_000, 0, 10), 1_000);
+	}
+
+	#[test]
+	fn minting_needs_collateral() {
+		new_test_ext().execute_with(|| {
+			round(Some(2 * ONE_TO_ONE));
+			assert_ok!(Vault::deposit(Origin::signed(1), 1_000));
+			assert_eq!(Balances::reserved_balance(&1), 1_000);
+
+			assert_err!(
+				Vault::mint(Origin::signed(1), 1_334),
+				"not enough collateral for the debt"
+			);
+			assert_ok!(Vault::mint(Origin::signed(1), 1_333));
+			assert_eq!(Vault::stable(&1), 1_333);
+			assert_eq!(Vault::stable_supply(), 1_333);
+			assert_eq!(Vault::vault(&1), Position { collateral: 1_000, debt: 1_333 });
+			assert_err!(Vault::mint(Origin::signed(1), 1), "not enough collateral for the debt");
+
+			assert_err!(Vault::deposit(Origin::signed(2), 10_001), "cannot reserve the collateral");
+		})
+	}
+
+	#[test]
+	fn withdrawing_keeps_the_debt_covered() {
+		new_test_ext().execute_with(|| {
+			round(Some(2 * ONE_TO_ONE));
+			assert_ok!(Vault::deposit(Origin::signed(1), 1_000));
+			assert_ok!(Vault::mint(Origin::signed(1), 1_000));
+
+			// 750 is worth 1500, which is 150% of 1000
+			assert_ok!(Vault::withdraw(Origin::signed(1), 250));
+			assert_err!(
+				Vault::withdraw(Origin::signed(1), 1),
+				"not enough collateral for the debt"
+			);
+			assert_eq!(Balances::reserved_balance(&1), 750);
+
+			// Repaying frees the collateral, and an empty vault is removed
+			assert_ok!(Vault::repay(Origin::signed(1), 1_000));
+			assert_eq!(Vault::stable_supply(), 0);
+			assert_err!(Vault::withdraw(Origin::signed(1), 751), "not enough collateral");
+			assert_ok!(Vault::withdraw(Origin::signed(1), 750));
+			assert_eq!(Balances::free_balance(&1), 10_000);
+			assert!(!<Vaults<TestRuntime>>::exists(&1));
+		})
+	}
+
+	#[test]
+	fn repaying_burns_stable_tokens() {
+		new_test_ext().execute_with(|| {
+			round(Some(ONE_TO_ONE));
+			assert_ok!(Vault::deposit(Origin::signed(1), 1_500));
+			assert_ok!(Vault::mint(Origin::signed(1), 1_000));
+			assert_ok!(Vault::transfer(Origin::signed(1), 2, 600));
+			assert_eq!(Vault::stable(&2), 600);
+
+			assert_err!(Vault::repay(Origin::signed(1), 401), "not enough stable tokens");
+			assert_err!(Vault::repay(Origin::signed(2), 1), "more than the debt");
+			assert_ok!(Vault::repay(Origin::signed(1), 400));
+			assert_eq!(Vault::vault(&1).debt, 600);
+			assert_eq!(Vault::stable_supply(), 600);
+			assert_err!(Vault::transfer(Origin::signed(1), 2, 1), "not enough stable tokens");
+		})
+	}
+
+	#[test]
+	fn prices_must_be_recent() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(Vault::deposit(Origin::signed(1), 1_000));
+			assert_err!(Vault::mint(Origin::signed(1), 1), "no price");
+
+			round(Some(ONE_TO_ONE));
+			// One round without a price is tolerated, but not two
+			round(None);
+			assert_ok!(Vault::mint(Origin::signed(1), 1));
+			round(None);
+			assert_err!(Vault::mint(Origin::signed(1), 1), "price too old");
+			// Vaults without debt don't need a price
+			assert_ok!(Vault::deposit(Origin::signed(2), 100));
+			assert_ok!(Vault::withdraw(Origin::signed(2), 100));
+
+			round(Some(ONE_TO_ONE));
+			assert_ok!(Vault::mint(Origin::signed(1), 1));
+		})
+	}
+
+	#[test]
+	fn liquidation_below_the_threshold() {
+		new_test_ext().execute_with(|| {
+			round(Some(2 * ONE_TO_ONE));
+			assert_ok!(Vault::deposit(Origin::signed(1), 1_000));
+			assert_ok!(Vault::mint(Origin::signed(1), 1_333));
+			// Account 2 mints stable tokens of its own, to liquidate with
+			assert_ok!(Vault::deposit(Origin::signed(2), 5_000));
+			assert_ok!(Vault::mint(Origin::signed(2), 2_000));
+
+			assert_err!(Vault::liquidate(Origin::signed(2), 1), "vault is safe");
+			// 1000 at 1.6 is worth 1600, which is still 120% of 1333
+			round(Some(1_600_000));
+			assert_err!(Vault::liquidate(Origin::signed(2), 1), "vault is safe");
+
+			// At 1.5 it is worth 1500, which is not
+			round(Some(1_500_000));
+			assert_err!(Vault::liquidate(Origin::signed(3), 1), "not enough stable tokens");
+			assert_ok!(Vault::liquidate(Origin::signed(2), 1));
+
+			// Account 2 paid 1333 stable tokens for 977 collateral, worth 1333 and 10%
+			assert_eq!(Vault::stable(&2), 2_000 - 1_333);
+			assert_eq!(Balances::free_balance(&2), 10_000 - 5_000 + 977);
+			assert_eq!(Balances::reserved_balance(&2), 5_000);
+			assert_has_event::<TestRuntime>(RawEvent::Liquidated(1, 2, 977, 1_333));
+			// Account 1 keeps the stable tokens it minted, and gets the rest of its collateral back
+			assert_eq!(Vault::stable(&1), 1_333);
+			assert_eq!(Balances::free_balance(&1), 9_000 + 23);
+			assert_eq!(Balances::reserved_balance(&1), 0);
+			assert!(!<Vaults<TestRuntime>>::exists(&1));
+			assert_eq!(Vault::stable_supply(), 2_000);
+			assert_err!(Vault::liquidate(Origin::signed(2), 1), "no debt");
+		})
+	}
+
+	#[test]
+	fn liquidation_pays_for_the_collateral_that_is_left() {
+		new_test_ext().execute_with(|| {
+			round(Some(2 * ONE_TO_ONE));
+			assert_ok!(Vault::deposit(Origin::signed(1), 1_000));
+			assert_ok!(Vault::mint(Origin::signed(1), 1_333));
+			assert_ok!(Vault::deposit(Origin::signed(2), 5_000));
+			assert_ok!(Vault::mint(Origin::signed(2), 2_000));
+			round(Some(1_500_000));
+
+			// Another pallet slashes half of the reserve
+			Balances::slash_reserved(&1, 500);
+			assert_ok!(Vault::liquidate(Origin::signed(2), 1));
+
+			// 500 of the 977 could be moved, so account 2 paid 500 / 977 of the debt
+			assert_eq!(Balances::free_balance(&2), 10_000 - 5_000 + 500);
+			assert_eq!(Vault::stable(&2), 2_000 - 682);
+			assert_has_event::<TestRuntime>(RawEvent::Liquidated(1, 2, 500, 682));
+			// The rest of the debt stays, without collateral
+			assert_eq!(Vault::vault(&1), Position { collateral: 0, debt: 1_333 - 682 });
+			assert_eq!(Vault::stable_supply(), 1_333 + 2_000 - 682);
+		})
+	}
+}
@@ -3,11 +3,19 @@
 // demonstrates how to use append instead of mutate
 // https://substrate.dev/rustdocs/master/frame_support/storage/trait.StorageValue.html#tymethod.append
 use rstd::prelude::*;
-use support::{decl_event, decl_module, decl_storage, dispatch::DispatchResult, ensure, StorageValue};
+use support::{
+    decl_event, decl_module, decl_storage, dispatch::DispatchResult, ensure, weights::SimpleDispatchInfo,
+    StorageValue,
+};
 use system::ensure_signed;
 
+pub mod weights;
+pub use weights::WeightInfo;
+
 pub trait Trait: system::Trait {
     type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+    /// Weight information for the dispatchables in this pallet
+    type WeightInfo: WeightInfo;
 }
 
 decl_storage! {
@@ -40,6 +48,10 @@ decl_module! {
 
         // don't do this
         // (unless appending new entries AND mutating existing entries)
+        #[weight = SimpleDispatchInfo::FixedNormal(T::WeightInfo::mutate_to_append(
+            <Module<T>>::len_of_current_values(),
+            <Module<T>>::len_of_new_values(),
+        ))]
         fn mutate_to_append(origin) -> DispatchResult {
             let user = ensure_signed(origin)?;
 
@@ -50,6 +62,9 @@ decl_module! {
         }
 
         // do this instead
+        #[weight = SimpleDispatchInfo::FixedNormal(
+            T::WeightInfo::append_new_entries(<Module<T>>::len_of_new_values())
+        )]
         fn append_new_entries(origin) -> DispatchResult {
             let user = ensure_signed(origin)?;
 
@@ -59,6 +74,9 @@ decl_module! {
             Ok(())
         }
 
+        #[weight = SimpleDispatchInfo::FixedNormal(
+            T::WeightInfo::add_member(<Module<T>>::member_count())
+        )]
         fn add_member(origin) -> DispatchResult {
             let new_member = ensure_signed(origin)?;
             ensure!(!Self::is_member(&new_member), "must not be a member to be added");
@@ -67,6 +85,9 @@ decl_module! {
             Ok(())
         }
 
+        #[weight = SimpleDispatchInfo::FixedNormal(
+            T::WeightInfo::remove_member(<Module<T>>::member_count())
+        )]
         fn remove_member(origin) -> DispatchResult {
             let old_member = ensure_signed(origin)?;
             ensure!(Self::is_member(&old_member), "must be a member in order to leave");
@@ -82,11 +103,27 @@ impl<T: Trait> Module<T> {
     pub fn is_member(who: &T::AccountId) -> bool {
         <Members<T>>::get().contains(who)
     }
+
+    /// The number of members, without decoding them
+    pub fn member_count() -> u32 {
+        <Members<T>>::decode_len().unwrap_or(0) as u32
+    }
+
+    /// The number of current values, without decoding them
+    pub fn len_of_current_values() -> u32 {
+        <CurrentValues>::decode_len().unwrap_or(0) as u32
+    }
+
+    /// The number of new values, without decoding them
+    pub fn len_of_new_values() -> u32 {
+        <NewValues>::decode_len().unwrap_or(0) as u32
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{Module, RawEvent, Trait};
+    use crate::{Call, Members, Module, RawEvent, Trait, WeightInfo};
+    use state_dump::StateDump;
     use primitives::H256;
     use runtime_io;
     use runtime_primitives::{
@@ -94,7 +131,10 @@ mod tests {
         traits::{BlakeTwo256, IdentityLookup},
         Perbill,
     };
-    use support::{assert_ok, assert_err, impl_outer_event, impl_outer_origin, parameter_types};
+    use support::{
+        assert_ok, assert_err, impl_outer_event, impl_outer_origin, parameter_types,
+        weights::GetDispatchInfo, StorageValue,
+    };
     use system;
 
     impl_outer_origin! {
@@ -141,6 +181,7 @@ mod tests {
 
     impl Trait for TestRuntime {
         type Event = TestEvent;
+        type WeightInfo = ();
     }
 
     pub type System = system::Module<TestRuntime>;
@@ -207,4 +248,34 @@ mod tests {
             assert_eq!(VecSet::members(), vec![2]);
         })
     }
+
+    #[test]
+    fn state_dump_shows_members() {
+        ExtBuilder::build().execute_with(|| {
+            assert_ok!(VecSet::add_member(Origin::signed(1)));
+            assert_ok!(VecSet::add_member(Origin::signed(2)));
+
+            let dump = StateDump::new()
+                .with_metadata(VecSet::storage_metadata())
+                .with_item::<Vec<u64>>("VecMap Members", <Members<TestRuntime>>::hashed_key())
+                .render();
+            assert!(dump.lines().any(|line| line == "VecMap Members = [1, 2]"));
+        })
+    }
+
+    #[test]
+    fn membership_weights_grow_with_members() {
+        ExtBuilder::build().execute_with(|| {
+            let add = || Call::<TestRuntime>::add_member().get_dispatch_info().weight;
+            let remove = || Call::<TestRuntime>::remove_member().get_dispatch_info().weight;
+            assert_eq!(add(), <() as WeightInfo>::add_member(0));
+
+            for who in 1..=3 {
+                assert_ok!(VecSet::add_member(Origin::signed(who)));
+            }
+            assert_eq!(VecSet::member_count(), 3);
+            assert_eq!(add(), <() as WeightInfo>::add_member(3));
+            assert_eq!(remove(), <() as WeightInfo>::remove_member(3));
+        })
+    }
 }
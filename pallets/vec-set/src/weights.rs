@@ -0,0 +1,57 @@
+//! Weights for the vec-set pallet
+//!
+//! Every call in this pallet decodes or encodes at least one `Vec`, so each weight is a function
+//! of the length of that `Vec`, its complexity parameter. The calls are charged for the length
+//! when they are submitted, read with `decode_len`. See the simple-map pallet for a description of
+//! the `WeightInfo` pattern.
+
+use rstd::marker::PhantomData;
+use support::weights::Weight;
+
+/// Weight functions needed by the vec-set pallet
+pub trait WeightInfo {
+    fn mutate_to_append(current: u32, new: u32) -> Weight;
+    fn append_new_entries(new: u32) -> Weight;
+    fn add_member(members: u32) -> Weight;
+    fn remove_member(members: u32) -> Weight;
+}
+
+/// Hand-picked estimates of the vec-set pallet's weights. They were not measured, because this
+/// Substrate version has no benchmarking framework.
+pub struct EstimatedWeight<T>(PhantomData<T>);
+impl<T: system::Trait> WeightInfo for EstimatedWeight<T> {
+    // decodes and re-encodes the whole existing vec, and decodes the new values
+    fn mutate_to_append(current: u32, new: u32) -> Weight {
+        20_000u32
+            .saturating_add(1_000u32.saturating_mul(current))
+            .saturating_add(500u32.saturating_mul(new))
+    }
+    // only decodes and encodes the new values
+    fn append_new_entries(new: u32) -> Weight {
+        20_000u32.saturating_add(500u32.saturating_mul(new))
+    }
+    // decodes the member vec to check membership, then appends
+    fn add_member(members: u32) -> Weight {
+        20_000u32.saturating_add(500u32.saturating_mul(members))
+    }
+    // decodes the member vec twice and re-encodes it
+    fn remove_member(members: u32) -> Weight {
+        20_000u32.saturating_add(1_500u32.saturating_mul(members))
+    }
+}
+
+// For backwards compatibility and tests
+impl WeightInfo for () {
+    fn mutate_to_append(current: u32, new: u32) -> Weight {
+        10_000u32.saturating_add(100u32.saturating_mul(current.saturating_add(new)))
+    }
+    fn append_new_entries(new: u32) -> Weight {
+        10_000u32.saturating_add(100u32.saturating_mul(new))
+    }
+    fn add_member(members: u32) -> Weight {
+        10_000u32.saturating_add(100u32.saturating_mul(members))
+    }
+    fn remove_member(members: u32) -> Weight {
+        10_000u32.saturating_add(100u32.saturating_mul(members))
+    }
+}
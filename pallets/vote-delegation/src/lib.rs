@@ -0,0 +1,345 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! Vote delegation: walking a graph without unbounded work
+//!
+//! Every account can vote on a single motion, or delegate its vote to another account. Delegations
+//! form a graph, and finding the vote that counts for an account means following its delegations
+//! until reaching an account that voted. On chain, every step of such a walk costs a storage read,
+//! so the pallet keeps the walks bounded:
+//! * `delegate` refuses delegations that would close a cycle. It finds cycles by walking the chain
+//!   from the new delegate, and refuses chains longer than `MaxDepth` rather than walk them.
+//! * `tally` follows at most `MaxDepth` delegations per account. Accounts whose chain is longer,
+//!   which can happen when two short chains are joined, abstain.
+//! * Both calls are weighed for the longest walk they may make, `MaxDepth` hops.
+
+use sp_std::prelude::*;
+use frame_support::{
+	decl_event, decl_module, decl_storage,
+	dispatch::{DispatchResult, PaysFee, WeighData},
+	ensure,
+	traits::Get,
+	weights::{ClassifyDispatch, DispatchClass, SimpleDispatchInfo, Weight},
+};
+use frame_system::{self as system, ensure_signed};
+
+/// The weight of one hop of a walk, which reads an account's vote and delegate
+pub const HOP_WEIGHT: Weight = 10_000;
+
+/// Weighs a call by the length of its single `Vec` argument. The weight is the number of accounts
+/// times the field of this struct.
+pub struct PerAccount(pub Weight);
+
+impl<AccountId> WeighData<(&Vec<AccountId>,)> for PerAccount {
+	fn weigh_data(&self, (accounts,): (&Vec<AccountId>,)) -> Weight {
+		(accounts.len() as Weight).saturating_mul(self.0)
+	}
+}
+
+impl PaysFee for PerAccount {
+	fn pays_fee(&self) -> bool {
+		true
+	}
+}
+
+impl<T> ClassifyDispatch<T> for PerAccount {
+	fn classify_dispatch(&self, _: T) -> DispatchClass {
+		Default::default()
+	}
+}
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The most delegations followed in one walk
+	type MaxDepth: Get<u32>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as VoteDelegation {
+		/// The account each account delegates its vote to
+		Delegations get(fn delegate_of): map T::AccountId => Option<T::AccountId>;
+
+		/// The votes of accounts that voted themselves, `true` for aye
+		Votes get(fn vote_of): map T::AccountId => Option<bool>;
+
+		/// Accounts that `tally` has already counted
+		Counted get(fn counted): map T::AccountId => bool;
+
+		/// The number of counted accounts in favor of the motion
+		Ayes get(fn ayes): u32;
+
+		/// The number of counted accounts against the motion
+		Nays get(fn nays): u32;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as system::Trait>::AccountId,
+	{
+		/// The first account delegated its vote to the second
+		Delegated(AccountId, AccountId),
+		/// An account took back its delegation
+		Undelegated(AccountId),
+		/// An account voted, `true` for aye
+		Voted(AccountId, bool),
+		/// A tally ended with this many ayes and nays in total
+		Tallied(u32, u32),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		const MaxDepth: u32 = T::MaxDepth::get();
+
+		/// Delegate the caller's vote to `to`, replacing any earlier delegation
+		#[weight = SimpleDispatchInfo::FixedNormal(<Module<T>>::walk_weight())]
+		fn delegate(origin, to: T::AccountId) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			ensure!(who != to, "cannot delegate to yourself");
+
+			// A cycle would have to lead from `to` back to the caller. Count the delegation from
+			// the caller to `to` as the first hop.
+			let mut current = to.clone();
+			let mut hops = 1;
+			while let Some(next) = Self::delegate_of(&current) {
+				ensure!(next != who, "delegation would create a cycle");
+				hops += 1;
+				ensure!(hops <= T::MaxDepth::get(), "delegation chain too long");
+				current = next;
+			}
+
+			<Delegations<T>>::insert(&who, &to);
+			Self::deposit_event(RawEvent::Delegated(who, to));
+			Ok(())
+		}
+
+		fn undelegate(origin) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			ensure!(<Delegations<T>>::exists(&who), "not delegating");
+
+			<Delegations<T>>::remove(&who);
+			Self::deposit_event(RawEvent::Undelegated(who));
+			Ok(())
+		}
+
+		/// Vote on the motion. A vote of their own takes precedence over an account's delegation.
+		fn vote(origin, aye: bool) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			<Votes<T>>::insert(&who, aye);
+			Self::deposit_event(RawEvent::Voted(who, aye));
+			Ok(())
+		}
+
+		/// Count the votes of `accounts`, each once. The caller chooses how many accounts to count
+		/// at a time, and pays for the longest possible walk for each of them.
+		#[weight = PerAccount(<Module<T>>::walk_weight())]
+		fn tally(origin, accounts: Vec<T::AccountId>) -> DispatchResult {
+			ensure_signed(origin)?;
+			let (mut ayes, mut nays) = (Self::ayes(), Self::nays());
+			for account in accounts {
+				if Self::counted(&account) {
+					continue;
+				}
+				match Self::resolve(&account) {
+					Some(true) => ayes += 1,
+					Some(false) => nays += 1,
+					None => {}
+				}
+				<Counted<T>>::insert(&account, true);
+			}
+			Ayes::put(ayes);
+			Nays::put(nays);
+
+			Self::deposit_event(RawEvent::Tallied(ayes, nays));
+			Ok(())
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// The vote that counts for `who`: its own, or the first vote along its delegations. `None`
+	/// if nobody along the chain voted, or the chain is longer than `MaxDepth` delegations.
+	pub fn resolve(who: &T::AccountId) -> Option<bool> {
+		let mut current = who.clone();
+		// Reads the votes of `who` and of up to `MaxDepth` delegates
+		for _ in 0..=T::MaxDepth::get() {
+			if let Some(aye) = Self::vote_of(&current) {
+				return Some(aye);
+			}
+			current = Self::delegate_of(&current)?;
+		}
+		None
+	}
+
+	/// The weight of the longest walk, `MaxDepth` hops plus the starting account
+	pub fn walk_weight() -> Weight {
+		T::MaxDepth::get().saturating_add(1).saturating_mul(HOP_WEIGHT)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use event_assert::assert_last_event;
+	use sp_core::H256;
+	use frame_support::{assert_err, assert_ok, impl_outer_event, impl_outer_origin, parameter_types};
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup},
+		Perbill,
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const MaxDepth: u32 = 3;
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	mod vote_delegation {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			vote_delegation<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type MaxDepth = MaxDepth;
+	}
+
+	pub type VoteDelegation = Module<TestRuntime>;
+
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		let t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		t.into()
+	}
+
+	fn delegate(from: u64, to: u64) -> DispatchResult {
+		VoteDelegation::delegate(Origin::signed(from), to)
+	}
+
+	#[test]
+	fn votes_flow_along_delegations() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(delegate(1, 2));
+			assert_ok!(delegate(2, 3));
+			assert_last_event::<TestRuntime>(RawEvent::Delegated(2, 3));
+			assert_eq!(VoteDelegation::resolve(&1), None);
+
+			assert_ok!(VoteDelegation::vote(Origin::signed(3), true));
+			assert_eq!(VoteDelegation::resolve(&1), Some(true));
+
+			// An account's own vote takes precedence, also for those who delegate to it
+			assert_ok!(VoteDelegation::vote(Origin::signed(2), false));
+			assert_eq!(VoteDelegation::resolve(&1), Some(false));
+
+			assert_ok!(VoteDelegation::undelegate(Origin::signed(1)));
+			assert_eq!(VoteDelegation::resolve(&1), None);
+			assert_err!(VoteDelegation::undelegate(Origin::signed(1)), "not delegating");
+		})
+	}
+
+	#[test]
+	fn cycles_are_refused() {
+		new_test_ext().execute_with(|| {
+			assert_err!(delegate(1, 1), "cannot delegate to yourself");
+			assert_ok!(delegate(1, 2));
+			assert_err!(delegate(2, 1), "delegation would create a cycle");
+			assert_ok!(delegate(2, 3));
+			assert_err!(delegate(3, 1), "delegation would create a cycle");
+
+			// Redirecting a delegation is checked like a new one
+			assert_ok!(delegate(3, 4));
+			assert_err!(delegate(4, 2), "delegation would create a cycle");
+		})
+	}
+
+	#[test]
+	fn long_chains_are_refused() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(delegate(3, 4));
+			assert_ok!(delegate(2, 3));
+			assert_ok!(delegate(1, 2));
+			// 5 -> 1 -> 2 -> 3 -> 4 would be four hops
+			assert_err!(delegate(5, 1), "delegation chain too long");
+		})
+	}
+
+	#[test]
+	fn resolution_stops_at_max_depth() {
+		new_test_ext().execute_with(|| {
+			// Two short chains, 1 -> 2 and 3 -> 4 -> 5, joined into one of four hops
+			assert_ok!(delegate(1, 2));
+			assert_ok!(delegate(3, 4));
+			assert_ok!(delegate(4, 5));
+			assert_ok!(delegate(2, 3));
+			assert_ok!(VoteDelegation::vote(Origin::signed(5), true));
+
+			assert_eq!(VoteDelegation::resolve(&2), Some(true));
+			// Too far, so account 1 abstains
+			assert_eq!(VoteDelegation::resolve(&1), None);
+		})
+	}
+
+	#[test]
+	fn tally_counts_each_account_once() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(delegate(1, 3));
+			assert_ok!(delegate(2, 4));
+			assert_ok!(VoteDelegation::vote(Origin::signed(3), true));
+			assert_ok!(VoteDelegation::vote(Origin::signed(4), false));
+
+			assert_ok!(VoteDelegation::tally(Origin::signed(9), vec![1, 3, 5]));
+			assert_last_event::<TestRuntime>(RawEvent::Tallied(2, 0));
+
+			// Account 1 has been counted already, and account 5 abstains
+			assert_ok!(VoteDelegation::tally(Origin::signed(9), vec![1, 2, 4, 5]));
+			assert_eq!((VoteDelegation::ayes(), VoteDelegation::nays()), (2, 2));
+		})
+	}
+
+	#[test]
+	fn weight_covers_the_longest_walk() {
+		assert_eq!(VoteDelegation::walk_weight(), 4 * HOP_WEIGHT);
+		assert_eq!(PerAccount(HOP_WEIGHT).weigh_data((&vec![1u64, 2, 3],)), 3 * HOP_WEIGHT);
+	}
+}
@@ -0,0 +1,358 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! A miniature cross-consensus message executor
+//!
+//! Cross-chain messages are small programs. Instead of calling a fixed function on the receiving
+//! chain, the sender writes a list of instructions, and the receiving chain runs them in an
+//! executor against its own pallets. This pallet implements three instructions:
+//!
+//! * `WithdrawAsset` moves funds from the origin's account into the *holding register*, a
+//!   temporary place for assets that belong to no account while the program runs.
+//! * `DepositAsset` moves everything in holding to a beneficiary.
+//! * `Transact` dispatches a runtime call as the origin.
+//!
+//! Programs are checked before anything runs, so malformed ones are rejected as a whole. An
+//! instruction that fails at run time stops execution, returns the held funds to the origin, and
+//! reports how far the program got.
+
+use codec::{Decode, Encode};
+use sp_std::{mem, prelude::*};
+use sp_runtime::{
+	traits::{Dispatchable, Zero},
+	RuntimeDebug,
+};
+use frame_support::{
+	decl_event, decl_module,
+	dispatch::DispatchResult,
+	ensure,
+	traits::{Currency, ExistenceRequirement, Get, Imbalance, WithdrawReason},
+	Parameter,
+};
+use frame_system::{self as system, ensure_signed};
+
+type BalanceOf<T> = <<T as Trait>::Currency as Currency<<T as system::Trait>::AccountId>>::Balance;
+type NegativeImbalanceOf<T> =
+	<<T as Trait>::Currency as Currency<<T as system::Trait>::AccountId>>::NegativeImbalance;
+type InstructionOf<T> =
+	Instruction<<T as system::Trait>::AccountId, BalanceOf<T>, <T as Trait>::Call>;
+
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub enum Instruction<AccountId, Balance, Call> {
+	/// Move funds from the origin into holding
+	WithdrawAsset(Balance),
+	/// Move all funds in holding to an account
+	DepositAsset(AccountId),
+	/// Dispatch a call as the origin
+	Transact(Call),
+}
+
+/// The result of running a well-formed program
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub enum Outcome {
+	/// Every instruction was executed
+	Complete,
+	/// The instruction with this index failed. It and those after it had no effect.
+	Incomplete(u32),
+}
+
+pub trait Trait: system::Trait {
+	/// The overarching event type
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// The currency that programs move around
+	type Currency: Currency<Self::AccountId>;
+
+	/// The runtime calls that `Transact` may dispatch
+	type Call: Parameter + Dispatchable<Origin = <Self as system::Trait>::Origin>;
+
+	/// The most instructions a program may have
+	type MaxInstructions: Get<u32>;
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as system::Trait>::AccountId,
+	{
+		/// A program sent by this origin was executed
+		Executed(AccountId, Outcome),
+	}
+);
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		const MaxInstructions: u32 = T::MaxInstructions::get();
+
+		/// Check a program, then execute it with the caller as its origin
+		fn execute(origin, program: Vec<InstructionOf<T>>) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			Self::check(&program)?;
+
+			let outcome = Self::run(&who, program);
+
+			Self::deposit_event(RawEvent::Executed(who, outcome));
+			Ok(())
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// Reject programs that could never run to completion, before any of them runs
+	pub fn check(program: &[InstructionOf<T>]) -> DispatchResult {
+		ensure!(!program.is_empty(), "empty program");
+		ensure!(program.len() as u32 <= T::MaxInstructions::get(), "program too long");
+
+		let mut holding = false;
+		for instruction in program {
+			match instruction {
+				Instruction::WithdrawAsset(amount) => {
+					ensure!(!amount.is_zero(), "withdrawal of nothing");
+					holding = true;
+				}
+				Instruction::DepositAsset(_) => {
+					ensure!(holding, "deposit with empty holding");
+					holding = false;
+				}
+				Instruction::Transact(_) => {}
+			}
+		}
+		// Funds left in holding would otherwise be lost
+		ensure!(!holding, "funds left in holding");
+		Ok(())
+	}
+
+	/// Execute a checked program. Funds still held when an instruction fails go back to `who`.
+	fn run(who: &T::AccountId, program: Vec<InstructionOf<T>>) -> Outcome {
+		let mut holding = NegativeImbalanceOf::<T>::zero();
+		for (index, instruction) in program.into_iter().enumerate() {
+			if Self::execute_instruction(who, instruction, &mut holding).is_err() {
+				T::Currency::resolve_creating(who, holding);
+				return Outcome::Incomplete(index as u32);
+			}
+		}
+		Outcome::Complete
+	}
+
+	fn execute_instruction(
+		who: &T::AccountId,
+		instruction: InstructionOf<T>,
+		holding: &mut NegativeImbalanceOf<T>,
+	) -> DispatchResult {
+		match instruction {
+			Instruction::WithdrawAsset(amount) => {
+				let funds = T::Currency::withdraw(
+					who,
+					amount,
+					WithdrawReason::Transfer.into(),
+					ExistenceRequirement::KeepAlive,
+				)?;
+				holding.subsume(funds);
+			}
+			Instruction::DepositAsset(beneficiary) => {
+				let funds = mem::replace(holding, NegativeImbalanceOf::<T>::zero());
+				T::Currency::resolve_creating(&beneficiary, funds);
+			}
+			Instruction::Transact(call) => {
+				call.dispatch(system::RawOrigin::Signed(who.clone()).into())
+					.map_err(|_| "transact failed")?;
+			}
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use sp_core::H256;
+	use frame_support::{
+		assert_err, assert_ok, impl_outer_dispatch, impl_outer_event, impl_outer_origin,
+		parameter_types,
+	};
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup},
+		Perbill,
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	impl_outer_dispatch! {
+		pub enum Call for TestRuntime where origin: Origin {
+			balances::Balances,
+		}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+
+		pub const ExistentialDeposit: u64 = 0;
+		pub const TransferFee: u64 = 0;
+		pub const CreationFee: u64 = 0;
+
+		pub const MaxInstructions: u32 = 4;
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	impl balances::Trait for TestRuntime {
+		type Balance = u64;
+		type OnFreeBalanceZero = ();
+		type OnNewAccount = ();
+		type Event = TestEvent;
+		type TransferPayment = ();
+		type DustRemoval = ();
+		type ExistentialDeposit = ExistentialDeposit;
+		type TransferFee = TransferFee;
+		type CreationFee = CreationFee;
+	}
+
+	mod xcm_lite {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			balances<T>,
+			xcm_lite<T>,
+		}
+	}
+
+	impl Trait for TestRuntime {
+		type Event = TestEvent;
+		type Currency = balances::Module<Self>;
+		type Call = Call;
+		type MaxInstructions = MaxInstructions;
+	}
+
+	pub type System = system::Module<TestRuntime>;
+	pub type Balances = balances::Module<TestRuntime>;
+	pub type XcmLite = Module<TestRuntime>;
+
+	use Instruction::*;
+
+	/// Accounts 1 and 2 have 100 each
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		let mut t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		balances::GenesisConfig::<TestRuntime> {
+			balances: vec![(1, 100), (2, 100)],
+			vesting: vec![],
+		}
+		.assimilate_storage(&mut t)
+		.unwrap();
+		t.into()
+	}
+
+	fn event_emitted(event: RawEvent<u64>) -> bool {
+		let expected = TestEvent::xcm_lite(event);
+		System::events().iter().any(|a| a.event == expected)
+	}
+
+	fn transfer(dest: u64, value: u64) -> InstructionOf<TestRuntime> {
+		Transact(Call::Balances(balances::Call::transfer(dest, value)))
+	}
+
+	#[test]
+	fn withdraw_and_deposit_moves_funds() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(XcmLite::execute(
+				Origin::signed(1),
+				vec![WithdrawAsset(30), WithdrawAsset(10), DepositAsset(3)]
+			));
+
+			assert_eq!(Balances::free_balance(&1), 60);
+			assert_eq!(Balances::free_balance(&3), 40);
+			assert!(event_emitted(RawEvent::Executed(1, Outcome::Complete)));
+		})
+	}
+
+	#[test]
+	fn transact_dispatches_as_origin() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(XcmLite::execute(Origin::signed(2), vec![transfer(3, 25)]));
+
+			assert_eq!(Balances::free_balance(&2), 75);
+			assert_eq!(Balances::free_balance(&3), 25);
+		})
+	}
+
+	#[test]
+	fn malformed_programs_are_rejected() {
+		new_test_ext().execute_with(|| {
+			let rejected = vec![
+				(vec![], "empty program"),
+				(vec![transfer(3, 1); 5], "program too long"),
+				(vec![WithdrawAsset(0), DepositAsset(3)], "withdrawal of nothing"),
+				(vec![DepositAsset(3)], "deposit with empty holding"),
+				(vec![WithdrawAsset(5), DepositAsset(3), DepositAsset(4)], "deposit with empty holding"),
+				(vec![WithdrawAsset(5)], "funds left in holding"),
+				(vec![WithdrawAsset(5), transfer(3, 1)], "funds left in holding"),
+			];
+			for (program, error) in rejected {
+				assert_err!(XcmLite::execute(Origin::signed(1), program), error);
+			}
+
+			// Nothing ran
+			assert_eq!(Balances::free_balance(&1), 100);
+			assert!(System::events().is_empty());
+		})
+	}
+
+	#[test]
+	fn failed_withdrawal_stops_execution() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(XcmLite::execute(
+				Origin::signed(1),
+				vec![WithdrawAsset(60), WithdrawAsset(60), DepositAsset(3), transfer(3, 1)]
+			));
+
+			// The first withdrawal went back to the origin and nothing after it ran
+			assert!(event_emitted(RawEvent::Executed(1, Outcome::Incomplete(1))));
+			assert_eq!(Balances::free_balance(&1), 100);
+			assert_eq!(Balances::free_balance(&3), 0);
+		})
+	}
+
+	#[test]
+	fn failed_transact_returns_holding() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(XcmLite::execute(
+				Origin::signed(1),
+				vec![WithdrawAsset(50), transfer(3, 80), DepositAsset(3)]
+			));
+
+			assert!(event_emitted(RawEvent::Executed(1, Outcome::Incomplete(1))));
+			assert_eq!(Balances::free_balance(&1), 100);
+			assert_eq!(Balances::free_balance(&3), 0);
+		})
+	}
+}
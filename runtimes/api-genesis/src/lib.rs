@@ -1,11 +1,11 @@
 use runtime::{
-	AccountId, AuraConfig, BalancesConfig, GenesisConfig, GrandpaConfig,
-	SudoConfig, IndicesConfig, SystemConfig, WASM_BINARY,
+	AccountId, ApprovedAuthorsConfig, AuraConfig, BalancesConfig, GenesisConfig, GrandpaConfig,
+	NodeAuthorizationConfig, SudoConfig, IndicesConfig, SystemConfig, WASM_BINARY,
 };
 use sp_consensus_aura::sr25519::{AuthorityId as AuraId};
 use sp_finality_grandpa::{AuthorityId as GrandpaId};
 
-pub fn testnet_genesis(initial_authorities: Vec<(AuraId, GrandpaId)>,
+pub fn testnet_genesis(initial_authorities: Vec<(AccountId, AuraId, GrandpaId)>,
 	root_key: AccountId,
 	endowed_accounts: Vec<AccountId>,
 	_enable_println: bool) -> GenesisConfig {
@@ -25,10 +25,17 @@ pub fn testnet_genesis(initial_authorities: Vec<(AuraId, GrandpaId)>,
 			key: root_key,
 		}),
 		aura: Some(AuraConfig {
-			authorities: initial_authorities.iter().map(|x| (x.0.clone())).collect(),
+			authorities: initial_authorities.iter().map(|x| (x.1.clone())).collect(),
 		}),
 		grandpa: Some(GrandpaConfig {
-			authorities: initial_authorities.iter().map(|x| (x.1.clone(), 1)).collect(),
+			authorities: initial_authorities.iter().map(|x| (x.2.clone(), 1)).collect(),
+		}),
+		approved_authors: Some(ApprovedAuthorsConfig {
+			authors: initial_authorities.iter().map(|x| (x.0.clone(), x.1.clone())).collect(),
+		}),
+		// Well-known nodes are added once their peer ids are known
+		node_authorization: Some(NodeAuthorizationConfig {
+			nodes: vec![],
 		}),
 	}
 }
@@ -12,7 +12,7 @@ use sp_std::prelude::*;
 use sp_core::OpaqueMetadata;
 use sp_runtime::{
 	ApplyExtrinsicResult, transaction_validity::TransactionValidity, generic, create_runtime_str,
-	impl_opaque_keys, MultiSignature
+	impl_opaque_keys, MultiSignature, ConsensusEngineId,
 };
 use sp_runtime::traits::{
 	NumberFor, BlakeTwo256, Block as BlockT, StaticLookup, Verify, ConvertInto, IdentifyAccount
@@ -22,6 +22,7 @@ use sp_consensus_aura::sr25519::AuthorityId as AuraId;
 use grandpa::AuthorityList as GrandpaAuthorityList;
 use grandpa::fg_primitives;
 use frame_system as system;
+use parity_scale_codec::{Decode, Encode};
 #[cfg(feature = "std")]
 use sp_version::NativeVersion;
 use sp_version::RuntimeVersion;
@@ -34,7 +35,7 @@ pub use balances::Call as BalancesCall;
 pub use sp_runtime::{Permill, Perbill};
 pub use frame_support::{
 	StorageValue, construct_runtime, parameter_types,
-	traits::Randomness,
+	traits::{FindAuthor, Randomness},
 	weights::Weight,
 };
 
@@ -221,7 +222,7 @@ parameter_types! {
 
 impl transaction_payment::Trait for Runtime {
 	type Currency = balances::Module<Runtime>;
-	type OnTransactionPayment = ();
+	type OnTransactionPayment = BlockReward;
 	type TransactionBaseFee = TransactionBaseFee;
 	type TransactionByteFee = TransactionByteFee;
 	type WeightToFee = ConvertInto;
@@ -234,10 +235,84 @@ impl sudo::Trait for Runtime {
 }
 
 // ---------------------- Recipe Pallet Configurations ----------------------
+parameter_types! {
+	pub const MaxAuthors: u32 = 16;
+}
+
+impl approved_authors::Trait for Runtime {
+	type Event = Event;
+	type AuthorityId = AuraId;
+	type ManageOrigin = system::EnsureRoot<AccountId>;
+	type MaxAuthors = MaxAuthors;
+	type OnAuthorsChanged = approved_authors::AuraAuthorities<Runtime>;
+}
+
+parameter_types! {
+	pub const MaxWellKnownNodes: u32 = 32;
+	pub const MaxConnections: u32 = 16;
+	// The peer id of an ed25519 key, which nodes use by default, is 38 bytes long
+	pub const MaxPeerIdLength: u32 = 128;
+}
+
+impl node_authorization::Trait for Runtime {
+	type Event = Event;
+	type ManageOrigin = system::EnsureRoot<AccountId>;
+	type MaxWellKnownNodes = MaxWellKnownNodes;
+	type MaxConnections = MaxConnections;
+	type MaxPeerIdLength = MaxPeerIdLength;
+}
+
+/// Finds the account of the current Aura author.
+///
+/// Aura only records the index of the author in its authority list, so we look up that authority's
+/// sr25519 key. An sr25519 key and an `AccountId` share the same 32 byte encoding.
+pub struct AuraAccountAdapter;
+
+impl FindAuthor<AccountId> for AuraAccountAdapter {
+	fn find_author<'a, I>(digests: I) -> Option<AccountId>
+		where I: 'a + IntoIterator<Item=(ConsensusEngineId, &'a [u8])>
+	{
+		let index = Aura::find_author(digests)? as usize;
+		let authority = Aura::authorities().get(index)?.encode();
+		AccountId::decode(&mut &authority[..]).ok()
+	}
+}
+
+parameter_types! {
+	pub const RewardPerBlock: Balance = 1_000;
+	pub const FeeShare: Perbill = Perbill::from_percent(50);
+}
+
+impl block_reward::Trait for Runtime {
+	type Event = Event;
+	type Currency = balances::Module<Runtime>;
+	type FindAuthor = AuraAccountAdapter;
+	type RewardPerBlock = RewardPerBlock;
+	type FeeShare = FeeShare;
+	// The other half of each fee is burned
+	type FeeRemainder = ();
+}
+
 impl sum_storage::Trait for Runtime {
 	type Event = Event;
 }
 
+parameter_types! {
+	pub const BlobByteFee: Balance = 10;
+	pub const MaxBlobLength: u32 = 1024 * 1024;
+}
+
+impl transaction_storage::Trait for Runtime {
+	type Event = Event;
+	type Currency = balances::Module<Runtime>;
+	type ByteFee = BlobByteFee;
+	type MaxBlobLength = MaxBlobLength;
+	// Storage fees are burned
+	type FeeDestination = ();
+	// Storage fees are the only limit
+	type RateLimiter = ();
+}
+
 construct_runtime!(
 	pub enum Runtime where
 		Block = Block,
@@ -254,6 +329,10 @@ construct_runtime!(
 		Sudo: sudo,
 		RandomnessCollectiveFlip: randomness_collective_flip::{Module, Call, Storage},
 		SumStorage: sum_storage::{Module, Call, Storage, Event},
+		BlockReward: block_reward::{Module, Storage, Event<T>},
+		TransactionStorage: transaction_storage::{Module, Call, Storage, Event<T>},
+		ApprovedAuthors: approved_authors::{Module, Call, Storage, Config<T>, Event<T>},
+		NodeAuthorization: node_authorization::{Module, Call, Storage, Config<T>, Event<T>},
 	}
 );
 
@@ -362,6 +441,29 @@ impl_runtime_apis! {
 		}
 	}
 
+	impl sum_storage_rpc_runtime_api::SumStorageProofApi<Block> for Runtime {
+		fn storage_keys() -> Vec<Vec<u8>> {
+			SumStorage::storage_keys()
+		}
+	}
+
+	impl node_authorization_runtime_api::NodeAuthorizationApi<Block> for Runtime {
+		fn authorized_peers(node: Vec<u8>) -> Vec<Vec<u8>> {
+			NodeAuthorization::authorized_peers(&node)
+		}
+	}
+
+	// Dry runs dispatch calls to any pallet in this runtime, so the API is implemented here
+	// rather than by a pallet
+	impl dry_run_runtime_api::DryRunApi<Block, AccountId, Call, Event> for Runtime {
+		fn simulate_call(
+			origin: dry_run::DryRunOrigin<AccountId>,
+			call: Call,
+		) -> dry_run::Simulation<Event> {
+			dry_run::simulate::<Runtime, _>(origin, call)
+		}
+	}
+
 	impl sp_session::SessionKeys<Block> for Runtime {
 		fn generate_session_keys(seed: Option<Vec<u8>>) -> Vec<u8> {
 			opaque::SessionKeys::generate(seed)
@@ -0,0 +1,28 @@
+use runtime::{
+	AccountId, BalancesConfig, GenesisConfig, SlashingConfig, SudoConfig, IndicesConfig, SystemConfig, WASM_BINARY,
+};
+
+// Proof of work has no authority set, anybody who finds a seal may author a block
+pub fn testnet_genesis(root_key: AccountId,
+	endowed_accounts: Vec<AccountId>,
+	_enable_println: bool) -> GenesisConfig {
+	GenesisConfig {
+		system: Some(SystemConfig {
+			code: WASM_BINARY.to_vec(),
+			changes_trie_config: Default::default(),
+		}),
+		indices: Some(IndicesConfig {
+			ids: endowed_accounts.clone(),
+		}),
+		balances: Some(BalancesConfig {
+			balances: endowed_accounts.iter().cloned().map(|k|(k, 1 << 60)).collect(),
+			vesting: vec![],
+		}),
+		sudo: Some(SudoConfig {
+			key: root_key.clone(),
+		}),
+		slashing: Some(SlashingConfig {
+			reporters: vec![root_key],
+		}),
+	}
+}
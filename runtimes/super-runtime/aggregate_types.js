@@ -14,9 +14,15 @@ const pallets = [
   "default-instance",
   "double-map",
   "execution-schedule",
+  "feedback",
   "generic-event",
+  "hash-chain",
+  "hello-substrate",
   "last-caller",
   "linked-map",
+  "multi-block-task",
+  "ocw-backoff",
+  "ocw-finality",
   "simple-event",
   "simple-map",
   "charity",
@@ -24,6 +30,7 @@ const pallets = [
   "storage-cache",
   "struct-storage",
   "vec-set",
+  "vote-delegation",
 ]
 
 // Loop through all pallets aggregating types
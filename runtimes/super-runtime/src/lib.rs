@@ -233,6 +233,10 @@ parameter_types! {
 
 impl transaction_payment::Trait for Runtime {
     type Currency = balances::Module<Runtime>;
+    // Governance decides where fees go. Without it, they are burned.
+    #[cfg(feature = "with-governance")]
+    type OnTransactionPayment = FeePolicy;
+    #[cfg(not(feature = "with-governance"))]
     type OnTransactionPayment = ();
     type TransactionBaseFee = TransactionBaseFee;
     type TransactionByteFee = TransactionByteFee;
@@ -252,11 +256,7 @@ impl adding_machine::Trait for Runtime {
 
 impl basic_token::Trait for Runtime {
     type Event = Event;
-}
-
-impl charity::Trait for Runtime {
-    type Event = Event;
-    type Currency = Balances;
+    type TransferFilter = ();
 }
 
 parameter_types! {
@@ -270,8 +270,38 @@ impl constant_config::Trait for Runtime {
     type ClearFrequency = ClearFrequency;
 }
 
-impl check_membership::Trait for Runtime {
+parameter_types! {
+    pub const MaxSchemaLength: u32 = 1024;
+}
+
+impl credentials::Trait for Runtime {
+    type Event = Event;
+    type MaxSchemaLength = MaxSchemaLength;
+}
+
+parameter_types! {
+    pub const MinHeirTimeout: BlockNumber = 7 * DAYS;
+}
+
+impl dead_mans_switch::Trait for Runtime {
+    type Event = Event;
+    type Currency = Balances;
+    type MinTimeout = MinHeirTimeout;
+}
+
+parameter_types! {
+    pub const MaxDidKeys: u32 = 16;
+    pub const MaxDidServices: u32 = 8;
+    pub const MaxServiceLength: u32 = 256;
+}
+
+impl did::Trait for Runtime {
     type Event = Event;
+    type Public = <Signature as Verify>::Signer;
+    type Signature = Signature;
+    type MaxKeys = MaxDidKeys;
+    type MaxServices = MaxDidServices;
+    type MaxServiceLength = MaxServiceLength;
 }
 
 // The following two configuration traits are for two different instances of the deafult-instance
@@ -286,44 +316,90 @@ impl default_instance::Trait<default_instance::Instance2> for Runtime {
 
 impl double_map::Trait for Runtime {
     type Event = Event;
+    type WeightInfo = double_map::weights::EstimatedWeight<Runtime>;
 }
 
 parameter_types! {
-    pub const ExecutionFrequency: u32 = 10;
-    pub const SignalQuota: u32 = 1000;
-    pub const TaskLimit: u32 = 10;
+    // About 65 thousand hashes per submission
+    pub const Difficulty: u32 = 16;
+    pub const MaxFeedbackLength: u32 = 256;
+    pub const ProofLifetime: BlockNumber = 10;
 }
 
-impl execution_schedule::Trait for Runtime {
+impl feedback::Trait for Runtime {
     type Event = Event;
-    type ExecutionFrequency = ExecutionFrequency;
-    type SignalQuota = SignalQuota;
-    type TaskLimit = TaskLimit;
+    type Difficulty = Difficulty;
+    type MaxLength = MaxFeedbackLength;
+    type ProofLifetime = ProofLifetime;
 }
 
 impl generic_event::Trait for Runtime {
     type Event = Event;
+    type Currency = Balances;
+}
+
+parameter_types! {
+    pub const MaxIterations: u32 = 100_000;
+}
+
+impl hash_chain::Trait for Runtime {
+    type Event = Event;
+    type MaxIterations = MaxIterations;
+}
+
+impl hello_substrate::Trait for Runtime {
+    type Event = Event;
 }
 
 // The following two configuration traits are for two different instances of the last-caller pallet
+parameter_types! {
+    pub const ShortHistory: u32 = 10;
+    pub const LongHistory: u32 = 100;
+}
+
 impl last_caller::Trait<last_caller::Instance1> for Runtime {
     type Event = Event;
+    type MaxHistory = ShortHistory;
 }
 
 impl last_caller::Trait<last_caller::Instance2> for Runtime {
     type Event = Event;
+    type MaxHistory = LongHistory;
 }
 
 impl linked_map::Trait for Runtime {
     type Event = Event;
 }
 
+parameter_types! {
+    pub const StepsPerBlock: u32 = 100;
+}
+
+impl multi_block_task::Trait for Runtime {
+    type Event = Event;
+    type StepsPerBlock = StepsPerBlock;
+}
+
 impl simple_event::Trait for Runtime {
     type Event = Event;
 }
 
 impl simple_map::Trait for Runtime {
     type Event = Event;
+    type WeightInfo = simple_map::weights::EstimatedWeight<Runtime>;
+    // No new behavior is switched on
+    type FeatureFlags = ();
+    // Both handlers see every change of an entry, in this order
+    type OnValueChanged = (ValueHooks, PrintValueChanges);
+}
+
+/// Prints every change of a simple-map entry to the node's log, under the `runtime` target
+pub struct PrintValueChanges;
+
+impl value_hooks::OnValueChanged<AccountId> for PrintValueChanges {
+    fn on_value_changed(who: &AccountId, old: Option<u32>, new: Option<u32>) {
+        support::runtime_print!("simple-map: entry of {:?} changed from {:?} to {:?}", who, old, new);
+    }
 }
 
 impl single_value::Trait for Runtime {
@@ -338,47 +414,228 @@ impl struct_storage::Trait for Runtime {
     type Event = Event;
 }
 
+impl value_hooks::Trait for Runtime {}
+
 impl vec_set::Trait for Runtime {
     type Event = Event;
+    type WeightInfo = vec_set::weights::EstimatedWeight<Runtime>;
 }
 
-construct_runtime!(
-	pub enum Runtime where
-		Block = Block,
-		NodeBlock = opaque::Block,
-		UncheckedExtrinsic = UncheckedExtrinsic
-	{
-		System: system::{Module, Call, Storage, Config, Event},
-		Timestamp: timestamp::{Module, Call, Storage, Inherent},
-		Babe: babe::{Module, Call, Storage, Config, Inherent(Timestamp)},
-		Grandpa: grandpa::{Module, Call, Storage, Config, Event},
-		Indices: indices,
-		Balances: balances,
-		RandomnessCollectiveFlip: randomness_collective_flip::{Module, Call, Storage},
-		Sudo: sudo,
-		TransactionPayment: transaction_payment::{Module, Storage},
-		// The Recipe Pallets
-		AddingMachine: adding_machine::{Module, Call, Event},
-		BasicToken: basic_token::{Module, Call, Storage, Event<T>},
-		Charity: charity::{Module, Call, Storage, Event<T>},
-		CheckMembership: check_membership::{Module, Call, Storage, Event<T>},
-		ConstantConfig: constant_config::{Module, Call, Storage, Event},
-		DefaultInstance1: default_instance::{Module, Call, Storage, Event<T>},
-		DefaultInstance2: default_instance::<Instance2>::{Module, Call, Storage, Event<T>},
-		DoubleMap: double_map::{Module, Call, Storage, Event<T>},
-		ExecutionSchedule: execution_schedule::{Module, Call, Storage, Event<T>},
-		GenericEvent: generic_event::{Module, Call, Event<T>},
-		LastCaller1: last_caller::<Instance1>::{Module, Call, Storage, Event<T>},
-		LastCaller2: last_caller::<Instance2>::{Module, Call, Storage, Event<T>},
-		LinkedMap: linked_map::{Module, Call, Storage, Event<T>},
-		SimpleEvent: simple_event::{Module, Call, Event},
-		SimpleMap: simple_map::{Module, Call, Storage, Event<T>},
-		SingleValue: single_value::{Module, Call, Storage, Event<T>},
-		StorageCache: storage_cache::{Module, Call, Storage, Event<T>},
-		StructStorage: struct_storage::{Module, Call, Storage, Event<T>},
-		VecSet: vec_set::{Module, Call, Storage, Event<T>},
-	}
-);
+parameter_types! {
+    pub const MaxDelegationDepth: u32 = 8;
+}
+
+impl vote_delegation::Trait for Runtime {
+    type Event = Event;
+    type MaxDepth = MaxDelegationDepth;
+}
+
+/// Finds the account of the current Babe author.
+///
+/// Babe only records the index of the author in its authority list, so we look up that authority's
+/// sr25519 key. An sr25519 key and an `AccountId` share the same 32 byte encoding.
+pub struct BabeAccountAdapter;
+
+impl support::traits::FindAuthor<AccountId> for BabeAccountAdapter {
+    fn find_author<'a, I>(digests: I) -> Option<AccountId>
+        where I: 'a + IntoIterator<Item=(sp_runtime::ConsensusEngineId, &'a [u8])>
+    {
+        use parity_scale_codec::{Decode, Encode};
+        use support::traits::FindAuthor;
+
+        let index = Babe::find_author(digests)? as usize;
+        let (authority, _weight) = Babe::authorities().get(index)?.clone();
+        AccountId::decode(&mut &authority.encode()[..]).ok()
+    }
+}
+
+// ---------------------- Governance Recipe Configurations ----------------------
+// Only compiled when the `with-governance` feature is enabled. See `construct_recipes_runtime!`.
+#[cfg(feature = "with-governance")]
+impl charity::Trait for Runtime {
+    type Event = Event;
+    type Currency = Balances;
+    type WeightInfo = charity::weights::EstimatedWeight<Runtime>;
+}
+
+#[cfg(feature = "with-governance")]
+impl fee_policy::Trait for Runtime {
+    type Event = Event;
+    type Currency = Balances;
+    type FindAuthor = BabeAccountAdapter;
+    type Treasury = Charity;
+    type ManageOrigin = system::EnsureRoot<AccountId>;
+}
+
+#[cfg(feature = "with-governance")]
+impl check_membership::Trait for Runtime {
+    type Event = Event;
+}
+
+#[cfg(feature = "with-governance")]
+parameter_types! {
+    pub const ExecutionFrequency: u32 = 10;
+    pub const SignalQuota: u32 = 1000;
+    pub const TaskLimit: u32 = 10;
+}
+
+#[cfg(feature = "with-governance")]
+impl execution_schedule::Trait for Runtime {
+    type Event = Event;
+    type ExecutionFrequency = ExecutionFrequency;
+    type SignalQuota = SignalQuota;
+    type TaskLimit = TaskLimit;
+}
+
+// ---------------------- Off-chain Worker Recipe Configurations ----------------------
+// Only compiled when the `with-ocw` feature is enabled. See `construct_recipes_runtime!`.
+#[cfg(feature = "with-ocw")]
+parameter_types! {
+    pub const PriceEndpoints: &'static [&'static str] = &[
+        "http://localhost:8000/price/DOT",
+        "http://localhost:8001/price/DOT",
+    ];
+    pub const FailureThreshold: u32 = 5;
+    pub const EndpointCooldown: BlockNumber = 100;
+}
+
+#[cfg(feature = "with-ocw")]
+impl ocw_backoff::Trait for Runtime {
+    type Event = Event;
+    type Call = Call;
+    type SubmitTransaction = system::offchain::TransactionSubmitter<(), Runtime, UncheckedExtrinsic>;
+    type Endpoints = PriceEndpoints;
+    type FailureThreshold = FailureThreshold;
+    type Cooldown = EndpointCooldown;
+}
+
+#[cfg(feature = "with-ocw")]
+parameter_types! {
+    pub const Confirmations: BlockNumber = 5;
+}
+
+#[cfg(feature = "with-ocw")]
+impl ocw_finality::Trait for Runtime {
+    type Event = Event;
+    type Call = Call;
+    type SubmitTransaction = system::offchain::TransactionSubmitter<(), Runtime, UncheckedExtrinsic>;
+    type Confirmations = Confirmations;
+}
+
+// ---------------------- Proof of Work Recipe Configurations ----------------------
+// Only compiled when the `with-pow` feature is enabled. See `construct_recipes_runtime!`.
+#[cfg(feature = "with-pow")]
+parameter_types! {
+    pub const InitialReward: Balance = 50_000;
+    pub const HalvingPeriod: BlockNumber = 210_000;
+    pub const MaxSupply: Balance = 1 << 60;
+}
+
+// This runtime's blocks are authored with Babe, so the rewards go to the Babe author. The
+// pow-runtime shows how to find a miner instead.
+#[cfg(feature = "with-pow")]
+impl halving_reward::Trait for Runtime {
+    type Event = Event;
+    type Currency = Balances;
+    type FindAuthor = BabeAccountAdapter;
+    type InitialReward = InitialReward;
+    type HalvingPeriod = HalvingPeriod;
+    type MaxSupply = MaxSupply;
+}
+
+/// Assembles the runtime from the always-present pallets plus whichever optional groups of recipe
+/// pallets have been enabled through cargo features.
+///
+/// `construct_runtime!` does not accept `#[cfg]` attributes on individual pallets, so each optional
+/// group is handled by one step of this macro. A step appends its pallets to the accumulated list
+/// when its feature is enabled, passes the list along unchanged when it is not, and hands over to
+/// the next step. The final step invokes `construct_runtime!` with everything that was collected.
+///
+/// * `with-governance`: pallets that are controlled by a council or by root
+/// * `with-ocw`: pallets that rely on off-chain workers
+/// * `with-pow`: pallets that model the economics of proof of work chains
+macro_rules! construct_recipes_runtime {
+	(@governance [ $( $pallets:tt )* ]) => {
+		#[cfg(feature = "with-governance")]
+		construct_recipes_runtime!(@ocw [
+			$( $pallets )*
+			Charity: charity::{Module, Call, Storage, Event<T>},
+			CheckMembership: check_membership::{Module, Call, Storage, Event<T>},
+			ExecutionSchedule: execution_schedule::{Module, Call, Storage, Event<T>},
+			FeePolicy: fee_policy::{Module, Call, Storage, Event<T>},
+		]);
+		#[cfg(not(feature = "with-governance"))]
+		construct_recipes_runtime!(@ocw [ $( $pallets )* ]);
+	};
+	(@ocw [ $( $pallets:tt )* ]) => {
+		#[cfg(feature = "with-ocw")]
+		construct_recipes_runtime!(@pow [
+			$( $pallets )*
+			OcwBackoff: ocw_backoff::{Module, Call, Storage, Event<T>, ValidateUnsigned},
+			OcwFinality: ocw_finality::{Module, Call, Storage, Event<T>, ValidateUnsigned},
+		]);
+		#[cfg(not(feature = "with-ocw"))]
+		construct_recipes_runtime!(@pow [ $( $pallets )* ]);
+	};
+	(@pow [ $( $pallets:tt )* ]) => {
+		#[cfg(feature = "with-pow")]
+		construct_recipes_runtime!(@construct [
+			$( $pallets )*
+			HalvingReward: halving_reward::{Module, Storage, Event<T>},
+		]);
+		#[cfg(not(feature = "with-pow"))]
+		construct_recipes_runtime!(@construct [ $( $pallets )* ]);
+	};
+	(@construct [ $( $pallets:tt )* ]) => {
+		construct_runtime!(
+			pub enum Runtime where
+				Block = Block,
+				NodeBlock = opaque::Block,
+				UncheckedExtrinsic = UncheckedExtrinsic
+			{
+				System: system::{Module, Call, Storage, Config, Event},
+				Timestamp: timestamp::{Module, Call, Storage, Inherent},
+				Babe: babe::{Module, Call, Storage, Config, Inherent(Timestamp)},
+				Grandpa: grandpa::{Module, Call, Storage, Config, Event},
+				Indices: indices,
+				Balances: balances,
+				RandomnessCollectiveFlip: randomness_collective_flip::{Module, Call, Storage},
+				Sudo: sudo,
+				TransactionPayment: transaction_payment::{Module, Storage},
+				// The Recipe Pallets
+				AddingMachine: adding_machine::{Module, Call, Storage, Event},
+				BasicToken: basic_token::{Module, Call, Storage, Event<T>},
+				ConstantConfig: constant_config::{Module, Call, Storage, Event},
+				Credentials: credentials::{Module, Call, Storage, Event<T>},
+				DeadMansSwitch: dead_mans_switch::{Module, Call, Storage, Event<T>},
+				Did: did::{Module, Call, Storage, Event<T>},
+				DefaultInstance1: default_instance::{Module, Call, Storage, Event<T>},
+				DefaultInstance2: default_instance::<Instance2>::{Module, Call, Storage, Event<T>},
+				DoubleMap: double_map::{Module, Call, Storage, Event<T>},
+				Feedback: feedback::{Module, Call, Storage, Event, ValidateUnsigned},
+				GenericEvent: generic_event::{Module, Call, Event<T>},
+				HashChain: hash_chain::{Module, Call, Storage, Event<T>},
+				HelloSubstrate: hello_substrate::{Module, Call, Storage, Event<T>},
+				LastCaller1: last_caller::<Instance1>::{Module, Call, Storage, Event<T>},
+				LastCaller2: last_caller::<Instance2>::{Module, Call, Storage, Event<T>},
+				LinkedMap: linked_map::{Module, Call, Storage, Event<T>},
+				MultiBlockTask: multi_block_task::{Module, Call, Storage, Event<T>},
+				SimpleEvent: simple_event::{Module, Call, Event},
+				SimpleMap: simple_map::{Module, Call, Storage, Event<T>},
+				SingleValue: single_value::{Module, Call, Storage, Event<T>},
+				StorageCache: storage_cache::{Module, Call, Storage, Event<T>},
+				StructStorage: struct_storage::{Module, Call, Storage, Event<T>},
+				ValueHooks: value_hooks::{Module, Storage},
+				VecSet: vec_set::{Module, Call, Storage, Event<T>},
+				VoteDelegation: vote_delegation::{Module, Call, Storage, Event<T>},
+				// The optional groups of Recipe Pallets
+				$( $pallets )*
+			}
+		);
+	};
+}
+
+construct_recipes_runtime!(@governance []);
 
 /// The address format for describing accounts.
 pub type Address = <Indices as StaticLookup>::Source;
@@ -398,6 +655,7 @@ pub type SignedExtra = (
     system::CheckNonce<Runtime>,
     system::CheckWeight<Runtime>,
     transaction_payment::ChargeTransactionPayment<Runtime>,
+    dead_mans_switch::TrackActivity<Runtime>,
 );
 /// Unchecked extrinsic type as expected by this runtime.
 pub type UncheckedExtrinsic = generic::UncheckedExtrinsic<Address, Call, Signature, SignedExtra>;
@@ -493,4 +751,95 @@ impl_runtime_apis! {
             opaque::SessionKeys::generate(seed)
         }
     }
+
+    // The API reports the history of the first instance
+    impl last_caller_runtime_api::LastCallerApi<Block, AccountId, BlockNumber> for Runtime {
+        fn history(who: AccountId) -> Vec<(BlockNumber, u32)> {
+            LastCaller1::history(who)
+        }
+    }
+
+    impl credentials_runtime_api::CredentialsApi<Block, Hash> for Runtime {
+        fn credential_status(id: Hash) -> credentials::CredentialStatus {
+            Credentials::credential_status(&id)
+        }
+    }
+
+    impl did_runtime_api::DidApi<Block, Hash, <Signature as Verify>::Signer> for Runtime {
+        fn resolve(did: Hash) -> Option<did::DidDocument<<Signature as Verify>::Signer>> {
+            Did::document(&did)
+        }
+    }
+
+    // Without the `with-pow` pallets, the chain mints no block rewards at all
+    impl halving_reward_runtime_api::HalvingRewardApi<Block, BlockNumber, Balance> for Runtime {
+        fn emission_curve() -> Vec<(BlockNumber, Balance)> {
+            #[cfg(feature = "with-pow")]
+            let curve = HalvingReward::emission_curve();
+            #[cfg(not(feature = "with-pow"))]
+            let curve = vec![(0, 0)];
+            curve
+        }
+
+        fn max_supply() -> Balance {
+            #[cfg(feature = "with-pow")]
+            let max_supply = MaxSupply::get();
+            #[cfg(not(feature = "with-pow"))]
+            let max_supply = 0;
+            max_supply
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use support::metadata::{DecodeDifferent, RuntimeMetadata};
+
+	/// The names of all modules that ended up in `construct_runtime!` for the enabled features
+	fn module_names() -> Vec<&'static str> {
+		let modules = match Runtime::metadata().1 {
+			RuntimeMetadata::V10(metadata) => metadata.modules,
+			_ => panic!("unexpected metadata version"),
+		};
+		match modules {
+			DecodeDifferent::Encode(modules) => modules
+				.iter()
+				.filter_map(|module| match module.name {
+					DecodeDifferent::Encode(name) => Some(name),
+					_ => None,
+				})
+				.collect(),
+			_ => panic!("freshly generated metadata is never decoded"),
+		}
+	}
+
+	#[test]
+	fn core_pallets_are_always_included() {
+		let names = module_names();
+		for name in &["System", "Balances", "Sudo", "SimpleMap", "ValueHooks", "VecSet"] {
+			assert!(names.contains(name), "{} is missing", name);
+		}
+	}
+
+	#[test]
+	fn governance_pallets_follow_feature() {
+		let names = module_names();
+		for name in &["Charity", "CheckMembership", "ExecutionSchedule", "FeePolicy"] {
+			assert_eq!(names.contains(name), cfg!(feature = "with-governance"), "{}", name);
+		}
+	}
+
+	#[test]
+	fn ocw_pallets_follow_feature() {
+		let names = module_names();
+		for name in &["OcwBackoff", "OcwFinality"] {
+			assert_eq!(names.contains(name), cfg!(feature = "with-ocw"), "{}", name);
+		}
+	}
+
+	#[test]
+	fn pow_pallets_follow_feature() {
+		assert_eq!(module_names().contains(&"HalvingReward"), cfg!(feature = "with-pow"));
+	}
 }
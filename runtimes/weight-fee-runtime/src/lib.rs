@@ -130,9 +130,12 @@ pub fn native_version() -> NativeVersion {
 
 parameter_types! {
 	pub const BlockHashCount: BlockNumber = 250;
+	// The most weight and length a block may hold. Operational calls may use all of both.
 	pub const MaximumBlockWeight: Weight = 1_000_000;
-	pub const AvailableBlockRatio: Perbill = Perbill::from_percent(75);
 	pub const MaximumBlockLength: u32 = 5 * 1024 * 1024;
+	// Normal calls may only use this portion of the weight and length. The remaining quarter is
+	// headroom reserved for operational calls, so they get in even when blocks are full.
+	pub const AvailableBlockRatio: Perbill = Perbill::from_percent(75);
 	pub const Version: RuntimeVersion = VERSION;
 }
 
@@ -247,6 +250,8 @@ impl sudo::Trait for Runtime {
 
 impl weights::Trait for Runtime {}
 
+impl block_limits::Trait for Runtime {}
+
 
 // --------------------- Multiple Options for WeightToFee -----------------------
 
@@ -366,6 +371,7 @@ construct_runtime!(
 		TransactionPayment: transaction_payment::{Module, Storage},
 		// The Recipe Pallets
 		Weights: weights::{Module, Call, Storage},
+		BlockLimits: block_limits::{Module, Call, Storage},
 	}
 );
 
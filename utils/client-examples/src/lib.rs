@@ -0,0 +1,217 @@
+//! Client Examples
+//!
+//! A client that talks to a kitchen node over JSON-RPC the way a wallet or an indexer would. It
+//! signs and submits extrinsics, waits for the block that includes them, and reads the events they
+//! deposited and the storage they changed.
+//!
+//! ```rust,ignore
+//! let mut client = Client::connect(DEV_NODE)?;
+//! let call = Call::SimpleMap(simple_map::Call::set_single_entry(7));
+//! let included = client.submit_and_watch(&alice, call)?;
+//! println!("Included in {} with events {:?}", included.block, included.events);
+//! ```
+//!
+//! The types of calls, events and extrinsics come from the super runtime crate itself, so the
+//! client always encodes exactly what the runtime decodes. The RPC clients are the ones
+//! `sc-rpc-api` generates for its `author`, `chain` and `state` APIs.
+//!
+//! The `raw` module builds the same extrinsics by hand, to show their wire format.
+
+use codec::{Decode, Encode};
+use dead_mans_switch::TrackActivity;
+use frame_support::{StorageMap, StorageValue};
+use frame_system::{
+	CheckEra, CheckGenesis, CheckNonce, CheckVersion, CheckWeight, EventRecord, Phase,
+};
+use futures01::Future;
+use jsonrpc_core_client::{transports::http, RpcError};
+use pallet_transaction_payment::ChargeTransactionPayment;
+use sc_rpc_api::{author::AuthorClient, chain::ChainClient, state::StateClient};
+use sp_core::{sr25519, storage::StorageKey, Pair};
+use sp_rpc::number::NumberOrHex;
+use sp_runtime::{
+	generic::{Era, SignedPayload},
+	traits::IdentifyAccount,
+	MultiSigner,
+};
+use std::{thread, time::Duration};
+use super_runtime::{
+	AccountId, BlockNumber, Call, Event, Hash, Header, Index, Runtime, SignedBlock,
+	SignedExtra, UncheckedExtrinsic,
+};
+
+pub mod raw;
+
+/// The HTTP RPC endpoint of a node started with `--dev`
+pub const DEV_NODE: &str = "http://localhost:9933";
+
+/// How often the client looks for new blocks while it waits for an extrinsic
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How many times the client looks for new blocks before it gives up on an extrinsic
+const MAX_POLLS: u32 = 60;
+
+type Chain = ChainClient<BlockNumber, Hash, Header, SignedBlock>;
+
+/// An extrinsic in a block, and the events it deposited
+#[derive(Debug)]
+pub struct Included {
+	pub block: Hash,
+	/// The position of the extrinsic in the block
+	pub index: u32,
+	pub events: Vec<Event>,
+}
+
+/// A connection to a node's HTTP RPC endpoint
+pub struct Client {
+	/// The HTTP transport runs on a tokio 0.1 runtime, which the client blocks on for every call
+	executor: tokio::runtime::Runtime,
+	author: AuthorClient<Hash, Hash>,
+	chain: Chain,
+	state: StateClient<Hash>,
+	genesis_hash: Hash,
+	spec_version: u32,
+}
+
+impl Client {
+	/// Connect to the node at `url`, and read what signing needs to know about its chain
+	pub fn connect(url: &str) -> Result<Self, String> {
+		let mut executor = tokio::runtime::Runtime::new()
+			.map_err(|e| format!("Failed to start the tokio runtime: {}", e))?;
+		let author = executor.block_on(http::connect(url)).map_err(rpc_error)?;
+		let chain: Chain = executor.block_on(http::connect(url)).map_err(rpc_error)?;
+		let state: StateClient<Hash> = executor.block_on(http::connect(url)).map_err(rpc_error)?;
+
+		let genesis_hash = executor.block_on(chain.block_hash(Some(NumberOrHex::Number(0))))
+			.map_err(rpc_error)?
+			.ok_or("The node has no genesis block")?;
+		let spec_version = executor.block_on(state.runtime_version(None))
+			.map_err(rpc_error)?
+			.spec_version;
+
+		Ok(Self { executor, author, chain, state, genesis_hash, spec_version })
+	}
+
+	/// Read and decode a storage item at block `at`, or at the best block
+	pub fn storage<T: Decode>(&mut self, key: Vec<u8>, at: Option<Hash>) -> Result<Option<T>, String> {
+		let data = self.run(self.state.storage(StorageKey(key), at))?;
+		data.map(|data| T::decode(&mut &data.0[..]).map_err(|e| format!("Failed to decode: {:?}", e)))
+			.transpose()
+	}
+
+	/// The nonce the next extrinsic of `account` must use
+	///
+	/// Read from the best block, so it does not count extrinsics that are still in the pool.
+	pub fn nonce(&mut self, account: &AccountId) -> Result<Index, String> {
+		let key = frame_system::AccountNonce::<Runtime>::hashed_key_for(account);
+		Ok(self.storage(key, None)?.unwrap_or_default())
+	}
+
+	/// The number of the best block
+	pub fn best_number(&mut self) -> Result<BlockNumber, String> {
+		let header = self.run(self.chain.header(None))?.ok_or("The node has no best block")?;
+		Ok(header.number)
+	}
+
+	/// Sign `call` as an immortal transaction without a tip
+	pub fn sign(&mut self, signer: &sr25519::Pair, call: Call) -> Result<UncheckedExtrinsic, String> {
+		let account = account_of(signer);
+		let extra: SignedExtra = (
+			CheckVersion::new(),
+			CheckGenesis::new(),
+			CheckEra::from(Era::Immortal),
+			CheckNonce::from(self.nonce(&account)?),
+			CheckWeight::new(),
+			ChargeTransactionPayment::from(0),
+			TrackActivity::new(),
+		);
+		// The signature also covers data that the extrinsic does not carry: the runtime version, the
+		// genesis hash and, for an immortal era, the genesis hash again. The node fills in its own
+		// values when it checks the signature, so a transaction for another chain is invalid.
+		let additional_signed = (self.spec_version, self.genesis_hash, self.genesis_hash, (), (), (), ());
+		let payload = SignedPayload::from_raw(call, extra, additional_signed);
+		let signature = payload.using_encoded(|payload| signer.sign(payload));
+		let (call, extra, _) = payload.deconstruct();
+		Ok(UncheckedExtrinsic::new_signed(call, account.into(), signature.into(), extra))
+	}
+
+	/// Submit an extrinsic to the node's pool, and return its hash
+	pub fn submit(&mut self, extrinsic: &UncheckedExtrinsic) -> Result<Hash, String> {
+		self.run(self.author.submit_extrinsic(extrinsic.encode().into()))
+	}
+
+	/// Wait until `extrinsic` is included in a block numbered `from` or later
+	///
+	/// `author_submitAndWatchExtrinsic` would report this by subscription, which needs a WebSocket
+	/// connection. Over HTTP, the client looks for the extrinsic in each new block instead.
+	pub fn watch(
+		&mut self,
+		extrinsic: &UncheckedExtrinsic,
+		from: BlockNumber,
+	) -> Result<Included, String> {
+		let encoded = extrinsic.encode();
+		let mut next = from;
+		for _ in 0..MAX_POLLS {
+			let best = self.best_number()?;
+			while next <= best {
+				if let Some(included) = self.find_in_block(next, &encoded)? {
+					return Ok(included);
+				}
+				next += 1;
+			}
+			thread::sleep(POLL_INTERVAL);
+		}
+		Err(format!("The extrinsic was not included up to block #{}", next - 1))
+	}
+
+	/// Sign and submit `call`, and wait until it is included
+	pub fn submit_and_watch(
+		&mut self,
+		signer: &sr25519::Pair,
+		call: Call,
+	) -> Result<Included, String> {
+		let extrinsic = self.sign(signer, call)?;
+		let from = self.best_number()? + 1;
+		self.submit(&extrinsic)?;
+		self.watch(&extrinsic, from)
+	}
+
+	fn find_in_block(
+		&mut self,
+		number: BlockNumber,
+		encoded: &[u8],
+	) -> Result<Option<Included>, String> {
+		let hash = self.run(self.chain.block_hash(Some(NumberOrHex::Number(number))))?
+			.ok_or_else(|| format!("The node has no block #{}", number))?;
+		let block = self.run(self.chain.block(Some(hash)))?
+			.ok_or_else(|| format!("The node has no body for block #{}", number))?;
+		let index = match block.block.extrinsics.iter().position(|xt| xt.encode() == encoded) {
+			Some(index) => index as u32,
+			None => return Ok(None),
+		};
+
+		let key = frame_system::Events::<Runtime>::hashed_key().to_vec();
+		let records: Vec<EventRecord<Event, Hash>> = self.storage(key, Some(hash))?.unwrap_or_default();
+		let events = records.into_iter()
+			.filter(|record| record.phase == Phase::ApplyExtrinsic(index))
+			.map(|record| record.event)
+			.collect();
+		Ok(Some(Included { block: hash, index, events }))
+	}
+
+	fn run<F>(&mut self, future: F) -> Result<F::Item, String> where
+		F: Future<Error = RpcError> + Send + 'static,
+		F::Item: Send + 'static,
+	{
+		self.executor.block_on(future).map_err(rpc_error)
+	}
+}
+
+/// The account that `signer` signs for
+pub fn account_of(signer: &sr25519::Pair) -> AccountId {
+	MultiSigner::from(signer.public()).into_account()
+}
+
+fn rpc_error(e: RpcError) -> String {
+	format!("RPC failed: {:?}", e)
+}
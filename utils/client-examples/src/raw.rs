@@ -0,0 +1,226 @@
+//! Transactions without the runtime's types
+//!
+//! `Client` encodes calls and extrinsics with the super runtime's own types. This module builds
+//! the same bytes by hand, from SCALE primitives and a key pair, and talks to the node with hand
+//! written JSON-RPC requests. It shows the wire format that SDKs hide:
+//!
+//! ```text
+//! compact length | 0x84 | 0xff account | 0x01 signature | era | compact nonce | compact tip | call
+//! ```
+//!
+//! * `0x84` is the extrinsic format version 4, with the top bit set for a signed extrinsic.
+//! * The address is an indices `Address`, whose `0xff` variant holds a full account id.
+//! * The signature is a `MultiSignature`, whose variant `0x01` is sr25519.
+//! * The era, nonce and tip are what the signed extensions `CheckEra`, `CheckNonce` and
+//!   `ChargeTransactionPayment` encode. `CheckVersion`, `CheckGenesis`, `CheckWeight` and
+//!   `TrackActivity` encode nothing.
+//! * The call is the index of the pallet among those with calls, the index of the call within the
+//!   pallet, and the arguments.
+
+use codec::{Compact, Decode, Encode};
+use serde_json::{json, Value};
+use sp_core::{blake2_256, bytes, sr25519, Pair, H256};
+use sp_runtime::generic::Era;
+use storage_keys::{map_key, Hasher};
+use std::io::{Read, Write};
+use std::net::TcpStream;
+
+/// The extrinsic format version, with the bit that marks signed extrinsics
+const SIGNED_V4: u8 = 0b1000_0000 | 4;
+
+/// The `Address` variant that holds an account id
+const ADDRESS_ID: u8 = 0xff;
+
+/// The `MultiSignature` variant of sr25519 signatures
+const SR25519: u8 = 1;
+
+/// Signed payloads longer than this are hashed before signing
+const MAX_UNHASHED_PAYLOAD: usize = 256;
+
+/// What a signature must cover besides the extrinsic itself
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChainInfo {
+	pub genesis_hash: H256,
+	pub spec_version: u32,
+}
+
+/// Encode a call from the pallet's index, the call's index within the pallet, and the arguments
+pub fn call(pallet_index: u8, call_index: u8, args: impl Encode) -> Vec<u8> {
+	let mut call = vec![pallet_index, call_index];
+	args.encode_to(&mut call);
+	call
+}
+
+/// Sign `call` as an immortal transaction, and encode it as the pool expects it
+pub fn signed_extrinsic(
+	signer: &sr25519::Pair,
+	call: &[u8],
+	nonce: u32,
+	tip: u128,
+	chain: &ChainInfo,
+) -> Vec<u8> {
+	// The signed extensions, in the order of the runtime's `SignedExtra`
+	let mut extra = Era::Immortal.encode();
+	Compact(nonce).encode_to(&mut extra);
+	Compact(tip).encode_to(&mut extra);
+
+	// `CheckVersion`, `CheckGenesis` and `CheckEra` sign data that the extrinsic does not carry.
+	// An immortal era starts at the genesis block.
+	let mut payload = call.to_vec();
+	payload.extend_from_slice(&extra);
+	chain.spec_version.encode_to(&mut payload);
+	chain.genesis_hash.encode_to(&mut payload);
+	chain.genesis_hash.encode_to(&mut payload);
+	let signature = if payload.len() > MAX_UNHASHED_PAYLOAD {
+		signer.sign(&blake2_256(&payload))
+	} else {
+		signer.sign(&payload)
+	};
+
+	let mut extrinsic = vec![SIGNED_V4, ADDRESS_ID];
+	extrinsic.extend_from_slice(signer.public().as_ref());
+	extrinsic.push(SR25519);
+	extrinsic.extend_from_slice(signature.as_ref());
+	extrinsic.extend_from_slice(&extra);
+	extrinsic.extend_from_slice(call);
+	// Extrinsics are encoded as a `Vec<u8>`, which prefixes them with their length
+	extrinsic.encode()
+}
+
+/// The storage key of an account's nonce, `System::AccountNonce`
+///
+/// Maps hash the item's prefix together with the encoded key, with the map's hasher.
+pub fn nonce_key(account: &[u8; 32]) -> Vec<u8> {
+	map_key("System", "AccountNonce", Hasher::Blake2_256, account)
+}
+
+/// A JSON-RPC client over plain HTTP, without an HTTP library
+pub struct RawRpc {
+	/// `host:port` of the node's HTTP RPC endpoint
+	address: String,
+}
+
+impl RawRpc {
+	pub fn new(address: &str) -> Self {
+		Self { address: address.into() }
+	}
+
+	/// Call `method` and return its result
+	pub fn request(&self, method: &str, params: Value) -> Result<Value, String> {
+		let body = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params })
+			.to_string();
+		let mut stream = TcpStream::connect(&self.address)
+			.map_err(|e| format!("Failed to connect to {}: {}", self.address, e))?;
+		write!(
+			stream,
+			"POST / HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\n\
+			Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+			self.address, body.len(), body,
+		).map_err(|e| format!("Failed to send {}: {}", method, e))?;
+
+		let mut response = String::new();
+		stream.read_to_string(&mut response)
+			.map_err(|e| format!("Failed to read {}: {}", method, e))?;
+		let body = response.splitn(2, "\r\n\r\n").nth(1).ok_or("Response without a body")?;
+		let mut response: Value = serde_json::from_str(body)
+			.map_err(|e| format!("Invalid response to {}: {}", method, e))?;
+		match response.get("error") {
+			Some(error) => Err(format!("{} failed: {}", method, error)),
+			None => Ok(response["result"].take()),
+		}
+	}
+
+	/// The genesis hash and the runtime version of the node's chain
+	pub fn chain_info(&self) -> Result<ChainInfo, String> {
+		let genesis_hash = self.request("chain_getBlockHash", json!([0]))?;
+		let version = self.request("state_getRuntimeVersion", json!([]))?;
+		Ok(ChainInfo {
+			genesis_hash: serde_json::from_value(genesis_hash).map_err(|e| e.to_string())?,
+			spec_version: version["specVersion"].as_u64().ok_or("No spec version")? as u32,
+		})
+	}
+
+	/// Read and decode the value under a raw storage `key` at block `at`, or at the best block
+	pub fn storage<T: Decode>(&self, key: &[u8], at: Option<H256>) -> Result<Option<T>, String> {
+		let key = bytes::to_hex(key, false);
+		match self.request("state_getStorage", json!([key, at]))? {
+			Value::String(value) => {
+				let value = bytes::from_hex(&value).map_err(|e| format!("{:?}", e))?;
+				storage_keys::decode(&value).map(Some)
+			}
+			// The node answers `null` for keys without a value
+			_ => Ok(None),
+		}
+	}
+
+	/// The nonce of `account` at the best block
+	pub fn nonce(&self, account: &[u8; 32]) -> Result<u32, String> {
+		// Accounts that never sent a transaction have no nonce in storage
+		Ok(self.storage(&nonce_key(account), None)?.unwrap_or_default())
+	}
+
+	/// Submit an encoded extrinsic, and return its hash
+	pub fn submit(&self, extrinsic: &[u8]) -> Result<H256, String> {
+		let extrinsic = bytes::to_hex(extrinsic, false);
+		let hash = self.request("author_submitExtrinsic", json!([extrinsic]))?;
+		serde_json::from_value(hash).map_err(|e| e.to_string())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use frame_support::StorageMap;
+	use sp_runtime::{generic::SignedPayload, traits::Verify};
+	use super_runtime::{Call, Runtime, UncheckedExtrinsic};
+
+	fn alice() -> sr25519::Pair {
+		sr25519::Pair::from_string("//Alice", None).unwrap()
+	}
+
+	fn chain() -> ChainInfo {
+		ChainInfo { genesis_hash: H256::repeat_byte(7), spec_version: 3 }
+	}
+
+	fn set_entry() -> (Call, Vec<u8>) {
+		let typed = Call::SimpleMap(simple_map::Call::set_single_entry(42));
+		// The pallet index depends on the order of `construct_runtime!`
+		let pallet_index = typed.encode()[0];
+		(typed, call(pallet_index, 0, 42u32))
+	}
+
+	#[test]
+	fn call_matches_runtime_encoding() {
+		let (typed, raw) = set_entry();
+		assert_eq!(raw, typed.encode());
+	}
+
+	#[test]
+	fn extrinsic_decodes_and_verifies() {
+		let (typed, raw) = set_entry();
+		let encoded = signed_extrinsic(&alice(), &raw, 5, 0, &chain());
+		let extrinsic = UncheckedExtrinsic::decode(&mut &encoded[..]).unwrap();
+		assert_eq!(extrinsic.function, typed);
+
+		let (address, signature, extra) = extrinsic.signature.unwrap();
+		let account = crate::account_of(&alice());
+		let expected: super_runtime::Address = account.clone().into();
+		assert_eq!(address, expected);
+
+		// The runtime checks the signature against the payload built from its own types
+		let additional = (3u32, chain().genesis_hash, chain().genesis_hash, (), (), ());
+		let payload = SignedPayload::from_raw(extrinsic.function, extra, additional);
+		assert!(payload.using_encoded(|payload| signature.verify(payload, &account)));
+		let (_, extra, _) = payload.deconstruct();
+		assert_eq!(extra.3.encode(), Compact(5u32).encode());
+	}
+
+	#[test]
+	fn nonce_key_matches_runtime() {
+		let account = crate::account_of(&alice());
+		assert_eq!(
+			nonce_key(account.as_ref()),
+			frame_system::AccountNonce::<Runtime>::hashed_key_for(&account),
+		);
+	}
+}
@@ -0,0 +1,58 @@
+//! End-to-end tests against a running dev node. They are ignored by default, because they need a
+//! node to talk to:
+//!
+//! ```text
+//! ./target/release/kitchen-node --dev --tmp
+//! cargo test -p client-examples -- --ignored
+//! ```
+
+use client_examples::{account_of, Client, DEV_NODE};
+use frame_support::StorageMap;
+use sp_core::{sr25519, Pair};
+use super_runtime::{Balance, Call, Event, Runtime};
+
+fn alice() -> sr25519::Pair {
+	sr25519::Pair::from_string("//Alice", None).expect("the dev seed is valid; qed")
+}
+
+#[test]
+#[ignore]
+fn set_simple_map_entry() {
+	let mut client = Client::connect(DEV_NODE).unwrap();
+	let account = account_of(&alice());
+	// A value that differs from run to run, so the test does not pass on an old entry
+	let entry = client.best_number().unwrap();
+
+	let call = Call::SimpleMap(simple_map::Call::set_single_entry(entry));
+	let included = client.submit_and_watch(&alice(), call).unwrap();
+
+	let set = Event::simple_map(simple_map::RawEvent::EntrySet(account.clone(), entry));
+	assert!(included.events.contains(&set), "events: {:?}", included.events);
+
+	let key = simple_map::SimpleMap::<Runtime>::hashed_key_for(&account);
+	assert_eq!(client.storage::<u32>(key, Some(included.block)), Ok(Some(entry)));
+}
+
+#[test]
+#[ignore]
+fn donate_to_charity() {
+	let mut client = Client::connect(DEV_NODE).unwrap();
+	let account = account_of(&alice());
+	let amount: Balance = 1_000;
+
+	let call = Call::Charity(charity::Call::donate(amount));
+	let included = client.submit_and_watch(&alice(), call).unwrap();
+
+	let pot = included.events.iter()
+		.find_map(|event| match event {
+			Event::charity(charity::RawEvent::DonationReceived(donor, donated, pot))
+				if *donor == account && *donated == amount => Some(*pot),
+			_ => None,
+		})
+		.expect("the donation is reported");
+
+	// The event reports the pot after the donation, which is the charity account's free balance
+	let pot_account = charity::Module::<Runtime>::account_id();
+	let key = pallet_balances::FreeBalance::<Runtime>::hashed_key_for(&pot_account);
+	assert_eq!(client.storage::<Balance>(key, Some(included.block)), Ok(Some(pot)));
+}
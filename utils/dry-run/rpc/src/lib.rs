@@ -0,0 +1,105 @@
+//! RPC interface for dry runs.
+//!
+//! Callers pass the origin and the call SCALE-encoded, the same way they would sign them, and get
+//! the events back SCALE-encoded. The RPC decodes and encodes them with the runtime's own types.
+
+use std::sync::Arc;
+use codec::{Decode, Encode};
+use jsonrpc_core::{Error as RpcError, ErrorCode, Result};
+use jsonrpc_derive::rpc;
+use serde::{Deserialize, Serialize};
+use sp_blockchain::HeaderBackend;
+use sp_core::Bytes;
+use sp_runtime::{
+	generic::BlockId,
+	traits::{Block as BlockT, ProvideRuntimeApi},
+};
+use dry_run_runtime_api::{DryRunApi as DryRunRuntimeApi, DryRunOrigin};
+
+/// What a call would do, as returned over RPC
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulationResult {
+	/// The error message, if the call would fail
+	pub error: Option<String>,
+	/// The weight the call would be charged for
+	pub weight: u32,
+	/// The SCALE-encoded events the call would deposit, in order
+	pub events: Vec<Bytes>,
+}
+
+#[rpc]
+pub trait DryRunApi<BlockHash> {
+	/// Dispatch `call` from `origin` on the state at `at`, and report what would happen. Nothing
+	/// is written to the chain.
+	#[rpc(name = "dryRun_simulateCall")]
+	fn simulate_call(
+		&self,
+		origin: Bytes,
+		call: Bytes,
+		at: Option<BlockHash>
+	) -> Result<SimulationResult>;
+}
+
+/// A struct that implements the `DryRunApi`.
+pub struct DryRun<C, M> {
+	client: Arc<C>,
+	// The block, account id, call and event types of the runtime
+	_marker: std::marker::PhantomData<M>,
+}
+
+impl<C, M> DryRun<C, M> {
+	/// Create new `DryRun` instance with the given reference to the client.
+	pub fn new(client: Arc<C>) -> Self {
+		Self { client, _marker: Default::default() }
+	}
+}
+
+/// Turn any error into an RPC error
+fn rpc_error(message: &str, e: impl std::fmt::Debug) -> RpcError {
+	RpcError {
+		code: ErrorCode::ServerError(1),
+		message: message.into(),
+		data: Some(format!("{:?}", e).into()),
+	}
+}
+
+impl<C, Block, AccountId, Call, Event> DryRunApi<<Block as BlockT>::Hash>
+	for DryRun<C, (Block, AccountId, Call, Event)>
+where
+	Block: BlockT,
+	AccountId: Encode + Decode + Send + Sync + 'static,
+	Call: Encode + Decode + Send + Sync + 'static,
+	Event: Encode + Decode + Send + Sync + 'static,
+	C: Send + Sync + 'static,
+	C: ProvideRuntimeApi,
+	C: HeaderBackend<Block>,
+	C::Api: DryRunRuntimeApi<Block, AccountId, Call, Event>,
+{
+	fn simulate_call(
+		&self,
+		origin: Bytes,
+		call: Bytes,
+		at: Option<<Block as BlockT>::Hash>
+	) -> Result<SimulationResult> {
+		let origin = DryRunOrigin::<AccountId>::decode(&mut &origin[..])
+			.map_err(|e| rpc_error("Unable to decode origin", e))?;
+		let call = Call::decode(&mut &call[..])
+			.map_err(|e| rpc_error("Unable to decode call", e))?;
+		let at = BlockId::hash(at.unwrap_or_else(||
+			// If the block hash is not supplied assume the best block.
+			self.client.info().best_hash
+		));
+
+		// A fresh runtime API instance starts from the block's state, and its changes are
+		// discarded when it is dropped
+		let simulation = self.client.runtime_api().simulate_call(&at, origin, call)
+			.map_err(|e| rpc_error("Unable to simulate call", e))?;
+
+		Ok(SimulationResult {
+			error: simulation.error.map(|message| String::from_utf8_lossy(&message).into_owned()),
+			weight: simulation.weight,
+			events: simulation.events.iter().map(|event| event.encode().into()).collect(),
+		})
+	}
+}
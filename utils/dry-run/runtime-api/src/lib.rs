@@ -0,0 +1,15 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use codec::Codec;
+pub use dry_run::{DryRunOrigin, Simulation};
+
+sp_api::decl_runtime_apis! {
+	pub trait DryRunApi<AccountId, Call, Event> where
+		AccountId: Codec,
+		Call: Codec,
+		Event: Codec,
+	{
+		/// Dispatch `call` from `origin`, and report what it would do. Nothing it does is kept.
+		fn simulate_call(origin: DryRunOrigin<AccountId>, call: Call) -> Simulation<Event>;
+	}
+}
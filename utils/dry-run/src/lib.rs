@@ -0,0 +1,233 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! Dry runs
+//!
+//! Dispatches a call the way a block would, and reports its result, its weight and the events it
+//! deposited. A runtime exposes `simulate` through the `DryRunApi` runtime API, and nodes serve it
+//! over RPC, so that users can preview a call before they sign and pay for it.
+//!
+//! `simulate` itself writes to storage like any dispatch. Nothing is kept because runtime API
+//! calls run on a copy of the state that the client throws away afterwards. Don't call `simulate`
+//! from within a block.
+//!
+//! A dry run only dispatches the call. Signature, nonce and fee checks are left out, so a call that
+//! succeeds here may still be rejected as a transaction.
+
+use codec::{Decode, Encode};
+use frame_support::weights::{GetDispatchInfo, Weight};
+use frame_system::{self as system, RawOrigin};
+use sp_runtime::{traits::Dispatchable, RuntimeDebug};
+use sp_std::prelude::*;
+
+/// The origin to simulate a call from
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub enum DryRunOrigin<AccountId> {
+	Root,
+	Signed(AccountId),
+	None,
+}
+
+impl<AccountId> From<DryRunOrigin<AccountId>> for RawOrigin<AccountId> {
+	fn from(origin: DryRunOrigin<AccountId>) -> Self {
+		match origin {
+			DryRunOrigin::Root => RawOrigin::Root,
+			DryRunOrigin::Signed(who) => RawOrigin::Signed(who),
+			DryRunOrigin::None => RawOrigin::None,
+		}
+	}
+}
+
+/// What a call would do
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct Simulation<Event> {
+	/// The error message, if the call would fail
+	pub error: Option<Vec<u8>>,
+	/// The weight the call would be charged for
+	pub weight: Weight,
+	/// The events the call would deposit, in order
+	pub events: Vec<Event>,
+}
+
+/// Dispatch `call` from `origin`, and report what happened
+pub fn simulate<T, C>(origin: DryRunOrigin<T::AccountId>, call: C) -> Simulation<T::Event>
+where
+	T: system::Trait,
+	C: Dispatchable<Origin = T::Origin> + GetDispatchInfo,
+{
+	let weight = call.get_dispatch_info().weight;
+	// The events of the block the state is taken from are still in storage
+	let earlier_events = <system::Module<T>>::events().len();
+
+	let result = call.dispatch(RawOrigin::from(origin).into());
+
+	let events = <system::Module<T>>::events()
+		.into_iter()
+		.skip(earlier_events)
+		.map(|record| record.event)
+		.collect();
+	let error = result.err().map(|error| {
+		let message: &'static str = error.into();
+		message.as_bytes().to_vec()
+	});
+	Simulation { error, weight, events }
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use frame_support::{impl_outer_event, impl_outer_origin, parameter_types};
+	use sp_core::H256;
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup},
+		Perbill,
+	};
+
+	/// A pallet to simulate calls to
+	mod counter {
+		use frame_support::{
+			decl_event, decl_module, decl_storage, dispatch::DispatchResult,
+			weights::SimpleDispatchInfo,
+		};
+		use frame_system::{self as system, ensure_root, ensure_signed};
+
+		pub trait Trait: system::Trait {
+			type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+		}
+
+		decl_storage! {
+			trait Store for Module<T: Trait> as Counter {
+				pub Count get(fn count): u32;
+			}
+		}
+
+		decl_event!(
+			pub enum Event<T>
+			where
+				AccountId = <T as system::Trait>::AccountId,
+			{
+				Incremented(AccountId, u32),
+			}
+		);
+
+		decl_module! {
+			pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+				fn deposit_event() = default;
+
+				#[weight = SimpleDispatchInfo::FixedNormal(42)]
+				fn increment(origin, by: u32) -> DispatchResult {
+					let who = ensure_signed(origin)?;
+					let count = Self::count().checked_add(by).ok_or("count overflowed")?;
+					Count::put(count);
+					Self::deposit_event(RawEvent::Incremented(who, count));
+					Ok(())
+				}
+
+				fn reset(origin) -> DispatchResult {
+					ensure_root(origin)?;
+					Count::kill();
+					Ok(())
+				}
+			}
+		}
+	}
+
+	impl_outer_origin! {
+		pub enum Origin for TestRuntime {}
+	}
+
+	// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+	#[derive(Clone, PartialEq, Eq, Debug)]
+	pub struct TestRuntime;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: u32 = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+	}
+	impl system::Trait for TestRuntime {
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for TestRuntime {
+			counter<T>,
+		}
+	}
+
+	impl counter::Trait for TestRuntime {
+		type Event = TestEvent;
+	}
+
+	type Counter = counter::Module<TestRuntime>;
+	type CounterCall = counter::Call<TestRuntime>;
+
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		let t = system::GenesisConfig::default()
+			.build_storage::<TestRuntime>()
+			.unwrap();
+		t.into()
+	}
+
+	fn incremented(who: u64, count: u32) -> TestEvent {
+		TestEvent::counter(counter::RawEvent::Incremented(who, count))
+	}
+
+	#[test]
+	fn reports_events_and_weight() {
+		new_test_ext().execute_with(|| {
+			let simulation =
+				simulate::<TestRuntime, _>(DryRunOrigin::Signed(1), CounterCall::increment(5));
+			assert_eq!(
+				simulation,
+				Simulation { error: None, weight: 42, events: vec![incremented(1, 5)] }
+			);
+		})
+	}
+
+	#[test]
+	fn leaves_out_earlier_events() {
+		new_test_ext().execute_with(|| {
+			// An event of the block the simulation starts from
+			assert!(Counter::increment(Origin::signed(1), 5).is_ok());
+
+			let simulation =
+				simulate::<TestRuntime, _>(DryRunOrigin::Signed(2), CounterCall::increment(1));
+			assert_eq!(simulation.events, vec![incremented(2, 6)]);
+		})
+	}
+
+	#[test]
+	fn reports_errors() {
+		new_test_ext().execute_with(|| {
+			let simulation =
+				simulate::<TestRuntime, _>(DryRunOrigin::Signed(1), CounterCall::reset());
+			assert!(simulation.error.is_some());
+			assert_eq!(simulation.events, vec![]);
+
+			assert!(Counter::increment(Origin::signed(1), u32::max_value()).is_ok());
+			let simulation =
+				simulate::<TestRuntime, _>(DryRunOrigin::Signed(1), CounterCall::increment(1));
+			assert_eq!(simulation.error, Some(b"count overflowed".to_vec()));
+
+			let simulation = simulate::<TestRuntime, _>(DryRunOrigin::Root, CounterCall::reset());
+			assert_eq!(simulation.error, None);
+		})
+	}
+}
@@ -0,0 +1,49 @@
+//! Event Assert
+//!
+//! Assertions about the events deposited during a recipe test. Without them, each test builds the
+//! outer event by hand and searches `System::events()` for it. These helpers accept the pallet's own
+//! event, convert it into the test runtime's outer event, and print every deposited event when the
+//! assertion fails.
+//!
+//! ```rust,ignore
+//! ExtBuilder::build().execute_with(|| {
+//!     assert_ok!(GenericEvent::do_something(Origin::signed(1), 32));
+//!     assert_last_event::<TestRuntime>(RawEvent::EmitInput(1, 32));
+//! })
+//! ```
+
+use frame_system::{Module, Trait};
+
+/// The events deposited so far, oldest first
+fn deposited<T: Trait>() -> Vec<T::Event> {
+	<Module<T>>::events().into_iter().map(|record| record.event).collect()
+}
+
+/// Assert that `event` is the most recently deposited event
+pub fn assert_last_event<T: Trait>(event: impl Into<T::Event>) {
+	let event = event.into();
+	let events = deposited::<T>();
+	match events.last() {
+		Some(last) => assert_eq!(
+			last, &event,
+			"unexpected last event; all deposited events: {:?}", events
+		),
+		None => panic!("expected {:?} as the last event, but no events were deposited", event),
+	}
+}
+
+/// Assert that `event` was deposited at any point
+pub fn assert_has_event<T: Trait>(event: impl Into<T::Event>) {
+	let event = event.into();
+	let events = deposited::<T>();
+	assert!(
+		events.contains(&event),
+		"expected {:?} among the deposited events {:?}", event, events
+	);
+}
+
+/// Assert that no events were deposited at all
+pub fn assert_no_events<T: Trait>() {
+	let events = deposited::<T>();
+	assert!(events.is_empty(), "expected no events, but these were deposited: {:?}", events);
+}
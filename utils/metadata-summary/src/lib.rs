@@ -0,0 +1,264 @@
+//! Metadata Summary
+//!
+//! A starting point for tools that need to know what a runtime offers. The metadata generated by
+//! `construct_runtime!` describes every pallet's calls, storage items, events, constants and
+//! errors, but its types are built for encoding, not for reading: names may be static strings or
+//! decoded strings, and lists may sit behind functions. This crate walks the metadata and turns it
+//! into a plain summary that serializes to JSON.
+//!
+//! ```rust,ignore
+//! let summary = summarize(Runtime::metadata())?;
+//! println!("{}", to_json(&summary));
+//! ```
+//!
+//! The `metadata-summary` binary does the same for the metadata a node returns from
+//! `state_getMetadata`.
+
+use codec::Decode;
+use frame_metadata::{
+	DecodeDifferent, DecodeDifferentArray, FnEncode, ModuleMetadata, RuntimeMetadata,
+	RuntimeMetadataPrefixed, StorageEntryMetadata, StorageEntryModifier, StorageEntryType,
+	StorageHasher, META_RESERVED,
+};
+use serde::Serialize;
+
+/// Everything a runtime's metadata says about its pallets
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RuntimeSummary {
+	pub metadata_version: u32,
+	/// In the order of `construct_runtime!`
+	pub pallets: Vec<PalletSummary>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PalletSummary {
+	pub name: String,
+	pub calls: Vec<CallSummary>,
+	/// The prefix of the storage items, if the pallet has any
+	pub storage_prefix: Option<String>,
+	pub storage: Vec<StorageSummary>,
+	pub events: Vec<EventSummary>,
+	pub constants: Vec<TypedName>,
+	pub errors: Vec<String>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CallSummary {
+	pub name: String,
+	pub arguments: Vec<TypedName>,
+}
+
+/// A name together with the name of its Rust type
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TypedName {
+	pub name: String,
+	#[serde(rename = "type")]
+	pub ty: String,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum StorageKind {
+	Plain,
+	Map,
+	DoubleMap,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageSummary {
+	pub name: String,
+	pub kind: StorageKind,
+	/// The key types of maps, with the hasher of each key
+	pub keys: Vec<StorageKey>,
+	pub value: String,
+	/// Whether reading an absent item gives `None` rather than the default value
+	pub optional: bool,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageKey {
+	pub hasher: String,
+	#[serde(rename = "type")]
+	pub ty: String,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct EventSummary {
+	pub name: String,
+	/// The types of the event's fields
+	pub arguments: Vec<String>,
+}
+
+/// Summarize runtime metadata, e.g. the result of `Runtime::metadata()`
+pub fn summarize(metadata: RuntimeMetadataPrefixed) -> Result<RuntimeSummary, String> {
+	let RuntimeMetadataPrefixed(reserved, metadata) = metadata;
+	if reserved != META_RESERVED {
+		return Err("not runtime metadata".into());
+	}
+	match metadata {
+		RuntimeMetadata::V10(metadata) => Ok(RuntimeSummary {
+			metadata_version: 10,
+			pallets: list(metadata.modules).into_iter().map(pallet).collect(),
+		}),
+		_ => Err("unsupported metadata version, only V10 is known".into()),
+	}
+}
+
+/// Decode the hex encoded metadata a node returns from `state_getMetadata`
+pub fn decode_hex(hex: &str) -> Result<RuntimeMetadataPrefixed, String> {
+	let bytes = sp_core::bytes::from_hex(hex.trim()).map_err(|e| format!("invalid hex: {:?}", e))?;
+	RuntimeMetadataPrefixed::decode(&mut &bytes[..]).map_err(|e| format!("invalid metadata: {:?}", e))
+}
+
+/// The summary as pretty printed JSON
+pub fn to_json(summary: &RuntimeSummary) -> String {
+	serde_json::to_string_pretty(summary).expect("the summary only contains strings and lists; qed")
+}
+
+fn pallet(module: ModuleMetadata) -> PalletSummary {
+	let (storage_prefix, storage) = match module.storage.map(lazy) {
+		Some(storage) => (
+			Some(text(storage.prefix)),
+			list(storage.entries).into_iter().map(storage_item).collect(),
+		),
+		None => (None, Vec::new()),
+	};
+	PalletSummary {
+		name: text(module.name),
+		calls: module.calls.map(lazy_list).unwrap_or_default().into_iter()
+			.map(|call| CallSummary {
+				name: text(call.name),
+				arguments: list(call.arguments).into_iter()
+					.map(|argument| TypedName { name: text(argument.name), ty: text(argument.ty) })
+					.collect(),
+			})
+			.collect(),
+		storage_prefix,
+		storage,
+		events: module.event.map(lazy_list).unwrap_or_default().into_iter()
+			.map(|event| EventSummary { name: text(event.name), arguments: texts(event.arguments) })
+			.collect(),
+		constants: lazy_list(module.constants).into_iter()
+			.map(|constant| TypedName { name: text(constant.name), ty: text(constant.ty) })
+			.collect(),
+		errors: lazy_list(module.errors).into_iter().map(|error| text(error.name)).collect(),
+	}
+}
+
+fn storage_item(entry: StorageEntryMetadata) -> StorageSummary {
+	let key = |hasher: StorageHasher, ty| StorageKey { hasher: format!("{:?}", hasher), ty: text(ty) };
+	let (kind, keys, value) = match entry.ty {
+		StorageEntryType::Plain(value) => (StorageKind::Plain, vec![], value),
+		StorageEntryType::Map { hasher, key: key1, value, .. } =>
+			(StorageKind::Map, vec![key(hasher, key1)], value),
+		StorageEntryType::DoubleMap { hasher, key1, key2, value, key2_hasher } =>
+			(StorageKind::DoubleMap, vec![key(hasher, key1), key(key2_hasher, key2)], value),
+	};
+	StorageSummary {
+		name: text(entry.name),
+		kind,
+		keys,
+		value: text(value),
+		optional: entry.modifier == StorageEntryModifier::Optional,
+	}
+}
+
+// Metadata that was just generated holds static data, metadata that was decoded holds owned data.
+
+fn text(value: DecodeDifferent<&'static str, String>) -> String {
+	match value {
+		DecodeDifferent::Encode(value) => value.to_string(),
+		DecodeDifferent::Decoded(value) => value,
+	}
+}
+
+fn texts(values: DecodeDifferentArray<&'static str, String>) -> Vec<String> {
+	match values {
+		DecodeDifferent::Encode(values) => values.iter().map(|value| value.to_string()).collect(),
+		DecodeDifferent::Decoded(values) => values,
+	}
+}
+
+fn list<T: Clone>(values: DecodeDifferentArray<T>) -> Vec<T> {
+	match values {
+		DecodeDifferent::Encode(values) => values.to_vec(),
+		DecodeDifferent::Decoded(values) => values,
+	}
+}
+
+fn lazy<T>(value: DecodeDifferent<FnEncode<T>, T>) -> T {
+	match value {
+		DecodeDifferent::Encode(FnEncode(get)) => get(),
+		DecodeDifferent::Decoded(value) => value,
+	}
+}
+
+fn lazy_list<T: Clone>(values: DecodeDifferent<FnEncode<&'static [T]>, Vec<T>>) -> Vec<T> {
+	match values {
+		DecodeDifferent::Encode(FnEncode(get)) => get().to_vec(),
+		DecodeDifferent::Decoded(values) => values,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use codec::Encode;
+	use sp_core::hexdisplay::HexDisplay;
+	use super_runtime::Runtime;
+
+	fn super_runtime() -> RuntimeSummary {
+		summarize(Runtime::metadata()).unwrap()
+	}
+
+	fn find<'a>(summary: &'a RuntimeSummary, name: &str) -> &'a PalletSummary {
+		summary.pallets.iter().find(|pallet| pallet.name == name).unwrap()
+	}
+
+	#[test]
+	fn walks_calls_storage_and_events() {
+		let summary = super_runtime();
+		assert_eq!(summary.metadata_version, 10);
+
+		let simple_map = find(&summary, "SimpleMap");
+		let set = simple_map.calls.iter().find(|call| call.name == "set_single_entry").unwrap();
+		assert_eq!(set.arguments, vec![TypedName { name: "entry".into(), ty: "u32".into() }]);
+
+		assert_eq!(simple_map.storage_prefix.as_ref().map(String::as_str), Some("SimpleMap"));
+		let entries = &simple_map.storage[0];
+		assert_eq!(entries.kind, StorageKind::Map);
+		assert_eq!(entries.keys.len(), 1);
+		assert_eq!(entries.value, "u32");
+		assert!(!entries.optional);
+
+		let set_event = simple_map.events.iter().find(|event| event.name == "EntrySet").unwrap();
+		assert_eq!(set_event.arguments, vec!["AccountId".to_string(), "u32".to_string()]);
+	}
+
+	#[test]
+	fn decoded_metadata_gives_the_same_summary() {
+		let hex = format!("0x{}", HexDisplay::from(&Runtime::metadata().encode()));
+		assert_eq!(summarize(decode_hex(&hex).unwrap()), Ok(super_runtime()));
+	}
+
+	#[test]
+	fn json_uses_camel_case() {
+		let json: serde_json::Value = serde_json::from_str(&to_json(&super_runtime())).unwrap();
+		assert_eq!(json["metadataVersion"], 10);
+		assert_eq!(json["pallets"][0]["name"], "System");
+		assert!(json["pallets"][0]["storagePrefix"].is_string());
+	}
+
+	#[test]
+	fn rejects_other_data() {
+		assert!(decode_hex("0x1234").is_err());
+		assert!(decode_hex("not hex").is_err());
+	}
+}
@@ -0,0 +1,49 @@
+//! Print a JSON summary of the metadata a node serves
+//!
+//! ```text
+//! curl -s http://localhost:9933 -H "Content-Type:application/json" \
+//!     -d '{"jsonrpc":"2.0","id":1,"method":"state_getMetadata","params":[]}' \
+//!     | metadata-summary
+//! ```
+//!
+//! Reads the `state_getMetadata` response, or just its hex encoded result, from the file given as
+//! the only argument, or from standard input.
+
+use std::io::Read;
+
+fn main() {
+	if let Err(e) = run() {
+		eprintln!("{}", e);
+		std::process::exit(1);
+	}
+}
+
+fn run() -> Result<(), String> {
+	let input = match std::env::args().nth(1) {
+		Some(path) => std::fs::read_to_string(&path)
+			.map_err(|e| format!("Failed to read {}: {}", path, e))?,
+		None => {
+			let mut input = String::new();
+			std::io::stdin().read_to_string(&mut input)
+				.map_err(|e| format!("Failed to read standard input: {}", e))?;
+			input
+		}
+	};
+	let metadata = metadata_summary::decode_hex(&metadata_hex(&input)?)?;
+	let summary = metadata_summary::summarize(metadata)?;
+	println!("{}", metadata_summary::to_json(&summary));
+	Ok(())
+}
+
+/// The hex encoded metadata, taken from a JSON-RPC response if the input is one
+fn metadata_hex(input: &str) -> Result<String, String> {
+	if !input.trim_start().starts_with('{') {
+		return Ok(input.to_string());
+	}
+	let response: serde_json::Value = serde_json::from_str(input)
+		.map_err(|e| format!("Invalid JSON-RPC response: {}", e))?;
+	match response["result"].as_str() {
+		Some(hex) => Ok(hex.to_string()),
+		None => Err(format!("No metadata in the response: {}", response["error"])),
+	}
+}
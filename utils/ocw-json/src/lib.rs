@@ -0,0 +1,319 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! OCW JSON
+//!
+//! Off-chain workers talk to HTTP APIs in JSON, while the chain stores SCALE-encoded structs. This
+//! crate converts between the two without the standard library, so it can be used from a
+//! runtime.
+//!
+//! Reading goes through a *wire struct* that mirrors the JSON payload and borrows its strings,
+//! parsed with `serde-json-core`. The wire struct is then converted field by field into the
+//! on-chain struct. Writing builds the JSON text directly with `JsonObject`.
+//!
+//! ```rust,ignore
+//! let body = response.body().collect::<Vec<u8>>();
+//! let point = PricePoint::from_json(&body).map_err(|_| "unexpected price payload")?;
+//! let call = Call::submit_price(point);
+//! ```
+//!
+//! Runtimes do not use floats, because they round differently on different machines. Decimal
+//! amounts are carried as strings in JSON and as integers with a fixed number of decimals on
+//! chain, see `parse_fixed` and `format_fixed`.
+
+use codec::{Decode, Encode};
+use serde::Deserialize;
+use sp_std::prelude::*;
+
+/// Why a JSON payload could not be converted
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum JsonError {
+	/// The payload is not JSON, or does not have the fields of the wire struct
+	Malformed,
+	/// A decimal amount is not a plain non-negative decimal, or does not fit
+	BadNumber,
+	/// Bytes that should be text are not valid UTF-8
+	BadString,
+}
+
+/// Parse a JSON payload into a wire struct. Strings in the wire struct can borrow from `json`.
+pub fn from_json<'a, T: Deserialize<'a>>(json: &'a [u8]) -> Result<T, JsonError> {
+	serde_json_core::from_slice(json).map_err(|_| JsonError::Malformed)
+}
+
+/// Parse a decimal such as `"12.345"` into an integer with `decimals` decimal places, such as
+/// `12_345_000` for six places. Digits beyond `decimals` are dropped, rounding towards zero.
+pub fn parse_fixed(text: &str, decimals: u32) -> Result<u128, JsonError> {
+	let mut parts = text.splitn(2, '.');
+	let whole = parts.next().unwrap_or_default();
+	let fraction = parts.next().unwrap_or_default();
+	// "12." and ".5" are not valid JSON numbers either
+	if whole.is_empty() || (text.contains('.') && fraction.is_empty()) {
+		return Err(JsonError::BadNumber);
+	}
+
+	let mut value: u128 = 0;
+	let mut push_digit = |c: u8| -> Result<(), JsonError> {
+		if !c.is_ascii_digit() {
+			return Err(JsonError::BadNumber);
+		}
+		value = value
+			.checked_mul(10)
+			.and_then(|v| v.checked_add(u128::from(c - b'0')))
+			.ok_or(JsonError::BadNumber)?;
+		Ok(())
+	};
+
+	for c in whole.bytes() {
+		push_digit(c)?;
+	}
+	let fraction = fraction.as_bytes();
+	for place in 0..decimals as usize {
+		push_digit(fraction.get(place).copied().unwrap_or(b'0'))?;
+	}
+	// The dropped digits must still be digits
+	if fraction.iter().skip(decimals as usize).any(|c| !c.is_ascii_digit()) {
+		return Err(JsonError::BadNumber);
+	}
+	Ok(value)
+}
+
+/// Write an integer with `decimals` decimal places as a decimal, the reverse of `parse_fixed`.
+/// All `decimals` places are written, so `format_fixed(1_500, 3)` is `"1.500"`.
+pub fn format_fixed(value: u128, decimals: u32) -> Vec<u8> {
+	let mut digits = Vec::new();
+	write_integer(&mut digits, value);
+	// Pad with leading zeros so that there is at least one digit before the point
+	let decimals = decimals as usize;
+	while digits.len() <= decimals {
+		digits.insert(0, b'0');
+	}
+	if decimals > 0 {
+		let point = digits.len() - decimals;
+		digits.insert(point, b'.');
+	}
+	digits
+}
+
+/// Append the decimal digits of `value` to `out`
+fn write_integer(out: &mut Vec<u8>, mut value: u128) {
+	let start = out.len();
+	loop {
+		out.push(b'0' + (value % 10) as u8);
+		value /= 10;
+		if value == 0 {
+			break;
+		}
+	}
+	out[start..].reverse();
+}
+
+/// Append `text` to `out` as a quoted JSON string
+fn write_string(out: &mut Vec<u8>, text: &str) {
+	const HEX: &[u8; 16] = b"0123456789abcdef";
+	out.push(b'"');
+	for c in text.bytes() {
+		match c {
+			b'"' => out.extend_from_slice(b"\\\""),
+			b'\\' => out.extend_from_slice(b"\\\\"),
+			// Control characters must be escaped. Multi-byte UTF-8 may be written as it is.
+			c if c < 0x20 => {
+				out.extend_from_slice(b"\\u00");
+				out.push(HEX[(c >> 4) as usize]);
+				out.push(HEX[(c & 0xf) as usize]);
+			}
+			c => out.push(c),
+		}
+	}
+	out.push(b'"');
+}
+
+/// Builds a flat JSON object one field at a time, in the order the fields are added
+///
+/// ```rust,ignore
+/// let body = JsonObject::new().string("symbol", "DOT").number("timestamp", 1_580_000_000).finish();
+/// ```
+pub struct JsonObject {
+	buf: Vec<u8>,
+}
+
+impl JsonObject {
+	pub fn new() -> Self {
+		JsonObject { buf: vec![b'{'] }
+	}
+
+	fn key(&mut self, key: &str) {
+		if self.buf.len() > 1 {
+			self.buf.push(b',');
+		}
+		write_string(&mut self.buf, key);
+		self.buf.push(b':');
+	}
+
+	/// Add a string field
+	pub fn string(mut self, key: &str, value: &str) -> Self {
+		self.key(key);
+		write_string(&mut self.buf, value);
+		self
+	}
+
+	/// Add a number field. `serde-json-core` reads numbers up to `u64`, so larger integers should
+	/// be written with `fixed` instead.
+	pub fn number(mut self, key: &str, value: u64) -> Self {
+		self.key(key);
+		write_integer(&mut self.buf, u128::from(value));
+		self
+	}
+
+	/// Add a decimal amount as a string, formatted by `format_fixed`
+	pub fn fixed(mut self, key: &str, value: u128, decimals: u32) -> Self {
+		self.key(key);
+		self.buf.push(b'"');
+		self.buf.extend(format_fixed(value, decimals));
+		self.buf.push(b'"');
+		self
+	}
+
+	/// The finished object
+	pub fn finish(mut self) -> Vec<u8> {
+		self.buf.push(b'}');
+		self.buf
+	}
+}
+
+impl Default for JsonObject {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+/// The number of decimal places of `PricePoint::price`
+pub const PRICE_DECIMALS: u32 = 6;
+
+/// A price as it is stored on chain. This is the shape most price-feed recipes need, and a
+/// template for converting other payloads.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, Debug)]
+pub struct PricePoint {
+	/// The ticker symbol, such as `b"DOT"`
+	pub symbol: Vec<u8>,
+	/// The price with `PRICE_DECIMALS` decimal places
+	pub price: u128,
+	/// The time of the quote, in seconds since the Unix epoch
+	pub timestamp: u64,
+}
+
+/// The JSON payload of a `PricePoint`, for example
+/// `{"symbol":"DOT","price":"12.345000","timestamp":1580000000}`
+#[derive(Deserialize)]
+struct PriceJson<'a> {
+	symbol: &'a str,
+	price: &'a str,
+	timestamp: u64,
+}
+
+impl PricePoint {
+	pub fn from_json(json: &[u8]) -> Result<Self, JsonError> {
+		let wire: PriceJson = from_json(json)?;
+		Ok(PricePoint {
+			symbol: wire.symbol.as_bytes().to_vec(),
+			price: parse_fixed(wire.price, PRICE_DECIMALS)?,
+			timestamp: wire.timestamp,
+		})
+	}
+
+	pub fn to_json(&self) -> Result<Vec<u8>, JsonError> {
+		let symbol = core::str::from_utf8(&self.symbol).map_err(|_| JsonError::BadString)?;
+		Ok(JsonObject::new()
+			.string("symbol", symbol)
+			.fixed("price", self.price, PRICE_DECIMALS)
+			.number("timestamp", self.timestamp)
+			.finish())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const PAYLOAD: &[u8] = br#"{"symbol":"DOT","price":"12.345000","timestamp":1580000000}"#;
+
+	fn dot() -> PricePoint {
+		PricePoint { symbol: b"DOT".to_vec(), price: 12_345_000, timestamp: 1_580_000_000 }
+	}
+
+	#[test]
+	fn json_to_struct() {
+		assert_eq!(PricePoint::from_json(PAYLOAD), Ok(dot()));
+	}
+
+	#[test]
+	fn json_scale_json_round_trip() {
+		let point = PricePoint::from_json(PAYLOAD).unwrap();
+		let on_chain = point.encode();
+		let decoded = PricePoint::decode(&mut &on_chain[..]).unwrap();
+		assert_eq!(decoded.to_json().unwrap(), PAYLOAD.to_vec());
+	}
+
+	#[test]
+	fn struct_json_struct_round_trip() {
+		let points = vec![
+			dot(),
+			PricePoint { symbol: b"KSM".to_vec(), price: 0, timestamp: 0 },
+			PricePoint { symbol: vec![], price: u128::max_value(), timestamp: u64::max_value() },
+		];
+		for point in points {
+			let json = point.to_json().unwrap();
+			assert_eq!(PricePoint::from_json(&json), Ok(point));
+		}
+	}
+
+	#[test]
+	fn field_order_and_whitespace_do_not_matter() {
+		let json = br#"{ "timestamp": 1580000000, "price": "12.345", "symbol": "DOT" }"#;
+		assert_eq!(PricePoint::from_json(json), Ok(dot()));
+	}
+
+	#[test]
+	fn bad_payloads_are_errors() {
+		assert_eq!(PricePoint::from_json(b"not json"), Err(JsonError::Malformed));
+		assert_eq!(PricePoint::from_json(br#"{"symbol":"DOT"}"#), Err(JsonError::Malformed));
+		let json = br#"{"symbol":"DOT","price":"-1","timestamp":0}"#;
+		assert_eq!(PricePoint::from_json(json), Err(JsonError::BadNumber));
+
+		let point = PricePoint { symbol: vec![0xff], ..dot() };
+		assert_eq!(point.to_json(), Err(JsonError::BadString));
+	}
+
+	#[test]
+	fn parse_fixed_works() {
+		assert_eq!(parse_fixed("12", 2), Ok(1200));
+		assert_eq!(parse_fixed("12.3", 2), Ok(1230));
+		assert_eq!(parse_fixed("0.05", 2), Ok(5));
+		assert_eq!(parse_fixed("12.349", 2), Ok(1234));
+		assert_eq!(parse_fixed("7", 0), Ok(7));
+
+		for bad in &["", ".5", "12.", "1.2.3", "1e3", "+1", "12.3x"] {
+			assert_eq!(parse_fixed(bad, 2), Err(JsonError::BadNumber), "{}", bad);
+		}
+		// One more digit than a u128 can hold
+		assert_eq!(parse_fixed("1000000000000000000000000000000000000000", 0), Err(JsonError::BadNumber));
+	}
+
+	#[test]
+	fn format_fixed_works() {
+		assert_eq!(format_fixed(1234, 2), b"12.34".to_vec());
+		assert_eq!(format_fixed(5, 2), b"0.05".to_vec());
+		assert_eq!(format_fixed(0, 3), b"0.000".to_vec());
+		assert_eq!(format_fixed(1234, 0), b"1234".to_vec());
+		for value in &[0, 1, 99, 100, 123_456_789, u128::max_value()] {
+			let text = format_fixed(*value, 6);
+			assert_eq!(parse_fixed(core::str::from_utf8(&text).unwrap(), 6), Ok(*value));
+		}
+	}
+
+	#[test]
+	fn strings_are_escaped() {
+		let json = JsonObject::new().string("note", "say \"hi\"\\\n").finish();
+		assert_eq!(json, br#"{"note":"say \"hi\"\\\u000a"}"#.to_vec());
+		assert_eq!(JsonObject::new().finish(), b"{}".to_vec());
+	}
+}
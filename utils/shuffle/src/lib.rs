@@ -0,0 +1,166 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! Shuffle
+//!
+//! Deterministic shuffling and weighted selection for pallets. Every node must reach the same
+//! result, so all choices are derived from a seed, typically the output of an on-chain randomness
+//! source such as `randomness-collective-flip`.
+//!
+//! ```rust,ignore
+//! let seed = T::Randomness::random(b"draw");
+//! let mut rng = SeededRng::new(seed.as_ref());
+//! shuffle(&mut candidates, &mut rng);
+//! let winner = weighted_select(&stakes, &mut rng);
+//! ```
+//!
+//! The results are only as unpredictable as the seed. Block authors can influence most on-chain
+//! randomness sources, so do not let large sums depend on a single draw.
+
+use sp_io::hashing::blake2_256;
+
+/// An endless stream of numbers derived from a seed by hashing it with a counter
+pub struct SeededRng {
+	seed: [u8; 32],
+	counter: u64,
+}
+
+impl SeededRng {
+	pub fn new(seed: &[u8]) -> Self {
+		SeededRng { seed: blake2_256(seed), counter: 0 }
+	}
+
+	/// The next number of the stream
+	pub fn next_u64(&mut self) -> u64 {
+		let mut input = [0u8; 40];
+		input[..32].copy_from_slice(&self.seed);
+		input[32..].copy_from_slice(&self.counter.to_le_bytes());
+		self.counter += 1;
+
+		let mut bytes = [0u8; 8];
+		bytes.copy_from_slice(&blake2_256(&input)[..8]);
+		u64::from_le_bytes(bytes)
+	}
+
+	/// A number in `0..bound`, each equally likely. `bound` must not be zero.
+	pub fn below(&mut self, bound: u64) -> u64 {
+		// Taking the remainder of any number would favor small results. Only numbers below the
+		// largest multiple of `bound` are used, which is rarely more than one draw.
+		let limit = (u64::max_value() / bound) * bound;
+		loop {
+			let n = self.next_u64();
+			if n < limit {
+				return n % bound;
+			}
+		}
+	}
+}
+
+/// Put `items` into a random order using the Fisher-Yates shuffle. Every order is equally likely.
+pub fn shuffle<T>(items: &mut [T], rng: &mut SeededRng) {
+	for i in (1..items.len()).rev() {
+		let j = rng.below(i as u64 + 1) as usize;
+		items.swap(i, j);
+	}
+}
+
+/// Pick an index into `weights`, each with a probability proportional to its weight. Returns
+/// `None` if all weights are zero or their sum overflows.
+pub fn weighted_select(weights: &[u64], rng: &mut SeededRng) -> Option<usize> {
+	let total = weights.iter().try_fold(0u64, |sum, w| sum.checked_add(*w))?;
+	if total == 0 {
+		return None;
+	}
+
+	let mut target = rng.below(total);
+	for (index, weight) in weights.iter().enumerate() {
+		if target < *weight {
+			return Some(index);
+		}
+		target -= weight;
+	}
+	unreachable!("target is below the sum of weights; qed")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Runs `draw` once for each of `runs` different seeds, and counts how often each outcome
+	/// occurs
+	fn tally(outcomes: usize, runs: u64, draw: impl Fn(&mut SeededRng) -> usize) -> Vec<u64> {
+		let mut counts = vec![0; outcomes];
+		for run in 0..runs {
+			let mut rng = SeededRng::new(&run.to_le_bytes());
+			counts[draw(&mut rng)] += 1;
+		}
+		counts
+	}
+
+	/// Whether `count` is within 10% of `expected`
+	fn close_to(count: u64, expected: u64) -> bool {
+		count * 10 >= expected * 9 && count * 10 <= expected * 11
+	}
+
+	#[test]
+	fn same_seed_same_stream() {
+		let mut a = SeededRng::new(b"seed");
+		let mut b = SeededRng::new(b"seed");
+		let mut c = SeededRng::new(b"other seed");
+		let from_a: Vec<_> = (0..5).map(|_| a.next_u64()).collect();
+		let from_b: Vec<_> = (0..5).map(|_| b.next_u64()).collect();
+		let from_c: Vec<_> = (0..5).map(|_| c.next_u64()).collect();
+		assert_eq!(from_a, from_b);
+		assert_ne!(from_a, from_c);
+	}
+
+	#[test]
+	fn below_is_uniform() {
+		let counts = tally(7, 7000, |rng| rng.below(7) as usize);
+		assert!(counts.iter().all(|c| close_to(*c, 1000)), "{:?}", counts);
+	}
+
+	#[test]
+	fn shuffle_keeps_items() {
+		let mut items: Vec<u32> = (0..50).collect();
+		shuffle(&mut items, &mut SeededRng::new(b"seed"));
+		assert_ne!(items, (0..50).collect::<Vec<_>>());
+		items.sort();
+		assert_eq!(items, (0..50).collect::<Vec<_>>());
+
+		let mut empty: Vec<u32> = vec![];
+		shuffle(&mut empty, &mut SeededRng::new(b"seed"));
+		let mut single = vec![1];
+		shuffle(&mut single, &mut SeededRng::new(b"seed"));
+		assert_eq!(single, vec![1]);
+	}
+
+	#[test]
+	fn all_orders_equally_likely() {
+		let orders = [[0, 1, 2], [0, 2, 1], [1, 0, 2], [1, 2, 0], [2, 0, 1], [2, 1, 0]];
+		let counts = tally(6, 12_000, |rng| {
+			let mut items = [0, 1, 2];
+			shuffle(&mut items, rng);
+			orders.iter().position(|order| *order == items).unwrap()
+		});
+		assert!(counts.iter().all(|c| close_to(*c, 2000)), "{:?}", counts);
+	}
+
+	#[test]
+	fn selection_follows_weights() {
+		let weights = [1, 0, 2, 7];
+		let counts = tally(4, 10_000, |rng| weighted_select(&weights, rng).unwrap());
+		assert_eq!(counts[1], 0);
+		assert!(close_to(counts[0], 1000), "{:?}", counts);
+		assert!(close_to(counts[2], 2000), "{:?}", counts);
+		assert!(close_to(counts[3], 7000), "{:?}", counts);
+	}
+
+	#[test]
+	fn selection_needs_weight() {
+		let mut rng = SeededRng::new(b"seed");
+		assert_eq!(weighted_select(&[], &mut rng), None);
+		assert_eq!(weighted_select(&[0, 0], &mut rng), None);
+		assert_eq!(weighted_select(&[u64::max_value(), 1], &mut rng), None);
+		assert_eq!(weighted_select(&[0, 5], &mut rng), Some(1));
+	}
+}
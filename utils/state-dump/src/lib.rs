@@ -0,0 +1,197 @@
+//! State Dump
+//!
+//! A debugging aid for recipe tests. When a test involving several storage items fails, it is often
+//! easier to look at the entire state than to add one assertion after another. This crate walks every
+//! key in the current externalities and prints it, decoded where the item is known and as hex where
+//! it is not.
+//!
+//! Items become known in two ways:
+//! * `with_metadata` takes the storage metadata generated by `decl_storage!` and labels every plain
+//!   (non-map) item. Items whose type is a simple primitive are decoded as well.
+//! * `with_item` registers a single key together with the Rust type stored under it. Use it for map
+//!   entries and for items whose type is generic over the runtime.
+//!
+//! ```rust,ignore
+//! ExtBuilder::build().execute_with(|| {
+//!     assert_ok!(VecSet::add_member(Origin::signed(1)));
+//!     StateDump::new()
+//!         .with_metadata(VecSet::storage_metadata())
+//!         .with_item::<Vec<u64>>("VecMap Members", <Members<TestRuntime>>::hashed_key())
+//!         .print();
+//! })
+//! ```
+use frame_support::metadata::{DecodeDifferent, StorageEntryType, StorageMetadata};
+use parity_scale_codec::Decode;
+use sp_core::hexdisplay::HexDisplay;
+use sp_io::hashing::twox_128;
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+
+/// Turns the raw bytes of a storage item into something printable, or `None` if they don't decode
+type Decoder = Box<dyn Fn(&[u8]) -> Option<String>>;
+
+/// What is known about a single storage key
+struct KnownItem {
+    label: String,
+    decoder: Option<Decoder>,
+}
+
+/// A collection of known storage items that can render the current state
+#[derive(Default)]
+pub struct StateDump {
+    known: BTreeMap<Vec<u8>, KnownItem>,
+}
+
+impl StateDump {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Label every plain storage item described by a pallet's storage metadata
+    ///
+    /// The metadata only describes types by name, so only a handful of primitive types can be
+    /// decoded. Everything else is labelled and printed as hex unless it is also registered through
+    /// `with_item`.
+    pub fn with_metadata(mut self, metadata: StorageMetadata) -> Self {
+        let prefix = match metadata.prefix {
+            DecodeDifferent::Encode(prefix) => prefix.to_string(),
+            DecodeDifferent::Decoded(prefix) => prefix,
+        };
+        let entries = match metadata.entries {
+            DecodeDifferent::Encode(entries) => entries.to_vec(),
+            DecodeDifferent::Decoded(entries) => entries,
+        };
+        for entry in entries {
+            let name = match entry.name {
+                DecodeDifferent::Encode(name) => name.to_string(),
+                DecodeDifferent::Decoded(name) => name,
+            };
+            // Only plain items live at a key that can be computed from the metadata alone
+            let ty = match entry.ty {
+                StorageEntryType::Plain(DecodeDifferent::Encode(ty)) => ty.to_string(),
+                StorageEntryType::Plain(DecodeDifferent::Decoded(ty)) => ty,
+                _ => continue,
+            };
+            let key = plain_key(&prefix, &name).to_vec();
+            // Don't overwrite a typed registration with the weaker metadata one
+            if self.known.contains_key(&key) {
+                continue;
+            }
+            self.known.insert(
+                key,
+                KnownItem {
+                    label: format!("{} {}: {}", prefix, name, ty),
+                    decoder: primitive_decoder(&ty),
+                },
+            );
+        }
+        self
+    }
+
+    /// Register a single storage key together with the type stored under it
+    pub fn with_item<T: Decode + Debug>(mut self, label: &str, key: impl AsRef<[u8]>) -> Self {
+        self.known.insert(
+            key.as_ref().to_vec(),
+            KnownItem {
+                label: label.to_string(),
+                decoder: Some(typed_decoder::<T>()),
+            },
+        );
+        self
+    }
+
+    /// Render the entire state of the current externalities, one key per line
+    ///
+    /// Must be called from within `TestExternalities::execute_with`.
+    pub fn render(&self) -> String {
+        raw_storage()
+            .into_iter()
+            .map(|(key, value)| match self.known.get(&key) {
+                Some(KnownItem {
+                    label,
+                    decoder: Some(decoder),
+                }) => match decoder(&value) {
+                    Some(decoded) => format!("{} = {}", label, decoded),
+                    None => format!("{} = 0x{} (failed to decode)", label, HexDisplay::from(&value)),
+                },
+                Some(KnownItem { label, decoder: None }) => {
+                    format!("{} = 0x{}", label, HexDisplay::from(&value))
+                }
+                None => format!("0x{} = 0x{}", HexDisplay::from(&key), HexDisplay::from(&value)),
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Print the rendered state. Run tests with `--nocapture` to see the output.
+    pub fn print(&self) {
+        println!("{}", self.render());
+    }
+}
+
+/// Every key and value in the current externalities, in key order
+///
+/// Must be called from within `TestExternalities::execute_with`.
+pub fn raw_storage() -> Vec<(Vec<u8>, Vec<u8>)> {
+    let mut pairs = Vec::new();
+    let mut previous = Vec::new();
+    while let Some(key) = sp_io::storage::next_key(&previous) {
+        if let Some(value) = sp_io::storage::get(&key) {
+            pairs.push((key.clone(), value));
+        }
+        previous = key;
+    }
+    pairs
+}
+
+/// The key of a plain storage item, as generated by `decl_storage!`
+fn plain_key(prefix: &str, name: &str) -> [u8; 16] {
+    twox_128(format!("{} {}", prefix, name).as_bytes())
+}
+
+fn typed_decoder<T: Decode + Debug>() -> Decoder {
+    Box::new(|mut bytes: &[u8]| T::decode(&mut bytes).ok().map(|value| format!("{:?}", value)))
+}
+
+/// A decoder for the few type names that mean the same thing in every runtime
+fn primitive_decoder(ty: &str) -> Option<Decoder> {
+    match ty {
+        "bool" => Some(typed_decoder::<bool>()),
+        "u8" => Some(typed_decoder::<u8>()),
+        "u16" => Some(typed_decoder::<u16>()),
+        "u32" => Some(typed_decoder::<u32>()),
+        "u64" => Some(typed_decoder::<u64>()),
+        "u128" => Some(typed_decoder::<u128>()),
+        "Vec<u8>" => Some(typed_decoder::<Vec<u8>>()),
+        "Vec<u32>" => Some(typed_decoder::<Vec<u32>>()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sp_io::TestExternalities;
+
+    #[test]
+    fn raw_storage_is_ordered_and_complete() {
+        TestExternalities::default().execute_with(|| {
+            sp_io::storage::set(b"b", b"2");
+            sp_io::storage::set(b"a", b"1");
+            assert_eq!(
+                raw_storage(),
+                vec![(b"a".to_vec(), b"1".to_vec()), (b"b".to_vec(), b"2".to_vec())]
+            );
+        })
+    }
+
+    #[test]
+    fn known_items_are_decoded() {
+        TestExternalities::default().execute_with(|| {
+            sp_io::storage::set(b"known", &parity_scale_codec::Encode::encode(&vec![7u32, 8]));
+            sp_io::storage::set(b"unknown", &[0xab]);
+            let dump = StateDump::new().with_item::<Vec<u32>>("Known", b"known").render();
+            assert_eq!(dump, "Known = [7, 8]\n0x756e6b6e6f776e = 0xab");
+        })
+    }
+}
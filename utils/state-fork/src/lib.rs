@@ -0,0 +1,238 @@
+//! State Fork
+//!
+//! Tests against the state of a live chain. A snapshot is every key and value of a block's state,
+//! exported from a node over RPC and saved as JSON. A `Fork` loads the snapshot into
+//! `TestExternalities` and dispatches super runtime calls on top of it, so a recipe pallet can be
+//! tried against accounts, balances and storage that a mock genesis does not have.
+//!
+//! ```rust,ignore
+//! let snapshot = Snapshot::export(&RawRpc::new("localhost:9933"), None)?;
+//! snapshot.save("snapshot.json")?;
+//!
+//! let mut fork = Fork::new(&Snapshot::load("snapshot.json")?);
+//! let call = Call::SimpleMap(simple_map::Call::set_single_entry(7));
+//! let simulation = fork.dispatch(DryRunOrigin::Signed(alice), call);
+//! assert_eq!(simulation.error, None);
+//! ```
+//!
+//! `migration::try_migration` runs a storage migration on a fork, and checks what it changed.
+//!
+//! Nothing is checked besides the call itself: neither signatures, nor nonces, nor fees. This lets
+//! a test act as any account of the forked chain, and replay extrinsics exactly as they were
+//! submitted.
+
+use client_examples::raw::RawRpc;
+use codec::Decode;
+use dry_run::{simulate, DryRunOrigin, Simulation};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sp_core::{Bytes, H256};
+use sp_io::TestExternalities;
+use sp_runtime::traits::StaticLookup;
+use std::{
+	collections::{BTreeMap, BTreeSet},
+	fs,
+	path::Path,
+};
+use super_runtime::{AccountId, Call, Event, Indices, Runtime, UncheckedExtrinsic};
+
+pub mod migration;
+
+/// Every key and value of a block's state
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Snapshot {
+	/// The block the state was exported from, if it came from a node
+	pub block: Option<H256>,
+	/// Ordered by key, in the format `state_getPairs` returns
+	pub pairs: Vec<(Bytes, Bytes)>,
+}
+
+impl Snapshot {
+	/// Export the state of block `at`, or of the best block, from a node
+	///
+	/// `state_getPairs` returns the whole state in a single response. That is fine for a dev chain,
+	/// but on a large chain it is slow, and public nodes often refuse it.
+	pub fn export(rpc: &RawRpc, at: Option<H256>) -> Result<Self, String> {
+		let at = match at {
+			Some(at) => at,
+			None => {
+				let best = rpc.request("chain_getBlockHash", json!([]))?;
+				serde_json::from_value(best).map_err(|e| format!("Invalid block hash: {}", e))?
+			}
+		};
+		let pairs = rpc.request("state_getPairs", json!(["0x", at]))?;
+		let mut pairs: Vec<(Bytes, Bytes)> = serde_json::from_value(pairs)
+			.map_err(|e| format!("Invalid storage pairs: {}", e))?;
+		pairs.sort_by(|(a, _), (b, _)| a.0.cmp(&b.0));
+		Ok(Self { block: Some(at), pairs })
+	}
+
+	/// Capture the state of the current externalities
+	///
+	/// Must be called from within `TestExternalities::execute_with`.
+	pub fn capture() -> Self {
+		let pairs = state_dump::raw_storage()
+			.into_iter()
+			.map(|(key, value)| (Bytes(key), Bytes(value)))
+			.collect();
+		Self { block: None, pairs }
+	}
+
+	pub fn from_json(json: &str) -> Result<Self, String> {
+		serde_json::from_str(json).map_err(|e| format!("Invalid snapshot: {}", e))
+	}
+
+	pub fn to_json(&self) -> String {
+		serde_json::to_string_pretty(self).expect("the snapshot only contains hex strings; qed")
+	}
+
+	pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {
+		let json = fs::read_to_string(path).map_err(|e| format!("Failed to read snapshot: {}", e))?;
+		Self::from_json(&json)
+	}
+
+	pub fn save(&self, path: impl AsRef<Path>) -> Result<(), String> {
+		fs::write(path, self.to_json()).map_err(|e| format!("Failed to write snapshot: {}", e))
+	}
+
+	/// The value under `key`, if the snapshot has one
+	pub fn get(&self, key: &[u8]) -> Option<&[u8]> {
+		self.pairs.iter().find(|(k, _)| &k.0[..] == key).map(|(_, value)| &value.0[..])
+	}
+
+	/// The keys whose values differ between the snapshots, including keys only one of them has
+	pub fn changed_keys(&self, other: &Snapshot) -> Vec<Vec<u8>> {
+		let values = |snapshot: &Snapshot| snapshot.pairs.iter()
+			.map(|(key, value)| (key.0.clone(), value.0.clone()))
+			.collect::<BTreeMap<_, _>>();
+		let (ours, theirs) = (values(self), values(other));
+		ours.keys().chain(theirs.keys())
+			.filter(|key| ours.get(*key) != theirs.get(*key))
+			.cloned()
+			.collect::<BTreeSet<_>>()
+			.into_iter()
+			.collect()
+	}
+}
+
+/// Test externalities that start from a snapshot
+pub struct Fork {
+	ext: TestExternalities,
+}
+
+impl Fork {
+	pub fn new(snapshot: &Snapshot) -> Self {
+		let mut ext = TestExternalities::default();
+		ext.execute_with(|| {
+			for (key, value) in &snapshot.pairs {
+				sp_io::storage::set(&key.0, &value.0);
+			}
+		});
+		Self { ext }
+	}
+
+	/// Run `f` against the fork's state, e.g. to read storage or to call a pallet directly
+	pub fn execute_with<R>(&mut self, f: impl FnOnce() -> R) -> R {
+		self.ext.execute_with(f)
+	}
+
+	/// Dispatch `call` from `origin`, and report what happened
+	pub fn dispatch(&mut self, origin: DryRunOrigin<AccountId>, call: Call) -> Simulation<Event> {
+		self.execute_with(|| simulate::<Runtime, _>(origin, call))
+	}
+
+	/// Dispatch the call of an extrinsic from its signer, or as unsigned if it has none
+	///
+	/// The signature is not checked, so the extrinsic may come from any chain, or be made up.
+	pub fn replay(&mut self, extrinsic: UncheckedExtrinsic) -> Result<Simulation<Event>, String> {
+		self.execute_with(|| {
+			let origin = match extrinsic.signature {
+				Some((address, _, _)) => DryRunOrigin::Signed(
+					Indices::lookup(address).map_err(|e| format!("Unknown signer: {}", e))?,
+				),
+				None => DryRunOrigin::None,
+			};
+			Ok(simulate::<Runtime, _>(origin, extrinsic.function))
+		})
+	}
+
+	/// Replay an extrinsic in the encoding that `author_submitExtrinsic` accepts
+	pub fn replay_encoded(&mut self, extrinsic: &[u8]) -> Result<Simulation<Event>, String> {
+		let extrinsic = UncheckedExtrinsic::decode(&mut &extrinsic[..])
+			.map_err(|e| format!("Invalid extrinsic: {:?}", e))?;
+		self.replay(extrinsic)
+	}
+
+	/// The fork's current state, to save it or to start other forks from it
+	pub fn snapshot(&mut self) -> Snapshot {
+		self.execute_with(Snapshot::capture)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use client_examples::{account_of, raw};
+	use codec::Encode;
+	use frame_support::StorageMap;
+	use sp_core::{sr25519, Pair};
+
+	fn alice() -> sr25519::Pair {
+		sr25519::Pair::from_string("//Alice", None).unwrap()
+	}
+
+	/// Stands in for a snapshot exported from a node
+	fn genesis() -> Snapshot {
+		let storage = frame_system::GenesisConfig::default().build_storage::<Runtime>().unwrap();
+		TestExternalities::new(storage).execute_with(Snapshot::capture)
+	}
+
+	fn entry_of(fork: &mut Fork, who: &AccountId) -> u32 {
+		fork.execute_with(|| simple_map::SimpleMap::<Runtime>::get(who))
+	}
+
+	#[test]
+	fn json_round_trip() {
+		let snapshot = genesis();
+		assert!(!snapshot.pairs.is_empty());
+		assert_eq!(Snapshot::from_json(&snapshot.to_json()), Ok(snapshot));
+	}
+
+	#[test]
+	fn reads_the_format_of_state_get_pairs() {
+		let json = r#"{ "block": null, "pairs": [["0x0102", "0x2a000000"]] }"#;
+		let snapshot = Snapshot::from_json(json).unwrap();
+		assert_eq!(snapshot.get(&[1, 2]), Some(&42u32.encode()[..]));
+
+		let mut fork = Fork::new(&snapshot);
+		assert_eq!(fork.execute_with(|| sp_io::storage::get(&[1, 2])), Some(42u32.encode()));
+	}
+
+	#[test]
+	fn dispatches_on_top_of_the_snapshot() {
+		let alice = account_of(&alice());
+		let mut fork = Fork::new(&genesis());
+		let call = Call::SimpleMap(simple_map::Call::set_single_entry(7));
+		assert_eq!(fork.dispatch(DryRunOrigin::Signed(alice.clone()), call).error, None);
+		assert_eq!(entry_of(&mut fork, &alice), 7);
+
+		// The snapshot itself is left alone
+		assert_eq!(entry_of(&mut Fork::new(&genesis()), &alice), 0);
+		// but the fork's state can be saved and forked again
+		assert_eq!(entry_of(&mut Fork::new(&fork.snapshot()), &alice), 7);
+	}
+
+	#[test]
+	fn replays_extrinsics_without_checking_them() {
+		let typed = Call::SimpleMap(simple_map::Call::set_single_entry(9));
+		// Signed for a chain that does not exist, with a nonce nobody has reached
+		let chain = raw::ChainInfo { genesis_hash: H256::repeat_byte(1), spec_version: 0 };
+		let extrinsic = raw::signed_extrinsic(&alice(), &typed.encode(), 1_000, 0, &chain);
+
+		let mut fork = Fork::new(&genesis());
+		let simulation = fork.replay_encoded(&extrinsic).unwrap();
+		assert_eq!(simulation.error, None);
+		assert_eq!(entry_of(&mut fork, &account_of(&alice())), 9);
+	}
+}
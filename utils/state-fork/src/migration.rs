@@ -0,0 +1,173 @@
+//! Trying storage migrations on a fork
+//!
+//! A migration runs once on the real chain, against state that grew for months. A mock genesis
+//! only has the entries somebody thought to write by hand, so a migration that passes its unit
+//! tests can still fail on the chain. `try_migration` runs a migration on a fork of a snapshot,
+//! such as one exported from a node, and checks it the way a release should:
+//!
+//! * `pre` reads what the migration must preserve, before it runs.
+//! * `post` checks the migrated state against what `pre` read.
+//! * Running the migration a second time must change nothing. A runtime runs its migrations again
+//!   after every upgrade, so a migration that does not notice it already ran breaks the state again.
+//!
+//! ```rust,ignore
+//! let snapshot = Snapshot::export(&RawRpc::new("localhost:9933"), None)?;
+//! let migrated = try_migration(&snapshot, read_old_sum, migrate, check_new_sum)?;
+//! assert!(migrated.changed.iter().all(|key| pallet_keys.contains(key)));
+//! ```
+
+use crate::{Fork, Snapshot};
+
+/// A fork after a migration that passed its checks
+pub struct Migrated {
+	/// The migrated state
+	pub fork: Fork,
+	/// The keys the migration changed, in order
+	pub changed: Vec<Vec<u8>>,
+}
+
+/// Run `migrate` on a fork of `snapshot`, check the result with `pre` and `post`, and make sure
+/// that running it again changes nothing
+pub fn try_migration<P>(
+	snapshot: &Snapshot,
+	pre: impl FnOnce() -> P,
+	migrate: impl Fn(),
+	post: impl FnOnce(P) -> Result<(), String>,
+) -> Result<Migrated, String> {
+	let mut fork = Fork::new(snapshot);
+	let before = fork.snapshot();
+	let preserved = fork.execute_with(pre);
+
+	fork.execute_with(&migrate);
+	fork.execute_with(|| post(preserved))?;
+	let after = fork.snapshot();
+
+	fork.execute_with(&migrate);
+	let again = fork.snapshot();
+	if again != after {
+		return Err(format!(
+			"Running the migration again changed {} keys",
+			after.changed_keys(&again).len(),
+		));
+	}
+
+	Ok(Migrated { changed: before.changed_keys(&after), fork })
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use adding_machine::{MigratedSpecVersion, Sum, SumIsU128};
+	use client_examples::raw::RawRpc;
+	use frame_support::{storage::unhashed, StorageValue};
+	use sp_io::TestExternalities;
+	use sp_runtime::traits::OnInitialize;
+	use super_runtime::{AddingMachine, BlockNumber, Runtime};
+
+	/// Stands in for a snapshot exported from a node, with `write` applied to the genesis state
+	fn chain_with(write: impl FnOnce()) -> Snapshot {
+		let storage = frame_system::GenesisConfig::default().build_storage::<Runtime>().unwrap();
+		TestExternalities::new(storage).execute_with(|| {
+			write();
+			Snapshot::capture()
+		})
+	}
+
+	/// The adding machine's migration of its sum to a `u128`, as the runtime runs it. Forgetting the
+	/// migrated version makes the pallet treat every run as the first block after an upgrade.
+	fn migrate() {
+		MigratedSpecVersion::kill();
+		<AddingMachine as OnInitialize<BlockNumber>>::on_initialize(1);
+	}
+
+	/// The sum in whichever encoding the state has
+	fn sum_before() -> u128 {
+		if SumIsU128::get() {
+			Sum::get()
+		} else {
+			unhashed::get::<u32>(&Sum::hashed_key()).map(u128::from).unwrap_or_default()
+		}
+	}
+
+	fn sum_preserved(sum: u128) -> Result<(), String> {
+		if !SumIsU128::get() {
+			return Err("The sum is not marked as migrated".into());
+		}
+		if Sum::get() != sum {
+			return Err(format!("The sum changed from {} to {}", sum, Sum::get()));
+		}
+		Ok(())
+	}
+
+	fn adding_machine_keys() -> Vec<Vec<u8>> {
+		vec![
+			Sum::hashed_key().to_vec(),
+			SumIsU128::hashed_key().to_vec(),
+			MigratedSpecVersion::hashed_key().to_vec(),
+		]
+	}
+
+	#[test]
+	fn old_sums_are_converted() {
+		let snapshot = chain_with(|| unhashed::put(&Sum::hashed_key(), &7u32));
+		let mut migrated = try_migration(&snapshot, sum_before, migrate, sum_preserved).unwrap();
+
+		let mut expected = adding_machine_keys();
+		expected.sort();
+		assert_eq!(migrated.changed, expected);
+		assert_eq!(migrated.fork.execute_with(Sum::get), 7);
+	}
+
+	#[test]
+	fn migrated_sums_are_left_alone() {
+		// A sum that a second conversion would truncate
+		let sum = u128::from(u32::max_value()) + 1;
+		let snapshot = chain_with(|| {
+			Sum::put(sum);
+			SumIsU128::put(true);
+		});
+		let migrated = try_migration(&snapshot, sum_before, migrate, sum_preserved).unwrap();
+		// Only the version the pallet migrated for is recorded
+		assert_eq!(migrated.changed, vec![MigratedSpecVersion::hashed_key().to_vec()]);
+	}
+
+	#[test]
+	fn failed_post_conditions_are_reported() {
+		let snapshot = chain_with(|| unhashed::put(&Sum::hashed_key(), &7u32));
+		let lossy = || {
+			Sum::kill();
+			SumIsU128::put(true);
+		};
+		let result = try_migration(&snapshot, sum_before, lossy, sum_preserved);
+		assert_eq!(result.err(), Some("The sum changed from 7 to 0".to_string()));
+	}
+
+	#[test]
+	fn migrations_that_run_twice_are_caught() {
+		const BALANCE: &[u8] = b":token:balance";
+		let snapshot = chain_with(|| unhashed::put(BALANCE, &7u64));
+		// Rescales the balance, but does not record that it did
+		let to_cents = || {
+			let balance = unhashed::get::<u64>(BALANCE).unwrap_or_default();
+			unhashed::put(BALANCE, &(balance * 100));
+		};
+		let result = try_migration(&snapshot, || (), to_cents, |()| Ok(()));
+		assert_eq!(result.err(), Some("Running the migration again changed 1 keys".to_string()));
+	}
+
+	/// Needs a running dev node, so it is ignored by default:
+	///
+	/// ```text
+	/// ./target/release/kitchen-node --dev --tmp
+	/// cargo test -p state-fork -- --ignored
+	/// ```
+	#[test]
+	#[ignore]
+	fn migrates_the_state_of_a_dev_node() {
+		let snapshot = Snapshot::export(&RawRpc::new("localhost:9933"), None).unwrap();
+		let migrated = try_migration(&snapshot, sum_before, migrate, sum_preserved).unwrap();
+		// The node's runtime may have migrated the sum already. Either way, nothing else changes.
+		let keys = adding_machine_keys();
+		assert!(migrated.changed.iter().all(|key| keys.contains(key)), "{:?}", migrated.changed);
+	}
+}
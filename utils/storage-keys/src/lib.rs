@@ -0,0 +1,164 @@
+//! Storage Keys
+//!
+//! Every storage item of a runtime lives under a key of the state's key-value store. Tools that
+//! read the state without the runtime's types, like indexers and wallets, have to compute these
+//! keys themselves. This crate computes them the way `decl_storage!` does.
+//!
+//! Each item has a prefix of its own, the name of its pallet's storage and the item's name joined
+//! by a space, e.g. `"System AccountNonce"`.
+//!
+//! * A plain value lives under `twox_128(prefix)`.
+//! * A map entry lives under `hasher(prefix ++ key)`, where `key` is the encoded key and `hasher`
+//!   is the one the map declares, `blake2_256` by default.
+//! * A double map entry lives under `hasher1(prefix ++ key1) ++ hasher2(key2)`.
+//!
+//! ```rust,ignore
+//! let key = map_key("System", "AccountNonce", Hasher::Blake2_256, &account);
+//! let nonce: Option<u32> = read(&key)?;
+//! ```
+//!
+//! `read` works inside externalities, e.g. in tests. Outside the runtime, a node returns the same
+//! bytes from the `state_getStorage` RPC, and `decode` turns them into a value.
+
+use codec::{Decode, Encode};
+use sp_core::hashing::{blake2_128, blake2_256, twox_128, twox_256, twox_64};
+
+/// The hashers that `decl_storage!` offers for map keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hasher {
+	Blake2_128,
+	Blake2_256,
+	Twox128,
+	Twox256,
+	/// Keeps the hashed data after its 64 bit hash, so keys can be read back from the storage key
+	Twox64Concat,
+}
+
+impl Hasher {
+	pub fn hash(self, data: &[u8]) -> Vec<u8> {
+		match self {
+			Hasher::Blake2_128 => blake2_128(data).to_vec(),
+			Hasher::Blake2_256 => blake2_256(data).to_vec(),
+			Hasher::Twox128 => twox_128(data).to_vec(),
+			Hasher::Twox256 => twox_256(data).to_vec(),
+			Hasher::Twox64Concat => {
+				let mut hash = twox_64(data).to_vec();
+				hash.extend_from_slice(data);
+				hash
+			}
+		}
+	}
+}
+
+/// The prefix of a storage item, from the name of its pallet's storage and the item's name
+///
+/// The storage name is the one after `as` in `decl_storage!`, which need not be the pallet's name.
+pub fn prefix(storage: &str, item: &str) -> Vec<u8> {
+	format!("{} {}", storage, item).into_bytes()
+}
+
+/// The key of a plain storage value
+pub fn value_key(storage: &str, item: &str) -> Vec<u8> {
+	twox_128(&prefix(storage, item)).to_vec()
+}
+
+/// The key of the entry for `key` in a map
+pub fn map_key(storage: &str, item: &str, hasher: Hasher, key: &impl Encode) -> Vec<u8> {
+	let mut data = prefix(storage, item);
+	key.encode_to(&mut data);
+	hasher.hash(&data)
+}
+
+/// The key of the entry for `key1` and `key2` in a double map
+pub fn double_map_key(
+	storage: &str,
+	item: &str,
+	hasher1: Hasher,
+	key1: &impl Encode,
+	hasher2: Hasher,
+	key2: &impl Encode,
+) -> Vec<u8> {
+	let mut final_key = map_key(storage, item, hasher1, key1);
+	final_key.extend_from_slice(&hasher2.hash(&key2.encode()));
+	final_key
+}
+
+/// Decode a value read from storage
+pub fn decode<T: Decode>(data: &[u8]) -> Result<T, String> {
+	T::decode(&mut &data[..]).map_err(|e| format!("Failed to decode: {:?}", e))
+}
+
+/// Read and decode the value under `key` from the current externalities
+///
+/// Absent values are `None`. Items that `decl_storage!` declares without `Option` read as their
+/// default value in the runtime, but they are absent from storage all the same.
+pub fn read<T: Decode>(key: &[u8]) -> Result<Option<T>, String> {
+	sp_io::storage::get(key).map(|data| decode(&data)).transpose()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use frame_support::{StorageDoubleMap, StorageMap, StorageValue};
+	use sp_io::TestExternalities;
+	use super_runtime::{AccountId, Runtime};
+
+	fn account(byte: u8) -> AccountId {
+		AccountId::from([byte; 32])
+	}
+
+	#[test]
+	fn value_keys_match_decl_storage() {
+		assert_eq!(
+			value_key("System", "Number"),
+			frame_system::Number::<Runtime>::hashed_key().to_vec(),
+		);
+	}
+
+	#[test]
+	fn map_keys_match_decl_storage() {
+		assert_eq!(
+			map_key("SimpleMap", "SimpleMap", Hasher::Blake2_256, &account(1)),
+			simple_map::SimpleMap::<Runtime>::hashed_key_for(&account(1)),
+		);
+		assert_eq!(
+			map_key("System", "AccountNonce", Hasher::Blake2_256, &account(2)),
+			frame_system::AccountNonce::<Runtime>::hashed_key_for(&account(2)),
+		);
+	}
+
+	#[test]
+	fn double_map_keys_match_decl_storage() {
+		let score_key = |who: u8| {
+			let who = account(who);
+			double_map_key("Dmap", "MemberScore", Hasher::Blake2_256, &7u32, Hasher::Twox128, &who)
+		};
+		assert_eq!(
+			score_key(1),
+			double_map::MemberScore::<Runtime>::hashed_key_for(&7u32, &account(1)),
+		);
+		// Entries with the same first key share the start of their storage keys
+		assert_eq!(score_key(1)[..32], score_key(2)[..32]);
+	}
+
+	#[test]
+	fn twox_64_concat_keeps_the_data() {
+		let hash = Hasher::Twox64Concat.hash(b"data");
+		assert_eq!(hash.len(), 8 + 4);
+		assert_eq!(&hash[8..], b"data");
+	}
+
+	#[test]
+	fn reads_round_trip() {
+		TestExternalities::default().execute_with(|| {
+			simple_map::SimpleMap::<Runtime>::insert(&account(1), 42);
+			let key = map_key("SimpleMap", "SimpleMap", Hasher::Blake2_256, &account(1));
+			assert_eq!(read::<u32>(&key), Ok(Some(42)));
+
+			let absent = map_key("SimpleMap", "SimpleMap", Hasher::Blake2_256, &account(2));
+			assert_eq!(read::<u32>(&absent), Ok(None));
+			// A `u32` is four bytes, so it is too short for a `u64`
+			assert!(read::<u64>(&key).is_err());
+		})
+	}
+}